@@ -0,0 +1,39 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use txtHistoryRust::file_writer::{Chunking, CsvOptions};
+use txtHistoryRust::models::{Message, OutputFormat};
+use txtHistoryRust::style::ExportStyle;
+use txtHistoryRust::testing::MessageBuilder;
+
+// Every chunking splits the messages into non-empty chunks that together hold each message
+// exactly once, in order. The first two bytes pick the chunking and its limit; each line of
+// the rest is a message.
+fuzz_target!(|data: &[u8]| {
+    let [kind, limit, rest @ ..] = data else {
+        return;
+    };
+    let messages: Vec<Message> = String::from_utf8_lossy(rest)
+        .lines()
+        .enumerate()
+        .map(|(i, line)| MessageBuilder::new("Phil", line).id(&i.to_string()).build())
+        .collect();
+
+    let limit = usize::from(*limit);
+    let chunking = match kind % 4 {
+        0 => Chunking::Single,
+        1 => Chunking::Lines(limit),
+        2 => Chunking::Tokens(limit),
+        _ => Chunking::SizeMb(limit as f64 / 1024.0),
+    };
+
+    for format in [OutputFormat::Txt, OutputFormat::Csv, OutputFormat::Json] {
+        let chunks = chunking
+            .split(&messages, format, &CsvOptions::default(), &ExportStyle::default())
+            .expect("chunking failed");
+
+        assert!(chunks.iter().all(|chunk| !chunk.is_empty()));
+        let ids: Vec<_> = chunks.iter().flat_map(|chunk| chunk.iter().map(|m| &m.imessage_id)).collect();
+        assert!(ids.into_iter().eq(messages.iter().map(|m| &m.imessage_id)));
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use txtHistoryRust::nlp::NlpProcessor;
+
+thread_local! {
+    static PROCESSOR: NlpProcessor = NlpProcessor::new("fuzz");
+}
+
+// Message text is arbitrary; cleaning and analyzing it must never panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    PROCESSOR.with(|processor| {
+        let _ = processor.process_text(text);
+    });
+});
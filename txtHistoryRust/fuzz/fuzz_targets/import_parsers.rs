@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use txtHistoryRust::importers::dates::DateFormat;
+use txtHistoryRust::importers::export::read_export_contents;
+
+// Export files and timestamps from other tools can hold anything; parsing them must fail
+// with an error, never panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(contents) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    for kind in ["txt", "csv", "json"] {
+        let _ = read_export_contents(contents, kind, "Phil", "Jess", &DateFormat::Infer);
+    }
+
+    let samples: Vec<&str> = contents.lines().collect();
+    if let Ok(parser) = DateFormat::Infer.resolve(&samples) {
+        for sample in &samples {
+            let _ = parser.parse(sample);
+        }
+    }
+});
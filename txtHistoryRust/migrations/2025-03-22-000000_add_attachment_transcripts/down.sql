@@ -0,0 +1,2 @@
+-- Drop the attachment_transcripts table
+DROP TABLE IF EXISTS attachment_transcripts;
@@ -0,0 +1,16 @@
+-- Create attachment_transcripts table for text extracted from attachments
+CREATE TABLE attachment_transcripts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    attachment_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    processor TEXT NOT NULL,          -- Name of the processor that produced the transcript
+    transcript TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(attachment_id) REFERENCES attachments(id),
+    FOREIGN KEY(message_id) REFERENCES messages(id),
+    UNIQUE(attachment_id, processor)
+);
+
+-- Create indexes for attachment_transcripts table
+CREATE INDEX idx_attachment_transcripts_message ON attachment_transcripts(message_id);
+CREATE INDEX idx_attachment_transcripts_attachment ON attachment_transcripts(attachment_id);
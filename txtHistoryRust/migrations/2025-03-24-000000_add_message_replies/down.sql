@@ -0,0 +1,5 @@
+-- Drop the index
+DROP INDEX IF EXISTS idx_messages_reply_to;
+
+-- Remove the column
+ALTER TABLE messages DROP COLUMN reply_to;
@@ -0,0 +1,3 @@
+-- Add reply_to to messages table to keep reply references from imported sources
+ALTER TABLE messages ADD COLUMN reply_to TEXT;
+CREATE INDEX idx_messages_reply_to ON messages(reply_to);
@@ -0,0 +1,34 @@
+-- Rebuild the messages table without the foreign key
+PRAGMA foreign_keys = OFF;
+
+CREATE TABLE messages_old (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    imessage_id TEXT NOT NULL UNIQUE,
+    text TEXT,
+    sender TEXT NOT NULL,
+    is_from_me BOOLEAN NOT NULL,
+    date_created TIMESTAMP NOT NULL,
+    date_imported TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    handle_id TEXT,
+    service TEXT,
+    thread_id TEXT,
+    has_attachments BOOLEAN NOT NULL DEFAULT false,
+    contact_id INTEGER,
+    reply_to TEXT
+);
+
+INSERT INTO messages_old SELECT id, imessage_id, text, sender, is_from_me, date_created, date_imported,
+                                handle_id, service, thread_id, has_attachments, contact_id, reply_to
+FROM messages;
+
+DROP TABLE messages;
+ALTER TABLE messages_old RENAME TO messages;
+
+CREATE INDEX idx_messages_date ON messages(date_created);
+CREATE INDEX idx_messages_sender ON messages(sender);
+CREATE INDEX idx_messages_handle ON messages(handle_id);
+CREATE INDEX idx_messages_imessage_id ON messages(imessage_id);
+CREATE INDEX idx_messages_contact_id ON messages(contact_id);
+CREATE INDEX idx_messages_reply_to ON messages(reply_to);
+
+PRAGMA foreign_keys = ON;
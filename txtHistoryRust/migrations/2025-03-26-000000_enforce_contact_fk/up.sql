@@ -0,0 +1,58 @@
+-- SQLite can't add a foreign key to an existing column, so rebuild the messages table
+PRAGMA foreign_keys = OFF;
+
+-- Received messages link to their sender, never to the "me" contact
+UPDATE messages SET contact_id = (
+    SELECT c.id FROM contacts c WHERE c.name = messages.sender AND c.is_me = 0
+)
+WHERE is_from_me = 0
+  AND (contact_id IS NULL OR contact_id IN (SELECT id FROM contacts WHERE is_me = 1))
+  AND EXISTS (SELECT 1 FROM contacts c WHERE c.name = messages.sender AND c.is_me = 0);
+
+-- Sent messages link to the other person in the same thread
+UPDATE messages SET contact_id = (
+    SELECT m.contact_id FROM messages m
+    WHERE m.thread_id = messages.thread_id AND m.is_from_me = 0 AND m.contact_id IS NOT NULL
+    LIMIT 1
+)
+WHERE is_from_me = 1
+  AND (contact_id IS NULL OR contact_id IN (SELECT id FROM contacts WHERE is_me = 1))
+  AND thread_id IS NOT NULL;
+
+-- Drop links to contacts that no longer exist
+UPDATE messages SET contact_id = NULL
+WHERE contact_id IS NOT NULL AND contact_id NOT IN (SELECT id FROM contacts);
+
+CREATE TABLE messages_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    imessage_id TEXT NOT NULL UNIQUE,
+    text TEXT,
+    sender TEXT NOT NULL,
+    is_from_me BOOLEAN NOT NULL,
+    date_created TIMESTAMP NOT NULL,
+    date_imported TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    handle_id TEXT,
+    service TEXT,
+    thread_id TEXT,
+    has_attachments BOOLEAN NOT NULL DEFAULT false,
+    contact_id INTEGER REFERENCES contacts(id),
+    reply_to TEXT
+);
+
+INSERT INTO messages_new (id, imessage_id, text, sender, is_from_me, date_created, date_imported,
+                          handle_id, service, thread_id, has_attachments, contact_id, reply_to)
+SELECT id, imessage_id, text, sender, is_from_me, date_created, date_imported,
+       handle_id, service, thread_id, has_attachments, contact_id, reply_to
+FROM messages;
+
+DROP TABLE messages;
+ALTER TABLE messages_new RENAME TO messages;
+
+CREATE INDEX idx_messages_date ON messages(date_created);
+CREATE INDEX idx_messages_sender ON messages(sender);
+CREATE INDEX idx_messages_handle ON messages(handle_id);
+CREATE INDEX idx_messages_imessage_id ON messages(imessage_id);
+CREATE INDEX idx_messages_contact_id ON messages(contact_id);
+CREATE INDEX idx_messages_reply_to ON messages(reply_to);
+
+PRAGMA foreign_keys = ON;
@@ -0,0 +1,2 @@
+-- Drop the message_revisions table
+DROP TABLE IF EXISTS message_revisions;
@@ -0,0 +1,6 @@
+-- Drop the index
+DROP INDEX IF EXISTS idx_processed_messages_flag_severity;
+
+-- Remove the columns
+ALTER TABLE processed_messages DROP COLUMN flag_severity;
+ALTER TABLE processed_messages DROP COLUMN flags;
@@ -0,0 +1,4 @@
+-- Store the results of the optional profanity/abusive-language pass
+ALTER TABLE processed_messages ADD COLUMN flags TEXT;           -- Flagged terms (JSON)
+ALTER TABLE processed_messages ADD COLUMN flag_severity REAL;   -- Highest severity, NULL if the pass didn't run
+CREATE INDEX idx_processed_messages_flag_severity ON processed_messages(flag_severity);
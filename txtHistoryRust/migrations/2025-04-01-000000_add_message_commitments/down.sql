@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_message_commitments_kind;
+DROP INDEX IF EXISTS idx_message_commitments_message;
+DROP TABLE IF EXISTS message_commitments;
@@ -0,0 +1,13 @@
+-- Questions, commitments and agreements detected in messages
+CREATE TABLE message_commitments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id INTEGER NOT NULL REFERENCES messages(id),
+    kind TEXT NOT NULL,                                -- question, commitment or agreement
+    snippet TEXT NOT NULL,                             -- Sentence the rule matched
+    answered_by INTEGER REFERENCES messages(id),       -- First reply from the other side, for questions
+    detected_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(message_id, kind, snippet)
+);
+
+CREATE INDEX idx_message_commitments_message ON message_commitments(message_id);
+CREATE INDEX idx_message_commitments_kind ON message_commitments(kind);
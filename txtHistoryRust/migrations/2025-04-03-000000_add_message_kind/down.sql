@@ -0,0 +1,5 @@
+-- Drop the index
+DROP INDEX IF EXISTS idx_messages_message_kind;
+
+-- Remove the column
+ALTER TABLE messages DROP COLUMN message_kind;
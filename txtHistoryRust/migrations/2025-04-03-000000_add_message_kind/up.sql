@@ -0,0 +1,3 @@
+-- Add message_kind to tell stickers, locations, payments and system messages from plain text
+ALTER TABLE messages ADD COLUMN message_kind TEXT NOT NULL DEFAULT 'text';
+CREATE INDEX idx_messages_message_kind ON messages(message_kind);
@@ -0,0 +1,6 @@
+-- Drop the index
+DROP INDEX IF EXISTS idx_messages_hidden;
+
+-- Remove the columns
+ALTER TABLE messages DROP COLUMN hidden_reason;
+ALTER TABLE messages DROP COLUMN hidden;
@@ -0,0 +1,4 @@
+-- Hidden messages stay in the archive but are left out of exports and reports
+ALTER TABLE messages ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE messages ADD COLUMN hidden_reason TEXT;
+CREATE INDEX idx_messages_hidden ON messages(hidden);
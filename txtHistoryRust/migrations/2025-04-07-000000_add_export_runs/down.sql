@@ -0,0 +1,6 @@
+-- Drop the index
+DROP INDEX IF EXISTS idx_export_chunks_export;
+
+-- Drop the tables
+DROP TABLE IF EXISTS export_chunks;
+DROP TABLE IF EXISTS export_runs;
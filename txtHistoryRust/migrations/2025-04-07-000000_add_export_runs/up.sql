@@ -0,0 +1,25 @@
+-- Progress of each export, so one that stopped partway can be resumed
+CREATE TABLE export_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    query_hash TEXT NOT NULL,
+    output_dir TEXT NOT NULL,
+    total_chunks INTEGER NOT NULL,
+    started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP
+);
+
+-- Chunk files each export has finished writing, with the checksum they were written with
+CREATE TABLE export_chunks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    export_id INTEGER NOT NULL,
+    chunk_index INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    messages INTEGER NOT NULL,
+    bytes INTEGER NOT NULL,
+    written_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(export_id) REFERENCES export_runs(id),
+    UNIQUE(export_id, chunk_index)
+);
+
+CREATE INDEX idx_export_chunks_export ON export_chunks(export_id);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_message_events_starts_on;
+DROP INDEX IF EXISTS idx_message_events_message;
+DROP TABLE IF EXISTS message_events;
@@ -0,0 +1,14 @@
+-- Dates and plans detected in messages ("Friday at 3", "dentist on the 12th")
+CREATE TABLE message_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id INTEGER NOT NULL REFERENCES messages(id),
+    starts_on DATE NOT NULL,                           -- Local date the message refers to
+    starts_at TEXT,                                    -- Local time (HH:MM:SS), NULL for all-day
+    expression TEXT NOT NULL,                          -- Words the date was read from
+    snippet TEXT NOT NULL,                             -- Sentence containing the expression
+    detected_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(message_id, expression)
+);
+
+CREATE INDEX idx_message_events_message ON message_events(message_id);
+CREATE INDEX idx_message_events_starts_on ON message_events(starts_on);
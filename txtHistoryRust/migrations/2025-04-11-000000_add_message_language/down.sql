@@ -0,0 +1,5 @@
+-- Drop the index
+DROP INDEX IF EXISTS idx_processed_messages_language;
+
+-- Remove the column
+ALTER TABLE processed_messages DROP COLUMN language;
@@ -0,0 +1,3 @@
+-- Language detected for each processed message, for `report languages`
+ALTER TABLE processed_messages ADD COLUMN language TEXT;  -- ISO 639-3 code, NULL if too short to tell
+CREATE INDEX idx_processed_messages_language ON processed_messages(language);
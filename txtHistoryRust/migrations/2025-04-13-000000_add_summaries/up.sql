@@ -0,0 +1,12 @@
+-- Abstractive summaries of days or sessions from the summarizer endpoint, cached by the
+-- transcript they were made from so unchanged days aren't sent again
+CREATE TABLE summaries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    input_hash TEXT NOT NULL,                          -- SHA-256 of the prompt version and transcript
+    model TEXT NOT NULL,                               -- Model that wrote the summary
+    unit TEXT NOT NULL,                                -- day or session
+    unit_key TEXT NOT NULL,                            -- Local date, or start of the session
+    summary TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(input_hash, model)
+);
@@ -0,0 +1,2 @@
+-- Remove the column
+ALTER TABLE processed_messages DROP COLUMN sentence_sentiments;
@@ -0,0 +1,2 @@
+-- Sentiment of each sentence of a processed message, for long messages with mixed tone
+ALTER TABLE processed_messages ADD COLUMN sentence_sentiments TEXT;  -- JSON array of {text, start, end, score}
@@ -0,0 +1,5 @@
+-- Remove the columns
+ALTER TABLE contacts DROP COLUMN notes;
+ALTER TABLE contacts DROP COLUMN birthday;
+ALTER TABLE contacts DROP COLUMN timezone;
+ALTER TABLE contacts DROP COLUMN relationship;
@@ -0,0 +1,5 @@
+-- Free-form notes and typed details on contacts, shown by `contacts edit` and passed to export templates
+ALTER TABLE contacts ADD COLUMN relationship TEXT;  -- e.g. "co-parent"
+ALTER TABLE contacts ADD COLUMN timezone TEXT;      -- IANA name, e.g. "America/Denver"
+ALTER TABLE contacts ADD COLUMN birthday DATE;
+ALTER TABLE contacts ADD COLUMN notes TEXT;
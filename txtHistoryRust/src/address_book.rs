@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OpenFlags};
+
+/// A person from the macOS Contacts app with every phone number and email they have
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressBookEntry {
+    pub name: String,
+    pub phones: Vec<String>,
+    pub emails: Vec<String>,
+}
+
+impl AddressBookEntry {
+    /// The first of this person's phone numbers or emails that `identifier` refers to
+    pub fn matching_identifier(&self, identifier: &str) -> Option<&str> {
+        self.phones
+            .iter()
+            .chain(&self.emails)
+            .find(|own| same_identifier(own, identifier))
+            .map(String::as_str)
+    }
+}
+
+/// Contacts keeps one database per account (iCloud, Google, "On My Mac") under `Sources`,
+/// plus a top-level one for older setups
+pub fn address_book_paths(home: &Path) -> Vec<PathBuf> {
+    let root = home.join("Library/Application Support/AddressBook");
+    let mut paths = vec![root.join("AddressBook-v22.abcddb")];
+
+    if let Ok(sources) = fs::read_dir(root.join("Sources")) {
+        let mut sources: Vec<PathBuf> = sources
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join("AddressBook-v22.abcddb"))
+            .collect();
+        sources.sort();
+        paths.extend(sources);
+    }
+
+    paths.retain(|path| path.is_file());
+    paths
+}
+
+/// Read every person with a phone number or email from a Contacts database
+pub fn read_address_book(path: &Path) -> Result<Vec<AddressBookEntry>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    let mut names = BTreeMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT Z_PK, trim(coalesce(ZFIRSTNAME, '') || ' ' || coalesce(ZLASTNAME, '')), ZORGANIZATION \
+         FROM ZABCDRECORD",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+    })?;
+    for row in rows {
+        let (id, name, organization) = row?;
+        let name = if name.is_empty() { organization.unwrap_or_default() } else { name };
+        if !name.is_empty() {
+            names.insert(id, AddressBookEntry { name, phones: Vec::new(), emails: Vec::new() });
+        }
+    }
+
+    for (table, column) in [("ZABCDPHONENUMBER", "ZFULLNUMBER"), ("ZABCDEMAILADDRESS", "ZADDRESS")] {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT ZOWNER, {} FROM {} WHERE {} IS NOT NULL ORDER BY ZORDERINGINDEX",
+            column, table, column
+        ))?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+        for row in rows {
+            let (owner, value) = row?;
+            if let Some(entry) = names.get_mut(&owner) {
+                if table == "ZABCDPHONENUMBER" {
+                    entry.phones.push(value);
+                } else {
+                    entry.emails.push(value);
+                }
+            }
+        }
+    }
+
+    Ok(names
+        .into_values()
+        .filter(|entry| !entry.phones.is_empty() || !entry.emails.is_empty())
+        .collect())
+}
+
+/// Whether two identifiers are the same phone number or email. Contacts stores numbers as
+/// typed ("(780) 555-0134") while chat.db uses E.164 ("+17805550134"), so numbers are compared
+/// by their last ten digits.
+pub fn same_identifier(a: &str, b: &str) -> bool {
+    if a.contains('@') || b.contains('@') {
+        return a.trim().eq_ignore_ascii_case(b.trim());
+    }
+
+    let digits = |value: &str| value.chars().filter(|c| c.is_ascii_digit()).collect::<String>();
+    let (a, b) = (digits(a), digits(b));
+    let tail = |value: &str| value[value.len().saturating_sub(10)..].to_string();
+    a.len() >= 7 && b.len() >= 7 && tail(&a) == tail(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_identifier() {
+        assert!(same_identifier("(780) 555-0134", "+17805550134"));
+        assert!(same_identifier("Phil@Example.com", "phil@example.com"));
+        assert!(!same_identifier("780-555-0134", "780-555-0135"));
+        assert!(!same_identifier("123", "+1123"));
+    }
+
+    #[test]
+    fn test_reads_contacts_database() {
+        let path = std::env::temp_dir().join("txt_history_address_book_test.abcddb");
+        let _ = fs::remove_file(&path);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE ZABCDRECORD (Z_PK INTEGER PRIMARY KEY, ZFIRSTNAME TEXT, ZLASTNAME TEXT, ZORGANIZATION TEXT);
+             CREATE TABLE ZABCDPHONENUMBER (ZOWNER INTEGER, ZFULLNUMBER TEXT, ZORDERINGINDEX INTEGER);
+             CREATE TABLE ZABCDEMAILADDRESS (ZOWNER INTEGER, ZADDRESS TEXT, ZORDERINGINDEX INTEGER);
+             INSERT INTO ZABCDRECORD VALUES (1, 'Phil', 'G', NULL), (2, NULL, NULL, 'Dentist'), (3, 'No', 'Numbers', NULL);
+             INSERT INTO ZABCDPHONENUMBER VALUES (1, '(867) 333-5566', 0), (2, '780 555 0100', 0);
+             INSERT INTO ZABCDEMAILADDRESS VALUES (1, 'apple@phil-g.com', 0);",
+        )
+        .unwrap();
+        drop(conn);
+
+        let entries = read_address_book(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "Phil G");
+        assert_eq!(entries[0].matching_identifier("+18673335566"), Some("(867) 333-5566"));
+        assert_eq!(entries[0].matching_identifier("apple@phil-g.com"), Some("apple@phil-g.com"));
+        assert_eq!(entries[1].name, "Dentist");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
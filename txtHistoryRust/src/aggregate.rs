@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::Duration;
+use serde::Serialize;
+
+use crate::models::DbMessage;
+use crate::sessions::{split_bursts, split_sessions};
+
+/// Summary of a set of counts, with nearest-rank percentiles
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Distribution {
+    pub count: usize,
+    pub mean: f64,
+    pub min: usize,
+    pub p25: usize,
+    pub p50: usize,
+    pub p75: usize,
+    pub p90: usize,
+    pub max: usize,
+}
+
+impl Distribution {
+    pub fn from_values(mut values: Vec<usize>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+
+        values.sort_unstable();
+        let percentile = |p: usize| values[((p * values.len()).div_ceil(100)).max(1) - 1];
+        let mean = values.iter().sum::<usize>() as f64 / values.len() as f64;
+
+        Self {
+            count: values.len(),
+            mean: (mean * 10.0).round() / 10.0,
+            min: values[0],
+            p25: percentile(25),
+            p50: percentile(50),
+            p75: percentile(75),
+            p90: percentile(90),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// How one person writes: how long their messages are, how many they send before the other
+/// side answers, and how many they send per session
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SenderStyle {
+    pub sender: String,
+    /// Characters per text message
+    pub length: Distribution,
+    /// Messages in a row before the other side replies
+    pub burst: Distribution,
+    /// Messages per session they took part in
+    pub per_session: Distribution,
+}
+
+/// Per-sender style statistics for `report style`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleReport {
+    pub contact: String,
+    pub sessions: usize,
+    pub senders: Vec<SenderStyle>,
+}
+
+impl StyleReport {
+    /// Build from a conversation sorted by date. A session ends after `session_gap` without
+    /// messages.
+    pub fn new(messages: &[DbMessage], contact: &str, session_gap: Duration) -> Self {
+        let mut lengths: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        let mut bursts: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        let mut per_session: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+
+        for message in messages {
+            let length = lengths.entry(message.sender.as_str()).or_default();
+            if let Some(text) = message.text.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+                length.push(text.chars().count());
+            }
+        }
+
+        let sessions = split_sessions(messages, session_gap);
+        for session in &sessions {
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for burst in split_bursts(session) {
+                let sender = burst[0].sender.as_str();
+                bursts.entry(sender).or_default().push(burst.len());
+                *counts.entry(sender).or_default() += burst.len();
+            }
+            for (sender, count) in counts {
+                per_session.entry(sender).or_default().push(count);
+            }
+        }
+
+        let senders = lengths
+            .into_iter()
+            .map(|(sender, length)| SenderStyle {
+                sender: sender.to_string(),
+                length: Distribution::from_values(length),
+                burst: Distribution::from_values(bursts.remove(sender).unwrap_or_default()),
+                per_session: Distribution::from_values(per_session.remove(sender).unwrap_or_default()),
+            })
+            .collect();
+
+        Self { contact: contact.to_string(), sessions: sessions.len(), senders }
+    }
+
+    /// One row per sender and measure
+    pub fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(["sender", "measure", "count", "mean", "min", "p25", "p50", "p75", "p90", "max"])?;
+        for style in &self.senders {
+            for (measure, d) in [("length", &style.length), ("burst", &style.burst), ("per_session", &style.per_session)] {
+                writer.write_record([
+                    style.sender.clone(),
+                    measure.to_string(),
+                    d.count.to_string(),
+                    d.mean.to_string(),
+                    d.min.to_string(),
+                    d.p25.to_string(),
+                    d.p50.to_string(),
+                    d.p75.to_string(),
+                    d.p90.to_string(),
+                    d.max.to_string(),
+                ])?;
+            }
+        }
+
+        let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&serde_json::json!({
+            "contact": self.contact,
+            "sessions": self.sessions,
+            "senders": self.senders,
+        }))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MessageKind;
+    use chrono::NaiveDateTime;
+
+    fn message(sender: &str, at: &str, text: &str) -> DbMessage {
+        let date_created = NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M").unwrap();
+        DbMessage {
+            id: 0,
+            imessage_id: String::new(),
+            text: Some(text.to_string()),
+            sender: sender.to_string(),
+            is_from_me: sender == "Jess",
+            date_created,
+            date_imported: date_created,
+            handle_id: None,
+            service: None,
+            thread_id: None,
+            has_attachments: false,
+            reply_to: None,
+            contact_id: Some(1),
+            message_kind: MessageKind::Text,
+            hidden: false,
+            hidden_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_distribution() {
+        let d = Distribution::from_values(vec![10, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!((d.count, d.min, d.p25, d.p50, d.p75, d.p90, d.max), (10, 1, 3, 5, 8, 9, 10));
+        assert_eq!(d.mean, 5.5);
+        assert_eq!(Distribution::from_values(vec![]), Distribution::default());
+    }
+
+    #[test]
+    fn test_style_report() {
+        let messages = vec![
+            message("Phil", "2025-01-20 09:00", "hey"),
+            message("Phil", "2025-01-20 09:01", "you there?"),
+            message("Phil", "2025-01-20 09:02", "call me"),
+            message("Jess", "2025-01-20 09:30", "Sorry, I was in a meeting. What's up?"),
+            message("Phil", "2025-01-21 18:00", "ok"),
+        ];
+        let report = StyleReport::new(&messages, "Phil", Duration::minutes(60));
+
+        assert_eq!(report.sessions, 2);
+        assert_eq!(report.senders.len(), 2);
+
+        let jess = &report.senders[0];
+        assert_eq!(jess.sender, "Jess");
+        assert_eq!(jess.length.max, 37);
+        assert_eq!(jess.per_session.count, 1);
+
+        let phil = &report.senders[1];
+        assert_eq!(phil.length.p50, 3);
+        assert_eq!((phil.burst.count, phil.burst.max, phil.burst.min), (2, 3, 1));
+        assert_eq!(phil.per_session.mean, 2.0);
+
+        let csv = report.to_csv().unwrap();
+        assert!(csv.starts_with("sender,measure,count,mean,min,p25,p50,p75,p90,max\nJess,length,1,37,"));
+        assert_eq!(csv.lines().count(), 7);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["senders"][1]["burst"]["max"], 3);
+    }
+}
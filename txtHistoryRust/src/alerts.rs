@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::config::SentimentAlertConfig;
+use crate::db::Database;
+use crate::error::TxtHistoryError;
+use crate::nlp::NlpProcessor;
+use crate::throttle::{Throttle, ThrottleConfig};
+
+/// A conversation whose recent messages dropped below the sentiment threshold
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SentimentAlert {
+    pub contact: String,
+    /// Average sentiment of the window, from -1.0 to 1.0
+    pub average: f32,
+    pub threshold: f32,
+    /// Number of messages averaged
+    pub messages: usize,
+}
+
+impl SentimentAlert {
+    /// One-line human readable summary
+    pub fn summary(&self) -> String {
+        format!(
+            "Sentiment with {} dropped to {:.2} over the last {} messages (threshold {:.2})",
+            self.contact, self.average, self.messages, self.threshold
+        )
+    }
+}
+
+/// Where one conversation stood at the last check
+#[derive(Debug, Clone, Copy, Default)]
+struct ConversationState {
+    /// Newest message in the window, so unchanged conversations aren't scored again
+    last_message_id: i32,
+    /// Whether the window is below the threshold; alerts fire only when this turns true
+    below: bool,
+}
+
+/// Scores new messages in the configured conversations for `watch` mode and reports the ones
+/// whose rolling average drops below the threshold. A conversation alerts once when it drops
+/// and again only after recovering above the threshold.
+pub struct SentimentMonitor {
+    config: SentimentAlertConfig,
+    processor: NlpProcessor,
+    throttle: ThrottleConfig,
+    states: HashMap<String, ConversationState>,
+}
+
+impl SentimentMonitor {
+    pub fn new(config: &SentimentAlertConfig) -> Self {
+        Self {
+            config: config.clone(),
+            processor: NlpProcessor::new(&config.version),
+            throttle: ThrottleConfig::default(),
+            states: HashMap::new(),
+        }
+    }
+
+    /// Limit how fast new messages are scored
+    pub fn with_throttle(mut self, throttle: ThrottleConfig) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Score messages imported since the last check, storing the results under the configured
+    /// processing version, and return an alert for each conversation that dropped
+    pub fn check(&mut self, db: &Database) -> Result<Vec<SentimentAlert>> {
+        let mut alerts = Vec::new();
+        let mut throttle = Throttle::new(self.throttle);
+
+        for name in &self.config.contacts {
+            let contact = db
+                .get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+
+            let ids = db.get_recent_message_ids(contact.id, self.config.window)?;
+            // Wait for a full window so a single harsh message can't trigger an alert
+            let Some(&last_message_id) = ids.last().filter(|_| ids.len() >= self.config.window) else {
+                continue;
+            };
+            let state = self.states.entry(contact.name.clone()).or_default();
+            if state.last_message_id == last_message_id {
+                continue;
+            }
+
+            let report = self.processor.process_messages(db, &ids)?;
+            throttle.wait(report.inserted);
+            let scores: Vec<f32> = report.processed.iter().filter_map(|p| p.sentiment_score).collect();
+            if let Some(alert) = evaluate(state, last_message_id, &contact.name, &scores, self.config.threshold) {
+                alerts.push(alert);
+            }
+        }
+
+        Ok(alerts)
+    }
+}
+
+/// Update a conversation's state with the window's scores, returning an alert if it just
+/// dropped below `threshold`
+fn evaluate(
+    state: &mut ConversationState,
+    last_message_id: i32,
+    contact: &str,
+    scores: &[f32],
+    threshold: f32,
+) -> Option<SentimentAlert> {
+    state.last_message_id = last_message_id;
+    if scores.is_empty() {
+        return None;
+    }
+
+    let average = scores.iter().sum::<f32>() / scores.len() as f32;
+    let was_below = std::mem::replace(&mut state.below, average < threshold);
+    (state.below && !was_below).then(|| SentimentAlert {
+        contact: contact.to_string(),
+        average,
+        threshold,
+        messages: scores.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alert_fires_once_per_drop() {
+        let mut state = ConversationState::default();
+
+        assert_eq!(evaluate(&mut state, 1, "Phil", &[0.5, 0.0, -0.5], -0.2), None);
+
+        let alert = evaluate(&mut state, 2, "Phil", &[0.0, -0.5, -1.0], -0.2).unwrap();
+        assert_eq!(alert.average, -0.5);
+        assert_eq!(alert.messages, 3);
+        assert!(alert.summary().starts_with("Sentiment with Phil dropped to -0.50"));
+
+        // Still below: no repeat until the conversation recovers
+        assert_eq!(evaluate(&mut state, 3, "Phil", &[-0.5, -1.0, -1.0], -0.2), None);
+        assert_eq!(evaluate(&mut state, 4, "Phil", &[-1.0, 0.5, 1.0], -0.2), None);
+        assert!(evaluate(&mut state, 5, "Phil", &[0.5, -1.0, -1.0], -0.2).is_some());
+        assert_eq!(state.last_message_id, 5);
+
+        // Messages without scores leave the state alone
+        assert_eq!(evaluate(&mut state, 6, "Phil", &[], -0.2), None);
+        assert!(state.below);
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+use crate::columnar::{write_parquet, NlpLookup};
+use crate::file_writer::{write_csv, write_txt, CsvOptions, WritePolicy, WriteStatus, WrittenFile};
+use crate::models::Message;
+use crate::style::ExportStyle;
+
+/// Async counterpart of `file_writer::write_file`: the same temp-file-and-rename and policy
+/// handling, but through `tokio::fs` so exports don't block the runtime while other tasks read
+/// from the database. `encode` renders the file's contents and is told when it's appending to
+/// a non-empty file so it can leave out headers.
+pub async fn write_file_async(
+    file_path: &str,
+    messages: usize,
+    policy: WritePolicy,
+    encode: impl FnOnce(bool) -> Result<Vec<u8>>,
+) -> Result<WrittenFile> {
+    let existing_len = fs::metadata(file_path).await.ok().map(|metadata| metadata.len());
+
+    let status = match (existing_len, policy) {
+        (Some(_), WritePolicy::SkipExisting) => {
+            return Ok(WrittenFile::new(file_path, messages, 0, WriteStatus::Skipped))
+        },
+        (Some(len), WritePolicy::Append) => {
+            let contents = encode(len > 0)?;
+            let file = OpenOptions::new()
+                .append(true)
+                .open(file_path)
+                .await
+                .with_context(|| format!("Failed to open {}", file_path))?;
+            let mut writer = BufWriter::new(file);
+            writer.write_all(&contents).await?;
+            writer.flush().await?;
+            writer.into_inner().sync_all().await?;
+            return Ok(WrittenFile::new(file_path, messages, contents.len() as u64, WriteStatus::Appended));
+        },
+        (Some(_), _) => WriteStatus::Replaced,
+        (None, _) => WriteStatus::Created,
+    };
+
+    let contents = encode(false)?;
+    let temp_path = format!("{}.{}.tmp", file_path, std::process::id());
+    let result = async {
+        let file = File::create(&temp_path)
+            .await
+            .with_context(|| format!("Failed to create {}", temp_path))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&contents).await?;
+        writer.flush().await?;
+        writer.into_inner().sync_all().await?;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            fs::rename(&temp_path, file_path)
+                .await
+                .with_context(|| format!("Failed to move {} into place", temp_path))?;
+            Ok(WrittenFile::new(file_path, messages, contents.len() as u64, status))
+        },
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path).await;
+            Err(e)
+        },
+    }
+}
+
+/// Write messages to a TXT file without blocking the runtime
+pub async fn write_txt_file_async(
+    messages: &[Message],
+    file_path: &str,
+    label_sources: bool,
+    style: &ExportStyle,
+    policy: WritePolicy,
+) -> Result<WrittenFile> {
+    write_file_async(file_path, messages.len(), policy, |_| {
+        let mut contents = Vec::new();
+        write_txt(&mut contents, messages, label_sources, style)?;
+        Ok(contents)
+    })
+    .await
+}
+
+/// Write messages to a CSV file without blocking the runtime. Appends leave out the header.
+pub async fn write_csv_file_async(
+    messages: &[Message],
+    file_path: &str,
+    options: &CsvOptions,
+    policy: WritePolicy,
+) -> Result<WrittenFile> {
+    write_file_async(file_path, messages.len(), policy, |appending| {
+        let mut contents = Vec::new();
+        write_csv(&mut contents, messages, options, options.header && !appending)?;
+        Ok(contents)
+    })
+    .await
+}
+
+/// Write messages to a JSON file without blocking the runtime
+pub async fn write_json_file_async(messages: &[Message], file_path: &str, policy: WritePolicy) -> Result<WrittenFile> {
+    // An array can't be extended in place, so appending rewrites the file with the messages added
+    if policy == WritePolicy::Append {
+        if let Ok(existing) = fs::read(file_path).await {
+            let mut combined: Vec<Message> = serde_json::from_slice(&existing)
+                .with_context(|| format!("Failed to read {} to append to it", file_path))?;
+            combined.extend_from_slice(messages);
+
+            let written = write_file_async(file_path, messages.len(), WritePolicy::Overwrite, |_| {
+                Ok(serde_json::to_vec_pretty(&combined)?)
+            })
+            .await?;
+            return Ok(WrittenFile {
+                bytes: written.bytes.saturating_sub(existing.len() as u64),
+                status: WriteStatus::Appended,
+                ..written
+            });
+        }
+    }
+
+    write_file_async(file_path, messages.len(), policy, |_| Ok(serde_json::to_vec_pretty(messages)?)).await
+}
+
+/// Write messages to a Parquet file without blocking the runtime. Parquet can't be appended to.
+pub async fn write_parquet_file_async(
+    messages: &[Message],
+    file_path: &str,
+    nlp: Option<&NlpLookup>,
+    policy: WritePolicy,
+) -> Result<WrittenFile> {
+    if policy == WritePolicy::Append {
+        return Err(anyhow::anyhow!("Parquet files can't be appended to; use --overwrite or --skip-existing"));
+    }
+
+    write_file_async(file_path, messages.len(), policy, |_| {
+        let mut contents = Vec::new();
+        write_parquet(&mut contents, messages, nlp)?;
+        Ok(contents)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_writer::write_txt_file;
+    use chrono::{Local, TimeZone};
+
+    fn message(content: &str) -> Message {
+        Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_writer_matches_sync_writer() {
+        let output_dir = std::env::temp_dir().join(format!("txt_history_async_test_{}", std::process::id()));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let sync_path = output_dir.join("sync.txt").to_string_lossy().to_string();
+        let async_path = output_dir.join("async.txt").to_string_lossy().to_string();
+        let csv_path = output_dir.join("chunk_1.csv").to_string_lossy().to_string();
+        let messages = vec![message("one"), message("two")];
+        let style = ExportStyle::default();
+
+        write_txt_file(&messages, &sync_path, false, &style, WritePolicy::Overwrite).unwrap();
+        let written = write_txt_file_async(&messages, &async_path, false, &style, WritePolicy::Overwrite)
+            .await
+            .unwrap();
+        assert_eq!(written.status, WriteStatus::Created);
+        assert_eq!(std::fs::read(&sync_path).unwrap(), std::fs::read(&async_path).unwrap());
+        assert_eq!(written.bytes, std::fs::metadata(&async_path).unwrap().len());
+
+        let options = CsvOptions::default();
+        write_csv_file_async(&messages[..1], &csv_path, &options, WritePolicy::Overwrite).await.unwrap();
+        let skipped = write_csv_file_async(&messages[1..], &csv_path, &options, WritePolicy::SkipExisting)
+            .await
+            .unwrap();
+        assert_eq!(skipped.status, WriteStatus::Skipped);
+        write_csv_file_async(&messages[1..], &csv_path, &options, WritePolicy::Append).await.unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        assert_eq!(csv.matches("Sender").count(), 1);
+        assert!(csv.contains("one") && csv.contains("two"));
+
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(&output_dir).unwrap().count(), 3);
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+
+use crate::db::Database;
+use crate::models::{AttachmentReportEntry, DbAttachment};
+
+/// Hook for extracting text from message attachments
+pub trait AttachmentProcessor {
+    /// Name stored alongside every transcript this processor produces
+    fn name(&self) -> &str;
+
+    /// Whether this processor can handle the given MIME type
+    fn supports(&self, mime_type: &str) -> bool;
+
+    /// Extract text from the attachment file
+    fn process(&self, path: &Path) -> Result<String>;
+}
+
+/// Transcribes audio and video attachments with the whisper.cpp CLI
+pub struct WhisperProcessor {
+    binary: PathBuf,
+    model: PathBuf,
+    ffmpeg: PathBuf,
+}
+
+impl WhisperProcessor {
+    /// Create a processor using the given whisper.cpp binary and model file
+    pub fn new(binary: impl Into<PathBuf>, model: impl Into<PathBuf>) -> Self {
+        Self {
+            binary: binary.into(),
+            model: model.into(),
+            ffmpeg: PathBuf::from("ffmpeg"),
+        }
+    }
+}
+
+impl AttachmentProcessor for WhisperProcessor {
+    fn name(&self) -> &str {
+        "whisper"
+    }
+
+    fn supports(&self, mime_type: &str) -> bool {
+        mime_type.starts_with("audio/") || mime_type.starts_with("video/")
+    }
+
+    fn process(&self, path: &Path) -> Result<String> {
+        // whisper.cpp only reads 16kHz mono WAV, so convert first (iMessage audio is CAF). Stems
+        // like "Audio Message" repeat across attachments, so a random suffix keeps runs apart
+        let wav_path = std::env::temp_dir().join(format!(
+            "txt_history_{}_{:016x}.wav",
+            path.file_stem().and_then(|s| s.to_str()).unwrap_or("attachment"),
+            rand::random::<u64>()
+        ));
+
+        let status = Command::new(&self.ffmpeg)
+            .args(["-y", "-loglevel", "error", "-i"])
+            .arg(path)
+            .args(["-ar", "16000", "-ac", "1"])
+            .arg(&wav_path)
+            .status()
+            .context("Failed to run ffmpeg")?;
+
+        if !status.success() {
+            return Err(anyhow::anyhow!("ffmpeg failed to convert {}", path.display()));
+        }
+
+        let output = Command::new(&self.binary)
+            .arg("-m")
+            .arg(&self.model)
+            .arg("-f")
+            .arg(&wav_path)
+            .arg("--no-timestamps")
+            .output()
+            .context("Failed to run whisper")?;
+
+        let _ = std::fs::remove_file(&wav_path);
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "whisper failed on {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// Extracts text from image attachments with the tesseract CLI
+pub struct OcrProcessor {
+    binary: PathBuf,
+}
+
+impl OcrProcessor {
+    /// Create a processor using the given tesseract binary
+    pub fn new(binary: impl Into<PathBuf>) -> Self {
+        Self { binary: binary.into() }
+    }
+}
+
+impl AttachmentProcessor for OcrProcessor {
+    fn name(&self) -> &str {
+        "ocr"
+    }
+
+    fn supports(&self, mime_type: &str) -> bool {
+        mime_type.starts_with("image/")
+    }
+
+    fn process(&self, path: &Path) -> Result<String> {
+        let output = Command::new(&self.binary)
+            .arg(path)
+            .arg("stdout")
+            .output()
+            .context("Failed to run tesseract")?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "tesseract failed on {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// Runs a set of attachment processors over every attachment still lacking a transcript
+pub struct AttachmentPipeline {
+    processors: Vec<Box<dyn AttachmentProcessor>>,
+}
+
+impl AttachmentPipeline {
+    /// Create an empty pipeline
+    pub fn new() -> Self {
+        Self { processors: Vec::new() }
+    }
+
+    /// Register a processor with the pipeline
+    pub fn add_processor(&mut self, processor: Box<dyn AttachmentProcessor>) {
+        self.processors.push(processor);
+    }
+
+    /// Process pending attachments and store their transcripts, returning how many were stored
+    pub fn process_pending(&self, db: &Database) -> Result<usize> {
+        let mut stored = 0;
+
+        for processor in &self.processors {
+            for attachment in db.get_untranscribed_attachments(processor.name())? {
+                if !Self::is_supported(processor.as_ref(), &attachment) {
+                    continue;
+                }
+
+                let Some(path) = Self::resolve_path(&attachment) else {
+                    continue;
+                };
+
+                // A single unreadable attachment shouldn't stop the whole run
+                match processor.process(&path) {
+                    Ok(transcript) if !transcript.is_empty() => {
+                        db.add_attachment_transcript(&attachment, processor.name(), &transcript)?;
+                        stored += 1;
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Skipping {}: {}", path.display(), e),
+                }
+            }
+        }
+
+        Ok(stored)
+    }
+
+    fn is_supported(processor: &dyn AttachmentProcessor, attachment: &DbAttachment) -> bool {
+        attachment
+            .mime_type
+            .as_deref()
+            .map_or(false, |mime| processor.supports(mime))
+    }
+
+    /// Expand the `~` prefix chat.db uses for attachment paths. Windows has no `HOME`, so
+    /// copied attachments are looked up under the user profile there.
+    fn resolve_path(attachment: &DbAttachment) -> Option<PathBuf> {
+        let filename = attachment.filename.as_deref()?;
+
+        let path = match filename.strip_prefix("~/") {
+            Some(rest) => {
+                let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+                PathBuf::from(home).join(rest)
+            },
+            None => PathBuf::from(filename),
+        };
+
+        path.exists().then_some(path)
+    }
+}
+
+impl Default for AttachmentPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One row per attachment for `attachments list --csv`, with the message's local send time
+pub fn attachments_csv(entries: &[AttachmentReportEntry]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["date", "sender", "type", "size_bytes", "filename", "message_text", "message_id"])?;
+    for entry in entries {
+        writer.write_record([
+            Local.from_utc_datetime(&entry.date_created).format("%Y-%m-%d %H:%M:%S").to_string(),
+            entry.sender.clone(),
+            entry.mime_type.clone().unwrap_or_default(),
+            entry.size_bytes.map(|size| size.to_string()).unwrap_or_default(),
+            entry.filename.clone().unwrap_or_default(),
+            entry.text.clone().unwrap_or_default(),
+            entry.message_id.to_string(),
+        ])?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(String::from_utf8(bytes)?)
+}
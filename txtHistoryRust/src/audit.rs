@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, Local, NaiveDateTime, TimeZone};
+
+use crate::discovery::ChatDbMessageDate;
+
+/// How quiet a stretch must be, and how busy around it, to count as a suspicious gap
+#[derive(Debug, Clone, Copy)]
+pub struct GapOptions {
+    /// Shortest stretch without messages to report
+    pub min_gap_days: i64,
+    /// Days before and after the gap to measure activity over
+    pub window_days: i64,
+    /// Messages needed on each side of the gap for the conversation to count as active
+    pub min_activity: usize,
+}
+
+impl Default for GapOptions {
+    fn default() -> Self {
+        Self {
+            min_gap_days: 7,
+            window_days: 30,
+            min_activity: 10,
+        }
+    }
+}
+
+/// A stretch with no messages inside an otherwise active conversation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    /// Last message before the gap, in UTC
+    pub start: NaiveDateTime,
+    /// First message after the gap, in UTC
+    pub end: NaiveDateTime,
+    /// Messages in the window before the gap
+    pub before: usize,
+    /// Messages in the window after the gap
+    pub after: usize,
+}
+
+impl Gap {
+    pub fn days(&self) -> i64 {
+        (self.end - self.start).num_days()
+    }
+}
+
+/// Find gaps of at least `min_gap_days` between consecutive messages where the
+/// conversation was active on both sides. `dates` must be sorted.
+pub fn find_gaps(dates: &[NaiveDateTime], options: &GapOptions) -> Vec<Gap> {
+    let min_gap = Duration::days(options.min_gap_days);
+    let window = Duration::days(options.window_days);
+
+    dates
+        .windows(2)
+        .filter(|pair| pair[1] - pair[0] >= min_gap)
+        .map(|pair| {
+            let (start, end) = (pair[0], pair[1]);
+            let before = dates.partition_point(|d| *d <= start) - dates.partition_point(|d| *d < start - window);
+            let after = dates.partition_point(|d| *d <= end + window) - dates.partition_point(|d| *d < end);
+            Gap { start, end, before, after }
+        })
+        .filter(|gap| gap.before >= options.min_activity && gap.after >= options.min_activity)
+        .collect()
+}
+
+/// Message counts for one month, imported versus what chat.db holds
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthAudit {
+    /// YYYY-MM in local time
+    pub month: String,
+    pub imported: usize,
+    pub chat_db: usize,
+    /// chat.db messages whose text is only in `attributedBody`, which older imports dropped
+    pub attributed_body_only: usize,
+}
+
+impl MonthAudit {
+    /// Messages in chat.db that aren't in the local database
+    pub fn missing(&self) -> usize {
+        self.chat_db.saturating_sub(self.imported)
+    }
+}
+
+/// Compare imported messages to chat.db month by month, oldest first
+pub fn compare_months(imported: &[NaiveDateTime], chat_db: &[ChatDbMessageDate]) -> Vec<MonthAudit> {
+    let mut months: BTreeMap<String, MonthAudit> = BTreeMap::new();
+
+    for date in imported {
+        month_entry(&mut months, date).imported += 1;
+    }
+    for message in chat_db {
+        let audit = month_entry(&mut months, &message.date);
+        audit.chat_db += 1;
+        if message.attributed_body_only {
+            audit.attributed_body_only += 1;
+        }
+    }
+
+    months.into_values().collect()
+}
+
+fn month_entry<'a>(months: &'a mut BTreeMap<String, MonthAudit>, date: &NaiveDateTime) -> &'a mut MonthAudit {
+    let month = Local.from_utc_datetime(date).format("%Y-%m").to_string();
+    months.entry(month.clone()).or_insert(MonthAudit {
+        month,
+        imported: 0,
+        chat_db: 0,
+        attributed_body_only: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32, hour: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2025, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_find_gaps() {
+        // Busy first and last weeks of January with nothing in between
+        let mut dates: Vec<NaiveDateTime> = (1..=5).flat_map(|day| [date(day, 9), date(day, 18)]).collect();
+        dates.extend((25..=29).flat_map(|day| [date(day, 9), date(day, 18)]));
+
+        let gaps = find_gaps(&dates, &GapOptions::default());
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].start, date(5, 18));
+        assert_eq!(gaps[0].end, date(25, 9));
+        assert_eq!(gaps[0].days(), 19);
+        assert_eq!((gaps[0].before, gaps[0].after), (10, 10));
+
+        // A quiet conversation going silent isn't suspicious
+        let options = GapOptions { min_activity: 11, ..GapOptions::default() };
+        assert!(find_gaps(&dates, &options).is_empty());
+        assert!(find_gaps(&[], &GapOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_compare_months() {
+        let imported = vec![date(10, 12), date(11, 12)];
+        let chat_db = vec![
+            ChatDbMessageDate { date: date(10, 12), attributed_body_only: false },
+            ChatDbMessageDate { date: date(11, 12), attributed_body_only: false },
+            ChatDbMessageDate { date: date(12, 12), attributed_body_only: true },
+        ];
+
+        let months = compare_months(&imported, &chat_db);
+        assert_eq!(months.len(), 1);
+        assert_eq!(months[0].month, "2025-01");
+        assert_eq!(months[0].missing(), 1);
+        assert_eq!(months[0].attributed_body_only, 1);
+    }
+}
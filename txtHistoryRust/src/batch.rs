@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Name of the summary written at the top of an `export-all` output directory
+pub const BATCH_SUMMARY_FILE: &str = "export-summary.json";
+
+/// How one contact's export went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Exported,
+    NoMessages,
+    Failed,
+}
+
+/// One contact's entry in the summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchContact {
+    pub contact: String,
+    /// Subdirectory the contact's files were written to, relative to the summary
+    pub directory: String,
+    pub status: BatchStatus,
+    pub messages: usize,
+    pub elapsed_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchContact {
+    pub fn new(contact: &str, status: BatchStatus, messages: usize, elapsed: Duration) -> Self {
+        Self {
+            contact: contact.to_string(),
+            directory: contact.to_string(),
+            status,
+            messages,
+            elapsed_ms: elapsed.as_millis() as u64,
+            error: None,
+        }
+    }
+
+    pub fn failed(contact: &str, error: &anyhow::Error, elapsed: Duration) -> Self {
+        Self { error: Some(format!("{:#}", error)), ..Self::new(contact, BatchStatus::Failed, 0, elapsed) }
+    }
+}
+
+/// Combined record of a multi-contact export, written to `export-summary.json`. Each contact's
+/// own `manifest.json` (with `--hashes`) stays in its subdirectory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchSummary {
+    /// When the export was generated, in UTC
+    pub generated_at: String,
+    pub format: String,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub contacts: Vec<BatchContact>,
+}
+
+impl BatchSummary {
+    pub fn new(format: &str, start_date: &Option<String>, end_date: &Option<String>) -> Self {
+        Self {
+            generated_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            format: format.to_lowercase(),
+            start_date: start_date.clone(),
+            end_date: end_date.clone(),
+            contacts: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, contact: BatchContact) {
+        self.contacts.push(contact);
+    }
+
+    /// Messages written across every contact
+    pub fn messages(&self) -> usize {
+        self.contacts.iter().map(|c| c.messages).sum()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.contacts.iter().filter(|c| c.status == BatchStatus::Failed).count()
+    }
+
+    /// Write the summary into `output_dir`, returning its path
+    pub fn write(&self, output_dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(output_dir)?;
+        let path = output_dir.join(BATCH_SUMMARY_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Run `run` for every item on up to `jobs` threads, returning the results in item order.
+/// Unlike `write_in_parallel`, a failure doesn't stop the other items.
+pub fn run_in_parallel<T: Sync, R: Send>(items: &[T], jobs: usize, run: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let workers = jobs.clamp(1, items.len().max(1));
+    if workers == 1 {
+        return items.iter().map(&run).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let worker = || {
+        let mut done = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(item) = items.get(i) else { break };
+            done.push((i, run(item)));
+        }
+        done
+    };
+
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers).map(|_| scope.spawn(worker)).collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("export-all worker thread panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_summary() {
+        let contacts = vec!["Phil", "Robert", "Rhonda", "Sam"];
+        let results = run_in_parallel(&contacts, 3, |name| match *name {
+            "Sam" => Err(anyhow::anyhow!("disk full")),
+            "Rhonda" => Ok(0),
+            name => Ok(name.len() * 10),
+        });
+
+        let mut summary = BatchSummary::new("TXT", &Some("2025-01-01".to_string()), &None);
+        for (name, result) in contacts.iter().zip(results) {
+            summary.add(match result {
+                Ok(0) => BatchContact::new(name, BatchStatus::NoMessages, 0, Duration::ZERO),
+                Ok(count) => BatchContact::new(name, BatchStatus::Exported, count, Duration::from_millis(5)),
+                Err(e) => BatchContact::failed(name, &e, Duration::ZERO),
+            });
+        }
+
+        // Results stay in contact order however the threads finish
+        let names: Vec<&str> = summary.contacts.iter().map(|c| c.contact.as_str()).collect();
+        assert_eq!(names, contacts);
+        assert_eq!(summary.messages(), 100);
+        assert_eq!(summary.failed(), 1);
+        assert_eq!(summary.contacts[3].error.as_deref(), Some("disk full"));
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["format"], "txt");
+        assert_eq!(json["contacts"][2]["status"], "no_messages");
+        assert!(json["contacts"][0].get("error").is_none());
+    }
+}
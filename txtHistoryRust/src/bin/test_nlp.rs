@@ -1,7 +1,7 @@
 use anyhow::Result;
-use txt_history_rust::{
+use txtHistoryRust::{
     db,
-    models::{DbMessage, NewMessage},
+    models::{DbMessage, MessageKind, NewMessage},
     nlp::NlpProcessor,
 };
 use chrono::Local;
@@ -41,7 +41,7 @@ fn main() -> Result<()> {
     let batch_ids = &unprocessed_ids[0..batch_size];
     
     println!("Processing a batch of {} messages...", batch_ids.len());
-    let processed = processor.process_messages(&db, batch_ids)?;
+    let processed = processor.process_messages(&db, batch_ids)?.processed;
     
     // Print results
     println!("Successfully processed {} messages", processed.len());
@@ -162,7 +162,9 @@ fn ensure_test_messages(db: &db::Database) -> Result<()> {
             service: Some("iMessage".to_string()),
             thread_id: Some("test_thread".to_string()),
             has_attachments: false,
+            reply_to: None,
             contact_id: None,
+            message_kind: MessageKind::Text,
         };
         
         db.add_message(new_message)?;
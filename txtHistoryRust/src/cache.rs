@@ -1,8 +1,28 @@
+use crate::config::CacheConfig;
 use crate::models::{Contact, DateRange, Message};
-use anyhow::{Context, Result};
-use chrono::{DateTime, Local};
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use chrono::{DateTime, Duration, Local};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Environment variable holding the passphrase for an encrypted cache
+pub const PASSPHRASE_ENV: &str = "TXT_HISTORY_PASSPHRASE";
+
+/// Tree recording when each entry was last used and how big it is, for LRU eviction
+const ACCESS_TREE: &str = "access";
+
+/// Tree holding the key derivation salt and a value encrypted with the current key, used to
+/// tell whether existing entries can be read
+const META_TREE: &str = "meta";
+const SALT_KEY: &[u8] = b"salt";
+const CHECK_KEY: &[u8] = b"check";
+const CHECK_VALUE: &[u8] = b"txt-history message cache";
+const NONCE_LEN: usize = 24;
 
 #[derive(Serialize, Deserialize)]
 struct CacheEntry {
@@ -12,38 +32,272 @@ struct CacheEntry {
     timestamp: DateTime<Local>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct AccessRecord {
+    /// Milliseconds since the Unix epoch
+    last_used: i64,
+    bytes: u64,
+}
+
+/// What the cache holds, for `cache stats`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    pub entries: usize,
+    /// Contacts with at least one cached entry
+    pub contacts: usize,
+    pub bytes: u64,
+    /// Entries older than the TTL, removed the next time they're read
+    pub expired: usize,
+    pub oldest: Option<DateTime<Local>>,
+    pub newest: Option<DateTime<Local>>,
+}
+
+impl CacheStats {
+    /// Multi-line description for the terminal
+    pub fn summary(&self, config: &CacheConfig) -> String {
+        let format = |at: Option<DateTime<Local>>| at.map_or_else(|| "-".to_string(), |at| at.format("%Y-%m-%d %H:%M").to_string());
+        let ttl = match config.ttl_hours {
+            0 => "never".to_string(),
+            hours => format!("after {} hours", hours),
+        };
+
+        [
+            format!("Entries:  {} for {} contacts ({} expired)", self.entries, self.contacts, self.expired),
+            format!("Size:     {:.1} of {} MB", self.bytes as f64 / (1024.0 * 1024.0), config.max_size_mb),
+            format!("Oldest:   {}", format(self.oldest)),
+            format!("Newest:   {}", format(self.newest)),
+            format!("Expires:  {}", ttl),
+        ]
+        .join("\n")
+    }
+}
+
+/// Encrypts entries with XChaCha20-Poly1305 under a key derived from the passphrase with Argon2
+struct CacheCipher {
+    key: [u8; 32],
+    cipher: XChaCha20Poly1305,
+}
+
+impl CacheCipher {
+    fn derive(passphrase: &str, salt: &[u8]) -> Result<Self> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| anyhow!("Failed to derive the cache key: {}", e))?;
+        let cipher = XChaCha20Poly1305::new_from_slice(&key).map_err(|e| anyhow!("Invalid cache key: {}", e))?;
+        Ok(Self { key, cipher })
+    }
+
+    /// A random nonce followed by the ciphertext
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = self
+            .cipher
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .map_err(|_| anyhow!("Failed to encrypt cache entry"))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < NONCE_LEN {
+            bail!("Cache entry is too short to decrypt");
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("Failed to decrypt cache entry"))
+    }
+
+    /// Keyed hash standing in for contact names and dates in entry keys
+    fn tag(&self, value: &str) -> String {
+        let hash = Sha256::new().chain_update(self.key).chain_update(value.as_bytes()).finalize();
+        format!("{:x}", hash)[..32].to_string()
+    }
+}
+
 pub struct MessageCache {
     db: sled::Db,
+    access: sled::Tree,
+    enabled: bool,
+    cipher: Option<CacheCipher>,
+    max_bytes: u64,
+    ttl: Option<Duration>,
 }
 
 impl MessageCache {
     pub fn new() -> Result<Self> {
+        Self::open(&PathBuf::from(".message_cache"))
+    }
+
+    /// Open the cache in a specific directory, e.g. a profile's cache directory
+    pub fn open(cache_dir: &Path) -> Result<Self> {
+        Self::open_with(cache_dir, &CacheConfig::default())
+    }
+
+    /// Open the cache with size, age and encryption settings from the config. Entries written
+    /// unencrypted can't be read and are dropped, as is everything when caching is disabled.
+    /// Entries written with another passphrase fail to open instead, since a mistyped passphrase
+    /// shouldn't empty the cache; see `reset`.
+    pub fn open_with(cache_dir: &Path, config: &CacheConfig) -> Result<Self> {
+        let passphrase = std::env::var(PASSPHRASE_ENV).ok();
+        Self::open_with_passphrase(cache_dir, config, passphrase.as_deref(), false)
+    }
+
+    /// Open the cache like `open_with`, dropping entries written with another passphrase
+    pub fn reset(cache_dir: &Path, config: &CacheConfig) -> Result<Self> {
+        let passphrase = std::env::var(PASSPHRASE_ENV).ok();
+        Self::open_with_passphrase(cache_dir, config, passphrase.as_deref(), true)
+    }
+
+    fn open_with_passphrase(
+        cache_dir: &Path,
+        config: &CacheConfig,
+        passphrase: Option<&str>,
+        reset: bool,
+    ) -> Result<Self> {
         // Create cache directory if it doesn't exist
-        let cache_dir = PathBuf::from(".message_cache");
-        std::fs::create_dir_all(&cache_dir)?;
-        
-        let db = sled::open(&cache_dir)
+        std::fs::create_dir_all(cache_dir)?;
+
+        let db = sled::open(cache_dir)
             .context("Failed to open cache database")?;
-        
-        Ok(Self { db })
+        let access = db.open_tree(ACCESS_TREE)?;
+        let meta = db.open_tree(META_TREE)?;
+
+        let cipher = match (config.enabled && config.encrypt, passphrase) {
+            (false, _) => None,
+            (true, None) => bail!("Cache encryption is on but {} isn't set", PASSPHRASE_ENV),
+            (true, Some(passphrase)) => {
+                let salt = match meta.get(SALT_KEY)? {
+                    Some(salt) => salt.to_vec(),
+                    None => {
+                        let salt: [u8; 16] = rand::random();
+                        meta.insert(SALT_KEY, &salt[..])?;
+                        salt.to_vec()
+                    },
+                };
+                Some(CacheCipher::derive(passphrase, &salt)?)
+            },
+        };
+
+        let readable = match (&cipher, meta.get(CHECK_KEY)?) {
+            (Some(cipher), Some(check)) => {
+                let readable = cipher.decrypt(&check).is_ok();
+                if !readable && !reset {
+                    bail!(
+                        "The cache was encrypted with another passphrase; set {} to it or pass --reset-cache",
+                        PASSPHRASE_ENV
+                    );
+                }
+                readable
+            },
+            (None, None) => true,
+            _ => false,
+        };
+
+        let cache = Self {
+            db,
+            access,
+            enabled: config.enabled,
+            cipher,
+            max_bytes: config.max_size_mb.saturating_mul(1024 * 1024),
+            ttl: (config.ttl_hours > 0).then(|| Duration::hours(config.ttl_hours as i64)),
+        };
+
+        if !readable || !cache.enabled {
+            cache.clear_cache()?;
+            match &cache.cipher {
+                Some(cipher) => meta.insert(CHECK_KEY, cipher.encrypt(CHECK_VALUE)?)?,
+                None => meta.remove(CHECK_KEY)?,
+            };
+            meta.flush()?;
+        }
+
+        Ok(cache)
+    }
+
+    /// Drop a contact's cached messages from the cache in `cache_dir` after an import changed
+    /// them, returning the number of entries dropped. Does nothing if the cache was never created.
+    pub fn invalidate_contact_at(cache_dir: &Path, config: &CacheConfig, contact_name: &str) -> Result<usize> {
+        if !cache_dir.exists() {
+            return Ok(0);
+        }
+        Self::open_with(cache_dir, config)?.invalidate_contact(contact_name)
     }
 
-    fn make_key(contact: &Contact, date_range: &DateRange) -> Vec<u8> {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Key prefix shared by every entry for a contact
+    fn contact_prefix(&self, contact_name: &str) -> String {
+        match &self.cipher {
+            Some(cipher) => format!("{}:", cipher.tag(contact_name)),
+            None => format!("{}:", contact_name),
+        }
+    }
+
+    fn make_key(&self, contact: &Contact, date_range: &DateRange) -> Vec<u8> {
         // Create a unique key based on contact and date range
-        let key = format!(
-            "{}:{}:{}",
-            contact.name,
+        let range = format!(
+            "{}:{}",
             date_range.start.map_or("".to_string(), |d| d.to_rfc3339()),
             date_range.end.map_or("".to_string(), |d| d.to_rfc3339())
         );
-        key.into_bytes()
+        let range = match &self.cipher {
+            Some(cipher) => cipher.tag(&range),
+            None => range,
+        };
+        format!("{}{}", self.contact_prefix(&contact.name), range).into_bytes()
+    }
+
+    fn encode(&self, entry: &CacheEntry) -> Result<Vec<u8>> {
+        let data = bincode::serialize(entry)?;
+        match &self.cipher {
+            Some(cipher) => cipher.encrypt(&data),
+            None => Ok(data),
+        }
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<CacheEntry> {
+        Ok(match &self.cipher {
+            Some(cipher) => bincode::deserialize(&cipher.decrypt(data)?)?,
+            None => bincode::deserialize(data)?,
+        })
+    }
+
+    fn is_expired(&self, entry: &CacheEntry) -> bool {
+        self.ttl.is_some_and(|ttl| Local::now() - entry.timestamp > ttl)
+    }
+
+    fn touch(&self, key: &[u8], bytes: u64) -> Result<()> {
+        let record = AccessRecord { last_used: Local::now().timestamp_millis(), bytes };
+        self.access.insert(key, bincode::serialize(&record)?)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<()> {
+        self.db.remove(key)?;
+        self.access.remove(key)?;
+        Ok(())
     }
 
     pub fn get_cached_messages(&self, contact: &Contact, date_range: &DateRange) -> Result<Option<Vec<Message>>> {
-        let key = Self::make_key(contact, date_range);
-        
+        if !self.enabled {
+            return Ok(None);
+        }
+        let key = self.make_key(contact, date_range);
+
         if let Some(data) = self.db.get(&key)? {
-            let entry: CacheEntry = bincode::deserialize(&data)?;
+            let entry = self.decode(&data)?;
+            if self.is_expired(&entry) {
+                self.remove(&key)?;
+                return Ok(None);
+            }
+            self.touch(&key, data.len() as u64)?;
             Ok(Some(entry.messages))
         } else {
             Ok(None)
@@ -51,8 +305,11 @@ impl MessageCache {
     }
 
     pub fn cache_messages(&self, contact: &Contact, date_range: &DateRange, messages: &[Message]) -> Result<()> {
-        let key = Self::make_key(contact, date_range);
-        
+        if !self.enabled {
+            return Ok(());
+        }
+        let key = self.make_key(contact, date_range);
+
         let entry = CacheEntry {
             contact_name: contact.name.clone(),
             date_range: date_range.clone(),
@@ -60,16 +317,213 @@ impl MessageCache {
             timestamp: Local::now(),
         };
 
-        let data = bincode::serialize(&entry)?;
-        self.db.insert(key, data)?;
+        let data = self.encode(&entry)?;
+        // An entry that can't fit would only evict everything else
+        if data.len() as u64 > self.max_bytes {
+            self.remove(&key)?;
+            return Ok(());
+        }
+
+        self.db.insert(&key, data.as_slice())?;
+        self.touch(&key, data.len() as u64)?;
+        self.evict()?;
         self.db.flush()?;
-        
+
         Ok(())
     }
 
+    /// Remove least recently used entries until the cache fits in its size limit, returning the
+    /// number removed
+    fn evict(&self) -> Result<usize> {
+        let mut records = Vec::new();
+        for item in self.access.iter() {
+            let (key, value) = item?;
+            let record: AccessRecord = bincode::deserialize(&value)?;
+            records.push((key, record));
+        }
+
+        let mut total: u64 = records.iter().map(|(_, record)| record.bytes).sum();
+        records.sort_by_key(|(_, record)| record.last_used);
+
+        let mut evicted = 0;
+        for (key, record) in records {
+            if total <= self.max_bytes {
+                break;
+            }
+            self.remove(&key)?;
+            total -= record.bytes;
+            evicted += 1;
+        }
+
+        Ok(evicted)
+    }
+
+    /// Drop every cached date range for a contact, returning the number of entries dropped
+    pub fn invalidate_contact(&self, contact_name: &str) -> Result<usize> {
+        let prefix = self.contact_prefix(contact_name);
+        let keys: Vec<sled::IVec> = self.db.scan_prefix(prefix.as_bytes()).keys().collect::<sled::Result<_>>()?;
+
+        for key in &keys {
+            self.remove(key)?;
+        }
+        self.db.flush()?;
+
+        Ok(keys.len())
+    }
+
+    pub fn stats(&self) -> Result<CacheStats> {
+        let mut stats = CacheStats::default();
+        let mut contacts = BTreeSet::new();
+
+        for item in self.db.iter() {
+            let (_, data) = item?;
+            let entry = self.decode(&data)?;
+
+            stats.entries += 1;
+            stats.bytes += data.len() as u64;
+            if self.is_expired(&entry) {
+                stats.expired += 1;
+            }
+            stats.oldest = Some(stats.oldest.map_or(entry.timestamp, |oldest| oldest.min(entry.timestamp)));
+            stats.newest = Some(stats.newest.map_or(entry.timestamp, |newest| newest.max(entry.timestamp)));
+            contacts.insert(entry.contact_name);
+        }
+
+        stats.contacts = contacts.len();
+        Ok(stats)
+    }
+
     pub fn clear_cache(&self) -> Result<()> {
         self.db.clear()?;
+        self.access.clear()?;
         self.db.flush()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn message(content: &str) -> Message {
+        Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    fn contact(name: &str) -> Contact {
+        Contact { name: name.to_string(), phone: None, email: None }
+    }
+
+    #[test]
+    fn test_invalidation_and_eviction() {
+        let dir = std::env::temp_dir().join(format!("txt_history_cache_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = MessageCache::open(&dir).unwrap();
+        let all = DateRange { start: None, end: None };
+        let january = DateRange { start: Some(Local.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()), end: None };
+
+        cache.cache_messages(&contact("Phil"), &all, &[message("hi")]).unwrap();
+        cache.cache_messages(&contact("Phil"), &january, &[message("hi")]).unwrap();
+        cache.cache_messages(&contact("Phillip"), &all, &[message("hello")]).unwrap();
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.entries, stats.contacts, stats.expired), (3, 2, 0));
+
+        // Only Phil's entries go, not those of a contact whose name starts the same way
+        assert_eq!(cache.invalidate_contact("Phil").unwrap(), 2);
+        assert!(cache.get_cached_messages(&contact("Phil"), &all).unwrap().is_none());
+        assert!(cache.get_cached_messages(&contact("Phillip"), &all).unwrap().is_some());
+
+        // With room for about one entry, the least recently used one is evicted
+        let size = cache.stats().unwrap().bytes;
+        let cache = MessageCache { max_bytes: size + size / 2, ..cache };
+        cache.cache_messages(&contact("Sherry"), &all, &[message("hey")]).unwrap();
+        assert!(cache.get_cached_messages(&contact("Phillip"), &all).unwrap().is_none());
+        assert!(cache.get_cached_messages(&contact("Sherry"), &all).unwrap().is_some());
+
+        // Entries past their TTL aren't served
+        let cache = MessageCache { ttl: Some(Duration::seconds(-1)), ..cache };
+        assert_eq!(cache.stats().unwrap().expired, 1);
+        assert!(cache.get_cached_messages(&contact("Sherry"), &all).unwrap().is_none());
+        assert_eq!(cache.stats().unwrap().entries, 0);
+
+        drop(cache);
+        assert_eq!(MessageCache::invalidate_contact_at(&dir.join("missing"), &CacheConfig::default(), "Phil").unwrap(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_encryption() {
+        let dir = std::env::temp_dir().join(format!("txt_history_cache_encryption_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let encrypted = CacheConfig { encrypt: true, ..CacheConfig::default() };
+        let all = DateRange { start: None, end: None };
+
+        // Each handle is flushed and dropped at the end of its block, so sled has released the
+        // directory's lock before the next open
+        {
+            let cache = MessageCache::open_with_passphrase(&dir, &encrypted, Some("hunter2"), false).unwrap();
+            assert!(cache.is_encrypted());
+            cache.cache_messages(&contact("Phil"), &all, &[message("pick up Everly at 3")]).unwrap();
+
+            // Neither the message nor the contact's name is readable on disk
+            for item in cache.db.iter() {
+                let (key, data) = item.unwrap();
+                assert!(!String::from_utf8_lossy(&key).contains("Phil"));
+                assert!(!String::from_utf8_lossy(&data).contains("Everly"));
+            }
+            assert_eq!(cache.stats().unwrap().contacts, 1);
+            cache.db.flush().unwrap();
+        }
+
+        {
+            let cache = MessageCache::open_with_passphrase(&dir, &encrypted, Some("hunter2"), false).unwrap();
+            let cached = cache.get_cached_messages(&contact("Phil"), &all).unwrap().unwrap();
+            assert_eq!(cached[0].content, "pick up Everly at 3");
+            assert_eq!(cache.invalidate_contact("Phil").unwrap(), 1);
+            cache.cache_messages(&contact("Phil"), &all, &[message("hi")]).unwrap();
+            cache.db.flush().unwrap();
+        }
+
+        // Entries from another passphrase are only dropped when asked to, and entries from
+        // encrypted mode can't be read and are dropped
+        assert!(MessageCache::open_with_passphrase(&dir, &encrypted, Some("wrong"), false).is_err());
+        {
+            let cache = MessageCache::open_with_passphrase(&dir, &encrypted, Some("hunter2"), false).unwrap();
+            assert_eq!(cache.stats().unwrap().entries, 1);
+        }
+        {
+            let cache = MessageCache::open_with_passphrase(&dir, &encrypted, Some("wrong"), true).unwrap();
+            assert_eq!(cache.stats().unwrap().entries, 0);
+            cache.cache_messages(&contact("Phil"), &all, &[message("hi")]).unwrap();
+            cache.db.flush().unwrap();
+        }
+        {
+            let cache = MessageCache::open_with_passphrase(&dir, &CacheConfig::default(), None, false).unwrap();
+            assert_eq!(cache.stats().unwrap().entries, 0);
+            cache.cache_messages(&contact("Phil"), &all, &[message("hi")]).unwrap();
+            cache.db.flush().unwrap();
+        }
+
+        assert!(MessageCache::open_with_passphrase(&dir, &encrypted, None, false).is_err());
+
+        // Disabling the cache empties it and stops caching
+        {
+            let disabled = CacheConfig { enabled: false, ..CacheConfig::default() };
+            let cache = MessageCache::open_with_passphrase(&dir, &disabled, None, false).unwrap();
+            assert_eq!(cache.stats().unwrap().entries, 0);
+            cache.cache_messages(&contact("Phil"), &all, &[message("hi")]).unwrap();
+            assert!(cache.get_cached_messages(&contact("Phil"), &all).unwrap().is_none());
+            assert_eq!(cache.stats().unwrap().entries, 0);
+            cache.db.flush().unwrap();
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::Write;
+
+use anyhow::Result;
+use chrono::Utc;
+
+use crate::file_writer::{write_file, WritePolicy, WrittenFile};
+use crate::models::Message;
+use crate::style::ExportStyle;
+
+/// Timestamps in canonical exports: UTC, so the text doesn't change with the machine's time zone
+/// or daylight saving
+pub const CANONICAL_TIMESTAMP: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Put messages in canonical order and normalize their line endings. Messages sent in the same
+/// second are ordered by ID, then sender and text, so the order never depends on how they were
+/// fetched.
+pub fn canonicalize(messages: &mut [Message]) {
+    for message in messages.iter_mut() {
+        if message.content.contains('\r') {
+            message.content = message.content.replace("\r\n", "\n").replace('\r', "\n");
+        }
+    }
+
+    messages.sort_by(|a, b| {
+        a.timestamp
+            .timestamp()
+            .cmp(&b.timestamp.timestamp())
+            .then_with(|| a.imessage_id.cmp(&b.imessage_id))
+            .then_with(|| a.sender.cmp(&b.sender))
+            .then_with(|| a.content.cmp(&b.content))
+    });
+}
+
+/// Format one message as a canonical line. Lines after the first in a multi-line message are
+/// indented by two spaces, and trailing whitespace is dropped so editors don't add noise to diffs.
+fn format_canonical_message(message: &Message, style: &ExportStyle) -> String {
+    let id = match (&message.imessage_id, style.include_ids) {
+        (Some(id), true) => format!(" [id:{}]", id),
+        _ => String::new(),
+    };
+
+    let mut lines = message.content.split('\n');
+    let mut text = format!(
+        "{}{} {}: {}",
+        message.timestamp.with_timezone(&Utc).format(CANONICAL_TIMESTAMP),
+        id,
+        message.sender,
+        lines.next().unwrap_or_default()
+    )
+    .trim_end()
+    .to_string();
+    text.push('\n');
+
+    for line in lines {
+        let line = line.trim_end();
+        if !line.is_empty() {
+            text.push_str("  ");
+            text.push_str(line);
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Write messages in the canonical layout to `writer`: one line per message in canonical order,
+/// with `\n` line endings and no headers, chunk boundaries or totals
+pub(crate) fn write_canonical(writer: &mut impl Write, messages: &[Message], style: &ExportStyle) -> Result<()> {
+    let mut messages = messages.to_vec();
+    canonicalize(&mut messages);
+
+    for message in &messages {
+        writer.write_all(format_canonical_message(message, style).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Write a conversation to `conversation.txt` in the canonical layout. The same messages always
+/// produce the same bytes, so exports stored in git diff cleanly from one month to the next.
+pub fn write_canonical_file(
+    messages: &[Message],
+    style: &ExportStyle,
+    output_dir: &str,
+    policy: WritePolicy,
+) -> Result<WrittenFile> {
+    if policy == WritePolicy::Append {
+        return Err(anyhow::anyhow!("Canonical exports can't be appended to; use --overwrite or --skip-existing"));
+    }
+
+    let file_path = format!("{}/conversation.txt", output_dir);
+    write_file(&file_path, messages.len(), policy, |writer, _| write_canonical(writer, messages, style))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(id: &str, sender: &str, second: u32, content: &str) -> Message {
+        Message {
+            sender: sender.to_string(),
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 20, 17, 0, second).unwrap().with_timezone(&Local),
+            content: content.to_string(),
+            source: None,
+            imessage_id: Some(id.to_string()),
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    #[test]
+    fn test_canonical_output_is_stable() {
+        let messages = vec![
+            message("b", "Phil", 5, "Second \r\nline two  \r\n\r\nline four"),
+            message("a", "Jess", 5, "First"),
+            message("c", "Phil", 1, "Earliest "),
+        ];
+        let mut reversed = messages.clone();
+        reversed.reverse();
+
+        let render = |messages: &[Message], style: &ExportStyle| {
+            let mut output = Vec::new();
+            write_canonical(&mut output, messages, style).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let style = ExportStyle::default();
+        let text = render(&messages, &style);
+        assert_eq!(
+            text,
+            "2025-01-20T17:00:01Z Phil: Earliest\n\
+             2025-01-20T17:00:05Z Jess: First\n\
+             2025-01-20T17:00:05Z Phil: Second\n  line two\n\n  line four\n"
+        );
+        assert_eq!(render(&reversed, &style), text);
+
+        let with_ids = render(&messages, &ExportStyle::default().with_ids(true));
+        assert!(with_ids.starts_with("2025-01-20T17:00:01Z [id:c] Phil: Earliest\n"));
+
+        let error = write_canonical_file(&messages, &style, "unused", WritePolicy::Append).unwrap_err();
+        assert!(error.to_string().contains("can't be appended"));
+    }
+}
@@ -0,0 +1,202 @@
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::config::ChatDbConfig;
+use crate::error::TxtHistoryError;
+
+/// Where Messages.app keeps chat.db for the current user
+#[cfg(target_os = "macos")]
+pub fn default_chat_db_path() -> Result<PathBuf> {
+    imessage_database::util::dirs::get_imessage_chat_db_path().context("Failed to locate iMessage database")
+}
+
+/// Messages.app only exists on macOS; elsewhere chat.db has to be copied over from a Mac
+#[cfg(not(target_os = "macos"))]
+pub fn default_chat_db_path() -> Result<PathBuf> {
+    Err(TxtHistoryError::ChatDbNotFound(None).into())
+}
+
+/// Retries operations on chat.db that fail because Messages.app holds a lock
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for every following retry
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::from_config(&ChatDbConfig::default())
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &ChatDbConfig) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            initial_backoff: Duration::from_millis(config.initial_backoff_ms),
+        }
+    }
+
+    /// Delay before retry number `attempt` (starting at 0)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff.saturating_mul(2u32.saturating_pow(attempt))
+    }
+
+    /// Run a blocking operation, retrying while the database is busy
+    pub fn run<T>(&self, mut operation: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match operation() {
+                Err(e) if attempt < self.max_retries && is_busy_error(&e) => {
+                    let delay = self.backoff(attempt);
+                    eprintln!("chat.db is busy, retrying in {:?} ({}/{})", delay, attempt + 1, self.max_retries);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(e) if is_busy_error(&e) => return Err(e.context(TxtHistoryError::ChatDbLocked)),
+                result => return result,
+            }
+        }
+    }
+
+    /// Run an async operation, retrying while the database is busy
+    pub async fn run_async<T, F, Fut>(&self, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Err(e) if attempt < self.max_retries && is_busy_error(&e) => {
+                    let delay = self.backoff(attempt);
+                    eprintln!("chat.db is busy, retrying in {:?} ({}/{})", delay, attempt + 1, self.max_retries);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) if is_busy_error(&e) => return Err(e.context(TxtHistoryError::ChatDbLocked)),
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether an error was caused by SQLite lock contention (SQLITE_BUSY / SQLITE_LOCKED)
+pub fn is_busy_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(rusqlite::Error::SqliteFailure(e, _)) = cause.downcast_ref::<rusqlite::Error>() {
+            return matches!(
+                e.code,
+                rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+            );
+        }
+
+        // imessage_database errors only reach us as text
+        let message = cause.to_string().to_lowercase();
+        message.contains("database is locked") || message.contains("sqlite_busy")
+    })
+}
+
+/// A private copy of chat.db (with its WAL and shared memory files) that Messages.app
+/// can't lock while we read it. The copy is deleted when dropped.
+pub struct ChatDbSnapshot {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl ChatDbSnapshot {
+    /// Copy chat.db to a temporary directory, retrying if a file is mid-write
+    pub fn create(chat_db_path: &Path, retry: &RetryPolicy) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("txt-history-chatdb-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let file_name = chat_db_path
+            .file_name()
+            .context("chat.db path has no file name")?;
+        let path = dir.join(file_name);
+
+        // WAL mode keeps recent writes in -wal; copy it too so the snapshot is current
+        for suffix in ["", "-wal", "-shm"] {
+            let source = PathBuf::from(format!("{}{}", chat_db_path.display(), suffix));
+            if !source.exists() {
+                continue;
+            }
+            let target = PathBuf::from(format!("{}{}", path.display(), suffix));
+            retry.run(|| {
+                fs::copy(&source, &target)
+                    .with_context(|| format!("Failed to copy {}", source.display()))
+            })?;
+        }
+
+        Ok(Self { dir, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ChatDbSnapshot {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            eprintln!("Failed to remove chat.db snapshot {}: {}", self.dir.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_retries_only_busy_errors() {
+        let policy = RetryPolicy { max_retries: 3, initial_backoff: Duration::from_millis(1) };
+        assert_eq!(policy.backoff(2), Duration::from_millis(4));
+
+        let attempts = Cell::new(0);
+        let result = policy.run(|| {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 {
+                Err(anyhow::anyhow!("Failed to get messages: database is locked"))
+            } else {
+                Ok(attempts.get())
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let attempts = Cell::new(0);
+        let result: Result<()> = policy.run(|| {
+            attempts.set(attempts.get() + 1);
+            Err(anyhow::anyhow!("no such table: message"))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn test_explicit_chat_db_path() {
+        let path = std::env::temp_dir().join("txt_history_chat_db_path_test.db");
+        fs::write(&path, b"").unwrap();
+
+        let config = ChatDbConfig { path: Some(path.clone()), ..ChatDbConfig::default() };
+        assert_eq!(config.chat_db_path().unwrap(), path);
+
+        let missing = ChatDbConfig { path: Some(path.with_extension("missing")), ..ChatDbConfig::default() };
+        assert!(missing.chat_db_path().is_err());
+
+        if !cfg!(target_os = "macos") {
+            let error = ChatDbConfig::default().chat_db_path().unwrap_err();
+            assert!(error.to_string().contains("--chat-db"));
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+}
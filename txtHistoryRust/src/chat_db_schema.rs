@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::error::TxtHistoryError;
+
+/// Tables every import and discovery query reads
+const REQUIRED_TABLES: &[&str] =
+    &["message", "handle", "chat", "chat_message_join", "chat_handle_join", "attachment", "message_attachment_join"];
+
+/// `message` columns every import reads; others are optional and replaced when missing
+const REQUIRED_MESSAGE_COLUMNS: &[&str] = &["guid", "text", "handle_id", "date", "is_from_me", "service"];
+
+/// The macOS releases whose chat.db layouts differ in the columns this tool reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaVersion {
+    /// Catalina and earlier: no inline replies or edits
+    Catalina,
+    /// Big Sur and Monterey: inline replies (`thread_originator_guid`)
+    Monterey,
+    /// Ventura, Sonoma and later: edited and unsent messages (`date_edited`)
+    Ventura,
+}
+
+impl SchemaVersion {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Catalina => "Catalina or earlier",
+            Self::Monterey => "Big Sur or Monterey",
+            Self::Ventura => "Ventura or later",
+        }
+    }
+}
+
+/// What a chat.db can hold, depending on the macOS release that wrote it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchemaCapabilities {
+    /// Text may only be in the typedstream `attributedBody`, as newer releases often store it
+    pub attributed_body: bool,
+    /// Inline replies point at the message they answer
+    pub replies: bool,
+    /// Messages can be edited or unsent after they're sent
+    pub edited_messages: bool,
+}
+
+/// A chat.db's detected layout, for building queries that work on every supported release
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatDbSchema {
+    pub version: SchemaVersion,
+    pub capabilities: SchemaCapabilities,
+    message_columns: HashSet<String>,
+}
+
+impl ChatDbSchema {
+    /// Detect the layout from the tables and `message` columns, failing with
+    /// `TxtHistoryError::UnsupportedChatDb` when something every query needs is missing
+    pub fn detect(conn: &Connection) -> Result<Self> {
+        let tables = names(conn, "SELECT name FROM sqlite_master WHERE type = 'table'")?;
+        let missing: Vec<&str> = REQUIRED_TABLES.iter().copied().filter(|table| !tables.contains(*table)).collect();
+        if !missing.is_empty() {
+            return Err(TxtHistoryError::UnsupportedChatDb(format!("no {} table", missing.join(", "))).into());
+        }
+
+        let message_columns = names(conn, "SELECT name FROM pragma_table_info('message')")?;
+        let missing: Vec<&str> =
+            REQUIRED_MESSAGE_COLUMNS.iter().copied().filter(|column| !message_columns.contains(*column)).collect();
+        if !missing.is_empty() {
+            return Err(TxtHistoryError::UnsupportedChatDb(format!(
+                "the message table has no {} column",
+                missing.join(", ")
+            ))
+            .into());
+        }
+
+        let capabilities = SchemaCapabilities {
+            attributed_body: message_columns.contains("attributedBody"),
+            replies: message_columns.contains("thread_originator_guid"),
+            edited_messages: message_columns.contains("date_edited"),
+        };
+        let version = if capabilities.edited_messages {
+            SchemaVersion::Ventura
+        } else if capabilities.replies {
+            SchemaVersion::Monterey
+        } else {
+            SchemaVersion::Catalina
+        };
+
+        Ok(Self { version, capabilities, message_columns })
+    }
+
+    pub fn has_message_column(&self, column: &str) -> bool {
+        self.message_columns.contains(column)
+    }
+
+    /// `m.<column>` when the `message` table has the column, or `fallback` (a SQL literal) for
+    /// releases without it, so one query works across them
+    pub fn message_column(&self, column: &str, fallback: &str) -> String {
+        if self.has_message_column(column) {
+            format!("m.{}", column)
+        } else {
+            fallback.to_string()
+        }
+    }
+
+    /// e.g. "Ventura or later (attributedBody, replies, edited messages)"
+    pub fn describe(&self) -> String {
+        let capabilities: Vec<&str> = [
+            (self.capabilities.attributed_body, "attributedBody"),
+            (self.capabilities.replies, "replies"),
+            (self.capabilities.edited_messages, "edited messages"),
+        ]
+        .into_iter()
+        .filter_map(|(supported, name)| supported.then_some(name))
+        .collect();
+
+        if capabilities.is_empty() {
+            self.version.as_str().to_string()
+        } else {
+            format!("{} ({})", self.version.as_str(), capabilities.join(", "))
+        }
+    }
+}
+
+/// The first column of every row a query returns
+fn names(conn: &Connection, sql: &str) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::exit_code;
+
+    /// A chat.db with the required tables and the given extra `message` columns
+    fn chat_db(extra_columns: &str) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!(
+            "CREATE TABLE handle (ROWID INTEGER PRIMARY KEY, id TEXT, service TEXT);
+             CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, chat_identifier TEXT);
+             CREATE TABLE chat_handle_join (chat_id INTEGER, handle_id INTEGER);
+             CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+             CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, filename TEXT);
+             CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+             CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT, handle_id INTEGER,
+                 date INTEGER, is_from_me INTEGER, service TEXT{});",
+            extra_columns
+        ))
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_detect_schema_versions() {
+        let catalina = ChatDbSchema::detect(&chat_db(", attributedBody BLOB")).unwrap();
+        assert_eq!(catalina.version, SchemaVersion::Catalina);
+        assert!(catalina.capabilities.attributed_body && !catalina.capabilities.replies);
+        assert_eq!(catalina.describe(), "Catalina or earlier (attributedBody)");
+
+        let monterey = ChatDbSchema::detect(&chat_db(", attributedBody BLOB, thread_originator_guid TEXT")).unwrap();
+        assert_eq!(monterey.version, SchemaVersion::Monterey);
+
+        let ventura = ChatDbSchema::detect(&chat_db(
+            ", attributedBody BLOB, thread_originator_guid TEXT, date_edited INTEGER, balloon_bundle_id TEXT",
+        ))
+        .unwrap();
+        assert_eq!(ventura.version, SchemaVersion::Ventura);
+        assert_eq!(ventura.describe(), "Ventura or later (attributedBody, replies, edited messages)");
+
+        // Missing optional columns are swapped for a fallback
+        assert_eq!(ventura.message_column("balloon_bundle_id", "NULL"), "m.balloon_bundle_id");
+        assert_eq!(catalina.message_column("balloon_bundle_id", "NULL"), "NULL");
+        assert!(!catalina.has_message_column("date_edited"));
+
+        let bare = ChatDbSchema::detect(&chat_db("")).unwrap();
+        assert_eq!(bare.describe(), "Catalina or earlier");
+    }
+
+    #[test]
+    fn test_unsupported_chat_db() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT);").unwrap();
+        let error = ChatDbSchema::detect(&conn).unwrap_err();
+        assert_eq!(exit_code(&error), 9);
+        assert!(error.to_string().contains("no handle, chat, chat_message_join"));
+
+        let conn = chat_db("");
+        conn.execute_batch("DROP TABLE message; CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT);").unwrap();
+        let error = ChatDbSchema::detect(&conn).unwrap_err();
+        assert!(error.to_string().contains("the message table has no text, handle_id, date, is_from_me, service column"));
+    }
+}
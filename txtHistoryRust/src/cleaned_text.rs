@@ -0,0 +1,184 @@
+use std::ops::Range;
+
+use regex::Regex;
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// Text put through cleaning steps that remembers, for each of its bytes, the range of the
+/// original text it came from. Positions found in the cleaned text, like entity offsets, can
+/// then be mapped back to the message as it was sent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CleanedText {
+    text: String,
+    /// Byte range of the original text behind each byte of `text`
+    origins: Vec<Range<usize>>,
+}
+
+impl CleanedText {
+    pub fn new(original: &str) -> Self {
+        let mut cleaned = Self::default();
+        for (start, c) in original.char_indices() {
+            cleaned.push(c, start..start + c.len_utf8());
+        }
+        cleaned
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn into_string(self) -> String {
+        self.text
+    }
+
+    /// The range of the original text that bytes `range` of the cleaned text came from, or None
+    /// when it's empty or out of bounds
+    pub fn original_range(&self, range: Range<usize>) -> Option<Range<usize>> {
+        if range.is_empty() || range.end > self.text.len() {
+            return None;
+        }
+        Some(self.origins[range.start].start..self.origins[range.end - 1].end)
+    }
+
+    /// Unicode NFC. Each character is composed together with the combining marks that follow
+    /// it, and the result covers all of them in the original text.
+    pub fn nfc(self) -> Self {
+        if is_nfc(&self.text) {
+            return self;
+        }
+
+        let mut normalized = Self::default();
+        let chars: Vec<(usize, char)> = self.text.char_indices().collect();
+        let mut group_start = 0;
+        for (i, &(start, c)) in chars.iter().enumerate() {
+            let next_is_starter = chars.get(i + 1).map_or(true, |&(_, next)| canonical_combining_class(next) == 0);
+            if next_is_starter {
+                let end = start + c.len_utf8();
+                let origin = self.span(group_start..end);
+                for composed in self.text[group_start..end].nfc() {
+                    normalized.push(composed, origin.clone());
+                }
+                group_start = end;
+            }
+        }
+        normalized
+    }
+
+    /// Every match of `regex` replaced with `replacement`, which covers the whole match in the
+    /// original text. Empty matches are left alone.
+    pub fn replace_all(self, regex: &Regex, replacement: &str) -> Self {
+        let mut replaced = Self::default();
+        let mut copied = 0;
+        for found in regex.find_iter(&self.text).filter(|found| !found.is_empty()) {
+            replaced.copy(&self, copied..found.start());
+            let origin = self.span(found.range());
+            for c in replacement.chars() {
+                replaced.push(c, origin.clone());
+            }
+            copied = found.end();
+        }
+        replaced.copy(&self, copied..self.text.len());
+        replaced
+    }
+
+    /// Without leading and trailing whitespace
+    pub fn trim(self) -> Self {
+        let start = self.text.len() - self.text.trim_start().len();
+        let end = start + self.text.trim().len();
+        let mut trimmed = Self::default();
+        trimmed.copy(&self, start..end);
+        trimmed
+    }
+
+    /// Lowercased character by character, so a character that lowercases to several keeps
+    /// pointing at the one it came from
+    pub fn to_lowercase(&self) -> Self {
+        let mut lowercased = Self::default();
+        for (start, c) in self.text.char_indices() {
+            for lower in c.to_lowercase() {
+                lowercased.push(lower, self.origins[start].clone());
+            }
+        }
+        lowercased
+    }
+
+    fn push(&mut self, c: char, origin: Range<usize>) {
+        let mut buffer = [0; 4];
+        self.text.push_str(c.encode_utf8(&mut buffer));
+        self.origins.extend(std::iter::repeat(origin).take(c.len_utf8()));
+    }
+
+    fn copy(&mut self, from: &Self, range: Range<usize>) {
+        self.text.push_str(&from.text[range.clone()]);
+        self.origins.extend_from_slice(&from.origins[range]);
+    }
+
+    /// Original range covered by a non-empty range of the current text
+    fn span(&self, range: Range<usize>) -> Range<usize> {
+        self.origins[range.start].start..self.origins[range.end - 1].end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cleaned_text_offsets() {
+        let original = "Hey, PHIL!  see https://example.com café";
+        let cleaned = CleanedText::new(original)
+            .replace_all(&Regex::new(r"https?://\S+").unwrap(), " ")
+            .replace_all(&Regex::new(r"[^\w\s]").unwrap(), " ")
+            .replace_all(&Regex::new(r"\s+").unwrap(), " ")
+            .trim()
+            .to_lowercase();
+        assert_eq!(cleaned.as_str(), "hey phil see café");
+
+        // "phil" in the cleaned text is "PHIL" in the original
+        let start = cleaned.as_str().find("phil").unwrap();
+        let range = cleaned.original_range(start..start + 4).unwrap();
+        assert_eq!(&original[range], "PHIL");
+
+        // Multi-byte characters map to the whole character
+        let start = cleaned.as_str().find("café").unwrap();
+        let range = cleaned.original_range(start.."hey phil see café".len()).unwrap();
+        assert_eq!(&original[range], "café");
+
+        // A collapsed space covers everything it replaced
+        let range = cleaned.original_range(8..9).unwrap();
+        assert_eq!(&original[range], "!  ");
+
+        assert_eq!(cleaned.original_range(3..3), None);
+        assert_eq!(cleaned.original_range(0..100), None);
+    }
+
+    #[test]
+    fn test_nfc_offsets() {
+        // "e" followed by a combining acute accent composes into one character
+        let original = "cafe\u{301} ok";
+        let cleaned = CleanedText::new(original).nfc();
+        assert_eq!(cleaned.as_str(), "caf\u{e9} ok");
+
+        let range = cleaned.original_range(3..5).unwrap();
+        assert_eq!(&original[range], "e\u{301}");
+        assert_eq!(&original[cleaned.original_range(6..8).unwrap()], "ok");
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_original_ranges_are_in_bounds(text in "\\PC{0,100}") {
+            let cleaned = CleanedText::new(&text)
+                .nfc()
+                .replace_all(&Regex::new(r"[^\w\s]").unwrap(), " ")
+                .replace_all(&Regex::new(r"\s+").unwrap(), " ")
+                .trim()
+                .to_lowercase();
+
+            for (start, c) in cleaned.as_str().char_indices() {
+                let range = cleaned.original_range(start..start + c.len_utf8()).unwrap();
+                proptest::prop_assert!(range.end <= text.len());
+                proptest::prop_assert!(text.is_char_boundary(range.start) && text.is_char_boundary(range.end));
+            }
+        }
+    }
+}
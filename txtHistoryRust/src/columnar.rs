@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, BooleanArray, Float32Array, StringArray, TimestampMicrosecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::file_writer::{write_file, write_in_parallel, Chunking, CsvOptions, WritePolicy, WrittenFile};
+use crate::models::{DbProcessedMessage, Message, OutputFormat};
+use crate::style::ExportStyle;
+use crate::verbosity::progress;
+
+/// NLP results to join into a Parquet export, keyed by the message's `imessage_id`
+pub type NlpLookup = HashMap<String, DbProcessedMessage>;
+
+/// Write messages to a Parquet file with typed columns: UTC timestamps, a boolean
+/// `is_from_me` and nullable source/service/ID strings. With `nlp`, the processed text,
+/// lemmas, entities (JSON), sentiment and flag severity are added as nullable columns.
+/// Parquet files can't be appended to, so `WritePolicy::Append` is an error.
+pub fn write_parquet_file(
+    messages: &[Message],
+    file_path: &str,
+    nlp: Option<&NlpLookup>,
+    policy: WritePolicy,
+) -> Result<WrittenFile> {
+    if policy == WritePolicy::Append {
+        return Err(anyhow::anyhow!("Parquet files can't be appended to; use --overwrite or --skip-existing"));
+    }
+
+    write_file(file_path, messages.len(), policy, |file, _| write_parquet(file, messages, nlp))
+}
+
+/// Write messages as a single-batch, Snappy-compressed Parquet file to `writer`
+pub(crate) fn write_parquet<W: Write + Send>(writer: W, messages: &[Message], nlp: Option<&NlpLookup>) -> Result<()> {
+    let mut fields = vec![
+        Field::new("imessage_id", DataType::Utf8, true),
+        Field::new("sender", DataType::Utf8, false),
+        Field::new("timestamp", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
+        Field::new("content", DataType::Utf8, false),
+        Field::new("is_from_me", DataType::Boolean, false),
+        Field::new("service", DataType::Utf8, true),
+        Field::new("source", DataType::Utf8, true),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter(messages.iter().map(|m| m.imessage_id.as_deref()))),
+        Arc::new(StringArray::from_iter_values(messages.iter().map(|m| m.sender.as_str()))),
+        Arc::new(
+            TimestampMicrosecondArray::from_iter_values(messages.iter().map(|m| m.timestamp.timestamp_micros()))
+                .with_timezone("UTC"),
+        ),
+        Arc::new(StringArray::from_iter_values(messages.iter().map(|m| m.content.as_str()))),
+        Arc::new(BooleanArray::from_iter(messages.iter().map(|m| Some(m.is_from_me)))),
+        Arc::new(StringArray::from_iter(messages.iter().map(|m| m.service.as_deref()))),
+        Arc::new(StringArray::from_iter(messages.iter().map(|m| m.source.as_deref()))),
+    ];
+
+    if let Some(nlp) = nlp {
+        let processed: Vec<Option<&DbProcessedMessage>> = messages
+            .iter()
+            .map(|m| m.imessage_id.as_ref().and_then(|id| nlp.get(id)))
+            .collect();
+        let text = |value: fn(&DbProcessedMessage) -> Option<&str>| -> ArrayRef {
+            Arc::new(StringArray::from_iter(processed.iter().map(|p| p.and_then(value))))
+        };
+        let number = |value: fn(&DbProcessedMessage) -> Option<f32>| -> ArrayRef {
+            Arc::new(Float32Array::from_iter(processed.iter().map(|p| p.and_then(value))))
+        };
+
+        fields.extend([
+            Field::new("processing_version", DataType::Utf8, true),
+            Field::new("processed_text", DataType::Utf8, true),
+            Field::new("lemmatized_text", DataType::Utf8, true),
+            Field::new("named_entities", DataType::Utf8, true),
+            Field::new("sentiment_score", DataType::Float32, true),
+            Field::new("flag_severity", DataType::Float32, true),
+        ]);
+        columns.extend([
+            text(|p| Some(p.processing_version.as_str())),
+            text(|p| Some(p.processed_text.as_str())),
+            text(|p| p.lemmatized_text.as_deref()),
+            text(|p| p.named_entities.as_deref()),
+            number(|p| p.sentiment_score),
+            number(|p| p.flag_severity),
+        ]);
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, schema, Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+/// Write messages to `chunk_N.parquet` files with NLP results joined in, up to
+/// `parallel_writes` at a time
+pub fn write_parquet_files(
+    messages: &[Message],
+    nlp: &NlpLookup,
+    chunking: Chunking,
+    output_dir: &str,
+    policy: WritePolicy,
+    parallel_writes: usize,
+) -> Result<Vec<WrittenFile>> {
+    if messages.is_empty() {
+        progress("No messages to write");
+        return Ok(Vec::new());
+    }
+
+    let chunks = chunking.split(messages, OutputFormat::Parquet, &CsvOptions::default(), &ExportStyle::default())?;
+    progress(format!("Writing {} chunks", chunks.len()));
+
+    write_in_parallel(&chunks, parallel_writes, |i, chunk| {
+        let started = Instant::now();
+        let file_path = format!("{}/chunk_{}.parquet", output_dir, i + 1);
+        let file = write_parquet_file(chunk, &file_path, Some(nlp), policy)?;
+        file.report(chunk, Some(started.elapsed()));
+        Ok(file)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    use arrow::array::Array;
+    use chrono::{Local, NaiveDateTime, TimeZone};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn message(id: &str, content: &str, is_from_me: bool) -> Message {
+        Message {
+            sender: if is_from_me { "Jess" } else { "Phil" }.to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: Some("iMessage".to_string()),
+            imessage_id: Some(id.to_string()),
+            service: None,
+            is_from_me,
+        }
+    }
+
+    #[test]
+    fn test_write_parquet_with_nlp() {
+        let path = std::env::temp_dir().join(format!("txt_history_parquet_test_{}.parquet", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let messages = vec![message("guid-1", "Great game today", false), message("guid-2", "ok", true)];
+
+        let mut nlp = NlpLookup::new();
+        nlp.insert(
+            "guid-1".to_string(),
+            DbProcessedMessage {
+                id: 1,
+                original_message_id: 1,
+                processed_text: "great game today".to_string(),
+                tokens: None,
+                lemmatized_text: Some("great game today".to_string()),
+                named_entities: None,
+                sentiment_score: Some(0.8),
+                processed_at: NaiveDateTime::default(),
+                processing_version: "v1.0".to_string(),
+                flags: None,
+                flag_severity: None,
+                language: None,
+                sentence_sentiments: None,
+            },
+        );
+        write_parquet_file(&messages, &path, Some(&nlp), WritePolicy::Overwrite).unwrap();
+        assert!(write_parquet_file(&messages, &path, None, WritePolicy::Append).is_err());
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|b| b.unwrap()).collect();
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.num_columns(), 13);
+        assert!(matches!(batch.schema().field(2).data_type(), DataType::Timestamp(TimeUnit::Microsecond, _)));
+
+        let sentiment = batch
+            .column_by_name("sentiment_score")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float32Array>()
+            .unwrap();
+        assert_eq!(sentiment.value(0), 0.8);
+        assert!(sentiment.is_null(1));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
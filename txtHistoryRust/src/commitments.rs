@@ -0,0 +1,190 @@
+use chrono::Duration;
+use regex::Regex;
+
+use crate::models::{CommitmentKind, DbMessage, NewCommitment};
+
+/// Longest snippet stored for a detected sentence
+const MAX_SNIPPET_CHARS: usize = 200;
+
+/// Rule-based detection of questions, commitments and agreements in a conversation
+pub struct CommitmentExtractor {
+    interrogative_start: Regex,
+    commitment: Regex,
+    agreement: Regex,
+    /// How long the other side has to reply before a question counts as unanswered
+    answer_window: Duration,
+}
+
+impl Default for CommitmentExtractor {
+    fn default() -> Self {
+        Self::new(Duration::hours(24))
+    }
+}
+
+impl CommitmentExtractor {
+    pub fn new(answer_window: Duration) -> Self {
+        Self {
+            interrogative_start: Regex::new(
+                r"(?i)^(can|could|will|would|are|is|do|does|did|should|when|where|what|who|why|how|which)\b",
+            )
+            .unwrap(),
+            commitment: Regex::new(
+                r"(?i)\b(i'll|i will|i'm going to|im going to|i am going to|i promise|we'll|we will|let me)\b",
+            )
+            .unwrap(),
+            agreement: Regex::new(
+                r"(?i)^(ok|okay|k|sure|yes|yeah|yep|deal|agreed|sounds good|that works|fine|will do|no problem|of course)\b",
+            )
+            .unwrap(),
+            answer_window,
+        }
+    }
+
+    /// Questions, commitments and agreements in one message's text, one per matching sentence
+    pub fn detect(&self, text: &str) -> Vec<(CommitmentKind, String)> {
+        // iMessage uses typographic apostrophes ("I’ll")
+        let text = text.replace('\u{2019}', "'");
+        let mut found = Vec::new();
+
+        for sentence in sentences(&text) {
+            let snippet: String = sentence.chars().take(MAX_SNIPPET_CHARS).collect();
+            let unterminated = !sentence.ends_with(['.', '!']);
+
+            let is_agreement = self.agreement.is_match(sentence);
+            if sentence.ends_with('?')
+                || (!is_agreement
+                    && unterminated
+                    && sentence.split_whitespace().count() >= 3
+                    && self.interrogative_start.is_match(sentence))
+            {
+                found.push((CommitmentKind::Question, snippet));
+                continue;
+            }
+
+            if is_agreement {
+                found.push((CommitmentKind::Agreement, snippet.clone()));
+            }
+            if self.commitment.is_match(sentence) {
+                found.push((CommitmentKind::Commitment, snippet));
+            }
+        }
+
+        found
+    }
+
+    /// Detect references in one conversation's messages (sorted oldest first), linking each
+    /// question to the first reply from the other side within the answer window
+    pub fn extract(&self, messages: &[DbMessage]) -> Vec<NewCommitment> {
+        let mut commitments = Vec::new();
+
+        for (i, message) in messages.iter().enumerate() {
+            let Some(text) = &message.text else {
+                continue;
+            };
+
+            for (kind, snippet) in self.detect(text) {
+                let answered_by = match kind {
+                    CommitmentKind::Question => messages[i + 1..]
+                        .iter()
+                        .take_while(|reply| reply.date_created - message.date_created <= self.answer_window)
+                        .find(|reply| reply.is_from_me != message.is_from_me)
+                        .map(|reply| reply.id),
+                    _ => None,
+                };
+
+                commitments.push(NewCommitment {
+                    message_id: message.id,
+                    kind,
+                    snippet,
+                    answered_by,
+                });
+            }
+        }
+
+        commitments
+    }
+}
+
+/// Split text into trimmed sentences, keeping each sentence's closing punctuation
+pub(crate) fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        if matches!(c, '.' | '!' | '?' | '\n') {
+            let end = i + c.len_utf8();
+            sentences.push(text[start..end].trim_end_matches('\n').trim());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim());
+
+    sentences.into_iter().filter(|s| s.chars().any(char::is_alphanumeric)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MessageKind;
+    use chrono::NaiveDateTime;
+
+    fn message(id: i32, is_from_me: bool, hour: u32, text: &str) -> DbMessage {
+        let date = NaiveDateTime::parse_from_str(&format!("2025-01-20 {:02}:00:00", hour), "%Y-%m-%d %H:%M:%S").unwrap();
+        DbMessage {
+            id,
+            imessage_id: format!("guid-{}", id),
+            text: Some(text.to_string()),
+            sender: if is_from_me { "Jess" } else { "Phil" }.to_string(),
+            is_from_me,
+            date_created: date,
+            date_imported: date,
+            handle_id: None,
+            service: None,
+            thread_id: None,
+            has_attachments: false,
+            contact_id: None,
+            reply_to: None,
+            message_kind: MessageKind::Text,
+            hidden: false,
+            hidden_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let extractor = CommitmentExtractor::default();
+        let kinds = |text: &str| extractor.detect(text).into_iter().map(|(kind, _)| kind).collect::<Vec<_>>();
+
+        assert_eq!(kinds("Can you pick her up?"), vec![CommitmentKind::Question]);
+        assert_eq!(kinds("can you pick her up at 5"), vec![CommitmentKind::Question]);
+        assert_eq!(kinds("I’ll take her Friday."), vec![CommitmentKind::Commitment]);
+        assert_eq!(
+            kinds("Ok, I'll bring the forms. When is the hearing?"),
+            vec![CommitmentKind::Agreement, CommitmentKind::Commitment, CommitmentKind::Question]
+        );
+        assert_eq!(kinds("Will do"), vec![CommitmentKind::Agreement]);
+        assert!(kinds("The game was fun. She scored twice!").is_empty());
+    }
+
+    #[test]
+    fn test_questions_link_to_replies() {
+        let messages = vec![
+            message(1, false, 9, "Can you pick her up Friday?"),
+            message(2, false, 10, "Also the dentist"),
+            message(3, true, 12, "Yes, I'll get her at 3."),
+            message(4, true, 13, "Did you sign the form?"),
+        ];
+        let commitments = CommitmentExtractor::default().extract(&messages);
+
+        let question = &commitments[0];
+        assert_eq!(question.kind, CommitmentKind::Question);
+        assert_eq!(question.answered_by, Some(3));
+
+        assert!(commitments.iter().any(|c| c.message_id == 3 && c.kind == CommitmentKind::Commitment));
+
+        // Nobody replied to the last question
+        let last = commitments.last().unwrap();
+        assert_eq!(last.message_id, 4);
+        assert_eq!(last.answered_by, None);
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::entities::EntityPattern;
+use crate::error::TxtHistoryError;
+use crate::flags::FlagConfig;
+use crate::import_filter::ImportFilterConfig;
+use crate::pipeline::PipelineConfig;
+use crate::sender_map::SenderMapping;
+use crate::style::ExportStyle;
+use crate::summarizer::SummarizerConfig;
+use crate::throttle::ThrottleConfig;
+
+/// Default location of the configuration file
+const DEFAULT_CONFIG_PATH: &str = "data/config.json";
+
+/// Application configuration loaded from a JSON file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Who you are: the name your own messages are shown under
+    pub me: MeConfig,
+
+    /// Recurring export run by `watch` mode
+    pub export_schedule: Option<ExportSchedule>,
+
+    /// Where to report finished or failed long-running operations
+    pub notifications: NotificationConfig,
+
+    /// How to read chat.db while Messages.app is writing to it
+    pub chat_db: ChatDbConfig,
+
+    /// Per-sender sides, colors and TXT prefixes for exports
+    pub export_style: ExportStyle,
+
+    /// How export files are written
+    pub export: ExportConfig,
+
+    /// Word lists and severities for the profanity/abusive-language pass
+    pub language_flags: FlagConfig,
+
+    /// Custom entity types NLP processing finds alongside the built-in ones
+    pub entity_patterns: Vec<EntityPattern>,
+
+    /// Named step chains run with `pipeline run NAME`
+    pub pipelines: BTreeMap<String, PipelineConfig>,
+
+    /// How long messages are kept before `watch` mode purges them
+    pub retention: RetentionConfig,
+
+    /// Size and age limits for the message cache
+    pub cache: CacheConfig,
+
+    /// Conversations `watch` mode notifies about when their recent messages turn negative
+    pub sentiment_alerts: SentimentAlertConfig,
+
+    /// Write limits for `watch` mode's background work
+    pub throttle: ThrottleConfig,
+
+    /// Endpoint `summarize` sends days or sessions to for abstractive summaries
+    pub summarizer: SummarizerConfig,
+
+    /// Whether large imports and purges run `db maintain` afterwards
+    pub maintenance: MaintenanceConfig,
+
+    /// Daily database snapshots taken before imports and purges and by `watch` mode
+    pub snapshots: SnapshotConfig,
+
+    /// Messages imports leave out, e.g. "Ok", emoji-only replies and verification codes
+    pub import_filter: ImportFilterConfig,
+
+    /// Display names from WhatsApp, Telegram and email imports and the contacts they belong to
+    pub sender_map: Vec<SenderMapping>,
+}
+
+/// The user's own name and the phone numbers and emails they send from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeConfig {
+    /// Sender name for messages you sent, in exports and the database
+    pub name: String,
+
+    /// Phone numbers and emails you send from, e.g. "+15555550100" or "me@example.com"
+    pub identifiers: Vec<String>,
+}
+
+impl Default for MeConfig {
+    fn default() -> Self {
+        Self { name: "Me".to_string(), identifiers: Vec::new() }
+    }
+}
+
+impl MeConfig {
+    /// The first phone number among the identifiers
+    pub fn phone(&self) -> Option<&str> {
+        self.identifiers.iter().map(String::as_str).find(|id| !id.contains('@'))
+    }
+
+    /// Every email among the identifiers
+    pub fn emails(&self) -> impl Iterator<Item = &str> {
+        self.identifiers.iter().map(String::as_str).filter(|id| id.contains('@'))
+    }
+}
+
+/// Retry and snapshot settings for reading chat.db
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatDbConfig {
+    /// Retries when chat.db is locked (SQLITE_BUSY)
+    pub max_retries: u32,
+
+    /// Delay before the first retry in milliseconds, doubled on every retry
+    pub initial_backoff_ms: u64,
+
+    /// Copy chat.db to a temporary snapshot before reading it
+    pub snapshot: bool,
+
+    /// chat.db to read instead of ~/Library/Messages/chat.db, e.g. a copy on Linux or Windows.
+    /// `--chat-db` overrides it.
+    pub path: Option<PathBuf>,
+}
+
+impl Default for ChatDbConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff_ms: 250,
+            snapshot: false,
+            path: None,
+        }
+    }
+}
+
+impl ChatDbConfig {
+    /// The chat.db to read: the configured one, or Messages.app's own on macOS
+    pub fn chat_db_path(&self) -> Result<PathBuf> {
+        match &self.path {
+            Some(path) if path.is_file() => Ok(path.clone()),
+            Some(path) => Err(TxtHistoryError::ChatDbNotFound(Some(path.clone())).into()),
+            None => crate::chat_db::default_chat_db_path(),
+        }
+    }
+}
+
+/// How exports write their chunk files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// Chunk files written at the same time; 1 writes them one after another
+    pub parallel_writes: usize,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self { parallel_writes: 4 }
+    }
+}
+
+/// Limits for the message cache. Imports drop the cached messages of the contacts they touch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Whether fetched messages are cached at all; turning it off empties the cache
+    pub enabled: bool,
+
+    /// Encrypt cached entries with a key derived from the `TXT_HISTORY_PASSPHRASE` passphrase
+    pub encrypt: bool,
+
+    /// Largest total size of cached messages in MB; least recently used entries go first
+    pub max_size_mb: u64,
+
+    /// Hours before cached messages are fetched again; 0 keeps them until an import changes them
+    pub ttl_hours: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { enabled: true, encrypt: false, max_size_mb: 100, ttl_hours: 24 }
+    }
+}
+
+/// Maximum message ages enforced once a day by `watch` mode. Nothing is purged automatically
+/// unless a limit is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Delete messages older than this many days
+    pub max_age_days: Option<u32>,
+
+    /// Per-contact limits in days, used instead of `max_age_days` for those contacts
+    pub contacts: BTreeMap<String, u32>,
+}
+
+impl RetentionConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days.is_some() || !self.contacts.is_empty()
+    }
+}
+
+/// Running `db maintain` after imports and purges, including `watch` mode's retention purge.
+/// Nothing runs automatically unless `auto` is on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    pub auto: bool,
+
+    /// Fewest messages an import or purge must touch before maintenance runs
+    pub min_messages: usize,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self { auto: false, min_messages: 10_000 }
+    }
+}
+
+impl MaintenanceConfig {
+    pub fn is_due(&self, messages: usize) -> bool {
+        self.auto && messages >= self.min_messages
+    }
+}
+
+/// Copies of the database taken with SQLite's backup API, once a day at most: by the first
+/// import, restore or purge of the day and by `watch` mode. Older snapshots are rotated out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    pub enabled: bool,
+
+    /// Number of most recent days to keep a snapshot of
+    pub daily: usize,
+
+    /// Number of most recent weeks to keep a snapshot of, counting the daily ones
+    pub weekly: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self { enabled: true, daily: 7, weekly: 4 }
+    }
+}
+
+/// Rolling sentiment checks run by `watch` mode. Nothing is checked unless contacts are listed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SentimentAlertConfig {
+    /// Contacts whose conversations are checked
+    pub contacts: Vec<String>,
+
+    /// Number of most recent text messages averaged
+    pub window: usize,
+
+    /// Notify when the average sentiment (-1.0 to 1.0) drops below this
+    pub threshold: f32,
+
+    /// Processing version the scores are stored under
+    pub version: String,
+}
+
+impl Default for SentimentAlertConfig {
+    fn default() -> Self {
+        Self { contacts: Vec::new(), window: 20, threshold: -0.2, version: "v1.0".to_string() }
+    }
+}
+
+impl SentimentAlertConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.contacts.is_empty()
+    }
+}
+
+/// Notification sinks fired after import, export and NLP runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// URL that receives a JSON POST with the operation report
+    pub webhook_url: Option<String>,
+
+    /// Show a macOS notification center banner
+    pub macos: bool,
+}
+
+/// Rolling monthly export produced automatically by `watch` mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSchedule {
+    /// Cron-like expression (minute hour day-of-month month day-of-week), e.g. "0 3 1 * *"
+    pub cron: String,
+
+    /// Contacts to export on every run
+    pub contacts: Vec<String>,
+
+    /// Directory that receives one `YYYY-MM` folder per run
+    pub target_dir: String,
+
+    /// Output format (txt, csv, json or parquet)
+    #[serde(default = "default_format")]
+    pub format: String,
+
+    /// Number of lines per chunk
+    #[serde(default)]
+    pub lines_per_chunk: Option<usize>,
+
+    /// Approximate number of LLM tokens per chunk (takes precedence over lines)
+    #[serde(default)]
+    pub tokens_per_chunk: Option<usize>,
+
+    /// Number of monthly export directories to keep (all are kept if not set). The newest is
+    /// always kept, so 0 behaves like 1
+    #[serde(default)]
+    pub retention: Option<usize>,
+}
+
+fn default_format() -> String {
+    "txt".to_string()
+}
+
+impl Config {
+    /// Load configuration from `TXT_HISTORY_CONFIG` or the default path, falling back to defaults
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path())
+    }
+
+    /// Load configuration from a specific file, falling back to defaults if it doesn't exist
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Write the configuration as pretty JSON, creating its directory if needed
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write config file {}", path.display()))
+    }
+
+    /// Path of the configuration file in use
+    pub fn path() -> PathBuf {
+        env::var("TXT_HISTORY_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_CONFIG_PATH))
+    }
+}
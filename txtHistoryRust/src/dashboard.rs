@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+use minijinja::{context, Environment};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::models::DbMessage;
+
+const TEMPLATE: &str = include_str!("../templates/dashboard.html");
+
+/// The chart library the page loads from next to `index.html`
+pub const CHART_JS: &str = include_str!("../templates/chart.js");
+
+/// Replies slower than this start a new conversation rather than answer the last message
+const MAX_REPLY_GAP_HOURS: i64 = 12;
+
+/// One week (starting Monday) of a conversation, from my side ("sent") and theirs ("received")
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DashboardWeek {
+    pub week: NaiveDate,
+    pub sent: usize,
+    pub received: usize,
+    /// Average sentiment score, `None` for weeks without processed messages
+    pub sentiment_sent: Option<f64>,
+    pub sentiment_received: Option<f64>,
+    /// Median minutes between the other side's last message and a reply
+    pub reply_minutes_sent: Option<f64>,
+    pub reply_minutes_received: Option<f64>,
+}
+
+/// Weekly message volume, sentiment and reply times for `report dashboard`, in local time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dashboard {
+    pub contact: String,
+    pub me: String,
+    /// Every week from the first message to the last, including weeks without messages
+    pub weeks: Vec<DashboardWeek>,
+}
+
+#[derive(Default)]
+struct WeekSamples {
+    sent: usize,
+    received: usize,
+    sentiment: [Vec<f32>; 2],
+    replies: [Vec<f64>; 2],
+}
+
+impl Dashboard {
+    /// Build from a conversation sorted by date and the sentiment scores of its processed
+    /// messages, keyed by message id
+    pub fn new(messages: &[DbMessage], sentiment: &HashMap<i32, f32>, contact: &str, me: &str) -> Self {
+        let mut samples: HashMap<NaiveDate, WeekSamples> = HashMap::new();
+        let mut previous: Option<(bool, NaiveDateTime)> = None;
+
+        for message in messages {
+            let local = Local.from_utc_datetime(&message.date_created).date_naive();
+            let week = samples.entry(week_start(local)).or_default();
+            let side = usize::from(!message.is_from_me);
+
+            if message.is_from_me {
+                week.sent += 1;
+            } else {
+                week.received += 1;
+            }
+
+            if let Some(score) = sentiment.get(&message.id) {
+                week.sentiment[side].push(*score);
+            }
+
+            if let Some((was_from_me, at)) = previous {
+                let gap = message.date_created - at;
+                if was_from_me != message.is_from_me && gap <= Duration::hours(MAX_REPLY_GAP_HOURS) {
+                    week.replies[side].push(gap.num_seconds() as f64 / 60.0);
+                }
+            }
+            previous = Some((message.is_from_me, message.date_created));
+        }
+
+        let mut weeks = Vec::new();
+        if let (Some(first), Some(last)) = (samples.keys().min().copied(), samples.keys().max().copied()) {
+            let mut date = first;
+            while date <= last {
+                let week = samples.remove(&date).unwrap_or_default();
+                weeks.push(DashboardWeek {
+                    week: date,
+                    sent: week.sent,
+                    received: week.received,
+                    sentiment_sent: average(&week.sentiment[0]),
+                    sentiment_received: average(&week.sentiment[1]),
+                    reply_minutes_sent: median(week.replies[0].clone()),
+                    reply_minutes_received: median(week.replies[1].clone()),
+                });
+                date += Duration::weeks(1);
+            }
+        }
+
+        Self { contact: contact.to_string(), me: me.to_string(), weeks }
+    }
+
+    pub fn total(&self) -> usize {
+        self.weeks.iter().map(|w| w.sent + w.received).sum()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&json!({
+            "contact": self.contact,
+            "me": self.me,
+            "weeks": self.weeks,
+        }))?)
+    }
+
+    /// The page, with the data embedded so it opens from disk without a server
+    pub fn to_html(&self) -> Result<String> {
+        // `</script>` in a message-derived name would end the data block early
+        let data = self.to_json()?.replace("</", "<\\/");
+
+        let mut env = Environment::new();
+        env.add_template("dashboard.html", TEMPLATE)?;
+        env.get_template("dashboard.html")?
+            .render(context! {
+                contact => self.contact,
+                total => self.total(),
+                first => self.weeks.first().map(|w| w.week.to_string()),
+                last => self.weeks.last().map(|w| (w.week + Duration::days(6)).to_string()),
+                data => data,
+            })
+            .context("Failed to render dashboard")
+    }
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn average(values: &[f32]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let average = values.iter().map(|v| *v as f64).sum::<f64>() / values.len() as f64;
+    Some((average * 100.0).round() / 100.0)
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    let median = if values.len() % 2 == 0 { (values[middle - 1] + values[middle]) / 2.0 } else { values[middle] };
+    Some((median * 10.0).round() / 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MessageKind;
+
+    fn message(id: i32, is_from_me: bool, at: &str) -> DbMessage {
+        let date_created = NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M").unwrap();
+        DbMessage {
+            id,
+            imessage_id: format!("guid-{}", id),
+            text: Some("hi".to_string()),
+            sender: if is_from_me { "Jess" } else { "Phil" }.to_string(),
+            is_from_me,
+            date_created,
+            date_imported: date_created,
+            handle_id: None,
+            service: None,
+            thread_id: None,
+            has_attachments: false,
+            reply_to: None,
+            contact_id: Some(1),
+            message_kind: MessageKind::Text,
+            hidden: false,
+            hidden_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_dashboard_weeks() {
+        // Midday UTC keeps every message on the same local day
+        let messages = vec![
+            message(1, false, "2025-01-21 12:00"),
+            message(2, true, "2025-01-21 12:10"),
+            message(3, false, "2025-01-21 12:40"),
+            message(4, true, "2025-01-22 12:00"),
+            message(5, true, "2025-02-05 12:00"),
+        ];
+        let sentiment = HashMap::from([(1, 0.5), (3, -0.25), (4, 0.8)]);
+        let dashboard = Dashboard::new(&messages, &sentiment, "Phil", "Jess");
+
+        assert_eq!(dashboard.total(), 5);
+        // The empty week between is filled in
+        assert_eq!(dashboard.weeks.len(), 3);
+        let empty = DashboardWeek { week: NaiveDate::from_ymd_opt(2025, 1, 27).unwrap(), ..Default::default() };
+        assert_eq!(dashboard.weeks[1], empty);
+
+        let first = &dashboard.weeks[0];
+        assert_eq!(first.week, NaiveDate::from_ymd_opt(2025, 1, 20).unwrap());
+        assert_eq!((first.sent, first.received), (2, 2));
+        assert_eq!(first.sentiment_sent, Some(0.8));
+        assert_eq!(first.sentiment_received, Some(0.13));
+        // The next day's message is too late to count as a reply
+        assert_eq!(first.reply_minutes_sent, Some(10.0));
+        assert_eq!(first.reply_minutes_received, Some(30.0));
+
+        let html = dashboard.to_html().unwrap();
+        assert!(html.contains("<title>Conversation with Phil</title>"));
+        assert!(html.contains("5 messages from 2025-01-20 to 2025-02-09"));
+        assert!(html.contains("\"reply_minutes_sent\":10.0"));
+    }
+}
@@ -1,58 +1,218 @@
-use std::env;
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
-use chrono::{Local, NaiveDateTime, Utc};
+use chrono::{Local, NaiveDate, NaiveDateTime, Utc};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, Connection, OptionalExtension, Row};
+use rusqlite::fallible_iterator::FallibleIterator;
+use rusqlite::{params, Batch, Connection, DatabaseName, OptionalExtension, Row};
 
-use crate::models::{DbContact, DbMessage, DbProcessedMessage, Filter, FilterType, NewContact, NewMessage, NewProcessedMessage, Operator, QueryBuilder};
-use crate::schema::{contacts, messages, processed_messages};
+use crate::config::MeConfig;
+use crate::entities::EntityMessage;
+use crate::error::TxtHistoryError;
+use crate::heatmap::ActivityHeatmap;
+use crate::languages::LanguageSample;
+use crate::nlp_export::{split_tokens, NlpExportRow};
+use crate::models::{AttachmentReportEntry, CommitmentKind, CommitmentReportEntry, EventReportEntry, ContactDetails, ContactMerge, Conversation, DbAttachment, DbAttachmentTranscript, DbContact, DbExportChunk, DbExportRun, DbMessage, DbMessageRevision, DbProcessedMessage, Filter, FilterType, Message, MessageChange, MessageKind, NewAttachment, NewCommitment, NewContact, NewEvent, NewMessage, NewProcessedMessage, NewSummary, Operator, ProcessFilter, PurgeCounts, QueryBuilder, ServiceFilter, UpsertOutcome};
+use crate::profiles::Profile;
+use crate::sql::QueryResult;
+use crate::undo::{JournalTable, UndoJournal};
+use crate::schema::{
+    attachment_transcripts, attachments, contacts, export_chunks, export_runs, message_commitments, message_events, message_revisions, messages, processed_messages, summaries,
+};
 
 // Type alias for the database connection pool
 pub type DbPool = Pool<SqliteConnectionManager>;
 pub type DbConnection = r2d2::PooledConnection<SqliteConnectionManager>;
 
+/// Migrations that ran on every open before applied migrations were tracked, with a query
+/// telling whether a database already has each one
+const UNTRACKED_MIGRATIONS: &[(&str, &str)] = &[
+    (
+        "2025-03-15-000000_create_tables",
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'messages')",
+    ),
+    (
+        "2025-03-15-000001_add_processed_messages",
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'processed_messages')",
+    ),
+    (
+        "2025-03-19-000000_enhance_contact_linking",
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('messages') WHERE name = 'contact_id')",
+    ),
+];
+
+/// Migrations in the order they must be applied
+const MIGRATIONS: &[(&str, &str)] = &[
+    (
+        "2025-03-15-000000_create_tables",
+        include_str!("../migrations/2025-03-15-000000_create_tables/up.sql"),
+    ),
+    (
+        "2025-03-15-000001_add_processed_messages",
+        include_str!("../migrations/2025-03-15-000001_add_processed_messages/up.sql"),
+    ),
+    (
+        "2025-03-19-000000_enhance_contact_linking",
+        include_str!("../migrations/2025-03-19-000000_enhance_contact_linking/up.sql"),
+    ),
+    (
+        "2025-03-22-000000_add_attachment_transcripts",
+        include_str!("../migrations/2025-03-22-000000_add_attachment_transcripts/up.sql"),
+    ),
+    (
+        "2025-03-24-000000_add_message_replies",
+        include_str!("../migrations/2025-03-24-000000_add_message_replies/up.sql"),
+    ),
+    (
+        "2025-03-26-000000_enforce_contact_fk",
+        include_str!("../migrations/2025-03-26-000000_enforce_contact_fk/up.sql"),
+    ),
+    (
+        "2025-03-28-000000_add_message_revisions",
+        include_str!("../migrations/2025-03-28-000000_add_message_revisions/up.sql"),
+    ),
+    (
+        "2025-03-30-000000_add_language_flags",
+        include_str!("../migrations/2025-03-30-000000_add_language_flags/up.sql"),
+    ),
+    (
+        "2025-04-01-000000_add_message_commitments",
+        include_str!("../migrations/2025-04-01-000000_add_message_commitments/up.sql"),
+    ),
+    (
+        "2025-04-03-000000_add_message_kind",
+        include_str!("../migrations/2025-04-03-000000_add_message_kind/up.sql"),
+    ),
+    (
+        "2025-04-05-000000_add_hidden_messages",
+        include_str!("../migrations/2025-04-05-000000_add_hidden_messages/up.sql"),
+    ),
+    (
+        "2025-04-07-000000_add_export_runs",
+        include_str!("../migrations/2025-04-07-000000_add_export_runs/up.sql"),
+    ),
+    (
+        "2025-04-09-000000_add_message_events",
+        include_str!("../migrations/2025-04-09-000000_add_message_events/up.sql"),
+    ),
+    (
+        "2025-04-11-000000_add_message_language",
+        include_str!("../migrations/2025-04-11-000000_add_message_language/up.sql"),
+    ),
+    (
+        "2025-04-13-000000_add_summaries",
+        include_str!("../migrations/2025-04-13-000000_add_summaries/up.sql"),
+    ),
+    (
+        "2025-04-15-000000_add_sentence_sentiments",
+        include_str!("../migrations/2025-04-15-000000_add_sentence_sentiments/up.sql"),
+    ),
+    (
+        "2025-04-17-000000_add_contact_details",
+        include_str!("../migrations/2025-04-17-000000_add_contact_details/up.sql"),
+    ),
+];
+
+/// Contacts `initialize` adds when they're missing: name, phone and email
+const DEFAULT_CONTACTS: [(&str, Option<&str>, Option<&str>); 4] = [
+    ("Phil", Some("+18673335566"), Some("apple@phil-g.com")),
+    ("Robert", Some("+17806793467"), None),
+    ("Rhonda", Some("+17803944504"), None),
+    ("Sherry", Some("+17807223445"), None),
+];
+
 /// Database manager for handling connections and operations
 pub struct Database {
     pool: DbPool,
+    database_url: String,
+    /// Where purges and merges journal the rows they change, next to the database file
+    journal_dir: PathBuf,
 }
 
 impl Database {
-    /// Create a new database connection pool
+    /// Create a new database connection pool, applying any pending migrations
     pub fn new(database_url: &str) -> Result<Self> {
+        let db = Self::open(database_url)?;
+        db.migrate()?;
+        Ok(db)
+    }
+
+    /// Open the connection pool without migrating, for read-only commands that check
+    /// `needs_setup` before writing anything
+    pub fn open(database_url: &str) -> Result<Self> {
         // Create parent directory if it doesn't exist
         if let Some(parent) = Path::new(database_url).parent() {
             fs::create_dir_all(parent)?;
         }
 
-        // Set up connection manager and pool
-        let manager = SqliteConnectionManager::file(database_url);
-        let pool = Pool::builder()
-            .build(manager)
-            .context("Failed to create database connection pool")?;
+        Ok(Self {
+            pool: Self::connection_pool(database_url)?,
+            database_url: database_url.to_string(),
+            journal_dir: PathBuf::from(format!("{}.undo", database_url)),
+        })
+    }
 
-        // Run migrations
-        let conn = pool.get()?;
-        Self::run_migrations(&conn)?;
+    fn connection_pool(database_url: &str) -> Result<DbPool> {
+        // Set up connection manager and pool, enforcing foreign keys on every connection and
+        // waiting out another process's write instead of failing with "database is locked"
+        let manager = SqliteConnectionManager::file(database_url)
+            .with_init(|conn| conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;"));
+        Pool::builder()
+            .build(manager)
+            .context("Failed to create database connection pool")
+    }
 
-        Ok(Self { pool })
+    /// Apply any migrations that haven't run yet
+    pub fn migrate(&self) -> Result<()> {
+        Self::run_migrations(&self.get_connection()?)
     }
 
     /// Run database migrations
     fn run_migrations(conn: &Connection) -> Result<()> {
-        // Create tables if they don't exist
-        conn.execute_batch(include_str!("../migrations/2025-03-15-000000_create_tables/up.sql"))
-            .context("Failed to run initial migration")?;
-            
-        // Run additional migrations
-        conn.execute_batch(include_str!("../migrations/2025-03-15-000001_add_processed_messages/up.sql"))
-            .context("Failed to run processed_messages migration")?;
-            
-        conn.execute_batch(include_str!("../migrations/2025-03-19-000000_enhance_contact_linking/up.sql"))
-            .context("Failed to run contact linking migration")?;
+        let tracked: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '__migrations')",
+            params![],
+            |row| row.get(0)
+        )?;
+
+        // Track applied migrations so each one only runs once
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS __migrations (
+                name TEXT PRIMARY KEY,
+                applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .context("Failed to create migrations table")?;
+
+        // An untracked database already ran the first migrations, so record the ones it has
+        // instead of running them again
+        if !tracked {
+            for (name, check) in UNTRACKED_MIGRATIONS {
+                let applied: bool = conn.query_row(check, params![], |row| row.get(0))?;
+                if applied {
+                    conn.execute("INSERT INTO __migrations (name) VALUES (?)", params![name])?;
+                }
+            }
+        }
+
+        for (name, sql) in MIGRATIONS {
+            let applied: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM __migrations WHERE name = ?)",
+                params![name],
+                |row| row.get(0)
+            )?;
+
+            if applied {
+                continue;
+            }
+
+            conn.execute_batch(sql)
+                .with_context(|| format!("Failed to run migration {}", name))?;
+            conn.execute("INSERT INTO __migrations (name) VALUES (?)", params![name])?;
+        }
 
         Ok(())
     }
@@ -62,16 +222,101 @@ impl Database {
         self.pool.get().context("Failed to get database connection")
     }
 
+    /// Run one ad-hoc SELECT (or other read-only statement) and return every row.
+    ///
+    /// Statements SQLite reports as writing are rejected before they run, and the connection
+    /// is put in `query_only` mode while the query runs as a second line of defence. Only one
+    /// statement is accepted, since `prepare` would silently ignore any after the first.
+    pub fn run_read_only_query(&self, sql: &str) -> Result<QueryResult> {
+        let conn = self.get_connection()?;
+        conn.pragma_update(None, "query_only", true)?;
+
+        let result = (|| -> Result<QueryResult> {
+            let mut statements = Batch::new(&conn, sql);
+            let mut stmt = statements.next()?.ok_or_else(|| anyhow::anyhow!("The query is empty"))?;
+            if statements.next()?.is_some() {
+                return Err(anyhow::anyhow!("Only one statement can run at a time"));
+            }
+            if !stmt.readonly() {
+                return Err(anyhow::anyhow!("Only read-only queries are allowed"));
+            }
+
+            let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+            let mut rows = Vec::new();
+            let mut query = stmt.query([])?;
+            while let Some(row) = query.next()? {
+                let values = (0..columns.len())
+                    .map(|i| row.get_ref(i).map(QueryResult::value))
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                rows.push(values);
+            }
+
+            Ok(QueryResult { columns, rows })
+        })();
+
+        // The connection goes back to the pool, so always allow writes on it again
+        conn.pragma_update(None, "query_only", false)?;
+        result
+    }
+
+    /// Whether `migrate`, `initialize` or `sync_me` would change anything. Only checks, so a
+    /// read-only command can find out under the shared lock. Messages `initialize` would link
+    /// to contacts are left to the next command that writes.
+    pub fn needs_setup(&self, me: &MeConfig) -> Result<bool> {
+        let conn = self.get_connection()?;
+
+        let tracked: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '__migrations')",
+            params![],
+            |row| row.get(0),
+        )?;
+        if !tracked {
+            return Ok(true);
+        }
+        for (name, _) in MIGRATIONS {
+            let applied: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM __migrations WHERE name = ?)",
+                params![name],
+                |row| row.get(0),
+            )?;
+            if !applied {
+                return Ok(true);
+            }
+        }
+
+        for (name, _, _) in DEFAULT_CONTACTS {
+            let exists: bool = conn.query_row(
+                &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE {} = ?)", contacts::TABLE, contacts::NAME),
+                params![name],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Ok(true);
+            }
+        }
+
+        // `sync_me` keeps stored identifiers the config doesn't mention
+        let Some(current) = self.get_me_contact()? else {
+            return Ok(true);
+        };
+        let phone = me.phone();
+        let email = me.emails().next();
+        Ok(current.name != me.name
+            || phone.is_some_and(|phone| current.phone.as_deref() != Some(phone))
+            || email.is_some_and(|email| current.email.as_deref() != Some(email)))
+    }
+
     /// Initialize the database with default settings
     pub fn initialize(&self) -> Result<()> {
         let conn = self.get_connection()?;
         
-        // Add default contacts if they don't exist
-        self.ensure_contact(&conn, "Jess", None, None, true)?;
-        self.ensure_contact(&conn, "Phil", Some("+18673335566"), Some("apple@phil-g.com"), false)?;
-        self.ensure_contact(&conn, "Robert", Some("+17806793467"), None, false)?;
-        self.ensure_contact(&conn, "Rhonda", Some("+17803944504"), None, false)?;
-        self.ensure_contact(&conn, "Sherry", Some("+17807223445"), None, false)?;
+        // Add default contacts if they don't exist. The me contact comes from the config (see `sync_me`).
+        for (name, phone, email) in DEFAULT_CONTACTS {
+            self.ensure_contact(&conn, name, phone, email, false)?;
+        }
+        
+        // Link messages imported before contacts existed
+        self.backfill_contact_ids()?;
         
         Ok(())
     }
@@ -135,7 +380,7 @@ impl Database {
             
             conn.execute(
                 &format!(
-                    "INSERT INTO {} ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    "INSERT INTO {} ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
                     messages::TABLE,
                     messages::IMESSAGE_ID,
                     messages::TEXT,
@@ -146,7 +391,10 @@ impl Database {
                     messages::HANDLE_ID,
                     messages::SERVICE,
                     messages::THREAD_ID,
-                    messages::HAS_ATTACHMENTS
+                    messages::HAS_ATTACHMENTS,
+                    messages::REPLY_TO,
+                    messages::CONTACT_ID,
+                    messages::MESSAGE_KIND
                 ),
                 params![
                     new_message.imessage_id,
@@ -158,7 +406,10 @@ impl Database {
                     new_message.handle_id,
                     new_message.service,
                     new_message.thread_id,
-                    new_message.has_attachments
+                    new_message.has_attachments,
+                    new_message.reply_to,
+                    new_message.contact_id,
+                    new_message.message_kind.as_str()
                 ],
             )?;
             
@@ -178,9 +429,142 @@ impl Database {
                 service: new_message.service,
                 thread_id: new_message.thread_id,
                 has_attachments: new_message.has_attachments,
+                reply_to: new_message.reply_to,
                 contact_id: new_message.contact_id,
+                message_kind: new_message.message_kind,
+                hidden: false,
+                hidden_reason: None,
+            })
+        }
+    }
+
+    /// Insert a message, or update the text, attachment flag, service and kind of an existing
+    /// message with the same GUID. Each changed field is recorded in `message_revisions`.
+    pub fn upsert_message(&self, new_message: NewMessage) -> Result<UpsertOutcome> {
+        let existing = {
+            let conn = self.get_connection()?;
+            conn.query_row(
+                &format!(
+                    "SELECT * FROM {} WHERE {} = ?",
+                    messages::TABLE, messages::IMESSAGE_ID
+                ),
+                params![new_message.imessage_id],
+                |row| self.map_db_message(row)
+            ).optional()?
+        };
+
+        let Some(mut message) = existing else {
+            return Ok(UpsertOutcome::Inserted(self.add_message(new_message)?));
+        };
+
+        let mut changes = Vec::new();
+        if message.text != new_message.text {
+            changes.push(MessageChange {
+                field: messages::TEXT,
+                old_value: message.text.clone(),
+                new_value: new_message.text.clone(),
+            });
+        }
+        if message.has_attachments != new_message.has_attachments {
+            changes.push(MessageChange {
+                field: messages::HAS_ATTACHMENTS,
+                old_value: Some(message.has_attachments.to_string()),
+                new_value: Some(new_message.has_attachments.to_string()),
+            });
+        }
+        if message.service != new_message.service {
+            changes.push(MessageChange {
+                field: messages::SERVICE,
+                old_value: message.service.clone(),
+                new_value: new_message.service.clone(),
+            });
+        }
+        if message.message_kind != new_message.message_kind {
+            changes.push(MessageChange {
+                field: messages::MESSAGE_KIND,
+                old_value: Some(message.message_kind.as_str().to_string()),
+                new_value: Some(new_message.message_kind.as_str().to_string()),
+            });
+        }
+
+        if changes.is_empty() {
+            return Ok(UpsertOutcome::Unchanged(message));
+        }
+
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            &format!(
+                "UPDATE {} SET {} = ?, {} = ?, {} = ?, {} = ? WHERE {} = ?",
+                messages::TABLE,
+                messages::TEXT,
+                messages::HAS_ATTACHMENTS,
+                messages::SERVICE,
+                messages::MESSAGE_KIND,
+                messages::ID
+            ),
+            params![
+                new_message.text,
+                new_message.has_attachments,
+                new_message.service,
+                new_message.message_kind.as_str(),
+                message.id
+            ],
+        )?;
+
+        for change in &changes {
+            tx.execute(
+                &format!(
+                    "INSERT INTO {} ({}, {}, {}, {}) VALUES (?, ?, ?, ?)",
+                    message_revisions::TABLE,
+                    message_revisions::MESSAGE_ID,
+                    message_revisions::FIELD,
+                    message_revisions::OLD_VALUE,
+                    message_revisions::NEW_VALUE
+                ),
+                params![message.id, change.field, change.old_value, change.new_value],
+            )?;
+        }
+
+        tx.commit()?;
+
+        message.text = new_message.text;
+        message.has_attachments = new_message.has_attachments;
+        message.service = new_message.service;
+        message.message_kind = new_message.message_kind;
+
+        Ok(UpsertOutcome::Updated(message, changes))
+    }
+
+    /// Get the recorded changes to a message, oldest first
+    pub fn get_message_revisions(&self, message_id: i32) -> Result<Vec<DbMessageRevision>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} WHERE {} = ? ORDER BY {} ASC",
+            message_revisions::TABLE,
+            message_revisions::MESSAGE_ID,
+            message_revisions::ID
+        ))?;
+
+        let revision_iter = stmt.query_map(params![message_id], |row| {
+            Ok(DbMessageRevision {
+                id: row.get(message_revisions::ID)?,
+                message_id: row.get(message_revisions::MESSAGE_ID)?,
+                field: row.get(message_revisions::FIELD)?,
+                old_value: row.get(message_revisions::OLD_VALUE)?,
+                new_value: row.get(message_revisions::NEW_VALUE)?,
+                changed_at: row.get(message_revisions::CHANGED_AT)?,
             })
+        })?;
+
+        let mut results = Vec::new();
+        for revision in revision_iter {
+            results.push(revision?);
         }
+
+        Ok(results)
     }
 
     /// Get messages for a contact within a date range
@@ -189,12 +573,202 @@ impl Database {
         contact_name: &str,
         start_date: Option<NaiveDateTime>,
         end_date: Option<NaiveDateTime>,
+    ) -> Result<Vec<DbMessage>> {
+        let contact = self.get_contact(contact_name)?
+            .ok_or_else(|| TxtHistoryError::ContactNotFound(contact_name.to_string()))?;
+
+        self.get_messages_for_contact_id(contact.id, start_date, end_date)
+    }
+
+    /// Get both sides of the conversation linked to a contact within a date range
+    pub fn get_messages_for_contact_id(
+        &self,
+        contact_id: i32,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<Vec<DbMessage>> {
+        self.get_messages_where(messages::CONTACT_ID, Box::new(contact_id), start_date, end_date)
+    }
+
+    /// Get every message imported from a chat (its chat.db `chat_identifier`) within a date range
+    pub fn get_messages_for_thread(
+        &self,
+        thread_id: &str,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<Vec<DbMessage>> {
+        self.get_messages_where(messages::THREAD_ID, Box::new(thread_id.to_string()), start_date, end_date)
+    }
+
+    /// Get every message I sent, across all contacts and chats, within a date range
+    pub fn get_my_messages(
+        &self,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<Vec<DbMessage>> {
+        self.get_messages_where(messages::IS_FROM_ME, Box::new(true), start_date, end_date)
+    }
+
+    /// Up to `before` messages preceding `target` and `after` following it in `conversation`,
+    /// oldest first with the target in between. Messages sent in the same second are ordered by ID,
+    /// and hidden messages are left out of the window.
+    pub fn get_message_context(
+        &self,
+        conversation: &Conversation,
+        target: &DbMessage,
+        before: usize,
+        after: usize,
+    ) -> Result<Vec<DbMessage>> {
+        let conn = self.get_connection()?;
+        let (column, value) = conversation_filter(conversation);
+
+        let window = |comparison: &str, order: &str, limit: usize| -> Result<Vec<DbMessage>> {
+            let query = format!(
+                "SELECT * FROM {table} WHERE {column} = ?1 AND {hidden} = 0 \
+                 AND ({date} {comparison} ?3 OR ({date} = ?3 AND {id} {comparison} ?2)) \
+                 ORDER BY {date} {order}, {id} {order} LIMIT ?4",
+                table = messages::TABLE,
+                column = column,
+                hidden = messages::HIDDEN,
+                id = messages::ID,
+                date = messages::DATE_CREATED,
+                comparison = comparison,
+                order = order,
+            );
+            let mut stmt = conn.prepare(&query)?;
+            let rows = stmt.query_map(
+                params![value, target.id, target.date_created, limit as i64],
+                |row| self.map_db_message(row),
+            )?;
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        };
+
+        let mut messages = window("<", "DESC", before)?;
+        messages.reverse();
+        messages.push(target.clone());
+        messages.extend(window(">", "ASC", after)?);
+
+        Ok(messages)
+    }
+
+    /// Visible messages whose text or attachment transcripts contain `text`, ignoring case (ASCII letters only, as SQLite's
+    /// LIKE does), oldest first. `conversation` limits the search to one contact or chat.
+    pub fn search_messages(
+        &self,
+        text: &str,
+        conversation: Option<&Conversation>,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<Vec<DbMessage>> {
+        let conn = self.get_connection()?;
+
+        // The text or any of the message's attachment transcripts can match
+        let mut query = format!(
+            "SELECT * FROM {messages} WHERE {hidden} = 0 AND ({text} LIKE ? ESCAPE '\\' \
+             OR EXISTS (SELECT 1 FROM {transcripts} t WHERE t.{message_id} = {messages}.{id} \
+             AND t.{transcript} LIKE ? ESCAPE '\\'))",
+            messages = messages::TABLE,
+            hidden = messages::HIDDEN,
+            text = messages::TEXT,
+            transcripts = attachment_transcripts::TABLE,
+            message_id = attachment_transcripts::MESSAGE_ID,
+            id = messages::ID,
+            transcript = attachment_transcripts::TRANSCRIPT,
+        );
+        let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let pattern = format!("%{}%", escaped);
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(pattern.clone()), Box::new(pattern)];
+
+        if let Some(conversation) = conversation {
+            let (column, value) = conversation_filter(conversation);
+            query.push_str(&format!(" AND {} = ?", column));
+            params.push(Box::new(value));
+        }
+
+        if let Some(start) = start_date {
+            query.push_str(&format!(" AND {} >= ?", messages::DATE_CREATED));
+            params.push(Box::new(start));
+        }
+
+        if let Some(end) = end_date {
+            query.push_str(&format!(" AND {} <= ?", messages::DATE_CREATED));
+            params.push(Box::new(end));
+        }
+
+        query.push_str(&format!(" ORDER BY {} ASC, {} ASC", messages::DATE_CREATED, messages::ID));
+
+        let mut stmt = conn.prepare(&query)?;
+        let message_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            self.map_db_message(row)
+        })?;
+
+        let mut results = Vec::new();
+        for message in message_iter {
+            results.push(message?);
+        }
+
+        Ok(results)
+    }
+
+    /// The visible message in `conversation` sent closest to `at` (UTC)
+    pub fn find_message_nearest(&self, conversation: &Conversation, at: NaiveDateTime) -> Result<Option<DbMessage>> {
+        let conn = self.get_connection()?;
+        let (column, value) = conversation_filter(conversation);
+
+        let message = conn
+            .query_row(
+                &format!(
+                    "SELECT * FROM {} WHERE {} = ?1 AND {} = 0 \
+                     ORDER BY abs(julianday({}) - julianday(?2)), {} LIMIT 1",
+                    messages::TABLE,
+                    column,
+                    messages::HIDDEN,
+                    messages::DATE_CREATED,
+                    messages::ID
+                ),
+                params![value, at],
+                |row| self.map_db_message(row),
+            )
+            .optional()?;
+
+        Ok(message)
+    }
+
+    /// Count a conversation's messages per year, oldest first. Years are taken from the stored
+    /// UTC times, matching how `--start-date` and `--end-date` are read. Hidden messages are left out.
+    pub fn get_message_years(&self, conversation: &Conversation) -> Result<Vec<(i32, usize)>> {
+        let conn = self.get_connection()?;
+        let (column, value) = conversation_filter(conversation);
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT CAST(strftime('%Y', {}) AS INTEGER), COUNT(*) FROM {} WHERE {} = ?1 AND {} = 0 \
+             GROUP BY 1 ORDER BY 1",
+            messages::DATE_CREATED,
+            messages::TABLE,
+            column,
+            messages::HIDDEN
+        ))?;
+        let years = stmt
+            .query_map(params![value], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(years)
+    }
+
+    /// Get messages whose `column` equals `value` within a date range, oldest first. Hidden
+    /// messages are left out.
+    fn get_messages_where(
+        &self,
+        column: &str,
+        value: Box<dyn rusqlite::ToSql>,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
     ) -> Result<Vec<DbMessage>> {
         let conn = self.get_connection()?;
         
         // Build query
-        let mut query = String::from(format!("SELECT * FROM {} WHERE {} = ?", messages::TABLE, messages::SENDER));
-        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(contact_name.to_string())];
+        let mut query = format!("SELECT * FROM {} WHERE {} = ? AND {} = 0", messages::TABLE, column, messages::HIDDEN);
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![value];
         
         // Apply date filters if provided
         if let Some(start) = start_date {
@@ -224,6 +798,53 @@ impl Database {
         Ok(results)
     }
 
+    /// Link messages without a contact to one, using the sender name for received messages
+    /// and the rest of the thread for sent ones. Returns the number of messages linked.
+    pub fn backfill_contact_ids(&self) -> Result<usize> {
+        let conn = self.get_connection()?;
+
+        let sender_contact = format!(
+            "SELECT c.{id} FROM {contacts} c WHERE c.{name} = {messages}.{sender} AND c.{is_me} = 0",
+            id = contacts::ID,
+            contacts = contacts::TABLE,
+            name = contacts::NAME,
+            is_me = contacts::IS_ME,
+            messages = messages::TABLE,
+            sender = messages::SENDER,
+        );
+        let received = conn.execute(
+            &format!(
+                "UPDATE {messages} SET {contact_id} = ({lookup})
+                 WHERE {contact_id} IS NULL AND {is_from_me} = 0 AND EXISTS ({lookup})",
+                messages = messages::TABLE,
+                contact_id = messages::CONTACT_ID,
+                is_from_me = messages::IS_FROM_ME,
+                lookup = sender_contact,
+            ),
+            params![],
+        )?;
+
+        let thread_contact = format!(
+            "SELECT m.{contact_id} FROM {messages} m
+             WHERE m.{thread_id} = {messages}.{thread_id} AND m.{contact_id} IS NOT NULL LIMIT 1",
+            contact_id = messages::CONTACT_ID,
+            messages = messages::TABLE,
+            thread_id = messages::THREAD_ID,
+        );
+        let sent = conn.execute(
+            &format!(
+                "UPDATE {messages} SET {contact_id} = ({lookup})
+                 WHERE {contact_id} IS NULL AND EXISTS ({lookup})",
+                messages = messages::TABLE,
+                contact_id = messages::CONTACT_ID,
+                lookup = thread_contact,
+            ),
+            params![],
+        )?;
+
+        Ok(received + sent)
+    }
+
     /// Map a database row to a DbMessage
     fn map_db_message(&self, row: &Row) -> rusqlite::Result<DbMessage> {
         Ok(DbMessage {
@@ -238,7 +859,11 @@ impl Database {
             service: row.get(messages::SERVICE)?,
             thread_id: row.get(messages::THREAD_ID)?,
             has_attachments: row.get(messages::HAS_ATTACHMENTS)?,
-            contact_id: row.get("contact_id").optional()?,
+            reply_to: row.get(messages::REPLY_TO)?,
+            contact_id: row.get(messages::CONTACT_ID)?,
+            message_kind: MessageKind::parse(&row.get::<_, String>(messages::MESSAGE_KIND)?).unwrap_or_default(),
+            hidden: row.get(messages::HIDDEN)?,
+            hidden_reason: row.get(messages::HIDDEN_REASON)?,
         })
     }
 
@@ -250,6 +875,12 @@ impl Database {
             phone: row.get(contacts::PHONE)?,
             email: row.get(contacts::EMAIL)?,
             is_me: row.get(contacts::IS_ME)?,
+            details: ContactDetails {
+                relationship: row.get(contacts::RELATIONSHIP)?,
+                timezone: row.get(contacts::TIMEZONE)?,
+                birthday: row.get(contacts::BIRTHDAY)?,
+                notes: row.get(contacts::NOTES)?,
+            },
         })
     }
 
@@ -266,65 +897,318 @@ impl Database {
         Ok(message)
     }
 
-    /// Get a contact by name
-    pub fn get_contact(&self, name: &str) -> Result<Option<DbContact>> {
+    /// Get a message by its original ID (iMessage GUID or the imported source's ID)
+    pub fn get_message_by_imessage_id(&self, imessage_id: &str) -> Result<Option<DbMessage>> {
         let conn = self.get_connection()?;
-        
-        let contact = conn.query_row(
-            &format!("SELECT * FROM {} WHERE {} = ?", contacts::TABLE, contacts::NAME),
-            params![name],
-            |row| self.map_db_contact(row)
-        ).optional()?;
-            
-        Ok(contact)
+
+        let message = conn
+            .query_row(
+                &format!("SELECT * FROM {} WHERE {} = ?", messages::TABLE, messages::IMESSAGE_ID),
+                params![imessage_id],
+                |row| self.map_db_message(row),
+            )
+            .optional()?;
+
+        Ok(message)
     }
 
-    /// Add a new contact or update an existing one with improved identifier handling
-    pub fn add_or_update_contact(&self, new_contact: NewContact) -> Result<DbContact> {
+    /// Get messages by ID in one query; IDs that don't exist are left out
+    pub fn get_messages_by_ids(&self, message_ids: &[i32]) -> Result<Vec<DbMessage>> {
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
         let conn = self.get_connection()?;
-        
-        // Check if contact already exists by name
-        let existing: Option<DbContact> = conn.query_row(
-            &format!(
-                "SELECT * FROM {} WHERE {} = ? AND {} = ?",
-                contacts::TABLE, contacts::NAME, contacts::IS_ME
-            ),
-            params![new_contact.name, new_contact.is_me],
-            |row| self.map_db_contact(row)
-        ).optional()?;
-        
-        if let Some(contact) = existing {
-            // Update existing contact if needed
-            let mut update_fields = Vec::new();
-            let mut update_params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-            
-            if let Some(phone) = &new_contact.phone {
-                if contact.phone.as_ref() != Some(phone) {
-                    update_fields.push(format!("{} = ?", contacts::PHONE));
-                    update_params.push(Box::new(phone.clone()));
-                }
-            }
-            
-            if let Some(email) = &new_contact.email {
-                if contact.email.as_ref() != Some(email) {
-                    update_fields.push(format!("{} = ?", contacts::EMAIL));
-                    update_params.push(Box::new(email.clone()));
-                }
-            }
-            
-            if !update_fields.is_empty() {
-                // Add the contact ID for the WHERE clause
-                update_params.push(Box::new(contact.id));
-                
-                let query = format!(
-                    "UPDATE {} SET {} WHERE {} = ?",
-                    contacts::TABLE,
-                    update_fields.join(", "),
-                    contacts::ID
-                );
-                
-                conn.execute(&query, rusqlite::params_from_iter(update_params.iter()))?;
-                
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} WHERE {} IN ({})",
+            messages::TABLE,
+            messages::ID,
+            placeholders(message_ids.len())
+        ))?;
+
+        let message_iter = stmt.query_map(rusqlite::params_from_iter(message_ids), |row| self.map_db_message(row))?;
+
+        let mut results = Vec::new();
+        for message in message_iter {
+            results.push(message?);
+        }
+
+        Ok(results)
+    }
+
+    /// Hide messages from exports and reports without deleting them, returning the number hidden.
+    /// Hiding an already hidden message replaces its reason.
+    pub fn hide_messages(&self, message_ids: &[i32], reason: &str) -> Result<usize> {
+        self.set_hidden(message_ids, true, Some(reason))
+    }
+
+    /// Show hidden messages in exports and reports again, returning the number unhidden
+    pub fn unhide_messages(&self, message_ids: &[i32]) -> Result<usize> {
+        self.set_hidden(message_ids, false, None)
+    }
+
+    fn set_hidden(&self, message_ids: &[i32], hidden: bool, reason: Option<&str>) -> Result<usize> {
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()?;
+        let mut updated = 0;
+
+        for chunk in message_ids.chunks(500) {
+            let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(hidden), Box::new(reason.map(String::from))];
+            params.extend(chunk.iter().map(|id| Box::new(*id) as Box<dyn rusqlite::ToSql>));
+
+            updated += tx.execute(
+                &format!(
+                    "UPDATE {} SET {} = ?, {} = ? WHERE {} IN ({})",
+                    messages::TABLE,
+                    messages::HIDDEN,
+                    messages::HIDDEN_REASON,
+                    messages::ID,
+                    placeholders(chunk.len())
+                ),
+                rusqlite::params_from_iter(params.iter()),
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    /// Get every hidden message with the reason it was hidden, oldest first
+    pub fn get_hidden_messages(&self) -> Result<Vec<DbMessage>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} WHERE {} = 1 ORDER BY {} ASC",
+            messages::TABLE,
+            messages::HIDDEN,
+            messages::DATE_CREATED
+        ))?;
+        let message_iter = stmt.query_map([], |row| self.map_db_message(row))?;
+
+        let mut results = Vec::new();
+        for message in message_iter {
+            results.push(message?);
+        }
+
+        Ok(results)
+    }
+
+    /// GUIDs of hidden messages, for leaving them out of exports made straight from chat.db
+    pub fn get_hidden_imessage_ids(&self) -> Result<HashSet<String>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM {} WHERE {} = 1",
+            messages::IMESSAGE_ID,
+            messages::TABLE,
+            messages::HIDDEN
+        ))?;
+        let ids = stmt.query_map([], |row| row.get(0))?;
+
+        Ok(ids.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Dates of every message linked to a contact, hidden or not, oldest first. Hidden messages
+    /// are still in the archive, so they count when checking it for gaps.
+    pub fn get_message_dates_for_contact_id(&self, contact_id: i32) -> Result<Vec<NaiveDateTime>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM {} WHERE {} = ? ORDER BY {} ASC",
+            messages::DATE_CREATED,
+            messages::TABLE,
+            messages::CONTACT_ID,
+            messages::DATE_CREATED
+        ))?;
+        let dates = stmt.query_map(params![contact_id], |row| row.get(0))?;
+
+        Ok(dates.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Get the IDs of the `limit` most recent text messages with a contact, oldest first.
+    /// Hidden messages are left out.
+    pub fn get_recent_message_ids(&self, contact_id: i32, limit: usize) -> Result<Vec<i32>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {id} FROM {table} WHERE {contact} = ? AND {hidden} = 0 AND {text} IS NOT NULL AND {text} != '' \
+             ORDER BY {date} DESC, {id} DESC LIMIT ?",
+            id = messages::ID,
+            table = messages::TABLE,
+            contact = messages::CONTACT_ID,
+            hidden = messages::HIDDEN,
+            text = messages::TEXT,
+            date = messages::DATE_CREATED
+        ))?;
+        let mut ids = stmt
+            .query_map(params![contact_id, limit as i64], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<i32>>>()?;
+        ids.reverse();
+
+        Ok(ids)
+    }
+
+    /// Get a contact by name
+    pub fn get_contact(&self, name: &str) -> Result<Option<DbContact>> {
+        let conn = self.get_connection()?;
+        
+        let contact = conn.query_row(
+            &format!("SELECT * FROM {} WHERE {} = ?", contacts::TABLE, contacts::NAME),
+            params![name],
+            |row| self.map_db_contact(row)
+        ).optional()?;
+            
+        Ok(contact)
+    }
+
+    /// Get the contact marked as me
+    pub fn get_me_contact(&self) -> Result<Option<DbContact>> {
+        let conn = self.get_connection()?;
+
+        let contact = conn.query_row(
+            &format!("SELECT * FROM {} WHERE {} = ? LIMIT 1", contacts::TABLE, contacts::IS_ME),
+            params![true],
+            |row| self.map_db_contact(row)
+        ).optional()?;
+
+        Ok(contact)
+    }
+
+    /// Make `me` the user's own contact. An existing me contact is renamed and its sent messages
+    /// follow it, so earlier imports stay attributed to the user.
+    pub fn set_me_contact(&self, me: NewContact) -> Result<DbContact> {
+        let Some(current) = self.get_me_contact()? else {
+            return self.add_or_update_contact(NewContact { is_me: true, ..me });
+        };
+
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            &format!(
+                "UPDATE {} SET {} = ?1, {} = coalesce(?2, {}), {} = coalesce(?3, {}) WHERE {} = ?4",
+                contacts::TABLE,
+                contacts::NAME,
+                contacts::PHONE,
+                contacts::PHONE,
+                contacts::EMAIL,
+                contacts::EMAIL,
+                contacts::ID
+            ),
+            params![me.name, me.phone, me.email, current.id],
+        )?;
+        tx.execute(
+            &format!(
+                "UPDATE {} SET {} = ?1 WHERE {} = ?2 AND {} = 1",
+                messages::TABLE,
+                messages::SENDER,
+                messages::SENDER,
+                messages::IS_FROM_ME
+            ),
+            params![me.name, current.name],
+        )?;
+        tx.commit()?;
+
+        self.get_me_contact()?.ok_or_else(|| anyhow::anyhow!("Failed to retrieve updated contact"))
+    }
+
+    /// Keep the me contact in line with the `me` config section, creating it on first run
+    pub fn sync_me(&self, me: &MeConfig) -> Result<DbContact> {
+        self.set_me_contact(NewContact {
+            name: me.name.clone(),
+            phone: me.phone().map(String::from),
+            email: me.emails().next().map(String::from),
+            is_me: true,
+            primary_identifier: None,
+        })
+    }
+
+    /// Sender name for the user's own messages
+    pub fn my_name(&self) -> Result<String> {
+        Ok(self.get_me_contact()?.map_or_else(|| MeConfig::default().name, |me| me.name))
+    }
+
+    /// Get every contact other than me, by name
+    pub fn get_other_contacts(&self) -> Result<Vec<DbContact>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} WHERE {} = ? ORDER BY {}",
+            contacts::TABLE,
+            contacts::IS_ME,
+            contacts::NAME
+        ))?;
+        let contact_iter = stmt.query_map(params![false], |row| self.map_db_contact(row))?;
+
+        let mut results = Vec::new();
+        for contact in contact_iter {
+            results.push(contact?);
+        }
+
+        Ok(results)
+    }
+
+    /// Find a contact by email address, phone number or primary identifier
+    pub fn find_contact_by_identifier(&self, identifier: &str) -> Result<Option<DbContact>> {
+        let conn = self.get_connection()?;
+
+        let contact = conn.query_row(
+            &format!(
+                "SELECT * FROM {} WHERE LOWER({}) = LOWER(?1) OR {} = ?1 OR {} = ?1 LIMIT 1",
+                contacts::TABLE, contacts::EMAIL, contacts::PHONE, contacts::PRIMARY_IDENTIFIER
+            ),
+            params![identifier],
+            |row| self.map_db_contact(row)
+        ).optional()?;
+
+        Ok(contact)
+    }
+
+    /// Add a new contact or update an existing one with improved identifier handling
+    pub fn add_or_update_contact(&self, new_contact: NewContact) -> Result<DbContact> {
+        let conn = self.get_connection()?;
+        
+        // Check if contact already exists by name
+        let existing: Option<DbContact> = conn.query_row(
+            &format!(
+                "SELECT * FROM {} WHERE {} = ? AND {} = ?",
+                contacts::TABLE, contacts::NAME, contacts::IS_ME
+            ),
+            params![new_contact.name, new_contact.is_me],
+            |row| self.map_db_contact(row)
+        ).optional()?;
+        
+        if let Some(contact) = existing {
+            // Update existing contact if needed
+            let mut update_fields = Vec::new();
+            let mut update_params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+            
+            if let Some(phone) = &new_contact.phone {
+                if contact.phone.as_ref() != Some(phone) {
+                    update_fields.push(format!("{} = ?", contacts::PHONE));
+                    update_params.push(Box::new(phone.clone()));
+                }
+            }
+            
+            if let Some(email) = &new_contact.email {
+                if contact.email.as_ref() != Some(email) {
+                    update_fields.push(format!("{} = ?", contacts::EMAIL));
+                    update_params.push(Box::new(email.clone()));
+                }
+            }
+            
+            if !update_fields.is_empty() {
+                // Add the contact ID for the WHERE clause
+                update_params.push(Box::new(contact.id));
+                
+                let query = format!(
+                    "UPDATE {} SET {} WHERE {} = ?",
+                    contacts::TABLE,
+                    update_fields.join(", "),
+                    contacts::ID
+                );
+                
+                conn.execute(&query, rusqlite::params_from_iter(update_params.iter()))?;
+                
                 // Get the updated contact
                 return self.get_contact(&new_contact.name)?.ok_or_else(|| anyhow::anyhow!("Failed to retrieve updated contact"));
             }
@@ -350,6 +1234,123 @@ impl Database {
         }
     }
 
+    /// Replace a contact's notes and typed details
+    pub fn update_contact_details(&self, contact_id: i32, details: &ContactDetails) -> Result<()> {
+        let conn = self.get_connection()?;
+
+        let updated = conn.execute(
+            &format!(
+                "UPDATE {} SET {} = ?, {} = ?, {} = ?, {} = ? WHERE {} = ?",
+                contacts::TABLE,
+                contacts::RELATIONSHIP,
+                contacts::TIMEZONE,
+                contacts::BIRTHDAY,
+                contacts::NOTES,
+                contacts::ID
+            ),
+            params![details.relationship, details.timezone, details.birthday, details.notes, contact_id],
+        )?;
+        if updated == 0 {
+            return Err(anyhow::anyhow!("Contact with ID {} not found", contact_id));
+        }
+
+        Ok(())
+    }
+
+    /// Merge the duplicate contact `from` into `into` in one transaction: `into` takes over
+    /// identifiers it doesn't have yet, messages linked to `from` are linked to `into`, received
+    /// messages sent under `from`'s name are renamed and `from` is deleted. With `dry_run` the
+    /// transaction is rolled back, so the result previews the merge without changing anything.
+    pub fn merge_contacts(&self, into: &DbContact, from: &DbContact, dry_run: bool) -> Result<ContactMerge> {
+        if into.id == from.id {
+            return Err(anyhow::anyhow!("Can't merge {} into itself", into.name));
+        }
+        if into.is_me != from.is_me {
+            return Err(anyhow::anyhow!("Can't merge my own contact with someone else's"));
+        }
+
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()?;
+        let mut merge = ContactMerge::default();
+
+        // Both contacts come before their messages, so undo recreates `from` before relinking
+        let mut journal = UndoJournal::new("contacts merge", &format!("Merged {} into {}", from.name, into.name));
+        if !dry_run {
+            journal.tables.push(JournalTable::capture(
+                &tx,
+                contacts::TABLE,
+                &format!("{} IN (?, ?)", contacts::ID),
+                params![into.id, from.id],
+            )?);
+            journal.tables.push(JournalTable::capture(
+                &tx,
+                messages::TABLE,
+                &format!(
+                    "{} = ? OR ({} = ? AND {} = 0)",
+                    messages::CONTACT_ID, messages::SENDER, messages::IS_FROM_ME
+                ),
+                params![from.id, from.name],
+            )?);
+        }
+
+        let identifiers = |id: i32| -> Result<[Option<String>; 3]> {
+            Ok(tx.query_row(
+                &format!(
+                    "SELECT {}, {}, {} FROM {} WHERE {} = ?",
+                    contacts::PHONE, contacts::EMAIL, contacts::PRIMARY_IDENTIFIER, contacts::TABLE, contacts::ID
+                ),
+                params![id],
+                |row| Ok([row.get(0)?, row.get(1)?, row.get(2)?]),
+            )?)
+        };
+        let mut slots = identifiers(into.id)?;
+        let [phone, email, primary] = identifiers(from.id)?;
+
+        // Phones and emails go in their own column, or the primary identifier when that's taken
+        for (value, column) in [(phone, 0), (email, 1), (primary, 2)] {
+            let Some(value) = value else { continue };
+            if slots.iter().flatten().any(|known| known.eq_ignore_ascii_case(&value)) {
+                continue;
+            }
+
+            match [column, 2].into_iter().find(|&slot| slots[slot].is_none()) {
+                Some(slot) => {
+                    let name = [contacts::PHONE, contacts::EMAIL, contacts::PRIMARY_IDENTIFIER][slot];
+                    tx.execute(
+                        &format!("UPDATE {} SET {} = ? WHERE {} = ?", contacts::TABLE, name, contacts::ID),
+                        params![value, into.id],
+                    )?;
+                    merge.identifiers.push((name, value.clone()));
+                    slots[slot] = Some(value);
+                },
+                None => merge.dropped_identifiers.push(value),
+            }
+        }
+
+        merge.messages = tx.execute(
+            &format!("UPDATE {} SET {} = ? WHERE {} = ?", messages::TABLE, messages::CONTACT_ID, messages::CONTACT_ID),
+            params![into.id, from.id],
+        )?;
+        merge.renamed_senders = tx.execute(
+            &format!(
+                "UPDATE {} SET {} = ? WHERE {} = ? AND {} = 0",
+                messages::TABLE, messages::SENDER, messages::SENDER, messages::IS_FROM_ME
+            ),
+            params![into.name, from.name],
+        )?;
+        tx.execute(
+            &format!("DELETE FROM {} WHERE {} = ?", contacts::TABLE, contacts::ID),
+            params![from.id],
+        )?;
+
+        if !dry_run {
+            let journal = journal.write(&self.journal_dir)?;
+            tx.commit()?;
+            journal.commit()?;
+        }
+        Ok(merge)
+    }
+
     /// Get all messages for a specific person, combining both phone and email conversations
     pub fn get_conversation_with_person(
         &self,
@@ -357,52 +1358,10 @@ impl Database {
         start_date: Option<NaiveDateTime>,
         end_date: Option<NaiveDateTime>,
     ) -> Result<Vec<DbMessage>> {
-        let conn = self.get_connection()?;
-        
-        // Get the contact
-        let contact = self.get_contact(person_name)?.ok_or_else(|| anyhow::anyhow!("Contact not found: {}", person_name))?;
-        
-        // Get messages where the sender is the person
-        let mut query = format!(
-            "SELECT * FROM {} WHERE {} = ?",
-            messages::TABLE, messages::SENDER
-        );
-        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(person_name.to_string())];
-        
-        // Apply date filters if provided
-        if let Some(start) = start_date {
-            query.push_str(&format!(" AND {} >= ?", messages::DATE_CREATED));
-            params.push(Box::new(start));
-        }
-        
-        if let Some(end) = end_date {
-            query.push_str(&format!(" AND {} <= ?", messages::DATE_CREATED));
-            params.push(Box::new(end));
-        }
-        
-        // Get messages where the sender is me and the recipient is the person
-        query.push_str(&format!(
-            " UNION SELECT * FROM {} WHERE {} = ? AND {} = ?",
-            messages::TABLE, messages::IS_FROM_ME, messages::SENDER
-        ));
-        params.push(Box::new(true));
-        params.push(Box::new("Jess".to_string()));
-        
-        // Order by date
-        query.push_str(&format!(" ORDER BY {} ASC", messages::DATE_CREATED));
-        
-        // Execute query
-        let mut stmt = conn.prepare(&query)?;
-        let message_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
-            self.map_db_message(row)
-        })?;
-        
-        let mut results = Vec::new();
-        for message in message_iter {
-            results.push(message?);
-        }
+        let contact = self.get_contact(person_name)?.ok_or_else(|| TxtHistoryError::ContactNotFound(person_name.to_string()))?;
         
-        Ok(results)
+        // Both sides of the conversation are linked to the person by contact_id
+        self.get_messages_for_contact_id(contact.id, start_date, end_date)
     }
 
     /// Add a new processed message to the database
@@ -430,7 +1389,8 @@ impl Database {
             
             conn.execute(
                 &format!(
-                    "INSERT INTO {} ({}, {}, {}, {}, {}, {}, {}, {}) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                    "INSERT INTO {} ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}) \
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
                     processed_messages::TABLE,
                     processed_messages::ORIGINAL_MESSAGE_ID,
                     processed_messages::PROCESSED_TEXT,
@@ -439,7 +1399,11 @@ impl Database {
                     processed_messages::NAMED_ENTITIES,
                     processed_messages::SENTIMENT_SCORE,
                     processed_messages::PROCESSED_AT,
-                    processed_messages::PROCESSING_VERSION
+                    processed_messages::PROCESSING_VERSION,
+                    processed_messages::FLAGS,
+                    processed_messages::FLAG_SEVERITY,
+                    processed_messages::LANGUAGE,
+                    processed_messages::SENTENCE_SENTIMENTS
                 ),
                 params![
                     new_processed.original_message_id,
@@ -449,7 +1413,11 @@ impl Database {
                     new_processed.named_entities,
                     new_processed.sentiment_score,
                     now,
-                    new_processed.processing_version
+                    new_processed.processing_version,
+                    new_processed.flags,
+                    new_processed.flag_severity,
+                    new_processed.language,
+                    new_processed.sentence_sentiments
                 ],
             )?;
             
@@ -467,6 +1435,10 @@ impl Database {
                 sentiment_score: new_processed.sentiment_score,
                 processed_at: now,
                 processing_version: new_processed.processing_version,
+                flags: new_processed.flags,
+                flag_severity: new_processed.flag_severity,
+                language: new_processed.language,
+                sentence_sentiments: new_processed.sentence_sentiments,
             })
         }
     }
@@ -483,6 +1455,10 @@ impl Database {
             sentiment_score: row.get(processed_messages::SENTIMENT_SCORE)?,
             processed_at: row.get(processed_messages::PROCESSED_AT)?,
             processing_version: row.get(processed_messages::PROCESSING_VERSION)?,
+            flags: row.get(processed_messages::FLAGS)?,
+            flag_severity: row.get(processed_messages::FLAG_SEVERITY)?,
+            language: row.get(processed_messages::LANGUAGE)?,
+            sentence_sentiments: row.get(processed_messages::SENTENCE_SENTIMENTS)?,
         })
     }
 
@@ -504,20 +1480,117 @@ impl Database {
         Ok(processed)
     }
 
-    /// Get all processed messages for a specific processing version
-    pub fn get_processed_messages_by_version(&self, version: &str) -> Result<Vec<DbProcessedMessage>> {
+    /// Get the processed messages stored for `version` among `message_ids`, in one query
+    pub fn get_processed_messages_for_ids(&self, message_ids: &[i32], version: &str) -> Result<Vec<DbProcessedMessage>> {
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
         let conn = self.get_connection()?;
-        
         let mut stmt = conn.prepare(&format!(
-            "SELECT * FROM {} WHERE {} = ?",
-            processed_messages::TABLE, processed_messages::PROCESSING_VERSION
+            "SELECT * FROM {} WHERE {} = ? AND {} IN ({})",
+            processed_messages::TABLE,
+            processed_messages::PROCESSING_VERSION,
+            processed_messages::ORIGINAL_MESSAGE_ID,
+            placeholders(message_ids.len())
         ))?;
-        
-        let processed_iter = stmt.query_map(params![version], |row| {
-            self.map_db_processed_message(row)
-        })?;
-        
-        let mut results = Vec::new();
+
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&version];
+        values.extend(message_ids.iter().map(|id| id as &dyn rusqlite::ToSql));
+        let processed_iter = stmt.query_map(values.as_slice(), |row| self.map_db_processed_message(row))?;
+
+        let mut results = Vec::new();
+        for processed in processed_iter {
+            results.push(processed?);
+        }
+
+        Ok(results)
+    }
+
+    /// Insert a batch of processed messages in a single transaction with one prepared statement.
+    ///
+    /// Messages already processed with the same version are skipped; only newly inserted rows are
+    /// returned.
+    pub fn add_processed_messages(&self, batch: Vec<NewProcessedMessage>) -> Result<Vec<DbProcessedMessage>> {
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()?;
+        let now = Utc::now().naive_utc();
+        let mut inserted = Vec::with_capacity(batch.len());
+
+        {
+            let mut stmt = tx.prepare(&format!(
+                "INSERT OR IGNORE INTO {} ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                processed_messages::TABLE,
+                processed_messages::ORIGINAL_MESSAGE_ID,
+                processed_messages::PROCESSED_TEXT,
+                processed_messages::TOKENS,
+                processed_messages::LEMMATIZED_TEXT,
+                processed_messages::NAMED_ENTITIES,
+                processed_messages::SENTIMENT_SCORE,
+                processed_messages::PROCESSED_AT,
+                processed_messages::PROCESSING_VERSION,
+                processed_messages::FLAGS,
+                processed_messages::FLAG_SEVERITY,
+                processed_messages::LANGUAGE,
+                processed_messages::SENTENCE_SENTIMENTS
+            ))?;
+
+            for new_processed in batch {
+                let changed = stmt.execute(params![
+                    new_processed.original_message_id,
+                    new_processed.processed_text,
+                    new_processed.tokens,
+                    new_processed.lemmatized_text,
+                    new_processed.named_entities,
+                    new_processed.sentiment_score,
+                    now,
+                    new_processed.processing_version,
+                    new_processed.flags,
+                    new_processed.flag_severity,
+                    new_processed.language,
+                    new_processed.sentence_sentiments
+                ])?;
+                if changed == 0 {
+                    continue;
+                }
+
+                inserted.push(DbProcessedMessage {
+                    id: tx.last_insert_rowid() as i32,
+                    original_message_id: new_processed.original_message_id,
+                    processed_text: new_processed.processed_text,
+                    tokens: new_processed.tokens,
+                    lemmatized_text: new_processed.lemmatized_text,
+                    named_entities: new_processed.named_entities,
+                    sentiment_score: new_processed.sentiment_score,
+                    processed_at: now,
+                    processing_version: new_processed.processing_version,
+                    flags: new_processed.flags,
+                    flag_severity: new_processed.flag_severity,
+                    language: new_processed.language,
+                    sentence_sentiments: new_processed.sentence_sentiments,
+                });
+            }
+        }
+
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /// Get all processed messages for a specific processing version
+    pub fn get_processed_messages_by_version(&self, version: &str) -> Result<Vec<DbProcessedMessage>> {
+        let conn = self.get_connection()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} WHERE {} = ?",
+            processed_messages::TABLE, processed_messages::PROCESSING_VERSION
+        ))?;
+        
+        let processed_iter = stmt.query_map(params![version], |row| {
+            self.map_db_processed_message(row)
+        })?;
+        
+        let mut results = Vec::new();
         for processed in processed_iter {
             results.push(processed?);
         }
@@ -551,6 +1624,78 @@ impl Database {
         Ok(results)
     }
 
+    /// Get IDs of messages matching `filter` that have not been processed with `version`,
+    /// oldest first
+    pub fn get_unprocessed_message_ids_filtered(&self, version: &str, filter: &ProcessFilter) -> Result<Vec<i32>> {
+        let conn = self.get_connection()?;
+
+        let mut query = format!(
+            "SELECT m.{} FROM {} m LEFT JOIN {} p ON m.{} = p.{} AND p.{} = ? WHERE p.{} IS NULL \
+             AND m.{} IS NOT NULL",
+            messages::ID,
+            messages::TABLE,
+            processed_messages::TABLE,
+            messages::ID,
+            processed_messages::ORIGINAL_MESSAGE_ID,
+            processed_messages::PROCESSING_VERSION,
+            processed_messages::ID,
+            messages::TEXT
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(version.to_string())];
+
+        if let Some(contact_id) = filter.contact_id {
+            query.push_str(&format!(" AND m.{} = ?", messages::CONTACT_ID));
+            params.push(Box::new(contact_id));
+        }
+
+        if let Some(is_from_me) = filter.is_from_me {
+            query.push_str(&format!(" AND m.{} = ?", messages::IS_FROM_ME));
+            params.push(Box::new(is_from_me));
+        }
+
+        if let Some(min_length) = filter.min_length {
+            query.push_str(&format!(" AND LENGTH(m.{}) >= ?", messages::TEXT));
+            params.push(Box::new(min_length as i64));
+        }
+
+        let service = match filter.service {
+            ServiceFilter::All => None,
+            ServiceFilter::IMessage => Some("iMessage"),
+            ServiceFilter::Sms => Some("SMS"),
+        };
+        if let Some(service) = service {
+            query.push_str(&format!(" AND m.{} = ? COLLATE NOCASE", messages::SERVICE));
+            params.push(Box::new(service));
+        }
+
+        if let Some(start) = filter.start_date {
+            query.push_str(&format!(" AND m.{} >= ?", messages::DATE_CREATED));
+            params.push(Box::new(start));
+        }
+
+        if let Some(end) = filter.end_date {
+            query.push_str(&format!(" AND m.{} <= ?", messages::DATE_CREATED));
+            params.push(Box::new(end));
+        }
+
+        query.push_str(&format!(" ORDER BY m.{} ASC", messages::DATE_CREATED));
+
+        if let Some(limit) = filter.limit {
+            query.push_str(" LIMIT ?");
+            params.push(Box::new(limit as i64));
+        }
+
+        let mut stmt = conn.prepare(&query)?;
+        let id_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| row.get::<_, i32>(0))?;
+
+        let mut results = Vec::new();
+        for id in id_iter {
+            results.push(id?);
+        }
+
+        Ok(results)
+    }
+
     /// Get statistics about processed messages
     pub fn get_processing_stats(&self) -> Result<ProcessingStats> {
         let conn = self.get_connection()?;
@@ -583,26 +1728,1378 @@ impl Database {
             versions.push(version?);
         }
         
+        // Get message counts per service
+        let mut stmt = conn.prepare(&format!(
+            "SELECT COALESCE({}, 'Unknown'), COUNT(*) FROM {} GROUP BY 1 ORDER BY 2 DESC",
+            messages::SERVICE,
+            messages::TABLE
+        ))?;
+
+        let service_iter = stmt.query_map(params![], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+        })?;
+
+        let mut messages_by_service = Vec::new();
+        for service in service_iter {
+            messages_by_service.push(service?);
+        }
+
         Ok(ProcessingStats {
             total_messages: total_messages as usize,
             processed_messages: processed_messages as usize,
             processing_versions: versions,
+            messages_by_service,
         })
     }
-}
 
-/// Statistics about message processing
-#[derive(Debug)]
-pub struct ProcessingStats {
-    pub total_messages: usize,
-    pub processed_messages: usize,
-    pub processing_versions: Vec<String>,
+    /// Summarize every processing version stored in processed_messages
+    pub fn list_processing_versions(&self) -> Result<Vec<ProcessingVersionSummary>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, COUNT(*), MIN({}), MAX({}) FROM {} GROUP BY 1 ORDER BY 3",
+            processed_messages::PROCESSING_VERSION,
+            processed_messages::PROCESSED_AT,
+            processed_messages::PROCESSED_AT,
+            processed_messages::TABLE
+        ))?;
+
+        let version_iter = stmt.query_map(params![], |row| {
+            Ok(ProcessingVersionSummary {
+                version: row.get(0)?,
+                message_count: row.get::<_, i64>(1)? as usize,
+                first_processed_at: row.get(2)?,
+                last_processed_at: row.get(3)?,
+            })
+        })?;
+
+        let mut versions = Vec::new();
+        for version in version_iter {
+            versions.push(version?);
+        }
+
+        Ok(versions)
+    }
+
+    /// Count messages processed with both versions
+    pub fn count_shared_processed(&self, version_a: &str, version_b: &str) -> Result<usize> {
+        let conn = self.get_connection()?;
+
+        let count: i64 = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {table} a JOIN {table} b ON a.{original} = b.{original} \
+                 AND b.{version} = ?2 WHERE a.{version} = ?1",
+                table = processed_messages::TABLE,
+                original = processed_messages::ORIGINAL_MESSAGE_ID,
+                version = processed_messages::PROCESSING_VERSION
+            ),
+            params![version_a, version_b],
+            |row| row.get(0),
+        )?;
+
+        Ok(count as usize)
+    }
+
+    /// Pick a random sample of message IDs processed with both versions
+    pub fn sample_shared_processed_ids(&self, version_a: &str, version_b: &str, sample: usize) -> Result<Vec<i32>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT a.{original} FROM {table} a JOIN {table} b ON a.{original} = b.{original} \
+             AND b.{version} = ?2 WHERE a.{version} = ?1 ORDER BY RANDOM() LIMIT ?3",
+            table = processed_messages::TABLE,
+            original = processed_messages::ORIGINAL_MESSAGE_ID,
+            version = processed_messages::PROCESSING_VERSION
+        ))?;
+
+        let id_iter = stmt.query_map(params![version_a, version_b, sample as i64], |row| row.get::<_, i32>(0))?;
+
+        let mut results = Vec::new();
+        for id in id_iter {
+            results.push(id?);
+        }
+
+        Ok(results)
+    }
+
+    /// Count flagged messages per sender per month for a processing version
+    pub fn get_flag_report(
+        &self,
+        version: &str,
+        contact_id: Option<i32>,
+        min_severity: f32,
+    ) -> Result<Vec<FlagReportRow>> {
+        let conn = self.get_connection()?;
+
+        let mut query = format!(
+            "SELECT m.{sender}, strftime('%Y-%m', m.{date}), COUNT(*), MAX(p.{severity}), AVG(p.{severity}) \
+             FROM {processed} p JOIN {messages} m ON m.{id} = p.{original} \
+             WHERE p.{version} = ? AND p.{severity} > 0 AND p.{severity} >= ? AND m.{hidden} = 0",
+            sender = messages::SENDER,
+            hidden = messages::HIDDEN,
+            date = messages::DATE_CREATED,
+            severity = processed_messages::FLAG_SEVERITY,
+            processed = processed_messages::TABLE,
+            messages = messages::TABLE,
+            id = messages::ID,
+            original = processed_messages::ORIGINAL_MESSAGE_ID,
+            version = processed_messages::PROCESSING_VERSION
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(version.to_string()), Box::new(min_severity)];
+
+        if let Some(contact_id) = contact_id {
+            query.push_str(&format!(" AND m.{} = ?", messages::CONTACT_ID));
+            params.push(Box::new(contact_id));
+        }
+
+        query.push_str(" GROUP BY 1, 2 ORDER BY 2, 1");
+
+        let mut stmt = conn.prepare(&query)?;
+        let row_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            Ok(FlagReportRow {
+                sender: row.get(0)?,
+                month: row.get(1)?,
+                flagged_messages: row.get::<_, i64>(2)? as usize,
+                max_severity: row.get(3)?,
+                average_severity: row.get(4)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in row_iter {
+            results.push(row?);
+        }
+
+        Ok(results)
+    }
+
+    /// Delete every processed message stored for a version, returning the number deleted
+    pub fn purge_processing_version(&self, version: &str) -> Result<usize> {
+        let conn = self.get_connection()?;
+
+        let deleted = conn.execute(
+            &format!(
+                "DELETE FROM {} WHERE {} = ?",
+                processed_messages::TABLE,
+                processed_messages::PROCESSING_VERSION
+            ),
+            params![version],
+        )?;
+
+        Ok(deleted)
+    }
+
+    /// Page counts of the database file
+    pub fn database_size(&self) -> Result<DatabaseSize> {
+        let conn = self.get_connection()?;
+        let pragma = |name: &str| -> Result<u64> {
+            Ok(conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get::<_, i64>(0))? as u64)
+        };
+
+        Ok(DatabaseSize {
+            page_size: pragma("page_size")?,
+            pages: pragma("page_count")?,
+            free_pages: pragma("freelist_count")?,
+        })
+    }
+
+    /// Check the database for corruption, then rebuild its indexes, refresh the query planner's
+    /// statistics and VACUUM it to return the space freed by deletes. Nothing is rebuilt when the
+    /// integrity check finds problems, so they can be looked at first.
+    pub fn maintain(&self) -> Result<MaintenanceReport> {
+        let before = self.database_size()?;
+        let conn = self.get_connection()?;
+
+        let problems: Vec<String> = {
+            let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|row| row != "ok")
+                .collect()
+        };
+        if !problems.is_empty() {
+            return Ok(MaintenanceReport { before, after: before, problems });
+        }
+
+        conn.execute_batch("REINDEX").context("Failed to rebuild indexes")?;
+        conn.execute_batch("ANALYZE").context("Failed to analyze the database")?;
+        conn.execute_batch("VACUUM").context("Failed to vacuum the database")?;
+        drop(conn);
+
+        Ok(MaintenanceReport { before, after: self.database_size()?, problems })
+    }
+
+    /// Copy the database to `path` with SQLite's online backup API, which gives a consistent copy
+    /// even while other connections use the database
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        let conn = self.get_connection()?;
+        conn.backup(DatabaseName::Main, path, None)
+            .with_context(|| format!("Failed to back up the database to {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Replace the database's contents with the database at `path`, e.g. a snapshot, then run any
+    /// migrations it's missing. Taking `&mut self` means no pooled connection is in use, and the
+    /// pool is replaced afterwards, so every connection from then on opens the restored database.
+    pub fn restore_from(&mut self, path: &Path) -> Result<()> {
+        let mut conn = Connection::open(&self.database_url)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.restore(DatabaseName::Main, path, None::<fn(rusqlite::backup::Progress)>)
+            .with_context(|| format!("Failed to restore the database from {}", path.display()))?;
+        drop(conn);
+
+        self.pool = Self::connection_pool(&self.database_url)?;
+        self.migrate()
+    }
+
+    /// Delete messages stored more than once for a contact: same sender, direction, text and
+    /// time as an earlier row, e.g. after restoring an export of messages already imported
+    /// from chat.db. Duplicates with attachments are kept. Returns the number deleted.
+    pub fn delete_duplicate_messages(&self, contact_id: i32) -> Result<usize> {
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()?;
+
+        let duplicate_ids: Vec<i32> = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT d.{id} FROM {table} d WHERE d.{contact_id} = ? AND d.{has_attachments} = 0 \
+                 AND EXISTS (SELECT 1 FROM {table} o WHERE o.{contact_id} = d.{contact_id} \
+                 AND o.{sender} = d.{sender} AND o.{is_from_me} = d.{is_from_me} \
+                 AND o.{text} IS d.{text} AND o.{date} = d.{date} AND o.{id} < d.{id})",
+                id = messages::ID,
+                table = messages::TABLE,
+                contact_id = messages::CONTACT_ID,
+                has_attachments = messages::HAS_ATTACHMENTS,
+                sender = messages::SENDER,
+                is_from_me = messages::IS_FROM_ME,
+                text = messages::TEXT,
+                date = messages::DATE_CREATED
+            ))?;
+            let ids = stmt.query_map(params![contact_id], |row| row.get(0))?;
+            ids.collect::<rusqlite::Result<_>>()?
+        };
+
+        for chunk in duplicate_ids.chunks(500) {
+            let ids = placeholders(chunk.len());
+            let params = rusqlite::params_from_iter(chunk.iter());
+
+            tx.execute(
+                &format!(
+                    "UPDATE {} SET {} = NULL WHERE {} IN ({})",
+                    message_commitments::TABLE,
+                    message_commitments::ANSWERED_BY,
+                    message_commitments::ANSWERED_BY,
+                    ids
+                ),
+                params.clone(),
+            )?;
+            for (table, column) in [
+                (message_commitments::TABLE, message_commitments::MESSAGE_ID),
+                (message_events::TABLE, message_events::MESSAGE_ID),
+                (processed_messages::TABLE, processed_messages::ORIGINAL_MESSAGE_ID),
+                (message_revisions::TABLE, message_revisions::MESSAGE_ID),
+                (messages::TABLE, messages::ID),
+            ] {
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE {} IN ({})", table, column, ids),
+                    params.clone(),
+                )?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(duplicate_ids.len())
+    }
+
+    /// Delete messages sent before `before` (UTC), with everything stored about them, in one
+    /// transaction. With `contact_id` only that contact's messages are deleted; messages linked
+    /// to a contact in `keep_contact_ids` are never deleted. With `dry_run` the transaction is
+    /// rolled back, so the counts preview the purge without changing anything.
+    pub fn purge_messages(
+        &self,
+        before: NaiveDateTime,
+        contact_id: Option<i32>,
+        keep_contact_ids: &[i32],
+        dry_run: bool,
+    ) -> Result<PurgeCounts> {
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()?;
+
+        let mut selected = format!(
+            "SELECT {} FROM {} WHERE {} < ?",
+            messages::ID,
+            messages::TABLE,
+            messages::DATE_CREATED
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(before)];
+
+        if let Some(contact_id) = contact_id {
+            selected.push_str(&format!(" AND {} = ?", messages::CONTACT_ID));
+            params.push(Box::new(contact_id));
+        }
+
+        if !keep_contact_ids.is_empty() {
+            selected.push_str(&format!(
+                " AND ({} IS NULL OR {} NOT IN ({}))",
+                messages::CONTACT_ID,
+                messages::CONTACT_ID,
+                placeholders(keep_contact_ids.len())
+            ));
+            params.extend(keep_contact_ids.iter().map(|id| Box::new(*id) as Box<dyn rusqlite::ToSql>));
+        }
+
+        // Messages come before the rows referencing them, so undo restores them first
+        let mut journal = UndoJournal::new("purge", &format!("Purged messages sent before {}", before));
+        if !dry_run {
+            let capture = |table: &str, condition: String| {
+                JournalTable::capture(&tx, table, &condition, rusqlite::params_from_iter(params.iter()))
+            };
+            let in_selected = |column: &str| format!("{} IN ({})", column, selected);
+
+            journal.tables.push(capture(messages::TABLE, in_selected(messages::ID))?);
+            journal.tables.push(capture(attachments::TABLE, in_selected(attachments::MESSAGE_ID))?);
+            journal.tables.push(capture(
+                attachment_transcripts::TABLE,
+                in_selected(attachment_transcripts::MESSAGE_ID),
+            )?);
+            journal.tables.push(capture(
+                processed_messages::TABLE,
+                in_selected(processed_messages::ORIGINAL_MESSAGE_ID),
+            )?);
+            journal.tables.push(capture(message_revisions::TABLE, in_selected(message_revisions::MESSAGE_ID))?);
+            journal.tables.push(capture(message_events::TABLE, in_selected(message_events::MESSAGE_ID))?);
+
+            // The selection is used twice, so its parameters are bound twice
+            journal.tables.push(JournalTable::capture(
+                &tx,
+                message_commitments::TABLE,
+                &format!(
+                    "{} OR {}",
+                    in_selected(message_commitments::MESSAGE_ID),
+                    in_selected(message_commitments::ANSWERED_BY)
+                ),
+                rusqlite::params_from_iter(params.iter().chain(params.iter())),
+            )?);
+        }
+
+        let delete = |table: &str, column: &str| -> Result<usize> {
+            Ok(tx.execute(
+                &format!("DELETE FROM {} WHERE {} IN ({})", table, column, selected),
+                rusqlite::params_from_iter(params.iter()),
+            )?)
+        };
+
+        // Answers to kept questions may be purged, so unlink them before deleting anything
+        tx.execute(
+            &format!(
+                "UPDATE {} SET {} = NULL WHERE {} IN ({})",
+                message_commitments::TABLE,
+                message_commitments::ANSWERED_BY,
+                message_commitments::ANSWERED_BY,
+                selected
+            ),
+            rusqlite::params_from_iter(params.iter()),
+        )?;
+
+        let counts = PurgeCounts {
+            commitments: delete(message_commitments::TABLE, message_commitments::MESSAGE_ID)?,
+            events: delete(message_events::TABLE, message_events::MESSAGE_ID)?,
+            processed_messages: delete(processed_messages::TABLE, processed_messages::ORIGINAL_MESSAGE_ID)?,
+            revisions: delete(message_revisions::TABLE, message_revisions::MESSAGE_ID)?,
+            attachment_transcripts: delete(attachment_transcripts::TABLE, attachment_transcripts::MESSAGE_ID)?,
+            attachments: delete(attachments::TABLE, attachments::MESSAGE_ID)?,
+            messages: delete(messages::TABLE, messages::ID)?,
+        };
+
+        // Only offered to `undo` once the purge is committed
+        let journal = (!dry_run && journal.rows() > 0).then(|| journal.write(&self.journal_dir)).transpose()?;
+        if !dry_run {
+            tx.commit()?;
+        }
+        if let Some(journal) = journal {
+            journal.commit()?;
+        }
+        Ok(counts)
+    }
+
+    /// Journals of purges and merges that can still be undone, oldest first
+    pub fn undo_journals(&self) -> Result<Vec<(PathBuf, UndoJournal)>> {
+        UndoJournal::list(&self.journal_dir)
+    }
+
+    /// Restore the rows changed by the most recent purge or merge that hasn't been undone yet,
+    /// returning its journal, or `None` when there is nothing to undo
+    pub fn undo_last(&self) -> Result<Option<UndoJournal>> {
+        let Some((path, journal)) = self.undo_journals()?.pop() else {
+            return Ok(None);
+        };
+
+        let mut conn = self.get_connection()?;
+        journal.undo(&mut conn, &path)?;
+        Ok(Some(journal))
+    }
+
+    /// Record the start of an export so its progress can be tracked
+    pub fn create_export_run(&self, query_hash: &str, output_dir: &str, total_chunks: usize) -> Result<DbExportRun> {
+        let conn = self.get_connection()?;
+
+        conn.execute(
+            &format!(
+                "INSERT INTO {} ({}, {}, {}) VALUES (?, ?, ?)",
+                export_runs::TABLE,
+                export_runs::QUERY_HASH,
+                export_runs::OUTPUT_DIR,
+                export_runs::TOTAL_CHUNKS
+            ),
+            params![query_hash, output_dir, total_chunks as i64],
+        )?;
+
+        let id = conn.last_insert_rowid() as i32;
+        drop(conn);
+        self.get_export_run(id)?
+            .ok_or_else(|| anyhow::anyhow!("Export {} was not recorded", id))
+    }
+
+    /// Get a recorded export by ID
+    pub fn get_export_run(&self, id: i32) -> Result<Option<DbExportRun>> {
+        let conn = self.get_connection()?;
+
+        let run = conn
+            .query_row(
+                &format!("SELECT * FROM {} WHERE {} = ?", export_runs::TABLE, export_runs::ID),
+                params![id],
+                |row| {
+                    Ok(DbExportRun {
+                        id: row.get(export_runs::ID)?,
+                        query_hash: row.get(export_runs::QUERY_HASH)?,
+                        output_dir: row.get(export_runs::OUTPUT_DIR)?,
+                        total_chunks: row.get::<_, i64>(export_runs::TOTAL_CHUNKS)? as usize,
+                        started_at: row.get(export_runs::STARTED_AT)?,
+                        finished_at: row.get(export_runs::FINISHED_AT)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(run)
+    }
+
+    /// Get the chunks an export has finished writing, in chunk order
+    pub fn get_export_chunks(&self, export_id: i32) -> Result<Vec<DbExportChunk>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} WHERE {} = ? ORDER BY {} ASC",
+            export_chunks::TABLE,
+            export_chunks::EXPORT_ID,
+            export_chunks::CHUNK_INDEX
+        ))?;
+
+        let chunk_iter = stmt.query_map(params![export_id], |row| {
+            Ok(DbExportChunk {
+                export_id: row.get(export_chunks::EXPORT_ID)?,
+                chunk_index: row.get::<_, i64>(export_chunks::CHUNK_INDEX)? as usize,
+                path: row.get(export_chunks::PATH)?,
+                sha256: row.get(export_chunks::SHA256)?,
+                messages: row.get::<_, i64>(export_chunks::MESSAGES)? as usize,
+                bytes: row.get::<_, i64>(export_chunks::BYTES)? as u64,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for chunk in chunk_iter {
+            results.push(chunk?);
+        }
+
+        Ok(results)
+    }
+
+    /// Record a chunk file an export finished writing, replacing any earlier record of that chunk
+    pub fn record_export_chunk(&self, chunk: &DbExportChunk) -> Result<()> {
+        let conn = self.get_connection()?;
+
+        conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} ({}, {}, {}, {}, {}, {}, {}) VALUES (?, ?, ?, ?, ?, ?, ?)",
+                export_chunks::TABLE,
+                export_chunks::EXPORT_ID,
+                export_chunks::CHUNK_INDEX,
+                export_chunks::PATH,
+                export_chunks::SHA256,
+                export_chunks::MESSAGES,
+                export_chunks::BYTES,
+                export_chunks::WRITTEN_AT
+            ),
+            params![
+                chunk.export_id,
+                chunk.chunk_index as i64,
+                chunk.path,
+                chunk.sha256,
+                chunk.messages as i64,
+                chunk.bytes as i64,
+                Utc::now().naive_utc()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Mark an export as finished
+    pub fn finish_export_run(&self, id: i32) -> Result<()> {
+        let conn = self.get_connection()?;
+
+        conn.execute(
+            &format!(
+                "UPDATE {} SET {} = ? WHERE {} = ?",
+                export_runs::TABLE,
+                export_runs::FINISHED_AT,
+                export_runs::ID
+            ),
+            params![Utc::now().naive_utc(), id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Store detected questions, commitments and agreements in one transaction. Re-detected
+    /// entries keep their row but pick up a newly found answer.
+    pub fn save_commitments(&self, commitments: &[NewCommitment]) -> Result<usize> {
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut stmt = tx.prepare(&format!(
+                "INSERT INTO {table} ({message_id}, {kind}, {snippet}, {answered_by}) VALUES (?, ?, ?, ?) \
+                 ON CONFLICT({message_id}, {kind}, {snippet}) \
+                 DO UPDATE SET {answered_by} = COALESCE(excluded.{answered_by}, {answered_by})",
+                table = message_commitments::TABLE,
+                message_id = message_commitments::MESSAGE_ID,
+                kind = message_commitments::KIND,
+                snippet = message_commitments::SNIPPET,
+                answered_by = message_commitments::ANSWERED_BY
+            ))?;
+
+            for commitment in commitments {
+                stmt.execute(params![
+                    commitment.message_id,
+                    commitment.kind.as_str(),
+                    commitment.snippet,
+                    commitment.answered_by
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(commitments.len())
+    }
+
+    /// Get detected questions, commitments and agreements with their messages, oldest first
+    pub fn get_commitment_report(
+        &self,
+        contact_id: Option<i32>,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<Vec<CommitmentReportEntry>> {
+        let conn = self.get_connection()?;
+
+        let mut query = format!(
+            "SELECT c.{message_id}, c.{kind}, c.{snippet}, m.{sender}, m.{date}, c.{answered_by}, a.{text} \
+             FROM {commitments} c JOIN {messages} m ON m.{id} = c.{message_id} \
+             LEFT JOIN {messages} a ON a.{id} = c.{answered_by} WHERE m.{hidden} = 0",
+            message_id = message_commitments::MESSAGE_ID,
+            hidden = messages::HIDDEN,
+            kind = message_commitments::KIND,
+            snippet = message_commitments::SNIPPET,
+            answered_by = message_commitments::ANSWERED_BY,
+            sender = messages::SENDER,
+            date = messages::DATE_CREATED,
+            text = messages::TEXT,
+            commitments = message_commitments::TABLE,
+            messages = messages::TABLE,
+            id = messages::ID
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(contact_id) = contact_id {
+            query.push_str(&format!(" AND m.{} = ?", messages::CONTACT_ID));
+            params.push(Box::new(contact_id));
+        }
+
+        if let Some(start) = start_date {
+            query.push_str(&format!(" AND m.{} >= ?", messages::DATE_CREATED));
+            params.push(Box::new(start));
+        }
+
+        if let Some(end) = end_date {
+            query.push_str(&format!(" AND m.{} <= ?", messages::DATE_CREATED));
+            params.push(Box::new(end));
+        }
+
+        query.push_str(&format!(" ORDER BY m.{} ASC, c.{} ASC", messages::DATE_CREATED, message_commitments::ID));
+
+        let mut stmt = conn.prepare(&query)?;
+        let entry_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, NaiveDateTime>(4)?,
+                row.get::<_, Option<i32>>(5)?,
+                row.get::<_, Option<String>>(6)?,
+            ))
+        })?;
+
+        let mut results = Vec::new();
+        for entry in entry_iter {
+            let (message_id, kind, snippet, sender, date_created, answered_by, answer) = entry?;
+            results.push(CommitmentReportEntry {
+                message_id,
+                kind: CommitmentKind::parse(&kind)?,
+                snippet,
+                sender,
+                date_created,
+                answered_by,
+                answer,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Store detected events, ignoring ones already stored for the same message and expression.
+    /// Returns the number detected.
+    pub fn save_events(&self, events: &[NewEvent]) -> Result<usize> {
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut stmt = tx.prepare(&format!(
+                "INSERT INTO {table} ({message_id}, {starts_on}, {starts_at}, {expression}, {snippet}) \
+                 VALUES (?, ?, ?, ?, ?) \
+                 ON CONFLICT({message_id}, {expression}) \
+                 DO UPDATE SET {starts_on} = excluded.{starts_on}, {starts_at} = excluded.{starts_at}, \
+                 {snippet} = excluded.{snippet}",
+                table = message_events::TABLE,
+                message_id = message_events::MESSAGE_ID,
+                starts_on = message_events::STARTS_ON,
+                starts_at = message_events::STARTS_AT,
+                expression = message_events::EXPRESSION,
+                snippet = message_events::SNIPPET
+            ))?;
+
+            for event in events {
+                stmt.execute(params![
+                    event.message_id,
+                    event.starts_on,
+                    event.starts_at,
+                    event.expression,
+                    event.snippet
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(events.len())
+    }
+
+    /// Get the cached summary of a transcript by a model
+    pub fn get_summary(&self, input_hash: &str, model: &str) -> Result<Option<String>> {
+        let conn = self.get_connection()?;
+
+        let summary = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM {} WHERE {} = ? AND {} = ?",
+                    summaries::SUMMARY,
+                    summaries::TABLE,
+                    summaries::INPUT_HASH,
+                    summaries::MODEL
+                ),
+                params![input_hash, model],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(summary)
+    }
+
+    /// Cache a summary, replacing any earlier one of the same transcript by the same model
+    pub fn save_summary(&self, summary: &NewSummary) -> Result<()> {
+        let conn = self.get_connection()?;
+
+        conn.execute(
+            &format!(
+                "INSERT INTO {table} ({input_hash}, {model}, {unit}, {unit_key}, {summary}) \
+                 VALUES (?, ?, ?, ?, ?) \
+                 ON CONFLICT({input_hash}, {model}) \
+                 DO UPDATE SET {unit} = excluded.{unit}, {unit_key} = excluded.{unit_key}, \
+                 {summary} = excluded.{summary}, {created_at} = CURRENT_TIMESTAMP",
+                table = summaries::TABLE,
+                input_hash = summaries::INPUT_HASH,
+                model = summaries::MODEL,
+                unit = summaries::UNIT,
+                unit_key = summaries::UNIT_KEY,
+                summary = summaries::SUMMARY,
+                created_at = summaries::CREATED_AT
+            ),
+            params![summary.input_hash, summary.model, summary.unit, summary.unit_key, summary.summary],
+        )?;
+
+        Ok(())
+    }
+
+    /// Get detected events with their messages, by event date. `start_date` and `end_date`
+    /// filter on when the messages were sent.
+    pub fn get_event_report(
+        &self,
+        contact_id: Option<i32>,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<Vec<EventReportEntry>> {
+        let conn = self.get_connection()?;
+
+        let mut query = format!(
+            "SELECT e.{message_id}, m.{imessage_id}, m.{sender}, m.{date}, e.{starts_on}, e.{starts_at}, \
+             e.{expression}, e.{snippet} \
+             FROM {events} e JOIN {messages} m ON m.{id} = e.{message_id} WHERE m.{hidden} = 0",
+            message_id = message_events::MESSAGE_ID,
+            imessage_id = messages::IMESSAGE_ID,
+            sender = messages::SENDER,
+            date = messages::DATE_CREATED,
+            starts_on = message_events::STARTS_ON,
+            starts_at = message_events::STARTS_AT,
+            expression = message_events::EXPRESSION,
+            snippet = message_events::SNIPPET,
+            events = message_events::TABLE,
+            messages = messages::TABLE,
+            id = messages::ID,
+            hidden = messages::HIDDEN
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(contact_id) = contact_id {
+            query.push_str(&format!(" AND m.{} = ?", messages::CONTACT_ID));
+            params.push(Box::new(contact_id));
+        }
+
+        if let Some(start) = start_date {
+            query.push_str(&format!(" AND m.{} >= ?", messages::DATE_CREATED));
+            params.push(Box::new(start));
+        }
+
+        if let Some(end) = end_date {
+            query.push_str(&format!(" AND m.{} <= ?", messages::DATE_CREATED));
+            params.push(Box::new(end));
+        }
+
+        query.push_str(&format!(
+            " ORDER BY e.{} ASC, e.{} ASC, e.{} ASC",
+            message_events::STARTS_ON,
+            message_events::STARTS_AT,
+            message_events::ID
+        ));
+
+        let mut stmt = conn.prepare(&query)?;
+        let entry_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            Ok(EventReportEntry {
+                message_id: row.get(0)?,
+                imessage_id: row.get(1)?,
+                sender: row.get(2)?,
+                date_created: row.get(3)?,
+                starts_on: row.get(4)?,
+                starts_at: row.get(5)?,
+                expression: row.get(6)?,
+                snippet: row.get(7)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for entry in entry_iter {
+            results.push(entry?);
+        }
+
+        Ok(results)
+    }
+
+    /// Count visible messages per local day of week and hour, and per local calendar day. The
+    /// grouping happens in SQLite so large archives never leave the database.
+    pub fn get_activity_heatmap(
+        &self,
+        contact_id: Option<i32>,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<ActivityHeatmap> {
+        let conn = self.get_connection()?;
+
+        let mut filter = format!("WHERE {} = 0", messages::HIDDEN);
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(contact_id) = contact_id {
+            filter.push_str(&format!(" AND {} = ?", messages::CONTACT_ID));
+            params.push(Box::new(contact_id));
+        }
+
+        if let Some(start) = start_date {
+            filter.push_str(&format!(" AND {} >= ?", messages::DATE_CREATED));
+            params.push(Box::new(start));
+        }
+
+        if let Some(end) = end_date {
+            filter.push_str(&format!(" AND {} <= ?", messages::DATE_CREATED));
+            params.push(Box::new(end));
+        }
+
+        // Dates are stored in UTC, so shift them to local time before grouping
+        let weekly_query = format!(
+            "SELECT CAST(strftime('%w', {date}, 'localtime') AS INTEGER), \
+             CAST(strftime('%H', {date}, 'localtime') AS INTEGER), COUNT(*) \
+             FROM {messages} {filter} GROUP BY 1, 2",
+            date = messages::DATE_CREATED,
+            messages = messages::TABLE,
+            filter = filter
+        );
+        let mut stmt = conn.prepare(&weekly_query)?;
+        let weekly = stmt
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                Ok((row.get::<_, i64>(0)? as usize, row.get::<_, i64>(1)? as usize, row.get::<_, i64>(2)? as usize))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let daily_query = format!(
+            "SELECT date({date}, 'localtime'), COUNT(*) FROM {messages} {filter} GROUP BY 1 ORDER BY 1",
+            date = messages::DATE_CREATED,
+            messages = messages::TABLE,
+            filter = filter
+        );
+        let mut stmt = conn.prepare(&daily_query)?;
+        let daily = stmt
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                Ok((row.get::<_, NaiveDate>(0)?, row.get::<_, i64>(1)? as usize))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(ActivityHeatmap::from_counts(&weekly, &daily))
+    }
+
+    /// Get the detected language of each message processed with `version`, oldest first, with
+    /// dates in local time
+    pub fn get_message_languages(
+        &self,
+        version: &str,
+        contact_id: i32,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<Vec<LanguageSample>> {
+        let conn = self.get_connection()?;
+
+        let mut query = format!(
+            "SELECT datetime(m.{date}, 'localtime'), m.{sender}, p.{language} \
+             FROM {processed} p JOIN {messages} m ON m.{id} = p.{original} \
+             WHERE p.{version} = ? AND m.{contact} = ? AND m.{hidden} = 0",
+            date = messages::DATE_CREATED,
+            sender = messages::SENDER,
+            language = processed_messages::LANGUAGE,
+            processed = processed_messages::TABLE,
+            messages = messages::TABLE,
+            id = messages::ID,
+            original = processed_messages::ORIGINAL_MESSAGE_ID,
+            version = processed_messages::PROCESSING_VERSION,
+            contact = messages::CONTACT_ID,
+            hidden = messages::HIDDEN
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(version.to_string()), Box::new(contact_id)];
+
+        if let Some(start) = start_date {
+            query.push_str(&format!(" AND m.{} >= ?", messages::DATE_CREATED));
+            params.push(Box::new(start));
+        }
+
+        if let Some(end) = end_date {
+            query.push_str(&format!(" AND m.{} <= ?", messages::DATE_CREATED));
+            params.push(Box::new(end));
+        }
+
+        query.push_str(&format!(" ORDER BY m.{}, m.{}", messages::DATE_CREATED, messages::ID));
+
+        let mut stmt = conn.prepare(&query)?;
+        let samples = stmt
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                Ok(LanguageSample { sent_at: row.get(0)?, sender: row.get(1)?, language: row.get(2)? })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(samples)
+    }
+
+    /// Processed messages with named entities for a version, oldest first. With `entity_type`,
+    /// only messages that have an entity of that type are returned.
+    pub fn get_entity_messages(
+        &self,
+        version: &str,
+        contact_id: Option<i32>,
+        entity_type: Option<&str>,
+    ) -> Result<Vec<EntityMessage>> {
+        let conn = self.get_connection()?;
+
+        let mut query = format!(
+            "SELECT datetime(m.{date}, 'localtime'), m.{sender}, p.{entities} \
+             FROM {processed} p JOIN {messages} m ON m.{id} = p.{original} \
+             WHERE p.{version} = ? AND p.{entities} IS NOT NULL AND m.{hidden} = 0",
+            date = messages::DATE_CREATED,
+            sender = messages::SENDER,
+            entities = processed_messages::NAMED_ENTITIES,
+            processed = processed_messages::TABLE,
+            messages = messages::TABLE,
+            id = messages::ID,
+            original = processed_messages::ORIGINAL_MESSAGE_ID,
+            version = processed_messages::PROCESSING_VERSION,
+            hidden = messages::HIDDEN
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(version.to_string())];
+
+        if let Some(contact_id) = contact_id {
+            query.push_str(&format!(" AND m.{} = ?", messages::CONTACT_ID));
+            params.push(Box::new(contact_id));
+        }
+
+        // Narrow by the type in the stored JSON; callers still check each entity
+        if let Some(entity_type) = entity_type {
+            query.push_str(&format!(" AND p.{} LIKE ?", processed_messages::NAMED_ENTITIES));
+            params.push(Box::new(format!("%\"entity_type\":\"{}\"%", entity_type)));
+        }
+
+        query.push_str(&format!(" ORDER BY m.{}, m.{}", messages::DATE_CREATED, messages::ID));
+
+        let mut stmt = conn.prepare(&query)?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                Ok((row.get::<_, NaiveDateTime>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(sent_at, sender, entities)| EntityMessage {
+                sent_at,
+                sender,
+                entities: serde_json::from_str(&entities).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Messages processed with `version`, each joined with its processed row and the name of
+    /// its contact, oldest first. Hidden messages are left out.
+    pub fn get_nlp_export_rows(
+        &self,
+        version: &str,
+        contact_id: Option<i32>,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<Vec<NlpExportRow>> {
+        let conn = self.get_connection()?;
+
+        let mut query = format!(
+            "SELECT m.{id}, m.{imessage_id}, datetime(m.{date}, 'localtime'), c.{name}, m.{sender}, \
+                 m.{is_from_me}, m.{service}, m.{text}, p.{processed_text}, p.{tokens}, p.{lemmatized}, \
+                 p.{entities}, p.{sentiment}, p.{language}, p.{flag_severity}, p.{version}, p.{processed_at}, \
+                 p.{sentences} \
+             FROM {processed} p JOIN {messages} m ON m.{id} = p.{original} \
+             LEFT JOIN {contacts} c ON c.{contact_pk} = m.{contact_id} \
+             WHERE p.{version} = ? AND m.{hidden} = 0",
+            id = messages::ID,
+            imessage_id = messages::IMESSAGE_ID,
+            date = messages::DATE_CREATED,
+            name = contacts::NAME,
+            sender = messages::SENDER,
+            is_from_me = messages::IS_FROM_ME,
+            service = messages::SERVICE,
+            text = messages::TEXT,
+            processed_text = processed_messages::PROCESSED_TEXT,
+            tokens = processed_messages::TOKENS,
+            lemmatized = processed_messages::LEMMATIZED_TEXT,
+            entities = processed_messages::NAMED_ENTITIES,
+            sentiment = processed_messages::SENTIMENT_SCORE,
+            language = processed_messages::LANGUAGE,
+            flag_severity = processed_messages::FLAG_SEVERITY,
+            version = processed_messages::PROCESSING_VERSION,
+            processed_at = processed_messages::PROCESSED_AT,
+            sentences = processed_messages::SENTENCE_SENTIMENTS,
+            processed = processed_messages::TABLE,
+            messages = messages::TABLE,
+            original = processed_messages::ORIGINAL_MESSAGE_ID,
+            contacts = contacts::TABLE,
+            contact_pk = contacts::ID,
+            contact_id = messages::CONTACT_ID,
+            hidden = messages::HIDDEN
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(version.to_string())];
+
+        if let Some(contact_id) = contact_id {
+            query.push_str(&format!(" AND m.{} = ?", messages::CONTACT_ID));
+            params.push(Box::new(contact_id));
+        }
+
+        if let Some(start) = start_date {
+            query.push_str(&format!(" AND m.{} >= ?", messages::DATE_CREATED));
+            params.push(Box::new(start));
+        }
+
+        if let Some(end) = end_date {
+            query.push_str(&format!(" AND m.{} <= ?", messages::DATE_CREATED));
+            params.push(Box::new(end));
+        }
+
+        query.push_str(&format!(" ORDER BY m.{}, m.{}", messages::DATE_CREATED, messages::ID));
+
+        let mut stmt = conn.prepare(&query)?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                let tokens: Option<String> = row.get(9)?;
+                let entities: Option<String> = row.get(11)?;
+                let sentences: Option<String> = row.get(17)?;
+                Ok(NlpExportRow {
+                    message_id: row.get(0)?,
+                    imessage_id: row.get(1)?,
+                    sent_at: row.get(2)?,
+                    contact: row.get(3)?,
+                    sender: row.get(4)?,
+                    is_from_me: row.get(5)?,
+                    service: row.get(6)?,
+                    text: row.get(7)?,
+                    processed_text: row.get(8)?,
+                    tokens: split_tokens(tokens.as_deref()),
+                    lemmatized_text: row.get(10)?,
+                    entities: entities.and_then(|e| serde_json::from_str(&e).ok()).unwrap_or_default(),
+                    sentiment: row.get(12)?,
+                    sentences: sentences.and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default(),
+                    language: row.get(13)?,
+                    flag_severity: row.get(14)?,
+                    processing_version: row.get(15)?,
+                    processed_at: row.get(16)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(rows)
+    }
+
+    /// Record an attachment for a message if it hasn't been recorded yet
+    pub fn add_attachment(&self, new_attachment: NewAttachment) -> Result<DbAttachment> {
+        let conn = self.get_connection()?;
+
+        // Check if attachment already exists for this message
+        let existing: Option<DbAttachment> = conn.query_row(
+            &format!(
+                "SELECT * FROM {} WHERE {} = ? AND {} IS ?",
+                attachments::TABLE, attachments::MESSAGE_ID, attachments::FILENAME
+            ),
+            params![new_attachment.message_id, new_attachment.filename],
+            |row| self.map_db_attachment(row)
+        ).optional()?;
+
+        if let Some(attachment) = existing {
+            return Ok(attachment);
+        }
+
+        conn.execute(
+            &format!(
+                "INSERT INTO {} ({}, {}, {}, {}, {}) VALUES (?, ?, ?, ?, ?)",
+                attachments::TABLE,
+                attachments::MESSAGE_ID,
+                attachments::FILENAME,
+                attachments::MIME_TYPE,
+                attachments::SIZE_BYTES,
+                attachments::CREATED_AT
+            ),
+            params![
+                new_attachment.message_id,
+                new_attachment.filename,
+                new_attachment.mime_type,
+                new_attachment.size_bytes,
+                new_attachment.created_at
+            ],
+        )?;
+
+        Ok(DbAttachment {
+            id: conn.last_insert_rowid() as i32,
+            message_id: new_attachment.message_id,
+            filename: new_attachment.filename,
+            mime_type: new_attachment.mime_type,
+            size_bytes: new_attachment.size_bytes,
+            created_at: new_attachment.created_at,
+        })
+    }
+
+    /// Map a database row to a DbAttachment
+    fn map_db_attachment(&self, row: &Row) -> rusqlite::Result<DbAttachment> {
+        Ok(DbAttachment {
+            id: row.get(attachments::ID)?,
+            message_id: row.get(attachments::MESSAGE_ID)?,
+            filename: row.get(attachments::FILENAME)?,
+            mime_type: row.get(attachments::MIME_TYPE)?,
+            size_bytes: row.get(attachments::SIZE_BYTES)?,
+            created_at: row.get(attachments::CREATED_AT)?,
+        })
+    }
+
+    /// Get all attachments that have no transcript from the given processor
+    pub fn get_untranscribed_attachments(&self, processor: &str) -> Result<Vec<DbAttachment>> {
+        let conn = self.get_connection()?;
+
+        let query = format!(
+            "SELECT a.* FROM {} a LEFT JOIN {} t ON a.{} = t.{} AND t.{} = ? WHERE t.{} IS NULL",
+            attachments::TABLE,
+            attachment_transcripts::TABLE,
+            attachments::ID,
+            attachment_transcripts::ATTACHMENT_ID,
+            attachment_transcripts::PROCESSOR,
+            attachment_transcripts::ID
+        );
+
+        let mut stmt = conn.prepare(&query)?;
+        let attachment_iter = stmt.query_map(params![processor], |row| {
+            self.map_db_attachment(row)
+        })?;
+
+        let mut results = Vec::new();
+        for attachment in attachment_iter {
+            results.push(attachment?);
+        }
+
+        Ok(results)
+    }
+
+    /// Store a transcript extracted from an attachment
+    pub fn add_attachment_transcript(
+        &self,
+        attachment: &DbAttachment,
+        processor: &str,
+        transcript: &str,
+    ) -> Result<()> {
+        let conn = self.get_connection()?;
+
+        conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} ({}, {}, {}, {}, {}) VALUES (?, ?, ?, ?, ?)",
+                attachment_transcripts::TABLE,
+                attachment_transcripts::ATTACHMENT_ID,
+                attachment_transcripts::MESSAGE_ID,
+                attachment_transcripts::PROCESSOR,
+                attachment_transcripts::TRANSCRIPT,
+                attachment_transcripts::CREATED_AT
+            ),
+            params![
+                attachment.id,
+                attachment.message_id,
+                processor,
+                transcript,
+                Utc::now().naive_utc()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Get all transcripts attached to a message
+    pub fn get_transcripts_for_message(&self, message_id: i32) -> Result<Vec<DbAttachmentTranscript>> {
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} WHERE {} = ? ORDER BY {} ASC",
+            attachment_transcripts::TABLE,
+            attachment_transcripts::MESSAGE_ID,
+            attachment_transcripts::ATTACHMENT_ID
+        ))?;
+
+        let transcript_iter = stmt.query_map(params![message_id], |row| {
+            Ok(DbAttachmentTranscript {
+                id: row.get(attachment_transcripts::ID)?,
+                attachment_id: row.get(attachment_transcripts::ATTACHMENT_ID)?,
+                message_id: row.get(attachment_transcripts::MESSAGE_ID)?,
+                processor: row.get(attachment_transcripts::PROCESSOR)?,
+                transcript: row.get(attachment_transcripts::TRANSCRIPT)?,
+                created_at: row.get(attachment_transcripts::CREATED_AT)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for transcript in transcript_iter {
+            results.push(transcript?);
+        }
+
+        Ok(results)
+    }
+
+    /// Convert messages for export, appending each one's attachment transcripts to its text
+    pub fn to_messages_with_transcripts(&self, db_messages: &[DbMessage]) -> Result<Vec<Message>> {
+        let mut messages = Vec::with_capacity(db_messages.len());
+        for db_message in db_messages {
+            let mut message = db_message.to_message();
+            for transcript in self.get_transcripts_for_message(db_message.id)? {
+                message.content.push_str(&format!(" [Transcript: {}]", transcript.transcript));
+            }
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
+    /// List attachments of visible messages in a date range, oldest first. `mime_prefix` keeps
+    /// only types starting with it, e.g. "image/".
+    pub fn get_attachment_report(
+        &self,
+        contact_id: Option<i32>,
+        start_date: Option<NaiveDateTime>,
+        end_date: Option<NaiveDateTime>,
+        mime_prefix: Option<&str>,
+    ) -> Result<Vec<AttachmentReportEntry>> {
+        let conn = self.get_connection()?;
+
+        let mut query = format!(
+            "SELECT a.{id}, a.{message_id}, m.{sender}, m.{date}, a.{filename}, a.{mime_type}, a.{size}, m.{text} \
+             FROM {attachments} a JOIN {messages} m ON m.{message_pk} = a.{message_id} WHERE m.{hidden} = 0",
+            id = attachments::ID,
+            message_id = attachments::MESSAGE_ID,
+            sender = messages::SENDER,
+            date = messages::DATE_CREATED,
+            filename = attachments::FILENAME,
+            mime_type = attachments::MIME_TYPE,
+            size = attachments::SIZE_BYTES,
+            text = messages::TEXT,
+            attachments = attachments::TABLE,
+            messages = messages::TABLE,
+            message_pk = messages::ID,
+            hidden = messages::HIDDEN
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(contact_id) = contact_id {
+            query.push_str(&format!(" AND m.{} = ?", messages::CONTACT_ID));
+            params.push(Box::new(contact_id));
+        }
+
+        if let Some(start) = start_date {
+            query.push_str(&format!(" AND m.{} >= ?", messages::DATE_CREATED));
+            params.push(Box::new(start));
+        }
+
+        if let Some(end) = end_date {
+            query.push_str(&format!(" AND m.{} <= ?", messages::DATE_CREATED));
+            params.push(Box::new(end));
+        }
+
+        if let Some(prefix) = mime_prefix {
+            query.push_str(&format!(" AND a.{} LIKE ? ESCAPE '\\'", attachments::MIME_TYPE));
+            let escaped = prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            params.push(Box::new(format!("{}%", escaped)));
+        }
+
+        query.push_str(&format!(" ORDER BY m.{} ASC, a.{} ASC", messages::DATE_CREATED, attachments::ID));
+
+        let mut stmt = conn.prepare(&query)?;
+        let entry_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            Ok(AttachmentReportEntry {
+                attachment_id: row.get(0)?,
+                message_id: row.get(1)?,
+                sender: row.get(2)?,
+                date_created: row.get(3)?,
+                filename: row.get(4)?,
+                mime_type: row.get(5)?,
+                size_bytes: row.get(6)?,
+                text: row.get(7)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for entry in entry_iter {
+            results.push(entry?);
+        }
+
+        Ok(results)
+    }
+}
+
+/// Flagged messages from one sender in one month
+#[derive(Debug)]
+pub struct FlagReportRow {
+    pub sender: String,
+    /// "YYYY-MM"
+    pub month: String,
+    pub flagged_messages: usize,
+    pub max_severity: f64,
+    pub average_severity: f64,
+}
+
+/// Size of the database file in pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DatabaseSize {
+    pub page_size: u64,
+    pub pages: u64,
+    /// Pages left empty by deletes, which only VACUUM gives back
+    pub free_pages: u64,
+}
+
+impl DatabaseSize {
+    pub fn bytes(&self) -> u64 {
+        self.page_size * self.pages
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        self.page_size * self.free_pages
+    }
+}
+
+/// Sizes before and after `db maintain`, and what the integrity check found
+#[derive(Debug)]
+pub struct MaintenanceReport {
+    pub before: DatabaseSize,
+    pub after: DatabaseSize,
+    /// Lines from `PRAGMA integrity_check`; empty when the database is intact
+    pub problems: Vec<String>,
+}
+
+impl MaintenanceReport {
+    /// Bytes the database shrank by
+    pub fn reclaimed(&self) -> u64 {
+        self.before.bytes().saturating_sub(self.after.bytes())
+    }
+}
+
+/// "?, ?, ?" for an IN clause with `count` values
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+/// Statistics about message processing
+#[derive(Debug)]
+pub struct ProcessingStats {
+    pub total_messages: usize,
+    pub processed_messages: usize,
+    pub processing_versions: Vec<String>,
+    pub messages_by_service: Vec<(String, usize)>,
+}
+
+/// One processing version and how many messages it covers
+#[derive(Debug)]
+pub struct ProcessingVersionSummary {
+    pub version: String,
+    pub message_count: usize,
+    pub first_processed_at: NaiveDateTime,
+    pub last_processed_at: NaiveDateTime,
+}
+
+/// Column and value selecting a conversation's messages
+fn conversation_filter(conversation: &Conversation) -> (&'static str, rusqlite::types::Value) {
+    match conversation {
+        Conversation::Contact(id) => (messages::CONTACT_ID, rusqlite::types::Value::Integer(i64::from(*id))),
+        Conversation::Thread(thread_id) => (messages::THREAD_ID, rusqlite::types::Value::Text(thread_id.clone())),
+    }
 }
 
 /// Initialize the database connection
 pub fn establish_connection() -> Result<Database> {
-    // Get database URL from environment or use default
-    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:data/messages.db".to_string());
+    // Get database URL from environment or use the default profile's database
+    let database_url = Profile::load(None)?.database_url();
     
     // Create database connection
     let database = Database::new(&database_url)?;
@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDate;
+
+use crate::models::Message;
+use crate::nlp::NlpProcessor;
+
+/// Messages with fewer content words than this are never picked as highlights
+const MIN_HIGHLIGHT_TOKENS: usize = 3;
+
+/// One day of an extractive digest
+#[derive(Debug, Clone)]
+pub struct DigestDay {
+    pub date: NaiveDate,
+    pub message_count: usize,
+    /// The day's most representative messages, in the order they were sent
+    pub highlights: Vec<Message>,
+}
+
+/// Extractive summary of a conversation: a few representative messages per day
+#[derive(Debug, Clone)]
+pub struct Digest {
+    pub days: Vec<DigestDay>,
+}
+
+impl Digest {
+    /// Pick up to `per_day` highlights for every day with messages.
+    ///
+    /// Messages are scored by how often their content words (stopwords removed) occur in
+    /// the whole day, averaged over the message's words so long messages don't always win.
+    pub fn build(messages: &[Message], per_day: usize) -> Self {
+        let processor = NlpProcessor::new("digest");
+        let mut by_day: BTreeMap<NaiveDate, Vec<(&Message, Vec<String>)>> = BTreeMap::new();
+
+        for message in messages {
+            let tokens = processor
+                .process_text(&message.content)
+                .map(|analysis| analysis.tokens)
+                .unwrap_or_default();
+            by_day.entry(message.timestamp.date_naive()).or_default().push((message, tokens));
+        }
+
+        let days = by_day
+            .into_iter()
+            .map(|(date, day)| {
+                let mut frequencies: HashMap<&str, usize> = HashMap::new();
+                for token in day.iter().flat_map(|(_, tokens)| tokens) {
+                    *frequencies.entry(token.as_str()).or_default() += 1;
+                }
+
+                let mut scored: Vec<(usize, f64)> = day
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, tokens))| tokens.len() >= MIN_HIGHLIGHT_TOKENS)
+                    .map(|(i, (_, tokens))| {
+                        let total: usize = tokens.iter().map(|t| frequencies[t.as_str()]).sum();
+                        (i, total as f64 / tokens.len() as f64)
+                    })
+                    .collect();
+                scored.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+                let mut picked: Vec<usize> = scored.into_iter().take(per_day).map(|(i, _)| i).collect();
+                picked.sort_unstable();
+
+                DigestDay {
+                    date,
+                    message_count: day.len(),
+                    highlights: picked.into_iter().map(|i| day[i].0.clone()).collect(),
+                }
+            })
+            .collect();
+
+        Self { days }
+    }
+
+    /// Render the digest as Markdown with one section per day
+    pub fn to_markdown(&self, title: &str) -> String {
+        let mut output = format!("# {}\n", title);
+
+        for day in &self.days {
+            output.push_str(&format!(
+                "\n## {} ({} messages)\n\n",
+                day.date.format("%a %b %d, %Y"),
+                day.message_count
+            ));
+            for message in &day.highlights {
+                output.push_str(&format!(
+                    "- {} {}: {}\n",
+                    message.timestamp.format("%H:%M"),
+                    message.sender,
+                    message.content.replace('\n', " ")
+                ));
+            }
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(day: u32, hour: u32, content: &str) -> Message {
+        Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, day, hour, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    #[test]
+    fn test_digest_picks_representative_messages() {
+        let messages = vec![
+            message(20, 9, "ok"),
+            message(20, 10, "The school called about the field trip form"),
+            message(20, 11, "Lunch was great today honestly"),
+            message(20, 12, "I signed the field trip form for school"),
+            message(21, 9, "Soccer practice moved to Thursday evening"),
+        ];
+
+        let digest = Digest::build(&messages, 1);
+        assert_eq!(digest.days.len(), 2);
+        assert_eq!(digest.days[0].message_count, 4);
+        assert_eq!(digest.days[0].highlights.len(), 1);
+        assert!(digest.days[0].highlights[0].content.contains("field trip"));
+        assert_eq!(digest.days[1].highlights.len(), 1);
+
+        let markdown = digest.to_markdown("Phil");
+        assert!(markdown.starts_with("# Phil\n"));
+        assert!(markdown.contains("## Mon Jan 20, 2025 (4 messages)"));
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime};
+use imessage_database::util::streamtyped;
+use rusqlite::{params, Connection, OpenFlags};
+
+use crate::chat_db::RetryPolicy;
+use crate::chat_db_schema::ChatDbSchema;
+use crate::models::MessageKind;
+
+/// Seconds between the Unix epoch and 2001-01-01, where chat.db dates start
+const APPLE_EPOCH_OFFSET: i64 = 978_307_200;
+
+/// A handle (phone number or email) from chat.db and where it's used
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandleSummary {
+    pub identifier: String,
+    /// iMessage, SMS, RCS...
+    pub service: String,
+    /// `chat_identifier` of every chat the handle is a member of
+    pub chats: Vec<String>,
+    pub message_count: usize,
+    /// Newest message from or to the handle, in UTC
+    pub last_message: Option<NaiveDateTime>,
+}
+
+/// A chat (one-on-one or group) from chat.db
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatSummary {
+    pub chat_identifier: String,
+    /// Name given to a group chat, if any
+    pub display_name: Option<String>,
+    pub service: String,
+    /// Handle identifiers of everyone in the chat except me
+    pub participants: Vec<String>,
+    pub message_count: usize,
+    /// Newest message in the chat, in UTC
+    pub last_message: Option<NaiveDateTime>,
+}
+
+/// A message read straight from a chat in chat.db
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatDbMessage {
+    pub guid: String,
+    pub text: Option<String>,
+    pub is_from_me: bool,
+    /// In UTC
+    pub date: NaiveDateTime,
+    /// Handle identifier of the sender; `None` for my own messages
+    pub sender_handle: Option<String>,
+    pub service: Option<String>,
+    pub has_attachments: bool,
+    pub kind: MessageKind,
+}
+
+/// When a chat.db message was sent and whether its text is only in `attributedBody`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChatDbMessageDate {
+    /// In UTC
+    pub date: NaiveDateTime,
+    pub attributed_body_only: bool,
+}
+
+/// Open chat.db read-only, retrying while Messages.app holds a lock
+pub fn open_read_only(chat_db_path: &Path, retry: &RetryPolicy) -> Result<Connection> {
+    retry.run(|| {
+        Connection::open_with_flags(chat_db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Failed to open {}", chat_db_path.display()))
+    })
+}
+
+/// List every handle in chat.db, most messages first. `filter` matches part of the
+/// identifier or of a chat's identifier or display name, ignoring case.
+pub fn discover_handles(conn: &Connection, filter: Option<&str>) -> Result<Vec<HandleSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT h.id, h.service, \
+             (SELECT group_concat(c.chat_identifier, char(31)) FROM chat_handle_join chj \
+              JOIN chat c ON c.ROWID = chj.chat_id WHERE chj.handle_id = h.ROWID), \
+             (SELECT count(*) FROM message m WHERE m.handle_id = h.ROWID), \
+             (SELECT max(m.date) FROM message m WHERE m.handle_id = h.ROWID) \
+         FROM handle h \
+         WHERE ?1 IS NULL OR h.id LIKE '%' || ?1 || '%' OR EXISTS ( \
+             SELECT 1 FROM chat_handle_join chj JOIN chat c ON c.ROWID = chj.chat_id \
+             WHERE chj.handle_id = h.ROWID \
+             AND (c.chat_identifier LIKE '%' || ?1 || '%' OR c.display_name LIKE '%' || ?1 || '%')) \
+         ORDER BY 4 DESC, h.id",
+    )?;
+
+    let handle_iter = stmt.query_map(params![filter], |row| {
+        let chats: Option<String> = row.get(2)?;
+        Ok(HandleSummary {
+            identifier: row.get(0)?,
+            service: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            chats: split_identifiers(chats),
+            message_count: row.get::<_, i64>(3)? as usize,
+            last_message: row.get::<_, Option<i64>>(4)?.and_then(apple_time_to_utc),
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for handle in handle_iter {
+        results.push(handle?);
+    }
+
+    Ok(results)
+}
+
+/// List every chat in chat.db, most messages first. `filter` matches part of the chat
+/// identifier, display name or a participant's identifier, ignoring case.
+pub fn discover_chats(conn: &Connection, filter: Option<&str>) -> Result<Vec<ChatSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT c.chat_identifier, c.display_name, c.service_name, \
+             (SELECT group_concat(h.id, char(31)) FROM chat_handle_join chj \
+              JOIN handle h ON h.ROWID = chj.handle_id WHERE chj.chat_id = c.ROWID), \
+             (SELECT count(*) FROM chat_message_join cmj WHERE cmj.chat_id = c.ROWID), \
+             (SELECT max(m.date) FROM chat_message_join cmj \
+              JOIN message m ON m.ROWID = cmj.message_id WHERE cmj.chat_id = c.ROWID) \
+         FROM chat c \
+         WHERE ?1 IS NULL OR c.chat_identifier LIKE '%' || ?1 || '%' OR c.display_name LIKE '%' || ?1 || '%' \
+             OR EXISTS ( \
+             SELECT 1 FROM chat_handle_join chj JOIN handle h ON h.ROWID = chj.handle_id \
+             WHERE chj.chat_id = c.ROWID AND h.id LIKE '%' || ?1 || '%') \
+         ORDER BY 5 DESC, c.chat_identifier",
+    )?;
+
+    let chat_iter = stmt.query_map(params![filter], |row| {
+        let participants: Option<String> = row.get(3)?;
+        Ok(ChatSummary {
+            chat_identifier: row.get(0)?,
+            display_name: row.get::<_, Option<String>>(1)?.filter(|name| !name.is_empty()),
+            service: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            participants: split_identifiers(participants),
+            message_count: row.get::<_, i64>(4)? as usize,
+            last_message: row.get::<_, Option<i64>>(5)?.and_then(apple_time_to_utc),
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for chat in chat_iter {
+        results.push(chat?);
+    }
+
+    Ok(results)
+}
+
+/// Read the messages of a chat by its `chat_identifier`, oldest first, within an optional
+/// UTC date range. Text is recovered from `attributedBody` when `text` is empty; plain
+/// messages with neither (bare attachments) are skipped, while stickers, locations,
+/// payments, app and system messages are kept with their detected kind. Columns older
+/// macOS releases don't have read as NULL or 0.
+pub fn read_chat_messages(
+    conn: &Connection,
+    chat_identifier: &str,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+) -> Result<Vec<ChatDbMessage>> {
+    let schema = ChatDbSchema::detect(conn)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT m.guid, m.text, {}, m.is_from_me, m.date, h.id, m.service, {}, {}, {}, {}, \
+             (SELECT group_concat(a.filename, char(31)) FROM message_attachment_join maj \
+              JOIN attachment a ON a.ROWID = maj.attachment_id WHERE maj.message_id = m.ROWID) \
+         FROM chat c \
+         JOIN chat_message_join cmj ON cmj.chat_id = c.ROWID \
+         JOIN message m ON m.ROWID = cmj.message_id \
+         LEFT JOIN handle h ON h.ROWID = m.handle_id \
+         WHERE c.chat_identifier = ?1 \
+         ORDER BY m.date, m.ROWID",
+        schema.message_column("attributedBody", "NULL"),
+        schema.message_column("cache_has_attachments", "0"),
+        schema.message_column("item_type", "0"),
+        schema.message_column("associated_message_type", "0"),
+        schema.message_column("balloon_bundle_id", "NULL"),
+    ))?;
+
+    let message_iter = stmt.query_map(params![chat_identifier], |row| {
+        let text: Option<String> = row.get(1)?;
+        let attributed_body: Option<Vec<u8>> = row.get(2)?;
+        let is_from_me: bool = row.get(3)?;
+        let sender_handle: Option<String> = row.get(5)?;
+        let service: Option<String> = row.get(6)?;
+        let has_attachments = row.get::<_, Option<bool>>(7)?.unwrap_or(false);
+        let balloon_bundle_id: Option<String> = row.get(10)?;
+        let attachment_names = split_identifiers(row.get(11)?);
+        let kind = MessageKind::detect(
+            row.get::<_, Option<i32>>(8)?.unwrap_or(0),
+            row.get::<_, Option<i32>>(9)?.unwrap_or(0),
+            balloon_bundle_id.as_deref(),
+            &attachment_names.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+        let guid: String = row.get(0)?;
+        Ok(apple_time_to_utc(row.get(4)?).map(|date| ChatDbMessage {
+            guid,
+            text: message_text(text, attributed_body),
+            is_from_me,
+            date,
+            sender_handle: sender_handle.filter(|_| !is_from_me),
+            service,
+            has_attachments,
+            kind,
+        }))
+    })?;
+
+    let mut results = Vec::new();
+    for message in message_iter {
+        let Some(message) = message? else { continue };
+        if (message.text.is_none() && message.kind == MessageKind::Text)
+            || start.is_some_and(|start| message.date < start)
+            || end.is_some_and(|end| message.date > end)
+        {
+            continue;
+        }
+        results.push(message);
+    }
+
+    Ok(results)
+}
+
+/// Dates of the messages with text in one-on-one chats with any of `identifiers` (phone
+/// numbers or emails, ignoring case), oldest first. Group chats are left out so the
+/// counts line up with a contact's imported conversation.
+pub fn one_on_one_message_dates(conn: &Connection, identifiers: &[String]) -> Result<Vec<ChatDbMessageDate>> {
+    if identifiers.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let attributed_body = ChatDbSchema::detect(conn)?.message_column("attributedBody", "NULL");
+    let placeholders = vec!["lower(?)"; identifiers.len()].join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT m.date, (m.text IS NULL OR trim(m.text) = '') AND {0} IS NOT NULL \
+         FROM chat c \
+         JOIN chat_message_join cmj ON cmj.chat_id = c.ROWID \
+         JOIN message m ON m.ROWID = cmj.message_id \
+         WHERE (SELECT count(*) FROM chat_handle_join chj WHERE chj.chat_id = c.ROWID) = 1 \
+         AND EXISTS (SELECT 1 FROM chat_handle_join chj JOIN handle h ON h.ROWID = chj.handle_id \
+             WHERE chj.chat_id = c.ROWID AND lower(h.id) IN ({1})) \
+         AND ((m.text IS NOT NULL AND trim(m.text) != '') OR {0} IS NOT NULL) \
+         ORDER BY m.date",
+        attributed_body, placeholders
+    ))?;
+
+    let date_iter = stmt.query_map(rusqlite::params_from_iter(identifiers), |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?))
+    })?;
+
+    let mut results = Vec::new();
+    for row in date_iter {
+        let (date, attributed_body_only) = row?;
+        if let Some(date) = apple_time_to_utc(date) {
+            results.push(ChatDbMessageDate { date, attributed_body_only });
+        }
+    }
+
+    Ok(results)
+}
+
+/// When each of the messages with the given GUIDs was read, in UTC. Messages that are unread,
+/// mine or not in chat.db are left out, as are all of them when chat.db doesn't record reads.
+pub fn read_times(conn: &Connection, guids: &[String]) -> Result<HashMap<String, NaiveDateTime>> {
+    let mut results = HashMap::new();
+    if !ChatDbSchema::detect(conn)?.has_message_column("date_read") {
+        return Ok(results);
+    }
+
+    for chunk in guids.chunks(500) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT guid, date_read FROM message WHERE is_from_me = 0 AND date_read != 0 AND guid IN ({})",
+            placeholders
+        ))?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(chunk), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (guid, date_read) = row?;
+            if let Some(date) = apple_time_to_utc(date_read) {
+                results.insert(guid, date);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Message text, decoding the typedstream `attributedBody` when `text` is NULL or blank
+fn message_text(text: Option<String>, attributed_body: Option<Vec<u8>>) -> Option<String> {
+    if let Some(text) = text.filter(|t| !t.trim().is_empty()) {
+        return Some(text);
+    }
+
+    streamtyped::parse(attributed_body?)
+        .ok()
+        .filter(|text| !text.trim().is_empty())
+}
+
+/// Values joined by `group_concat`, sorted
+fn split_identifiers(joined: Option<String>) -> Vec<String> {
+    let mut identifiers: Vec<String> = joined
+        .map(|joined| joined.split('\u{1f}').map(String::from).collect())
+        .unwrap_or_default();
+    identifiers.sort();
+    identifiers
+}
+
+/// Convert a chat.db date to UTC. Newer macOS versions store nanoseconds since 2001,
+/// older ones seconds.
+pub fn apple_time_to_utc(value: i64) -> Option<NaiveDateTime> {
+    if value == 0 {
+        return None;
+    }
+
+    let seconds = if value.abs() > 1_000_000_000_000 { value / 1_000_000_000 } else { value };
+    DateTime::from_timestamp(seconds + APPLE_EPOCH_OFFSET, 0).map(|dt| dt.naive_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The parts of the chat.db schema discovery reads, with two contacts and a group chat
+    fn sample_chat_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE handle (ROWID INTEGER PRIMARY KEY, id TEXT, service TEXT);
+             CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, chat_identifier TEXT, display_name TEXT, service_name TEXT);
+             CREATE TABLE chat_handle_join (chat_id INTEGER, handle_id INTEGER);
+             CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT, attributedBody BLOB,
+                 handle_id INTEGER, date INTEGER, is_from_me INTEGER, service TEXT, cache_has_attachments INTEGER,
+                 item_type INTEGER DEFAULT 0, associated_message_type INTEGER DEFAULT 0, balloon_bundle_id TEXT,
+                 date_read INTEGER DEFAULT 0);
+             CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, filename TEXT);
+             CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+             CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+
+             INSERT INTO handle VALUES (1, '+15551234567', 'iMessage'), (2, 'phil@example.com', 'iMessage'),
+                 (3, '+15559876543', 'SMS');
+             INSERT INTO chat VALUES (1, '+15551234567', '', 'iMessage'), (2, 'chat123456', 'Soccer parents', 'iMessage'),
+                 (3, '+15559876543', '', 'SMS');
+             INSERT INTO chat_handle_join VALUES (1, 1), (2, 1), (2, 3), (3, 3);
+             INSERT INTO message (ROWID, guid, text, handle_id, date, is_from_me, service, cache_has_attachments)
+                 VALUES (1, 'guid-1', 'Running late', 1, 757382400000000000, 0, 'iMessage', 0),
+                 (2, 'guid-2', 'No worries', 1, 757386000000000000, 1, 'iMessage', 0),
+                 (3, 'guid-3', 'Who has snacks?', 3, 757389600000000000, 0, 'SMS', 1),
+                 (4, 'guid-4', 'Me', 0, 757390000000000000, 1, 'SMS', 0),
+                 (5, 'guid-5', NULL, 3, 757390100000000000, 0, 'SMS', 1);
+             INSERT INTO chat_message_join VALUES (1, 1), (1, 2), (2, 3), (2, 4), (2, 5);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_discover_handles() {
+        let conn = sample_chat_db();
+
+        let handles = discover_handles(&conn, None).unwrap();
+        assert_eq!(handles.len(), 3);
+        assert_eq!(handles[0].identifier, "+15551234567");
+        assert_eq!(handles[0].message_count, 2);
+        assert_eq!(handles[0].chats, vec!["+15551234567", "chat123456"]);
+        assert_eq!(
+            handles[0].last_message.unwrap().format("%Y-%m-%d %H:%M").to_string(),
+            "2025-01-01 01:00"
+        );
+
+        // Unused handles are still listed
+        assert_eq!(handles[2].identifier, "phil@example.com");
+        assert_eq!(handles[2].message_count, 0);
+        assert!(handles[2].chats.is_empty());
+
+        // Filters match identifiers and group chat names
+        assert_eq!(discover_handles(&conn, Some("example")).unwrap().len(), 1);
+        assert_eq!(discover_handles(&conn, Some("soccer")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_discover_chats() {
+        let conn = sample_chat_db();
+
+        let chats = discover_chats(&conn, None).unwrap();
+        assert_eq!(chats.len(), 3);
+        assert_eq!(chats[0].chat_identifier, "chat123456");
+        assert_eq!(chats[0].display_name.as_deref(), Some("Soccer parents"));
+        assert_eq!(chats[0].participants, vec!["+15551234567", "+15559876543"]);
+        assert_eq!(chats[0].message_count, 3);
+
+        // Empty display names are treated as unnamed
+        assert_eq!(chats[1].chat_identifier, "+15551234567");
+        assert_eq!(chats[1].display_name, None);
+
+        // Filters match chat names and participants
+        assert_eq!(discover_chats(&conn, Some("soccer")).unwrap().len(), 1);
+        assert_eq!(discover_chats(&conn, Some("9876")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_read_chat_messages() {
+        let conn = sample_chat_db();
+
+        // Group chat messages name their sender; mine and text-less ones don't
+        let messages = read_chat_messages(&conn, "chat123456", None, None).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].guid, "guid-3");
+        assert_eq!(messages[0].sender_handle.as_deref(), Some("+15559876543"));
+        assert!(messages[0].has_attachments);
+        assert!(messages[1].is_from_me);
+        assert_eq!(messages[1].sender_handle, None);
+
+        let start = apple_time_to_utc(757385000000000000);
+        let messages = read_chat_messages(&conn, "+15551234567", start, None).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].text.as_deref(), Some("No worries"));
+
+        assert!(read_chat_messages(&conn, "missing", None, None).unwrap().is_empty());
+
+        // A shared location has no text but is kept with its kind
+        conn.execute_batch(
+            "INSERT INTO message (ROWID, guid, handle_id, date, is_from_me, cache_has_attachments)
+                 VALUES (7, 'guid-7', 1, 757400000000000000, 0, 1);
+             INSERT INTO chat_message_join VALUES (1, 7);
+             INSERT INTO attachment VALUES (1, '~/Library/Messages/Attachments/CL.loc.vcf');
+             INSERT INTO message_attachment_join VALUES (7, 1);",
+        )
+        .unwrap();
+        let messages = read_chat_messages(&conn, "+15551234567", None, None).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2].kind, MessageKind::Location);
+        assert_eq!(messages[0].kind, MessageKind::Text);
+    }
+
+    #[test]
+    fn test_one_on_one_message_dates() {
+        let conn = sample_chat_db();
+        conn.execute_batch(
+            "INSERT INTO message (ROWID, guid, text, attributedBody, handle_id, date, is_from_me)
+                 VALUES (6, 'guid-6', NULL, x'0401', 3, 757393600000000000, 0);
+             INSERT INTO chat_message_join VALUES (3, 6);",
+        )
+        .unwrap();
+
+        // Only the one-on-one chat counts, not the group chat the handle is also in
+        let dates = one_on_one_message_dates(&conn, &["+15559876543".to_string()]).unwrap();
+        assert_eq!(dates.len(), 1);
+        assert!(dates[0].attributed_body_only);
+
+        let dates = one_on_one_message_dates(&conn, &["+15551234567".to_string()]).unwrap();
+        assert_eq!(dates.len(), 2);
+        assert!(dates.iter().all(|d| !d.attributed_body_only));
+
+        assert!(one_on_one_message_dates(&conn, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_times() {
+        let conn = sample_chat_db();
+        conn.execute("UPDATE message SET date_read = 757383000000000000 WHERE guid IN ('guid-1', 'guid-2')", [])
+            .unwrap();
+
+        // guid-2 is mine and guid-3 is unread
+        let guids = ["guid-1", "guid-2", "guid-3", "missing"].map(String::from);
+        let read = read_times(&conn, &guids).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read["guid-1"], apple_time_to_utc(757383000000000000).unwrap());
+    }
+
+    #[test]
+    fn test_apple_time_to_utc() {
+        let nanoseconds = apple_time_to_utc(757382400000000000).unwrap();
+        assert_eq!(apple_time_to_utc(757382400), Some(nanoseconds));
+        assert_eq!(nanoseconds.format("%Y-%m-%d").to_string(), "2025-01-01");
+        assert_eq!(apple_time_to_utc(0), None);
+    }
+}
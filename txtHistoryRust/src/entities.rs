@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::models::NamedEntity;
+
+/// A user-defined entity type and the pattern that finds it, e.g. case numbers or flight numbers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityPattern {
+    /// Type stored with each match, e.g. "CASE_NUMBER"
+    pub entity_type: String,
+
+    /// Regular expression matched against the original message text. When it has a capture
+    /// group, the first group is stored as the entity instead of the whole match.
+    pub pattern: String,
+
+    #[serde(default)]
+    pub case_insensitive: bool,
+}
+
+/// Finds the configured custom entities in message text
+#[derive(Debug)]
+pub struct EntityExtractor {
+    patterns: Vec<(Regex, String)>,
+}
+
+impl EntityExtractor {
+    pub fn new(patterns: &[EntityPattern]) -> Result<Self> {
+        let mut compiled = Vec::new();
+
+        for pattern in patterns {
+            let entity_type = pattern.entity_type.trim();
+            if entity_type.is_empty() {
+                return Err(anyhow::anyhow!("Entity pattern \"{}\" has no entity_type", pattern.pattern));
+            }
+
+            let regex = RegexBuilder::new(&pattern.pattern)
+                .case_insensitive(pattern.case_insensitive)
+                .build()
+                .with_context(|| format!("Invalid pattern for entity type {}", entity_type))?;
+            compiled.push((regex, entity_type.to_string()));
+        }
+
+        Ok(Self { patterns: compiled })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Every match of every pattern, in the order they appear in `text`. Positions are byte
+    /// offsets into `text`.
+    pub fn extract(&self, text: &str) -> Vec<NamedEntity> {
+        let mut entities = Vec::new();
+
+        for (regex, entity_type) in &self.patterns {
+            for captures in regex.captures_iter(text) {
+                let Some(found) = captures.get(1).or_else(|| captures.get(0)) else {
+                    continue;
+                };
+                if found.as_str().trim().is_empty() {
+                    continue;
+                }
+                entities.push(NamedEntity {
+                    text: found.as_str().to_string(),
+                    entity_type: entity_type.clone(),
+                    start: found.start(),
+                    end: found.end(),
+                });
+            }
+        }
+
+        entities.sort_by_key(|entity| (entity.start, entity.end));
+        entities
+    }
+}
+
+/// A processed message's named entities, for searching them
+#[derive(Debug, Clone)]
+pub struct EntityMessage {
+    pub sent_at: NaiveDateTime,
+    pub sender: String,
+    pub entities: Vec<NamedEntity>,
+}
+
+/// Which stored entities `report entities` lists
+#[derive(Debug, Clone, Default)]
+pub struct EntityFilter {
+    /// Only entities of this type (case-insensitive)
+    pub entity_type: Option<String>,
+
+    /// Only entities whose text contains this (case-insensitive)
+    pub text: Option<String>,
+}
+
+impl EntityFilter {
+    pub fn matches(&self, entity: &NamedEntity) -> bool {
+        let type_matches = match &self.entity_type {
+            Some(entity_type) => entity.entity_type.eq_ignore_ascii_case(entity_type),
+            None => true,
+        };
+        let text_matches = match &self.text {
+            Some(text) => entity.text.to_lowercase().contains(&text.to_lowercase()),
+            None => true,
+        };
+        type_matches && text_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(entity_type: &str, pattern: &str) -> EntityPattern {
+        EntityPattern { entity_type: entity_type.to_string(), pattern: pattern.to_string(), case_insensitive: false }
+    }
+
+    #[test]
+    fn test_custom_entities() {
+        let extractor = EntityExtractor::new(&[
+            pattern("CASE_NUMBER", r"\b\d{2}-CV-\d{4,}\b"),
+            pattern("FLIGHT", r"\bflight ([A-Z]{2}\d{1,4})\b"),
+            EntityPattern { case_insensitive: true, ..pattern("SCHOOL", r"lincoln elementary") },
+        ])
+        .unwrap();
+
+        let text = "Filed 24-CV-01932 today. I'm on flight UA1234, pick up from Lincoln Elementary";
+        let entities = extractor.extract(text);
+        let found: Vec<(&str, &str)> =
+            entities.iter().map(|e| (e.entity_type.as_str(), e.text.as_str())).collect();
+        assert_eq!(found, vec![("CASE_NUMBER", "24-CV-01932"), ("FLIGHT", "UA1234"), ("SCHOOL", "Lincoln Elementary")]);
+        assert_eq!(&text[entities[1].start..entities[1].end], "UA1234");
+
+        let filter = EntityFilter { entity_type: Some("flight".to_string()), text: Some("ua".to_string()) };
+        assert_eq!(entities.iter().filter(|e| filter.matches(e)).count(), 1);
+        assert_eq!(entities.iter().filter(|e| EntityFilter::default().matches(e)).count(), 3);
+
+        assert!(EntityExtractor::new(&[pattern("BROKEN", "(")]).is_err());
+        assert!(EntityExtractor::new(&[pattern(" ", "x")]).is_err());
+    }
+}
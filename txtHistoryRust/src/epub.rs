@@ -0,0 +1,329 @@
+use std::io::{Cursor, Write};
+
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::file_writer::{write_file, WritePolicy, WrittenFile};
+use crate::integrity::{chain_hash, message_hash};
+use crate::legal::xml_escape;
+use crate::models::Message;
+
+const CONTAINER: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    r#"<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">"#,
+    r#"<rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>"#,
+    r#"</container>"#
+);
+
+const STYLESHEET: &str = "\
+body { font-family: serif; line-height: 1.4; }
+h1, h2 { text-align: center; }
+p.message { margin: 0 0 0.6em 0; }
+p.mine { margin-left: 2em; }
+span.time { color: #666; font-size: 0.8em; }
+span.sender { font-weight: bold; }
+";
+
+/// Messages from one calendar month in local time
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    /// e.g. "March 2025"
+    pub title: String,
+    pub messages: Vec<Message>,
+}
+
+/// A conversation laid out as an e-book: a title page with the participants and date range,
+/// then one chapter per month
+#[derive(Debug, Clone)]
+pub struct EpubBook {
+    pub title: String,
+    pub participants: Vec<String>,
+    /// First and last day of the conversation, e.g. "Jan 20, 2025 to Mar 4, 2025"
+    pub range: String,
+    pub chapters: Vec<Chapter>,
+    identifier: String,
+    modified: String,
+}
+
+impl EpubBook {
+    pub fn new(messages: &[Message], contact: &str) -> Self {
+        let mut participants: Vec<String> = Vec::new();
+        for message in messages {
+            if !participants.contains(&message.sender) {
+                participants.push(message.sender.clone());
+            }
+        }
+
+        let mut chapters: Vec<Chapter> = Vec::new();
+        for message in messages {
+            let title = message.timestamp.format("%B %Y").to_string();
+            match chapters.last_mut() {
+                Some(chapter) if chapter.title == title => chapter.messages.push(message.clone()),
+                _ => chapters.push(Chapter { title, messages: vec![message.clone()] }),
+            }
+        }
+
+        let range = match (messages.first(), messages.last()) {
+            (Some(first), Some(last)) => format!(
+                "{} to {}",
+                first.timestamp.format("%b %-d, %Y"),
+                last.timestamp.format("%b %-d, %Y")
+            ),
+            _ => String::new(),
+        };
+
+        // Same messages, same book: the identifier and date come from the messages, not the clock
+        let hashes: Vec<String> = messages.iter().map(message_hash).collect();
+        let modified = messages
+            .last()
+            .map(|m| m.timestamp.with_timezone(&Utc))
+            .unwrap_or_default()
+            .to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        Self {
+            title: format!("Messages with {}", contact),
+            participants,
+            range,
+            chapters,
+            identifier: format!("urn:txt-history:{}", chain_hash(&hashes)),
+            modified,
+        }
+    }
+
+    /// Package the book as EPUB 3, with an NCX table of contents for older readers
+    pub fn to_epub(&self) -> Result<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        // The mimetype must come first and be stored uncompressed
+        zip.start_file("mimetype", SimpleFileOptions::default().compression_method(CompressionMethod::Stored))?;
+        zip.write_all(b"application/epub+zip")?;
+
+        let mut files = vec![
+            ("META-INF/container.xml".to_string(), CONTAINER.to_string()),
+            ("OEBPS/content.opf".to_string(), self.package()),
+            ("OEBPS/nav.xhtml".to_string(), self.nav()),
+            ("OEBPS/toc.ncx".to_string(), self.ncx()),
+            ("OEBPS/style.css".to_string(), STYLESHEET.to_string()),
+            ("OEBPS/title.xhtml".to_string(), self.title_page()),
+        ];
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            files.push((format!("OEBPS/{}", chapter_file(i)), chapter_page(chapter)));
+        }
+
+        for (name, contents) in files {
+            zip.start_file(name, SimpleFileOptions::default())?;
+            zip.write_all(contents.as_bytes())?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+
+    fn package(&self) -> String {
+        let mut manifest = vec![
+            r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#.to_string(),
+            r#"<item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>"#.to_string(),
+            r#"<item id="css" href="style.css" media-type="text/css"/>"#.to_string(),
+            r#"<item id="title" href="title.xhtml" media-type="application/xhtml+xml"/>"#.to_string(),
+        ];
+        let mut spine = vec![r#"<itemref idref="title"/>"#.to_string()];
+        for i in 0..self.chapters.len() {
+            manifest.push(format!(
+                r#"<item id="chapter-{}" href="{}" media-type="application/xhtml+xml"/>"#,
+                i + 1,
+                chapter_file(i)
+            ));
+            spine.push(format!(r#"<itemref idref="chapter-{}"/>"#, i + 1));
+        }
+
+        let creators: String = self
+            .participants
+            .iter()
+            .map(|name| format!("<dc:creator>{}</dc:creator>", xml_escape(name)))
+            .collect();
+
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">"#,
+                r#"<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">"#,
+                r#"<dc:identifier id="id">{}</dc:identifier><dc:title>{}</dc:title>{}"#,
+                r#"<dc:description>{}</dc:description><dc:language>en</dc:language>"#,
+                r#"<meta property="dcterms:modified">{}</meta></metadata>"#,
+                r#"<manifest>{}</manifest><spine toc="ncx">{}</spine></package>"#
+            ),
+            self.identifier,
+            xml_escape(&self.title),
+            creators,
+            xml_escape(&self.range),
+            self.modified,
+            manifest.join(""),
+            spine.join("")
+        )
+    }
+
+    fn nav(&self) -> String {
+        let items: String = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| format!(r#"<li><a href="{}">{}</a></li>"#, chapter_file(i), chapter.title))
+            .collect();
+
+        xhtml(
+            "Contents",
+            &format!(r#"<nav epub:type="toc"><h1>Contents</h1><ol>{}</ol></nav>"#, items),
+        )
+    }
+
+    fn ncx(&self) -> String {
+        let points: String = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| {
+                format!(
+                    r#"<navPoint id="nav-{0}" playOrder="{0}"><navLabel><text>{1}</text></navLabel><content src="{2}"/></navPoint>"#,
+                    i + 1,
+                    chapter.title,
+                    chapter_file(i)
+                )
+            })
+            .collect();
+
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">"#,
+                r#"<head><meta name="dtb:uid" content="{}"/></head>"#,
+                r#"<docTitle><text>{}</text></docTitle><navMap>{}</navMap></ncx>"#
+            ),
+            self.identifier,
+            xml_escape(&self.title),
+            points
+        )
+    }
+
+    fn title_page(&self) -> String {
+        let participants = self.participants.iter().map(|name| xml_escape(name)).collect::<Vec<_>>().join(", ");
+        let messages: usize = self.chapters.iter().map(|chapter| chapter.messages.len()).sum();
+
+        xhtml(
+            &self.title,
+            &format!(
+                "<h1>{}</h1><p>{}</p><p>{}</p><p>{} messages</p>",
+                xml_escape(&self.title),
+                participants,
+                xml_escape(&self.range),
+                messages
+            ),
+        )
+    }
+}
+
+/// Write a conversation to `conversation.epub` in `output_dir`
+pub fn write_epub(messages: &[Message], contact: &str, output_dir: &str, policy: WritePolicy) -> Result<WrittenFile> {
+    if policy == WritePolicy::Append {
+        return Err(anyhow::anyhow!("EPUB books can't be appended to; use --overwrite or --skip-existing"));
+    }
+
+    let contents = EpubBook::new(messages, contact).to_epub()?;
+    let file_path = format!("{}/conversation.epub", output_dir);
+    write_file(&file_path, messages.len(), policy, |writer, _| Ok(writer.write_all(&contents)?))
+}
+
+fn chapter_file(index: usize) -> String {
+    format!("chapter-{}.xhtml", index + 1)
+}
+
+fn chapter_page(chapter: &Chapter) -> String {
+    let mut body = format!("<h2>{}</h2>", chapter.title);
+    let mut day = None;
+
+    for message in &chapter.messages {
+        let date = message.timestamp.date_naive();
+        if day != Some(date) {
+            body.push_str(&format!("<h3>{}</h3>", message.timestamp.format("%A, %B %-d")));
+            day = Some(date);
+        }
+
+        let class = if message.is_from_me { "message mine" } else { "message" };
+        let content = xml_escape(&message.content).replace('\n', "<br/>");
+        body.push_str(&format!(
+            r#"<p class="{}"><span class="time">{}</span> <span class="sender">{}</span>: {}</p>"#,
+            class,
+            message.timestamp.format("%-I:%M %p"),
+            xml_escape(&message.sender),
+            content
+        ));
+    }
+
+    xhtml(&chapter.title, &body)
+}
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">"#,
+            r#"<head><title>{}</title><link rel="stylesheet" type="text/css" href="style.css"/></head>"#,
+            r#"<body>{}</body></html>"#
+        ),
+        xml_escape(title),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(sender: &str, month: u32, day: u32, content: &str) -> Message {
+        Message {
+            sender: sender.to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, month, day, 14, 5, 0).unwrap(),
+            content: content.to_string(),
+            source: Some("iMessage".to_string()),
+            imessage_id: None,
+            service: Some("iMessage".to_string()),
+            is_from_me: sender == "Jess",
+        }
+    }
+
+    #[test]
+    fn test_chapters_by_month() {
+        let messages = vec![
+            message("Phil", 1, 20, "hi"),
+            message("Jess", 1, 21, "hey"),
+            message("Phil", 3, 4, "<b>later</b> & more"),
+        ];
+
+        let book = EpubBook::new(&messages, "Phil");
+        assert_eq!(book.title, "Messages with Phil");
+        assert_eq!(book.participants, vec!["Phil", "Jess"]);
+        assert_eq!(book.range, "Jan 20, 2025 to Mar 4, 2025");
+        let titles: Vec<&str> = book.chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, vec!["January 2025", "March 2025"]);
+
+        let epub = book.to_epub().unwrap();
+        assert_eq!(epub, EpubBook::new(&messages, "Phil").to_epub().unwrap());
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(epub)).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        assert_eq!(archive.by_index(0).unwrap().compression(), CompressionMethod::Stored);
+
+        let mut chapter = String::new();
+        archive.by_name("OEBPS/chapter-2.xhtml").unwrap().read_to_string(&mut chapter).unwrap();
+        assert!(chapter.contains("&lt;b&gt;later&lt;/b&gt; &amp; more"));
+        assert!(chapter.contains("Tuesday, March 4"));
+
+        let mut package = String::new();
+        archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut package).unwrap();
+        assert!(package.contains("<dc:creator>Jess</dc:creator>"));
+        assert!(package.contains(r#"<itemref idref="chapter-2"/>"#));
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Exit codes, listed in `--help`. Clap itself exits with 2 on invalid arguments.
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Success
+  1  Any other error
+  2  Invalid arguments
+  3  Contact not found
+  4  No messages matched
+  5  chat.db is locked by Messages
+  6  chat.db not found
+  7  Export failed verification
+  8  Another txt-history process is writing to the database
+  9  chat.db schema not supported";
+
+/// Failures scripts may want to tell apart, each with its own exit code. Everything else
+/// stays a plain `anyhow` error and exits with 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxtHistoryError {
+    ContactNotFound(String),
+    /// No messages matched, for the contact or chat named
+    NoMessages(String),
+    /// chat.db stayed locked after every retry
+    ChatDbLocked,
+    /// No chat.db at the given path, or none found on this platform
+    ChatDbNotFound(Option<PathBuf>),
+    /// Export files that no longer match their manifest
+    VerificationFailed { failed: usize, total: usize },
+    /// Messages in an export that were changed, removed or added in the database since
+    ExportOutOfDate { differences: usize, total: usize },
+    /// Another process holds the database lock, doing `activity`
+    DatabaseBusy { activity: String, pid: Option<u32> },
+    /// chat.db is missing tables or columns every import reads, as described
+    UnsupportedChatDb(String),
+}
+
+impl TxtHistoryError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            TxtHistoryError::ContactNotFound(_) => 3,
+            TxtHistoryError::NoMessages(_) => 4,
+            TxtHistoryError::ChatDbLocked => 5,
+            TxtHistoryError::ChatDbNotFound(_) => 6,
+            TxtHistoryError::VerificationFailed { .. } => 7,
+            TxtHistoryError::ExportOutOfDate { .. } => 7,
+            TxtHistoryError::DatabaseBusy { .. } => 8,
+            TxtHistoryError::UnsupportedChatDb(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for TxtHistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxtHistoryError::ContactNotFound(name) => write!(f, "Contact not found: {}", name),
+            TxtHistoryError::NoMessages(name) => write!(f, "No messages found for {}", name),
+            TxtHistoryError::ChatDbLocked => write!(
+                f,
+                "chat.db is still locked. Quit Messages, raise chat_db.max_retries or pass --snapshot"
+            ),
+            TxtHistoryError::ChatDbNotFound(Some(path)) => write!(f, "chat.db not found at {}", path.display()),
+            TxtHistoryError::ChatDbNotFound(None) => write!(
+                f,
+                "chat.db is only found automatically on macOS. Copy ~/Library/Messages/chat.db from a Mac \
+                 and pass --chat-db PATH or set chat_db.path in the config"
+            ),
+            TxtHistoryError::VerificationFailed { failed, total } => {
+                write!(f, "{} of {} files failed verification", failed, total)
+            },
+            TxtHistoryError::ExportOutOfDate { differences, total } => write!(
+                f,
+                "The export no longer matches the database: {} differences across {} exported messages",
+                differences, total
+            ),
+            TxtHistoryError::DatabaseBusy { activity, pid: Some(pid) } => write!(
+                f,
+                "Another txt-history process (pid {}) is {}; try again when it finishes",
+                pid, activity
+            ),
+            TxtHistoryError::DatabaseBusy { activity, pid: None } => {
+                write!(f, "Another txt-history process is {}; try again when it finishes", activity)
+            },
+            TxtHistoryError::UnsupportedChatDb(reason) => write!(
+                f,
+                "Unsupported chat.db schema: {}. It may be from a macOS release this version doesn't support",
+                reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TxtHistoryError {}
+
+/// The exit code for an error, from the first `TxtHistoryError` in its chain
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    error
+        .downcast_ref::<TxtHistoryError>()
+        .or_else(|| error.chain().find_map(|cause| cause.downcast_ref::<TxtHistoryError>()))
+        .map_or(1, TxtHistoryError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_codes_survive_context() {
+        let error = anyhow::Error::from(TxtHistoryError::ContactNotFound("Phil".to_string()));
+        assert_eq!(exit_code(&error), 3);
+
+        let error: anyhow::Error = Err::<(), _>(TxtHistoryError::NoMessages("Phil".to_string()))
+            .context("Export failed")
+            .unwrap_err();
+        assert_eq!(exit_code(&error), 4);
+
+        let error = anyhow::anyhow!("database is locked").context(TxtHistoryError::ChatDbLocked);
+        assert_eq!(exit_code(&error), 5);
+
+        assert_eq!(exit_code(&anyhow::anyhow!("something else")), 1);
+    }
+}
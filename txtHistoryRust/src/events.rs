@@ -0,0 +1,389 @@
+use anyhow::Result;
+use chrono::{Datelike, Days, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday};
+use regex::{Captures, Match, Regex};
+
+use crate::commitments::sentences;
+use crate::models::{DbMessage, EventReportEntry, NewEvent};
+
+/// Longest snippet stored for a detected sentence
+const MAX_SNIPPET_CHARS: usize = 200;
+
+const MONTHS: &str = r"(jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sept?(?:ember)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?)";
+
+/// Rule-based detection of dates and times in messages ("Friday at 3", "dentist on the 12th"),
+/// resolved against the local date the message was sent
+pub struct EventExtractor {
+    relative_day: Regex,
+    weekday: Regex,
+    month_day: Regex,
+    day_month: Regex,
+    day_of_month: Regex,
+    clock_time: Regex,
+    hour_with_meridiem: Regex,
+    at_hour: Regex,
+    named_time: Regex,
+}
+
+impl Default for EventExtractor {
+    fn default() -> Self {
+        Self {
+            relative_day: Regex::new(r"(?i)\b(today|tonight|tomorrow|tmrw)\b").unwrap(),
+            weekday: Regex::new(r"(?i)\b(?:(this|next)\s+)?(monday|tuesday|wednesday|thursday|friday|saturday|sunday)\b")
+                .unwrap(),
+            month_day: Regex::new(&format!(r"(?i)\b{}\.?\s+(\d{{1,2}})(?:st|nd|rd|th)?\b", MONTHS)).unwrap(),
+            day_month: Regex::new(&format!(r"(?i)\b(\d{{1,2}})(?:st|nd|rd|th)?\s+(?:of\s+)?{}\b", MONTHS)).unwrap(),
+            day_of_month: Regex::new(r"(?i)\bthe\s+(\d{1,2})(?:st|nd|rd|th)\b").unwrap(),
+            clock_time: Regex::new(r"(?i)\b(\d{1,2}):(\d{2})(?:\s*(am|pm))?\b").unwrap(),
+            hour_with_meridiem: Regex::new(r"(?i)\b(\d{1,2})\s*(am|pm)\b").unwrap(),
+            at_hour: Regex::new(r"(?i)\bat\s+(\d{1,2})\b").unwrap(),
+            named_time: Regex::new(r"(?i)\b(noon|midnight)\b").unwrap(),
+        }
+    }
+}
+
+impl EventExtractor {
+    /// Dates and times in one message's text, one per sentence that mentions a day or a time.
+    /// Returns (date, time, expression, snippet), with relative dates counted from `sent_at`.
+    pub fn detect(
+        &self,
+        text: &str,
+        sent_at: NaiveDateTime,
+    ) -> Vec<(NaiveDate, Option<NaiveTime>, String, String)> {
+        let mut found = Vec::new();
+
+        for sentence in sentences(text) {
+            let date = self.find_date(sentence, sent_at.date());
+            let time = self.find_time(sentence);
+
+            let (starts_on, starts_at, spans) = match (date, time) {
+                (Some((date, date_span)), Some((time, time_span))) => (date, Some(time), vec![date_span, time_span]),
+                (Some((date, date_span)), None) => (date, None, vec![date_span]),
+                // A time alone means the next time the clock shows it
+                (None, Some((time, time_span))) => {
+                    let date = if time > sent_at.time() { sent_at.date() } else { sent_at.date() + Days::new(1) };
+                    (date, Some(time), vec![time_span])
+                },
+                (None, None) => continue,
+            };
+
+            let start = spans.iter().map(|(start, _)| *start).min().unwrap_or(0);
+            let end = spans.iter().map(|(_, end)| *end).max().unwrap_or(sentence.len());
+            found.push((
+                starts_on,
+                starts_at,
+                sentence[start..end].to_string(),
+                sentence.chars().take(MAX_SNIPPET_CHARS).collect(),
+            ));
+        }
+
+        found
+    }
+
+    /// Detect events in one conversation's messages. Message times are UTC; dates are
+    /// resolved in local time.
+    pub fn extract(&self, messages: &[DbMessage]) -> Vec<NewEvent> {
+        let mut events = Vec::new();
+
+        for message in messages {
+            let Some(text) = &message.text else {
+                continue;
+            };
+            let sent_at = Local.from_utc_datetime(&message.date_created).naive_local();
+
+            for (starts_on, starts_at, expression, snippet) in self.detect(text, sent_at) {
+                events.push(NewEvent { message_id: message.id, starts_on, starts_at, expression, snippet });
+            }
+        }
+
+        events
+    }
+
+    fn find_date(&self, sentence: &str, sent_on: NaiveDate) -> Option<(NaiveDate, (usize, usize))> {
+        if let Some(caps) = self.relative_day.captures(sentence) {
+            let days = match caps[1].to_lowercase().as_str() {
+                "tomorrow" | "tmrw" => 1,
+                _ => 0,
+            };
+            return Some((sent_on + Days::new(days), span(caps.get(0)?)));
+        }
+
+        if let Some(caps) = self.weekday.captures(sentence) {
+            let weekday: Weekday = caps[2].parse().ok()?;
+            // "this Friday" sent on a Friday is today; otherwise the next one after today
+            let same_day = caps.get(1).is_some_and(|m| m.as_str().eq_ignore_ascii_case("this"));
+            let mut date = if same_day { sent_on } else { sent_on + Days::new(1) };
+            while date.weekday() != weekday {
+                date = date + Days::new(1);
+            }
+            return Some((date, span(caps.get(0)?)));
+        }
+
+        for (regex, month_group, day_group) in [(&self.month_day, 1, 2), (&self.day_month, 2, 1)] {
+            if let Some(caps) = regex.captures(sentence) {
+                if let Some(date) = month_and_day(&caps, month_group, day_group, sent_on) {
+                    return Some((date, span(caps.get(0)?)));
+                }
+            }
+        }
+
+        if let Some(caps) = self.day_of_month.captures(sentence) {
+            let day: u32 = caps[1].parse().ok()?;
+            // The next month with that day, starting with this one
+            let first = sent_on.with_day(1)?;
+            let date = (0..12)
+                .filter_map(|i| first.checked_add_months(Months::new(i))?.with_day(day))
+                .find(|date| *date >= sent_on)?;
+            return Some((date, span(caps.get(0)?)));
+        }
+
+        None
+    }
+
+    fn find_time(&self, sentence: &str) -> Option<(NaiveTime, (usize, usize))> {
+        if let Some(caps) = self.clock_time.captures(sentence) {
+            let hour: u32 = caps[1].parse().ok()?;
+            let minute: u32 = caps[2].parse().ok()?;
+            let time = to_time(hour, minute, caps.get(3).map(|m| m.as_str()))?;
+            return Some((time, with_at(sentence, span(caps.get(0)?))));
+        }
+
+        if let Some(caps) = self.hour_with_meridiem.captures(sentence) {
+            let hour: u32 = caps[1].parse().ok()?;
+            let time = to_time(hour, 0, Some(&caps[2]))?;
+            return Some((time, with_at(sentence, span(caps.get(0)?))));
+        }
+
+        if let Some(caps) = self.named_time.captures(sentence) {
+            let hour = if caps[1].eq_ignore_ascii_case("noon") { 12 } else { 0 };
+            return Some((NaiveTime::from_hms_opt(hour, 0, 0)?, with_at(sentence, span(caps.get(0)?))));
+        }
+
+        if let Some(caps) = self.at_hour.captures(sentence) {
+            let hour: u32 = caps[1].parse().ok()?;
+            return Some((to_time(hour, 0, None)?, span(caps.get(0)?)));
+        }
+
+        None
+    }
+}
+
+/// A month and day in the year the message was sent, or the next year if that date has passed
+fn month_and_day(caps: &Captures<'_>, month_group: usize, day_group: usize, sent_on: NaiveDate) -> Option<NaiveDate> {
+    let month = month_number(&caps[month_group])?;
+    let day: u32 = caps[day_group].parse().ok()?;
+
+    let date = NaiveDate::from_ymd_opt(sent_on.year(), month, day)?;
+    if date >= sent_on {
+        Some(date)
+    } else {
+        NaiveDate::from_ymd_opt(sent_on.year() + 1, month, day)
+    }
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    let prefix: String = name.to_lowercase().chars().take(3).collect();
+    ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"]
+        .iter()
+        .position(|month| *month == prefix)
+        .map(|i| i as u32 + 1)
+}
+
+/// A time of day. Without AM or PM, 1 to 7 o'clock means the afternoon, since that's when
+/// plans are usually made for ("pick her up at 3").
+fn to_time(hour: u32, minute: u32, meridiem: Option<&str>) -> Option<NaiveTime> {
+    let hour = match meridiem.map(str::to_lowercase).as_deref() {
+        Some("am") if (1..=12).contains(&hour) => hour % 12,
+        Some("pm") if (1..=12).contains(&hour) => hour % 12 + 12,
+        Some(_) => return None,
+        None if (1..=7).contains(&hour) => hour + 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn span(m: Match<'_>) -> (usize, usize) {
+    (m.start(), m.end())
+}
+
+/// Widen a time's span to include a preceding "at ", so the expression reads "Friday at 3pm"
+fn with_at(sentence: &str, (start, end): (usize, usize)) -> (usize, usize) {
+    let before = sentence[..start].to_ascii_lowercase();
+    if before == "at " || before.ends_with(" at ") {
+        (start - 3, end)
+    } else {
+        (start, end)
+    }
+}
+
+/// Render events as an iCalendar file. Events with a time last an hour; the rest are all-day.
+/// Each event's description names the message it came from.
+pub fn to_ics(entries: &[EventReportEntry], calendar_name: &str) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//txt-history//Detected events//EN".to_string(),
+        format!("X-WR-CALNAME:{}", ics_text(calendar_name)),
+    ];
+
+    for entry in entries {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-{}@txt-history", entry.message_id, ics_uid_part(&entry.expression)));
+        lines.push(format!("DTSTAMP:{}", entry.date_created.format("%Y%m%dT%H%M%SZ")));
+        match entry.starts_at {
+            Some(time) => {
+                let start = entry.starts_on.and_time(time);
+                lines.push(format!("DTSTART:{}", start.format("%Y%m%dT%H%M%S")));
+                lines.push("DURATION:PT1H".to_string());
+            },
+            None => lines.push(format!("DTSTART;VALUE=DATE:{}", entry.starts_on.format("%Y%m%d"))),
+        }
+        lines.push(format!("SUMMARY:{}", ics_text(&entry.snippet)));
+        lines.push(format!(
+            "DESCRIPTION:{}",
+            ics_text(&format!(
+                "{} said \"{}\" on {}.\nMessage {} ({})",
+                entry.sender,
+                entry.snippet,
+                Local.from_utc_datetime(&entry.date_created).format("%b %-d, %Y %-I:%M %p"),
+                entry.message_id,
+                entry.imessage_id
+            ))
+        ));
+        lines.push(format!("X-TXT-HISTORY-MESSAGE-ID:{}", entry.message_id));
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line)).collect::<Vec<_>>().join("\r\n") + "\r\n"
+}
+
+/// One row per event with the message it came from
+pub fn to_csv(entries: &[EventReportEntry]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["date", "time", "expression", "snippet", "sender", "sent_at", "message_id", "imessage_id"])?;
+    for entry in entries {
+        writer.write_record([
+            entry.starts_on.to_string(),
+            entry.starts_at.map(|t| t.format("%H:%M").to_string()).unwrap_or_default(),
+            entry.expression.clone(),
+            entry.snippet.clone(),
+            entry.sender.clone(),
+            Local.from_utc_datetime(&entry.date_created).format("%Y-%m-%d %H:%M:%S").to_string(),
+            entry.message_id.to_string(),
+            entry.imessage_id.clone(),
+        ])?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Escape text for an iCalendar property value
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+        .replace('\r', "")
+}
+
+fn ics_uid_part(expression: &str) -> String {
+    expression
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+/// Fold a content line at 75 bytes, continuing on lines that start with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut width = 0;
+
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn detect(text: &str) -> Vec<(String, String)> {
+        // Monday, January 20, 2025 at 9:00
+        EventExtractor::default()
+            .detect(text, at("2025-01-20 09:00"))
+            .into_iter()
+            .map(|(date, time, expression, _)| {
+                let when = match time {
+                    Some(time) => format!("{} {}", date, time.format("%H:%M")),
+                    None => date.to_string(),
+                };
+                (when, expression)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("Can you take her Friday at 3?"), vec![("2025-01-24 15:00".into(), "Friday at 3".into())]);
+        assert_eq!(detect("Dentist on the 12th"), vec![("2025-02-12".into(), "the 12th".into())]);
+        assert_eq!(detect("Hearing is March 4th at 9:30am"), vec![("2025-03-04 09:30".into(), "March 4th at 9:30am".into())]);
+        assert_eq!(detect("See you tomorrow at noon."), vec![("2025-01-21 12:00".into(), "tomorrow at noon".into())]);
+        assert_eq!(detect("this monday works"), vec![("2025-01-20".into(), "this monday".into())]);
+        assert_eq!(detect("pickup at 8"), vec![("2025-01-21 08:00".into(), "at 8".into())]);
+        assert_eq!(detect("Her birthday is 5 January"), vec![("2026-01-05".into(), "5 January".into())]);
+        assert!(detect("She scored twice. What a game!").is_empty());
+        assert!(detect("February 30 at 25:00").is_empty());
+    }
+
+    #[test]
+    fn test_ics_and_csv() {
+        let entries = vec![
+            EventReportEntry {
+                message_id: 7,
+                imessage_id: "guid-7".to_string(),
+                sender: "Phil".to_string(),
+                date_created: at("2025-01-20 09:00"),
+                starts_on: NaiveDate::from_ymd_opt(2025, 1, 24).unwrap(),
+                starts_at: NaiveTime::from_hms_opt(15, 0, 0),
+                expression: "Friday at 3".to_string(),
+                snippet: "Can you take her Friday at 3, after school?".to_string(),
+            },
+            EventReportEntry {
+                message_id: 8,
+                imessage_id: "guid-8".to_string(),
+                sender: "Jess".to_string(),
+                date_created: at("2025-01-20 10:00"),
+                starts_on: NaiveDate::from_ymd_opt(2025, 2, 12).unwrap(),
+                starts_at: None,
+                expression: "the 12th".to_string(),
+                snippet: "Dentist on the 12th".to_string(),
+            },
+        ];
+
+        let ics = to_ics(&entries, "Phil");
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("UID:7-friday-at-3@txt-history\r\n"));
+        assert!(ics.contains("DTSTART:20250124T150000\r\nDURATION:PT1H\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20250212\r\n"));
+        assert!(ics.contains("SUMMARY:Can you take her Friday at 3\\, after school?\r\n"));
+        assert!(ics.contains("X-TXT-HISTORY-MESSAGE-ID:8\r\n"));
+        assert!(ics.lines().all(|line| line.len() <= 75));
+
+        let csv = to_csv(&entries).unwrap();
+        assert!(csv.starts_with("date,time,expression,snippet,sender,sent_at,message_id,imessage_id\n"));
+        assert!(csv.contains("2025-02-12,,the 12th,Dentist on the 12th,Jess,"));
+        assert!(csv.trim_end().ends_with(",8,guid-8"));
+    }
+}
@@ -0,0 +1,288 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::ExportConfig;
+use crate::file_writer::{Chunking, CsvOptions, WritePolicy};
+use crate::legal::LegalOptions;
+use crate::models::{DateRange, OutputFormat, ServiceFilter};
+use crate::sources::SourceFilter;
+use crate::style::ExportStyle;
+use crate::time_filter::TimeFilter;
+
+/// Everything that shapes a conversation export other than who it's with. Start from
+/// `ExportOptions::new` and set what differs from the defaults, so new options can be added
+/// without changing `MessageRepository` or its callers.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    output_path: PathBuf,
+    format: OutputFormat,
+    date_range: DateRange,
+    chunking: Chunking,
+    csv_options: CsvOptions,
+    style: ExportStyle,
+    policy: WritePolicy,
+    /// A single EPUB book instead of `format` files
+    epub: bool,
+    service: ServiceFilter,
+    sources: SourceFilter,
+    time_filter: TimeFilter,
+    template: Option<String>,
+    nlp_version: Option<String>,
+    legal: Option<LegalOptions>,
+    split_by_sender: bool,
+    parallel_writes: usize,
+    hashes: bool,
+    canonical: bool,
+}
+
+impl ExportOptions {
+    /// Export everything as a single TXT file at `output_path`, replacing existing files
+    pub fn new(output_path: impl Into<PathBuf>) -> Self {
+        Self {
+            output_path: output_path.into(),
+            format: OutputFormat::Txt,
+            date_range: DateRange { start: None, end: None },
+            chunking: Chunking::Single,
+            csv_options: CsvOptions::default(),
+            style: ExportStyle::default(),
+            policy: WritePolicy::Overwrite,
+            epub: false,
+            service: ServiceFilter::All,
+            sources: SourceFilter::default(),
+            time_filter: TimeFilter::default(),
+            template: None,
+            nlp_version: None,
+            legal: None,
+            split_by_sender: false,
+            parallel_writes: ExportConfig::default().parallel_writes,
+            hashes: false,
+            canonical: false,
+        }
+    }
+
+    /// Write somewhere else, e.g. each contact's own directory in a multi-contact export
+    pub fn with_output_path(mut self, output_path: impl Into<PathBuf>) -> Self {
+        self.output_path = output_path.into();
+        self
+    }
+
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Format from `--format`: txt, csv, json, parquet or epub. Anything else writes TXT.
+    pub fn with_format_name(mut self, format: &str) -> Self {
+        self.epub = format.eq_ignore_ascii_case("epub");
+        self.format = match format.to_lowercase().as_str() {
+            "csv" => OutputFormat::Csv,
+            "json" => OutputFormat::Json,
+            "parquet" => OutputFormat::Parquet,
+            _ => OutputFormat::Txt,
+        };
+        self
+    }
+
+    /// Only export messages sent within `date_range`
+    pub fn with_date_range(mut self, date_range: DateRange) -> Self {
+        self.date_range = date_range;
+        self
+    }
+
+    pub fn with_chunking(mut self, chunking: Chunking) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    pub fn with_csv_options(mut self, csv_options: CsvOptions) -> Self {
+        self.csv_options = csv_options;
+        self
+    }
+
+    pub fn with_style(mut self, style: ExportStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// What to do when an export file already exists
+    pub fn with_policy(mut self, policy: WritePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Only export messages sent over this service
+    pub fn with_service(mut self, service: ServiceFilter) -> Self {
+        self.service = service;
+        self
+    }
+
+    /// Only export messages from these sources, preferring the earlier ones when the same
+    /// exchange was imported from several
+    pub fn with_sources(mut self, sources: SourceFilter) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    pub fn with_time_filter(mut self, time_filter: TimeFilter) -> Self {
+        self.time_filter = time_filter;
+        self
+    }
+
+    /// Built-in template name or template file to render each chunk with
+    pub fn with_template(mut self, template: Option<String>) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Processing version whose NLP results are joined into Parquet exports
+    pub fn with_nlp_version(mut self, nlp_version: Option<String>) -> Self {
+        self.nlp_version = nlp_version;
+        self
+    }
+
+    /// Write a numbered legal document instead of chunk files
+    pub fn with_legal(mut self, legal: Option<LegalOptions>) -> Self {
+        self.legal = legal;
+        self
+    }
+
+    /// Also write one file per participant
+    pub fn with_split_by_sender(mut self, split_by_sender: bool) -> Self {
+        self.split_by_sender = split_by_sender;
+        self
+    }
+
+    /// How many chunk files are written at the same time
+    pub fn with_parallel_writes(mut self, parallel_writes: usize) -> Self {
+        self.parallel_writes = parallel_writes;
+        self
+    }
+
+    /// Write a manifest of message and file hashes next to the export
+    pub fn with_hashes(mut self, hashes: bool) -> Self {
+        self.hashes = hashes;
+        self
+    }
+
+    /// Write one stable, diff-friendly TXT file
+    pub fn with_canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// Where the export goes; chunked exports add `_chunk_N` to the file name
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// `--format` value the files are written in
+    pub fn format_name(&self) -> &'static str {
+        if self.epub {
+            return "epub";
+        }
+        match self.format {
+            OutputFormat::Txt => "txt",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Parquet => "parquet",
+        }
+    }
+
+    pub fn date_range(&self) -> &DateRange {
+        &self.date_range
+    }
+
+    pub fn chunking(&self) -> Chunking {
+        self.chunking
+    }
+
+    pub fn csv_options(&self) -> &CsvOptions {
+        &self.csv_options
+    }
+
+    pub fn style(&self) -> &ExportStyle {
+        &self.style
+    }
+
+    pub fn policy(&self) -> WritePolicy {
+        self.policy
+    }
+
+    pub fn epub(&self) -> bool {
+        self.epub
+    }
+
+    pub fn service(&self) -> ServiceFilter {
+        self.service
+    }
+
+    pub fn sources(&self) -> &SourceFilter {
+        &self.sources
+    }
+
+    pub fn time_filter(&self) -> &TimeFilter {
+        &self.time_filter
+    }
+
+    pub fn template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
+    pub fn nlp_version(&self) -> Option<&str> {
+        self.nlp_version.as_deref()
+    }
+
+    pub fn legal(&self) -> Option<&LegalOptions> {
+        self.legal.as_ref()
+    }
+
+    pub fn split_by_sender(&self) -> bool {
+        self.split_by_sender
+    }
+
+    pub fn parallel_writes(&self) -> usize {
+        self.parallel_writes
+    }
+
+    pub fn hashes(&self) -> bool {
+        self.hashes
+    }
+
+    pub fn canonical(&self) -> bool {
+        self.canonical
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_options() {
+        let options = ExportOptions::new("output/phil_conversation");
+        assert_eq!(options.output_path(), Path::new("output/phil_conversation"));
+        assert_eq!(options.chunking(), Chunking::Single);
+        assert_eq!(options.policy(), WritePolicy::Overwrite);
+        assert!(options.date_range().start.is_none());
+
+        let options = options
+            .with_chunking(Chunking::Lines(500))
+            .with_policy(WritePolicy::SkipExisting);
+        assert_eq!(options.chunking(), Chunking::Lines(500));
+        assert_eq!(options.policy(), WritePolicy::SkipExisting);
+        assert_eq!(options.service(), ServiceFilter::All);
+        assert!(options.sources().matches(Some("WhatsApp")));
+        assert!(!options.hashes() && options.template().is_none());
+
+        let epub = options.clone().with_format_name("EPUB");
+        assert!(epub.epub());
+        assert!(matches!(epub.format(), OutputFormat::Txt));
+        assert_eq!(epub.format_name(), "epub");
+        let csv = options.with_format_name("csv");
+        assert!(!csv.epub() && matches!(csv.format(), OutputFormat::Csv));
+        assert_eq!(csv.format_name(), "csv");
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::db::Database;
+use crate::file_writer::{WriteStatus, WrittenFile};
+use crate::integrity::{chain_hash, file_hash, message_hash};
+use crate::models::{DbExportChunk, Message};
+
+/// Hash identifying an export: `description` covers the query and format options, and the
+/// rolling hash of the selected messages catches messages added, edited or purged since
+pub fn export_hash(description: &str, messages: &[Message]) -> String {
+    let hashes: Vec<String> = messages.iter().map(message_hash).collect();
+    let mut parts = vec![description.to_string()];
+    parts.push(chain_hash(&hashes));
+    chain_hash(&parts)
+}
+
+/// Tracks which chunks of an export have been written, so an interrupted export can pick up
+/// where it stopped instead of writing everything again
+pub struct ExportProgress<'a> {
+    db: &'a Database,
+    export_id: i32,
+    completed: HashMap<usize, DbExportChunk>,
+}
+
+impl<'a> ExportProgress<'a> {
+    /// Record a new export of `total_chunks` chunks to `output_dir`
+    pub fn start(db: &'a Database, query_hash: &str, output_dir: &str, total_chunks: usize) -> Result<Self> {
+        let run = db.create_export_run(query_hash, output_dir, total_chunks)?;
+        Ok(Self {
+            db,
+            export_id: run.id,
+            completed: HashMap::new(),
+        })
+    }
+
+    /// Continue export `export_id`. Fails if the export would now write something different:
+    /// other query options, changed messages or another output directory.
+    pub fn resume(db: &'a Database, export_id: i32, query_hash: &str, output_dir: &str) -> Result<Self> {
+        let run = db
+            .get_export_run(export_id)?
+            .ok_or_else(|| anyhow::anyhow!("Export not found: {}", export_id))?;
+
+        if run.query_hash != query_hash {
+            return Err(anyhow::anyhow!(
+                "Export {} was run with different options or messages have changed since; start a new export instead",
+                export_id
+            ));
+        }
+        if run.output_dir != output_dir {
+            return Err(anyhow::anyhow!(
+                "Export {} wrote to {}, not {}",
+                export_id,
+                run.output_dir,
+                output_dir
+            ));
+        }
+
+        let completed = db
+            .get_export_chunks(export_id)?
+            .into_iter()
+            .map(|chunk| (chunk.chunk_index, chunk))
+            .collect();
+
+        Ok(Self {
+            db,
+            export_id,
+            completed,
+        })
+    }
+
+    pub fn export_id(&self) -> i32 {
+        self.export_id
+    }
+
+    /// Chunks recorded as written, whether or not their files are still intact
+    pub fn completed_count(&self) -> usize {
+        self.completed.len()
+    }
+
+    /// The file for chunk `index` if an earlier run wrote it and it still has the recorded
+    /// checksum. Missing or modified files are written again.
+    pub fn completed_chunk(&self, index: usize) -> Option<WrittenFile> {
+        let chunk = self.completed.get(&index)?;
+        let hash = file_hash(Path::new(&chunk.path)).ok()?;
+        (hash == chunk.sha256).then(|| WrittenFile::new(&chunk.path, chunk.messages, chunk.bytes, WriteStatus::Resumed))
+    }
+
+    /// Record that chunk `index` has been written. Skipped and appended files don't hold
+    /// exactly the chunk's messages, so they aren't recorded and get written again on resume.
+    pub fn record(&self, index: usize, file: &WrittenFile) -> Result<()> {
+        if !matches!(file.status, WriteStatus::Created | WriteStatus::Replaced) {
+            return Ok(());
+        }
+
+        let path = Path::new(&file.path);
+        self.db.record_export_chunk(&DbExportChunk {
+            export_id: self.export_id,
+            chunk_index: index,
+            path: file.path.clone(),
+            sha256: file_hash(path)?,
+            messages: file.messages,
+            bytes: std::fs::metadata(path)?.len(),
+        })
+    }
+
+    /// Mark the export as complete
+    pub fn finish(&self) -> Result<()> {
+        self.db.finish_export_run(self.export_id)
+    }
+}
@@ -0,0 +1,1453 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use crate::columnar::write_parquet_file;
+use crate::export_progress::ExportProgress;
+use crate::models::{Message, OutputFormat};
+use crate::sources::has_multiple_sources;
+use crate::style::ExportStyle;
+use crate::templates::{chunk_file_name, render_chunk_index, ExportTemplate, CHUNK_INDEX_FILE};
+use crate::totals::ExportTotals;
+use crate::verbosity::{detail, progress};
+
+/// What to do when an export file already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WritePolicy {
+    /// Replace the file once the new one is completely written
+    #[default]
+    Overwrite,
+    /// Leave the existing file alone
+    SkipExisting,
+    /// Add the messages to the end of the file (not supported for Parquet)
+    Append,
+}
+
+impl WritePolicy {
+    /// Pick the policy from command line flags; `--overwrite` wins, then `--skip-existing`
+    pub fn from_flags(overwrite: bool, skip_existing: bool, append: bool) -> Self {
+        match (overwrite, skip_existing, append) {
+            (true, _, _) => Self::Overwrite,
+            (false, true, _) => Self::SkipExisting,
+            (false, false, true) => Self::Append,
+            (false, false, false) => Self::Overwrite,
+        }
+    }
+}
+
+/// How an export file was written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteStatus {
+    Created,
+    Replaced,
+    Appended,
+    Skipped,
+    /// Written by an interrupted export and left in place when it was resumed
+    Resumed,
+}
+
+/// One file written by an export
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrittenFile {
+    pub path: String,
+    /// Messages in the file's chunk, including when it was skipped
+    pub messages: usize,
+    /// Bytes added to the file (0 when skipped)
+    pub bytes: u64,
+    pub status: WriteStatus,
+}
+
+impl WrittenFile {
+    pub(crate) fn new(path: &str, messages: usize, bytes: u64, status: WriteStatus) -> Self {
+        Self {
+            path: path.to_string(),
+            messages,
+            bytes,
+            status,
+        }
+    }
+
+    /// One line for the export log, e.g. "Wrote 120 messages (18.2 KB) to out/chunk_1.txt"
+    pub fn summary(&self) -> String {
+        let size = format_size(self.bytes);
+
+        match self.status {
+            WriteStatus::Created | WriteStatus::Replaced => {
+                format!("Wrote {} messages ({}) to {}", self.messages, size, self.path)
+            },
+            WriteStatus::Appended => format!("Appended {} messages ({}) to {}", self.messages, size, self.path),
+            WriteStatus::Skipped => format!("Skipped {}, it already exists", self.path),
+            WriteStatus::Resumed => format!("Kept {} from the interrupted export", self.path),
+        }
+    }
+
+    /// Print `summary()` unless quiet, and with `-v` the date range of the chunk's messages and
+    /// how long the file took to write
+    pub(crate) fn report(&self, messages: &[Message], elapsed: Option<Duration>) {
+        progress(self.summary());
+
+        if let (Some(first), Some(last)) = (messages.first(), messages.last()) {
+            let took = elapsed.map(|e| format!(", written in {} ms", e.as_millis())).unwrap_or_default();
+            detail(format!(
+                "  {} to {}{}",
+                first.timestamp.format("%Y-%m-%d %H:%M"),
+                last.timestamp.format("%Y-%m-%d %H:%M"),
+                took
+            ));
+        }
+    }
+}
+
+/// Write `file_path` according to `policy`. New content goes to a temporary file next to
+/// the target that is renamed over it once complete, so a crash never leaves a partial
+/// file behind. Appends go to the existing file directly; `write` is told when it's
+/// appending to a non-empty file so it can leave out headers.
+pub(crate) fn write_file(
+    file_path: &str,
+    messages: usize,
+    policy: WritePolicy,
+    write: impl FnOnce(&mut BufWriter<File>, bool) -> Result<()>,
+) -> Result<WrittenFile> {
+    let path = Path::new(file_path);
+    let existing_len = fs::metadata(path).ok().map(|metadata| metadata.len());
+    let written = |bytes: u64, status: WriteStatus| WrittenFile::new(file_path, messages, bytes, status);
+
+    let status = match (existing_len, policy) {
+        (Some(_), WritePolicy::SkipExisting) => return Ok(written(0, WriteStatus::Skipped)),
+        (Some(len), WritePolicy::Append) => {
+            let file = OpenOptions::new()
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open {}", file_path))?;
+            let mut writer = BufWriter::new(file);
+            write(&mut writer, len > 0)?;
+            writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            return Ok(written(fs::metadata(path)?.len() - len, WriteStatus::Appended));
+        },
+        (Some(_), _) => WriteStatus::Replaced,
+        (None, _) => WriteStatus::Created,
+    };
+
+    let temp_path = format!("{}.{}.tmp", file_path, std::process::id());
+    let result = (|| -> Result<u64> {
+        let file = File::create(&temp_path).with_context(|| format!("Failed to create {}", temp_path))?;
+        let mut writer = BufWriter::new(file);
+        write(&mut writer, false)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        Ok(file.metadata()?.len())
+    })();
+
+    match result {
+        Ok(bytes) => {
+            fs::rename(&temp_path, path).with_context(|| format!("Failed to move {} into place", temp_path))?;
+            Ok(written(bytes, status))
+        },
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        },
+    }
+}
+
+/// Run `write` for every job on up to `parallel_writes` threads, returning the files in job
+/// order. No new jobs are started once one fails, and the first failure is returned.
+pub(crate) fn write_in_parallel<T: Sync>(
+    jobs: &[T],
+    parallel_writes: usize,
+    write: impl Fn(usize, &T) -> Result<WrittenFile> + Sync,
+) -> Result<Vec<WrittenFile>> {
+    let workers = parallel_writes.clamp(1, jobs.len().max(1));
+    if workers == 1 {
+        return jobs.iter().enumerate().map(|(i, job)| write(i, job)).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let worker = || {
+        let mut done = Vec::new();
+        while !failed.load(Ordering::Relaxed) {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(job) = jobs.get(i) else { break };
+            let result = write(i, job);
+            if result.is_err() {
+                failed.store(true, Ordering::Relaxed);
+            }
+            done.push((i, result));
+        }
+        done
+    };
+
+    let mut results: Vec<(usize, Result<WrittenFile>)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers).map(|_| scope.spawn(worker)).collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("export writer thread panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Write messages to files with chunking, returning each file written. Up to
+/// `parallel_writes` chunk files are written at the same time.
+pub fn write_messages_to_files(
+    messages: &[Message],
+    format: OutputFormat,
+    csv_options: &CsvOptions,
+    style: &ExportStyle,
+    chunking: Chunking,
+    output_dir: &str,
+    policy: WritePolicy,
+    parallel_writes: usize,
+) -> Result<Vec<WrittenFile>> {
+    write_messages_with_progress(
+        messages,
+        format,
+        csv_options,
+        style,
+        chunking,
+        output_dir,
+        policy,
+        parallel_writes,
+        None,
+    )
+}
+
+/// Like `write_messages_to_files`, recording each chunk in `progress` once it's written.
+/// Chunks an earlier run of the export already wrote, with unchanged checksums, are kept.
+pub fn write_messages_with_progress(
+    messages: &[Message],
+    format: OutputFormat,
+    csv_options: &CsvOptions,
+    style: &ExportStyle,
+    chunking: Chunking,
+    output_dir: &str,
+    policy: WritePolicy,
+    parallel_writes: usize,
+    progress: Option<&ExportProgress<'_>>,
+) -> Result<Vec<WrittenFile>> {
+    if messages.is_empty() {
+        crate::verbosity::progress("No messages to write");
+        return Ok(Vec::new());
+    }
+
+    // Label sources only when the export mixes them, so single-source output is unchanged
+    let label_sources = has_multiple_sources(messages);
+
+    // Create chunks
+    let chunks = chunking.split(messages, format, csv_options, style)?;
+    crate::verbosity::progress(format!("Writing {} chunks", chunks.len()));
+
+    // Process each chunk
+    write_in_parallel(&chunks, parallel_writes, |i, chunk| {
+        if let Some(done) = progress.and_then(|progress| progress.completed_chunk(i)) {
+            done.report(chunk, None);
+            return Ok(done);
+        }
+
+        let file_base = format!("{}/chunk_{}", output_dir, i + 1);
+        let written = write_format_file(chunk, format, &file_base, csv_options, style, label_sources, policy)?;
+        if let Some(progress) = progress {
+            progress.record(i, &written)?;
+        }
+        Ok(written)
+    })
+}
+
+/// Write one chunk in `format` to `file_base` plus the format's extension
+fn write_format_file(
+    messages: &[Message],
+    format: OutputFormat,
+    file_base: &str,
+    csv_options: &CsvOptions,
+    style: &ExportStyle,
+    label_sources: bool,
+    policy: WritePolicy,
+) -> Result<WrittenFile> {
+    let started = Instant::now();
+    let written = match format {
+        OutputFormat::Txt => {
+            write_txt_file(messages, &format!("{}.txt", file_base), label_sources, style, policy)?
+        },
+        OutputFormat::Csv => write_csv_file(messages, &format!("{}.csv", file_base), csv_options, policy)?,
+        OutputFormat::Json => write_json_file(messages, &format!("{}.json", file_base), policy)?,
+        OutputFormat::Parquet => write_parquet_file(messages, &format!("{}.parquet", file_base), None, policy)?,
+    };
+
+    written.report(messages, Some(started.elapsed()));
+    Ok(written)
+}
+
+/// Write each sender's messages to their own files next to the merged transcript, e.g.
+/// `phil.txt` and `jess.txt`. A sender whose messages need more than one chunk gets
+/// `phil_chunk_1.txt`, `phil_chunk_2.txt` and so on.
+pub fn write_sender_files(
+    messages: &[Message],
+    format: OutputFormat,
+    template: Option<&ExportTemplate>,
+    contact: &str,
+    csv_options: &CsvOptions,
+    style: &ExportStyle,
+    chunking: Chunking,
+    output_dir: &str,
+    policy: WritePolicy,
+    parallel_writes: usize,
+) -> Result<Vec<WrittenFile>> {
+    // Keep source labels consistent with the merged transcript
+    let label_sources = has_multiple_sources(messages);
+    let senders = group_by_sender(messages);
+
+    // One job per file: its base path, chunk number, chunk count and messages
+    let mut jobs: Vec<(String, usize, usize, &[Message])> = Vec::new();
+    for (stem, sender_messages) in &senders {
+        let measure_format = if template.is_some() { OutputFormat::Txt } else { format };
+        let chunks = chunking.split(sender_messages, measure_format, csv_options, style)?;
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let file_base = if chunks.len() == 1 {
+                format!("{}/{}", output_dir, stem)
+            } else {
+                format!("{}/{}_chunk_{}", output_dir, stem, i + 1)
+            };
+            jobs.push((file_base, i + 1, chunks.len(), chunk));
+        }
+    }
+
+    write_in_parallel(&jobs, parallel_writes, |_, (file_base, chunk_num, chunk_count, chunk)| {
+        match template {
+            Some(template) => {
+                let started = Instant::now();
+                let file_path = format!("{}.{}", file_base, template.extension());
+                let output = template.render(chunk, contact, *chunk_num, *chunk_count, label_sources, style)?;
+                let file = write_file(&file_path, chunk.len(), policy, |writer, _| {
+                    Ok(writer.write_all(output.as_bytes())?)
+                })?;
+                file.report(chunk, Some(started.elapsed()));
+                Ok(file)
+            },
+            None => write_format_file(chunk, format, file_base, csv_options, style, label_sources, policy),
+        }
+    })
+}
+
+/// Messages grouped by sender in order of each sender's first message, keyed by a file name
+/// stem derived from the sender ("Phil Smith" becomes "phil_smith")
+fn group_by_sender(messages: &[Message]) -> Vec<(String, Vec<Message>)> {
+    let mut senders: Vec<(&str, Vec<Message>)> = Vec::new();
+    for message in messages {
+        match senders.iter_mut().find(|(sender, _)| *sender == message.sender) {
+            Some((_, sender_messages)) => sender_messages.push(message.clone()),
+            None => senders.push((&message.sender, vec![message.clone()])),
+        }
+    }
+
+    let mut stems: Vec<String> = Vec::new();
+    senders
+        .into_iter()
+        .map(|(sender, sender_messages)| {
+            let slug: String = sender
+                .to_lowercase()
+                .chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                .collect();
+            let slug = slug.trim_matches('_');
+            let slug = if slug.is_empty() { "unknown" } else { slug };
+
+            // Senders like "Phil S." and "Phil S" would otherwise overwrite each other
+            let mut stem = slug.to_string();
+            let mut n = 2;
+            while stems.contains(&stem) {
+                stem = format!("{}_{}", slug, n);
+                n += 1;
+            }
+            stems.push(stem.clone());
+
+            (stem, sender_messages)
+        })
+        .collect()
+}
+
+/// Write messages to files rendered with an export template, one file per chunk
+pub fn write_templated_files(
+    messages: &[Message],
+    template: &ExportTemplate,
+    contact: &str,
+    style: &ExportStyle,
+    chunking: Chunking,
+    output_dir: &str,
+    policy: WritePolicy,
+    parallel_writes: usize,
+) -> Result<Vec<WrittenFile>> {
+    if messages.is_empty() {
+        progress("No messages to write");
+        return Ok(Vec::new());
+    }
+
+    let label_sources = has_multiple_sources(messages);
+
+    // Template output can't be measured per message, so size it like the TXT layout
+    let chunks = chunking.split(messages, OutputFormat::Txt, &CsvOptions::default(), style)?;
+    progress(format!("Writing {} chunks", chunks.len()));
+
+    let mut written = write_in_parallel(&chunks, parallel_writes, |i, chunk| {
+        let started = Instant::now();
+        let chunk_num = i + 1;
+        let file_path = format!("{}/{}", output_dir, chunk_file_name(chunk_num, template.extension()));
+        let output = template.render(chunk, contact, chunk_num, chunks.len(), label_sources, style)?;
+        let file = write_file(&file_path, chunk.len(), policy, |writer, _| Ok(writer.write_all(output.as_bytes())?))?;
+        file.report(chunk, Some(started.elapsed()));
+        Ok(file)
+    })?;
+
+    // Multi-chunk HTML gets a page linking every chunk. It describes the chunks just written, so
+    // it's always replaced.
+    if template.extension() == "html" && chunks.len() > 1 {
+        let index = render_chunk_index(contact, &chunks, template.extension())?;
+        let file_path = format!("{}/{}", output_dir, CHUNK_INDEX_FILE);
+        let file = write_file(&file_path, 0, WritePolicy::Overwrite, |writer, _| Ok(writer.write_all(index.as_bytes())?))?;
+        progress(format!("Wrote an index of {} chunks to {}", chunks.len(), file.path));
+        written.push(file);
+    }
+
+    Ok(written)
+}
+
+/// A size in KB, or MB from 1 MB up, e.g. "18.2 KB"
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+/// One chunk file an export would write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkEstimate {
+    pub file_name: String,
+    pub messages: usize,
+    pub bytes: usize,
+}
+
+/// The chunk files an export would write, measured with the same sizes the chunker splits by.
+/// TXT sizes (less any totals footer) and CSV sizes are exact; JSON and Parquet sizes are upper
+/// bounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportEstimate {
+    pub chunks: Vec<ChunkEstimate>,
+}
+
+impl ExportEstimate {
+    pub fn new(
+        messages: &[Message],
+        format: OutputFormat,
+        csv_options: &CsvOptions,
+        style: &ExportStyle,
+        chunking: Chunking,
+    ) -> Result<Self> {
+        let label_sources = has_multiple_sources(messages);
+        let extension = match format {
+            OutputFormat::Txt => "txt",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Parquet => "parquet",
+        };
+
+        let chunks = chunking
+            .split(messages, format, csv_options, style)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let message_bytes = chunk
+                    .iter()
+                    .map(|m| serialized_size(m, format, csv_options, label_sources, style))
+                    .sum::<Result<usize>>()?;
+                Ok(ChunkEstimate {
+                    file_name: format!("chunk_{}.{}", i + 1, extension),
+                    messages: chunk.len(),
+                    bytes: message_bytes + file_overhead(format, csv_options),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { chunks })
+    }
+
+    /// Total size of all chunk files in bytes
+    pub fn bytes(&self) -> usize {
+        self.chunks.iter().map(|c| c.bytes).sum()
+    }
+
+    pub fn messages(&self) -> usize {
+        self.chunks.iter().map(|c| c.messages).sum()
+    }
+}
+
+/// What an export is about to write, shown before asking for confirmation
+#[derive(Debug)]
+pub struct ExportPreview<'a> {
+    pub message_count: usize,
+    pub chunk_count: usize,
+    /// Total size of all chunk files in bytes
+    pub estimated_bytes: usize,
+    pub first: Option<&'a Message>,
+    pub last: Option<&'a Message>,
+}
+
+impl<'a> ExportPreview<'a> {
+    /// Measure an export without writing it
+    pub fn new(
+        messages: &'a [Message],
+        format: OutputFormat,
+        csv_options: &CsvOptions,
+        style: &ExportStyle,
+        chunking: Chunking,
+    ) -> Result<Self> {
+        let estimate = ExportEstimate::new(messages, format, csv_options, style, chunking)?;
+
+        Ok(Self {
+            message_count: messages.len(),
+            chunk_count: estimate.chunks.len(),
+            estimated_bytes: estimate.bytes(),
+            first: messages.first(),
+            last: messages.last(),
+        })
+    }
+
+    /// Multi-line description of the export for the confirmation prompt
+    pub fn summary(&self, output_dir: &str) -> String {
+        let mut lines = vec![format!(
+            "{} messages in {} files (about {:.1} MB) will be written to {}",
+            self.message_count,
+            self.chunk_count,
+            self.estimated_bytes as f64 / (1024.0 * 1024.0),
+            output_dir
+        )];
+
+        if let (Some(first), Some(last)) = (self.first, self.last) {
+            lines.push(format!(
+                "Date span: {} to {}",
+                first.timestamp.format("%Y-%m-%d"),
+                last.timestamp.format("%Y-%m-%d")
+            ));
+            lines.push(format!("First: {}", preview_line(first)));
+            lines.push(format!("Last:  {}", preview_line(last)));
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// One message on a single line, truncated for display
+fn preview_line(message: &Message) -> String {
+    const MAX_CHARS: usize = 80;
+
+    let content = message.content.replace('\n', " ");
+    let content = if content.chars().count() > MAX_CHARS {
+        format!("{}...", content.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        content
+    };
+
+    format!("{}, {}, {}", message.sender, message.timestamp.format("%b %d, %Y %r"), content)
+}
+
+/// How messages are split into chunk files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Chunking {
+    /// All messages in one file
+    Single,
+    /// Approximate maximum size of each file in MB
+    SizeMb(f64),
+    /// Maximum number of messages per file
+    Lines(usize),
+    /// Approximate maximum number of LLM tokens per file
+    Tokens(usize),
+}
+
+impl Chunking {
+    /// Pick chunking from command line options; tokens win over size, and size over lines
+    pub fn from_options(size_mb: Option<f64>, lines_per_chunk: Option<usize>, tokens_per_chunk: Option<usize>) -> Self {
+        match (tokens_per_chunk, size_mb, lines_per_chunk) {
+            (Some(tokens), _, _) => Chunking::Tokens(tokens),
+            (None, Some(size), _) => Chunking::SizeMb(size),
+            (None, None, Some(lines)) => Chunking::Lines(lines),
+            (None, None, None) => Chunking::Single,
+        }
+    }
+
+    /// Split messages into consecutive chunks, each with at least one message.
+    /// Size limits are measured against the actual bytes each message takes in `format`.
+    pub fn split<'a>(
+        &self,
+        messages: &'a [Message],
+        format: OutputFormat,
+        csv_options: &CsvOptions,
+        style: &ExportStyle,
+    ) -> Result<Vec<&'a [Message]>> {
+        if messages.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let label_sources = has_multiple_sources(messages);
+
+        let chunks = match *self {
+            Chunking::Single => vec![messages],
+            Chunking::Lines(lines) => messages.chunks(lines.max(1)).collect(),
+            Chunking::SizeMb(size) => {
+                let budget = (size * 1024.0 * 1024.0) as usize;
+                let costs = messages
+                    .iter()
+                    .map(|m| serialized_size(m, format, csv_options, label_sources, style))
+                    .collect::<Result<Vec<_>>>()?;
+                let overhead = file_overhead(format, csv_options);
+                split_by_budget(messages, budget.saturating_sub(overhead), |i, _| costs[i])
+            }
+            Chunking::Tokens(tokens) => split_by_budget(messages, tokens, |_, m| {
+                estimate_tokens(&format_txt_message(m, label_sources, style))
+            }),
+        };
+
+        Ok(chunks)
+    }
+}
+
+/// Bytes a message adds to a file in the given format
+fn serialized_size(
+    message: &Message,
+    format: OutputFormat,
+    csv_options: &CsvOptions,
+    label_sources: bool,
+    style: &ExportStyle,
+) -> Result<usize> {
+    match format {
+        OutputFormat::Txt => Ok(format_txt_message(message, label_sources, style).len()),
+        OutputFormat::Csv => {
+            let mut writer = csv_writer(Vec::new(), csv_options);
+            writer.write_record(csv_options.columns.iter().map(|c| csv_options.normalization.apply(&c.value(message))))?;
+            Ok(writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?.len())
+        }
+        OutputFormat::Json => {
+            // Inside the array every line is indented by two more spaces, and entries are
+            // separated by ",\n" (counted for every message, so the estimate never falls short)
+            let pretty = serde_json::to_string_pretty(message)?;
+            Ok(pretty.len() + 2 * pretty.lines().count() + 2)
+        }
+        // Uncompressed column values plus the timestamp and flag; compression only makes it smaller
+        OutputFormat::Parquet => Ok(message.sender.len()
+            + message.content.len()
+            + message.imessage_id.as_ref().map_or(0, String::len)
+            + message.service.as_ref().map_or(0, String::len)
+            + message.source.as_ref().map_or(0, String::len)
+            + 9),
+    }
+}
+
+/// Bytes every file in the given format has regardless of its messages
+fn file_overhead(format: OutputFormat, csv_options: &CsvOptions) -> usize {
+    match format {
+        OutputFormat::Txt => 0,
+        OutputFormat::Csv if csv_options.header => {
+            let mut writer = csv_writer(Vec::new(), csv_options);
+            let _ = writer.write_record(csv_options.columns.iter().map(|c| c.header()));
+            writer.into_inner().map(|bytes| bytes.len()).unwrap_or(0)
+        }
+        OutputFormat::Csv => 0,
+        // "[\n" and "\n]"
+        OutputFormat::Json => 4,
+        // Magic bytes, schema and footer metadata (approximate)
+        OutputFormat::Parquet => 1024,
+    }
+}
+
+/// Estimate the LLM tokens in some text with the common ~4 characters per token rule of thumb
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Greedily fill chunks until adding the next message would exceed `budget`.
+/// A single message larger than the budget gets a chunk of its own.
+fn split_by_budget<'a>(
+    messages: &'a [Message],
+    budget: usize,
+    cost: impl Fn(usize, &Message) -> usize,
+) -> Vec<&'a [Message]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut used: usize = 0;
+
+    for (i, message) in messages.iter().enumerate() {
+        let message_cost = cost(i, message);
+        if i > start && used.saturating_add(message_cost) > budget {
+            chunks.push(&messages[start..i]);
+            start = i;
+            used = 0;
+        }
+        used = used.saturating_add(message_cost);
+    }
+    chunks.push(&messages[start..]);
+
+    chunks
+}
+
+/// Write messages to a text file, prefixing each line with its source when `label_sources` is set
+/// and with the sender's prefix when the style turns TXT prefixes on
+pub fn write_txt_file(
+    messages: &[Message],
+    file_path: &str,
+    label_sources: bool,
+    style: &ExportStyle,
+    policy: WritePolicy,
+) -> Result<WrittenFile> {
+    write_file(file_path, messages.len(), policy, |writer, _| write_txt(writer, messages, label_sources, style))
+}
+
+/// Write messages in the TXT layout to `writer`, followed by their totals when the style asks
+/// for them. Appends get a footer for the appended messages only.
+pub(crate) fn write_txt(
+    writer: &mut impl Write,
+    messages: &[Message],
+    label_sources: bool,
+    style: &ExportStyle,
+) -> Result<()> {
+    for message in messages {
+        write!(writer, "{}", format_txt_message(message, label_sources, style))?;
+    }
+    if style.txt_totals {
+        write!(writer, "{}", ExportTotals::from_messages(messages).txt_footer())?;
+    }
+    Ok(())
+}
+
+/// Lines each message takes in the TXT layout, including its blank separator line
+pub(crate) fn txt_line_counts(messages: &[Message], label_sources: bool, style: &ExportStyle) -> Vec<usize> {
+    messages
+        .iter()
+        .map(|message| format_txt_message(message, label_sources, style).matches('\n').count())
+        .collect()
+}
+
+/// Format one message the way it appears in TXT exports, including the blank separator line
+fn format_txt_message(message: &Message, label_sources: bool, style: &ExportStyle) -> String {
+    let label = match (&message.source, label_sources) {
+        (Some(source), true) => format!("[{}] ", source),
+        _ => String::new(),
+    };
+    let id = match (&message.imessage_id, style.include_ids) {
+        (Some(id), true) => format!("[id:{}] ", id),
+        _ => String::new(),
+    };
+
+    format!(
+        "{}{}{}{}, {}, {}\n\n",
+        style.txt_prefix(message),
+        id,
+        label,
+        message.sender,
+        message.timestamp.format("%b %d, %Y %r"),
+        message.content
+    )
+}
+
+/// A column that can be included in CSV output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvColumn {
+    ImessageId,
+    Sender,
+    Timestamp,
+    Content,
+    Service,
+    Source,
+    IsFromMe,
+}
+
+impl CsvColumn {
+    const ALL: &'static [(&'static str, CsvColumn)] = &[
+        ("imessage_id", CsvColumn::ImessageId),
+        ("sender", CsvColumn::Sender),
+        ("timestamp", CsvColumn::Timestamp),
+        ("content", CsvColumn::Content),
+        ("service", CsvColumn::Service),
+        ("source", CsvColumn::Source),
+        ("is_from_me", CsvColumn::IsFromMe),
+    ];
+
+    fn header(self) -> &'static str {
+        match self {
+            CsvColumn::ImessageId => "ID",
+            CsvColumn::Sender => "Sender",
+            CsvColumn::Timestamp => "Timestamp",
+            CsvColumn::Content => "Content",
+            CsvColumn::Service => "Service",
+            CsvColumn::Source => "Source",
+            CsvColumn::IsFromMe => "IsFromMe",
+        }
+    }
+
+    fn value(self, message: &Message) -> String {
+        match self {
+            CsvColumn::ImessageId => message.imessage_id.clone().unwrap_or_default(),
+            CsvColumn::Sender => message.sender.clone(),
+            CsvColumn::Timestamp => message.timestamp.format("%b %d, %Y %r").to_string(),
+            CsvColumn::Content => message.content.clone(),
+            CsvColumn::Service => message.service.clone().unwrap_or_default(),
+            CsvColumn::Source => message.source.clone().unwrap_or_default(),
+            CsvColumn::IsFromMe => message.is_from_me.to_string(),
+        }
+    }
+}
+
+/// When CSV fields are quoted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvQuoting {
+    /// Only fields containing the delimiter, quotes or newlines (RFC 4180)
+    Necessary,
+    Always,
+    Never,
+}
+
+/// How CSV text is simplified for spreadsheet tools that mangle some characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvNormalization {
+    /// Text exactly as sent
+    None,
+    /// Smart quotes, dashes, ellipses and unusual spaces become their ASCII forms
+    Punctuation,
+    /// Punctuation as above, and characters outside the Basic Multilingual Plane (most emoji)
+    /// become a text emoticon where there's a common one and are dropped otherwise
+    Bmp,
+}
+
+impl CsvNormalization {
+    /// Parse a command line value: none, punctuation or bmp
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(CsvNormalization::None),
+            "punctuation" => Ok(CsvNormalization::Punctuation),
+            "bmp" => Ok(CsvNormalization::Bmp),
+            other => Err(anyhow::anyhow!("Invalid CSV normalization: {}. Use none, punctuation or bmp", other)),
+        }
+    }
+
+    pub fn apply(self, text: &str) -> String {
+        if self == CsvNormalization::None {
+            return text.to_string();
+        }
+
+        let mut normalized = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{2032}' => normalized.push('\''),
+                '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{2033}' => normalized.push('"'),
+                '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2212}' => normalized.push('-'),
+                '\u{2014}' | '\u{2015}' => normalized.push_str("--"),
+                '\u{2026}' => normalized.push_str("..."),
+                '\u{00A0}' | '\u{2007}' | '\u{202F}' | '\u{2009}' | '\u{200A}' => normalized.push(' '),
+                '\u{2022}' => normalized.push('*'),
+                // Joiners and variation selectors only glue emoji together
+                '\u{200D}' | '\u{FE0F}' if self == CsvNormalization::Bmp => {},
+                c if self == CsvNormalization::Bmp && u32::from(c) > 0xFFFF => {
+                    if let Some(emoticon) = emoticon(c) {
+                        normalized.push_str(emoticon);
+                    }
+                },
+                c => normalized.push(c),
+            }
+        }
+        normalized
+    }
+}
+
+/// Text stand-ins for common emoji outside the Basic Multilingual Plane
+fn emoticon(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{1F600}' | '\u{1F603}' | '\u{1F604}' | '\u{1F601}' | '\u{1F606}' => ":D",
+        '\u{1F602}' | '\u{1F923}' => ":'D",
+        '\u{1F642}' | '\u{1F60A}' => ":)",
+        '\u{1F641}' | '\u{1F61E}' => ":(",
+        '\u{1F622}' | '\u{1F62D}' => ":'(",
+        '\u{1F609}' => ";)",
+        '\u{1F61B}' | '\u{1F61C}' | '\u{1F61D}' => ":P",
+        '\u{1F62E}' | '\u{1F632}' => ":O",
+        '\u{1F618}' => ":*",
+        '\u{1F60D}' | '\u{1F970}' | '\u{1F496}' | '\u{1F495}' => "<3",
+        '\u{1F44D}' => "(y)",
+        '\u{1F44E}' => "(n)",
+        _ => return None,
+    })
+}
+
+/// Layout of CSV output, so exports match what downstream tools expect
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub columns: Vec<CsvColumn>,
+    pub quoting: CsvQuoting,
+    pub header: bool,
+    pub normalization: CsvNormalization,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            columns: vec![CsvColumn::Sender, CsvColumn::Timestamp, CsvColumn::Content],
+            quoting: CsvQuoting::Necessary,
+            header: true,
+            normalization: CsvNormalization::None,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Build options from command line values, e.g. ("tab", "imessage_id,sender,content", "always", true)
+    pub fn parse(delimiter: &str, columns: &str, quoting: &str, header: bool) -> Result<Self> {
+        let delimiter = match delimiter.to_lowercase().as_str() {
+            "comma" => b',',
+            "tab" | "\\t" => b'\t',
+            "semicolon" => b';',
+            "pipe" => b'|',
+            other if other.len() == 1 => other.as_bytes()[0],
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Invalid delimiter: {}. Use comma, tab, semicolon, pipe or a single character",
+                    other
+                ))
+            }
+        };
+
+        let columns = columns
+            .split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(|name| {
+                CsvColumn::ALL
+                    .iter()
+                    .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                    .map(|(_, column)| *column)
+                    .ok_or_else(|| {
+                        let known: Vec<&str> = CsvColumn::ALL.iter().map(|(known, _)| *known).collect();
+                        anyhow::anyhow!("Unknown CSV column: {}. Available columns: {}", name, known.join(", "))
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if columns.is_empty() {
+            return Err(anyhow::anyhow!("At least one CSV column is required"));
+        }
+
+        let quoting = match quoting.to_lowercase().as_str() {
+            "necessary" => CsvQuoting::Necessary,
+            "always" => CsvQuoting::Always,
+            "never" => CsvQuoting::Never,
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Invalid quoting: {}. Use necessary, always or never",
+                    other
+                ))
+            }
+        };
+
+        Ok(Self { delimiter, columns, quoting, header, normalization: CsvNormalization::None })
+    }
+
+    pub fn with_normalization(mut self, normalization: CsvNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Put the message ID column first unless the columns already include it
+    pub fn with_ids(mut self, include_ids: bool) -> Self {
+        if include_ids && !self.columns.contains(&CsvColumn::ImessageId) {
+            self.columns.insert(0, CsvColumn::ImessageId);
+        }
+        self
+    }
+}
+
+/// Write messages to a CSV file. When appending to an existing file the header is left out.
+pub fn write_csv_file(messages: &[Message], file_path: &str, options: &CsvOptions, policy: WritePolicy) -> Result<WrittenFile> {
+    write_file(file_path, messages.len(), policy, |output, appending| {
+        write_csv(output, messages, options, options.header && !appending)
+    })
+}
+
+/// Write messages as CSV rows to `output`, with the header row when `header` is set
+pub(crate) fn write_csv(output: impl Write, messages: &[Message], options: &CsvOptions, header: bool) -> Result<()> {
+    let mut writer = csv_writer(output, options);
+
+    // Write header
+    if header {
+        writer.write_record(options.columns.iter().map(|c| c.header()))?;
+    }
+
+    // Write data
+    for message in messages {
+        writer.write_record(options.columns.iter().map(|c| options.normalization.apply(&c.value(message))))?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn csv_writer<W: Write>(output: W, options: &CsvOptions) -> csv::Writer<W> {
+    let quote_style = match options.quoting {
+        CsvQuoting::Necessary => csv::QuoteStyle::Necessary,
+        CsvQuoting::Always => csv::QuoteStyle::Always,
+        CsvQuoting::Never => csv::QuoteStyle::Never,
+    };
+
+    csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .quote_style(quote_style)
+        .from_writer(output)
+}
+
+/// Write messages to a JSON file as an array of message objects
+pub fn write_json_file(messages: &[Message], file_path: &str, policy: WritePolicy) -> Result<WrittenFile> {
+    // An array can't be extended in place, so appending rewrites the file with the messages added
+    if policy == WritePolicy::Append && Path::new(file_path).exists() {
+        let existing_len = fs::metadata(file_path)?.len();
+        let file = File::open(file_path).with_context(|| format!("Failed to open {}", file_path))?;
+        let mut combined: Vec<Message> = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Failed to read {} to append to it", file_path))?;
+        combined.extend_from_slice(messages);
+
+        let written = write_file(file_path, messages.len(), WritePolicy::Overwrite, |writer, _| {
+            Ok(serde_json::to_writer_pretty(writer, &combined)?)
+        })?;
+        return Ok(WrittenFile {
+            bytes: written.bytes.saturating_sub(existing_len),
+            status: WriteStatus::Appended,
+            ..written
+        });
+    }
+
+    write_file(file_path, messages.len(), policy, |writer, _| {
+        Ok(serde_json::to_writer_pretty(writer, messages)?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn test_csv_options() {
+        let options = CsvOptions::parse("tab", "imessage_id, sender,is_from_me", "always", false).unwrap();
+        assert_eq!(options.delimiter, b'\t');
+        assert_eq!(options.columns, vec![CsvColumn::ImessageId, CsvColumn::Sender, CsvColumn::IsFromMe]);
+        assert!(CsvOptions::parse("comma", "sender,mood", "necessary", true).is_err());
+        assert!(CsvOptions::parse("--", "sender", "necessary", true).is_err());
+
+        let message = Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: "ok".to_string(),
+            source: None,
+            imessage_id: Some("guid-1".to_string()),
+            service: Some("SMS".to_string()),
+            is_from_me: false,
+        };
+
+        let path = std::env::temp_dir().join("txt_history_csv_options_test.csv");
+        write_csv_file(&[message], &path.to_string_lossy(), &options, WritePolicy::Overwrite).unwrap();
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(output, "\"guid-1\"\t\"Phil\"\t\"false\"\n");
+    }
+
+    #[test]
+    fn test_csv_normalization_round_trip() {
+        let message = Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: "\u{201C}Don\u{2019}t\u{201D} \u{2014} it\u{2019}s fine\u{2026} \u{1F602}\u{1F44D}\u{1F3FD} \u{2764}\u{FE0F} \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} caf\u{E9}".to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        };
+        let path = std::env::temp_dir().join(format!("txt_history_csv_normalization_test_{}.csv", std::process::id()));
+        let read_back = |normalization: CsvNormalization| -> String {
+            let options = CsvOptions::parse("comma", "content", "necessary", true).unwrap().with_normalization(normalization);
+            write_csv_file(&[message.clone()], &path.to_string_lossy(), &options, WritePolicy::Overwrite).unwrap();
+            // Read the file back the way a spreadsheet would import it
+            let mut reader = csv::Reader::from_path(&path).unwrap();
+            let record = reader.records().next().unwrap().unwrap();
+            record[0].to_string()
+        };
+
+        assert_eq!(read_back(CsvNormalization::None), message.content);
+        assert_eq!(
+            read_back(CsvNormalization::Punctuation),
+            "\"Don't\" -- it's fine... \u{1F602}\u{1F44D}\u{1F3FD} \u{2764}\u{FE0F} \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} caf\u{E9}"
+        );
+
+        // Every character fits in the Basic Multilingual Plane, which older Excel and Numbers
+        // versions handle, and accented letters and BMP symbols are left alone
+        let bmp = read_back(CsvNormalization::Bmp);
+        assert_eq!(bmp, "\"Don't\" -- it's fine... :'D(y) \u{2764}  caf\u{E9}");
+        assert!(bmp.chars().all(|c| u32::from(c) <= 0xFFFF));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(CsvNormalization::parse("ascii").is_err());
+    }
+
+    #[test]
+    fn test_write_policies() {
+        let message = |content: &str| Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        };
+        let output_dir = std::env::temp_dir().join(format!("txt_history_policy_test_{}", std::process::id()));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let csv_path = output_dir.join("chunk_1.csv").to_string_lossy().to_string();
+        let json_path = output_dir.join("chunk_1.json").to_string_lossy().to_string();
+        let options = CsvOptions::default();
+
+        let first = write_csv_file(&[message("one")], &csv_path, &options, WritePolicy::Overwrite).unwrap();
+        assert_eq!(first.status, WriteStatus::Created);
+        assert_eq!(first.bytes, std::fs::metadata(&csv_path).unwrap().len());
+
+        // Skipping leaves the file alone; appending adds rows without a second header
+        let skipped = write_csv_file(&[message("two")], &csv_path, &options, WritePolicy::SkipExisting).unwrap();
+        assert_eq!((skipped.status, skipped.bytes), (WriteStatus::Skipped, 0));
+        let appended = write_csv_file(&[message("two")], &csv_path, &options, WritePolicy::Append).unwrap();
+        assert_eq!(appended.status, WriteStatus::Appended);
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        assert_eq!(csv.matches("Sender").count(), 1);
+        assert!(csv.contains("one") && csv.contains("two"));
+
+        // JSON stays a single valid array
+        write_json_file(&[message("one")], &json_path, WritePolicy::Overwrite).unwrap();
+        write_json_file(&[message("two")], &json_path, WritePolicy::Append).unwrap();
+        let json: Vec<Message> = serde_json::from_str(&std::fs::read_to_string(&json_path).unwrap()).unwrap();
+        assert_eq!(json.len(), 2);
+
+        let replaced = write_csv_file(&[message("three")], &csv_path, &options, WritePolicy::Overwrite).unwrap();
+        assert_eq!(replaced.status, WriteStatus::Replaced);
+        assert!(!std::fs::read_to_string(&csv_path).unwrap().contains("one"));
+
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(&output_dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_txt_totals_footer() {
+        let message = Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: "See you at 3".to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        };
+        let style = ExportStyle { txt_totals: true, ..ExportStyle::default() };
+
+        let mut output = Vec::new();
+        write_txt(&mut output, &[message.clone(), message], false, &style).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.ends_with("PM, See you at 3\n\n-- 2 messages, 8 words, 24 characters, about 1 min to read --\n"));
+    }
+
+    #[test]
+    fn test_include_ids() {
+        let message = Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: "See you at 3".to_string(),
+            source: Some("iMessage".to_string()),
+            imessage_id: Some("guid-1".to_string()),
+            service: None,
+            is_from_me: false,
+        };
+        let style = ExportStyle::default().with_ids(true);
+        assert_eq!(
+            format_txt_message(&message, true, &style),
+            "[id:guid-1] [iMessage] Phil, Jan 20, 2025 12:00:00 PM, See you at 3\n\n"
+        );
+        assert!(format_txt_message(&message, false, &ExportStyle::default()).starts_with("Phil, "));
+
+        let options = CsvOptions::default().with_ids(true);
+        assert_eq!(options.columns[0], CsvColumn::ImessageId);
+        assert_eq!(options.clone().with_ids(true).columns.len(), 4);
+    }
+
+    #[test]
+    fn test_token_chunking() {
+        let message = |content: &str| Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        };
+        // Each formatted message is 40 characters, about 10 tokens
+        let messages: Vec<Message> = (0..5).map(|_| message("abcdef")).collect();
+        assert_eq!(estimate_tokens(&format_txt_message(&messages[0], false, &ExportStyle::default())), 10);
+
+        let split = |chunking: Chunking| chunking
+            .split(&messages, OutputFormat::Txt, &CsvOptions::default(), &ExportStyle::default())
+            .unwrap();
+        let chunks = split(Chunking::Tokens(25));
+        assert_eq!(chunks.iter().map(|c| c.len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+
+        // Oversized messages still get written, one per chunk
+        let chunks = split(Chunking::Tokens(5));
+        assert_eq!(chunks.len(), 5);
+
+        assert_eq!(Chunking::from_options(Some(1.0), Some(10), Some(8000)), Chunking::Tokens(8000));
+        assert_eq!(split(Chunking::from_options(None, None, None)).len(), 1);
+    }
+
+    #[test]
+    fn test_size_chunks_stay_within_limit() {
+        let messages: Vec<Message> = (0..60)
+            .map(|i| Message {
+                sender: "Phil".to_string(),
+                timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+                content: format!("message {} with \"quotes\", commas and\nnewlines", i),
+                source: None,
+                imessage_id: Some(format!("guid-{}", i)),
+                service: Some("iMessage".to_string()),
+                is_from_me: i % 2 == 0,
+            })
+            .collect();
+
+        let limit_mb = 0.002;
+        let limit_bytes = (limit_mb * 1024.0 * 1024.0) as u64;
+        let output_dir = std::env::temp_dir().join(format!("txt_history_size_test_{}", std::process::id()));
+
+        for format in [OutputFormat::Txt, OutputFormat::Csv, OutputFormat::Json] {
+            std::fs::create_dir_all(&output_dir).unwrap();
+            let written = write_messages_to_files(
+                &messages,
+                format,
+                &CsvOptions::default(),
+                &ExportStyle::default(),
+                Chunking::SizeMb(limit_mb),
+                &output_dir.to_string_lossy(),
+                WritePolicy::Overwrite,
+                4,
+            )
+            .unwrap();
+
+            // Chunks written in parallel still come back in order, with every message
+            assert!(written.iter().enumerate().all(|(i, file)| file.path.contains(&format!("chunk_{}.", i + 1))));
+            assert_eq!(written.iter().map(|file| file.messages).sum::<usize>(), messages.len());
+
+            let mut files = 0;
+            for entry in std::fs::read_dir(&output_dir).unwrap() {
+                let size = entry.unwrap().metadata().unwrap().len();
+                assert!(size <= limit_bytes, "{:?} chunk is {} bytes", format, size);
+                files += 1;
+            }
+            assert!(files > 1);
+            std::fs::remove_dir_all(&output_dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_export_estimate() {
+        let messages: Vec<Message> = (0..40)
+            .map(|i| Message {
+                sender: if i % 3 == 0 { "Jess" } else { "Phil" }.to_string(),
+                timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, i, 0).unwrap(),
+                content: format!("message {} with \"quotes\", commas and\nnewlines", i),
+                source: None,
+                imessage_id: Some(format!("guid-{}", i)),
+                service: Some("iMessage".to_string()),
+                is_from_me: i % 3 == 0,
+            })
+            .collect();
+        let output_dir = std::env::temp_dir().join(format!("txt_history_estimate_test_{}", std::process::id()));
+
+        for format in [OutputFormat::Txt, OutputFormat::Csv, OutputFormat::Json] {
+            let chunking = Chunking::SizeMb(0.002);
+            let estimate =
+                ExportEstimate::new(&messages, format, &CsvOptions::default(), &ExportStyle::default(), chunking)
+                    .unwrap();
+            std::fs::create_dir_all(&output_dir).unwrap();
+            let written = write_messages_to_files(
+                &messages,
+                format,
+                &CsvOptions::default(),
+                &ExportStyle::default(),
+                chunking,
+                &output_dir.to_string_lossy(),
+                WritePolicy::Overwrite,
+                1,
+            )
+            .unwrap();
+            std::fs::remove_dir_all(&output_dir).unwrap();
+
+            // The estimate splits exactly like the export, and its sizes never fall short
+            assert_eq!(estimate.chunks.len(), written.len());
+            assert_eq!(estimate.messages(), messages.len());
+            for (chunk, file) in estimate.chunks.iter().zip(&written) {
+                assert!(file.path.ends_with(&chunk.file_name));
+                assert_eq!(chunk.messages, file.messages);
+                match format {
+                    OutputFormat::Json => assert!(chunk.bytes as u64 >= file.bytes),
+                    _ => assert_eq!(chunk.bytes as u64, file.bytes, "{:?} {}", format, chunk.file_name),
+                }
+            }
+        }
+        assert_eq!(format_size(1536), "1.5 KB");
+    }
+
+    #[test]
+    fn test_sender_files() {
+        let message = |sender: &str, content: &str| Message {
+            sender: sender.to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: sender == "Jess",
+        };
+        let messages = vec![
+            message("Phil Smith", "hi"),
+            message("Jess", "hello"),
+            message("Phil Smith", "how are you"),
+            message("Phil-Smith", "impostor"),
+        ];
+
+        let groups = group_by_sender(&messages);
+        let stems: Vec<&str> = groups.iter().map(|(stem, _)| stem.as_str()).collect();
+        assert_eq!(stems, vec!["phil_smith", "jess", "phil_smith_2"]);
+        assert_eq!(groups[0].1.len(), 2);
+
+        let output_dir = std::env::temp_dir().join(format!("txt_history_sender_test_{}", std::process::id()));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        write_sender_files(
+            &messages,
+            OutputFormat::Txt,
+            None,
+            "Phil Smith",
+            &CsvOptions::default(),
+            &ExportStyle::default(),
+            Chunking::Single,
+            &output_dir.to_string_lossy(),
+            WritePolicy::Overwrite,
+            2,
+        )
+        .unwrap();
+
+        let jess = std::fs::read_to_string(output_dir.join("jess.txt")).unwrap();
+        let phil = std::fs::read_to_string(output_dir.join("phil_smith.txt")).unwrap();
+        std::fs::remove_dir_all(&output_dir).unwrap();
+
+        assert!(jess.contains("hello") && !jess.contains("Phil"));
+        assert!(phil.contains("how are you") && !phil.contains("Jess") && !phil.contains("impostor"));
+    }
+
+    #[test]
+    fn test_html_chunk_index() {
+        let messages: Vec<Message> = (0..5).map(|i| numbered_message(i, "hi")).collect();
+        let output_dir = std::env::temp_dir().join(format!("txt_history_html_index_test_{}", std::process::id()));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let output_dir_str = output_dir.to_string_lossy().to_string();
+        let style = ExportStyle::default();
+        let write = |template: &ExportTemplate, chunking: Chunking| {
+            write_templated_files(&messages, template, "Phil", &style, chunking, &output_dir_str, WritePolicy::Overwrite, 2)
+                .unwrap()
+        };
+        let bubbles = ExportTemplate::load("bubbles").unwrap();
+
+        // Three chunks, then the index
+        let written = write(&bubbles, Chunking::Lines(2));
+        assert_eq!(written.len(), 4);
+        assert!(written[3].path.ends_with("index.html"));
+        let index = std::fs::read_to_string(output_dir.join("index.html")).unwrap();
+        assert!(index.contains("chunk_3.html"));
+        let second = std::fs::read_to_string(output_dir.join("chunk_2.html")).unwrap();
+        assert!(second.contains("chunk_1.html") && second.contains("chunk_3.html") && second.contains("index.html"));
+        std::fs::remove_dir_all(&output_dir).unwrap();
+
+        // A single chunk or a text template gets no index
+        std::fs::create_dir_all(&output_dir).unwrap();
+        write(&bubbles, Chunking::Single);
+        write(&ExportTemplate::load("plain").unwrap(), Chunking::Lines(2));
+        assert!(!output_dir.join("index.html").exists());
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    fn numbered_message(i: usize, content: &str) -> Message {
+        Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: Some(format!("guid-{}", i)),
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_chunks_keep_every_message_within_bounds(
+            contents in proptest::collection::vec("\\PC{0,200}", 0..40),
+            lines in 0usize..8,
+            tokens in 0usize..120,
+            size_kb in 0u32..4,
+        ) {
+            let messages: Vec<Message> = contents.iter().enumerate().map(|(i, c)| numbered_message(i, c)).collect();
+            let csv_options = CsvOptions::default();
+            let style = ExportStyle::default();
+            let size_mb = f64::from(size_kb) / 1024.0;
+
+            for chunking in [Chunking::Single, Chunking::Lines(lines), Chunking::Tokens(tokens), Chunking::SizeMb(size_mb)] {
+                for format in [OutputFormat::Txt, OutputFormat::Csv, OutputFormat::Json] {
+                    let chunks = chunking.split(&messages, format, &csv_options, &style).unwrap();
+
+                    // No message lost, duplicated or reordered, and no empty chunks
+                    proptest::prop_assert!(chunks.iter().all(|chunk| !chunk.is_empty()));
+                    let ids: Vec<&Option<String>> = chunks.iter().flat_map(|chunk| chunk.iter().map(|m| &m.imessage_id)).collect();
+                    let expected: Vec<&Option<String>> = messages.iter().map(|m| &m.imessage_id).collect();
+                    proptest::prop_assert_eq!(ids, expected);
+
+                    // Only a single oversized message may exceed the limit
+                    for chunk in chunks.iter().filter(|chunk| chunk.len() > 1) {
+                        match chunking {
+                            Chunking::Single => {},
+                            Chunking::Lines(lines) => proptest::prop_assert!(chunk.len() <= lines.max(1)),
+                            Chunking::Tokens(tokens) => {
+                                let used: usize = chunk.iter().map(|m| estimate_tokens(&format_txt_message(m, false, &style))).sum();
+                                proptest::prop_assert!(used <= tokens);
+                            },
+                            Chunking::SizeMb(size) => {
+                                let budget = ((size * 1024.0 * 1024.0) as usize).saturating_sub(file_overhead(format, &csv_options));
+                                let used: usize = chunk
+                                    .iter()
+                                    .map(|m| serialized_size(m, format, &csv_options, false, &style).unwrap())
+                                    .sum();
+                                proptest::prop_assert!(used <= budget);
+                            },
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Built-in terms as (term, severity, category). A trailing `*` matches any word ending.
+const BUILTIN_TERMS: &[(&str, f32, &str)] = &[
+    ("damn*", 0.2, "profanity"),
+    ("hell", 0.2, "profanity"),
+    ("crap*", 0.3, "profanity"),
+    ("shit*", 0.5, "profanity"),
+    ("piss*", 0.4, "profanity"),
+    ("fuck*", 0.7, "profanity"),
+    ("bastard*", 0.7, "profanity"),
+    ("bitch*", 0.8, "profanity"),
+    ("asshole*", 0.8, "profanity"),
+    ("stupid", 0.4, "insult"),
+    ("idiot*", 0.5, "insult"),
+    ("loser*", 0.5, "insult"),
+    ("useless", 0.5, "insult"),
+    ("pathetic", 0.6, "insult"),
+    ("moron*", 0.6, "insult"),
+    ("worthless", 0.7, "insult"),
+    ("shut up", 0.5, "abuse"),
+    ("hate you", 0.8, "abuse"),
+    ("you'll regret", 0.8, "threat"),
+    ("kill you", 1.0, "threat"),
+];
+
+/// Word lists for the abusive-language pass
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlagConfig {
+    /// Include the built-in profanity, insult and threat terms
+    pub use_builtin: bool,
+
+    /// Extra terms, or built-in terms with a different severity
+    pub terms: Vec<FlagTerm>,
+
+    /// Files with one "term,severity[,category]" per line ('#' starts a comment)
+    pub word_lists: Vec<String>,
+}
+
+impl Default for FlagConfig {
+    fn default() -> Self {
+        Self {
+            use_builtin: true,
+            terms: Vec::new(),
+            word_lists: Vec::new(),
+        }
+    }
+}
+
+/// A word or phrase to flag and how serious it is
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlagTerm {
+    /// Case-insensitive word or phrase; a trailing `*` matches any ending ("fuck*" matches "fucking")
+    pub term: String,
+
+    /// 0.0 (mild) to 1.0 (severe)
+    pub severity: f32,
+
+    #[serde(default = "default_category")]
+    pub category: String,
+}
+
+fn default_category() -> String {
+    "profanity".to_string()
+}
+
+/// One flagged term found in a message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageFlag {
+    /// The text as it appears in the message
+    pub text: String,
+    pub category: String,
+    pub severity: f32,
+}
+
+/// Finds profanity and abusive language from configurable word lists
+#[derive(Debug)]
+pub struct LanguageFlagger {
+    terms: Vec<(Regex, FlagTerm)>,
+}
+
+impl LanguageFlagger {
+    /// Build the flagger from the built-in list, config terms and word list files. Later
+    /// entries for the same term replace earlier ones, so config can adjust built-in severities.
+    pub fn from_config(config: &FlagConfig) -> Result<Self> {
+        let mut terms: Vec<FlagTerm> = Vec::new();
+
+        if config.use_builtin {
+            terms.extend(BUILTIN_TERMS.iter().map(|(term, severity, category)| FlagTerm {
+                term: term.to_string(),
+                severity: *severity,
+                category: category.to_string(),
+            }));
+        }
+
+        for path in &config.word_lists {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read word list {}", path))?;
+            for (i, line) in contents.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or_default().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                terms.push(parse_word_list_line(line).with_context(|| format!("{}:{}", path, i + 1))?);
+            }
+        }
+
+        terms.extend(config.terms.iter().cloned());
+        Self::new(terms)
+    }
+
+    pub fn new(terms: Vec<FlagTerm>) -> Result<Self> {
+        let mut compiled: Vec<(Regex, FlagTerm)> = Vec::new();
+
+        for term in terms {
+            if !(0.0..=1.0).contains(&term.severity) {
+                return Err(anyhow::anyhow!(
+                    "Invalid severity {} for \"{}\": use 0.0 to 1.0",
+                    term.severity,
+                    term.term
+                ));
+            }
+
+            let normalized = term.term.trim().to_lowercase();
+            let pattern = match normalized.strip_suffix('*') {
+                Some(stem) => format!(r"(?i)\b{}\w*", regex::escape(stem)),
+                None => format!(r"(?i)\b{}\b", regex::escape(&normalized)),
+            };
+            let regex = Regex::new(&pattern)?;
+
+            compiled.retain(|(_, existing)| existing.term.trim().to_lowercase() != normalized);
+            compiled.push((regex, term));
+        }
+
+        Ok(Self { terms: compiled })
+    }
+
+    /// Every flagged term in `text`, in the order the terms are configured
+    pub fn flag(&self, text: &str) -> Vec<LanguageFlag> {
+        self.terms
+            .iter()
+            .flat_map(|(regex, term)| {
+                regex.find_iter(text).map(move |m| LanguageFlag {
+                    text: m.as_str().to_string(),
+                    category: term.category.clone(),
+                    severity: term.severity,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Highest severity among a message's flags, 0.0 when nothing was flagged
+pub fn max_severity(flags: &[LanguageFlag]) -> f32 {
+    flags.iter().map(|f| f.severity).fold(0.0, f32::max)
+}
+
+fn parse_word_list_line(line: &str) -> Result<FlagTerm> {
+    let mut fields = line.split(',').map(str::trim);
+    let term = fields.next().unwrap_or_default().to_string();
+    let severity = fields
+        .next()
+        .context("Expected \"term,severity[,category]\"")?
+        .parse()
+        .context("Invalid severity")?;
+    let category = fields.next().map_or_else(default_category, ToString::to_string);
+
+    Ok(FlagTerm { term, severity, category })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_builtin_terms() {
+        let flagger = LanguageFlagger::from_config(&FlagConfig::default()).unwrap();
+
+        let flags = flagger.flag("You're so STUPID, shut up. This is fucking useless");
+        let texts: Vec<&str> = flags.iter().map(|f| f.text.as_str()).collect();
+        assert_eq!(texts, vec!["fucking", "STUPID", "useless", "shut up"]);
+        assert_eq!(max_severity(&flags), 0.7);
+
+        // Whole words only
+        assert!(flagger.flag("Shell out for the class assessment").is_empty());
+        assert_eq!(max_severity(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_config_terms_override_builtin() {
+        let config = FlagConfig {
+            use_builtin: true,
+            terms: vec![
+                FlagTerm { term: "stupid".to_string(), severity: 0.9, category: "insult".to_string() },
+                FlagTerm { term: "bad mother".to_string(), severity: 0.8, category: "abuse".to_string() },
+            ],
+            word_lists: Vec::new(),
+        };
+        let flagger = LanguageFlagger::from_config(&config).unwrap();
+
+        let flags = flagger.flag("stupid, you're a bad mother");
+        assert_eq!(flags.len(), 2);
+        assert_eq!(flags[0].severity, 0.9);
+        assert_eq!(flags[1].category, "abuse");
+
+        assert_eq!(
+            parse_word_list_line("jerk, 0.4").unwrap(),
+            FlagTerm { term: "jerk".to_string(), severity: 0.4, category: "profanity".to_string() }
+        );
+        assert!(parse_word_list_line("jerk").is_err());
+        assert!(LanguageFlagger::new(vec![FlagTerm {
+            term: "jerk".to_string(),
+            severity: 4.0,
+            category: "insult".to_string(),
+        }])
+        .is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::legal::xml_escape;
+use crate::models::{Conversation, DbMessage};
+
+/// A person in the graph and how many messages they sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphNode {
+    pub name: String,
+    pub messages: usize,
+    pub is_me: bool,
+}
+
+/// Messages exchanged between two people, with names in alphabetical order
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    pub messages: usize,
+    /// Average sentiment of the scored messages, if any were scored
+    pub sentiment: Option<f32>,
+}
+
+/// Who talks to whom, for `report graph`. In a conversation between two people every message
+/// counts toward their edge. In a group chat a message counts toward the edge between its sender
+/// and the last other person who spoke, and messages before anyone else spoke aren't counted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InteractionGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl InteractionGraph {
+    /// Build from messages in any order; `sentiment` maps message IDs to scores
+    pub fn new(messages: &[DbMessage], sentiment: &HashMap<i32, f32>, my_name: &str) -> Self {
+        let speaker = |m: &DbMessage| if m.is_from_me { my_name.to_string() } else { m.sender.clone() };
+
+        let mut conversations: BTreeMap<String, Vec<&DbMessage>> = BTreeMap::new();
+        for message in messages {
+            let key = match message.conversation() {
+                Some(Conversation::Thread(thread_id)) => format!("thread:{}", thread_id),
+                Some(Conversation::Contact(contact_id)) => format!("contact:{}", contact_id),
+                None => continue,
+            };
+            conversations.entry(key).or_default().push(message);
+        }
+
+        let mut sent: BTreeMap<String, usize> = BTreeMap::new();
+        // (messages, sentiment sum, scored messages) per pair
+        let mut pairs: BTreeMap<(String, String), (usize, f32, usize)> = BTreeMap::new();
+        for conversation in conversations.values_mut() {
+            conversation.sort_by_key(|m| (m.date_created, m.id));
+            let people: BTreeSet<String> = conversation.iter().map(|m| speaker(m)).collect();
+
+            // Who each person in a group chat last replied to
+            let mut addressee: HashMap<String, String> = HashMap::new();
+            let mut previous: Option<String> = None;
+            for message in conversation.iter() {
+                let from = speaker(message);
+                *sent.entry(from.clone()).or_default() += 1;
+
+                let to = if people.len() == 2 {
+                    people.iter().find(|person| **person != from).cloned()
+                } else {
+                    if let Some(previous) = previous.as_ref().filter(|p| **p != from) {
+                        addressee.insert(from.clone(), previous.clone());
+                    }
+                    addressee.get(&from).cloned()
+                };
+                if let Some(to) = to {
+                    let key = if from < to { (from.clone(), to) } else { (to, from.clone()) };
+                    let pair = pairs.entry(key).or_default();
+                    pair.0 += 1;
+                    if let Some(score) = sentiment.get(&message.id) {
+                        pair.1 += score;
+                        pair.2 += 1;
+                    }
+                }
+                previous = Some(from);
+            }
+        }
+
+        let nodes = sent
+            .into_iter()
+            .map(|(name, messages)| GraphNode { is_me: name == my_name, name, messages })
+            .collect();
+        let edges = pairs
+            .into_iter()
+            .map(|((source, target), (messages, sum, scored))| GraphEdge {
+                source,
+                target,
+                messages,
+                sentiment: (scored > 0).then(|| sum / scored as f32),
+            })
+            .collect();
+
+        Self { nodes, edges }
+    }
+
+    /// GraphML for Gephi, Cytoscape or yEd, with node names as IDs
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n  \
+             <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n  \
+             <key id=\"sent\" for=\"node\" attr.name=\"messages\" attr.type=\"int\"/>\n  \
+             <key id=\"me\" for=\"node\" attr.name=\"is_me\" attr.type=\"boolean\"/>\n  \
+             <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n  \
+             <key id=\"sentiment\" for=\"edge\" attr.name=\"sentiment\" attr.type=\"double\"/>\n  \
+             <graph id=\"contacts\" edgedefault=\"undirected\">\n",
+        );
+
+        for node in &self.nodes {
+            let name = xml_escape(&node.name);
+            xml.push_str(&format!(
+                "    <node id=\"{name}\">\n      <data key=\"label\">{name}</data>\n      \
+                 <data key=\"sent\">{}</data>\n      <data key=\"me\">{}</data>\n    </node>\n",
+                node.messages, node.is_me
+            ));
+        }
+
+        for edge in &self.edges {
+            xml.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\">\n      <data key=\"weight\">{}</data>\n",
+                xml_escape(&edge.source),
+                xml_escape(&edge.target),
+                edge.messages
+            ));
+            if let Some(sentiment) = edge.sentiment {
+                xml.push_str(&format!("      <data key=\"sentiment\">{:.3}</data>\n", sentiment));
+            }
+            xml.push_str("    </edge>\n");
+        }
+
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    /// Graphviz DOT, with edge width scaled to message volume
+    pub fn to_dot(&self) -> String {
+        let max = self.edges.iter().map(|e| e.messages).max().unwrap_or(1) as f32;
+
+        let mut dot = String::from("graph contacts {\n");
+        for node in &self.nodes {
+            let shape = if node.is_me { ", shape=doublecircle" } else { "" };
+            dot.push_str(&format!(
+                "  {} [label={}, messages={}{}];\n",
+                dot_quote(&node.name),
+                dot_quote(&format!("{}\n{} sent", node.name, node.messages)),
+                node.messages,
+                shape
+            ));
+        }
+
+        for edge in &self.edges {
+            let sentiment = edge.sentiment.map(|s| format!(", sentiment={:.3}", s)).unwrap_or_default();
+            dot.push_str(&format!(
+                "  {} -- {} [weight={}, penwidth={:.2}, label=\"{}\"{}];\n",
+                dot_quote(&edge.source),
+                dot_quote(&edge.target),
+                edge.messages,
+                1.0 + 7.0 * edge.messages as f32 / max,
+                edge.messages,
+                sentiment
+            ));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Quote a DOT ID, escaping quotes and backslashes
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MessageKind;
+    use chrono::NaiveDateTime;
+
+    fn message(id: i32, sender: &str, thread_id: Option<&str>, contact_id: i32, minute: u32) -> DbMessage {
+        let date_created =
+            NaiveDateTime::parse_from_str(&format!("2025-01-21 12:{:02}", minute), "%Y-%m-%d %H:%M").unwrap();
+        DbMessage {
+            id,
+            imessage_id: format!("guid-{}", id),
+            text: Some("hi".to_string()),
+            sender: sender.to_string(),
+            is_from_me: sender == "Jess",
+            date_created,
+            date_imported: date_created,
+            handle_id: None,
+            service: None,
+            thread_id: thread_id.map(str::to_string),
+            has_attachments: false,
+            reply_to: None,
+            contact_id: Some(contact_id),
+            message_kind: MessageKind::Text,
+            hidden: false,
+            hidden_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_interaction_graph() {
+        let messages = vec![
+            // One-on-one with Phil, opening with a burst of mine
+            message(1, "Jess", None, 1, 0),
+            message(2, "Jess", None, 1, 1),
+            message(3, "Phil", None, 1, 2),
+            // A group chat: Rhonda's opener has no one to reply to
+            message(4, "Rhonda", Some("family"), 2, 10),
+            message(5, "Phil", Some("family"), 1, 11),
+            message(6, "Phil", Some("family"), 1, 12),
+            message(7, "Jess", Some("family"), 2, 13),
+            message(8, "Rhonda", Some("family"), 2, 14),
+        ];
+        let sentiment = HashMap::from([(1, 0.5), (3, -0.1), (8, 0.9)]);
+        let graph = InteractionGraph::new(&messages, &sentiment, "Jess");
+
+        let names: Vec<(&str, usize, bool)> =
+            graph.nodes.iter().map(|n| (n.name.as_str(), n.messages, n.is_me)).collect();
+        assert_eq!(names, vec![("Jess", 3, true), ("Phil", 3, false), ("Rhonda", 2, false)]);
+
+        let edges: Vec<(&str, &str, usize)> =
+            graph.edges.iter().map(|e| (e.source.as_str(), e.target.as_str(), e.messages)).collect();
+        assert_eq!(edges, vec![("Jess", "Phil", 4), ("Jess", "Rhonda", 1), ("Phil", "Rhonda", 2)]);
+        assert!((graph.edges[0].sentiment.unwrap() - 0.2).abs() < 1e-6);
+        assert_eq!(graph.edges[2].sentiment, None);
+
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<node id=\"Rhonda\">"));
+        assert!(graphml.contains("<edge source=\"Jess\" target=\"Phil\">\n      <data key=\"weight\">4</data>"));
+        assert!(graphml.contains("<data key=\"sentiment\">0.900</data>"));
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("graph contacts {"));
+        assert!(dot.contains("\"Jess\" -- \"Phil\" [weight=4, penwidth=8.00, label=\"4\", sentiment=0.200];"));
+        assert_eq!(dot_quote("Say \"hi\""), "\"Say \\\"hi\\\"\"");
+    }
+}
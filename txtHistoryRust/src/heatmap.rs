@@ -0,0 +1,135 @@
+use anyhow::Result;
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use serde_json::json;
+
+/// Day names in the order of SQLite's `strftime('%w')`
+pub const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+/// Messages on one calendar day
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DayCount {
+    pub date: NaiveDate,
+    pub messages: usize,
+}
+
+/// Message counts for `report heatmap`, in local time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActivityHeatmap {
+    /// `weekly[day][hour]`, with days in `WEEKDAYS` order
+    pub weekly: [[usize; 24]; 7],
+    /// Every day from the first message to the last, including days without messages
+    pub daily: Vec<DayCount>,
+}
+
+impl ActivityHeatmap {
+    /// Build from (day of week, hour, count) and (date, count) rows. Days missing from
+    /// `daily` between the first and last are filled in with zero.
+    pub fn from_counts(weekly: &[(usize, usize, usize)], daily: &[(NaiveDate, usize)]) -> Self {
+        let mut heatmap = Self::default();
+        for &(day, hour, count) in weekly {
+            if day < 7 && hour < 24 {
+                heatmap.weekly[day][hour] += count;
+            }
+        }
+
+        if let (Some(first), Some(last)) = (daily.iter().map(|d| d.0).min(), daily.iter().map(|d| d.0).max()) {
+            let mut date = first;
+            while date <= last {
+                let messages = daily.iter().filter(|d| d.0 == date).map(|d| d.1).sum();
+                heatmap.daily.push(DayCount { date, messages });
+                date += Duration::days(1);
+            }
+        }
+
+        heatmap
+    }
+
+    pub fn total(&self) -> usize {
+        self.weekly.iter().flatten().sum()
+    }
+
+    /// The day of week and hour with the most messages
+    pub fn busiest_hour(&self) -> Option<(&'static str, usize)> {
+        (0..7)
+            .flat_map(|day| (0..24).map(move |hour| (day, hour)))
+            .filter(|&(day, hour)| self.weekly[day][hour] > 0)
+            .max_by_key(|&(day, hour)| (self.weekly[day][hour], std::cmp::Reverse((day, hour))))
+            .map(|(day, hour)| (WEEKDAYS[day], hour))
+    }
+
+    /// One row per day of week with a column per hour (0 to 23)
+    pub fn weekly_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(std::iter::once("day".to_string()).chain((0..24).map(|hour| hour.to_string())))?;
+        for (day, hours) in WEEKDAYS.iter().zip(&self.weekly) {
+            writer.write_record(std::iter::once(day.to_string()).chain(hours.iter().map(|n| n.to_string())))?;
+        }
+
+        let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    /// One row per calendar day
+    pub fn daily_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(["date", "messages"])?;
+        for day in &self.daily {
+            writer.write_record([day.date.to_string(), day.messages.to_string()])?;
+        }
+
+        let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    /// Both views in one document, with the weekly matrix keyed by day name
+    pub fn to_json(&self, contact: &str) -> Result<String> {
+        let weekly: Vec<_> = WEEKDAYS
+            .iter()
+            .zip(&self.weekly)
+            .map(|(day, hours)| json!({ "day": day, "hours": hours }))
+            .collect();
+
+        Ok(serde_json::to_string_pretty(&json!({
+            "contact": contact,
+            "total": self.total(),
+            "weekly": weekly,
+            "daily": self.daily,
+        }))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap()
+    }
+
+    #[test]
+    fn test_heatmap_outputs() {
+        let heatmap = ActivityHeatmap::from_counts(&[(1, 9, 3), (1, 21, 5), (6, 0, 1)], &[(date(20), 8), (date(25), 1)]);
+
+        assert_eq!(heatmap.total(), 9);
+        assert_eq!(heatmap.busiest_hour(), Some(("Monday", 21)));
+        // Days without messages are filled in
+        assert_eq!(heatmap.daily.len(), 6);
+        assert_eq!(heatmap.daily[1], DayCount { date: date(21), messages: 0 });
+
+        let weekly = heatmap.weekly_csv().unwrap();
+        let lines: Vec<&str> = weekly.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].starts_with("day,0,1,2"));
+        assert_eq!(lines[2], "Monday,0,0,0,0,0,0,0,0,0,3,0,0,0,0,0,0,0,0,0,0,0,5,0,0");
+
+        assert!(heatmap.daily_csv().unwrap().starts_with("date,messages\n2025-01-20,8\n2025-01-21,0\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&heatmap.to_json("Phil").unwrap()).unwrap();
+        assert_eq!(json["weekly"][6]["day"], "Saturday");
+        assert_eq!(json["weekly"][6]["hours"][0], 1);
+        assert_eq!(json["daily"][0]["date"], "2025-01-20");
+
+        assert_eq!(ActivityHeatmap::from_counts(&[], &[]).busiest_hour(), None);
+    }
+}
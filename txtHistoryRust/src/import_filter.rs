@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::models::MessageKind;
+
+/// A 4 to 8 digit code, optionally with a letter prefix or split in two, e.g. 482913, G-482913
+/// or 482-913
+const CODE_PATTERN: &str = r"\b(?:[A-Z]{1,3}-)?(?:\d{4,8}|\d{3}[- ]\d{3})\b";
+
+/// Words that appear alongside one-time passcodes and verification codes
+const CODE_WORDS_PATTERN: &str = r"(?i)\b(?:code|passcode|pin|otp|verification|verify|2fa|one[- ]time)\b";
+
+/// Messages to leave out when importing. Nothing is skipped by default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportFilterConfig {
+    /// Skip text messages with fewer characters than this, e.g. 3 drops "Ok" and "k"
+    pub min_length: usize,
+
+    /// Skip text messages made only of emoji, e.g. "👍" or "😂😂"
+    pub skip_emoji_only: bool,
+
+    /// Skip one-time passcodes and verification codes
+    pub skip_codes: bool,
+
+    /// Skip text messages matching any of these regular expressions
+    pub skip_patterns: Vec<String>,
+}
+
+impl ImportFilterConfig {
+    /// Add `--min-length`, `--skip-emoji-only`, `--skip-codes` and `--skip-pattern` to the
+    /// configured rules
+    pub fn with_flags(
+        mut self,
+        min_length: Option<usize>,
+        skip_emoji_only: bool,
+        skip_codes: bool,
+        skip_patterns: &[String],
+    ) -> Self {
+        if let Some(min_length) = min_length {
+            self.min_length = min_length;
+        }
+        self.skip_emoji_only |= skip_emoji_only;
+        self.skip_codes |= skip_codes;
+        self.skip_patterns.extend(skip_patterns.iter().cloned());
+        self
+    }
+}
+
+/// Why a message was left out of an import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    EmojiOnly,
+    TooShort,
+    Code,
+    Pattern,
+}
+
+impl SkipReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::EmojiOnly => "emoji only",
+            Self::TooShort => "too short",
+            Self::Code => "verification codes",
+            Self::Pattern => "matching a skip pattern",
+        }
+    }
+}
+
+/// `ImportFilterConfig` with its patterns compiled. Only plain text messages without
+/// attachments are ever skipped, so photos, stickers, payments and the like are always imported.
+#[derive(Debug, Clone, Default)]
+pub struct ImportFilter {
+    min_length: usize,
+    skip_emoji_only: bool,
+    /// The code and code word patterns, when skipping codes
+    codes: Option<(Regex, Regex)>,
+    patterns: Vec<Regex>,
+}
+
+impl ImportFilter {
+    pub fn new(config: &ImportFilterConfig) -> Result<Self> {
+        let codes = if config.skip_codes {
+            Some((Regex::new(CODE_PATTERN)?, Regex::new(CODE_WORDS_PATTERN)?))
+        } else {
+            None
+        };
+        let patterns = config
+            .skip_patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).with_context(|| format!("Invalid skip pattern: {}", pattern)))
+            .collect::<Result<_>>()?;
+
+        Ok(Self { min_length: config.min_length, skip_emoji_only: config.skip_emoji_only, codes, patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min_length == 0 && !self.skip_emoji_only && self.codes.is_none() && self.patterns.is_empty()
+    }
+
+    /// Why a message should be left out, or None to import it
+    pub fn skip_reason(&self, text: Option<&str>, kind: MessageKind, has_attachments: bool) -> Option<SkipReason> {
+        if kind != MessageKind::Text || has_attachments {
+            return None;
+        }
+        let text = text?.trim();
+
+        if self.skip_emoji_only && is_emoji_only(text) {
+            Some(SkipReason::EmojiOnly)
+        } else if text.chars().count() < self.min_length {
+            Some(SkipReason::TooShort)
+        } else if self.codes.as_ref().map_or(false, |(code, words)| code.is_match(text) && words.is_match(text)) {
+            Some(SkipReason::Code)
+        } else if self.patterns.iter().any(|pattern| pattern.is_match(text)) {
+            Some(SkipReason::Pattern)
+        } else {
+            None
+        }
+    }
+}
+
+/// Counts of messages an import skipped, by reason
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SkippedMessages {
+    counts: BTreeMap<SkipReason, usize>,
+}
+
+impl SkippedMessages {
+    pub fn add(&mut self, reason: SkipReason) {
+        *self.counts.entry(reason).or_default() += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// e.g. "Skipped 42 messages: 30 emoji only, 12 too short"
+    pub fn summary(&self) -> String {
+        let reasons: Vec<String> =
+            self.counts.iter().map(|(reason, count)| format!("{} {}", count, reason.as_str())).collect();
+        format!("Skipped {} messages: {}", self.total(), reasons.join(", "))
+    }
+}
+
+/// Whether `text` has at least one emoji and nothing else but whitespace and the joiners,
+/// variation selectors and skin tones emoji are built from
+fn is_emoji_only(text: &str) -> bool {
+    let mut found = false;
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        match c as u32 {
+            // Joiner, variation selectors and the keycap combining mark
+            0x200D | 0xFE0E | 0xFE0F | 0x20E3 => {},
+            // Pictographs, emoticons, transport, flags, skin tones and supplemental symbols
+            0x1F000..=0x1FAFF
+            // Miscellaneous symbols and dingbats, e.g. ☀ ✅ ❤
+            | 0x2600..=0x27BF
+            // Arrows, stars and other symbols drawn as emoji, e.g. ⭐ ⬆
+            | 0x2B00..=0x2BFF
+            // Watches, hourglasses and media controls, e.g. ⌚ ⏰ ⏩
+            | 0x2300..=0x23FF
+            // Tag characters used in subdivision flags
+            | 0xE0020..=0xE007F => found = true,
+            _ => return false,
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(config: ImportFilterConfig) -> ImportFilter {
+        ImportFilter::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_import_filter() {
+        let filter = filter(ImportFilterConfig::default().with_flags(
+            Some(3),
+            true,
+            true,
+            &["(?i)^liked “".to_string()],
+        ));
+        let skip = |text: &str| filter.skip_reason(Some(text), MessageKind::Text, false);
+
+        assert_eq!(skip("👍"), Some(SkipReason::EmojiOnly));
+        assert_eq!(skip(" 😂😂 "), Some(SkipReason::EmojiOnly));
+        assert_eq!(skip("👍🏽"), Some(SkipReason::EmojiOnly));
+        assert_eq!(skip("❤️"), Some(SkipReason::EmojiOnly));
+        assert_eq!(skip("Ok"), Some(SkipReason::TooShort));
+        assert_eq!(skip("ok 👍"), None);
+        assert_eq!(skip("Your verification code is 482913"), Some(SkipReason::Code));
+        assert_eq!(skip("G-482913 is your Google verification code."), Some(SkipReason::Code));
+        assert_eq!(skip("Use 482-913 as your login code"), Some(SkipReason::Code));
+        assert_eq!(skip("Liked “see you at 6”"), Some(SkipReason::Pattern));
+        // A number or the word "code" alone isn't a passcode
+        assert_eq!(skip("The door code changed"), None);
+        assert_eq!(skip("Call me at 5550100"), None);
+        assert_eq!(skip("Sounds good"), None);
+
+        // Only plain text without attachments is filtered
+        assert_eq!(filter.skip_reason(Some("👍"), MessageKind::Text, true), None);
+        assert_eq!(filter.skip_reason(Some("📍"), MessageKind::Location, false), None);
+        assert_eq!(filter.skip_reason(None, MessageKind::Text, false), None);
+
+        assert!(ImportFilter::default().is_empty());
+        assert!(!filter.is_empty());
+        assert!(ImportFilter::new(&ImportFilterConfig { skip_patterns: vec!["(".to_string()], ..Default::default() })
+            .is_err());
+
+        let mut skipped = SkippedMessages::default();
+        skipped.add(SkipReason::TooShort);
+        skipped.add(SkipReason::EmojiOnly);
+        skipped.add(SkipReason::EmojiOnly);
+        assert_eq!(skipped.summary(), "Skipped 3 messages: 2 emoji only, 1 too short");
+    }
+}
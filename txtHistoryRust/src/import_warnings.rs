@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+use crate::file_writer::{write_file, WritePolicy, WrittenFile};
+
+/// File the warnings of an import are written to, in its output directory
+pub const IMPORT_WARNINGS_FILE: &str = "import_warnings.json";
+
+/// GUIDs kept per reason, enough to look a few of them up in chat.db
+const MAX_SAMPLES: usize = 20;
+
+/// Why a message chat.db holds couldn't be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningReason {
+    /// A plain message with neither `text` nor an `attributedBody`
+    MissingText,
+    /// The `attributedBody` couldn't be decoded, or held no text
+    ExtractionFailed,
+    /// The date is unset (chat.db's 2001-01-01 epoch) or in the future
+    BadTimestamp,
+}
+
+impl WarningReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MissingText => "without text",
+            Self::ExtractionFailed => "with text that couldn't be extracted",
+            Self::BadTimestamp => "with bad timestamps",
+        }
+    }
+}
+
+/// How many messages were dropped for one reason and a sample of their GUIDs
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReasonWarnings {
+    pub count: usize,
+    pub sample_guids: Vec<String>,
+}
+
+/// Messages an import couldn't bring in, by reason. Unlike `SkippedMessages`, which counts
+/// what the import filter left out on purpose, these are gaps in the archive.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportWarnings {
+    pub total: usize,
+    pub reasons: BTreeMap<WarningReason, ReasonWarnings>,
+}
+
+impl ImportWarnings {
+    pub fn add(&mut self, reason: WarningReason, guid: &str) {
+        let warnings = self.reasons.entry(reason).or_default();
+        warnings.count += 1;
+        if warnings.sample_guids.len() < MAX_SAMPLES {
+            warnings.sample_guids.push(guid.to_string());
+        }
+        self.total += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// e.g. "Couldn't import 12 messages: 10 without text, 2 with bad timestamps"
+    pub fn summary(&self) -> String {
+        let reasons: Vec<String> = self
+            .reasons
+            .iter()
+            .map(|(reason, warnings)| format!("{} {}", warnings.count, reason.as_str()))
+            .collect();
+        format!("Couldn't import {} messages: {}", self.total, reasons.join(", "))
+    }
+
+    /// Write the warnings as `import_warnings.json` in `output_dir`, replacing earlier ones
+    pub fn write(&self, output_dir: &str) -> Result<WrittenFile> {
+        let path = format!("{}/{}", output_dir, IMPORT_WARNINGS_FILE);
+        write_file(&path, self.total, WritePolicy::Overwrite, |writer, _| {
+            serde_json::to_writer_pretty(&mut *writer, self)?;
+            Ok(())
+        })
+    }
+}
+
+/// Whether a chat.db date, in UTC, was never set or is more than a day after `now`
+pub fn is_bad_timestamp(date: NaiveDateTime, now: NaiveDateTime) -> bool {
+    let unset = NaiveDate::from_ymd_opt(2001, 1, 1).and_then(|d| d.and_hms_opt(0, 0, 0)).unwrap_or_default();
+    date <= unset || date > now + Duration::days(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_warnings() {
+        let mut warnings = ImportWarnings::default();
+        assert!(warnings.is_empty());
+
+        for i in 0..25 {
+            warnings.add(WarningReason::MissingText, &format!("guid-{}", i));
+        }
+        warnings.add(WarningReason::BadTimestamp, "guid-late");
+
+        assert_eq!(warnings.total, 26);
+        let missing = &warnings.reasons[&WarningReason::MissingText];
+        assert_eq!(missing.count, 25);
+        assert_eq!(missing.sample_guids.len(), MAX_SAMPLES);
+        assert_eq!(missing.sample_guids[0], "guid-0");
+        assert_eq!(warnings.summary(), "Couldn't import 26 messages: 25 without text, 1 with bad timestamps");
+
+        let json = serde_json::to_value(&warnings).unwrap();
+        assert_eq!(json["total"], 26);
+        assert_eq!(json["reasons"]["bad_timestamp"]["sample_guids"][0], "guid-late");
+
+        let now = NaiveDate::from_ymd_opt(2025, 1, 20).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        assert!(!is_bad_timestamp(now - Duration::days(3000), now));
+        assert!(!is_bad_timestamp(now + Duration::hours(2), now));
+        assert!(is_bad_timestamp(now + Duration::days(2), now));
+        // chat.db's zero date
+        assert!(is_bad_timestamp(NaiveDate::from_ymd_opt(2001, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(), now));
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+/// Timestamp layouts seen in exports from common locales, tried in order. Day-first and
+/// month-first variants of the same layout are told apart by the samples.
+const CANDIDATE_FORMATS: &[&str] = &[
+    // This tool's TXT and CSV exports
+    "%b %d, %Y %I:%M:%S %p",
+    // ISO 8601
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    // US: 3/4/25, 2:05 PM
+    "%m/%d/%y, %I:%M:%S %p",
+    "%m/%d/%y, %I:%M %p",
+    "%m/%d/%Y, %I:%M:%S %p",
+    "%m/%d/%Y, %I:%M %p",
+    "%m/%d/%Y %I:%M:%S %p",
+    "%m/%d/%Y %I:%M %p",
+    // UK, France, Spain and most of the world: 04/03/2025, 14:05
+    "%d/%m/%y, %H:%M:%S",
+    "%d/%m/%y, %H:%M",
+    "%d/%m/%Y, %H:%M:%S",
+    "%d/%m/%Y, %H:%M",
+    "%d/%m/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%d/%m/%Y, %I:%M %p",
+    // Some US exports use 24 hour time
+    "%m/%d/%y, %H:%M:%S",
+    "%m/%d/%y, %H:%M",
+    "%m/%d/%Y, %H:%M",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    // Germany, Russia, Poland: 04.03.2025, 14:05
+    "%d.%m.%y, %H:%M:%S",
+    "%d.%m.%y, %H:%M",
+    "%d.%m.%Y, %H:%M:%S",
+    "%d.%m.%Y, %H:%M",
+    "%d.%m.%Y, %I:%M %p",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+    // Netherlands: 04-03-2025 14:05
+    "%d-%m-%Y %H:%M:%S",
+    "%d-%m-%Y %H:%M",
+];
+
+/// How import parsers read timestamps
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum DateFormat {
+    /// Work out the layout from the values in the file
+    #[default]
+    Infer,
+    /// A chrono format string given with `--date-format`, e.g. "%d.%m.%Y, %H:%M"
+    Custom(String),
+}
+
+impl DateFormat {
+    pub fn from_option(format: Option<&str>) -> Self {
+        match format {
+            Some(format) => DateFormat::Custom(format.to_string()),
+            None => DateFormat::Infer,
+        }
+    }
+
+    /// Pick the parser for a file's timestamps.
+    ///
+    /// Inference fails instead of guessing when the samples fit both a day-first and a
+    /// month-first layout (e.g. every date is on or before the 12th).
+    pub fn resolve(&self, samples: &[&str]) -> Result<TimestampParser> {
+        if let DateFormat::Custom(format) = self {
+            return Ok(TimestampParser::Format(format.clone()));
+        }
+
+        let samples: Vec<String> = samples
+            .iter()
+            .map(|s| normalize(s))
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        if samples.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No timestamps to infer the format from. Pass --date-format with a chrono format string"
+            ));
+        }
+
+        if samples.iter().all(|s| s.chars().all(|c| c.is_ascii_digit())) {
+            return Ok(TimestampParser::Epoch);
+        }
+
+        let matching: Vec<&str> = CANDIDATE_FORMATS
+            .iter()
+            .copied()
+            .filter(|format| samples.iter().all(|s| parses_plausibly(s, format)))
+            .collect();
+
+        let Some(first) = matching.first() else {
+            return Err(anyhow::anyhow!(
+                "Unrecognized timestamp format: {}. Pass --date-format with a chrono format string",
+                samples[0]
+            ));
+        };
+
+        // Formats that differ only in which field is the day are ambiguous for these samples
+        let day_first = matching.iter().any(|f| is_day_first(f));
+        let month_first = matching.iter().any(|f| !is_day_first(f) && f.contains("%m/%d"));
+        if day_first && month_first {
+            return Err(anyhow::anyhow!(
+                "Can't tell whether {} is day/month or month/day. Pass --date-format, e.g. \
+                 \"%d/%m/%Y, %H:%M\" or \"%m/%d/%y, %I:%M %p\"",
+                samples[0]
+            ));
+        }
+
+        Ok(TimestampParser::Format(first.to_string()))
+    }
+}
+
+/// `%Y` happily reads "25" as the year 25, so a two digit year only counts for `%y` formats
+fn parses_plausibly(value: &str, format: &str) -> bool {
+    NaiveDateTime::parse_from_str(value, format).is_ok_and(|dt| (1970..=2100).contains(&dt.year()))
+}
+
+fn is_day_first(format: &str) -> bool {
+    format.starts_with("%d")
+}
+
+/// Undo the typography some apps put in timestamps: brackets, narrow no-break spaces
+/// before AM/PM and doubled spaces
+fn normalize(value: &str) -> String {
+    value
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .replace(['\u{202f}', '\u{a0}'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reads one file's timestamps once the layout is known
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimestampParser {
+    /// Local time in a chrono format
+    Format(String),
+    /// Unix time in seconds, or milliseconds for 13 digit values
+    Epoch,
+}
+
+impl TimestampParser {
+    /// Parse a timestamp and convert it to UTC
+    pub fn parse(&self, value: &str) -> Result<NaiveDateTime> {
+        let value = normalize(value);
+
+        match self {
+            TimestampParser::Epoch => {
+                let number: i64 = value
+                    .parse()
+                    .with_context(|| format!("Invalid Unix timestamp: {}", value))?;
+                let datetime = if value.len() >= 13 {
+                    DateTime::from_timestamp_millis(number)
+                } else {
+                    DateTime::from_timestamp(number, 0)
+                };
+                datetime
+                    .map(|dt| dt.naive_utc())
+                    .with_context(|| format!("Unix timestamp out of range: {}", value))
+            },
+            TimestampParser::Format(format) => {
+                let local = NaiveDateTime::parse_from_str(&value, format)
+                    .with_context(|| format!("Invalid timestamp: {} (expected {})", value, format))?;
+                local_to_utc(&local).with_context(|| format!("Nonexistent local time: {}", value))
+            },
+        }
+    }
+}
+
+/// Interpret a wall clock time as local time and convert it to UTC. Times in the first or
+/// last year chrono can represent give `None`, since shifting them by the UTC offset overflows.
+pub fn local_to_utc(local: &NaiveDateTime) -> Option<NaiveDateTime> {
+    if local.year() <= NaiveDate::MIN.year() || local.year() >= NaiveDate::MAX.year() {
+        return None;
+    }
+
+    Local
+        .from_local_datetime(local)
+        .earliest()
+        .map(|dt| dt.naive_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+
+    fn local(value: &str, parser: &TimestampParser) -> String {
+        let utc = parser.parse(value).unwrap();
+        Local.from_utc_datetime(&utc).format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    #[test]
+    fn test_infers_common_locales() {
+        let cases = [
+            // US, day disambiguated by the 25th
+            (vec!["3/4/25, 2:05 PM", "3/25/25, 9:00 AM"], "2025-03-04 14:05"),
+            // UK
+            (vec!["04/03/2025, 14:05", "25/03/2025, 09:00"], "2025-03-04 14:05"),
+            // Germany, day first by convention
+            (vec!["04.03.2025, 14:05"], "2025-03-04 14:05"),
+            // Germany with a 12 hour clock
+            (vec!["04.03.2025, 2:05 PM"], "2025-03-04 14:05"),
+            // iOS WhatsApp brackets and narrow no-break space
+            (vec!["[04.03.25, 14:05:12]"], "2025-03-04 14:05"),
+            (vec!["3/4/25, 2:05\u{202f}PM", "12/31/24, 11:59\u{202f}PM"], "2025-03-04 14:05"),
+            // ISO and this tool's own exports
+            (vec!["2025-03-04 14:05:00"], "2025-03-04 14:05"),
+            (vec!["Mar 04, 2025 02:05:00 PM"], "2025-03-04 14:05"),
+        ];
+
+        for (samples, expected) in cases {
+            let parser = DateFormat::Infer.resolve(&samples).unwrap();
+            assert_eq!(local(samples[0], &parser), expected, "{:?}", samples);
+        }
+    }
+
+    #[test]
+    fn test_ambiguous_dates_need_an_explicit_format() {
+        let samples = ["03/04/2025, 14:05", "05/06/2025, 09:00"];
+        let error = DateFormat::Infer.resolve(&samples).unwrap_err();
+        assert!(error.to_string().contains("--date-format"));
+
+        let parser = DateFormat::Custom("%d/%m/%Y, %H:%M".to_string())
+            .resolve(&samples)
+            .unwrap();
+        assert_eq!(local(samples[0], &parser), "2025-04-03 14:05");
+
+        assert!(DateFormat::Infer.resolve(&["yesterday"]).is_err());
+    }
+
+    #[test]
+    fn test_epoch_timestamps() {
+        let parser = DateFormat::Infer.resolve(&["1741097100", "1741097160"]).unwrap();
+        assert_eq!(parser, TimestampParser::Epoch);
+        assert_eq!(
+            parser.parse("1741097100").unwrap(),
+            TimestampParser::Epoch.parse("1741097100000").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_no_samples() {
+        assert!(DateFormat::Infer.resolve(&[]).is_err());
+        assert!(DateFormat::Infer.resolve(&["", " [] "]).is_err());
+        assert!(local_to_utc(&NaiveDateTime::MAX).is_none());
+        assert!(local_to_utc(&NaiveDateTime::MIN).is_none());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_arbitrary_timestamps_never_panic(samples in proptest::collection::vec("\\PC{0,30}", 0..5), format in "\\PC{0,12}") {
+            let samples: Vec<&str> = samples.iter().map(String::as_str).collect();
+            if let Ok(parser) = DateFormat::Infer.resolve(&samples) {
+                for sample in &samples {
+                    let _ = parser.parse(sample);
+                }
+            }
+            for sample in &samples {
+                let _ = TimestampParser::Format(format.clone()).parse(sample);
+                let _ = TimestampParser::Epoch.parse(sample);
+            }
+        }
+
+        #[test]
+        fn prop_inferred_formats_round_trip(secs in 31_622_400i64..4_070_908_800, layout in 0usize..3) {
+            let local = Local.timestamp_opt(secs, 0).unwrap().naive_local();
+            let (format, expected) = match layout {
+                0 => ("%Y-%m-%d %H:%M:%S", local),
+                1 => ("%b %d, %Y %I:%M:%S %p", local),
+                _ => ("%d.%m.%Y, %H:%M", local.with_second(0).unwrap()),
+            };
+            let value = local.format(format).to_string();
+
+            let parser = DateFormat::Infer.resolve(&[&value]).unwrap();
+            proptest::prop_assert_eq!(parser.parse(&value).ok(), local_to_utc(&expected));
+        }
+    }
+}
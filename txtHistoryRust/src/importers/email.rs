@@ -0,0 +1,224 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use mailparse::{addrparse_header, dateparse, parse_mail, DispositionType, MailAddr, MailHeaderMap, ParsedMail};
+
+use super::ImportedMessage;
+
+/// Service name stored for imported emails
+pub const EMAIL_SERVICE: &str = "Email";
+
+/// Read emails from an mbox file, a single .eml file, or a directory of .eml files
+pub fn read_emails(path: &Path, my_addresses: &[String]) -> Result<Vec<ImportedMessage>> {
+    let raw_messages = if path.is_dir() {
+        let mut raw = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry_path = entry?.path();
+            if is_eml(&entry_path) {
+                raw.push(fs::read(&entry_path)?);
+            }
+        }
+        raw
+    } else if is_eml(path) {
+        vec![fs::read(path)?]
+    } else {
+        split_mbox(&fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?)
+    };
+
+    let mut messages = Vec::new();
+    for raw in &raw_messages {
+        match parse_email(raw, my_addresses) {
+            Ok(Some(message)) => messages.push(message),
+            Ok(None) => {}
+            Err(e) => eprintln!("Skipping unreadable email: {:#}", e),
+        }
+    }
+
+    Ok(messages)
+}
+
+fn is_eml(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("eml"))
+}
+
+/// Split an mbox file into raw messages on its "From " separator lines
+fn split_mbox(contents: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut current: Vec<u8> = Vec::new();
+
+    for line in contents.split_inclusive(|&b| b == b'\n') {
+        if line.starts_with(b"From ") {
+            if !current.is_empty() {
+                messages.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+
+        // Undo mboxrd quoting of body lines that start with "From "
+        match line.strip_prefix(b">") {
+            Some(rest) if rest.starts_with(b"From ") => current.extend_from_slice(rest),
+            _ => current.extend_from_slice(line),
+        }
+    }
+
+    if !current.iter().all(u8::is_ascii_whitespace) {
+        messages.push(current);
+    }
+
+    messages
+}
+
+/// Parse one raw email, returning `None` for emails without a usable sender
+fn parse_email(raw: &[u8], my_addresses: &[String]) -> Result<Option<ImportedMessage>> {
+    let mail = parse_mail(raw)?;
+    let headers = mail.get_headers();
+
+    let Some(from_header) = headers.get_first_header("From") else {
+        return Ok(None);
+    };
+    let Some((from_name, from_address)) = first_address(&addrparse_header(from_header)?) else {
+        return Ok(None);
+    };
+
+    let to = match headers.get_first_header("To") {
+        Some(header) => first_address(&addrparse_header(header)?),
+        None => None,
+    };
+
+    let date = headers.get_first_value("Date").context("Missing Date header")?;
+    let timestamp = DateTime::from_timestamp(dateparse(&date).map_err(|e| anyhow::anyhow!(e))?, 0)
+        .context("Date header out of range")?
+        .naive_utc();
+
+    let is_from_me = my_addresses
+        .iter()
+        .any(|address| address.eq_ignore_ascii_case(&from_address));
+
+    // The counterpart is whoever isn't me
+    let (counterpart_name, counterpart_identifier) = if is_from_me {
+        to.map_or((None, None), |(name, address)| (Some(name), Some(address)))
+    } else {
+        (Some(from_name.clone()), Some(from_address.clone()))
+    };
+
+    let message_id = headers
+        .get_first_value("Message-ID")
+        .map(|id| id.trim().to_string())
+        .unwrap_or_else(|| format!("{}-{}", from_address, timestamp.and_utc().timestamp()));
+
+    // Thread by the root of the References chain so replies group together
+    let thread_id = headers
+        .get_first_value("References")
+        .and_then(|refs| refs.split_whitespace().next().map(ToString::to_string))
+        .or_else(|| headers.get_first_value("In-Reply-To"))
+        .unwrap_or_else(|| message_id.clone());
+
+    let subject = headers.get_first_value("Subject").unwrap_or_default();
+    let body = find_text_body(&mail)?.unwrap_or_default();
+    let text = match (subject.trim(), body.trim()) {
+        ("", "") => None,
+        ("", body) => Some(body.to_string()),
+        (subject, "") => Some(subject.to_string()),
+        (subject, body) => Some(format!("{}\n\n{}", subject, body)),
+    };
+
+    Ok(Some(ImportedMessage {
+        source_id: format!("email:{}", message_id),
+        sender_name: from_name,
+        is_from_me,
+        counterpart_identifier,
+        counterpart_name,
+        timestamp,
+        text,
+        service: EMAIL_SERVICE.to_string(),
+        thread_id: Some(thread_id),
+        has_attachments: has_attachments(&mail),
+        reply_to: headers
+            .get_first_value("In-Reply-To")
+            .map(|id| format!("email:{}", id.trim())),
+    }))
+}
+
+/// First address in an address list as (display name, lowercased address)
+fn first_address(addresses: &[MailAddr]) -> Option<(String, String)> {
+    addresses.iter().find_map(|addr| {
+        let info = match addr {
+            MailAddr::Single(info) => info,
+            MailAddr::Group(group) => group.addrs.first()?,
+        };
+        Some((
+            info.display_name.clone().unwrap_or_else(|| info.addr.clone()),
+            info.addr.to_lowercase(),
+        ))
+    })
+}
+
+/// Find the first text/plain part that isn't an attachment
+fn find_text_body(mail: &ParsedMail<'_>) -> Result<Option<String>> {
+    if mail.subparts.is_empty() {
+        let is_attachment = mail.get_content_disposition().disposition == DispositionType::Attachment;
+        if mail.ctype.mimetype == "text/plain" && !is_attachment {
+            return Ok(Some(mail.get_body()?));
+        }
+        return Ok(None);
+    }
+
+    for part in &mail.subparts {
+        if let Some(body) = find_text_body(part)? {
+            return Ok(Some(body));
+        }
+    }
+
+    Ok(None)
+}
+
+fn has_attachments(mail: &ParsedMail<'_>) -> bool {
+    mail.get_content_disposition().disposition == DispositionType::Attachment
+        || mail.subparts.iter().any(has_attachments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MBOX: &str = "From phil@example.com Mon Jan 20 12:00:00 2025
+From: Phil <Phil@Example.com>
+To: Jess <jess@example.com>
+Date: Mon, 20 Jan 2025 12:00:00 +0000
+Message-ID: <one@example.com>
+Subject: Pickup
+
+Can you get her Friday?
+>From now on I'll drive.
+
+From jess@example.com Mon Jan 20 13:00:00 2025
+From: jess@example.com
+To: Phil <phil@example.com>
+Date: Mon, 20 Jan 2025 13:00:00 +0000
+Message-ID: <two@example.com>
+In-Reply-To: <one@example.com>
+Subject: Re: Pickup
+
+Yes
+";
+
+    #[test]
+    fn test_read_mbox() {
+        let raw = split_mbox(MBOX.as_bytes());
+        assert_eq!(raw.len(), 2);
+
+        let my_addresses = vec!["jess@example.com".to_string()];
+        let first = parse_email(&raw[0], &my_addresses).unwrap().unwrap();
+        assert!(!first.is_from_me);
+        assert_eq!(first.counterpart_identifier.as_deref(), Some("phil@example.com"));
+        assert_eq!(first.source_id, "email:<one@example.com>");
+        assert!(first.text.unwrap().contains("From now on"));
+
+        let second = parse_email(&raw[1], &my_addresses).unwrap().unwrap();
+        assert!(second.is_from_me);
+        assert_eq!(second.counterpart_name.as_deref(), Some("Phil"));
+        assert_eq!(second.thread_id.as_deref(), Some("<one@example.com>"));
+    }
+}
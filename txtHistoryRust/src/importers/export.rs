@@ -0,0 +1,384 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+use super::dates::{local_to_utc, DateFormat, TimestampParser};
+use super::ImportedMessage;
+use crate::models::Message;
+
+/// Timestamp layout used by the TXT and CSV writers ("%b %d, %Y %r")
+const EXPORT_TIMESTAMP_FORMAT: &str = "%b %d, %Y %I:%M:%S %p";
+
+/// Service recorded when an export doesn't say where a message came from
+const DEFAULT_SERVICE: &str = "iMessage";
+
+/// Read this tool's own TXT, CSV or JSON exports back into messages.
+///
+/// `path` may be a single chunk file or a directory of chunk files. Exports don't record who
+/// the conversation was with, so `contact` is used as the counterpart and messages sent by
+/// `my_name` are marked as mine. CSV timestamps are read with `date_format`, so CSVs written by
+/// other tools in other locales can be restored too.
+pub fn read_exports(
+    path: &Path,
+    contact: &str,
+    my_name: &str,
+    date_format: &DateFormat,
+) -> Result<Vec<ImportedMessage>> {
+    let files = if path.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| export_kind(p).is_some())
+            .collect();
+        files.sort_by_key(|p| chunk_number(p));
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut messages = Vec::new();
+    for file in &files {
+        let contents = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+
+        let kind = export_kind(file)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported export file: {}", file.display()))?;
+        messages.extend(read_export_contents(&contents, kind, contact, my_name, date_format)?);
+    }
+
+    Ok(messages)
+}
+
+/// Read the contents of one export file of the given kind ("txt", "csv" or "json")
+pub fn read_export_contents(
+    contents: &str,
+    kind: &str,
+    contact: &str,
+    my_name: &str,
+    date_format: &DateFormat,
+) -> Result<Vec<ImportedMessage>> {
+    let records = match kind {
+        "txt" => parse_txt(contents)?,
+        "csv" => parse_csv(contents, date_format)?,
+        "json" => parse_json(contents)?,
+        other => return Err(anyhow::anyhow!("Unsupported export kind: {}", other)),
+    };
+
+    Ok(records.into_iter().map(|record| record.into_imported(contact, my_name)).collect())
+}
+
+fn export_kind(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "txt" => Some("txt"),
+        "csv" => Some("csv"),
+        "json" => Some("json"),
+        _ => None,
+    }
+}
+
+/// Sort chunk_2 before chunk_10
+fn chunk_number(path: &Path) -> (usize, PathBuf) {
+    let number = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.rsplit('_').next())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(usize::MAX);
+    (number, path.to_path_buf())
+}
+
+/// One message recovered from an export file
+#[derive(Debug, Clone, PartialEq)]
+struct ExportRecord {
+    sender: String,
+    /// UTC timestamp
+    timestamp: NaiveDateTime,
+    content: String,
+    imessage_id: Option<String>,
+    service: Option<String>,
+}
+
+impl ExportRecord {
+    fn into_imported(self, contact: &str, my_name: &str) -> ImportedMessage {
+        // Exports written before IDs were available get a GUID derived from their content,
+        // so importing the same export twice doesn't duplicate messages
+        let source_id = self.imessage_id.clone().unwrap_or_else(|| {
+            format!(
+                "export:{:016x}",
+                fnv1a(&format!("{}\u{1f}{}\u{1f}{}", self.sender, self.timestamp, self.content))
+            )
+        });
+
+        ImportedMessage {
+            source_id,
+            is_from_me: self.sender == my_name,
+            sender_name: self.sender,
+            counterpart_identifier: None,
+            counterpart_name: Some(contact.to_string()),
+            timestamp: self.timestamp,
+            text: Some(self.content),
+            service: self.service.unwrap_or_else(|| DEFAULT_SERVICE.to_string()),
+            thread_id: None,
+            has_attachments: false,
+            reply_to: None,
+        }
+    }
+}
+
+/// FNV-1a, used instead of `DefaultHasher` because its output must not change between builds
+fn fnv1a(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Interpret an export timestamp as local time and convert it to UTC
+fn parse_export_timestamp(value: &str) -> Result<NaiveDateTime> {
+    let local = NaiveDateTime::parse_from_str(value.trim(), EXPORT_TIMESTAMP_FORMAT)
+        .with_context(|| format!("Invalid timestamp: {}", value))?;
+
+    local_to_utc(&local).with_context(|| format!("Nonexistent local time: {}", value))
+}
+
+/// TXT records are "Sender, Jan 20, 2025 12:00:00 PM, content" followed by a blank line,
+/// optionally prefixed with "[id:GUID] " and "[Source] ". Content may span several lines.
+/// Totals footers written with `txt_totals` are skipped.
+fn parse_txt(contents: &str) -> Result<Vec<ExportRecord>> {
+    let record_start = Regex::new(
+        r"^(?:\[id:([^\]]+)\] )?(?:\[([A-Za-z]+)\] )?(.+?), ([A-Z][a-z]{2} \d{2}, \d{4} \d{2}:\d{2}:\d{2} [AP]M), (.*)$",
+    )?;
+    let totals_footer = Regex::new(r"^-- \d+ messages, \d+ words, \d+ characters, about \d+ min to read --$")?;
+
+    let mut records: Vec<ExportRecord> = Vec::new();
+    for line in contents.lines() {
+        if totals_footer.is_match(line) {
+            continue;
+        }
+
+        if let Some(captures) = record_start.captures(line) {
+            records.push(ExportRecord {
+                sender: captures[3].to_string(),
+                timestamp: parse_export_timestamp(&captures[4])?,
+                content: captures[5].to_string(),
+                imessage_id: captures.get(1).map(|id| id.as_str().to_string()),
+                service: captures.get(2).map(|s| s.as_str().to_string()),
+            });
+        } else if let Some(record) = records.last_mut() {
+            record.content.push('\n');
+            record.content.push_str(line);
+        }
+    }
+
+    // Drop the blank separator line after each message
+    for record in &mut records {
+        let trimmed = record.content.trim_end_matches('\n').len();
+        record.content.truncate(trimmed);
+    }
+
+    Ok(records)
+}
+
+/// CSV exports are read by header name, so any column order or delimiter chosen at export works.
+/// The timestamp layout is inferred from the whole column unless `date_format` gives one.
+fn parse_csv(contents: &str, date_format: &DateFormat) -> Result<Vec<ExportRecord>> {
+    let header_line = contents.lines().next().unwrap_or_default();
+    let delimiter = [b'\t', b';', b'|']
+        .into_iter()
+        .find(|d| header_line.as_bytes().contains(d))
+        .unwrap_or(b',');
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(contents.as_bytes());
+
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let (Some(sender), Some(timestamp), Some(content)) =
+        (column("Sender"), column("Timestamp"), column("Content"))
+    else {
+        return Err(anyhow::anyhow!(
+            "CSV export needs Sender, Timestamp and Content header columns"
+        ));
+    };
+    let id = column("ID");
+    let service = column("Service").or_else(|| column("Source"));
+
+    let rows = reader.records().collect::<csv::Result<Vec<_>>>()?;
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+    let samples: Vec<&str> = rows.iter().filter_map(|row| row.get(timestamp)).collect();
+    let parser: TimestampParser = date_format.resolve(&samples)?;
+
+    let mut records = Vec::new();
+    for row in &rows {
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|i| row.get(i))
+                .filter(|value| !value.is_empty())
+                .map(ToString::to_string)
+        };
+
+        records.push(ExportRecord {
+            sender: row.get(sender).unwrap_or_default().to_string(),
+            timestamp: parser.parse(row.get(timestamp).unwrap_or_default())?,
+            content: row.get(content).unwrap_or_default().to_string(),
+            imessage_id: field(id),
+            service: field(service),
+        });
+    }
+
+    Ok(records)
+}
+
+fn parse_json(contents: &str) -> Result<Vec<ExportRecord>> {
+    let messages: Vec<Message> = serde_json::from_str(contents).context("Invalid JSON export")?;
+
+    Ok(messages
+        .into_iter()
+        .map(|message| ExportRecord {
+            sender: message.sender,
+            timestamp: message.timestamp.naive_utc(),
+            content: message.content,
+            imessage_id: message.imessage_id,
+            service: message.service.or(message.source),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn test_parse_txt() {
+        let contents = "Phil, Jan 20, 2025 12:00:00 PM, Pick up at 3, ok?\nSecond line\n\n\
+                        [id:guid-2] [Email] Jess, Jan 20, 2025 01:05:30 PM, Yes\n\n\
+                        -- 2 messages, 8 words, 32 characters, about 1 min to read --\n";
+        let records = parse_txt(contents).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].content, "Yes");
+        assert_eq!(records[0].sender, "Phil");
+        assert_eq!(records[0].content, "Pick up at 3, ok?\nSecond line");
+        assert_eq!(records[1].service.as_deref(), Some("Email"));
+        assert_eq!((records[0].imessage_id.as_deref(), records[1].imessage_id.as_deref()), (None, Some("guid-2")));
+        assert_eq!(
+            Local.from_utc_datetime(&records[1].timestamp).format("%H:%M:%S").to_string(),
+            "13:05:30"
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_with_custom_layout() {
+        let contents = "ID\tSender\tContent\tTimestamp\n\
+                        guid-1\tPhil\t\"tab\tand \"\"quotes\"\"\"\tJan 20, 2025 12:00:00 PM\n";
+        let records = parse_csv(contents, &DateFormat::Infer).unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].imessage_id.as_deref(), Some("guid-1"));
+        assert_eq!(records[0].content, "tab\tand \"quotes\"");
+    }
+
+    #[test]
+    fn test_parse_csv_with_locale_timestamps() {
+        let contents = "Sender;Timestamp;Content\n\
+                        Phil;04.03.2025, 14:05;Hallo\n\
+                        Jess;25.03.2025, 09:00;Hi\n";
+        let records = parse_csv(contents, &DateFormat::Infer).unwrap();
+        assert_eq!(
+            Local.from_utc_datetime(&records[0].timestamp).format("%Y-%m-%d %H:%M").to_string(),
+            "2025-03-04 14:05"
+        );
+
+        // Every date is on or before the 12th, so the order must be given
+        let contents = "Sender,Timestamp,Content\nPhil,\"03/04/2025, 14:05\",hi\n";
+        assert!(parse_csv(contents, &DateFormat::Infer).is_err());
+        let records =
+            parse_csv(contents, &DateFormat::Custom("%m/%d/%Y, %H:%M".to_string())).unwrap();
+        assert_eq!(
+            Local.from_utc_datetime(&records[0].timestamp).format("%Y-%m-%d").to_string(),
+            "2025-03-04"
+        );
+    }
+
+    #[test]
+    fn test_synthetic_guids_are_deterministic() {
+        let records = parse_txt("Phil, Jan 20, 2025 12:00:00 PM, hi\n\n").unwrap();
+        let first = records[0].clone().into_imported("Phil", "Jess");
+        let second = records[0].clone().into_imported("Phil", "Jess");
+
+        assert_eq!(first.source_id, second.source_id);
+        assert!(first.source_id.starts_with("export:"));
+        assert!(!first.is_from_me);
+    }
+
+    #[test]
+    fn test_parse_csv_without_rows() {
+        assert!(parse_csv("Sender,Timestamp,Content\n", &DateFormat::Infer).unwrap().is_empty());
+    }
+
+    fn messages(rows: Vec<(String, String, i64)>) -> Vec<Message> {
+        rows.into_iter()
+            .map(|(sender, content, secs)| Message {
+                sender,
+                timestamp: Local.timestamp_opt(secs, 0).unwrap(),
+                content,
+                source: None,
+                imessage_id: None,
+                service: None,
+                is_from_me: false,
+            })
+            .collect()
+    }
+
+    fn local_text(timestamp: &NaiveDateTime) -> String {
+        Local.from_utc_datetime(timestamp).format("%b %d, %Y %r").to_string()
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_arbitrary_exports_never_panic(contents in "\\PC{0,400}") {
+            let _ = parse_txt(&contents);
+            let _ = parse_csv(&contents, &DateFormat::Infer);
+            let _ = parse_json(&contents);
+        }
+
+        #[test]
+        fn prop_txt_round_trip(
+            rows in proptest::collection::vec(("[A-Z][a-z]{0,8}", "[a-zA-Z0-9 ,.!?]{0,40}", 946_684_800i64..1_893_456_000), 0..20)
+        ) {
+            let messages = messages(rows);
+            let mut output = Vec::new();
+            crate::file_writer::write_txt(&mut output, &messages, false, &crate::style::ExportStyle::default()).unwrap();
+
+            let records = parse_txt(&String::from_utf8(output).unwrap()).unwrap();
+            proptest::prop_assert_eq!(records.len(), messages.len());
+            for (record, message) in records.iter().zip(&messages) {
+                proptest::prop_assert_eq!(&record.sender, &message.sender);
+                proptest::prop_assert_eq!(&record.content, &message.content);
+                proptest::prop_assert_eq!(local_text(&record.timestamp), message.timestamp.format("%b %d, %Y %r").to_string());
+            }
+        }
+
+        #[test]
+        fn prop_csv_round_trip(
+            rows in proptest::collection::vec(("\\PC{1,20}", "\\PC{0,80}", 946_684_800i64..1_893_456_000), 0..20)
+        ) {
+            let messages = messages(rows);
+            let mut output = Vec::new();
+            crate::file_writer::write_csv(&mut output, &messages, &crate::file_writer::CsvOptions::default(), true).unwrap();
+
+            let records = parse_csv(&String::from_utf8(output).unwrap(), &DateFormat::Infer).unwrap();
+            proptest::prop_assert_eq!(records.len(), messages.len());
+            for (record, message) in records.iter().zip(&messages) {
+                proptest::prop_assert_eq!(&record.sender, &message.sender);
+                proptest::prop_assert_eq!(&record.content, &message.content);
+                proptest::prop_assert_eq!(local_text(&record.timestamp), message.timestamp.format("%b %d, %Y %r").to_string());
+            }
+        }
+    }
+}
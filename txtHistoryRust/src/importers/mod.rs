@@ -0,0 +1,132 @@
+pub mod dates;
+pub mod email;
+pub mod export;
+pub mod telegram;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+
+use crate::db::Database;
+use crate::error::TxtHistoryError;
+use crate::import_filter::{ImportFilter, SkippedMessages};
+use crate::models::{DbContact, MessageKind, NewMessage};
+use crate::sender_map::SenderMap;
+
+/// A message read from another platform's export, before it's matched to a contact
+#[derive(Debug, Clone)]
+pub struct ImportedMessage {
+    /// Stable ID from the source, stored in `imessage_id` so re-imports are deduplicated
+    pub source_id: String,
+    /// Display name of the sender as it appears in the source
+    pub sender_name: String,
+    pub is_from_me: bool,
+    /// Email address or phone number of the other party in the conversation
+    pub counterpart_identifier: Option<String>,
+    /// Display name of the other party, used when the identifier doesn't match a contact
+    pub counterpart_name: Option<String>,
+    /// UTC timestamp
+    pub timestamp: NaiveDateTime,
+    pub text: Option<String>,
+    pub service: String,
+    pub thread_id: Option<String>,
+    pub has_attachments: bool,
+    /// Source ID of the message this one replies to
+    pub reply_to: Option<String>,
+}
+
+/// Counts from saving imported messages
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// Messages saved (or already present)
+    pub imported: usize,
+    /// Messages whose counterpart didn't match any contact
+    pub unmatched: usize,
+    /// Messages left out by the import filter
+    pub skipped: SkippedMessages,
+}
+
+/// Match imported messages to contacts and save them to the database, leaving out those
+/// `filter` skips. Display names `senders` maps are matched to the contacts it names.
+pub fn save_imported_messages(
+    db: &Database,
+    messages: &[ImportedMessage],
+    filter: &ImportFilter,
+    senders: &SenderMap,
+) -> Result<ImportSummary> {
+    let me = db
+        .get_me_contact()?
+        .ok_or_else(|| anyhow::anyhow!("No contact is marked as me"))?;
+
+    // Catch misspelled contacts in the sender map before anything is saved
+    for contact in senders.contacts() {
+        db.get_contact(contact)?
+            .ok_or_else(|| TxtHistoryError::ContactNotFound(contact.to_string()))?;
+    }
+
+    let mut summary = ImportSummary::default();
+
+    for message in messages {
+        if let Some(reason) = filter.skip_reason(message.text.as_deref(), MessageKind::Text, message.has_attachments) {
+            summary.skipped.add(reason);
+            continue;
+        }
+
+        // Prefer the identifier, then fall back to the display name
+        let mut contact = match &message.counterpart_identifier {
+            Some(identifier) => db.find_contact_by_identifier(identifier)?,
+            None => None,
+        };
+        if contact.is_none() {
+            if let Some(name) = &message.counterpart_name {
+                contact = find_named_contact(db, senders, name)?;
+            }
+        }
+
+        // A mapped sender, e.g. in a group chat named after the group, is who the message is from
+        let mapped_sender = match senders.contact_for(&message.sender_name) {
+            Some(name) if !message.is_from_me => db.get_contact(name)?,
+            _ => None,
+        };
+        if contact.is_none() {
+            contact = mapped_sender.clone();
+        }
+
+        if contact.is_none() {
+            summary.unmatched += 1;
+        }
+
+        let sender = if message.is_from_me {
+            me.name.clone()
+        } else {
+            mapped_sender
+                .as_ref()
+                .or(contact.as_ref())
+                .map_or_else(|| message.sender_name.clone(), |c| c.name.clone())
+        };
+
+        db.add_message(NewMessage {
+            imessage_id: message.source_id.clone(),
+            text: message.text.clone(),
+            sender,
+            is_from_me: message.is_from_me,
+            date_created: message.timestamp,
+            date_imported: None,
+            handle_id: message.counterpart_identifier.clone(),
+            service: Some(message.service.clone()),
+            thread_id: message.thread_id.clone(),
+            has_attachments: message.has_attachments,
+            reply_to: message.reply_to.clone(),
+            contact_id: contact.map(|c| c.id),
+            message_kind: MessageKind::Text,
+        })?;
+
+        summary.imported += 1;
+    }
+
+    Ok(summary)
+}
+
+/// The contact a display name is mapped to, or else the contact with that exact name
+fn find_named_contact(db: &Database, senders: &SenderMap, name: &str) -> Result<Option<DbContact>> {
+    db.get_contact(senders.contact_for(name).unwrap_or(name))
+}
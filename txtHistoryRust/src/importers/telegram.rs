@@ -0,0 +1,229 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::ImportedMessage;
+
+/// Service name stored for imported Telegram messages
+pub const TELEGRAM_SERVICE: &str = "Telegram";
+
+/// Full account export ("Export Telegram data")
+#[derive(Debug, Deserialize)]
+struct TelegramAccountExport {
+    #[serde(default)]
+    personal_information: Option<PersonalInformation>,
+    chats: TelegramChatList,
+}
+
+#[derive(Debug, Deserialize)]
+struct PersonalInformation {
+    user_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramChatList {
+    list: Vec<TelegramChat>,
+}
+
+/// Single chat export ("Export chat history")
+#[derive(Debug, Deserialize)]
+struct TelegramChat {
+    #[serde(default)]
+    name: Option<String>,
+    id: i64,
+    #[serde(default)]
+    messages: Vec<TelegramMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramMessage {
+    id: i64,
+    #[serde(rename = "type")]
+    kind: String,
+    date: String,
+    #[serde(default)]
+    date_unixtime: Option<String>,
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    from_id: Option<String>,
+    #[serde(default)]
+    actor: Option<String>,
+    #[serde(default)]
+    actor_id: Option<String>,
+    #[serde(default)]
+    action: Option<String>,
+    #[serde(default)]
+    duration_seconds: Option<i64>,
+    #[serde(default)]
+    text: Value,
+    #[serde(default)]
+    reply_to_message_id: Option<i64>,
+    #[serde(default)]
+    photo: Option<String>,
+    #[serde(default)]
+    file: Option<String>,
+}
+
+/// Read a Telegram `result.json`, either a single chat export or a full account export.
+///
+/// Messages sent by `my_name` (or by the account owner in a full export) are marked as mine.
+pub fn read_telegram_export(path: &Path, my_name: Option<&str>) -> Result<Vec<ImportedMessage>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let value: Value = serde_json::from_str(&contents).context("Invalid Telegram export JSON")?;
+
+    let (chats, my_id) = if value.get("chats").is_some() {
+        let export: TelegramAccountExport = serde_json::from_value(value)?;
+        let my_id = export.personal_information.map(|info| format!("user{}", info.user_id));
+        (export.chats.list, my_id)
+    } else {
+        (vec![serde_json::from_value::<TelegramChat>(value)?], None)
+    };
+
+    let mut messages = Vec::new();
+    for chat in &chats {
+        for message in &chat.messages {
+            match convert_message(chat, message, my_id.as_deref(), my_name) {
+                Ok(Some(imported)) => messages.push(imported),
+                Ok(None) => {}
+                Err(e) => eprintln!("Skipping Telegram message {}: {:#}", message.id, e),
+            }
+        }
+    }
+
+    Ok(messages)
+}
+
+fn convert_message(
+    chat: &TelegramChat,
+    message: &TelegramMessage,
+    my_id: Option<&str>,
+    my_name: Option<&str>,
+) -> Result<Option<ImportedMessage>> {
+    let is_service = message.kind == "service";
+    let (sender_name, sender_id) = if is_service {
+        (message.actor.clone(), message.actor_id.as_deref())
+    } else {
+        (message.from.clone(), message.from_id.as_deref())
+    };
+    let sender_name = sender_name.unwrap_or_else(|| "Unknown".to_string());
+
+    let is_from_me = my_id.map_or(false, |id| sender_id == Some(id))
+        || my_name.map_or(false, |name| name == sender_name);
+
+    let text = if is_service {
+        Some(describe_action(message))
+    } else {
+        Some(flatten_text(&message.text)).filter(|t| !t.trim().is_empty())
+    };
+
+    let has_attachments = message.photo.is_some() || message.file.is_some();
+    if text.is_none() && !has_attachments {
+        return Ok(None);
+    }
+
+    Ok(Some(ImportedMessage {
+        source_id: source_id(chat.id, message.id),
+        sender_name: sender_name.clone(),
+        is_from_me,
+        counterpart_identifier: None,
+        // In one-on-one chats the chat is named after the other person
+        counterpart_name: chat.name.clone().or(Some(sender_name)),
+        timestamp: parse_timestamp(message)?,
+        text,
+        service: TELEGRAM_SERVICE.to_string(),
+        thread_id: Some(format!("telegram:{}", chat.id)),
+        has_attachments,
+        reply_to: message.reply_to_message_id.map(|id| source_id(chat.id, id)),
+    }))
+}
+
+fn source_id(chat_id: i64, message_id: i64) -> String {
+    format!("telegram:{}:{}", chat_id, message_id)
+}
+
+/// Telegram text is either a plain string or an array of strings and formatted entities
+fn flatten_text(text: &Value) -> String {
+    match text {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(|part| match part {
+                Value::String(s) => s.clone(),
+                other => other.get("text").and_then(Value::as_str).unwrap_or_default().to_string(),
+            })
+            .collect(),
+        _ => String::new(),
+    }
+}
+
+/// Render a service message (calls, pins, group changes) as a bracketed placeholder
+fn describe_action(message: &TelegramMessage) -> String {
+    let action = message.action.as_deref().unwrap_or("service message").replace('_', " ");
+
+    match message.duration_seconds {
+        Some(seconds) => format!("[{}, {}s]", action, seconds),
+        None => format!("[{}]", action),
+    }
+}
+
+/// Prefer the unambiguous unix time; older exports only have a local `date`
+fn parse_timestamp(message: &TelegramMessage) -> Result<NaiveDateTime> {
+    if let Some(unix) = &message.date_unixtime {
+        let seconds: i64 = unix.parse().context("Invalid date_unixtime")?;
+        return DateTime::from_timestamp(seconds, 0)
+            .map(|dt| dt.naive_utc())
+            .context("date_unixtime out of range");
+    }
+
+    let local = NaiveDateTime::parse_from_str(&message.date, "%Y-%m-%dT%H:%M:%S")
+        .with_context(|| format!("Invalid date {}", message.date))?;
+
+    Local
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|dt| dt.naive_utc())
+        .context("Nonexistent local time")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"{
+        "name": "Phil",
+        "type": "personal_chat",
+        "id": 42,
+        "messages": [
+            {"id": 1, "type": "message", "date": "2025-01-20T12:00:00", "date_unixtime": "1737374400",
+             "from": "Phil", "from_id": "user7", "text": ["Pick up at ", {"type": "bold", "text": "3pm"}]},
+            {"id": 2, "type": "message", "date": "2025-01-20T12:01:00", "date_unixtime": "1737374460",
+             "from": "Jess", "from_id": "user9", "text": "ok", "reply_to_message_id": 1},
+            {"id": 3, "type": "service", "date": "2025-01-20T12:02:00", "date_unixtime": "1737374520",
+             "actor": "Phil", "actor_id": "user7", "action": "phone_call", "duration_seconds": 30, "text": ""},
+            {"id": 4, "type": "message", "date": "2025-01-20T12:03:00", "date_unixtime": "1737374580",
+             "from": "Phil", "from_id": "user7", "text": ""}
+        ]
+    }"#;
+
+    #[test]
+    fn test_read_chat_export() {
+        let path = std::env::temp_dir().join("txt_history_telegram_test.json");
+        fs::write(&path, EXPORT).unwrap();
+        let messages = read_telegram_export(&path, Some("Jess")).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // The empty message without attachments is dropped
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].text.as_deref(), Some("Pick up at 3pm"));
+        assert_eq!(messages[0].counterpart_name.as_deref(), Some("Phil"));
+        assert!(messages[1].is_from_me);
+        assert_eq!(messages[1].reply_to.as_deref(), Some("telegram:42:1"));
+        assert_eq!(messages[2].text.as_deref(), Some("[phone call, 30s]"));
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::file_writer::{write_file, WritePolicy, WriteStatus, WrittenFile};
+use crate::models::Message;
+use crate::time_filter::DayFilter;
+use crate::totals::ExportTotals;
+
+/// Name of the manifest written next to hashed exports
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// SHA-256 of a message's sender, UTC timestamp and text, separated by unit separators so
+/// moving text between fields changes the hash. The timestamp is UTC so the hash doesn't
+/// depend on the time zone of the machine that made the export.
+pub fn message_hash(message: &Message) -> String {
+    let timestamp = message
+        .timestamp
+        .with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Secs, true);
+
+    let mut hasher = Sha256::new();
+    hasher.update(message.sender.as_bytes());
+    hasher.update([0x1f]);
+    hasher.update(timestamp.as_bytes());
+    hasher.update([0x1f]);
+    hasher.update(message.content.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Rolling hash over a file's messages in order: starting from 32 zero bytes, each step
+/// hashes the previous value followed by the next message hash. Changing, removing or
+/// reordering any message changes the result.
+pub fn chain_hash(message_hashes: &[String]) -> String {
+    let chain = message_hashes.iter().fold([0u8; 32], |previous, hash| {
+        let mut hasher = Sha256::new();
+        hasher.update(previous);
+        hasher.update(hash.as_bytes());
+        hasher.finalize().into()
+    });
+
+    chain.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// A message's timestamp in UTC, as recorded in manifests
+fn utc_timestamp(message: &Message) -> String {
+    message.timestamp.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// SHA-256 of a file's contents, the same value `shasum -a 256` prints
+pub fn file_hash(path: &Path) -> Result<String> {
+    let contents = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(format!("{:x}", Sha256::digest(&contents)))
+}
+
+/// Hashes recorded for one exported message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageHash {
+    pub imessage_id: Option<String>,
+    /// UTC timestamp as hashed
+    pub timestamp: String,
+    pub sha256: String,
+}
+
+/// Hashes recorded for one export file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// File name relative to the manifest
+    pub path: String,
+    pub bytes: u64,
+    /// SHA-256 of the file as written
+    pub sha256: String,
+    /// Rolling hash over `messages`; absent for files whose messages aren't listed
+    pub chain_hash: Option<String>,
+    /// Message, word and character counts for the file; absent like `chain_hash`
+    #[serde(default)]
+    pub totals: Option<ExportTotals>,
+    pub messages: Vec<MessageHash>,
+}
+
+/// The query a `query` export was made with, so `verify-export` can run it again
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ManifestQuery {
+    pub name: Option<String>,
+    pub chat_id: Option<String>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub service: String,
+    pub sources: String,
+    /// `--hours` window, e.g. "22:00-06:00"
+    #[serde(default)]
+    pub hours: Option<String>,
+    #[serde(default)]
+    pub days: DayFilter,
+    /// Pseudonyms depend on the mapping file, so anonymized exports can't be compared
+    #[serde(default)]
+    pub anonymized: bool,
+}
+
+/// How the messages recorded in a manifest compare with the same query run now
+#[derive(Debug, Default, PartialEq)]
+pub struct ExportDiff {
+    pub unchanged: usize,
+    /// Messages whose sender, time or text changed
+    pub changed: Vec<String>,
+    /// Messages in the export that the query no longer returns, e.g. deleted or hidden
+    pub removed: Vec<String>,
+    /// Messages the query now returns that aren't in the export
+    pub added: Vec<String>,
+}
+
+impl ExportDiff {
+    pub fn differences(&self) -> usize {
+        self.changed.len() + self.removed.len() + self.added.len()
+    }
+}
+
+/// Integrity record for an export, written to `manifest.json` in the output directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportManifest {
+    /// When the export was generated, in UTC
+    pub generated_at: String,
+    pub algorithm: String,
+    /// Counts for the whole export, which per-sender files would double if summed
+    #[serde(default)]
+    pub totals: Option<ExportTotals>,
+    /// Messages per service (iMessage, SMS, RCS, ...), for conversations merged across services
+    #[serde(default)]
+    pub services: BTreeMap<String, usize>,
+    /// The query behind the export, absent for exports `verify-export` can't run again
+    #[serde(default)]
+    pub query: Option<ManifestQuery>,
+    pub files: Vec<ManifestFile>,
+}
+
+impl ExportManifest {
+    pub fn new() -> Self {
+        Self {
+            generated_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            algorithm: "sha256".to_string(),
+            totals: None,
+            services: BTreeMap::new(),
+            query: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// Record a written file. With `messages`, the hash of every message in it and their
+    /// rolling hash and totals are recorded too. Files skipped because they already existed aren't
+    /// part of this export and are left out.
+    pub fn add_file(&mut self, file: &WrittenFile, messages: Option<&[Message]>) -> Result<()> {
+        if file.status == WriteStatus::Skipped {
+            return Ok(());
+        }
+
+        let path = Path::new(&file.path);
+        let totals = messages.map(ExportTotals::from_messages);
+        let messages: Vec<MessageHash> = messages
+            .unwrap_or_default()
+            .iter()
+            .map(|message| MessageHash {
+                imessage_id: message.imessage_id.clone(),
+                timestamp: utc_timestamp(message),
+                sha256: message_hash(message),
+            })
+            .collect();
+        let hashes: Vec<String> = messages.iter().map(|m| m.sha256.clone()).collect();
+
+        self.files.push(ManifestFile {
+            path: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| file.path.clone()),
+            bytes: fs::metadata(path)?.len(),
+            sha256: file_hash(path)?,
+            chain_hash: (!hashes.is_empty()).then(|| chain_hash(&hashes)),
+            totals,
+            messages,
+        });
+
+        Ok(())
+    }
+
+    /// Write the manifest to `manifest.json` in `output_dir`
+    pub fn write(&self, output_dir: &str) -> Result<WrittenFile> {
+        let file_path = format!("{}/{}", output_dir, MANIFEST_FILE);
+        let message_count = self.files.iter().map(|file| file.messages.len()).sum();
+
+        write_file(&file_path, message_count, WritePolicy::Overwrite, |writer, _| {
+            Ok(serde_json::to_writer_pretty(writer, self)?)
+        })
+    }
+
+    /// Load the manifest in `dir`
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let contents = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&contents).with_context(|| format!("Invalid manifest {}", path.display()))
+    }
+
+    /// Compare the recorded message hashes with `current`, the messages the export's query
+    /// returns now. Messages are matched by ID, or by time when they have none.
+    pub fn compare(&self, current: &[Message]) -> ExportDiff {
+        // Per-sender files repeat the merged transcript's messages, so each is counted once
+        let recorded: BTreeMap<String, &MessageHash> = self
+            .files
+            .iter()
+            .flat_map(|file| &file.messages)
+            .map(|m| (m.imessage_id.clone().unwrap_or_else(|| m.timestamp.clone()), m))
+            .collect();
+        let mut current: BTreeMap<String, (String, String)> = current
+            .iter()
+            .map(|m| {
+                let timestamp = utc_timestamp(m);
+                (m.imessage_id.clone().unwrap_or_else(|| timestamp.clone()), (timestamp, message_hash(m)))
+            })
+            .collect();
+
+        let describe = |key: &str, timestamp: &str| match key == timestamp {
+            true => timestamp.to_string(),
+            false => format!("{} [id:{}]", timestamp, key),
+        };
+
+        let mut diff = ExportDiff::default();
+        for (key, message) in recorded {
+            match current.remove(&key) {
+                Some((_, hash)) if hash == message.sha256 => diff.unchanged += 1,
+                Some((timestamp, _)) => diff.changed.push(describe(&key, &timestamp)),
+                None => diff.removed.push(describe(&key, &message.timestamp)),
+            }
+        }
+        diff.added = current.iter().map(|(key, (timestamp, _))| describe(key, timestamp)).collect();
+        diff
+    }
+
+    /// Check every listed file in `dir` against its recorded hash, returning a description of
+    /// each file that is missing or changed
+    pub fn verify(&self, dir: &Path) -> Vec<String> {
+        self.files
+            .iter()
+            .filter_map(|file| {
+                let path = dir.join(&file.path);
+                match file_hash(&path) {
+                    Ok(hash) if hash == file.sha256 => None,
+                    Ok(_) => Some(format!("{} has changed since it was generated", file.path)),
+                    Err(_) => Some(format!("{} is missing", file.path)),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for ExportManifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(content: &str) -> Message {
+        Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: Some(format!("guid-{}", content)),
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    #[test]
+    fn test_hashes() {
+        let hash = message_hash(&message("See you at 3"));
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, message_hash(&message("See you at 3")));
+        assert_ne!(hash, message_hash(&message("See you at 4")));
+
+        // Moving text between fields changes the hash
+        let mut moved = message("hi");
+        moved.sender = "Phil hi".to_string();
+        moved.content = String::new();
+        assert_ne!(message_hash(&moved), message_hash(&message("hi")));
+
+        let hashes = vec![message_hash(&message("one")), message_hash(&message("two"))];
+        let reordered = vec![hashes[1].clone(), hashes[0].clone()];
+        assert_ne!(chain_hash(&hashes), chain_hash(&reordered));
+        assert_ne!(chain_hash(&hashes), chain_hash(&hashes[..1]));
+    }
+
+    #[test]
+    fn test_manifest_verify() {
+        let output_dir = std::env::temp_dir().join(format!("txt_history_manifest_test_{}", std::process::id()));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let path = output_dir.join("chunk_1.txt");
+        std::fs::write(&path, "Phil, Jan 20, 2025 12:00:00 PM, one\n\n").unwrap();
+
+        let written = WrittenFile::new(&path.to_string_lossy(), 1, 38, WriteStatus::Created);
+        let mut manifest = ExportManifest::new();
+        manifest.add_file(&written, Some(&[message("one")])).unwrap();
+        manifest.write(&output_dir.to_string_lossy()).unwrap();
+
+        let loaded = ExportManifest::load(&output_dir).unwrap();
+        assert_eq!(loaded.files[0].path, "chunk_1.txt");
+        assert!(loaded.files[0].chain_hash.is_some());
+        assert_eq!(loaded.files[0].totals.map(|t| t.words), Some(1));
+        assert!(loaded.verify(&output_dir).is_empty());
+
+        std::fs::write(&path, "Phil, Jan 20, 2025 12:00:00 PM, edited\n\n").unwrap();
+        assert_eq!(loaded.verify(&output_dir), vec!["chunk_1.txt has changed since it was generated"]);
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_compare_with_database() {
+        let path = std::env::temp_dir().join(format!("txt_history_compare_test_{}.txt", std::process::id()));
+        std::fs::write(&path, "export").unwrap();
+        let written = WrittenFile::new(&path.to_string_lossy(), 3, 6, WriteStatus::Created);
+        let mut manifest = ExportManifest::new();
+        manifest.add_file(&written, Some(&[message("one"), message("two"), message("three")])).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let diff = manifest.compare(&[message("one"), message("two")]);
+        assert_eq!(diff.unchanged, 2);
+        assert_eq!(diff.removed, vec![format!("{} [id:guid-three]", utc_timestamp(&message("three")))]);
+
+        let mut edited = message("two");
+        edited.content = "two, edited".to_string();
+        let diff = manifest.compare(&[message("one"), edited, message("three"), message("four")]);
+        assert_eq!(diff.unchanged, 2);
+        assert_eq!(diff.changed.len(), 1);
+        assert!(diff.changed[0].ends_with("[id:guid-two]"));
+        assert!(diff.removed.is_empty());
+        assert!(diff.added[0].ends_with("[id:guid-four]"));
+        assert_eq!(diff.differences(), 2);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDateTime};
+use whatlang::Lang;
+
+/// One processed message's detected language, for `report languages`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageSample {
+    pub sent_at: NaiveDateTime,
+    pub sender: String,
+    /// ISO 639-3 code, `None` when the message was too short to tell
+    pub language: Option<String>,
+}
+
+/// Language mix and code-switching in one calendar month
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MonthLanguages {
+    /// "YYYY-MM"
+    pub month: String,
+    pub messages: usize,
+    /// Messages per language code
+    pub languages: BTreeMap<String, usize>,
+    /// Times a message was in a different language than the one before it in the same session
+    pub switches: usize,
+}
+
+impl MonthLanguages {
+    /// Messages whose language couldn't be detected
+    pub fn undetected(&self) -> usize {
+        self.messages - self.languages.values().sum::<usize>()
+    }
+
+    /// Share of the detected messages in `language`, from 0.0 to 1.0
+    pub fn share(&self, language: &str) -> f64 {
+        let detected: usize = self.languages.values().sum();
+        match detected {
+            0 => 0.0,
+            detected => *self.languages.get(language).unwrap_or(&0) as f64 / detected as f64,
+        }
+    }
+}
+
+/// How often one sender changes language mid-conversation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SenderSwitches {
+    pub sender: String,
+    /// Messages with a detected language
+    pub detected: usize,
+    /// Switches this sender made away from the previous message's language
+    pub switches: usize,
+    /// Of those, switches away from this sender's own previous message, i.e. mid-thought
+    pub own_switches: usize,
+}
+
+/// Language statistics for `report languages`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LanguageReport {
+    pub months: Vec<MonthLanguages>,
+    pub senders: Vec<SenderSwitches>,
+    /// Every language seen, most used first
+    pub languages: Vec<(String, usize)>,
+    /// (from, to) language pairs and how often the conversation switched between them
+    pub transitions: Vec<((String, String), usize)>,
+    pub sessions: usize,
+    /// Sessions with messages in more than one language
+    pub mixed_sessions: usize,
+}
+
+impl LanguageReport {
+    /// Build from samples sorted by date. Messages more than `session_gap` apart start a new
+    /// session, and a switch is only counted against the last detected message in the same one.
+    pub fn new(samples: &[LanguageSample], session_gap: Duration) -> Self {
+        let mut report = Self::default();
+        let mut months: BTreeMap<String, MonthLanguages> = BTreeMap::new();
+        let mut senders: BTreeMap<&str, SenderSwitches> = BTreeMap::new();
+        let mut languages: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut transitions: BTreeMap<(String, String), usize> = BTreeMap::new();
+
+        // The previous detected message in this session, and the languages used so far
+        let mut previous: Option<&LanguageSample> = None;
+        let mut session_languages: Vec<&str> = Vec::new();
+        let mut last_at: Option<NaiveDateTime> = None;
+
+        for sample in samples {
+            if last_at.map_or(true, |at| sample.sent_at - at > session_gap) {
+                report.sessions += 1;
+                if session_languages.len() > 1 {
+                    report.mixed_sessions += 1;
+                }
+                session_languages.clear();
+                previous = None;
+            }
+            last_at = Some(sample.sent_at);
+
+            let month_key = sample.sent_at.format("%Y-%m").to_string();
+            let month = months.entry(month_key.clone()).or_insert_with(|| MonthLanguages {
+                month: month_key,
+                ..MonthLanguages::default()
+            });
+            month.messages += 1;
+
+            let Some(language) = sample.language.as_deref() else {
+                continue;
+            };
+            *month.languages.entry(language.to_string()).or_default() += 1;
+            *languages.entry(language).or_default() += 1;
+            if !session_languages.contains(&language) {
+                session_languages.push(language);
+            }
+
+            let sender = senders.entry(sample.sender.as_str()).or_insert_with(|| SenderSwitches {
+                sender: sample.sender.clone(),
+                ..SenderSwitches::default()
+            });
+            sender.detected += 1;
+
+            if let Some(before) = previous {
+                let before_language = before.language.as_deref().unwrap_or_default();
+                if before_language != language {
+                    month.switches += 1;
+                    sender.switches += 1;
+                    if before.sender == sample.sender {
+                        sender.own_switches += 1;
+                    }
+                    *transitions
+                        .entry((before_language.to_string(), language.to_string()))
+                        .or_default() += 1;
+                }
+            }
+            previous = Some(sample);
+        }
+        if session_languages.len() > 1 {
+            report.mixed_sessions += 1;
+        }
+
+        report.months = months.into_values().collect();
+        report.senders = senders.into_values().collect();
+        report.languages = languages.into_iter().map(|(code, n)| (code.to_string(), n)).collect();
+        report.languages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        report.transitions = transitions.into_iter().collect();
+        report.transitions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        report
+    }
+
+    pub fn total_switches(&self) -> usize {
+        self.months.iter().map(|month| month.switches).sum()
+    }
+}
+
+/// English name for an ISO 639-3 code, falling back to the code itself
+pub fn language_name(code: &str) -> &str {
+    Lang::from_code(code).map(|lang| lang.eng_name()).unwrap_or(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(sender: &str, at: &str, language: Option<&str>) -> LanguageSample {
+        LanguageSample {
+            sent_at: NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M").unwrap(),
+            sender: sender.to_string(),
+            language: language.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_language_report() {
+        let samples = vec![
+            // A mixed session: Phil switches to French, then back to English mid-burst
+            sample("Jess", "2025-01-20 09:00", Some("eng")),
+            sample("Phil", "2025-01-20 09:05", Some("fra")),
+            sample("Phil", "2025-01-20 09:06", None),
+            sample("Phil", "2025-01-20 09:07", Some("eng")),
+            sample("Jess", "2025-01-20 09:10", Some("eng")),
+            // A new session doesn't count as a switch
+            sample("Phil", "2025-02-02 18:00", Some("fra")),
+            sample("Jess", "2025-02-02 18:02", Some("fra")),
+        ];
+        let report = LanguageReport::new(&samples, Duration::minutes(60));
+
+        assert_eq!(report.sessions, 2);
+        assert_eq!(report.mixed_sessions, 1);
+        assert_eq!(report.total_switches(), 2);
+        assert_eq!(report.languages, vec![("eng".to_string(), 3), ("fra".to_string(), 3)]);
+
+        let january = &report.months[0];
+        assert_eq!((january.month.as_str(), january.messages, january.undetected()), ("2025-01", 5, 1));
+        assert_eq!(january.switches, 2);
+        assert_eq!(january.share("eng"), 0.75);
+        assert_eq!(report.months[1].share("fra"), 1.0);
+        assert_eq!(report.months[1].switches, 0);
+
+        let phil = report.senders.iter().find(|s| s.sender == "Phil").unwrap();
+        assert_eq!((phil.detected, phil.switches, phil.own_switches), (3, 2, 1));
+        assert_eq!(report.transitions.len(), 2);
+
+        assert_eq!(language_name("fra"), "French");
+        assert_eq!(language_name("xyz"), "xyz");
+        assert_eq!(LanguageReport::new(&[], Duration::minutes(60)), LanguageReport::default());
+    }
+}
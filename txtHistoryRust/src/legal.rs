@@ -0,0 +1,584 @@
+use std::io::{Cursor, Write};
+
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::file_writer::{write_file, WritePolicy, WrittenFile};
+use crate::integrity::{chain_hash, message_hash};
+use crate::models::Message;
+
+/// Letter size in points
+const PAGE_WIDTH: f32 = 612.0;
+const PAGE_HEIGHT: f32 = 792.0;
+const MARGIN: f32 = 54.0;
+const FONT_SIZE: f32 = 10.0;
+const LEADING: f32 = 13.0;
+/// Lines of body text between the page header and the bottom margin
+const LINES_PER_PAGE: usize = 50;
+/// Characters per line; Helvetica at 10pt averages well under 5.4pt per character
+const CHARS_PER_LINE: usize = 92;
+
+/// Document type written by the legal preset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegalDocument {
+    Pdf,
+    Docx,
+}
+
+impl LegalDocument {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LegalDocument::Pdf => "pdf",
+            LegalDocument::Docx => "docx",
+        }
+    }
+}
+
+/// Settings for `--preset legal`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegalOptions {
+    pub document: LegalDocument,
+    /// Bates prefix; defaults to the contact's name in capitals
+    pub bates_prefix: Option<String>,
+    /// Number of the first message
+    pub bates_start: usize,
+}
+
+impl LegalOptions {
+    /// Options for `--preset`, if one was given. `legal` is the only preset; it writes a DOCX
+    /// with `--format docx` and a PDF otherwise.
+    pub fn from_preset(
+        preset: Option<&str>,
+        format: &str,
+        bates_prefix: &Option<String>,
+        bates_start: usize,
+    ) -> Result<Option<Self>> {
+        match preset.map(str::to_lowercase).as_deref() {
+            None => Ok(None),
+            Some("legal") => Ok(Some(Self {
+                document: if format.eq_ignore_ascii_case("docx") { LegalDocument::Docx } else { LegalDocument::Pdf },
+                bates_prefix: bates_prefix.clone(),
+                bates_start,
+            })),
+            Some(other) => Err(anyhow::anyhow!("Unknown preset: {}. Available presets: legal", other)),
+        }
+    }
+}
+
+/// One numbered message in the transcript
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    bates: String,
+    time: String,
+    sender: String,
+    content: String,
+}
+
+/// Messages from one calendar day, in local time
+#[derive(Debug, Clone, PartialEq)]
+struct Day {
+    date: String,
+    entries: Vec<Entry>,
+}
+
+/// A transcript laid out for filing: Bates-numbered messages grouped by day, a header naming
+/// the contact and date range, and a cover page with the integrity hash and an index by day
+#[derive(Debug, Clone)]
+pub struct LegalExport {
+    contact: String,
+    header: String,
+    cover: Vec<String>,
+    days: Vec<Day>,
+}
+
+impl LegalExport {
+    pub fn new(messages: &[Message], contact: &str, options: &LegalOptions) -> Self {
+        let prefix = options.bates_prefix.clone().unwrap_or_else(|| bates_prefix(contact));
+        let bates = |i: usize| format!("{}-{:06}", prefix, options.bates_start + i);
+
+        let mut days: Vec<Day> = Vec::new();
+        for (i, message) in messages.iter().enumerate() {
+            let date = message.timestamp.format("%A, %B %-d, %Y").to_string();
+            if days.last().map_or(true, |day| day.date != date) {
+                days.push(Day { date, entries: Vec::new() });
+            }
+            if let Some(day) = days.last_mut() {
+                day.entries.push(Entry {
+                    bates: bates(i),
+                    time: message.timestamp.format("%r").to_string(),
+                    sender: message.sender.clone(),
+                    content: message.content.clone(),
+                });
+            }
+        }
+
+        let span = match (messages.first(), messages.last()) {
+            (Some(first), Some(last)) => format!(
+                "{} to {}",
+                first.timestamp.format("%b %-d, %Y"),
+                last.timestamp.format("%b %-d, %Y")
+            ),
+            _ => "no messages".to_string(),
+        };
+        let hashes: Vec<String> = messages.iter().map(message_hash).collect();
+        let range = match messages.len() {
+            0 => String::new(),
+            n => format!(" ({} to {})", bates(0), bates(n - 1)),
+        };
+
+        let cover = vec![
+            format!("Contact: {}", contact),
+            format!("Date range: {}", span),
+            format!("Messages: {}{}", messages.len(), range),
+            format!("Generated: {}", Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+            "Integrity: SHA-256 rolling hash of every message (sender, UTC time, text) in order:".to_string(),
+            format!("    {}", chain_hash(&hashes)),
+            "Per-message hashes are recorded in manifest.json next to this document.".to_string(),
+        ];
+
+        Self {
+            contact: contact.to_string(),
+            header: format!("Messages with {}, {}", contact, span),
+            cover,
+            days,
+        }
+    }
+
+    /// Render as a PDF with a header and page number on every page. The standard Helvetica
+    /// font only covers Latin-1, so other characters (e.g. emoji) are shown as "?".
+    pub fn to_pdf(&self) -> Vec<u8> {
+        // Lay out the transcript first so the index can point at the page each day starts on
+        let mut body: Vec<(String, bool)> = Vec::new();
+        let mut day_lines = Vec::with_capacity(self.days.len());
+        for day in &self.days {
+            // Start each day on a fresh page if its heading would be the last line of one
+            if body.len() % LINES_PER_PAGE == LINES_PER_PAGE - 1 {
+                body.push((String::new(), false));
+            }
+            day_lines.push(body.len());
+            body.push((day.date.clone(), true));
+            for entry in &day.entries {
+                let line = format!("{}  {}  {}: {}", entry.bates, entry.time, entry.sender, entry.content);
+                body.extend(wrap(&line, CHARS_PER_LINE, 4).into_iter().map(|line| (line, false)));
+            }
+            body.push((String::new(), false));
+        }
+
+        let mut cover = self.cover_lines();
+        cover.push(("INDEX".to_string(), true));
+        let cover_pages = (cover.len() + self.days.len()).div_ceil(LINES_PER_PAGE).max(1);
+        for (day, line) in self.days.iter().zip(&day_lines) {
+            let page = cover_pages + line / LINES_PER_PAGE + 1;
+            cover.push((index_line(day, Some(page)), false));
+        }
+
+        // Cover and index pages, then the transcript on its own pages
+        let mut pages: Vec<&[(String, bool)]> = cover.chunks(LINES_PER_PAGE).collect();
+        pages.extend(body.chunks(LINES_PER_PAGE));
+        render_pdf(&pages, &self.header)
+    }
+
+    /// Render as a Word document with a header and page numbers on every page
+    pub fn to_docx(&self) -> Result<Vec<u8>> {
+        let mut paragraphs: Vec<String> = self
+            .cover_lines()
+            .into_iter()
+            .map(|(line, bold)| docx_paragraph(&line, bold))
+            .collect();
+        paragraphs.push(docx_paragraph("INDEX", true));
+        paragraphs.extend(self.days.iter().map(|day| docx_paragraph(&index_line(day, None), false)));
+        paragraphs.push(r#"<w:p><w:r><w:br w:type="page"/></w:r></w:p>"#.to_string());
+
+        for day in &self.days {
+            paragraphs.push(docx_paragraph(&day.date, true));
+            for entry in &day.entries {
+                paragraphs.push(docx_paragraph(
+                    &format!("{}  {}  {}: {}", entry.bates, entry.time, entry.sender, entry.content),
+                    false,
+                ));
+            }
+        }
+
+        let document = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                r#"<w:document xmlns:w="{}" xmlns:r="{}"><w:body>{}"#,
+                r#"<w:sectPr><w:headerReference w:type="default" r:id="rId1"/>"#,
+                r#"<w:pgSz w:w="12240" w:h="15840"/>"#,
+                r#"<w:pgMar w:top="1080" w:right="1080" w:bottom="1080" w:left="1080" w:header="540" w:footer="540" w:gutter="0"/>"#,
+                r#"</w:sectPr></w:body></w:document>"#
+            ),
+            WORD_NS,
+            RELATIONSHIPS_NS,
+            paragraphs.join("")
+        );
+        let header = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                r#"<w:hdr xmlns:w="{}"><w:p><w:r><w:t xml:space="preserve">{}    Page </w:t></w:r>"#,
+                r#"<w:fldSimple w:instr="PAGE"><w:r><w:t>1</w:t></w:r></w:fldSimple>"#,
+                r#"<w:r><w:t xml:space="preserve"> of </w:t></w:r>"#,
+                r#"<w:fldSimple w:instr="NUMPAGES"><w:r><w:t>1</w:t></w:r></w:fldSimple></w:p></w:hdr>"#
+            ),
+            WORD_NS,
+            xml_escape(&self.header)
+        );
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in [
+            ("[Content_Types].xml", CONTENT_TYPES.to_string()),
+            ("_rels/.rels", PACKAGE_RELS.to_string()),
+            ("word/_rels/document.xml.rels", DOCUMENT_RELS.to_string()),
+            ("word/document.xml", document),
+            ("word/header1.xml", header),
+        ] {
+            zip.start_file(name, SimpleFileOptions::default())?;
+            zip.write_all(contents.as_bytes())?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+
+    fn cover_lines(&self) -> Vec<(String, bool)> {
+        let mut lines = vec![(format!("MESSAGE TRANSCRIPT: {}", self.contact.to_uppercase()), true), (String::new(), false)];
+        for line in &self.cover {
+            lines.extend(wrap(line, CHARS_PER_LINE, 4).into_iter().map(|line| (line, false)));
+        }
+        lines.push((String::new(), false));
+        lines
+    }
+}
+
+/// Write the legal preset's document to `transcript.pdf` or `transcript.docx` in `output_dir`
+pub fn write_legal_export(
+    messages: &[Message],
+    contact: &str,
+    options: &LegalOptions,
+    output_dir: &str,
+    policy: WritePolicy,
+) -> Result<WrittenFile> {
+    if policy == WritePolicy::Append {
+        return Err(anyhow::anyhow!("The legal preset can't append to a document; use --overwrite or --skip-existing"));
+    }
+
+    let export = LegalExport::new(messages, contact, options);
+    let contents = match options.document {
+        LegalDocument::Pdf => export.to_pdf(),
+        LegalDocument::Docx => export.to_docx()?,
+    };
+
+    let file_path = format!("{}/transcript.{}", output_dir, options.document.extension());
+    write_file(&file_path, messages.len(), policy, |writer, _| Ok(writer.write_all(&contents)?))
+}
+
+/// Letters and digits of the contact's name in capitals, e.g. "PHIL" for "Phil"
+fn bates_prefix(contact: &str) -> String {
+    let prefix: String = contact
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    if prefix.is_empty() {
+        "MSG".to_string()
+    } else {
+        prefix
+    }
+}
+
+fn index_line(day: &Day, page: Option<usize>) -> String {
+    let first = day.entries.first().map_or("", |entry| entry.bates.as_str());
+    let last = day.entries.last().map_or("", |entry| entry.bates.as_str());
+    let line = format!("{:<30} {} to {}", day.date, first, last);
+
+    match page {
+        Some(page) => format!("{}   page {}", line, page),
+        None => line,
+    }
+}
+
+/// Word-wrap text to `width` characters, indenting continuation lines by `indent` spaces.
+/// Line breaks and the spacing between words on a line are kept, and words longer than a line
+/// are split.
+fn wrap(text: &str, width: usize, indent: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut len = 0;
+    // Whether the current line has no words yet
+    let mut empty = true;
+
+    for (i, paragraph) in text.split('\n').enumerate() {
+        if i > 0 {
+            lines.push(std::mem::replace(&mut line, " ".repeat(indent)));
+            len = indent;
+            empty = true;
+        }
+
+        for (gap, word) in words_with_gaps(paragraph) {
+            let mut word: Vec<char> = word.chars().collect();
+            while !word.is_empty() {
+                let needed = word.len() + if empty { 0 } else { gap.chars().count() };
+                if len + needed <= width {
+                    if !empty {
+                        line.push_str(gap);
+                    }
+                    line.extend(word.drain(..));
+                    len += needed;
+                    empty = false;
+                } else {
+                    if empty {
+                        // A word longer than a whole line is split across lines
+                        let fits = width.saturating_sub(len).clamp(1, word.len());
+                        line.extend(word.drain(..fits));
+                    }
+                    lines.push(std::mem::replace(&mut line, " ".repeat(indent)));
+                    len = indent;
+                    empty = true;
+                }
+            }
+        }
+    }
+    lines.push(line);
+
+    lines
+}
+
+/// Each word of `text` with the whitespace before it
+fn words_with_gaps(text: &str) -> Vec<(&str, &str)> {
+    let mut words = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        let end = rest[start..].find(char::is_whitespace).map_or(rest.len(), |end| start + end);
+        words.push((&rest[..start], &rest[start..end]));
+        rest = &rest[end..];
+    }
+    words
+}
+
+/// Build a PDF from pages of (text, bold) lines, with `header` and "Page N of M" on each
+fn render_pdf(pages: &[&[(String, bool)]], header: &str) -> Vec<u8> {
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        Vec::new(), // Page tree, filled in once the page objects are numbered
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_vec(),
+    ];
+
+    let mut kids = Vec::with_capacity(pages.len());
+    for (i, lines) in pages.iter().enumerate() {
+        let top = PAGE_HEIGHT - MARGIN;
+        let mut content = format!(
+            "BT /F2 9 Tf {margin} {top} Td ({header}) Tj ET\n\
+             BT /F1 9 Tf {page_x} {top} Td (Page {page} of {pages}) Tj ET\n\
+             0.5 w {margin} {rule} m {right} {rule} l S\n\
+             BT {leading} TL {margin} {body} Td\n",
+            margin = MARGIN,
+            top = top,
+            header = pdf_text(header),
+            page_x = PAGE_WIDTH - MARGIN - 70.0,
+            page = i + 1,
+            pages = pages.len(),
+            rule = top - 6.0,
+            right = PAGE_WIDTH - MARGIN,
+            leading = LEADING,
+            body = top - 6.0 - 2.0 * LEADING,
+        );
+        for (text, bold) in lines.iter() {
+            let font = if *bold { "F2" } else { "F1" };
+            content.push_str(&format!("/{} {} Tf ({}) Tj T*\n", font, FONT_SIZE, pdf_text(text)));
+        }
+        content.push_str("ET\n");
+
+        let page_id = objects.len() + 1;
+        kids.push(format!("{} 0 R", page_id));
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                page_id + 1
+            )
+            .into_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend(content.as_bytes());
+        stream.extend(b"\nendstream");
+        objects.push(stream);
+    }
+    objects[1] = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes();
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes());
+
+    pdf
+}
+
+/// Escape text for a PDF string in WinAnsi encoding, writing non-ASCII bytes as octal escapes
+fn pdf_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        let byte = match c {
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201c}' => 0x93,
+            '\u{201d}' => 0x94,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            '\u{2026}' => 0x85,
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            _ => b'?',
+        };
+        match byte {
+            b'\\' | b'(' | b')' => {
+                escaped.push('\\');
+                escaped.push(byte as char);
+            },
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+const WORD_NS: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const RELATIONSHIPS_NS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+const CONTENT_TYPES: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+    r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+    r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+    r#"<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>"#,
+    r#"<Override PartName="/word/header1.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.header+xml"/>"#,
+    r#"</Types>"#
+);
+
+const PACKAGE_RELS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>"#,
+    r#"</Relationships>"#
+);
+
+const DOCUMENT_RELS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/header" Target="header1.xml"/>"#,
+    r#"</Relationships>"#
+);
+
+/// A paragraph with one run per line of `text`
+fn docx_paragraph(text: &str, bold: bool) -> String {
+    let properties = if bold { "<w:rPr><w:b/></w:rPr>" } else { "" };
+    let runs: Vec<String> = text
+        .split('\n')
+        .map(|line| format!(r#"{}<w:t xml:space="preserve">{}</w:t>"#, properties, xml_escape(line)))
+        .collect();
+
+    format!("<w:p><w:r>{}</w:r></w:p>", runs.join("<w:br/>"))
+}
+
+/// Escape text for XML, dropping control characters XML can't contain
+pub(crate) fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' => escaped.push(c),
+            c if c.is_control() => {},
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn messages() -> Vec<Message> {
+        (0..120)
+            .map(|i| Message {
+                sender: if i % 2 == 0 { "Phil" } else { "Jess" }.to_string(),
+                timestamp: Local.with_ymd_and_hms(2025, 1, 20 + i / 60, 12, 0, i % 60).unwrap(),
+                content: format!("message {} (with parentheses) & \"quotes\"", i),
+                source: None,
+                imessage_id: None,
+                service: None,
+                is_from_me: i % 2 == 1,
+            })
+            .collect()
+    }
+
+    fn options(document: LegalDocument) -> LegalOptions {
+        LegalOptions { document, bates_prefix: None, bates_start: 1 }
+    }
+
+    #[test]
+    fn test_legal_export_numbering() {
+        let export = LegalExport::new(&messages(), "Phil O'Neil", &options(LegalDocument::Pdf));
+        assert_eq!(export.days.len(), 2);
+        assert_eq!(export.days[0].entries[0].bates, "PHILONEIL-000001");
+        assert_eq!(export.days[1].entries.last().unwrap().bates, "PHILONEIL-000120");
+        assert!(export.header.starts_with("Messages with Phil O'Neil, Jan 20, 2025 to Jan 21, 2025"));
+
+        let custom = LegalOptions { bates_prefix: Some("SMITH".to_string()), bates_start: 500, ..options(LegalDocument::Pdf) };
+        let export = LegalExport::new(&messages(), "Phil", &custom);
+        assert_eq!(export.days[0].entries[0].bates, "SMITH-000500");
+
+        assert!(LegalOptions::from_preset(Some("draft"), "txt", &None, 1).is_err());
+        let options = LegalOptions::from_preset(Some("legal"), "docx", &None, 1).unwrap().unwrap();
+        assert_eq!(options.document, LegalDocument::Docx);
+    }
+
+    #[test]
+    fn test_legal_pdf_and_docx() {
+        let export = LegalExport::new(&messages(), "Phil", &options(LegalDocument::Pdf));
+
+        let pdf = String::from_utf8_lossy(&export.to_pdf()).to_string();
+        assert!(pdf.starts_with("%PDF-1.4") && pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("(PHIL-000001  12:00:00 PM  Phil: message 0 \\(with parentheses\\)"));
+        // The index points at the transcript pages
+        assert!(pdf.contains("PHIL-000061 to PHIL-000120   page 3"));
+        assert!(pdf.contains("/Count 4"));
+
+        let docx = export.to_docx().unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(docx)).unwrap();
+        let mut document = String::new();
+        archive.by_name("word/document.xml").unwrap().read_to_string(&mut document).unwrap();
+        assert!(document.contains("PHIL-000120"));
+        assert!(document.contains("&amp; &quot;quotes&quot;"));
+        assert!(archive.by_name("word/header1.xml").is_ok());
+    }
+
+    #[test]
+    fn test_wrap_and_escape() {
+        assert_eq!(wrap("one two three", 8, 2), vec!["one two", "  three"]);
+        assert_eq!(wrap("abcdefghij", 4, 0), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("first\nsecond", 20, 2), vec!["first", "  second"]);
+        // Spacing within a line is kept, but not carried onto the next line
+        assert_eq!(wrap("A-1  12:00  Phil: hi there", 18, 2), vec!["A-1  12:00  Phil:", "  hi there"]);
+        assert_eq!(pdf_text("caf\u{e9} (ok) \u{1f600}"), "caf\\351 \\(ok\\) ?");
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rust_stemmers::{Algorithm, Stemmer};
+use stop_words::{get, LANGUAGE};
+
+/// Language used when a message's language can't be detected or isn't supported
+pub const DEFAULT_LANGUAGE: &str = "eng";
+
+/// Language pipelines kept loaded at once; enough for a conversation that mixes a few languages
+pub const DEFAULT_CACHED_LANGUAGES: usize = 4;
+
+/// Words the stopword lists drop that still say something in a message, by language
+const KEPT_WORDS: &[(&str, &str)] = &[("eng", "test")];
+
+/// Stopword list and stemmer for `DEFAULT_LANGUAGE`
+fn default_resources() -> (&'static str, LANGUAGE, Algorithm) {
+    (DEFAULT_LANGUAGE, LANGUAGE::English, Algorithm::English)
+}
+
+/// Stopword lists and stemmers available, keyed by ISO 639-3 code as `detect_language` returns
+fn resources_for(code: &str) -> Option<(&'static str, LANGUAGE, Algorithm)> {
+    let resources = match code {
+        "eng" => default_resources(),
+        "fra" => ("fra", LANGUAGE::French, Algorithm::French),
+        "deu" => ("deu", LANGUAGE::German, Algorithm::German),
+        "spa" => ("spa", LANGUAGE::Spanish, Algorithm::Spanish),
+        "ita" => ("ita", LANGUAGE::Italian, Algorithm::Italian),
+        "por" => ("por", LANGUAGE::Portuguese, Algorithm::Portuguese),
+        "nld" => ("nld", LANGUAGE::Dutch, Algorithm::Dutch),
+        "swe" => ("swe", LANGUAGE::Swedish, Algorithm::Swedish),
+        "dan" => ("dan", LANGUAGE::Danish, Algorithm::Danish),
+        "nob" => ("nob", LANGUAGE::Norwegian, Algorithm::Norwegian),
+        "fin" => ("fin", LANGUAGE::Finnish, Algorithm::Finnish),
+        "rus" => ("rus", LANGUAGE::Russian, Algorithm::Russian),
+        _ => return None,
+    };
+    Some(resources)
+}
+
+/// Stopwords and stemmer for one language
+pub struct LanguagePipeline {
+    pub language: &'static str,
+    stopwords: HashSet<String>,
+    stemmer: Stemmer,
+}
+
+impl LanguagePipeline {
+    fn load((language, stopwords, algorithm): (&'static str, LANGUAGE, Algorithm)) -> Self {
+        Self {
+            language,
+            stopwords: get(stopwords)
+                .into_iter()
+                .filter(|word| !KEPT_WORDS.contains(&(language, word.as_str())))
+                .collect(),
+            stemmer: Stemmer::create(algorithm),
+        }
+    }
+
+    /// Split cleaned text into words, leaving out stopwords
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        text.split_whitespace()
+            .map(|s| s.to_string())
+            .filter(|s| !s.is_empty() && !self.stopwords.contains(s))
+            .collect()
+    }
+
+    /// Stem each token, joined with spaces
+    pub fn lemmatize(&self, tokens: &[String]) -> String {
+        tokens
+            .iter()
+            .map(|token| self.stemmer.stem(token))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// How the language pipelines were used during a run
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LexiconStats {
+    /// Pipelines built, including ones built again after eviction
+    pub loaded: usize,
+    /// Lookups served by an already-loaded pipeline
+    pub reused: usize,
+    /// Pipelines dropped to make room for another language
+    pub evicted: usize,
+    /// Time spent building pipelines
+    pub load_time: Duration,
+}
+
+impl LexiconStats {
+    pub fn summary(&self) -> String {
+        format!(
+            "Language pipelines: {} loaded in {:.0?}, {} reused, {} evicted",
+            self.loaded, self.load_time, self.reused, self.evicted
+        )
+    }
+}
+
+#[derive(Default)]
+struct Cache {
+    /// Least recently used first
+    pipelines: Vec<Arc<LanguagePipeline>>,
+    stats: LexiconStats,
+}
+
+/// Language pipelines built on first use and kept in a small least-recently-used cache, so
+/// processing a single-language archive never loads the other languages' word lists
+pub struct Lexicon {
+    capacity: usize,
+    cache: Mutex<Cache>,
+}
+
+impl Lexicon {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), cache: Mutex::new(Cache::default()) }
+    }
+
+    /// The pipeline for an ISO 639-3 code, or the default language's when there's no code or
+    /// no resources for it
+    pub fn pipeline(&self, language: Option<&str>) -> Arc<LanguagePipeline> {
+        let resources = language.and_then(resources_for).unwrap_or_else(default_resources);
+        let code = resources.0;
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(i) = cache.pipelines.iter().position(|p| p.language == code) {
+            let pipeline = cache.pipelines.remove(i);
+            cache.pipelines.push(Arc::clone(&pipeline));
+            cache.stats.reused += 1;
+            return pipeline;
+        }
+
+        if cache.pipelines.len() >= self.capacity {
+            cache.pipelines.remove(0);
+            cache.stats.evicted += 1;
+        }
+
+        let started = Instant::now();
+        let pipeline = Arc::new(LanguagePipeline::load(resources));
+        cache.stats.load_time += started.elapsed();
+        cache.stats.loaded += 1;
+        cache.pipelines.push(Arc::clone(&pipeline));
+        pipeline
+    }
+
+    pub fn stats(&self) -> LexiconStats {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner()).stats
+    }
+
+    /// Languages currently loaded, least recently used first
+    pub fn loaded(&self) -> Vec<&'static str> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.pipelines.iter().map(|p| p.language).collect()
+    }
+}
+
+impl Default for Lexicon {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHED_LANGUAGES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipelines_load_lazily() {
+        let lexicon = Lexicon::new(2);
+        assert!(lexicon.loaded().is_empty());
+
+        let english = lexicon.pipeline(Some("eng"));
+        assert_eq!(english.tokenize("this is a test"), vec!["test"]);
+        let french = lexicon.pipeline(Some("fra"));
+        assert_eq!(french.tokenize("la maison et le jardin"), vec!["maison", "jardin"]);
+        assert_eq!(french.lemmatize(&["maisons".to_string()]), "maison");
+
+        // Unknown and missing languages fall back to English without loading anything new
+        assert_eq!(lexicon.pipeline(Some("xyz")).language, "eng");
+        assert_eq!(lexicon.pipeline(None).language, "eng");
+        assert_eq!(lexicon.loaded(), vec!["fra", "eng"]);
+
+        // A third language evicts the least recently used one
+        lexicon.pipeline(Some("deu"));
+        assert_eq!(lexicon.loaded(), vec!["eng", "deu"]);
+
+        let stats = lexicon.stats();
+        assert_eq!((stats.loaded, stats.reused, stats.evicted), (3, 2, 1));
+        assert!(stats.summary().starts_with("Language pipelines: 3 loaded"));
+    }
+}
@@ -1,8 +1,76 @@
+#![allow(non_snake_case)] // The crate is imported as txtHistoryRust
+
+pub mod address_book;
+pub mod aggregate;
+pub mod alerts;
+pub mod async_writer;
+pub mod attachments;
+pub mod audit;
+pub mod batch;
+pub mod cache;
+pub mod canonical;
+pub mod chat_db;
+pub mod chat_db_schema;
+pub mod cleaned_text;
+pub mod columnar;
+pub mod commitments;
+pub mod config;
+pub mod dashboard;
 pub mod db;
+pub mod digest;
+pub mod discovery;
+pub mod entities;
+pub mod epub;
+pub mod error;
+pub mod events;
+pub mod export_options;
+pub mod export_progress;
+pub mod file_writer;
+pub mod flags;
+pub mod graph;
+pub mod heatmap;
+pub mod import_filter;
+pub mod import_warnings;
+pub mod importers;
+pub mod integrity;
+pub mod languages;
+pub mod legal;
+pub mod lexicon;
+#[cfg(feature = "local-summarizer")]
+pub mod local_summarizer;
+pub mod lock;
+pub mod merged;
+pub mod metadata;
+pub mod milestones;
 pub mod models;
 pub mod nlp;
+pub mod nlp_export;
+pub mod notify;
+pub mod pipeline;
+pub mod profiles;
+pub mod pseudonym;
+pub mod redact;
 pub mod repository;
+pub mod retention;
+pub mod schedule;
 pub mod schema;
+pub mod search;
+pub mod sender_map;
+pub mod sessions;
+pub mod snapshots;
+pub mod sources;
+pub mod sql;
+pub mod style;
+pub mod summarizer;
+pub mod summary;
+pub mod templates;
+#[cfg(feature = "test-util")]
+pub mod testing;
+pub mod throttle;
+pub mod time_filter;
+pub mod totals;
+pub mod undo;
+pub mod verbosity;
 
 // Re-export key components for easier access
 pub use db::Database;
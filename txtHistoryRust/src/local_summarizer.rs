@@ -0,0 +1,101 @@
+use std::num::NonZeroU32;
+
+use anyhow::{Context, Result};
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+use llama_cpp_2::sampling::LlamaSampler;
+
+use crate::summarizer::{build_prompt, parse_summaries, Summarizer, SummarizerConfig, SummaryInput, SYSTEM_PROMPT};
+
+/// Tokens allowed for each summary in a batch's reply
+const TOKENS_PER_SUMMARY: usize = 200;
+
+/// Fixed so the same transcript gets the same summary on every run
+const SEED: u32 = 42;
+
+/// Runs a GGUF model in-process with llama.cpp, so no message leaves the machine. Summaries are
+/// stored like the API's, under the model file's name.
+pub struct LocalSummarizer {
+    backend: LlamaBackend,
+    model: LlamaModel,
+    context_size: u32,
+}
+
+impl LocalSummarizer {
+    /// Load the model at `model_path`, which takes a while for large models
+    pub fn new(config: &SummarizerConfig) -> Result<Self> {
+        let path = config
+            .model_path
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Set summarizer.model_path in the config to a GGUF model file"))?;
+
+        let backend = LlamaBackend::init().context("Failed to start llama.cpp")?;
+        let model = LlamaModel::load_from_file(&backend, path, &LlamaModelParams::default())
+            .with_context(|| format!("Failed to load model {}", path.display()))?;
+
+        Ok(Self { backend, model, context_size: config.context_size })
+    }
+
+    /// Run the prompt through the model's chat template and generate up to `max_tokens`
+    fn generate(&self, user_prompt: &str, max_tokens: usize) -> Result<String> {
+        let template = self.model.chat_template(None).context("The model has no chat template")?;
+        let chat = vec![
+            LlamaChatMessage::new("system".to_string(), SYSTEM_PROMPT.to_string())?,
+            LlamaChatMessage::new("user".to_string(), user_prompt.to_string())?,
+        ];
+        let prompt = self.model.apply_chat_template(&template, &chat, true)?;
+        let tokens = self.model.str_to_token(&prompt, AddBos::Always)?;
+
+        let context_size = self.context_size as usize;
+        if tokens.len() + max_tokens > context_size {
+            return Err(anyhow::anyhow!(
+                "The batch needs {} tokens but the context holds {}; lower summarizer.max_batch_chars or \
+                 raise summarizer.context_size",
+                tokens.len() + max_tokens,
+                context_size
+            ));
+        }
+
+        let params = LlamaContextParams::default().with_n_ctx(NonZeroU32::new(self.context_size));
+        let mut context = self
+            .model
+            .new_context(&self.backend, params)
+            .context("Failed to create a llama.cpp context")?;
+
+        let mut batch = LlamaBatch::new(tokens.len().max(1), 1);
+        let last = tokens.len() - 1;
+        for (i, token) in tokens.iter().enumerate() {
+            batch.add(*token, i as i32, &[0], i == last)?;
+        }
+        context.decode(&mut batch).context("llama.cpp failed to read the prompt")?;
+
+        let mut sampler = LlamaSampler::chain_simple([LlamaSampler::temp(0.2), LlamaSampler::dist(SEED)]);
+        let mut position = tokens.len() as i32;
+        let mut output = String::new();
+        for _ in 0..max_tokens {
+            let token = sampler.sample(&context, batch.n_tokens() - 1);
+            sampler.accept(token);
+            if self.model.is_eog_token(token) {
+                break;
+            }
+            output.push_str(&self.model.token_to_str(token, Special::Tokenize)?);
+
+            batch.clear();
+            batch.add(token, position, &[0], true)?;
+            position += 1;
+            context.decode(&mut batch).context("llama.cpp failed while writing summaries")?;
+        }
+
+        Ok(output)
+    }
+}
+
+impl Summarizer for LocalSummarizer {
+    fn summarize(&mut self, title: &str, batch: &[SummaryInput]) -> Result<Vec<(String, String)>> {
+        let reply = self.generate(&build_prompt(title, batch), TOKENS_PER_SUMMARY * batch.len())?;
+        parse_summaries(&reply, batch)
+    }
+}
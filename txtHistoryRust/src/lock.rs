@@ -0,0 +1,154 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use fs2::FileExt;
+
+use crate::error::TxtHistoryError;
+
+/// How a command uses the local database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Read-only commands, any number at a time
+    Shared,
+    /// A command that writes, described for other processes, e.g. "importing"
+    Exclusive(&'static str),
+}
+
+/// Advisory lock on `<database>.lock` so only one txt-history process writes to a database at a
+/// time. Read-only commands share it, so they run alongside each other but not during a write.
+/// The OS releases it when the process exits, even after a crash.
+#[derive(Debug)]
+pub struct DatabaseLock {
+    file: File,
+    mode: LockMode,
+}
+
+impl DatabaseLock {
+    pub fn path(database_url: &str) -> PathBuf {
+        PathBuf::from(format!("{}.lock", database_url))
+    }
+
+    /// Take the lock, failing with `TxtHistoryError::DatabaseBusy` if another process holds it
+    pub fn acquire(database_url: &str, mode: LockMode) -> Result<Self> {
+        let mut file = Self::open(&Self::path(database_url))?;
+        // Called through `FileExt` because newer std versions add inherent `File` locking
+        // methods with different error types
+        let locked = match mode {
+            LockMode::Shared => FileExt::try_lock_shared(&file),
+            LockMode::Exclusive(_) => FileExt::try_lock_exclusive(&file),
+        };
+
+        match locked {
+            Ok(()) => Self::locked(file, mode),
+            Err(e) if is_contended(&e) => Err(busy_error(&mut file, mode).into()),
+            Err(e) => Err(e).context("Failed to lock the database"),
+        }
+    }
+
+    /// Take the lock, waiting for other processes to release it. For `watch`, which shouldn't
+    /// skip scheduled work because a manual command was running.
+    pub fn wait(database_url: &str, mode: LockMode) -> Result<Self> {
+        let file = Self::open(&Self::path(database_url))?;
+        match mode {
+            LockMode::Shared => FileExt::lock_shared(&file),
+            LockMode::Exclusive(_) => FileExt::lock_exclusive(&file),
+        }
+        .context("Failed to lock the database")?;
+
+        Self::locked(file, mode)
+    }
+
+    fn open(path: &Path) -> Result<File> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open lock file {}", path.display()))
+    }
+
+    /// Record who is writing, for the error other processes show
+    fn locked(mut file: File, mode: LockMode) -> Result<Self> {
+        if let LockMode::Exclusive(activity) = mode {
+            file.set_len(0)?;
+            file.rewind()?;
+            writeln!(file, "{} {}", std::process::id(), activity)?;
+            file.flush()?;
+        }
+        Ok(Self { file, mode })
+    }
+}
+
+impl Drop for DatabaseLock {
+    fn drop(&mut self) {
+        // Readers shouldn't see a finished writer as the holder
+        if let LockMode::Exclusive(_) = self.mode {
+            let _ = self.file.set_len(0);
+        }
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+fn is_contended(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::WouldBlock || error.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+}
+
+/// Describe the holder from the lock file. Only writers record themselves, so an empty file
+/// while taking an exclusive lock means readers hold it.
+fn busy_error(file: &mut File, mode: LockMode) -> TxtHistoryError {
+    let mut contents = String::new();
+    let _ = file.read_to_string(&mut contents);
+    let holder = contents
+        .trim()
+        .split_once(' ')
+        .and_then(|(pid, activity)| Some((pid.parse::<u32>().ok()?, activity.to_string())));
+
+    match (holder, mode) {
+        (Some((pid, activity)), _) => TxtHistoryError::DatabaseBusy { activity, pid: Some(pid) },
+        (None, LockMode::Exclusive(_)) => {
+            TxtHistoryError::DatabaseBusy { activity: "reading the database".to_string(), pid: None }
+        },
+        (None, LockMode::Shared) => TxtHistoryError::DatabaseBusy { activity: "writing".to_string(), pid: None },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_writer() {
+        let dir = std::env::temp_dir().join(format!("txt_history_lock_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let database_url = dir.join("messages.db").to_string_lossy().to_string();
+
+        // Readers share the lock, and a writer can't take it while they hold it
+        let reader = DatabaseLock::acquire(&database_url, LockMode::Shared).unwrap();
+        let second_reader = DatabaseLock::acquire(&database_url, LockMode::Shared).unwrap();
+        let error = DatabaseLock::acquire(&database_url, LockMode::Exclusive("importing")).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TxtHistoryError>(),
+            Some(&TxtHistoryError::DatabaseBusy { activity: "reading the database".to_string(), pid: None })
+        );
+        drop((reader, second_reader));
+
+        // While a writer holds it, everyone else is told what it's doing
+        let writer = DatabaseLock::acquire(&database_url, LockMode::Exclusive("importing")).unwrap();
+        let busy = TxtHistoryError::DatabaseBusy { activity: "importing".to_string(), pid: Some(std::process::id()) };
+        let error = DatabaseLock::acquire(&database_url, LockMode::Shared).unwrap_err();
+        assert_eq!(error.downcast_ref::<TxtHistoryError>(), Some(&busy));
+        assert!(busy.to_string().starts_with("Another txt-history process"));
+        let error = DatabaseLock::acquire(&database_url, LockMode::Exclusive("purging")).unwrap_err();
+        assert_eq!(error.downcast_ref::<TxtHistoryError>(), Some(&busy));
+        drop(writer);
+
+        assert!(DatabaseLock::acquire(&database_url, LockMode::Exclusive("purging")).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
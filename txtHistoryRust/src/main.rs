@@ -1,23 +1,166 @@
+mod address_book;
+mod aggregate;
+mod alerts;
+mod async_writer;
+mod attachments;
+mod audit;
+mod batch;
+mod cache;
+mod canonical;
+mod chat_db;
+mod chat_db_schema;
+mod cleaned_text;
+mod columnar;
+mod commitments;
+mod config;
+mod dashboard;
 mod db;
+mod digest;
+mod discovery;
+mod entities;
+mod epub;
+mod error;
+mod events;
+mod export_options;
+mod export_progress;
+mod file_writer;
+mod flags;
+mod graph;
+mod heatmap;
+mod import_filter;
+mod import_warnings;
+mod importers;
+mod integrity;
+mod languages;
+mod legal;
+mod lexicon;
+#[cfg(feature = "local-summarizer")]
+mod local_summarizer;
+mod lock;
+mod merged;
+mod metadata;
+mod milestones;
 mod models;
 mod repository;
+mod retention;
 mod schema;
+mod search;
+mod sender_map;
+mod sessions;
+mod snapshots;
 mod nlp;
+mod nlp_export;
+mod notify;
+mod pipeline;
+mod profiles;
+mod pseudonym;
+mod redact;
+mod schedule;
+mod sources;
+mod sql;
+mod style;
+mod summarizer;
+mod summary;
+mod templates;
+mod throttle;
+mod time_filter;
+mod totals;
+mod undo;
+mod verbosity;
 
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 use anyhow::{Context, Result};
-use chrono::{DateTime, Local, NaiveDateTime};
-use clap::{Parser, Subcommand};
-use imessage_database::util::dirs;
-use repository::IMessageDatabaseRepo;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use clap::{CommandFactory, Parser, Subcommand};
+use repository::{IMessageDatabaseRepo, MessageRepository};
 
+use crate::address_book::{address_book_paths, read_address_book};
+use crate::aggregate::StyleReport;
+use crate::alerts::SentimentMonitor;
+use crate::attachments::{attachments_csv, AttachmentPipeline, OcrProcessor, WhisperProcessor};
+use crate::audit::{compare_months, find_gaps, GapOptions};
+use crate::batch::{run_in_parallel, BatchContact, BatchStatus, BatchSummary};
+use crate::chat_db::{ChatDbSnapshot, RetryPolicy};
+use crate::columnar::{write_parquet_files, NlpLookup};
+use crate::commitments::CommitmentExtractor;
+use crate::cache::MessageCache;
+use crate::canonical::write_canonical_file;
+use crate::config::{CacheConfig, ChatDbConfig, Config, MeConfig, SnapshotConfig};
+use crate::dashboard::{Dashboard, CHART_JS};
 use crate::db::Database;
-use crate::models::{Contact, DateRange, OutputFormat};
-use crate::nlp::NlpProcessor;
+use crate::digest::Digest;
+use crate::entities::{EntityExtractor, EntityFilter, EntityPattern};
+use crate::epub::write_epub;
+use crate::error::{exit_code, TxtHistoryError, EXIT_CODES_HELP};
+use crate::events::EventExtractor;
+use crate::export_options::ExportOptions;
+use crate::export_progress::{export_hash, ExportProgress};
+use crate::file_writer::{
+    format_size, write_file, write_messages_to_files, write_messages_with_progress, write_sender_files,
+    write_templated_files, Chunking, CsvNormalization, CsvOptions, ExportEstimate, ExportPreview, WritePolicy,
+    WriteStatus, WrittenFile,
+};
+use crate::flags::{FlagConfig, LanguageFlagger};
+use crate::graph::InteractionGraph;
+use crate::import_filter::{ImportFilter, SkippedMessages};
+use crate::import_warnings::ImportWarnings;
+use crate::importers::dates::{local_to_utc, DateFormat};
+use crate::importers::{email, export, save_imported_messages, telegram};
+use crate::integrity::{ExportDiff, ExportManifest, ManifestQuery};
+use crate::languages::LanguageReport;
+use crate::legal::{write_legal_export, LegalOptions};
+use crate::lock::{DatabaseLock, LockMode};
+use crate::merged::{merge_conversations, write_merged_file};
+use crate::metadata::{write_metadata_files, MetadataIndex};
+use crate::milestones::ConversationMilestones;
+use crate::models::{
+    CommitmentKind, Contact, ContactDetails, Conversation, DateRange, DbContact, DbMessage, Message, NewContact, NewMessage, NewSummary,
+    OutputFormat, ProcessFilter, ServiceFilter,
+};
+use crate::nlp::{changed_fields, extreme_sentences, NlpProcessor};
+use crate::notify::{Notifier, OperationReport};
+use crate::pipeline::{compress_dir, PipelineConfig, PipelineRun, PipelineStep};
+use crate::profiles::Profile;
+use crate::pseudonym::PseudonymMap;
+use crate::retention::enforce_retention;
+use crate::schedule::{run_scheduled_export, CronSchedule};
+use crate::search::{Highlighter, SearchResult, SearchRow};
+use crate::sender_map::SenderMap;
+use crate::snapshots::SnapshotStore;
+use crate::sources::{resolve_conflicts, SourceFilter};
+use crate::sql::SqlOutput;
+use crate::style::ExportStyle;
+use crate::summarizer::{open_summarizer, plan_batches, split_inputs, to_markdown, SummaryUnit};
+use crate::summary::ConversationSummary;
+use crate::templates::ExportTemplate;
+use crate::throttle::{Throttle, ThrottleConfig};
+use crate::time_filter::{HourRange, TimeFilter};
+use crate::totals::ExportTotals;
+use crate::verbosity::{detail, progress, Verbosity};
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = EXIT_CODES_HELP)]
 struct Cli {
+    /// Profile whose database, cache and output directory to use (e.g. personal, legal)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// chat.db to read instead of ~/Library/Messages/chat.db, e.g. a copy on Linux or Windows
+    #[arg(long, global = true)]
+    chat_db: Option<PathBuf>,
+
+    /// Print each chunk's date range, size and write time during exports
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Print only the final summary line of an export
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -27,8 +170,12 @@ enum Commands {
     /// Import messages from iMessage database
     Import {
         /// Name of the contact
-        #[arg(short, long)]
-        name: String,
+        #[arg(short, long, required_unless_present = "chat_id")]
+        name: Option<String>,
+
+        /// Import a chat by its chat.db identifier instead (see discover-chats), e.g. a group chat
+        #[arg(long, conflicts_with = "name")]
+        chat_id: Option<String>,
 
         /// Start date for message range (YYYY-MM-DD)
         #[arg(short, long)]
@@ -38,7 +185,7 @@ enum Commands {
         #[arg(short, long)]
         end_date: Option<String>,
 
-        /// Output format (txt or csv)
+        /// Output format (txt, csv, json or parquet)
         #[arg(short, long, default_value = "txt")]
         format: String,
 
@@ -50,15 +197,124 @@ enum Commands {
         #[arg(short, long)]
         lines: Option<usize>,
 
-        /// Output directory
-        #[arg(short, long, default_value = "./output")]
-        output_dir: String,
+        /// Approximate number of LLM tokens per chunk (about 4 characters per token)
+        #[arg(long)]
+        tokens_per_chunk: Option<usize>,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Only include messages sent over this service (imessage, sms or all)
+        #[arg(long, default_value = "all")]
+        service: String,
+
+        /// Update text, attachments and service of messages that were already imported
+        #[arg(long)]
+        refresh: bool,
+
+        /// Skip text messages shorter than this many characters (overrides import_filter.min_length)
+        #[arg(long)]
+        min_length: Option<usize>,
+
+        /// Skip text messages made only of emoji
+        #[arg(long)]
+        skip_emoji_only: bool,
+
+        /// Skip one-time passcodes and verification codes
+        #[arg(long)]
+        skip_codes: bool,
+
+        /// Skip text messages matching this regular expression (repeatable)
+        #[arg(long)]
+        skip_pattern: Vec<String>,
+
+        /// Read from a temporary copy of chat.db so Messages.app can't lock it mid-import
+        #[arg(long)]
+        snapshot: bool,
+
+        /// Save at most this many messages per second, to keep background imports light
+        #[arg(long)]
+        throttle: Option<u32>,
+
+        /// Milliseconds to pause after every 500 messages saved
+        #[arg(long)]
+        throttle_pause: Option<u64>,
+
+        /// Export template: a built-in name (plain, bubbles, court-transcript) or a template file
+        #[arg(long)]
+        template: Option<String>,
+
+        /// CSV delimiter: comma, tab, semicolon, pipe or a single character
+        #[arg(long, default_value = "comma")]
+        csv_delimiter: String,
+
+        /// CSV columns in order (imessage_id, sender, timestamp, content, service, source, is_from_me)
+        #[arg(long, default_value = "sender,timestamp,content")]
+        csv_columns: String,
+
+        /// When to quote CSV fields: necessary, always or never
+        #[arg(long, default_value = "necessary")]
+        csv_quote: String,
+
+        /// Leave out the CSV header row
+        #[arg(long)]
+        no_csv_header: bool,
+
+        /// Simplify CSV text for spreadsheet tools: none, punctuation (ASCII quotes and dashes)
+        /// or bmp (punctuation, plus emoji turned into emoticons or dropped)
+        #[arg(long, default_value = "none")]
+        csv_normalize: String,
+
+        /// Sender side and color as NAME=SIDE[:COLOR], e.g. "Jess=right:#0b84fe" (repeatable)
+        #[arg(long)]
+        sender_style: Vec<String>,
+
+        /// Prefix TXT lines from right-side senders with "> " (or their configured prefix)
+        #[arg(long)]
+        txt_prefixes: bool,
+
+        /// End each TXT file with its message, word and character counts and reading time
+        #[arg(long)]
+        txt_totals: bool,
+
+        /// Put each message's ID in TXT lines and templates, and first in CSV rows
+        #[arg(long)]
+        include_ids: bool,
+
+        /// Also write each sender's messages to their own files, e.g. phil.txt and jess.txt
+        #[arg(long)]
+        split_by_sender: bool,
+
+        /// Replace existing export files once the new ones are written (the default)
+        #[arg(long, conflicts_with_all = ["skip_existing", "append"])]
+        overwrite: bool,
+
+        /// Leave export files that already exist alone
+        #[arg(long, conflicts_with = "append")]
+        skip_existing: bool,
+
+        /// Add messages to the end of existing export files (not supported for Parquet)
+        #[arg(long)]
+        append: bool,
+
+        /// Write manifest.json with SHA-256 hashes of every message and file, for tamper-evidence
+        #[arg(long)]
+        hashes: bool,
+
+        /// Write files without showing the preview and asking for confirmation
+        #[arg(long)]
+        yes: bool,
     },
     /// Query messages from the database
     Query {
         /// Name of the contact
-        #[arg(short, long)]
-        name: String,
+        #[arg(short, long, required_unless_present = "chat_id")]
+        name: Option<String>,
+
+        /// Export a chat imported with `import --chat-id` instead
+        #[arg(long, conflicts_with = "name")]
+        chat_id: Option<String>,
 
         /// Start date for message range (YYYY-MM-DD)
         #[arg(short, long)]
@@ -68,10 +324,28 @@ enum Commands {
         #[arg(short, long)]
         end_date: Option<String>,
 
-        /// Output format (txt or csv)
+        /// Output format (txt, csv, json, parquet or epub; pdf or docx with --preset legal)
         #[arg(short, long, default_value = "txt")]
         format: String,
 
+        /// Export preset: "legal" writes one Bates-numbered PDF (or DOCX with --format docx)
+        /// with page headers, an index page and a hash manifest
+        #[arg(long)]
+        preset: Option<String>,
+
+        /// Prefix for Bates numbers with --preset legal (defaults to the contact's name in capitals)
+        #[arg(long)]
+        bates_prefix: Option<String>,
+
+        /// Bates number of the first message with --preset legal
+        #[arg(long, default_value_t = 1)]
+        bates_start: usize,
+
+        /// Join NLP results from this processing version into Parquet exports, or into the
+        /// metadata files with --with-metadata
+        #[arg(long)]
+        nlp_version: Option<String>,
+
         /// Size of each chunk in MB
         #[arg(short, long)]
         size: Option<f64>,
@@ -80,15 +354,145 @@ enum Commands {
         #[arg(short, long)]
         lines: Option<usize>,
 
-        /// Output directory
-        #[arg(short, long, default_value = "./output")]
-        output_dir: String,
+        /// Approximate number of LLM tokens per chunk (about 4 characters per token)
+        #[arg(long)]
+        tokens_per_chunk: Option<usize>,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Only include messages sent over this service (imessage, sms or all)
+        #[arg(long, default_value = "all")]
+        service: String,
+
+        /// Sources to include in priority order, e.g. "imessage,whatsapp,email" (or "all")
+        #[arg(long, default_value = "all")]
+        sources: String,
+
+        /// Only include messages sent in this local time window, e.g. "22:00-06:00" (may run
+        /// past midnight)
+        #[arg(long, value_name = "HH:MM-HH:MM")]
+        hours: Option<String>,
+
+        /// Only include messages sent Monday to Friday
+        #[arg(long, conflicts_with = "weekends")]
+        weekdays: bool,
+
+        /// Only include messages sent on Saturday or Sunday
+        #[arg(long)]
+        weekends: bool,
+
+        /// Export template: a built-in name (plain, bubbles, court-transcript) or a template file
+        #[arg(long)]
+        template: Option<String>,
+
+        /// CSV delimiter: comma, tab, semicolon, pipe or a single character
+        #[arg(long, default_value = "comma")]
+        csv_delimiter: String,
+
+        /// CSV columns in order (imessage_id, sender, timestamp, content, service, source, is_from_me)
+        #[arg(long, default_value = "sender,timestamp,content")]
+        csv_columns: String,
+
+        /// When to quote CSV fields: necessary, always or never
+        #[arg(long, default_value = "necessary")]
+        csv_quote: String,
+
+        /// Leave out the CSV header row
+        #[arg(long)]
+        no_csv_header: bool,
+
+        /// Simplify CSV text for spreadsheet tools: none, punctuation (ASCII quotes and dashes)
+        /// or bmp (punctuation, plus emoji turned into emoticons or dropped)
+        #[arg(long, default_value = "none")]
+        csv_normalize: String,
+
+        /// Sender side and color as NAME=SIDE[:COLOR], e.g. "Jess=right:#0b84fe" (repeatable)
+        #[arg(long)]
+        sender_style: Vec<String>,
+
+        /// Prefix TXT lines from right-side senders with "> " (or their configured prefix)
+        #[arg(long)]
+        txt_prefixes: bool,
+
+        /// End each TXT file with its message, word and character counts and reading time
+        #[arg(long)]
+        txt_totals: bool,
+
+        /// Put each message's ID in TXT lines and templates, and first in CSV rows
+        #[arg(long)]
+        include_ids: bool,
+
+        /// Also write each sender's messages to their own files, e.g. phil.txt and jess.txt
+        #[arg(long)]
+        split_by_sender: bool,
+
+        /// Replace existing export files once the new ones are written (the default)
+        #[arg(long, conflicts_with_all = ["skip_existing", "append"])]
+        overwrite: bool,
+
+        /// Leave export files that already exist alone
+        #[arg(long, conflicts_with = "append")]
+        skip_existing: bool,
+
+        /// Add messages to the end of existing export files (not supported for Parquet)
+        #[arg(long)]
+        append: bool,
+
+        /// Write manifest.json with SHA-256 hashes of every message and file, for tamper-evidence
+        #[arg(long)]
+        hashes: bool,
+
+        /// Write files without showing the preview and asking for confirmation
+        #[arg(long)]
+        yes: bool,
+
+        /// Continue an interrupted export, keeping chunks it already wrote whose checksums still match
+        #[arg(long, conflicts_with = "append")]
+        resume_export: Option<i32>,
+
+        /// Print message counts per sender, first and last dates and who starts conversations
+        /// instead of writing files
+        #[arg(long, conflicts_with_all = ["preset", "resume_export"])]
+        summary: bool,
+
+        /// Export the whole history, from the first message to the last, into a YYYY/ folder
+        /// per year
+        #[arg(long, conflicts_with_all = ["start_date", "end_date", "resume_export", "summary"])]
+        all: bool,
+
+        /// Write one conversation.txt in a stable layout (UTC timestamps, fixed order, no chunks)
+        /// for keeping exports in git and diffing them
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "template", "size", "lines", "tokens_per_chunk", "split_by_sender", "append",
+                "resume_export", "nlp_version", "preset", "summary"
+            ]
+        )]
+        canonical: bool,
+
+        /// Replace names with pseudonyms and phone numbers and emails with salted hashes, using
+        /// and updating the mapping file at this path so pseudonyms stay the same across exports
+        #[arg(long, value_name = "MAP_FILE", conflicts_with_all = ["preset", "summary"])]
+        anonymize: Option<PathBuf>,
+
+        /// Also write chunk_N.meta.jsonl next to each TXT chunk, with a JSON line per message
+        /// giving the line it starts on, its IDs, service, read time and NLP scores
+        #[arg(long, conflicts_with_all = ["template", "preset", "canonical", "summary", "anonymize"])]
+        with_metadata: bool,
     },
-    /// Export conversation with a specific person
-    ExportByPerson {
-        /// Name of the person
-        #[arg(short, long, default_value = "Phil")]
-        name: String,
+    /// Show how many chunk files a `query` export would write and how large they'd be, without
+    /// writing anything
+    Estimate {
+        /// Name of the contact
+        #[arg(short, long, required_unless_present = "chat_id")]
+        name: Option<String>,
+
+        /// Estimate a chat imported with `import --chat-id` instead
+        #[arg(long, conflicts_with = "name")]
+        chat_id: Option<String>,
 
         /// Start date for message range (YYYY-MM-DD)
         #[arg(short, long)]
@@ -98,28 +502,44 @@ enum Commands {
         #[arg(short, long)]
         end_date: Option<String>,
 
+        /// Output format (txt, csv, json or parquet)
+        #[arg(short, long, default_value = "txt")]
+        format: String,
+
         /// Size of each chunk in MB
-        #[arg(short, long)]
+        #[arg(long)]
         size: Option<f64>,
 
         /// Number of lines per chunk
         #[arg(short, long)]
         lines: Option<usize>,
 
-        /// Output directory
-        #[arg(short, long, default_value = "./output")]
-        output_dir: String,
-    },
-    /// Process messages with NLP
-    Process {
-        /// Processing version identifier
-        #[arg(short, long, default_value = "v1.0")]
-        version: String,
+        /// Approximate number of LLM tokens per chunk (about 4 characters per token)
+        #[arg(long)]
+        tokens_per_chunk: Option<usize>,
 
-        /// Name of the contact (optional, process all if not specified)
-        #[arg(short, long)]
-        name: Option<String>,
+        /// Only include messages sent over this service (imessage, sms or all)
+        #[arg(long, default_value = "all")]
+        service: String,
+
+        /// Sources to include in priority order, e.g. "imessage,whatsapp,email" (or "all")
+        #[arg(long, default_value = "all")]
+        sources: String,
 
+        /// Only include messages sent in this local time window, e.g. "22:00-06:00"
+        #[arg(long, value_name = "HH:MM-HH:MM")]
+        hours: Option<String>,
+
+        /// Only include messages sent Monday to Friday
+        #[arg(long, conflicts_with = "weekends")]
+        weekdays: bool,
+
+        /// Only include messages sent on Saturday or Sunday
+        #[arg(long)]
+        weekends: bool,
+    },
+    /// Export every message I sent, across all contacts, as one chronological export
+    ExportMyMessages {
         /// Start date for message range (YYYY-MM-DD)
         #[arg(short, long)]
         start_date: Option<String>,
@@ -128,463 +548,5434 @@ enum Commands {
         #[arg(short, long)]
         end_date: Option<String>,
 
-        /// Batch size for processing
-        #[arg(short, long, default_value = "100")]
-        batch_size: usize,
+        /// Output format (txt, csv, json or parquet)
+        #[arg(short, long, default_value = "txt")]
+        format: String,
 
-        /// Show processing statistics
+        /// Size of each chunk in MB
+        #[arg(long)]
+        size: Option<f64>,
+
+        /// Number of lines per chunk
         #[arg(short, long)]
-        stats: bool,
+        lines: Option<usize>,
+
+        /// Approximate number of LLM tokens per chunk (about 4 characters per token)
+        #[arg(long)]
+        tokens_per_chunk: Option<usize>,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Only include messages sent over this service (imessage, sms or all)
+        #[arg(long, default_value = "all")]
+        service: String,
+
+        /// Sources to include in priority order, e.g. "imessage,whatsapp,email" (or "all")
+        #[arg(long, default_value = "all")]
+        sources: String,
+
+        /// Only include messages sent in this local time window, e.g. "22:00-06:00" (may run
+        /// past midnight)
+        #[arg(long, value_name = "HH:MM-HH:MM")]
+        hours: Option<String>,
+
+        /// Only include messages sent Monday to Friday
+        #[arg(long, conflicts_with = "weekends")]
+        weekdays: bool,
+
+        /// Only include messages sent on Saturday or Sunday
+        #[arg(long)]
+        weekends: bool,
+
+        /// Replace existing export files once the new ones are written (the default)
+        #[arg(long, conflicts_with_all = ["skip_existing", "append"])]
+        overwrite: bool,
+
+        /// Leave export files that already exist alone
+        #[arg(long, conflicts_with = "append")]
+        skip_existing: bool,
+
+        /// Add messages to the end of existing export files (not supported for Parquet)
+        #[arg(long)]
+        append: bool,
+
+        /// Write manifest.json with SHA-256 hashes of every message and file, for tamper-evidence
+        #[arg(long)]
+        hashes: bool,
+
+        /// Write files without showing the preview and asking for confirmation
+        #[arg(long)]
+        yes: bool,
     },
-}
+    /// Export several contacts' conversations at once, each into its own subdirectory
+    ExportAll {
+        /// Contacts to export, comma separated, e.g. "Phil,Robert,Rhonda"
+        #[arg(long, value_delimiter = ',', required = true)]
+        contacts: Vec<String>,
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Parse command line arguments
-    let cli = Cli::parse();
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
 
-    // Initialize database
-    let db = db::establish_connection()?;
-    db.initialize()?;
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
 
-    // Process command
-    match &cli.command {
-        Commands::Import {
-            name,
-            start_date,
-            end_date,
-            format,
-            size,
-            lines,
-            output_dir,
-        } => {
-            import_messages(name, start_date, end_date, format, *size, *lines, output_dir)
-        }
-        Commands::Query {
-            name,
-            start_date,
-            end_date,
-            format,
-            size,
-            lines,
-            output_dir,
-        } => {
-            query_messages(&db, name, start_date, end_date, format, *size, *lines, output_dir)
-        }
-        Commands::ExportByPerson {
-            name,
-            start_date,
-            end_date,
-            size,
-            lines,
-            output_dir,
-        } => {
-            export_conversation_by_person(&db, name, start_date, end_date, *size, *lines, output_dir).await?
-        }
-        Commands::Process {
-            version,
-            name,
-            start_date,
-            end_date,
-            batch_size,
-            stats,
-        } => {
-            process_messages(&db, version, name, start_date, end_date, *batch_size, *stats)
-        }
-    }
-}
+        /// Output format (txt, csv, json or parquet)
+        #[arg(short, long, default_value = "txt")]
+        format: String,
 
-/// Import messages from iMessage database
-fn import_messages(
-    name: &str,
-    start_date: &Option<String>,
-    end_date: &Option<String>,
-    format: &str,
-    size: Option<f64>,
-    lines: Option<usize>,
-    output_dir: &str,
-) -> Result<()> {
-    // Get iMessage database path
-    let chat_db_path = dirs::get_imessage_chat_db_path()
-        .context("Failed to locate iMessage database")?;
+        /// Size of each chunk in MB
+        #[arg(long)]
+        size: Option<f64>,
 
-    println!("Using iMessage database at: {}", chat_db_path.display());
+        /// Number of lines per chunk
+        #[arg(short, long)]
+        lines: Option<usize>,
 
-    // Create repository
-    let repo = IMessageDatabaseRepo::new(chat_db_path)?;
+        /// Approximate number of LLM tokens per chunk (about 4 characters per token)
+        #[arg(long)]
+        tokens_per_chunk: Option<usize>,
 
-    // Get contact info
-    let contact = get_contact_info(name)?;
-    println!("Looking up messages for: {}", contact.name);
+        /// Output directory; each contact gets a subdirectory named after them (defaults to the
+        /// profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
 
-    // Parse date range
-    let date_range = parse_date_range(start_date, end_date)?;
-    if let Some(start) = &date_range.start {
-        println!("Start date: {}", start.format("%Y-%m-%d"));
-    }
-    if let Some(end) = &date_range.end {
-        println!("End date: {}", end.format("%Y-%m-%d"));
-    }
+        /// Contacts exported at the same time
+        #[arg(long, default_value_t = 2)]
+        jobs: usize,
 
-    // Fetch messages
-    println!("Fetching messages...");
-    let messages = repo.fetch_messages(&contact, &date_range).await?;
-    println!("Found {} messages", messages.len());
+        /// Replace existing export files once the new ones are written (the default)
+        #[arg(long, conflicts_with_all = ["skip_existing", "append"])]
+        overwrite: bool,
 
-    // Determine output format
-    let output_format = match format.to_lowercase().as_str() {
-        "csv" => OutputFormat::Csv,
-        _ => OutputFormat::Txt,
-    };
+        /// Leave export files that already exist alone
+        #[arg(long, conflicts_with = "append")]
+        skip_existing: bool,
 
-    // Create output directory if it doesn't exist
-    std::fs::create_dir_all(output_dir)?;
+        /// Add messages to the end of existing export files (not supported for Parquet)
+        #[arg(long)]
+        append: bool,
 
-    // Write messages to files
-    write_messages_to_files(&messages, output_format, size, lines, output_dir)?;
+        /// Write manifest.json with SHA-256 hashes into each contact's subdirectory
+        #[arg(long)]
+        hashes: bool,
 
-    Ok(())
-}
+        /// Write files without asking for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Export several one-on-one conversations as one chronological transcript, each message
+    /// labeled with its conversation
+    ExportMerged {
+        /// Contacts whose conversations are merged, comma separated, e.g. "Phil,Rhonda"
+        #[arg(long, value_delimiter = ',', required = true)]
+        contacts: Vec<String>,
 
-/// Query messages from the database
-fn query_messages(
-    db: &Database,
-    name: &str,
-    start_date: &Option<String>,
-    end_date: &Option<String>,
-    format: &str,
-    size: Option<f64>,
-    lines: Option<usize>,
-    output_dir: &str,
-) -> Result<()> {
-    // Get contact
-    let contact_info = match db.get_contact(name)? {
-        Some(contact) => contact,
-        None => return Err(anyhow::anyhow!("Contact not found: {}", name)),
-    };
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
 
-    println!("Looking up messages for: {}", contact_info.name);
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
 
-    // Parse date range
-    let date_range = parse_date_range(start_date, end_date)?;
-    let start_naive = date_range.start.map(|dt| dt.naive_local());
-    let end_naive = date_range.end.map(|dt| dt.naive_local());
+        /// Output format (txt, csv or json)
+        #[arg(short, long, default_value = "txt")]
+        format: String,
 
-    if let Some(start) = &date_range.start {
-        println!("Start date: {}", start.format("%Y-%m-%d"));
-    }
-    if let Some(end) = &date_range.end {
-        println!("End date: {}", end.format("%Y-%m-%d"));
-    }
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
 
-    // Fetch messages
-    println!("Fetching messages...");
-    let db_messages = db.get_messages(&contact_info.name, start_naive, end_naive)?;
-    println!("Found {} messages", db_messages.len());
+        /// Replace an existing merged file once the new one is written (the default)
+        #[arg(long, conflicts_with = "skip_existing")]
+        overwrite: bool,
 
-    // Convert to the original Message format
-    let messages: Vec<_> = db_messages.into_iter().map(|m| m.to_message()).collect();
+        /// Leave an existing merged file alone
+        #[arg(long)]
+        skip_existing: bool,
 
-    // Determine output format
-    let output_format = match format.to_lowercase().as_str() {
-        "csv" => OutputFormat::Csv,
-        _ => OutputFormat::Txt,
-    };
+        /// Write manifest.json with SHA-256 hashes of every message and the file, for tamper-evidence
+        #[arg(long)]
+        hashes: bool,
 
-    // Create output directory if it doesn't exist
-    std::fs::create_dir_all(output_dir)?;
+        /// Write the file without asking for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Export the messages around one message, e.g. the exchange a quoted text came from
+    Context {
+        /// Target message: its database ID (e.g. from `sql "SELECT id, text FROM messages WHERE ..."`)
+        /// or the ID shown in exports written with --include-ids
+        #[arg(long, required_unless_present = "at", conflicts_with = "at")]
+        message_id: Option<String>,
 
-    // Write messages to files
-    write_messages_to_files(&messages, output_format, size, lines, output_dir)?;
+        /// Target the message sent closest to this local time (YYYY-MM-DD HH:MM[:SS]) instead
+        #[arg(long)]
+        at: Option<String>,
 
-    Ok(())
-}
+        /// Conversation to search: a contact (required with --at unless --chat-id is given)
+        #[arg(short, long)]
+        name: Option<String>,
 
-/// Export conversation with a specific person
-async fn export_conversation_by_person(
-    db: &Database,
-    name: &str,
-    start_date: &Option<String>,
-    end_date: &Option<String>,
+        /// Conversation to search: a chat imported with `import --chat-id`
+        #[arg(long, conflicts_with = "name")]
+        chat_id: Option<String>,
+
+        /// Messages to include before the target
+        #[arg(long, default_value_t = 50)]
+        before: usize,
+
+        /// Messages to include after the target
+        #[arg(long, default_value_t = 50)]
+        after: usize,
+
+        /// Output format (txt, csv, json or parquet)
+        #[arg(short, long, default_value = "txt")]
+        format: String,
+
+        /// Output directory (defaults to the profile's output directory); files go in context_<ID>
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Write files without showing the preview and asking for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Find messages containing some text, ignoring case, with the matches marked [[like this]]
+    Search {
+        /// Text to find
+        text: String,
+
+        /// Only the conversation with this contact
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Only a chat imported with `import --chat-id`
+        #[arg(long, conflicts_with = "name")]
+        chat_id: Option<String>,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Messages to include before and after each match
+        #[arg(long, default_value_t = 0)]
+        context: usize,
+
+        /// Write the results to this CSV file instead of printing them, e.g. results.csv
+        #[arg(long)]
+        export: Option<String>,
+    },
+    /// Show first and last messages, anniversaries, streaks, silences and words exchanged, per
+    /// contact and overall
+    Stats {
+        /// Only the conversation with this contact
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Only include messages sent in this local time window, e.g. "22:00-06:00" (may run
+        /// past midnight)
+        #[arg(long, value_name = "HH:MM-HH:MM")]
+        hours: Option<String>,
+
+        /// Only include messages sent Monday to Friday
+        #[arg(long, conflicts_with = "weekends")]
+        weekdays: bool,
+
+        /// Only include messages sent on Saturday or Sunday
+        #[arg(long)]
+        weekends: bool,
+    },
+    /// Check that an export's files still match the hashes in its manifest.json and, for `query`
+    /// exports, that the database still holds the same messages. Nothing is written.
+    #[command(visible_alias = "verify")]
+    VerifyExport {
+        /// Export directory containing manifest.json
+        #[arg(required_unless_present = "export_dir", conflicts_with = "export_dir")]
+        dir: Option<String>,
+
+        /// Export directory containing manifest.json, instead of giving it as an argument
+        #[arg(long)]
+        export_dir: Option<String>,
+
+        /// Only check the files, not the database
+        #[arg(long)]
+        files_only: bool,
+    },
+    /// Export conversation with a specific person
+    ExportByPerson {
+        /// Name of the person
+        #[arg(short, long, default_value = "Phil")]
+        name: String,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Size of each chunk in MB
+        #[arg(short, long)]
+        size: Option<f64>,
+
+        /// Number of lines per chunk
+        #[arg(short, long)]
+        lines: Option<usize>,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Only include messages sent over this service (imessage, sms or all)
+        #[arg(long, default_value = "all")]
+        service: String,
+
+        /// Sources to include in priority order, e.g. "imessage,whatsapp,email" (or "all")
+        #[arg(long, default_value = "all")]
+        sources: String,
+
+        /// Read from a temporary copy of chat.db so Messages.app can't lock it mid-export
+        #[arg(long)]
+        snapshot: bool,
+    },
+    /// Process messages with NLP
+    #[command(args_conflicts_with_subcommands = true)]
+    Process {
+        /// Manage stored processing versions instead of processing messages
+        #[command(subcommand)]
+        action: Option<ProcessAction>,
+
+        /// Processing version identifier
+        #[arg(long, default_value = "v1.0")]
+        version: String,
+
+        /// Name of the contact (optional, process all if not specified)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Batch size for processing
+        #[arg(short, long, default_value = "100")]
+        batch_size: usize,
+
+        /// Process at most this many messages, oldest first
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Only process my messages
+        #[arg(long, conflicts_with = "only_contact")]
+        only_from_me: bool,
+
+        /// Only process messages from the other side of the conversation
+        #[arg(long)]
+        only_contact: bool,
+
+        /// Only process messages with at least this many characters
+        #[arg(long)]
+        min_length: Option<usize>,
+
+        /// Only process messages sent over this service (imessage, sms or all)
+        #[arg(long, default_value = "all")]
+        service: String,
+
+        /// Also flag profanity and abusive language using the configured word lists
+        #[arg(long)]
+        flag_language: bool,
+
+        /// Process at most this many messages per second, to keep background runs light
+        #[arg(long)]
+        throttle: Option<u32>,
+
+        /// Milliseconds to pause between batches
+        #[arg(long)]
+        throttle_pause: Option<u64>,
+
+        /// Show processing statistics
+        #[arg(short, long)]
+        stats: bool,
+    },
+    /// Export messages joined with their NLP results (tokens, entities, sentiment, language) as
+    /// a flat CSV or JSON file for analysis
+    ExportNlp {
+        /// Processing version to export
+        #[arg(long, default_value = "v1.0")]
+        version: String,
+
+        /// Only the conversation with this contact
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Output format (csv or json)
+        #[arg(short, long, default_value = "csv")]
+        format: String,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    /// Detect questions, commitments and agreements in conversations
+    ExtractCommitments {
+        /// Contact name (defaults to every contact)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Hours the other side has to reply before a question counts as open
+        #[arg(long, default_value = "24")]
+        answer_window: i64,
+    },
+    /// Detect dates and plans in conversations ("Friday at 3", "dentist on the 12th")
+    ExtractEvents {
+        /// Contact name (defaults to every contact)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+    },
+    /// Write abstractive summaries of each day or session with the configured summarizer
+    /// endpoint, caching them in the database
+    Summarize {
+        /// Name of the contact
+        #[arg(short, long)]
+        name: String,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Summarize each day or each session (day or session)
+        #[arg(long, default_value = "day")]
+        by: String,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Most days or sessions to send in this run; the rest are left for the next one
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Send days or sessions again even when they have a cached summary
+        #[arg(long)]
+        refresh: bool,
+
+        /// Show how many days or sessions and requests would be sent, without sending any
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Summarize processed messages
+    Report {
+        #[command(subcommand)]
+        report: ReportKind,
+    },
+    /// Check imported conversations for missing data
+    Audit {
+        #[command(subcommand)]
+        audit: AuditKind,
+    },
+    /// Inspect attachments without copying any files
+    Attachments {
+        #[command(subcommand)]
+        action: AttachmentsAction,
+    },
+    /// Extract text from audio, video and image attachments
+    Transcribe {
+        /// Path to the whisper.cpp model used for audio/video (skipped if not set)
+        #[arg(long)]
+        whisper_model: Option<String>,
+
+        /// whisper.cpp binary
+        #[arg(long, default_value = "whisper-cli")]
+        whisper_bin: String,
+
+        /// tesseract binary used for image OCR
+        #[arg(long, default_value = "tesseract")]
+        tesseract_bin: String,
+
+        /// Skip OCR of image attachments
+        #[arg(long)]
+        no_ocr: bool,
+    },
+    /// Import email threads from an mbox file, an .eml file or a directory of .eml files
+    ImportEmail {
+        /// Path to the mbox file, .eml file or directory
+        #[arg(short, long)]
+        path: String,
+
+        /// Email addresses that belong to me (the me contact's email is always included)
+        #[arg(long = "my-email")]
+        my_emails: Vec<String>,
+
+        /// Skip text messages shorter than this many characters (overrides import_filter.min_length)
+        #[arg(long)]
+        min_length: Option<usize>,
+
+        /// Skip text messages made only of emoji
+        #[arg(long)]
+        skip_emoji_only: bool,
+
+        /// Skip one-time passcodes and verification codes
+        #[arg(long)]
+        skip_codes: bool,
+
+        /// Skip text messages matching this regular expression (repeatable)
+        #[arg(long)]
+        skip_pattern: Vec<String>,
+    },
+    /// Import a Telegram "Export chat history" result.json
+    ImportTelegram {
+        /// Path to result.json
+        #[arg(short, long)]
+        path: String,
+
+        /// My Telegram display name (defaults to the me contact's name)
+        #[arg(long)]
+        my_name: Option<String>,
+
+        /// Skip text messages shorter than this many characters (overrides import_filter.min_length)
+        #[arg(long)]
+        min_length: Option<usize>,
+
+        /// Skip text messages made only of emoji
+        #[arg(long)]
+        skip_emoji_only: bool,
+
+        /// Skip one-time passcodes and verification codes
+        #[arg(long)]
+        skip_codes: bool,
+
+        /// Skip text messages matching this regular expression (repeatable)
+        #[arg(long)]
+        skip_pattern: Vec<String>,
+    },
+    /// Rebuild the database from this tool's TXT, CSV or JSON exports
+    Restore {
+        /// Export chunk file or directory of chunk files
+        #[arg(short, long)]
+        path: String,
+
+        /// Name of the contact the exported conversation is with
+        #[arg(short, long)]
+        name: String,
+
+        /// My name as it appears in the export (defaults to the me contact's name)
+        #[arg(long)]
+        my_name: Option<String>,
+
+        /// chrono format for CSV timestamps, e.g. "%d.%m.%Y, %H:%M" (inferred when omitted)
+        #[arg(long)]
+        date_format: Option<String>,
+    },
+    /// List every handle (phone number or email) in chat.db with its chats and message count
+    DiscoverHandles {
+        /// Only handles whose identifier or chat names contain this text
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// List every chat in chat.db, including group chats, with its participants and message count
+    DiscoverChats {
+        /// Only chats whose identifier, name or participants contain this text
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Run a read-only SQL query against the local database
+    Sql {
+        /// Query to run, e.g. "SELECT sender, count(*) FROM messages GROUP BY 1"
+        query: String,
+
+        /// Output format (table, csv or json)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+    },
+    /// Set up chat.db, your own name and contacts, then try a query
+    Init,
+    /// List profiles and where their data is stored
+    Profiles,
+    /// Run scheduled tasks from the config file until interrupted
+    Watch {
+        /// Seconds between schedule checks (keep at or below 60 so no minute is missed)
+        #[arg(short, long, default_value = "60")]
+        interval: u64,
+    },
+    /// Run and inspect the step chains defined under `pipelines` in the config file
+    Pipeline {
+        #[command(subcommand)]
+        action: PipelineAction,
+    },
+    /// Permanently delete old messages and everything stored about them
+    Purge {
+        /// Delete messages sent before this local date (YYYY-MM-DD)
+        #[arg(long)]
+        before: String,
+
+        /// Only delete messages with this contact
+        #[arg(long)]
+        contact: Option<String>,
+
+        /// Show what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Delete without asking for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Restore the rows changed by a purge or contact merge from its undo journal
+    Undo {
+        /// Undo the most recent purge or merge that hasn't been undone yet
+        #[arg(long, required_unless_present = "list")]
+        last: bool,
+
+        /// List the purges and merges that can be undone instead
+        #[arg(long, conflicts_with = "last")]
+        list: bool,
+    },
+    /// Leave messages out of every export and report without deleting them
+    Hide {
+        /// IDs of the messages in the local database
+        #[arg(required_unless_present = "list")]
+        message_ids: Vec<i32>,
+
+        /// Why the messages are hidden, e.g. "verification code"
+        #[arg(short, long, required_unless_present = "list")]
+        reason: Option<String>,
+
+        /// List hidden messages and their reasons instead
+        #[arg(long, conflicts_with_all = ["message_ids", "reason"])]
+        list: bool,
+    },
+    /// Include hidden messages in exports and reports again
+    Unhide {
+        /// IDs of the messages in the local database
+        #[arg(required = true)]
+        message_ids: Vec<i32>,
+    },
+    /// Manage contacts in the local database
+    Contacts {
+        #[command(subcommand)]
+        action: ContactsAction,
+    },
+    /// List the daily database snapshots or roll the database back to one
+    Snapshots {
+        #[command(subcommand)]
+        action: SnapshotsAction,
+    },
+    /// Inspect or empty the message cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+
+        /// Drop entries encrypted with another passphrase instead of failing
+        #[arg(long, global = true)]
+        reset_cache: bool,
+    },
+    /// Check and compact the local database
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+    /// Print a shell completion script, e.g. `completions zsh > ~/.zfunc/_txt-history-rust`
+    Completions {
+        /// Shell to generate the script for
+        shell: clap_complete::Shell,
+    },
+    /// Write man pages for the command and every subcommand
+    Mangen {
+        /// Directory to write the pages to
+        #[arg(short, long, default_value = "man")]
+        output_dir: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotsAction {
+    /// List snapshots, newest first
+    List,
+    /// Replace the database with a snapshot, after snapshotting it as it is now
+    Restore {
+        /// Snapshot file name from `snapshots list`, or "latest"
+        name: String,
+
+        /// Restore without asking for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Show how many entries the cache holds, their size and age
+    Stats,
+    /// Remove every cached entry
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Run an integrity check, rebuild indexes, refresh statistics and VACUUM to reclaim space
+    Maintain,
+}
+
+#[derive(Subcommand)]
+enum ContactsAction {
+    /// Merge a duplicate contact into another, moving its identifiers and messages
+    Merge {
+        /// Contact to keep
+        #[arg(long)]
+        into: String,
+
+        /// Duplicate contact to merge and delete
+        #[arg(long)]
+        from: String,
+
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Merge without asking for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Show or change a contact's notes and details. An empty value clears a field, e.g.
+    /// `--notes ""`; with no options the details are only shown
+    Edit {
+        /// Contact to edit
+        name: String,
+
+        /// How they're related to you, e.g. "co-parent", shown in export template headers
+        #[arg(long)]
+        relationship: Option<String>,
+
+        /// Their IANA time zone, e.g. "America/Denver"
+        #[arg(long)]
+        timezone: Option<String>,
+
+        /// Their birthday (YYYY-MM-DD)
+        #[arg(long)]
+        birthday: Option<String>,
+
+        /// Free-form notes
+        #[arg(long)]
+        notes: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PipelineAction {
+    /// List configured pipelines and their steps
+    List,
+    /// Run a pipeline's steps in order
+    Run {
+        /// Pipeline name from the config file
+        name: String,
+
+        /// Skip steps that finished in the previous run
+        #[arg(long)]
+        resume: bool,
+    },
+    /// Show each step's status from a pipeline's latest run
+    Status {
+        /// Pipeline name from the config file
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AttachmentsAction {
+    /// List every attachment with its date, type, size, filename and the text sent with it
+    List {
+        /// Only the conversation with this contact
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Only attachments whose MIME type starts with this, e.g. "image" or "video/quicktime"
+        #[arg(short = 't', long = "type")]
+        mime_type: Option<String>,
+
+        /// Write `attachments_<name>.csv` instead of printing the list
+        #[arg(long)]
+        csv: bool,
+
+        /// Output directory for --csv (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProcessAction {
+    /// List stored processing versions with message counts
+    ListVersions,
+    /// Compare two versions' output on a random sample of messages processed by both
+    Diff {
+        /// Version to compare from
+        old: String,
+
+        /// Version to compare to
+        new: String,
+
+        /// Number of messages to compare
+        #[arg(long, default_value = "100")]
+        sample: usize,
+    },
+    /// Delete every processed message stored for a version
+    Purge {
+        /// Version to delete
+        #[arg(long)]
+        version: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditKind {
+    /// Find silent stretches in an active conversation and months with fewer messages than chat.db
+    Gaps {
+        /// Name of the contact
+        #[arg(short, long)]
+        name: String,
+
+        /// Shortest stretch without messages to report, in days
+        #[arg(long, default_value = "7")]
+        min_gap_days: i64,
+
+        /// Days before and after a gap to measure activity over
+        #[arg(long, default_value = "30")]
+        window_days: i64,
+
+        /// Messages needed on each side of a gap for the conversation to count as active
+        #[arg(long, default_value = "10")]
+        min_activity: usize,
+
+        /// Only look for gaps, without comparing counts against chat.db
+        #[arg(long)]
+        skip_chat_db: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportKind {
+    /// Count messages flagged for profanity or abusive language per sender per month
+    Flags {
+        /// Processing version that ran with --flag-language
+        #[arg(long, default_value = "v1.0")]
+        version: String,
+
+        /// Only the conversation with this contact
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Only count messages with at least this severity (0.0 to 1.0)
+        #[arg(long, default_value = "0.0")]
+        min_severity: f32,
+    },
+    /// List named entities found by NLP processing, including custom `entity_patterns` types
+    Entities {
+        /// Processing version to read entities from
+        #[arg(long, default_value = "v1.0")]
+        version: String,
+
+        /// Only the conversation with this contact
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Only entities of this type, e.g. CASE_NUMBER
+        #[arg(short = 't', long = "type")]
+        entity_type: Option<String>,
+
+        /// Only entities whose text contains this
+        #[arg(long)]
+        search: Option<String>,
+    },
+    /// List open questions and commitments per contact, from `extract-commitments`
+    Commitments {
+        /// Only the conversation with this contact
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Also list answered questions and agreements
+        #[arg(long)]
+        all: bool,
+    },
+    /// Write an HTML page charting a conversation's volume, sentiment and reply times by week
+    Dashboard {
+        /// Contact name
+        #[arg(short, long)]
+        name: String,
+
+        /// Processing version to read sentiment scores from
+        #[arg(long, default_value = "v1.0")]
+        version: String,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    /// Write events found by `extract-events` to a calendar file or CSV
+    Events {
+        /// Only the conversation with this contact
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Only events mentioned in messages sent on or after this date (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// Only events mentioned in messages sent on or before this date (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Output format (ics or csv)
+        #[arg(short, long, default_value = "ics")]
+        format: String,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    /// Compare how each side writes: message length, bursts and messages per session
+    Style {
+        /// Contact name
+        #[arg(short, long)]
+        name: String,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Minutes without messages that end a session
+        #[arg(long, default_value_t = sessions::DEFAULT_SESSION_GAP_MINUTES)]
+        session_gap: i64,
+
+        /// Output format (csv or json)
+        #[arg(short, long, default_value = "csv")]
+        format: String,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    /// Show a conversation's language mix per month and how often it switches language
+    Languages {
+        /// Contact name
+        #[arg(short, long)]
+        name: String,
+
+        /// Processing version to read detected languages from
+        #[arg(long, default_value = "v1.0")]
+        version: String,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Minutes without messages that end a session; switches are only counted within one
+        #[arg(long, default_value_t = sessions::DEFAULT_SESSION_GAP_MINUTES)]
+        session_gap: i64,
+    },
+    /// Show the most negative and most positive sentence of each message with mixed or strong tone
+    Sentences {
+        /// Contact name
+        #[arg(short, long)]
+        name: String,
+
+        /// Processing version to read sentence sentiment from
+        #[arg(long, default_value = "v1.0")]
+        version: String,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Only messages with at least this many sentences
+        #[arg(long, default_value = "2")]
+        min_sentences: usize,
+    },
+    /// Count messages per day of week and hour, and per calendar day, for plotting
+    Heatmap {
+        /// Only the conversation with this contact
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Output format (csv or json)
+        #[arg(short, long, default_value = "csv")]
+        format: String,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    /// Write a GraphML or DOT file of who talks to whom, weighted by message volume and sentiment
+    Graph {
+        /// Processing version to read sentiment scores from
+        #[arg(long, default_value = "v1.0")]
+        version: String,
+
+        /// Start date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        start_date: Option<String>,
+
+        /// End date for message range (YYYY-MM-DD)
+        #[arg(short, long)]
+        end_date: Option<String>,
+
+        /// Output format (graphml or dot)
+        #[arg(short, long, default_value = "graphml")]
+        format: String,
+
+        /// Output directory (defaults to the profile's output directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Parse command line arguments
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(exit_code(&e))
+        },
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    Verbosity::from_flags(cli.verbose, cli.quiet).set();
+
+    // These only need the CLI definition, so they work before any setup
+    match &cli.command {
+        Commands::Completions { shell } => return print_completions(*shell),
+        Commands::Mangen { output_dir } => return write_man_pages(output_dir).map(|_| ()),
+        _ => {},
+    }
+
+    // Load configuration
+    let mut config = Config::load()?;
+    if let Some(chat_db) = &cli.chat_db {
+        config.chat_db.path = Some(chat_db.clone());
+    }
+
+    // Each profile has its own database, cache and output directory
+    let profile = Profile::load(cli.profile.as_deref())?;
+    let database_url = profile.database_url();
+    let default_output_dir = profile.output_dir().to_string_lossy().to_string();
+    let output_dir_or_default = |dir: &Option<String>| dir.clone().unwrap_or_else(|| default_output_dir.clone());
+
+    // Only one process writes to a database at a time; read-only commands share the lock
+    let (mut db, _lock) = open_database(&database_url, &config.me, lock_mode(&cli.command))?;
+
+    // The day's first import or purge copies the database first, so it can be rolled back
+    let snapshots = SnapshotStore::new(profile.snapshots_dir());
+    if snapshots_before(&cli.command) {
+        take_daily_snapshot(&db, &snapshots, &config.snapshots, Local::now().naive_local());
+    }
+
+    let notifier = Notifier::new(&config.notifications);
+    let started = Instant::now();
+
+    // Process command
+    let (operation, result) = match &cli.command {
+        Commands::Import {
+            name,
+            chat_id,
+            start_date,
+            end_date,
+            format,
+            size,
+            lines,
+            tokens_per_chunk,
+            output_dir,
+            service,
+            refresh,
+            min_length,
+            skip_emoji_only,
+            skip_codes,
+            skip_pattern,
+            snapshot,
+            throttle,
+            throttle_pause,
+            template,
+            csv_delimiter,
+            csv_columns,
+            csv_quote,
+            no_csv_header,
+            csv_normalize,
+            sender_style,
+            txt_prefixes,
+            txt_totals,
+            include_ids,
+            split_by_sender,
+            overwrite,
+            skip_existing,
+            append,
+            hashes,
+            yes,
+        } => (
+            "Import",
+            import_messages(
+                &db,
+                name,
+                chat_id,
+                &ExportOptions::new(output_dir_or_default(output_dir))
+                    .with_format_name(format)
+                    .with_date_range(parse_date_range(start_date, end_date)?)
+                    .with_chunking(Chunking::from_options(*size, *lines, *tokens_per_chunk))
+                    .with_csv_options(
+                        CsvOptions::parse(csv_delimiter, csv_columns, csv_quote, !no_csv_header)?
+                            .with_normalization(CsvNormalization::parse(csv_normalize)?)
+                            .with_ids(*include_ids || config.export_style.include_ids),
+                    )
+                    .with_style(
+                        config
+                            .export_style
+                            .clone()
+                            .with_overrides(sender_style, *txt_prefixes, *txt_totals)?
+                            .with_ids(*include_ids),
+                    )
+                    .with_policy(WritePolicy::from_flags(*overwrite, *skip_existing, *append))
+                    .with_service(ServiceFilter::parse(service)?)
+                    .with_template(template.clone())
+                    .with_split_by_sender(*split_by_sender)
+                    .with_parallel_writes(config.export.parallel_writes)
+                    .with_hashes(*hashes),
+                *refresh,
+                &ImportFilter::new(&config.import_filter.clone().with_flags(
+                    *min_length,
+                    *skip_emoji_only,
+                    *skip_codes,
+                    skip_pattern,
+                ))?,
+                *yes,
+                &database_url,
+                &config.chat_db,
+                *snapshot,
+                &ThrottleConfig::from_flags(*throttle, *throttle_pause),
+                &profile.cache_dir(),
+                &config.cache,
+            )
+            .await,
+        ),
+        Commands::Query {
+            name,
+            chat_id,
+            start_date,
+            end_date,
+            format,
+            size,
+            lines,
+            tokens_per_chunk,
+            output_dir,
+            service,
+            sources,
+            hours,
+            weekdays,
+            weekends,
+            template,
+            csv_delimiter,
+            csv_columns,
+            csv_quote,
+            no_csv_header,
+            csv_normalize,
+            sender_style,
+            txt_prefixes,
+            txt_totals,
+            include_ids,
+            split_by_sender,
+            overwrite,
+            skip_existing,
+            append,
+            hashes,
+            yes,
+            resume_export,
+            nlp_version,
+            preset,
+            bates_prefix,
+            bates_start,
+            summary,
+            all,
+            canonical,
+            anonymize,
+            with_metadata,
+        } => {
+            let legal = LegalOptions::from_preset(preset.as_deref(), format, bates_prefix, *bates_start)?;
+            let time_filter = TimeFilter::from_flags(hours, *weekdays, *weekends)?;
+            let service_filter = ServiceFilter::parse(service)?;
+            let source_filter = SourceFilter::parse(sources)?;
+            let csv_options = CsvOptions::parse(csv_delimiter, csv_columns, csv_quote, !no_csv_header)?
+                .with_normalization(CsvNormalization::parse(csv_normalize)?)
+                .with_ids(*include_ids || config.export_style.include_ids);
+            let style = config
+                .export_style
+                .clone()
+                .with_overrides(sender_style, *txt_prefixes, *txt_totals)?
+                .with_ids(*include_ids);
+            let export = |start_date: &Option<String>, end_date: &Option<String>, output_dir: &str, yes: bool| -> Result<usize> {
+                let options = ExportOptions::new(output_dir)
+                    .with_format_name(format)
+                    .with_date_range(parse_date_range(start_date, end_date)?)
+                    .with_chunking(Chunking::from_options(*size, *lines, *tokens_per_chunk))
+                    .with_csv_options(csv_options.clone())
+                    .with_style(style.clone())
+                    .with_policy(WritePolicy::from_flags(*overwrite, *skip_existing, *append))
+                    .with_service(service_filter)
+                    .with_sources(source_filter.clone())
+                    .with_time_filter(time_filter)
+                    .with_template(template.clone())
+                    .with_nlp_version(nlp_version.clone())
+                    .with_legal(legal.clone())
+                    .with_split_by_sender(*split_by_sender)
+                    .with_parallel_writes(config.export.parallel_writes)
+                    .with_hashes(*hashes)
+                    .with_canonical(*canonical);
+                let flags = QueryFlags {
+                    yes,
+                    resume_export: *resume_export,
+                    summary: *summary,
+                    anonymize: anonymize.clone(),
+                    metadata_chat_db: with_metadata.then(|| config.chat_db.clone()),
+                };
+                query_messages(&db, name, chat_id, &options, &flags)
+            };
+
+            let output_dir = output_dir_or_default(output_dir);
+            let result = if *all {
+                export_all_years(&db, name, chat_id, &output_dir, *yes, export)
+            } else {
+                export(start_date, end_date, &output_dir, *yes)
+            };
+            ("Query", result)
+        },
+        Commands::ExportMyMessages {
+            start_date,
+            end_date,
+            format,
+            size,
+            lines,
+            tokens_per_chunk,
+            output_dir,
+            service,
+            sources,
+            hours,
+            weekdays,
+            weekends,
+            overwrite,
+            skip_existing,
+            append,
+            hashes,
+            yes,
+        } => (
+            "Export my messages",
+            export_my_messages(
+                &db,
+                &ExportOptions::new(output_dir_or_default(output_dir))
+                    .with_format_name(format)
+                    .with_date_range(parse_date_range(start_date, end_date)?)
+                    .with_chunking(Chunking::from_options(*size, *lines, *tokens_per_chunk))
+                    .with_csv_options(CsvOptions::default().with_ids(config.export_style.include_ids))
+                    .with_style(config.export_style.clone())
+                    .with_policy(WritePolicy::from_flags(*overwrite, *skip_existing, *append))
+                    .with_service(ServiceFilter::parse(service)?)
+                    .with_sources(SourceFilter::parse(sources)?)
+                    .with_time_filter(TimeFilter::from_flags(hours, *weekdays, *weekends)?)
+                    .with_parallel_writes(config.export.parallel_writes)
+                    .with_hashes(*hashes),
+                *yes,
+            ),
+        ),
+        Commands::ExportAll {
+            contacts,
+            start_date,
+            end_date,
+            format,
+            size,
+            lines,
+            tokens_per_chunk,
+            output_dir,
+            jobs,
+            overwrite,
+            skip_existing,
+            append,
+            hashes,
+            yes,
+        } => (
+            "Export all",
+            export_all(
+                &db,
+                contacts,
+                &ExportOptions::new(output_dir_or_default(output_dir))
+                    .with_format_name(format)
+                    .with_date_range(parse_date_range(start_date, end_date)?)
+                    .with_chunking(Chunking::from_options(*size, *lines, *tokens_per_chunk))
+                    .with_style(config.export_style.clone())
+                    .with_policy(WritePolicy::from_flags(*overwrite, *skip_existing, *append))
+                    .with_parallel_writes(config.export.parallel_writes)
+                    .with_hashes(*hashes),
+                *jobs,
+                *yes,
+            ),
+        ),
+        Commands::ExportMerged {
+            contacts,
+            start_date,
+            end_date,
+            format,
+            output_dir,
+            overwrite,
+            skip_existing,
+            hashes,
+            yes,
+        } => (
+            "Merged export",
+            export_merged(
+                &db,
+                contacts,
+                start_date,
+                end_date,
+                format,
+                &output_dir_or_default(output_dir),
+                &config.export_style,
+                WritePolicy::from_flags(*overwrite, *skip_existing, false),
+                *hashes,
+                *yes,
+            ),
+        ),
+        Commands::Context {
+            message_id,
+            at,
+            name,
+            chat_id,
+            before,
+            after,
+            format,
+            output_dir,
+            yes,
+        } => (
+            "Context export",
+            export_context(
+                &db,
+                message_id.as_deref(),
+                at,
+                name,
+                chat_id,
+                *before,
+                *after,
+                format,
+                &output_dir_or_default(output_dir),
+                &config.export_style,
+                config.export.parallel_writes,
+                *yes,
+            ),
+        ),
+        Commands::Search { text, name, chat_id, start_date, end_date, context, export } => (
+            "Search",
+            search_messages(
+                &db,
+                text,
+                name,
+                chat_id,
+                start_date,
+                end_date,
+                *context,
+                export.as_deref(),
+                &config.export_style,
+            ),
+        ),
+        Commands::ExportByPerson {
+            name,
+            start_date,
+            end_date,
+            size,
+            lines,
+            output_dir,
+            service,
+            sources,
+            snapshot,
+        } => (
+            "Export",
+            export_conversation_by_person(
+                &database_url,
+                name,
+                start_date,
+                end_date,
+                *size,
+                *lines,
+                &output_dir_or_default(output_dir),
+                service,
+                sources,
+                &config.export_style,
+                &config.chat_db,
+                *snapshot,
+            )
+            .await,
+        ),
+        Commands::Process { action: Some(action), .. } => match action {
+            ProcessAction::ListVersions => return list_processing_versions(&db).map(|_| ()),
+            ProcessAction::Diff { old, new, sample } => {
+                return diff_processing_versions(&db, old, new, *sample).map(|_| ())
+            },
+            ProcessAction::Purge { version } => ("NLP purge", purge_processing_version(&db, version)),
+        },
+        Commands::Process {
+            action: None,
+            version,
+            name,
+            start_date,
+            end_date,
+            batch_size,
+            limit,
+            only_from_me,
+            only_contact,
+            min_length,
+            service,
+            flag_language,
+            throttle,
+            throttle_pause,
+            stats,
+        } => (
+            "NLP processing",
+            process_messages(
+                &db,
+                version,
+                name,
+                start_date,
+                end_date,
+                *batch_size,
+                ProcessFilter {
+                    is_from_me: match (*only_from_me, *only_contact) {
+                        (true, _) => Some(true),
+                        (_, true) => Some(false),
+                        _ => None,
+                    },
+                    min_length: *min_length,
+                    service: ServiceFilter::parse(service)?,
+                    limit: *limit,
+                    ..ProcessFilter::default()
+                },
+                flag_language.then_some(&config.language_flags),
+                &config.entity_patterns,
+                ThrottleConfig::from_flags(*throttle, *throttle_pause),
+                *stats,
+            ),
+        ),
+        Commands::Report {
+            report: ReportKind::Flags { version, name, min_severity },
+        } => return report_flags(&db, version, name, *min_severity).map(|_| ()),
+        Commands::Report {
+            report: ReportKind::Entities { version, name, entity_type, search },
+        } => {
+            let filter = EntityFilter { entity_type: entity_type.clone(), text: search.clone() };
+            return report_entities(&db, version, name, &filter).map(|_| ())
+        },
+        Commands::Report {
+            report: ReportKind::Commitments { name, start_date, end_date, all },
+        } => return report_commitments(&db, name, start_date, end_date, *all).map(|_| ()),
+        Commands::Report {
+            report: ReportKind::Dashboard { name, version, start_date, end_date, output_dir },
+        } => {
+            return report_dashboard(&db, name, version, start_date, end_date, &output_dir_or_default(output_dir))
+                .map(|_| ())
+        },
+        Commands::Report {
+            report: ReportKind::Events { name, start_date, end_date, format, output_dir },
+        } => {
+            return report_events(&db, name, start_date, end_date, format, &output_dir_or_default(output_dir))
+                .map(|_| ())
+        },
+        Commands::Report {
+            report: ReportKind::Style { name, start_date, end_date, session_gap, format, output_dir },
+        } => {
+            let output_dir = output_dir_or_default(output_dir);
+            return report_style(&db, name, start_date, end_date, *session_gap, format, &output_dir).map(|_| ());
+        },
+        Commands::Report {
+            report: ReportKind::Languages { name, version, start_date, end_date, session_gap },
+        } => return report_languages(&db, name, version, start_date, end_date, *session_gap).map(|_| ()),
+        Commands::Report {
+            report: ReportKind::Sentences { name, version, start_date, end_date, min_sentences },
+        } => return report_sentences(&db, name, version, start_date, end_date, *min_sentences).map(|_| ()),
+        Commands::Report {
+            report: ReportKind::Heatmap { name, start_date, end_date, format, output_dir },
+        } => {
+            return report_heatmap(&db, name, start_date, end_date, format, &output_dir_or_default(output_dir))
+                .map(|_| ())
+        },
+        Commands::Report {
+            report: ReportKind::Graph { version, start_date, end_date, format, output_dir },
+        } => {
+            return report_graph(&db, version, start_date, end_date, format, &output_dir_or_default(output_dir))
+                .map(|_| ())
+        },
+        Commands::Attachments {
+            action: AttachmentsAction::List { name, start_date, end_date, mime_type, csv, output_dir },
+        } => {
+            let output_dir = csv.then(|| output_dir_or_default(output_dir));
+            return list_attachments(&db, name, start_date, end_date, mime_type, output_dir.as_deref()).map(|_| ());
+        },
+        Commands::Audit {
+            audit: AuditKind::Gaps { name, min_gap_days, window_days, min_activity, skip_chat_db },
+        } => {
+            let options = GapOptions {
+                min_gap_days: *min_gap_days,
+                window_days: *window_days,
+                min_activity: *min_activity,
+            };
+            return audit_gaps(&db, &config.chat_db, name, &options, *skip_chat_db).map(|_| ())
+        },
+        Commands::ExportNlp { version, name, start_date, end_date, format, output_dir } => (
+            "NLP export",
+            export_nlp(
+                &db,
+                version,
+                name,
+                start_date,
+                end_date,
+                format,
+                &output_dir_or_default(output_dir),
+                &config.export_style,
+            ),
+        ),
+        Commands::ExtractCommitments {
+            name,
+            start_date,
+            end_date,
+            answer_window,
+        } => (
+            "Commitment extraction",
+            extract_commitments(&db, name, start_date, end_date, *answer_window),
+        ),
+        Commands::ExtractEvents { name, start_date, end_date } => {
+            ("Event extraction", extract_events(&db, name, start_date, end_date))
+        },
+        Commands::Summarize { name, start_date, end_date, by, output_dir, limit, refresh, dry_run } => (
+            "Summarize",
+            summarize_conversation(
+                &db,
+                &config,
+                name,
+                start_date,
+                end_date,
+                SummaryUnit::parse(by)?,
+                &output_dir_or_default(output_dir),
+                *limit,
+                *refresh,
+                *dry_run,
+            ),
+        ),
+        Commands::Transcribe {
+            whisper_model,
+            whisper_bin,
+            tesseract_bin,
+            no_ocr,
+        } => (
+            "Transcription",
+            transcribe_attachments(&db, whisper_model, whisper_bin, tesseract_bin, *no_ocr),
+        ),
+        Commands::ImportEmail { path, my_emails, min_length, skip_emoji_only, skip_codes, skip_pattern } => {
+            let filter = config.import_filter.clone().with_flags(*min_length, *skip_emoji_only, *skip_codes, skip_pattern);
+            let senders = SenderMap::new(&config.sender_map)?;
+            ("Email import", import_email(&db, path, my_emails, &config.me, &ImportFilter::new(&filter)?, &senders))
+        },
+        Commands::ImportTelegram { path, my_name, min_length, skip_emoji_only, skip_codes, skip_pattern } => {
+            let filter = config.import_filter.clone().with_flags(*min_length, *skip_emoji_only, *skip_codes, skip_pattern);
+            let senders = SenderMap::new(&config.sender_map)?;
+            ("Telegram import", import_telegram(&db, path, my_name, &ImportFilter::new(&filter)?, &senders))
+        },
+        Commands::Restore { path, name, my_name, date_format } => (
+            "Restore",
+            restore_exports(&db, path, name, my_name, &DateFormat::from_option(date_format.as_deref())),
+        ),
+        Commands::DiscoverHandles { filter } => {
+            return list_chat_db_handles(&db, &config.chat_db, filter).map(|_| ())
+        },
+        Commands::DiscoverChats { filter } => {
+            return list_chat_db_chats(&db, &config.chat_db, filter).map(|_| ())
+        },
+        Commands::Sql { query, format } => return run_sql(&db, query, format).map(|_| ()),
+        Commands::Estimate {
+            name,
+            chat_id,
+            start_date,
+            end_date,
+            format,
+            size,
+            lines,
+            tokens_per_chunk,
+            service,
+            sources,
+            hours,
+            weekdays,
+            weekends,
+        } => {
+            return estimate_export(
+                &db,
+                name,
+                chat_id,
+                start_date,
+                end_date,
+                format,
+                Chunking::from_options(*size, *lines, *tokens_per_chunk),
+                service,
+                sources,
+                &TimeFilter::from_flags(hours, *weekdays, *weekends)?,
+                &config.export_style,
+            )
+            .map(|_| ())
+        },
+        Commands::Stats { name, start_date, end_date, hours, weekdays, weekends } => {
+            let time_filter = TimeFilter::from_flags(hours, *weekdays, *weekends)?;
+            return show_milestones(&db, name, start_date, end_date, &time_filter).map(|_| ())
+        },
+        Commands::VerifyExport { dir, export_dir, files_only } => {
+            let dir = dir.as_ref().or(export_dir.as_ref()).expect("clap requires a directory");
+            return verify_export(&db, &config.export_style, dir, *files_only).map(|_| ())
+        },
+        Commands::Init => return init(&db, config.clone()).map(|_| ()),
+        Commands::Profiles => return list_profiles(&profile).map(|_| ()),
+        Commands::Watch { interval } => {
+            return watch(&db, &config, &notifier, &database_url, &snapshots, *interval).await
+        },
+        Commands::Pipeline { action } => {
+            return match action {
+                PipelineAction::List => list_pipelines(&config),
+                PipelineAction::Status { name } => pipeline_status(&config, &profile, name),
+                PipelineAction::Run { name, resume } => {
+                    run_pipeline(&db, &config, &profile, &notifier, &database_url, name, *resume).await
+                },
+            }
+            .map(|_| ())
+        },
+        Commands::Purge { before, contact, dry_run, yes } => {
+            ("Purge", purge_messages(&db, before, contact, *dry_run, *yes))
+        },
+        Commands::Undo { list: true, .. } => return list_undo_journals(&db).map(|_| ()),
+        Commands::Undo { .. } => ("Undo", undo_last(&db)),
+        Commands::Hide { list: true, .. } => return list_hidden_messages(&db).map(|_| ()),
+        Commands::Hide { message_ids, reason, .. } => (
+            "Hide messages",
+            hide_messages(&db, message_ids, reason.as_deref().unwrap_or_default()),
+        ),
+        Commands::Unhide { message_ids } => ("Unhide messages", unhide_messages(&db, message_ids)),
+        Commands::Contacts {
+            action: ContactsAction::Merge { into, from, dry_run, yes },
+        } => ("Contact merge", merge_contacts(&db, into, from, *dry_run, *yes)),
+        Commands::Contacts {
+            action: ContactsAction::Edit { name, relationship, timezone, birthday, notes },
+        } => return edit_contact(&db, name, relationship, timezone, birthday, notes),
+        Commands::Snapshots { action } => {
+            return match action {
+                SnapshotsAction::List => list_snapshots(&snapshots),
+                SnapshotsAction::Restore { name, yes } => restore_snapshot(&mut db, &snapshots, name, *yes),
+            };
+        },
+        Commands::Cache { action, reset_cache } => {
+            let cache = if *reset_cache {
+                MessageCache::reset(&profile.cache_dir(), &config.cache)?
+            } else {
+                MessageCache::open_with(&profile.cache_dir(), &config.cache)?
+            };
+            return match action {
+                CacheAction::Stats => cache_stats(&cache, &config.cache),
+                CacheAction::Clear => clear_cache(&cache),
+            };
+        },
+        Commands::Db { action: DbAction::Maintain } => ("Database maintenance", maintain_database(&db)),
+        Commands::Completions { .. } | Commands::Mangen { .. } => unreachable!("handled before setup"),
+    };
+
+    if let Ok(messages) = &result {
+        if maintains_after(&cli.command) && config.maintenance.is_due(*messages) {
+            progress(format!("{} touched {} messages, running database maintenance", operation, messages));
+            if let Err(e) = maintain_database(&db) {
+                eprintln!("Database maintenance failed: {:#}", e);
+            }
+        }
+    }
+
+    notifier.notify(&OperationReport::new(operation, &result, started.elapsed()));
+    result.map(|_| ())
+}
+
+/// Commands that can leave the database bloated or its statistics stale, so `maintenance.auto`
+/// runs `db maintain` after them
+fn maintains_after(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Import { .. }
+            | Commands::ImportEmail { .. }
+            | Commands::ImportTelegram { .. }
+            | Commands::Restore { .. }
+            | Commands::Purge { dry_run: false, .. }
+    )
+}
+
+/// Commands that change many messages at once, so the day's first run of them takes a snapshot
+fn snapshots_before(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Import { .. }
+            | Commands::ImportEmail { .. }
+            | Commands::ImportTelegram { .. }
+            | Commands::Restore { .. }
+            | Commands::Purge { dry_run: false, .. }
+    )
+}
+
+/// How a command uses the local database: writers take the lock alone and read-only commands
+/// share it. `watch` locks around each scheduled job instead, so it doesn't hold the lock while idle.
+fn lock_mode(command: &Commands) -> Option<LockMode> {
+    let activity = match command {
+        Commands::Import { .. } | Commands::ImportEmail { .. } | Commands::ImportTelegram { .. } => "importing",
+        Commands::Restore { .. } => "restoring exports",
+        Commands::Process { action: Some(ProcessAction::Purge { .. }), .. } => "purging processed messages",
+        Commands::Process { action: Some(_), .. } => return Some(LockMode::Shared),
+        Commands::Process { .. } => "processing messages",
+        Commands::ExtractCommitments { .. } => "extracting commitments",
+        Commands::ExtractEvents { .. } => "extracting events",
+        Commands::Summarize { dry_run: false, .. } => "summarizing conversations",
+        Commands::Transcribe { .. } => "transcribing attachments",
+        Commands::Init => "setting up contacts",
+        Commands::Pipeline { action: PipelineAction::Run { .. } } => "running a pipeline",
+        Commands::Purge { dry_run: false, .. } => "purging messages",
+        Commands::Undo { list: false, .. } => "undoing a purge or merge",
+        Commands::Hide { list: false, .. } | Commands::Unhide { .. } => "hiding messages",
+        Commands::Contacts { action: ContactsAction::Merge { dry_run: false, .. } } => "merging contacts",
+        Commands::Contacts { action: ContactsAction::Edit { .. } } => "editing a contact",
+        Commands::Snapshots { action: SnapshotsAction::Restore { .. } } => "restoring a snapshot",
+        Commands::Db { action: DbAction::Maintain } => "maintaining the database",
+        // Records each chunk as it's written so the export can be resumed
+        Commands::Query { .. } => "exporting",
+        Commands::Watch { .. } | Commands::Completions { .. } | Commands::Mangen { .. } => return None,
+        _ => return Some(LockMode::Shared),
+    };
+    Some(LockMode::Exclusive(activity))
+}
+
+/// Open the database holding the command's lock. Migrations and the default and me contacts
+/// are brought up to date first, which writes, so under the exclusive lock: read-only commands
+/// skip it when nothing has changed and otherwise wait their turn, and `watch`, which has no
+/// lock of its own, waits for it too.
+fn open_database(database_url: &str, me: &MeConfig, mode: Option<LockMode>) -> Result<(Database, Option<DatabaseLock>)> {
+    const SETTING_UP: LockMode = LockMode::Exclusive("setting up the database");
+    let set_up = |db: &Database| -> Result<()> {
+        db.migrate()?;
+        db.initialize()?;
+        db.sync_me(me)?;
+        Ok(())
+    };
+
+    let lock = mode.map(|mode| DatabaseLock::acquire(database_url, mode)).transpose()?;
+    let db = Database::open(database_url)?;
+    match mode {
+        Some(LockMode::Exclusive(_)) => set_up(&db)?,
+        Some(LockMode::Shared) if db.needs_setup(me)? => {
+            // The exclusive lock can't be taken while this process shares it
+            drop(lock);
+            let setup_lock = DatabaseLock::wait(database_url, SETTING_UP)?;
+            set_up(&db)?;
+            drop(setup_lock);
+            return Ok((db, Some(DatabaseLock::acquire(database_url, LockMode::Shared)?)));
+        },
+        Some(LockMode::Shared) => {},
+        None => {
+            let _setup_lock = DatabaseLock::wait(database_url, SETTING_UP)?;
+            set_up(&db)?;
+        },
+    }
+    Ok((db, lock))
+}
+
+/// Import messages from iMessage database, returning the number of messages fetched
+async fn import_messages(
+    db: &Database,
+    name: &Option<String>,
+    chat_id: &Option<String>,
+    options: &ExportOptions,
+    refresh: bool,
+    import_filter: &ImportFilter,
+    yes: bool,
+    database_url: &str,
+    chat_db: &ChatDbConfig,
+    snapshot: bool,
+    throttle: &ThrottleConfig,
+    cache_dir: &Path,
+    cache_config: &CacheConfig,
+) -> Result<usize> {
+    let output_dir = options.output_path().to_string_lossy();
+    let output_dir: &str = &output_dir;
+    let template = options.template().map(ExportTemplate::load).transpose()?;
+
+    // Get iMessage database path
+    let chat_db_path = chat_db.chat_db_path()?;
+
+    progress(format!("Using iMessage database at: {}", chat_db_path.display()));
+
+    let date_range = options.date_range();
+    if let Some(start) = &date_range.start {
+        progress(format!("Start date: {}", start.format("%Y-%m-%d")));
+    }
+    if let Some(end) = &date_range.end {
+        progress(format!("End date: {}", end.format("%Y-%m-%d")));
+    }
+
+    // Fetch messages, either for a chat by its identifier or for a known contact
+    let (messages, label, warnings) = match (chat_id, name) {
+        (Some(chat_id), _) => {
+            progress(format!("Looking up messages for chat: {}", chat_id));
+            let messages = import_chat(
+                db,
+                &chat_db_path,
+                chat_id,
+                date_range,
+                options.service(),
+                refresh,
+                import_filter,
+                chat_db,
+                snapshot,
+                throttle,
+            )?;
+            (messages, chat_id.clone(), ImportWarnings::default())
+        },
+        (None, Some(name)) => {
+            // Create repository
+            let (repo, _snapshot) = open_chat_db(chat_db_path, database_url, chat_db, snapshot)?;
+            let repo = repo
+                .with_service_filter(options.service())
+                .with_refresh(refresh)
+                .with_import_filter(import_filter.clone())
+                .with_throttle(*throttle);
+
+            // Get contact info
+            let contact = get_contact_info(db, name)?;
+            progress(format!("Looking up messages for: {}", contact.name));
+
+            progress("Fetching messages...");
+            let fetched = repo.fetch_messages(&contact, date_range).await?;
+            (fetched.messages, contact.name, fetched.warnings)
+        },
+        (None, None) => return Err(anyhow::anyhow!("Either --name or --chat-id is required")),
+    };
+    progress(format!("Found {} messages", messages.len()));
+    let template = match db.get_contact(&label)? {
+        Some(contact) => template.map(|template| template.with_contact_details(contact.details)),
+        None => template,
+    };
+
+    // Record what chat.db holds but couldn't be imported, so gaps in the archive are visible
+    if !warnings.is_empty() {
+        println!("{}", warnings.summary());
+        std::fs::create_dir_all(output_dir)?;
+        let written = warnings.write(output_dir)?;
+        println!("Wrote the skipped messages' GUIDs to {}", written.path);
+    }
+    invalidate_cached_messages(cache_dir, cache_config, &label);
+
+    // Messages hidden in the local database stay out of the export
+    let hidden = db.get_hidden_imessage_ids()?;
+    let found = messages.len();
+    let mut messages: Vec<Message> = messages
+        .into_iter()
+        .filter(|m| m.imessage_id.as_ref().map_or(true, |id| !hidden.contains(id)))
+        .collect();
+    if messages.len() < found {
+        progress(format!("Leaving out {} hidden messages", found - messages.len()));
+    }
+    options.style().redact(&mut messages)?;
+
+    // Show what will be written before creating any files
+    let preview_format = if template.is_some() { OutputFormat::Txt } else { options.format() };
+    let preview =
+        ExportPreview::new(&messages, preview_format, options.csv_options(), options.style(), options.chunking())?;
+    if !messages.is_empty() && !confirm_export(&preview, output_dir, yes)? {
+        progress("Export cancelled");
+        return Ok(0);
+    }
+
+    // Create output directory if it doesn't exist
+    std::fs::create_dir_all(output_dir)?;
+
+    // Write messages to files
+    let mut written = match &template {
+        Some(template) => write_templated_files(
+            &messages,
+            template,
+            &label,
+            options.style(),
+            options.chunking(),
+            output_dir,
+            options.policy(),
+            options.parallel_writes(),
+        )?,
+        None => write_messages_to_files(
+            &messages,
+            options.format(),
+            options.csv_options(),
+            options.style(),
+            options.chunking(),
+            output_dir,
+            options.policy(),
+            options.parallel_writes(),
+        )?,
+    };
+    if options.split_by_sender() {
+        written.extend(write_sender_files(
+            &messages,
+            options.format(),
+            template.as_ref(),
+            &label,
+            options.csv_options(),
+            options.style(),
+            options.chunking(),
+            output_dir,
+            options.policy(),
+            options.parallel_writes(),
+        )?);
+    }
+    report_written(&messages, &written);
+    if options.hashes() {
+        write_manifest(&messages, &written, output_dir, None)?;
+    }
+
+    Ok(messages.len())
+}
+
+/// Import a chat straight from chat.db by its `chat_identifier`, so group chats and people
+/// without a contact entry can be exported. Senders are named after the contact matching
+/// their handle, or the handle itself. In a one-on-one chat with a known contact both sides
+/// link to that contact, like a regular import.
+fn import_chat(
+    db: &Database,
+    chat_db_path: &Path,
+    chat_id: &str,
+    date_range: &DateRange,
+    service_filter: ServiceFilter,
+    refresh: bool,
+    import_filter: &ImportFilter,
+    config: &ChatDbConfig,
+    snapshot: bool,
+    throttle: &ThrottleConfig,
+) -> Result<Vec<Message>> {
+    let retry = RetryPolicy::from_config(config);
+    let snapshot = if snapshot || config.snapshot {
+        let snapshot = ChatDbSnapshot::create(chat_db_path, &retry)?;
+        progress(format!("Reading from snapshot at: {}", snapshot.path().display()));
+        Some(snapshot)
+    } else {
+        None
+    };
+    let conn = discovery::open_read_only(snapshot.as_ref().map_or(chat_db_path, |s| s.path()), &retry)?;
+
+    let chat = discovery::discover_chats(&conn, Some(chat_id))?
+        .into_iter()
+        .find(|chat| chat.chat_identifier == chat_id)
+        .ok_or_else(|| anyhow::anyhow!("No chat found with identifier: {}", chat_id))?;
+    let chat_contact = match chat.participants.as_slice() {
+        [participant] => db.find_contact_by_identifier(participant)?,
+        _ => None,
+    };
+    let my_name = db.my_name()?;
+
+    progress("Fetching messages...");
+    let chat_messages = discovery::read_chat_messages(
+        &conn,
+        chat_id,
+        date_range.start.map(|dt| dt.naive_utc()),
+        date_range.end.map(|dt| dt.naive_utc()),
+    )?;
+
+    let mut contacts: HashMap<String, Option<DbContact>> = HashMap::new();
+    let mut messages = Vec::new();
+    let mut throttle = Throttle::new(*throttle);
+    let mut skipped = SkippedMessages::default();
+    for chat_message in chat_messages {
+        if !service_filter.matches(chat_message.service.as_deref()) {
+            continue;
+        }
+        let reason = import_filter.skip_reason(
+            chat_message.text.as_deref(),
+            chat_message.kind,
+            chat_message.has_attachments,
+        );
+        if let Some(reason) = reason {
+            skipped.add(reason);
+            continue;
+        }
+
+        let sender_contact = match &chat_message.sender_handle {
+            Some(handle) => match contacts.get(handle) {
+                Some(contact) => contact.clone(),
+                None => {
+                    let contact = db.find_contact_by_identifier(handle)?;
+                    contacts.insert(handle.clone(), contact.clone());
+                    contact
+                },
+            },
+            None => None,
+        };
+        let sender = if chat_message.is_from_me {
+            my_name.clone()
+        } else {
+            sender_contact
+                .as_ref()
+                .map(|contact| contact.name.clone())
+                .or_else(|| chat_message.sender_handle.clone())
+                .unwrap_or_else(|| chat_id.to_string())
+        };
+
+        let new_message = NewMessage {
+            imessage_id: chat_message.guid,
+            text: chat_message.text,
+            sender,
+            is_from_me: chat_message.is_from_me,
+            date_created: chat_message.date,
+            date_imported: None,
+            handle_id: chat_message.sender_handle,
+            service: chat_message.service,
+            thread_id: Some(chat_id.to_string()),
+            has_attachments: chat_message.has_attachments,
+            reply_to: None,
+            contact_id: chat_contact.as_ref().or(sender_contact.as_ref()).map(|contact| contact.id),
+            message_kind: chat_message.kind,
+        };
+
+        // Add to database, updating the stored copy when refreshing
+        let saved = if refresh {
+            db.upsert_message(new_message)?.message().clone()
+        } else {
+            db.add_message(new_message)?
+        };
+        messages.push(saved.to_message());
+        throttle.wait(1);
+    }
+    if skipped.total() > 0 {
+        progress(skipped.summary());
+    }
+
+    Ok(messages)
+}
+
+/// Print an export preview and ask whether to write it. Skipped with `--yes`, and when
+/// stdin isn't a terminal so scripted runs keep working.
+fn confirm_export(preview: &ExportPreview, output_dir: &str, yes: bool) -> Result<bool> {
+    progress(preview.summary(output_dir));
+    confirm("Write these files?", yes)
+}
+
+/// Export a conversation's whole history with `export` into a `YYYY/` folder per year, after
+/// finding the years it spans. Returns the number of messages exported.
+fn export_all_years(
+    db: &Database,
+    name: &Option<String>,
+    chat_id: &Option<String>,
+    output_dir: &str,
+    yes: bool,
+    export: impl Fn(&Option<String>, &Option<String>, &str, bool) -> Result<usize>,
+) -> Result<usize> {
+    let (conversation, label) = match (chat_id, name) {
+        (Some(chat_id), _) => (Conversation::Thread(chat_id.clone()), chat_id.clone()),
+        (None, Some(name)) => {
+            let contact = db
+                .get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+            (Conversation::Contact(contact.id), contact.name)
+        },
+        (None, None) => return Err(anyhow::anyhow!("Either --name or --chat-id is required")),
+    };
+
+    let years = db.get_message_years(&conversation)?;
+    let (first, last) = match (years.first(), years.last()) {
+        (Some((first, _)), Some((last, _))) => (*first, *last),
+        _ => return Err(TxtHistoryError::NoMessages(label).into()),
+    };
+    let total: usize = years.iter().map(|(_, count)| count).sum();
+    progress(format!("{} has {} messages from {} to {}", label, total, first, last));
+    for (year, count) in &years {
+        detail(format!("  {}: {} messages", year, count));
+    }
+
+    let question = format!("Export {} messages into {} year folders in {}?", total, years.len(), output_dir);
+    if !confirm(&question, yes)? {
+        progress("Export cancelled");
+        return Ok(0);
+    }
+
+    // Each year was confirmed above, so its own preview isn't asked about again
+    let mut exported = 0;
+    for (year, _) in &years {
+        progress(format!("\n{}", year));
+        let year_dir = Path::new(output_dir).join(year.to_string());
+        let start_date = Some(format!("{}-01-01", year));
+        let end_date = Some(format!("{}-12-31", year));
+        match export(&start_date, &end_date, &year_dir.to_string_lossy(), true) {
+            Ok(count) => exported += count,
+            // Messages matched by sender name but not linked to the contact can leave a year empty
+            Err(e) if matches!(e.downcast_ref::<TxtHistoryError>(), Some(TxtHistoryError::NoMessages(_))) => {
+                detail(format!("No messages to export for {}", year));
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(exported)
+}
+
+/// Ask a yes/no question, answering yes without asking when `yes` is set or stdin isn't a terminal
+fn confirm(question: &str, yes: bool) -> Result<bool> {
+    if yes || !std::io::stdin().is_terminal() {
+        return Ok(true);
+    }
+
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Print how much an export wrote in total; per-file sizes are printed as each file is written.
+/// With `-q` this is the only line an export prints.
+fn report_written(messages: &[Message], written: &[WrittenFile]) {
+    let skipped = written.iter().filter(|file| file.status == WriteStatus::Skipped).count();
+    let resumed = written.iter().filter(|file| file.status == WriteStatus::Resumed).count();
+    let bytes: u64 = written
+        .iter()
+        .filter(|file| file.status != WriteStatus::Resumed)
+        .map(|file| file.bytes)
+        .sum();
+    if written.is_empty() {
+        return;
+    }
+
+    let totals = ExportTotals::from_messages(messages);
+    if Verbosity::current() == Verbosity::Quiet {
+        println!("Wrote {} files ({} bytes): {}", written.len() - skipped, bytes, totals.summary());
+        return;
+    }
+
+    println!("Wrote {} bytes to {} files ({} skipped)", bytes, written.len() - skipped - resumed, skipped);
+    if resumed > 0 {
+        println!("Kept {} files from the interrupted export", resumed);
+    }
+    println!("Export totals: {}", totals.summary());
+}
+
+/// Write manifest.json for an export with its totals and messages per service. The merged
+/// transcript's chunk files come first in `written` and cover `messages` in order, so each is
+/// listed with its message hashes and totals; files after them (per-sender files) are listed with
+/// their file hash only.
+fn write_manifest(
+    messages: &[Message],
+    written: &[WrittenFile],
+    output_dir: &str,
+    query: Option<&ManifestQuery>,
+) -> Result<()> {
+    let mut manifest = ExportManifest::new();
+    manifest.query = query.cloned();
+    manifest.totals = Some(ExportTotals::from_messages(messages));
+    for message in messages {
+        let service = message.service.clone().unwrap_or_else(|| "unknown".to_string());
+        *manifest.services.entry(service).or_default() += 1;
+    }
+    let mut offset = 0;
+
+    for file in written {
+        let chunk = messages.get(offset..offset + file.messages);
+        offset += file.messages;
+        manifest.add_file(file, chunk)?;
+    }
+
+    let file = manifest.write(output_dir)?;
+    progress(format!("Wrote hashes for {} files to {}", manifest.files.len(), file.path));
+    Ok(())
+}
+
+/// Print milestones for each contact's conversation and, for more than one, for all of them
+/// together. Returns the number of conversations with messages.
+fn show_milestones(
+    db: &Database,
+    name: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    time_filter: &TimeFilter,
+) -> Result<usize> {
+    let contacts = match name {
+        Some(name) => vec![db
+            .get_contact(name)?
+            .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?],
+        None => db.get_other_contacts()?,
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let today = Local::now().date_naive();
+    let mut everything = Vec::new();
+    let mut conversations = 0;
+
+    for contact in contacts {
+        let mut messages: Vec<Message> = db
+            .get_messages_for_contact_id(
+                contact.id,
+                date_range.start.map(|dt| dt.naive_local()),
+                date_range.end.map(|dt| dt.naive_local()),
+            )?
+            .iter()
+            .map(|m| m.to_message())
+            .filter(|m| time_filter.matches(&m.timestamp))
+            .collect();
+        messages.sort_by_key(|m| m.timestamp);
+
+        if let Some(milestones) = ConversationMilestones::new(&messages, &contact.name) {
+            println!("{}\n", milestones.to_text(today));
+            conversations += 1;
+            everything.extend(messages);
+        }
+    }
+
+    if conversations == 0 {
+        return Err(TxtHistoryError::NoMessages(name.clone().unwrap_or_else(|| "any contact".to_string())).into());
+    }
+    if conversations > 1 {
+        everything.sort_by_key(|m| m.timestamp);
+        if let Some(overall) = ConversationMilestones::new(&everything, "All contacts") {
+            println!("{}", overall.to_text(today));
+        }
+    }
+
+    Ok(conversations)
+}
+
+/// Check an export directory against its manifest.json and, when the manifest records the query
+/// behind the export, against the messages that query returns now. Returns the number of files
+/// checked.
+fn verify_export(db: &Database, style: &ExportStyle, dir: &str, files_only: bool) -> Result<usize> {
+    let manifest = ExportManifest::load(Path::new(dir))?;
+    println!("Manifest generated at {} lists {} files", manifest.generated_at, manifest.files.len());
+
+    let problems = manifest.verify(Path::new(dir));
+    for problem in &problems {
+        println!("  {}", problem);
+    }
+    if problems.is_empty() {
+        println!("All {} files match their recorded hashes", manifest.files.len());
+    }
+
+    let diff = match &manifest.query {
+        _ if files_only => None,
+        None => {
+            println!("The manifest doesn't record the query behind the export, so the database wasn't checked");
+            None
+        },
+        Some(query) if query.anonymized => {
+            println!("The export is anonymized, so it can't be compared with the database");
+            None
+        },
+        Some(query) => Some(compare_export_with_database(db, style, &manifest, query)?),
+    };
+
+    if !problems.is_empty() {
+        return Err(TxtHistoryError::VerificationFailed { failed: problems.len(), total: manifest.files.len() }.into());
+    }
+    if let Some(diff) = diff.filter(|diff| diff.differences() > 0) {
+        let total = diff.unchanged + diff.changed.len() + diff.removed.len();
+        return Err(TxtHistoryError::ExportOutOfDate { differences: diff.differences(), total }.into());
+    }
+    Ok(manifest.files.len())
+}
+
+/// Run an export's recorded query again and compare its messages with the hashes in the
+/// manifest. Redaction rules from the current config are applied, as the export would have.
+fn compare_export_with_database(
+    db: &Database,
+    style: &ExportStyle,
+    manifest: &ExportManifest,
+    query: &ManifestQuery,
+) -> Result<ExportDiff> {
+    let label = match (&query.chat_id, &query.name) {
+        (Some(chat_id), _) => chat_id.clone(),
+        (None, Some(name)) => {
+            db.get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?
+                .name
+        },
+        (None, None) => return Err(anyhow::anyhow!("The manifest's query names no contact or chat")),
+    };
+    let date_range = parse_date_range(&query.start_date, &query.end_date)?;
+    let (_, mut messages) = fetch_export_messages(
+        db,
+        &label,
+        &query.chat_id,
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+        &ServiceFilter::parse(&query.service)?,
+        &SourceFilter::parse(&query.sources)?,
+        &TimeFilter { hours: query.hours.as_deref().map(HourRange::parse).transpose()?, days: query.days },
+    )?;
+    style.redact(&mut messages)?;
+
+    let diff = manifest.compare(&messages);
+    for (kind, messages) in [("Changed", &diff.changed), ("Removed", &diff.removed), ("Added", &diff.added)] {
+        for message in messages {
+            println!("  {}: {}", kind, message);
+        }
+    }
+    if diff.differences() == 0 {
+        println!("All {} messages still match the database", diff.unchanged);
+    } else {
+        println!(
+            "{} unchanged, {} changed, {} removed and {} added since the export",
+            diff.unchanged,
+            diff.changed.len(),
+            diff.removed.len(),
+            diff.added.len()
+        );
+    }
+    Ok(diff)
+}
+
+/// Open chat.db with the configured retry policy, reading from a snapshot when requested.
+/// The snapshot must be kept alive for as long as the repository is used.
+fn open_chat_db(
+    chat_db_path: PathBuf,
+    database_url: &str,
+    config: &ChatDbConfig,
+    snapshot: bool,
+) -> Result<(IMessageDatabaseRepo, Option<ChatDbSnapshot>)> {
+    let retry = RetryPolicy::from_config(config);
+
+    if snapshot || config.snapshot {
+        let snapshot = ChatDbSnapshot::create(&chat_db_path, &retry)?;
+        progress(format!("Reading from snapshot at: {}", snapshot.path().display()));
+        let repo = IMessageDatabaseRepo::open(snapshot.path().to_path_buf(), database_url, retry)?;
+        progress(format!("chat.db schema: {}", repo.schema().describe()));
+        return Ok((repo, Some(snapshot)));
+    }
+
+    let repo = IMessageDatabaseRepo::open(chat_db_path, database_url, retry)?;
+    progress(format!("chat.db schema: {}", repo.schema().describe()));
+    Ok((repo, None))
+}
+
+/// Fetch a conversation's messages for a `query` export: filtered by service, source and time
+/// of day, with attachment transcripts appended and messages imported from several sources
+/// resolved
+fn fetch_export_messages(
+    db: &Database,
+    label: &str,
+    chat_id: &Option<String>,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+    service_filter: &ServiceFilter,
+    source_filter: &SourceFilter,
+    time_filter: &TimeFilter,
+) -> Result<(Vec<DbMessage>, Vec<Message>)> {
+    let mut db_messages = match chat_id {
+        Some(chat_id) => db.get_messages_for_thread(chat_id, start, end)?,
+        None => db.get_messages(label, start, end)?,
+    };
+    db_messages.retain(|m| service_filter.matches(m.service.as_deref()));
+    db_messages.retain(|m| source_filter.matches(m.service.as_deref()));
+    db_messages.retain(|m| time_filter.matches(&Local.from_utc_datetime(&m.date_created)));
+    progress(format!("Found {} messages", db_messages.len()));
+
+    // Convert to the original Message format, including any attachment transcripts
+    let messages = db.to_messages_with_transcripts(&db_messages)?;
+    Ok((db_messages, resolve_conflicts(messages, source_filter)))
+}
+
+/// Print the chunk files a `query` export with these options would write, measured the way the
+/// chunker measures them. Nothing is written. Returns the number of messages.
+fn estimate_export(
+    db: &Database,
+    name: &Option<String>,
+    chat_id: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    format: &str,
+    chunking: Chunking,
+    service: &str,
+    sources: &str,
+    time_filter: &TimeFilter,
+    style: &ExportStyle,
+) -> Result<usize> {
+    let output_format = match format.to_lowercase().as_str() {
+        "txt" => OutputFormat::Txt,
+        "csv" => OutputFormat::Csv,
+        "json" => OutputFormat::Json,
+        "parquet" => OutputFormat::Parquet,
+        _ => return Err(anyhow::anyhow!("Unsupported format: {} (use txt, csv, json or parquet)", format)),
+    };
+    let label = match (chat_id, name) {
+        (Some(chat_id), _) => chat_id.clone(),
+        (None, Some(name)) => {
+            db.get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?
+                .name
+        },
+        (None, None) => return Err(anyhow::anyhow!("Either --name or --chat-id is required")),
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let source_filter = SourceFilter::parse(sources)?;
+    let (_, mut messages) = fetch_export_messages(
+        db,
+        &label,
+        chat_id,
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+        &ServiceFilter::parse(service)?,
+        &source_filter,
+        time_filter,
+    )?;
+    if messages.is_empty() {
+        return Err(TxtHistoryError::NoMessages(label).into());
+    }
+    // Redacted text is what would be written, so measure that
+    style.redact(&mut messages)?;
+
+    let csv_options = CsvOptions::default().with_ids(style.include_ids);
+    let estimate = ExportEstimate::new(&messages, output_format, &csv_options, style, chunking)?;
+    println!(
+        "{}: {} messages in {} files, about {}",
+        label,
+        estimate.messages(),
+        estimate.chunks.len(),
+        format_size(estimate.bytes() as u64)
+    );
+    for chunk in &estimate.chunks {
+        println!("  {:<16} {:>8} messages  {:>10}", chunk.file_name, chunk.messages, format_size(chunk.bytes as u64));
+    }
+
+    Ok(messages.len())
+}
+
+/// `query` flags that only the command line needs, kept out of the library's `ExportOptions`
+#[derive(Default)]
+struct QueryFlags {
+    /// Write without showing the preview and asking for confirmation
+    yes: bool,
+    /// Continue an interrupted export, skipping the chunks it already wrote
+    resume_export: Option<i32>,
+    /// Print a summary of the conversation instead of writing files
+    summary: bool,
+    /// Replace names and identifiers using the pseudonym mapping file at this path
+    anonymize: Option<PathBuf>,
+    /// Write metadata files next to TXT chunks, with read times from this chat.db
+    metadata_chat_db: Option<ChatDbConfig>,
+}
+
+/// Query messages from the database, returning the number of messages written
+fn query_messages(
+    db: &Database,
+    name: &Option<String>,
+    chat_id: &Option<String>,
+    options: &ExportOptions,
+    flags: &QueryFlags,
+) -> Result<usize> {
+    let output_dir = options.output_path().to_string_lossy();
+    let output_dir: &str = &output_dir;
+    let template = options.template().map(ExportTemplate::load).transpose()?;
+    if flags.metadata_chat_db.is_some() && (options.epub() || !matches!(options.format(), OutputFormat::Txt)) {
+        return Err(anyhow::anyhow!("--with-metadata only applies to TXT exports"));
+    }
+    let rewritten = template.is_some() || options.nlp_version().is_some();
+    if flags.resume_export.is_some() && (rewritten || options.legal().is_some() || options.epub()) {
+        return Err(anyhow::anyhow!(
+            "--resume-export can't be combined with --template, --nlp-version, --preset or --format epub"
+        ));
+    }
+    if options.epub() && rewritten {
+        return Err(anyhow::anyhow!("--format epub can't be combined with --template or --nlp-version"));
+    }
+    if options.canonical() && (options.epub() || !matches!(options.format(), OutputFormat::Txt)) {
+        return Err(anyhow::anyhow!("--canonical always writes TXT; leave out --format"));
+    }
+
+    // Get contact, or label the export with the chat identifier
+    let (label, contact_details) = match (chat_id, name) {
+        (Some(chat_id), _) => {
+            progress(format!("Looking up messages for chat: {}", chat_id));
+            (chat_id.clone(), ContactDetails::default())
+        },
+        (None, Some(name)) => {
+            let contact_info = match db.get_contact(name)? {
+                Some(contact) => contact,
+                None => return Err(TxtHistoryError::ContactNotFound(name.to_string()).into()),
+            };
+            progress(format!("Looking up messages for: {}", contact_info.name));
+            (contact_info.name, contact_info.details)
+        },
+        (None, None) => return Err(anyhow::anyhow!("Either --name or --chat-id is required")),
+    };
+    // Notes and the relationship would give away who an anonymized export is with
+    let template = match flags.anonymize {
+        Some(_) => template,
+        None => template.map(|template| template.with_contact_details(contact_details)),
+    };
+
+    let date_range = options.date_range();
+    let start_naive = date_range.start.map(|dt| dt.naive_local());
+    let end_naive = date_range.end.map(|dt| dt.naive_local());
+
+    if let Some(start) = &date_range.start {
+        progress(format!("Start date: {}", start.format("%Y-%m-%d")));
+    }
+    if let Some(end) = &date_range.end {
+        progress(format!("End date: {}", end.format("%Y-%m-%d")));
+    }
+    if !options.time_filter().is_empty() {
+        progress(format!("Only messages sent {}", options.time_filter().describe()));
+    }
+
+    // Fetch messages
+    progress("Fetching messages...");
+    let (db_messages, mut messages) = fetch_export_messages(
+        db,
+        &label,
+        chat_id,
+        start_naive,
+        end_naive,
+        &options.service(),
+        options.sources(),
+        options.time_filter(),
+    )?;
+    if messages.is_empty() {
+        return Err(TxtHistoryError::NoMessages(label).into());
+    }
+
+    if flags.summary {
+        let gap = chrono::Duration::minutes(sessions::DEFAULT_SESSION_GAP_MINUTES);
+        println!("{}", ConversationSummary::new(&messages, &label, gap).to_text());
+        return Ok(messages.len());
+    }
+
+    let mut redacted = options.style().redact(&mut messages)?;
+
+    // Recorded in the manifest so `verify-export` can run the query again
+    let recorded = ManifestQuery {
+        name: name.clone(),
+        chat_id: chat_id.clone(),
+        start_date: date_range.start.map(|dt| format_date_option(&dt)),
+        end_date: date_range.end.map(|dt| format_date_option(&dt)),
+        service: options.service().to_string(),
+        sources: options.sources().to_string(),
+        hours: options.time_filter().hours.map(|hours| hours.to_string()),
+        days: options.time_filter().days,
+        anonymized: flags.anonymize.is_some(),
+    };
+
+    // Pseudonyms are read from the map and saved back to it, so later exports reuse them
+    let label = match &flags.anonymize {
+        Some(map_path) => {
+            let mut map = PseudonymMap::load(map_path)?;
+            redacted.extend(map.anonymize(&mut messages)?);
+            let label = map.pseudonym(&label);
+            map.save(map_path)?;
+            progress(format!("Anonymized with the pseudonym map {}", map_path.display()));
+            label
+        },
+        None => label,
+    };
+
+    // The legal preset writes a single numbered document and always records its hashes
+    if let Some(legal) = options.legal() {
+        let preview =
+            ExportPreview::new(&messages, OutputFormat::Txt, options.csv_options(), options.style(), Chunking::Single)?;
+        if !confirm_export(&preview, output_dir, flags.yes)? {
+            progress("Export cancelled");
+            return Ok(0);
+        }
+
+        std::fs::create_dir_all(output_dir)?;
+        let written = write_legal_export(&messages, &label, legal, output_dir, options.policy())?;
+        progress(written.summary());
+        write_manifest(&messages, &[written], output_dir, Some(&recorded))?;
+        return Ok(messages.len());
+    }
+
+    // EPUB is one book for the whole conversation with a chapter per month, so chunking doesn't apply
+    if options.epub() {
+        let preview =
+            ExportPreview::new(&messages, OutputFormat::Txt, options.csv_options(), options.style(), Chunking::Single)?;
+        if !confirm_export(&preview, output_dir, flags.yes)? {
+            progress("Export cancelled");
+            return Ok(0);
+        }
+
+        std::fs::create_dir_all(output_dir)?;
+        let written = write_epub(&messages, &label, output_dir, options.policy())?;
+        progress(written.summary());
+        write_manifest(&messages, &[written], output_dir, Some(&recorded))?;
+        return Ok(messages.len());
+    }
+
+    // One stable file for the whole conversation, so repeated exports diff line by line
+    if options.canonical() {
+        let preview =
+            ExportPreview::new(&messages, OutputFormat::Txt, options.csv_options(), options.style(), Chunking::Single)?;
+        if !confirm_export(&preview, output_dir, flags.yes)? {
+            progress("Export cancelled");
+            return Ok(0);
+        }
+
+        std::fs::create_dir_all(output_dir)?;
+        let written = write_canonical_file(&messages, options.style(), output_dir, options.policy())?;
+        progress(written.summary());
+        if options.hashes() {
+            write_manifest(&messages, &[written], output_dir, Some(&recorded))?;
+        }
+        return Ok(messages.len());
+    }
+
+    // Look up NLP results by message ID to join into the Parquet columns
+    let nlp = match options.nlp_version() {
+        Some(_) if flags.metadata_chat_db.is_some() => None,
+        Some(version) => {
+            if template.is_some() || !matches!(options.format(), OutputFormat::Parquet) {
+                return Err(anyhow::anyhow!("--nlp-version only applies to --format parquet"));
+            }
+
+            // Processed text of redacted messages would still show what was redacted
+            let redacted: HashSet<&str> = redacted.iter().filter_map(|&i| messages[i].imessage_id.as_deref()).collect();
+            let ids: Vec<i32> = db_messages.iter().map(|m| m.id).collect();
+            let guids: HashMap<i32, &str> = db_messages.iter().map(|m| (m.id, m.imessage_id.as_str())).collect();
+            let lookup: NlpLookup = db
+                .get_processed_messages_for_ids(&ids, version)?
+                .into_iter()
+                .filter_map(|p| guids.get(&p.original_message_id).map(|guid| (guid.to_string(), p)))
+                .filter(|(guid, _)| !redacted.contains(guid.as_str()))
+                .collect();
+            progress(format!("Joining NLP results for {} messages from version {}", lookup.len(), version));
+            Some(lookup)
+        },
+        None => None,
+    };
+
+    // Show what will be written before creating any files
+    let preview_format = if template.is_some() { OutputFormat::Txt } else { options.format() };
+    let preview =
+        ExportPreview::new(&messages, preview_format, options.csv_options(), options.style(), options.chunking())?;
+    if !messages.is_empty() && !confirm_export(&preview, output_dir, flags.yes)? {
+        progress("Export cancelled");
+        return Ok(0);
+    }
+
+    // Create output directory if it doesn't exist
+    std::fs::create_dir_all(output_dir)?;
+
+    // Record each chunk of the merged transcript as it's written, so an interrupted export
+    // can be resumed. Templated and NLP exports are written again in full.
+    let progress = if template.is_none() && nlp.is_none() && !messages.is_empty() {
+        let description = format!(
+            "{}|{:?}|{:?}|{:?}|{:?}",
+            label,
+            options.format(),
+            options.chunking(),
+            options.csv_options(),
+            options.style()
+        );
+        let query_hash = export_hash(&description, &messages);
+        let progress = match flags.resume_export {
+            Some(export_id) => {
+                let progress = ExportProgress::resume(db, export_id, &query_hash, output_dir)?;
+                crate::verbosity::progress(format!(
+                    "Resuming export {} ({} of {} chunks already written)",
+                    export_id,
+                    progress.completed_count(),
+                    preview.chunk_count
+                ));
+                progress
+            },
+            None => ExportProgress::start(db, &query_hash, output_dir, preview.chunk_count)?,
+        };
+        crate::verbosity::progress(format!(
+            "Recording progress as export {} (resume with --resume-export {})",
+            progress.export_id(),
+            progress.export_id()
+        ));
+        Some(progress)
+    } else {
+        None
+    };
+
+    // Write messages to files
+    let mut written = match (&template, &nlp) {
+        (Some(template), _) => {
+            write_templated_files(
+                &messages,
+                template,
+                &label,
+                options.style(),
+                options.chunking(),
+                output_dir,
+                options.policy(),
+                options.parallel_writes(),
+            )?
+        },
+        (None, Some(nlp)) => write_parquet_files(
+            &messages,
+            nlp,
+            options.chunking(),
+            output_dir,
+            options.policy(),
+            options.parallel_writes(),
+        )?,
+        (None, None) => write_messages_with_progress(
+            &messages,
+            options.format(),
+            options.csv_options(),
+            options.style(),
+            options.chunking(),
+            output_dir,
+            options.policy(),
+            options.parallel_writes(),
+            progress.as_ref(),
+        )?,
+    };
+    // Metadata lines up with the chunk files, so it's written before the sender files are added
+    if let Some(chat_db) = &flags.metadata_chat_db {
+        let redacted: HashSet<&str> = redacted.iter().filter_map(|&i| messages[i].imessage_id.as_deref()).collect();
+        let version = options.nlp_version().unwrap_or("v1.0");
+        let index = export_metadata_index(db, &db_messages, &redacted, version, chat_db)?;
+        let files = write_metadata_files(&messages, &written, &index, options.chunking(), options.style(), options.policy())?;
+        for file in files {
+            crate::verbosity::progress(file.summary());
+        }
+    }
+    if options.split_by_sender() {
+        written.extend(write_sender_files(
+            &messages,
+            options.format(),
+            template.as_ref(),
+            &label,
+            options.csv_options(),
+            options.style(),
+            options.chunking(),
+            output_dir,
+            options.policy(),
+            options.parallel_writes(),
+        )?);
+    }
+    report_written(&messages, &written);
+    if options.hashes() {
+        write_manifest(&messages, &written, output_dir, Some(&recorded))?;
+    }
+    if let Some(progress) = &progress {
+        progress.finish()?;
+    }
+
+    Ok(messages.len())
+}
+
+/// Look up what `--with-metadata` records about exported messages: NLP results from
+/// `nlp_version`, except for redacted messages whose processed text would show what was
+/// redacted, and read times from chat.db, which are left out when it can't be opened
+fn export_metadata_index(
+    db: &Database,
+    db_messages: &[DbMessage],
+    redacted: &HashSet<&str>,
+    nlp_version: &str,
+    chat_db: &ChatDbConfig,
+) -> Result<MetadataIndex> {
+    let ids: Vec<i32> =
+        db_messages.iter().filter(|m| !redacted.contains(m.imessage_id.as_str())).map(|m| m.id).collect();
+    let processed = db.get_processed_messages_for_ids(&ids, nlp_version)?;
+
+    let guids: Vec<String> = db_messages.iter().map(|m| m.imessage_id.clone()).collect();
+    let conn = chat_db
+        .chat_db_path()
+        .and_then(|path| discovery::open_read_only(&path, &RetryPolicy::from_config(chat_db)));
+    let read_times = match conn {
+        Ok(conn) => discovery::read_times(&conn, &guids)?,
+        Err(e) => {
+            progress(format!("Leaving read times out of the metadata: {}", e));
+            HashMap::new()
+        },
+    };
+
+    progress(format!(
+        "Recording metadata with NLP results from version {} for {} messages and read times for {}",
+        nlp_version,
+        processed.len(),
+        read_times.len()
+    ));
+    Ok(MetadataIndex::new(db_messages, processed, read_times))
+}
+
+/// Export every message I sent across all conversations in date order, e.g. as a corpus of
+/// my own writing. Returns the number of messages written.
+fn export_my_messages(db: &Database, options: &ExportOptions, yes: bool) -> Result<usize> {
+    let output_dir = options.output_path().to_string_lossy();
+    let output_dir: &str = &output_dir;
+    let date_range = options.date_range();
+    if let Some(start) = &date_range.start {
+        progress(format!("Start date: {}", start.format("%Y-%m-%d")));
+    }
+    if let Some(end) = &date_range.end {
+        progress(format!("End date: {}", end.format("%Y-%m-%d")));
+    }
+    if !options.time_filter().is_empty() {
+        progress(format!("Only messages sent {}", options.time_filter().describe()));
+    }
+
+    // Fetch messages
+    progress("Fetching my messages across all conversations...");
+    let mut db_messages = db.get_my_messages(
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+    )?;
+    db_messages.retain(|m| options.service().matches(m.service.as_deref()));
+    db_messages.retain(|m| options.sources().matches(m.service.as_deref()));
+    db_messages.retain(|m| options.time_filter().matches(&Local.from_utc_datetime(&m.date_created)));
+    progress(format!("Found {} messages", db_messages.len()));
+
+    let messages = db.to_messages_with_transcripts(&db_messages)?;
+    let mut messages = resolve_conflicts(messages, options.sources());
+    options.style().redact(&mut messages)?;
+
+    // Show what will be written before creating any files
+    let preview =
+        ExportPreview::new(&messages, options.format(), options.csv_options(), options.style(), options.chunking())?;
+    if !messages.is_empty() && !confirm_export(&preview, output_dir, yes)? {
+        progress("Export cancelled");
+        return Ok(0);
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+    let written = write_messages_to_files(
+        &messages,
+        options.format(),
+        options.csv_options(),
+        options.style(),
+        options.chunking(),
+        output_dir,
+        options.policy(),
+        options.parallel_writes(),
+    )?;
+    report_written(&messages, &written);
+    if options.hashes() {
+        write_manifest(&messages, &written, output_dir, None)?;
+    }
+
+    Ok(messages.len())
+}
+
+/// Interleave several contacts' conversations into one transcript labeled by conversation,
+/// returning the number of messages written
+fn export_merged(
+    db: &Database,
+    contacts: &[String],
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    format: &str,
+    output_dir: &str,
+    style: &ExportStyle,
+    policy: WritePolicy,
+    hashes: bool,
+    yes: bool,
+) -> Result<usize> {
+    let output_format = match format.to_lowercase().as_str() {
+        "txt" => OutputFormat::Txt,
+        "csv" => OutputFormat::Csv,
+        "json" => OutputFormat::Json,
+        other => return Err(anyhow::anyhow!("Invalid format for a merged export: {}. Use txt, csv or json", other)),
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let mut conversations = Vec::new();
+    for name in contacts {
+        let contact = db
+            .get_contact(name)?
+            .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+        let db_messages = db.get_messages(
+            &contact.name,
+            date_range.start.map(|dt| dt.naive_local()),
+            date_range.end.map(|dt| dt.naive_local()),
+        )?;
+        let mut messages = db.to_messages_with_transcripts(&db_messages)?;
+        style.redact(&mut messages)?;
+        progress(format!("{}: {} messages", contact.name, messages.len()));
+        conversations.push((contact.name, messages));
+    }
+
+    let merged = merge_conversations(conversations);
+    if merged.is_empty() {
+        return Err(TxtHistoryError::NoMessages(contacts.join(", ")).into());
+    }
+
+    let question = format!("Write {} messages from {} conversations to {}?", merged.len(), contacts.len(), output_dir);
+    if !confirm(&question, yes)? {
+        progress("Export cancelled");
+        return Ok(0);
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+    let written = write_merged_file(&merged, output_format, style, output_dir, policy)?;
+    progress(written.summary());
+    if hashes {
+        let messages: Vec<Message> = merged.iter().map(|m| m.message.clone()).collect();
+        write_manifest(&messages, &[written], output_dir, None)?;
+    }
+
+    Ok(merged.len())
+}
+
+/// Export up to `before` and `after` messages around a target message, found by its ID or as
+/// the message closest to a time. The window comes from the conversation named with `--name`
+/// or `--chat-id`, otherwise from the target's own chat (or contact). Returns the number of
+/// messages written.
+fn export_context(
+    db: &Database,
+    message_id: Option<&str>,
+    at: &Option<String>,
+    name: &Option<String>,
+    chat_id: &Option<String>,
+    before: usize,
+    after: usize,
+    format: &str,
+    output_dir: &str,
+    style: &ExportStyle,
+    parallel_writes: usize,
+    yes: bool,
+) -> Result<usize> {
+    let named = match (chat_id, name) {
+        (Some(chat_id), _) => Some(Conversation::Thread(chat_id.clone())),
+        (None, Some(name)) => {
+            let contact = db
+                .get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+            Some(Conversation::Contact(contact.id))
+        },
+        (None, None) => None,
+    };
+
+    // Find the target message and the conversation to take the window from
+    let (target, conversation) = match (message_id, at) {
+        (Some(id), _) => {
+            // Database IDs are numbers; anything else is the message's own ID from an export
+            let by_database_id = match id.parse::<i32>() {
+                Ok(database_id) => db.get_message_by_id(database_id)?,
+                Err(_) => None,
+            };
+            let target = match by_database_id {
+                Some(target) => target,
+                None => db
+                    .get_message_by_imessage_id(id)?
+                    .ok_or_else(|| anyhow::anyhow!("Message not found: {}", id))?,
+            };
+            if target.hidden {
+                return Err(anyhow::anyhow!("Message {} is hidden; run `unhide {}` to export it", id, target.id));
+            }
+            let conversation = named.or_else(|| target.conversation()).ok_or_else(|| {
+                anyhow::anyhow!("Message {} isn't linked to a contact or chat; pass --name or --chat-id", id)
+            })?;
+            (target, conversation)
+        },
+        (None, Some(at)) => {
+            let conversation = named.ok_or_else(|| anyhow::anyhow!("--at needs --name or --chat-id"))?;
+            let target = db
+                .find_message_nearest(&conversation, parse_local_datetime(at)?)?
+                .ok_or_else(|| anyhow::anyhow!("No messages found in that conversation"))?;
+            (target, conversation)
+        },
+        (None, None) => return Err(anyhow::anyhow!("Either --message-id or --at is required")),
+    };
+
+    let target_message = target.to_message();
+    progress(format!(
+        "Target message {}: {}, {}, {}",
+        target.id,
+        target_message.sender,
+        target_message.timestamp.format("%b %d, %Y %r"),
+        target_message.content
+    ));
+
+    let window = db.get_message_context(&conversation, &target, before, after)?;
+    let mut messages = db.to_messages_with_transcripts(&window)?;
+    progress(format!("Found {} messages around it", messages.len() - 1));
+    style.redact(&mut messages)?;
+
+    // Determine output format
+    let output_format = match format.to_lowercase().as_str() {
+        "csv" => OutputFormat::Csv,
+        "json" => OutputFormat::Json,
+        "parquet" => OutputFormat::Parquet,
+        _ => OutputFormat::Txt,
+    };
+
+    // Show what will be written before creating any files
+    let output_dir = format!("{}/context_{}", output_dir, target.id);
+    let csv_options = CsvOptions::default().with_ids(style.include_ids);
+    let preview = ExportPreview::new(&messages, output_format, &csv_options, style, Chunking::Single)?;
+    if !confirm_export(&preview, &output_dir, yes)? {
+        progress("Export cancelled");
+        return Ok(0);
+    }
+
+    std::fs::create_dir_all(&output_dir)?;
+    let written = write_messages_to_files(
+        &messages,
+        output_format,
+        &csv_options,
+        style,
+        Chunking::Single,
+        &output_dir,
+        WritePolicy::Overwrite,
+        parallel_writes,
+    )?;
+    report_written(&messages, &written);
+
+    Ok(messages.len())
+}
+
+/// Parse a local time ("YYYY-MM-DD HH:MM[:SS]") into the UTC time messages are stored in
+fn search_messages(
+    db: &Database,
+    text: &str,
+    name: &Option<String>,
+    chat_id: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    context: usize,
+    export: Option<&str>,
+    style: &ExportStyle,
+) -> Result<usize> {
+    if text.trim().is_empty() {
+        return Err(anyhow::anyhow!("Search text can't be empty"));
+    }
+    let conversation = match (chat_id, name) {
+        (Some(chat_id), _) => Some(Conversation::Thread(chat_id.clone())),
+        (None, Some(name)) => {
+            let contact = db
+                .get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+            Some(Conversation::Contact(contact.id))
+        },
+        (None, None) => None,
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let matches = db.search_messages(
+        text,
+        conversation.as_ref(),
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+    )?;
+
+    // Each match with the messages around it in its own conversation. Matches are checked again
+    // after redaction, so searching for hidden text doesn't show which messages contained it.
+    let highlighter = Highlighter::new(text)?;
+    let mut results = Vec::new();
+    for target in &matches {
+        let window = match target.conversation() {
+            Some(conversation) if context > 0 => db.get_message_context(&conversation, target, context, context)?,
+            _ => vec![target.clone()],
+        };
+        let mut messages = db.to_messages_with_transcripts(&window)?;
+        style.redact(&mut messages)?;
+        let rows = window
+            .iter()
+            .zip(messages)
+            .map(|(db_message, message)| SearchRow {
+                message_id: db_message.id,
+                message,
+                is_match: db_message.id == target.id,
+            })
+            .collect::<Vec<_>>();
+        if rows.iter().any(|row| row.is_match && highlighter.is_match(&row.message.content)) {
+            results.push(SearchResult { rows });
+        }
+    }
+    if results.is_empty() {
+        println!("No messages contain \"{}\"", text);
+        return Ok(0);
+    }
+
+    match export {
+        Some(file_path) => {
+            if let Some(parent) = Path::new(file_path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            let contents = search::to_csv(&results, &highlighter)?;
+            let written = write_file(file_path, results.len(), WritePolicy::Overwrite, |writer, _| {
+                Ok(writer.write_all(contents.as_bytes())?)
+            })?;
+            println!("{}", written.summary());
+        },
+        None => print!("{}", search::to_text(&results, &highlighter)),
+    }
+    progress(format!("{} messages contain \"{}\"", results.len(), text));
+
+    Ok(results.len())
+}
+
+fn parse_local_datetime(value: &str) -> Result<NaiveDateTime> {
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid time: {}. Use YYYY-MM-DD HH:MM[:SS]", value))?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.naive_utc())
+        .ok_or_else(|| anyhow::anyhow!("{} doesn't exist in the local time zone", value))
+}
+
+/// Export conversation with a specific person, returning the number of files written
+async fn export_conversation_by_person(
+    database_url: &str,
+    name: &str,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
     size_mb: Option<f64>,
     lines_per_chunk: Option<usize>,
     output_dir: &str,
+    service: &str,
+    sources: &str,
+    style: &ExportStyle,
+    chat_db: &ChatDbConfig,
+    snapshot: bool,
+) -> Result<usize> {
+    let service_filter = ServiceFilter::parse(service)?;
+    let source_filter = SourceFilter::parse(sources)?;
+    progress(format!("Exporting conversation with {}", name));
+    
+    // Create output directory if it doesn't exist
+    std::fs::create_dir_all(output_dir)?;
+    
+    // Parse date range
+    let date_range = parse_date_range(start_date, end_date)?;
+    
+    // Create repository
+    let chat_db_path = chat_db.chat_db_path()?;
+    let (repo, _snapshot) = open_chat_db(chat_db_path, database_url, chat_db, snapshot)?;
+    let repo = repo
+        .with_service_filter(service_filter)
+        .with_source_filter(source_filter);
+    
+    // Create output path
+    let output_path = std::path::Path::new(output_dir).join(format!("{}_conversation", name));
+    
+    // Export conversation
+    let options = ExportOptions::new(output_path)
+        .with_date_range(date_range)
+        .with_chunking(Chunking::from_options(size_mb, lines_per_chunk, None))
+        .with_style(style.clone());
+    let output_files = repo.export_conversation_by_person(name, &options).await?;
+    
+    if output_files.is_empty() {
+        return Err(TxtHistoryError::NoMessages(format!("{} in the specified date range", name)).into());
+    }
+
+    progress(format!("Successfully exported conversation with {}:", name));
+    for file in &output_files {
+        progress(format!("  - {}", file.display()));
+    }
+    
+    Ok(output_files.len())
+}
+
+/// Process messages with NLP, returning the number of messages processed
+fn process_messages(
+    db: &Database,
+    version: &str,
+    name: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    batch_size: usize,
+    mut filter: ProcessFilter,
+    flag_config: Option<&FlagConfig>,
+    entity_patterns: &[EntityPattern],
+    throttle: ThrottleConfig,
+    show_stats: bool,
+) -> Result<usize> {
+    // Create NLP processor
+    let mut processor = NlpProcessor::new(version);
+    println!("Using NLP processor version: {}", version);
+
+    if let Some(flag_config) = flag_config {
+        processor = processor.with_flagger(LanguageFlagger::from_config(flag_config)?);
+        println!("Flagging profanity and abusive language");
+    }
+
+    let extractor = EntityExtractor::new(entity_patterns)?;
+    if !extractor.is_empty() {
+        println!("Finding {} custom entity patterns", entity_patterns.len());
+        processor = processor.with_entity_extractor(extractor);
+    }
+
+    // Parse date range
+    let date_range = parse_date_range(start_date, end_date)?;
+    filter.start_date = date_range.start.map(|dt| dt.naive_local());
+    filter.end_date = date_range.end.map(|dt| dt.naive_local());
+
+    if let Some(contact_name) = name {
+        // Get contact
+        let contact_info = match db.get_contact(contact_name)? {
+            Some(contact) => contact,
+            None => return Err(TxtHistoryError::ContactNotFound(contact_name.to_string()).into()),
+        };
+
+        println!("Processing messages for: {}", contact_info.name);
+        filter.contact_id = Some(contact_info.id);
+    }
+
+    // Get IDs of matching messages this version hasn't processed yet
+    let message_ids = db.get_unprocessed_message_ids_filtered(version, &filter)?;
+    println!("Found {} unprocessed messages", message_ids.len());
+
+    // Process messages in batches
+    let total_messages = message_ids.len();
+    let mut processed_count = 0;
+    let mut throttle = Throttle::new(throttle.pausing_every(batch_size));
+
+    for chunk in message_ids.chunks(batch_size) {
+        let batch_ids = chunk.to_vec();
+        let batch_size = batch_ids.len();
+        
+        println!("Processing batch of {} messages...", batch_size);
+        let report = processor.process_messages(db, &batch_ids)?;
+
+        processed_count += report.processed.len();
+        let batch_time = report.analysis_time + report.write_time;
+        println!(
+            "Processed {}/{} messages ({} new; analysis {:.0?}, write {:.0?}, {:.0} msg/s)",
+            processed_count,
+            total_messages,
+            report.inserted,
+            report.analysis_time,
+            report.write_time,
+            batch_size as f64 / batch_time.as_secs_f64().max(f64::EPSILON)
+        );
+        throttle.wait(report.inserted);
+    }
+
+    // Show statistics if requested
+    if show_stats {
+        let stats = db.get_processing_stats()?;
+        println!("\nProcessing Statistics:");
+        println!("Total messages in database: {}", stats.total_messages);
+        println!("Total processed messages: {}", stats.processed_messages);
+        println!("Processing versions: {:?}", stats.processing_versions);
+        println!("Messages by service:");
+        for (service, count) in &stats.messages_by_service {
+            println!("  {}: {}", service, count);
+        }
+        println!("{}", processor.lexicon_stats().summary());
+    }
+
+    println!("\nProcessing complete!");
+    Ok(processed_count)
+}
+
+/// Print every stored processing version, returning the number of versions
+fn list_processing_versions(db: &Database) -> Result<usize> {
+    let versions = db.list_processing_versions()?;
+    if versions.is_empty() {
+        println!("No processed messages stored");
+    }
+
+    for version in &versions {
+        println!(
+            "{}: {} messages, processed {} to {}",
+            version.version,
+            version.message_count,
+            version.first_processed_at.format("%Y-%m-%d %H:%M"),
+            version.last_processed_at.format("%Y-%m-%d %H:%M")
+        );
+    }
+
+    Ok(versions.len())
+}
+
+/// Compare two processing versions on a sample of messages, returning the number that differ
+fn diff_processing_versions(db: &Database, old: &str, new: &str, sample: usize) -> Result<usize> {
+    let shared = db.count_shared_processed(old, new)?;
+    println!("{} messages were processed with both {} and {}", shared, old, new);
+
+    let message_ids = db.sample_shared_processed_ids(old, new, sample)?;
+    let mut field_counts: Vec<(&str, usize)> = Vec::new();
+    let mut differing = 0;
+
+    for &message_id in &message_ids {
+        let (Some(old_processed), Some(new_processed)) = (
+            db.get_processed_message(message_id, old)?,
+            db.get_processed_message(message_id, new)?,
+        ) else {
+            continue;
+        };
+
+        let fields = changed_fields(&old_processed, &new_processed);
+        if fields.is_empty() {
+            continue;
+        }
+
+        // Show a few examples so the kind of change is visible, not just the counts
+        if differing < 5 {
+            println!("\nMessage {} ({})", message_id, fields.join(", "));
+            println!("  {}: {}", old, old_processed.processed_text);
+            println!("  {}: {}", new, new_processed.processed_text);
+            if fields.contains(&"sentiment_score") {
+                println!(
+                    "  sentiment: {:?} -> {:?}",
+                    old_processed.sentiment_score, new_processed.sentiment_score
+                );
+            }
+        }
+
+        differing += 1;
+        for field in fields {
+            match field_counts.iter_mut().find(|(name, _)| *name == field) {
+                Some((_, count)) => *count += 1,
+                None => field_counts.push((field, 1)),
+            }
+        }
+    }
+
+    println!("\n{} of {} sampled messages differ", differing, message_ids.len());
+    for (field, count) in &field_counts {
+        println!("  {}: {}", field, count);
+    }
+
+    Ok(differing)
+}
+
+/// Delete a processing version's stored output, returning the number of rows deleted
+fn purge_processing_version(db: &Database, version: &str) -> Result<usize> {
+    let deleted = db.purge_processing_version(version)?;
+    println!("Deleted {} processed messages for version {}", deleted, version);
+    Ok(deleted)
+}
+
+/// Print flagged message counts per sender per month, returning the number of flagged messages
+fn report_flags(db: &Database, version: &str, name: &Option<String>, min_severity: f32) -> Result<usize> {
+    let contact_id = match name {
+        Some(name) => Some(
+            db.get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?
+                .id,
+        ),
+        None => None,
+    };
+
+    let rows = db.get_flag_report(version, contact_id, min_severity)?;
+    if rows.is_empty() {
+        println!(
+            "No flagged messages for version {}. Run `process --flag-language --version {}` first",
+            version, version
+        );
+        return Ok(0);
+    }
+
+    println!("{:<8} {:<20} {:>8} {:>8} {:>8}", "Month", "Sender", "Flagged", "Max", "Average");
+    for row in &rows {
+        println!(
+            "{:<8} {:<20} {:>8} {:>8.2} {:>8.2}",
+            row.month, row.sender, row.flagged_messages, row.max_severity, row.average_severity
+        );
+    }
+
+    Ok(rows.iter().map(|row| row.flagged_messages).sum())
+}
+
+/// List stored named entities matching `filter`, returning the number listed
+fn report_entities(db: &Database, version: &str, name: &Option<String>, filter: &EntityFilter) -> Result<usize> {
+    let contact_id = match name {
+        Some(name) => Some(
+            db.get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?
+                .id,
+        ),
+        None => None,
+    };
+
+    let messages = db.get_entity_messages(version, contact_id, filter.entity_type.as_deref())?;
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+
+    for message in &messages {
+        for entity in message.entities.iter().filter(|entity| filter.matches(entity)) {
+            println!(
+                "{} {:<20} {:<16} {}",
+                message.sent_at.format("%Y-%m-%d %H:%M"),
+                message.sender,
+                entity.entity_type,
+                entity.text
+            );
+            *counts.entry(entity.entity_type.clone()).or_default() += 1;
+        }
+    }
+
+    if counts.is_empty() {
+        println!("No matching entities for version {}. Run `process --version {}` first", version, version);
+        return Ok(0);
+    }
+
+    println!();
+    for (entity_type, count) in &counts {
+        println!("{:<16} {:>8}", entity_type, count);
+    }
+
+    Ok(counts.values().sum())
+}
+
+/// Write messages processed with `version` and their NLP results to `nlp_<version>_<name>.csv`
+/// or `.json`, returning the number written
+fn export_nlp(
+    db: &Database,
+    version: &str,
+    name: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    format: &str,
+    output_dir: &str,
+    style: &ExportStyle,
+) -> Result<usize> {
+    let contact = match name {
+        Some(name) => Some(
+            db.get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?,
+        ),
+        None => None,
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let mut rows = db.get_nlp_export_rows(
+        version,
+        contact.as_ref().map(|c| c.id),
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+    )?;
+    nlp_export::redact(&mut rows, style)?;
+
+    let label = contact.as_ref().map(|c| c.name.as_str()).unwrap_or("all");
+    let (file_name, contents) = match format.to_lowercase().as_str() {
+        "csv" => (format!("nlp_{}_{}.csv", version, label), nlp_export::to_csv(&rows)?),
+        "json" => (format!("nlp_{}_{}.json", version, label), nlp_export::to_json(&rows)?),
+        other => return Err(anyhow::anyhow!("Unsupported NLP export format: {} (use csv or json)", other)),
+    };
+
+    if rows.is_empty() {
+        println!("No messages processed with version {}. Run `process --version {}` first", version, version);
+        return Ok(0);
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+    let file_path = format!("{}/{}", output_dir, file_name);
+    let written = write_file(&file_path, rows.len(), WritePolicy::Overwrite, |writer, _| {
+        Ok(writer.write_all(contents.as_bytes())?)
+    })?;
+    println!("{}", written.summary());
+
+    Ok(rows.len())
+}
+
+/// Detect questions, commitments and agreements per conversation, returning the number stored
+fn extract_commitments(
+    db: &Database,
+    name: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    answer_window_hours: i64,
+) -> Result<usize> {
+    let contacts = match name {
+        Some(name) => vec![db
+            .get_contact(name)?
+            .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?],
+        None => db.get_other_contacts()?,
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let extractor = CommitmentExtractor::new(chrono::Duration::hours(answer_window_hours));
+    let mut stored = 0;
+
+    for contact in contacts {
+        let mut messages = db.get_messages_for_contact_id(
+            contact.id,
+            date_range.start.map(|dt| dt.naive_local()),
+            date_range.end.map(|dt| dt.naive_local()),
+        )?;
+        messages.sort_by_key(|m| m.date_created);
+
+        let commitments = extractor.extract(&messages);
+        let open = commitments
+            .iter()
+            .filter(|c| c.kind == CommitmentKind::Question && c.answered_by.is_none())
+            .count();
+        println!(
+            "{}: {} questions, commitments and agreements ({} open questions)",
+            contact.name,
+            commitments.len(),
+            open
+        );
+
+        stored += db.save_commitments(&commitments)?;
+    }
+
+    Ok(stored)
+}
+
+/// Print open questions and commitments grouped by contact, returning the number listed
+fn report_commitments(
+    db: &Database,
+    name: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    all: bool,
+) -> Result<usize> {
+    let contacts = match name {
+        Some(name) => vec![db
+            .get_contact(name)?
+            .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?],
+        None => db.get_other_contacts()?,
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let mut listed = 0;
+
+    for contact in contacts {
+        let entries: Vec<_> = db
+            .get_commitment_report(
+                Some(contact.id),
+                date_range.start.map(|dt| dt.naive_local()),
+                date_range.end.map(|dt| dt.naive_local()),
+            )?
+            .into_iter()
+            .filter(|entry| all || entry.is_open() || entry.kind == CommitmentKind::Commitment)
+            .collect();
+        if entries.is_empty() {
+            continue;
+        }
+
+        println!("\n{}", contact.name);
+        for entry in &entries {
+            let date = Local.from_utc_datetime(&entry.date_created).format("%Y-%m-%d %H:%M");
+            let status = match (entry.kind, &entry.answer) {
+                (CommitmentKind::Question, None) => "open".to_string(),
+                (CommitmentKind::Question, Some(answer)) => format!("answered: {}", answer),
+                _ => String::new(),
+            };
+            println!(
+                "  {} {:<10} {}: {} {}",
+                date,
+                entry.kind.as_str(),
+                entry.sender,
+                entry.snippet,
+                status
+            );
+        }
+        listed += entries.len();
+    }
+
+    if listed == 0 {
+        println!("No open questions or commitments found. Run `extract-commitments` first");
+    }
+
+    Ok(listed)
+}
+
+/// Detect dates and plans per conversation, returning the number stored
+fn extract_events(
+    db: &Database,
+    name: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+) -> Result<usize> {
+    let contacts = match name {
+        Some(name) => vec![db
+            .get_contact(name)?
+            .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?],
+        None => db.get_other_contacts()?,
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let extractor = EventExtractor::default();
+    let mut stored = 0;
+
+    for contact in contacts {
+        let mut messages = db.get_messages_for_contact_id(
+            contact.id,
+            date_range.start.map(|dt| dt.naive_local()),
+            date_range.end.map(|dt| dt.naive_local()),
+        )?;
+        messages.sort_by_key(|m| m.date_created);
+
+        let events = extractor.extract(&messages);
+        println!("{}: {} dates and plans", contact.name, events.len());
+
+        stored += db.save_events(&events)?;
+    }
+
+    Ok(stored)
+}
+
+/// Summarize a conversation's days or sessions with the configured endpoint and write
+/// `summaries_<name>.md`. Cached summaries are reused and at most `limit` days or sessions are
+/// sent, so a long history can be summarized over several runs. Returns the number summarized
+/// in this run.
+fn summarize_conversation(
+    db: &Database,
+    config: &Config,
+    name: &str,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    unit: SummaryUnit,
+    output_dir: &str,
+    limit: Option<usize>,
+    refresh: bool,
+    dry_run: bool,
+) -> Result<usize> {
+    let contact = db
+        .get_contact(name)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let mut messages: Vec<Message> = db
+        .get_messages_for_contact_id(
+            contact.id,
+            date_range.start.map(|dt| dt.naive_local()),
+            date_range.end.map(|dt| dt.naive_local()),
+        )?
+        .iter()
+        .map(|m| m.to_message())
+        .collect();
+    if messages.is_empty() {
+        return Err(TxtHistoryError::NoMessages(contact.name).into());
+    }
+    messages.sort_by_key(|m| m.timestamp);
+    // Redaction rules apply before anything is sent off the machine
+    config.export_style.redact(&mut messages)?;
+
+    let settings = &config.summarizer;
+    let model = settings.model_name();
+    let inputs = split_inputs(&messages, unit, settings.max_batch_chars);
+    let mut summaries = HashMap::new();
+    let mut pending = Vec::new();
+    for input in &inputs {
+        match db.get_summary(&input.input_hash(), &model)? {
+            Some(summary) if !refresh => {
+                summaries.insert(input.key.clone(), summary);
+            },
+            _ => pending.push(input.clone()),
+        }
+    }
+    pending.truncate(limit.unwrap_or(pending.len()));
+    let batches = plan_batches(&pending, settings.batch_size, settings.max_batch_chars);
+    progress(format!(
+        "{} of {} {}s already summarized; sending {} in {} requests",
+        summaries.len(),
+        inputs.len(),
+        unit.as_str(),
+        pending.len(),
+        batches.len()
+    ));
+    if dry_run {
+        return Ok(0);
+    }
+
+    let mut summarized = 0;
+    if !batches.is_empty() {
+        let mut summarizer = open_summarizer(settings)?;
+        for (i, batch) in batches.iter().enumerate() {
+            detail(format!("Request {} of {}: {} {}s", i + 1, batches.len(), batch.len(), unit.as_str()));
+            // Each batch is saved as it arrives, so a failed request doesn't lose the earlier ones
+            for (key, summary) in summarizer.summarize(&contact.name, batch)? {
+                let Some(input) = batch.iter().find(|input| input.key == key) else { continue };
+                db.save_summary(&NewSummary {
+                    input_hash: input.input_hash(),
+                    model: model.clone(),
+                    unit: unit.as_str().to_string(),
+                    unit_key: key.clone(),
+                    summary: summary.clone(),
+                })?;
+                summaries.insert(key, summary);
+                summarized += 1;
+            }
+        }
+    }
+
+    let missing = inputs.len() - summaries.len();
+    if missing > 0 {
+        progress(format!("{} {}s aren't summarized yet; run summarize again to send them", missing, unit.as_str()));
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+    let file_path = format!("{}/summaries_{}.md", output_dir, contact.name);
+    let markdown = to_markdown(&contact.name, &inputs, &summaries);
+    let written = write_file(&file_path, summaries.len(), WritePolicy::Overwrite, |writer, _| {
+        Ok(writer.write_all(markdown.as_bytes())?)
+    })?;
+    progress(written.summary());
+
+    Ok(summarized)
+}
+
+/// Write `dashboard_<name>/index.html` and the chart script it loads, returning the number of
+/// messages charted
+fn report_dashboard(
+    db: &Database,
+    name: &str,
+    version: &str,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    output_dir: &str,
+) -> Result<usize> {
+    let contact = db
+        .get_contact(name)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let mut messages = db.get_messages_for_contact_id(
+        contact.id,
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+    )?;
+    if messages.is_empty() {
+        return Err(TxtHistoryError::NoMessages(contact.name).into());
+    }
+    messages.sort_by_key(|m| m.date_created);
+
+    let ids: Vec<i32> = messages.iter().map(|m| m.id).collect();
+    let sentiment: HashMap<i32, f32> = db
+        .get_processed_messages_for_ids(&ids, version)?
+        .into_iter()
+        .filter_map(|p| p.sentiment_score.map(|score| (p.original_message_id, score)))
+        .collect();
+    if sentiment.is_empty() {
+        println!("No sentiment scores for version {}. Run `process` to chart sentiment", version);
+    }
+
+    let dashboard = Dashboard::new(&messages, &sentiment, &contact.name, &db.my_name()?);
+    let dir = format!("{}/dashboard_{}", output_dir, contact.name);
+    std::fs::create_dir_all(&dir)?;
+
+    let total = dashboard.total();
+    let html = dashboard.to_html()?;
+    for (file_name, contents) in [("index.html", html.as_str()), ("chart.js", CHART_JS)] {
+        let file_path = format!("{}/{}", dir, file_name);
+        write_file(&file_path, total, WritePolicy::Overwrite, |writer, _| Ok(writer.write_all(contents.as_bytes())?))?;
+    }
+
+    println!("Charted {} messages over {} weeks in {}/index.html", total, dashboard.weeks.len(), dir);
+
+    Ok(total)
+}
+
+/// Write per-sender length, burst and session statistics to `style_<name>.csv` or `.json`,
+/// returning the number of messages measured
+fn report_style(
+    db: &Database,
+    name: &str,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    session_gap: i64,
+    format: &str,
+    output_dir: &str,
+) -> Result<usize> {
+    if session_gap <= 0 {
+        return Err(anyhow::anyhow!("--session-gap must be at least 1 minute"));
+    }
+
+    let contact = db
+        .get_contact(name)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let mut messages = db.get_messages_for_contact_id(
+        contact.id,
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+    )?;
+    if messages.is_empty() {
+        return Err(TxtHistoryError::NoMessages(contact.name).into());
+    }
+    messages.sort_by_key(|m| m.date_created);
+
+    let report = StyleReport::new(&messages, &contact.name, chrono::Duration::minutes(session_gap));
+    let (file_name, contents) = match format.to_lowercase().as_str() {
+        "csv" => (format!("style_{}.csv", contact.name), report.to_csv()?),
+        "json" => (format!("style_{}.json", contact.name), report.to_json()?),
+        other => return Err(anyhow::anyhow!("Unsupported style format: {} (use csv or json)", other)),
+    };
+
+    std::fs::create_dir_all(output_dir)?;
+    let file_path = format!("{}/{}", output_dir, file_name);
+    let written = write_file(&file_path, messages.len(), WritePolicy::Overwrite, |writer, _| {
+        Ok(writer.write_all(contents.as_bytes())?)
+    })?;
+
+    println!("{} sessions", report.sessions);
+    for style in &report.senders {
+        println!(
+            "{}: median {} characters per message, {} messages per burst, {} per session",
+            style.sender, style.length.p50, style.burst.p50, style.per_session.p50
+        );
+    }
+    println!("Wrote {}", written.path);
+
+    Ok(messages.len())
+}
+
+/// Write detected events to `events_<name>.ics` or `.csv`, returning the number written
+fn report_events(
+    db: &Database,
+    name: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    format: &str,
+    output_dir: &str,
+) -> Result<usize> {
+    let contact = match name {
+        Some(name) => Some(
+            db.get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?,
+        ),
+        None => None,
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let entries = db.get_event_report(
+        contact.as_ref().map(|c| c.id),
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+    )?;
+
+    if entries.is_empty() {
+        println!("No events found. Run `extract-events` first");
+        return Ok(0);
+    }
+
+    let label = contact.as_ref().map(|c| c.name.as_str()).unwrap_or("all");
+    let (file_name, contents) = match format.to_lowercase().as_str() {
+        "ics" => (format!("events_{}.ics", label), events::to_ics(&entries, label)),
+        "csv" => (format!("events_{}.csv", label), events::to_csv(&entries)?),
+        other => return Err(anyhow::anyhow!("Unsupported events format: {} (use ics or csv)", other)),
+    };
+
+    std::fs::create_dir_all(output_dir)?;
+    let file_path = format!("{}/{}", output_dir, file_name);
+    let written = write_file(&file_path, entries.len(), WritePolicy::Overwrite, |writer, _| {
+        Ok(writer.write_all(contents.as_bytes())?)
+    })?;
+    println!("Wrote {} events to {}", entries.len(), written.path);
+
+    Ok(entries.len())
+}
+
+/// Delete messages sent before a local date after showing what will be deleted, returning the
+/// number of messages deleted
+fn purge_messages(db: &Database, before: &str, contact: &Option<String>, dry_run: bool, yes: bool) -> Result<usize> {
+    let date = NaiveDate::parse_from_str(before, "%Y-%m-%d").context("Invalid --before date, use YYYY-MM-DD")?;
+    let cutoff = local_to_utc(&date.and_time(NaiveTime::MIN))
+        .ok_or_else(|| anyhow::anyhow!("{} doesn't start at midnight in the local time zone", before))?;
+    let contact_id = match contact {
+        Some(name) => Some(
+            db.get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?
+                .id,
+        ),
+        None => None,
+    };
+
+    let preview = db.purge_messages(cutoff, contact_id, &[], true)?;
+    let scope = contact.as_ref().map(|name| format!(" with {}", name)).unwrap_or_default();
+    println!("Messages{} sent before {}: {} will be deleted", scope, before, preview.summary());
+    if dry_run || preview.messages == 0 {
+        return Ok(0);
+    }
+    if !confirm("Permanently delete them?", yes)? {
+        println!("Purge cancelled");
+        return Ok(0);
+    }
+
+    let purged = db.purge_messages(cutoff, contact_id, &[], false)?;
+    println!("Deleted {}", purged.summary());
+    println!("Run `undo --last` to restore them");
+    Ok(purged.messages)
+}
+
+/// Restore the rows changed by the most recent purge or merge, returning the number of rows
+/// restored
+fn undo_last(db: &Database) -> Result<usize> {
+    match db.undo_last()? {
+        Some(journal) => {
+            println!(
+                "Undid \"{}\" from {}: restored {} rows",
+                journal.description,
+                journal.created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                journal.rows()
+            );
+            Ok(journal.rows())
+        },
+        None => {
+            println!("Nothing to undo");
+            Ok(0)
+        },
+    }
+}
+
+/// Print the purges and merges that can be undone, most recent first, returning the number listed
+fn list_undo_journals(db: &Database) -> Result<usize> {
+    let journals = db.undo_journals()?;
+    if journals.is_empty() {
+        println!("Nothing to undo");
+        return Ok(0);
+    }
+
+    println!("{:<17} {:<16} {:>8} {}", "Date", "Operation", "Rows", "Description");
+    for (_, journal) in journals.iter().rev() {
+        println!(
+            "{:<17} {:<16} {:>8} {}",
+            journal.created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            journal.operation,
+            journal.rows(),
+            journal.description
+        );
+    }
+
+    Ok(journals.len())
+}
+
+/// Hide messages from exports and reports, returning the number hidden
+fn hide_messages(db: &Database, message_ids: &[i32], reason: &str) -> Result<usize> {
+    let hidden = db.hide_messages(message_ids, reason)?;
+    println!("Hid {} of {} messages: {}", hidden, message_ids.len(), reason);
+    Ok(hidden)
+}
+
+/// Include hidden messages in exports and reports again, returning the number unhidden
+fn unhide_messages(db: &Database, message_ids: &[i32]) -> Result<usize> {
+    let unhidden = db.unhide_messages(message_ids)?;
+    println!("Unhid {} of {} messages", unhidden, message_ids.len());
+    Ok(unhidden)
+}
+
+/// Print every hidden message with its reason, returning the number listed
+fn list_hidden_messages(db: &Database) -> Result<usize> {
+    let hidden = db.get_hidden_messages()?;
+    if hidden.is_empty() {
+        println!("No hidden messages");
+        return Ok(0);
+    }
+
+    println!("{:<8} {:<17} {:<20} {:<24} {}", "ID", "Date", "Sender", "Reason", "Text");
+    for db_message in &hidden {
+        let message = db_message.to_message();
+        println!(
+            "{:<8} {:<17} {:<20} {:<24} {}",
+            db_message.id,
+            message.timestamp.format("%Y-%m-%d %H:%M"),
+            message.sender,
+            db_message.hidden_reason.as_deref().unwrap_or(""),
+            message.content.chars().take(60).collect::<String>()
+        );
+    }
+
+    Ok(hidden.len())
+}
+
+/// Print what the message cache holds
+fn cache_stats(cache: &MessageCache, config: &CacheConfig) -> Result<()> {
+    if !cache.is_enabled() {
+        println!("Message caching is disabled in the config");
+        return Ok(());
+    }
+    println!("{}", cache.stats()?.summary(config));
+    println!("Encrypted: {}", if cache.is_encrypted() { "yes" } else { "no" });
+    Ok(())
+}
+
+/// Remove every entry from the message cache
+fn clear_cache(cache: &MessageCache) -> Result<()> {
+    let entries = cache.stats()?.entries;
+    cache.clear_cache()?;
+    println!("Removed {} cached entries", entries);
+    Ok(())
+}
+
+/// Drop a contact's cached messages after an import changed them. The import itself succeeded,
+/// so a cache that can't be opened (e.g. held by another process) only gets a warning.
+fn invalidate_cached_messages(cache_dir: &Path, config: &CacheConfig, contact_name: &str) {
+    match MessageCache::invalidate_contact_at(cache_dir, config, contact_name) {
+        Ok(0) => {},
+        Ok(dropped) => detail(format!("Dropped {} cached entries for {}", dropped, contact_name)),
+        Err(e) => eprintln!("Warning: cached messages for {} may be stale: {:#}", contact_name, e),
+    }
+}
+
+/// Merge a duplicate contact into another after showing what will change, returning the
+/// number of messages moved
+fn merge_contacts(db: &Database, into: &str, from: &str, dry_run: bool, yes: bool) -> Result<usize> {
+    let into_contact = db
+        .get_contact(into)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(into.to_string()))?;
+    let from_contact = db
+        .get_contact(from)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(from.to_string()))?;
+
+    let preview = db.merge_contacts(&into_contact, &from_contact, true)?;
+    println!("{}", preview.summary(from, into));
+    if dry_run {
+        return Ok(0);
+    }
+    if !confirm("Merge these contacts?", yes)? {
+        println!("Merge cancelled");
+        return Ok(0);
+    }
+
+    let merged = db.merge_contacts(&into_contact, &from_contact, false)?;
+    println!("Merged {} into {}", from, into);
+    println!("Run `undo --last` to separate them again");
+    Ok(merged.messages)
+}
+
+/// Apply the given changes to a contact's details and print them
+fn edit_contact(
+    db: &Database,
+    name: &str,
+    relationship: &Option<String>,
+    timezone: &Option<String>,
+    birthday: &Option<String>,
+    notes: &Option<String>,
 ) -> Result<()> {
-    println!("Exporting conversation with {}", name);
-    
-    // Create output directory if it doesn't exist
+    let contact = db
+        .get_contact(name)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+
+    // None leaves a field alone, an empty value clears it
+    let cleared = |value: &String| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+    let mut details = contact.details.clone();
+    if let Some(relationship) = relationship {
+        details.relationship = cleared(relationship);
+    }
+    if let Some(timezone) = timezone {
+        details.timezone = cleared(timezone);
+        if details.timezone.as_deref().map_or(false, |tz| tz.contains(char::is_whitespace)) {
+            return Err(anyhow::anyhow!("Invalid --timezone, use an IANA name like America/Denver"));
+        }
+    }
+    if let Some(birthday) = birthday {
+        details.birthday = cleared(birthday)
+            .map(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d"))
+            .transpose()
+            .context("Invalid --birthday, use YYYY-MM-DD")?;
+    }
+    if let Some(notes) = notes {
+        details.notes = cleared(notes);
+    }
+
+    if details != contact.details {
+        db.update_contact_details(contact.id, &details)?;
+        println!("Updated {}", contact.name);
+    }
+
+    let field = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    println!("Name:         {}", contact.name);
+    println!("Relationship: {}", field(details.relationship));
+    println!("Time zone:    {}", field(details.timezone));
+    println!("Birthday:     {}", field(details.birthday.map(|date| date.format("%Y-%m-%d").to_string())));
+    println!("Notes:        {}", field(details.notes));
+    Ok(())
+}
+
+/// Take the day's snapshot unless one was already taken, and delete the snapshots the policy no
+/// longer keeps. Failures are only reported, so they don't stop the command that triggered it.
+fn take_daily_snapshot(db: &Database, store: &SnapshotStore, config: &SnapshotConfig, now: NaiveDateTime) {
+    if !config.enabled {
+        return;
+    }
+
+    let result = (|| -> Result<()> {
+        if !store.is_due(now)? {
+            return Ok(());
+        }
+        let snapshot = store.take(db, now)?;
+        progress(format!("Snapshot of the database written to {}", snapshot.path.display()));
+        for expired in store.rotate(config)? {
+            detail(format!("Deleted old snapshot {}", expired.name()));
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        eprintln!("Warning: database snapshot failed: {:#}", e);
+    }
+}
+
+/// Print the snapshots with when they were taken and their size
+fn list_snapshots(store: &SnapshotStore) -> Result<()> {
+    let snapshots = store.list()?;
+    if snapshots.is_empty() {
+        println!("No snapshots in {}", store.dir().display());
+        return Ok(());
+    }
+
+    println!("{:<28} {:<16} {:>10}", "Snapshot", "Taken", "Size");
+    for snapshot in &snapshots {
+        println!(
+            "{:<28} {:<16} {:>10}",
+            snapshot.name(),
+            snapshot.taken_at.format("%Y-%m-%d %H:%M"),
+            format_size(snapshot.bytes)
+        );
+    }
+    println!("{} snapshots in {}", snapshots.len(), store.dir().display());
+
+    Ok(())
+}
+
+/// Roll the database back to a snapshot after confirming, snapshotting the current database
+/// first so the restore can itself be undone
+fn restore_snapshot(db: &mut Database, store: &SnapshotStore, name: &str, yes: bool) -> Result<()> {
+    let snapshot = store.find(name)?;
+    println!(
+        "Restoring {} replaces everything in the database with its state on {}",
+        snapshot.name(),
+        snapshot.taken_at.format("%Y-%m-%d %H:%M")
+    );
+    if !confirm("Restore this snapshot?", yes)? {
+        println!("Restore cancelled");
+        return Ok(());
+    }
+
+    let current = store.take(db, Local::now().naive_local())?;
+    db.restore_from(&snapshot.path)?;
+    println!("Restored {}", snapshot.name());
+    println!("The database as it was before is in {}", current.name());
+    Ok(())
+}
+
+/// Check the database, rebuild its indexes and statistics and VACUUM it, returning the number
+/// of bytes reclaimed
+fn maintain_database(db: &Database) -> Result<usize> {
+    let size = db.database_size()?;
+    println!(
+        "Database is {} ({} unused after deletes)",
+        format_size(size.bytes()),
+        format_size(size.free_bytes())
+    );
+
+    let report = db.maintain()?;
+    if !report.problems.is_empty() {
+        for problem in &report.problems {
+            eprintln!("  {}", problem);
+        }
+        return Err(anyhow::anyhow!(
+            "The integrity check found {} problems; nothing was rebuilt. Restore a backup or export what you can",
+            report.problems.len()
+        ));
+    }
+
+    println!("Integrity check passed; rebuilt indexes and statistics");
+    println!(
+        "Database is now {} (reclaimed {})",
+        format_size(report.after.bytes()),
+        format_size(report.reclaimed())
+    );
+
+    Ok(report.reclaimed() as usize)
+}
+
+/// Print a conversation's language mix per month, who switches language and between which
+/// languages, returning the number of messages counted
+fn report_languages(
+    db: &Database,
+    name: &str,
+    version: &str,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    session_gap: i64,
+) -> Result<usize> {
+    if session_gap <= 0 {
+        return Err(anyhow::anyhow!("--session-gap must be at least 1 minute"));
+    }
+
+    let contact = db
+        .get_contact(name)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let samples = db.get_message_languages(
+        version,
+        contact.id,
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+    )?;
+    if samples.is_empty() {
+        println!(
+            "No processed messages with {} for version {}. Run `process --name \"{}\" --version {}` first",
+            contact.name, version, contact.name, version
+        );
+        return Ok(0);
+    }
+
+    let report = LanguageReport::new(&samples, chrono::Duration::minutes(session_gap));
+    let codes: Vec<&str> = report.languages.iter().map(|(code, _)| code.as_str()).collect();
+
+    print!("{:<8} {:>8}", "Month", "Messages");
+    for code in &codes {
+        print!(" {:>10}", languages::language_name(code));
+    }
+    println!(" {:>10} {:>8}", "Undetected", "Switches");
+    for month in &report.months {
+        print!("{:<8} {:>8}", month.month, month.messages);
+        for code in &codes {
+            print!(" {:>9.0}%", month.share(code) * 100.0);
+        }
+        println!(" {:>10} {:>8}", month.undetected(), month.switches);
+    }
+
+    println!();
+    println!(
+        "{} of {} sessions mixed languages, with {} switches",
+        report.mixed_sessions,
+        report.sessions,
+        report.total_switches()
+    );
+    for sender in &report.senders {
+        println!(
+            "{}: switched {} times in {} messages ({} mid-burst)",
+            sender.sender, sender.switches, sender.detected, sender.own_switches
+        );
+    }
+    for ((from, to), count) in report.transitions.iter().take(5) {
+        println!("{} -> {}: {}", languages::language_name(from), languages::language_name(to), count);
+    }
+
+    Ok(samples.len())
+}
+
+/// Print the most negative and most positive sentence of each processed message with at least
+/// `min_sentences` sentences, returning the number of messages listed
+fn report_sentences(
+    db: &Database,
+    name: &str,
+    version: &str,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    min_sentences: usize,
+) -> Result<usize> {
+    let contact = db
+        .get_contact(name)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let rows = db.get_nlp_export_rows(
+        version,
+        Some(contact.id),
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+    )?;
+    if rows.iter().all(|row| row.sentences.is_empty()) {
+        println!(
+            "No sentence sentiment with {} for version {}. Run `process --name \"{}\" --version {}` first",
+            contact.name, version, contact.name, version
+        );
+        return Ok(0);
+    }
+
+    let mut listed = 0;
+    for row in rows.iter().filter(|row| row.sentences.len() >= min_sentences.max(1)) {
+        let (negative, positive) = extreme_sentences(&row.sentences);
+        if negative.is_none() && positive.is_none() {
+            continue;
+        }
+
+        println!("{} {} (message {})", row.sent_at.format("%Y-%m-%d %H:%M"), row.sender, row.message_id);
+        if let Some(sentence) = negative {
+            println!("  {:>5.2}  {}", sentence.score, sentence.text);
+        }
+        if let Some(sentence) = positive {
+            println!("  {:>5.2}  {}", sentence.score, sentence.text);
+        }
+        listed += 1;
+    }
+
+    if listed == 0 {
+        println!("No messages with {} or more sentences and a positive or negative one", min_sentences);
+    }
+
+    Ok(listed)
+}
+
+/// Write message counts per day of week and hour, and per calendar day, to CSV files or a
+/// single JSON file, returning the number of messages counted
+fn report_heatmap(
+    db: &Database,
+    name: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    format: &str,
+    output_dir: &str,
+) -> Result<usize> {
+    let contact = match name {
+        Some(name) => Some(
+            db.get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?,
+        ),
+        None => None,
+    };
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let heatmap = db.get_activity_heatmap(
+        contact.as_ref().map(|c| c.id),
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+    )?;
+
+    let total = heatmap.total();
+    if total == 0 {
+        println!("No messages found");
+        return Ok(0);
+    }
+
     std::fs::create_dir_all(output_dir)?;
-    
-    // Parse date range
+    let label = contact.as_ref().map(|c| c.name.as_str()).unwrap_or("all");
+    let files = match format.to_lowercase().as_str() {
+        "json" => vec![(format!("heatmap_{}.json", label), heatmap.to_json(label)?)],
+        "csv" => vec![
+            (format!("heatmap_{}_weekly.csv", label), heatmap.weekly_csv()?),
+            (format!("heatmap_{}_daily.csv", label), heatmap.daily_csv()?),
+        ],
+        other => return Err(anyhow::anyhow!("Unsupported heatmap format: {} (use csv or json)", other)),
+    };
+
+    for (file_name, contents) in files {
+        let file_path = format!("{}/{}", output_dir, file_name);
+        let written = write_file(&file_path, total, WritePolicy::Overwrite, |writer, _| {
+            Ok(writer.write_all(contents.as_bytes())?)
+        })?;
+        println!("Wrote {}", written.path);
+    }
+
+    println!("{} messages over {} days", total, heatmap.daily.len());
+    if let Some((day, hour)) = heatmap.busiest_hour() {
+        println!("Busiest hour: {} {:02}:00", day, hour);
+    }
+
+    Ok(total)
+}
+
+/// Write the contact interaction graph to `contacts.graphml` or `contacts.dot`, returning the
+/// number of messages counted
+fn report_graph(
+    db: &Database,
+    version: &str,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    format: &str,
+    output_dir: &str,
+) -> Result<usize> {
+    let extension = match format.to_lowercase().as_str() {
+        "graphml" => "graphml",
+        "dot" => "dot",
+        other => return Err(anyhow::anyhow!("Unsupported graph format: {} (use graphml or dot)", other)),
+    };
+
     let date_range = parse_date_range(start_date, end_date)?;
-    
-    // Create repository
-    let chat_db_path = dirs::chat_db_path().context("Failed to get iMessage database path")?;
-    let repo = IMessageDatabaseRepo::new(chat_db_path)?;
-    
-    // Create output path
-    let output_path = std::path::Path::new(output_dir).join(format!("{}_conversation", name));
-    
-    // Export conversation
-    let output_files = repo.export_conversation_by_person(
-        name,
-        OutputFormat::Txt, // This is ignored as both formats are created
-        &output_path,
-        &date_range,
-        size_mb.map(|s| s as usize),
-        lines_per_chunk,
-    ).await?;
-    
-    if output_files.is_empty() {
-        println!("No messages found for {} in the specified date range", name);
+    let start = date_range.start.map(|dt| dt.naive_local());
+    let end = date_range.end.map(|dt| dt.naive_local());
+    // Both sides of every conversation are linked to a contact
+    let mut messages = Vec::new();
+    for contact in db.get_other_contacts()? {
+        messages.extend(db.get_messages_for_contact_id(contact.id, start, end)?);
+    }
+    if messages.is_empty() {
+        println!("No messages found");
+        return Ok(0);
+    }
+
+    let ids: Vec<i32> = messages.iter().map(|m| m.id).collect();
+    let sentiment: HashMap<i32, f32> = db
+        .get_processed_messages_for_ids(&ids, version)?
+        .into_iter()
+        .filter_map(|p| p.sentiment_score.map(|score| (p.original_message_id, score)))
+        .collect();
+    if sentiment.is_empty() {
+        println!("No sentiment scores for version {}. Run `process` to weight edges by sentiment", version);
+    }
+
+    let graph = InteractionGraph::new(&messages, &sentiment, &db.my_name()?);
+    let contents = if extension == "dot" { graph.to_dot() } else { graph.to_graphml() };
+
+    std::fs::create_dir_all(output_dir)?;
+    let file_path = format!("{}/contacts.{}", output_dir, extension);
+    let written = write_file(&file_path, messages.len(), WritePolicy::Overwrite, |writer, _| {
+        Ok(writer.write_all(contents.as_bytes())?)
+    })?;
+
+    println!("{} people and {} connections from {} messages", graph.nodes.len(), graph.edges.len(), messages.len());
+    println!("Wrote {}", written.path);
+
+    Ok(messages.len())
+}
+
+/// Print suspicious gaps in a conversation and months where chat.db holds more messages
+/// than were imported, returning the number of problems found
+fn audit_gaps(
+    db: &Database,
+    chat_db: &ChatDbConfig,
+    name: &str,
+    options: &GapOptions,
+    skip_chat_db: bool,
+) -> Result<usize> {
+    let contact = db
+        .get_contact(name)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+    let dates = db.get_message_dates_for_contact_id(contact.id)?;
+    println!("Auditing {} imported messages with {}", dates.len(), contact.name);
+
+    // Messages chat.db holds for the contact's one-on-one chats
+    let chat_db_dates = if skip_chat_db {
+        Vec::new()
     } else {
-        println!("Successfully exported conversation with {}:", name);
-        for file in output_files {
-            println!("  - {}", file.display());
+        let chat_db_path = chat_db.chat_db_path()?;
+        let conn = discovery::open_read_only(&chat_db_path, &RetryPolicy::from_config(chat_db))?;
+        let identifiers: Vec<String> = [&contact.phone, &contact.email]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+        discovery::one_on_one_message_dates(&conn, &identifiers)?
+    };
+
+    let format_date = |date: &NaiveDateTime| Local.from_utc_datetime(date).format("%Y-%m-%d %H:%M").to_string();
+    let mut problems = 0;
+
+    let gaps = find_gaps(&dates, options);
+    println!(
+        "\n{} gaps of {}+ days with at least {} messages in the {} days either side",
+        gaps.len(),
+        options.min_gap_days,
+        options.min_activity,
+        options.window_days
+    );
+    for gap in &gaps {
+        let in_chat_db = chat_db_dates
+            .iter()
+            .filter(|m| m.date > gap.start && m.date < gap.end)
+            .count();
+        let note = match in_chat_db {
+            0 if skip_chat_db => String::new(),
+            0 => "  (also empty in chat.db)".to_string(),
+            n => format!("  chat.db has {} messages here, import may have dropped them", n),
+        };
+        println!(
+            "  {} to {}  {} days  ({} before, {} after){}",
+            format_date(&gap.start),
+            format_date(&gap.end),
+            gap.days(),
+            gap.before,
+            gap.after,
+            note
+        );
+        problems += 1;
+    }
+
+    if !skip_chat_db {
+        let months: Vec<_> = compare_months(&dates, &chat_db_dates)
+            .into_iter()
+            .filter(|month| month.missing() > 0)
+            .collect();
+        println!("\n{} months with fewer imported messages than chat.db", months.len());
+        for month in &months {
+            println!(
+                "  {}  {} imported, {} in chat.db, {} missing ({} only in attributedBody)",
+                month.month,
+                month.imported,
+                month.chat_db,
+                month.missing(),
+                month.attributed_body_only
+            );
         }
+        problems += months.len();
+    }
+
+    Ok(problems)
+}
+
+/// Print attachments, or write them to `attachments_<name>.csv` when `csv_dir` is given,
+/// returning the number listed
+fn list_attachments(
+    db: &Database,
+    name: &Option<String>,
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    mime_type: &Option<String>,
+    csv_dir: Option<&str>,
+) -> Result<usize> {
+    let contact = match name {
+        Some(name) => Some(
+            db.get_contact(name)?
+                .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?,
+        ),
+        None => None,
+    };
+
+    // "image" means every image type, "image/png" just that one
+    let mime_prefix = mime_type
+        .as_deref()
+        .map(|t| if t.contains('/') { t.to_lowercase() } else { format!("{}/", t.to_lowercase()) });
+
+    let date_range = parse_date_range(start_date, end_date)?;
+    let entries = db.get_attachment_report(
+        contact.as_ref().map(|c| c.id),
+        date_range.start.map(|dt| dt.naive_local()),
+        date_range.end.map(|dt| dt.naive_local()),
+        mime_prefix.as_deref(),
+    )?;
+
+    if entries.is_empty() {
+        println!("No attachments found");
+        return Ok(0);
+    }
+
+    let total_bytes: i64 = entries.iter().filter_map(|e| e.size_bytes).sum();
+
+    match csv_dir {
+        Some(dir) => {
+            let label = contact.as_ref().map(|c| c.name.as_str()).unwrap_or("all");
+            let contents = attachments_csv(&entries)?;
+            std::fs::create_dir_all(dir)?;
+            let file_path = format!("{}/attachments_{}.csv", dir, label);
+            let written = write_file(&file_path, entries.len(), WritePolicy::Overwrite, |writer, _| {
+                Ok(writer.write_all(contents.as_bytes())?)
+            })?;
+            println!("Wrote {}", written.path);
+        },
+        None => {
+            for entry in &entries {
+                let file_name = entry
+                    .filename
+                    .as_deref()
+                    .and_then(|f| Path::new(f).file_name())
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                println!(
+                    "{}  {:<10}  {:<20}  {:>10}  {}  {}",
+                    Local.from_utc_datetime(&entry.date_created).format("%Y-%m-%d %H:%M"),
+                    entry.sender,
+                    entry.mime_type.as_deref().unwrap_or("unknown"),
+                    entry.size_bytes.map(|size| size.to_string()).unwrap_or_default(),
+                    file_name,
+                    entry.text.as_deref().unwrap_or("").replace('\n', " ")
+                );
+            }
+        },
+    }
+
+    println!("{} attachments, {:.1} MB", entries.len(), total_bytes as f64 / (1024.0 * 1024.0));
+
+    Ok(entries.len())
+}
+
+/// Run attachment processors over attachments that have not been transcribed yet,
+/// returning the number of transcripts stored
+fn transcribe_attachments(
+    db: &Database,
+    whisper_model: &Option<String>,
+    whisper_bin: &str,
+    tesseract_bin: &str,
+    no_ocr: bool,
+) -> Result<usize> {
+    let mut pipeline = AttachmentPipeline::new();
+
+    if let Some(model) = whisper_model {
+        pipeline.add_processor(Box::new(WhisperProcessor::new(whisper_bin, model)));
     }
-    
-    Ok(())
+
+    if !no_ocr {
+        pipeline.add_processor(Box::new(OcrProcessor::new(tesseract_bin)));
+    }
+
+    println!("Transcribing attachments...");
+    let stored = pipeline.process_pending(db)?;
+    println!("Stored {} new transcripts", stored);
+
+    Ok(stored)
 }
 
-/// Process messages with NLP
-fn process_messages(
+/// Import emails into the database, returning the number of messages saved
+fn import_email(
     db: &Database,
-    version: &str,
-    name: &Option<String>,
-    start_date: &Option<String>,
-    end_date: &Option<String>,
-    batch_size: usize,
-    show_stats: bool,
-) -> Result<()> {
-    // Create NLP processor
-    let processor = NlpProcessor::new(version);
-    println!("Using NLP processor version: {}", version);
+    path: &str,
+    my_emails: &[String],
+    me: &MeConfig,
+    import_filter: &ImportFilter,
+    senders: &SenderMap,
+) -> Result<usize> {
+    // Always treat the addresses in the me config section as mine
+    let mut my_addresses = my_emails.to_vec();
+    my_addresses.extend(me.emails().map(String::from));
 
-    // Parse date range
-    let date_range = parse_date_range(start_date, end_date)?;
-    let start_naive = date_range.start.map(|dt| dt.naive_local());
-    let end_naive = date_range.end.map(|dt| dt.naive_local());
-
-    // Get message IDs to process
-    let message_ids = if let Some(contact_name) = name {
-        // Get contact
-        let contact_info = match db.get_contact(contact_name)? {
-            Some(contact) => contact,
-            None => return Err(anyhow::anyhow!("Contact not found: {}", contact_name)),
-        };
+    println!("Reading emails from {}", path);
+    let messages = email::read_emails(std::path::Path::new(path), &my_addresses)?;
+    println!("Found {} emails", messages.len());
 
-        println!("Processing messages for: {}", contact_info.name);
+    let summary = save_imported_messages(db, &messages, import_filter, senders)?;
+    println!("Imported {} emails", summary.imported);
+    if summary.skipped.total() > 0 {
+        println!("{}", summary.skipped.summary());
+    }
+    if summary.unmatched > 0 {
+        println!("{} emails didn't match any contact's email address", summary.unmatched);
+    }
 
-        // Fetch messages
-        let db_messages = db.get_messages(&contact_info.name, start_naive, end_naive)?;
-        println!("Found {} messages to process", db_messages.len());
+    Ok(summary.imported)
+}
 
-        // Get message IDs
-        db_messages.into_iter().map(|m| m.id).collect::<Vec<_>>()
-    } else {
-        // Get all unprocessed message IDs
-        let unprocessed_ids = db.get_unprocessed_message_ids(version)?;
-        println!("Found {} unprocessed messages", unprocessed_ids.len());
-        unprocessed_ids
+/// Import a Telegram export into the database, returning the number of messages saved
+fn import_telegram(
+    db: &Database,
+    path: &str,
+    my_name: &Option<String>,
+    import_filter: &ImportFilter,
+    senders: &SenderMap,
+) -> Result<usize> {
+    let my_name = match my_name {
+        Some(name) => Some(name.clone()),
+        None => db.get_me_contact()?.map(|me| me.name),
     };
 
-    // Process messages in batches
-    let total_messages = message_ids.len();
-    let mut processed_count = 0;
+    println!("Reading Telegram export from {}", path);
+    let messages = telegram::read_telegram_export(std::path::Path::new(path), my_name.as_deref())?;
+    println!("Found {} messages", messages.len());
 
-    for chunk in message_ids.chunks(batch_size) {
-        let batch_ids = chunk.to_vec();
-        let batch_size = batch_ids.len();
-        
-        println!("Processing batch of {} messages...", batch_size);
-        let processed = processor.process_messages(db, &batch_ids)?;
-        
-        processed_count += processed.len();
-        println!("Processed {}/{} messages", processed_count, total_messages);
+    let summary = save_imported_messages(db, &messages, import_filter, senders)?;
+    println!("Imported {} messages", summary.imported);
+    if summary.skipped.total() > 0 {
+        println!("{}", summary.skipped.summary());
     }
-
-    // Show statistics if requested
-    if show_stats {
-        let stats = db.get_processing_stats()?;
-        println!("\nProcessing Statistics:");
-        println!("Total messages in database: {}", stats.total_messages);
-        println!("Total processed messages: {}", stats.processed_messages);
-        println!("Processing versions: {:?}", stats.processing_versions);
+    if summary.unmatched > 0 {
+        println!("{} messages didn't match any contact by name or sender_map entry", summary.unmatched);
     }
 
-    println!("\nProcessing complete!");
-    Ok(())
+    Ok(summary.imported)
 }
 
-/// Get contact information by name
-fn get_contact_info(name: &str) -> Result<Contact> {
-    // For now, we'll just create a contact with the given name
-    // In a real application, you might look up the contact in an address book
-    let contact = match name {
-        "Jess" => Contact {
-            name: "Jess".to_string(),
-            phone: None,
-            email: None,
-        },
-        "Phil" => Contact {
-            name: "Phil".to_string(),
-            phone: Some("+18673335566".to_string()),
-            email: Some("apple@phil-g.com".to_string()),
-        },
-        "Robert" => Contact {
-            name: "Robert".to_string(),
-            phone: Some("+17806793467".to_string()),
-            email: None,
-        },
-        "Rhonda" => Contact {
-            name: "Rhonda".to_string(),
-            phone: Some("+17803944504".to_string()),
-            email: None,
-        },
-        "Sherry" => Contact {
-            name: "Sherry".to_string(),
-            phone: Some("+17807223445".to_string()),
-            email: None,
-        },
-        _ => {
-            return Err(anyhow::anyhow!(
-                "Contact not found: {}. Available contacts: Jess, Phil, Robert, Rhonda, Sherry",
-                name
-            ))
-        }
+/// Import this tool's own exports back into the database, returning the number of messages imported
+fn restore_exports(
+    db: &Database,
+    path: &str,
+    name: &str,
+    my_name: &Option<String>,
+    date_format: &DateFormat,
+) -> Result<usize> {
+    let my_name = match my_name {
+        Some(name) => name.clone(),
+        None => db
+            .get_me_contact()?
+            .map(|me| me.name)
+            .ok_or_else(|| anyhow::anyhow!("No contact is marked as me; pass --my-name"))?,
     };
 
-    Ok(contact)
+    println!("Reading exports from {}", path);
+    let messages = export::read_exports(std::path::Path::new(path), name, &my_name, date_format)?;
+    println!("Found {} messages", messages.len());
+
+    // Restores bring back everything the exports hold
+    let summary = save_imported_messages(db, &messages, &ImportFilter::default(), &SenderMap::default())?;
+    println!("Imported {} messages", summary.imported);
+    if summary.unmatched > 0 {
+        println!("{} messages didn't match a contact named {}", summary.unmatched, name);
+    }
+
+    Ok(summary.imported)
 }
 
-/// Parse date range from string options
-fn parse_date_range(
-    start_date: &Option<String>,
-    end_date: &Option<String>,
-) -> Result<DateRange> {
-    let start = if let Some(date_str) = start_date {
-        Some(
-            DateTime::parse_from_str(&format!("{} 00:00:00 +0000", date_str), "%Y-%m-%d %H:%M:%S %z")
-                .context("Invalid start date format, use YYYY-MM-DD")?
-                .with_timezone(&Local),
-        )
-    } else {
-        None
+/// Print every profile with its database, cache and output locations, returning the number of profiles
+fn list_profiles(current: &Profile) -> Result<usize> {
+    let names = Profile::list()?;
+
+    for name in &names {
+        let profile = Profile::load(Some(name))?;
+        let marker = if name == current.name() { "*" } else { " " };
+        println!("{} {}", marker, profile.name());
+        println!("    database: {}", profile.database_url());
+        println!("    cache:    {}", profile.cache_dir().display());
+        println!("    output:   {}", profile.output_dir().display());
+    }
+
+    Ok(names.len())
+}
+
+/// First-run setup: find chat.db, record who the user is, import matching people from
+/// Contacts, write the config file and list the busiest chats. Returns the number of
+/// contacts imported.
+fn init(db: &Database, mut config: Config) -> Result<usize> {
+    println!("Setting up txt-history. Press Enter to keep the value in brackets.");
+
+    // chat.db
+    let chat_db_path = match config.chat_db.chat_db_path() {
+        Ok(path) => {
+            println!("Found chat.db at {}", path.display());
+            Some(path)
+        },
+        Err(e) => {
+            println!("{}", e);
+            let answer = prompt("Path to chat.db (blank to skip)", "")?;
+            if answer.is_empty() {
+                None
+            } else {
+                config.chat_db.path = Some(PathBuf::from(answer));
+                Some(config.chat_db.chat_db_path()?)
+            }
+        },
     };
 
-    let end = if let Some(date_str) = end_date {
-        Some(
-            DateTime::parse_from_str(&format!("{} 23:59:59 +0000", date_str), "%Y-%m-%d %H:%M:%S %z")
-                .context("Invalid end date format, use YYYY-MM-DD")?
-                .with_timezone(&Local),
-        )
-    } else {
-        None
+    // Who "me" is in exports
+    let name = prompt("Your name", &config.me.name)?;
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("A name is required"));
+    }
+    let identifiers = prompt("Phone numbers and emails you send from, comma separated", &config.me.identifiers.join(", "))?;
+    config.me.name = name;
+    config.me.identifiers = identifiers
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect();
+    let me = db.sync_me(&config.me)?;
+    println!("Your messages will be attributed to {}", me.name);
+
+    // People from Contacts who appear in chat.db
+    let conn = match &chat_db_path {
+        Some(path) => Some(discovery::open_read_only(path, &RetryPolicy::from_config(&config.chat_db))?),
+        None => None,
     };
+    let mut imported = 0;
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let books = home.as_deref().map(address_book_paths).unwrap_or_default();
+    if let (Some(conn), false) = (&conn, books.is_empty()) {
+        let handles = discovery::discover_handles(conn, None)?;
+        // Each person once, under the chat.db handle they message from
+        let mut candidates: BTreeMap<String, String> = BTreeMap::new();
+        for book in &books {
+            for entry in read_address_book(book)? {
+                let Some(handle) = handles.iter().find(|h| entry.matching_identifier(&h.identifier).is_some()) else {
+                    continue;
+                };
+                if db.find_contact_by_identifier(&handle.identifier)?.is_none() {
+                    candidates.entry(entry.name).or_insert_with(|| handle.identifier.clone());
+                }
+            }
+        }
 
-    Ok(DateRange { start, end })
+        if candidates.is_empty() {
+            println!("Everyone in Contacts you've messaged is already a contact");
+        } else if confirm(&format!("Import {} people you've messaged from Contacts?", candidates.len()), false)? {
+            for (name, identifier) in candidates {
+                let is_email = identifier.contains('@');
+                db.add_or_update_contact(NewContact {
+                    name,
+                    phone: (!is_email).then(|| identifier.clone()),
+                    email: is_email.then_some(identifier),
+                    is_me: false,
+                    primary_identifier: None,
+                })?;
+                imported += 1;
+            }
+            println!("Imported {} contacts", imported);
+        }
+    }
+
+    let config_path = Config::path();
+    config.save_to(&config_path)?;
+    println!("Wrote {}", config_path.display());
+
+    // Show that chat.db can be read
+    if let Some(conn) = &conn {
+        let chats = discovery::discover_chats(conn, None)?;
+        println!("chat.db has {} chats. The busiest:", chats.len());
+        for chat in chats.iter().take(5) {
+            let name = match chat.participants.as_slice() {
+                [participant] => db.find_contact_by_identifier(participant)?.map(|c| c.name),
+                _ => None,
+            };
+            println!(
+                "  {:<28} {:<20} {:>8} messages",
+                chat.chat_identifier,
+                name.or_else(|| chat.display_name.clone()).unwrap_or_default(),
+                chat.message_count
+            );
+        }
+        println!("Import one with: txt-history import --name NAME");
+    }
+
+    Ok(imported)
 }
 
-/// Write messages to files with chunking
-fn write_messages_to_files(
-    messages: &[models::Message],
-    format: OutputFormat,
-    size_mb: Option<f64>,
-    lines_per_chunk: Option<usize>,
-    output_dir: &str,
-) -> Result<()> {
-    if messages.is_empty() {
-        println!("No messages to write");
-        return Ok(());
+/// Ask for a line of input, keeping `default` when the answer is blank or stdin isn't a terminal
+fn prompt(question: &str, default: &str) -> Result<String> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(default.to_string());
     }
 
-    // Determine chunking strategy
-    let chunk_size = if let Some(size) = size_mb {
-        // Estimate bytes per message and calculate chunk size
-        let avg_msg_size = messages
-            .iter()
-            .map(|m| m.content.len() + m.sender.len() + 50) // Add some overhead for formatting
-            .sum::<usize>() as f64
-            / messages.len() as f64;
-
-        let bytes_per_mb = 1024.0 * 1024.0;
-        let msgs_per_chunk = (size * bytes_per_mb / avg_msg_size).ceil() as usize;
-        msgs_per_chunk.max(1) // Ensure at least one message per chunk
-    } else if let Some(lines) = lines_per_chunk {
-        lines
+    if default.is_empty() {
+        print!("{}: ", question);
     } else {
-        // Default to all messages in one file
-        messages.len()
+        print!("{} [{}]: ", question, default);
+    }
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+/// Run scheduled tasks from the config until the process is stopped
+async fn watch(
+    db: &Database,
+    config: &Config,
+    notifier: &Notifier,
+    database_url: &str,
+    snapshots: &SnapshotStore,
+    interval_secs: u64,
+) -> Result<()> {
+    let export_schedule = match &config.export_schedule {
+        Some(schedule) => Some((schedule, CronSchedule::parse(&schedule.cron)?)),
+        None => None,
     };
 
-    // Create chunks
-    let chunks: Vec<_> = messages.chunks(chunk_size).collect();
-    println!("Writing {} chunks", chunks.len());
+    if export_schedule.is_none() {
+        println!("No export schedule configured in {}", Config::path().display());
+    }
+
+    println!("Watching (checking every {}s). Press Ctrl+C to stop.", interval_secs);
+
+    // Each check covers the minutes since the previous one, so a late check never skips the
+    // schedule and a short interval never runs it twice
+    let mut last_schedule_check: Option<DateTime<Local>> = None;
+    // Retention limits are enforced on start and then once a day
+    let mut last_retention: Option<NaiveDate> = None;
+    // Snapshots are taken on start and then once a day, unless an import took one already
+    let mut last_snapshot: Option<NaiveDate> = None;
+    let mut sentiment_monitor = config
+        .sentiment_alerts
+        .is_enabled()
+        .then(|| SentimentMonitor::new(&config.sentiment_alerts).with_throttle(config.throttle));
 
-    // Process each chunk
-    for (i, chunk) in chunks.iter().enumerate() {
-        let chunk_num = i + 1;
-        let file_base = format!("{}/chunk_{}", output_dir, chunk_num);
+    loop {
+        let now = Local::now();
+
+        if config.snapshots.enabled && last_snapshot != Some(now.date_naive()) {
+            last_snapshot = Some(now.date_naive());
+
+            // Taken before the retention purge so the purged messages are in it
+            let _lock = DatabaseLock::wait(database_url, LockMode::Shared)?;
+            take_daily_snapshot(db, snapshots, &config.snapshots, now.naive_local());
+        }
+
+        if config.retention.is_enabled() && last_retention != Some(now.date_naive()) {
+            last_retention = Some(now.date_naive());
+
+            // Wait for manual commands rather than skipping the day's purge
+            let _lock = DatabaseLock::wait(database_url, LockMode::Exclusive("purging old messages"))?;
+            let started = Instant::now();
+            let result = enforce_retention(db, &config.retention, now);
+            match &result {
+                Ok(purged) => println!("Retention purge deleted {}", purged.summary()),
+                Err(e) => eprintln!("Retention purge failed: {:#}", e),
+            }
+
+            let result = result.map(|purged| purged.messages);
+            notifier.notify(&OperationReport::new("Retention purge", &result, started.elapsed()));
+            if matches!(result, Ok(purged) if config.maintenance.is_due(purged)) {
+                if let Err(e) = maintain_database(db) {
+                    eprintln!("Database maintenance failed: {:#}", e);
+                }
+            }
+        }
 
-        match format {
-            OutputFormat::Txt => {
-                let file_path = format!("{}.txt", file_base);
-                write_txt_file(chunk, &file_path)?;
-                println!("Wrote {} messages to {}", chunk.len(), file_path);
+        if let Some(monitor) = &mut sentiment_monitor {
+            // Scoring new messages stores the results, so this writes
+            let _lock = DatabaseLock::wait(database_url, LockMode::Exclusive("checking sentiment"))?;
+            match monitor.check(db) {
+                Ok(alerts) => {
+                    for alert in alerts {
+                        println!("{}", alert.summary());
+                        notifier.alert(&alert);
+                    }
+                },
+                Err(e) => eprintln!("Sentiment check failed: {:#}", e),
             }
-            OutputFormat::Csv => {
-                let file_path = format!("{}.csv", file_base);
-                write_csv_file(chunk, &file_path)?;
-                println!("Wrote {} messages to {}", chunk.len(), file_path);
+        }
+
+        if let Some((schedule, cron)) = &export_schedule {
+            let due = match &last_schedule_check {
+                Some(last_check) => cron.fires_between(last_check, &now),
+                None => cron.matches(&now),
+            };
+            last_schedule_check = Some(now);
+
+            if due {
+                let _lock = DatabaseLock::wait(database_url, LockMode::Shared)?;
+                let started = Instant::now();
+                let result = run_scheduled_export(db, schedule, &config.export_style, config.export.parallel_writes, now);
+                match &result {
+                    Ok(Some(dir)) => println!("Scheduled export written to {}", dir.display()),
+                    Ok(None) => println!("Scheduled export for last month already exists"),
+                    Err(e) => eprintln!("Scheduled export failed: {:#}", e),
+                }
+
+                let result = result.map(|dir| usize::from(dir.is_some()));
+                notifier.notify(&OperationReport::new("Scheduled export", &result, started.elapsed()));
             }
         }
+
+        tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+    }
+}
+
+/// Print the handles in chat.db and which contact each belongs to, returning the number listed
+fn list_chat_db_handles(db: &Database, chat_db: &ChatDbConfig, filter: &Option<String>) -> Result<usize> {
+    let chat_db_path = chat_db.chat_db_path()?;
+    let conn = discovery::open_read_only(&chat_db_path, &RetryPolicy::from_config(chat_db))?;
+    let handles = discovery::discover_handles(&conn, filter.as_deref())?;
+
+    println!(
+        "{:<28} {:<9} {:>8}  {:<16} {:<16} {}",
+        "Handle", "Service", "Messages", "Last message", "Contact", "Chats"
+    );
+    for handle in &handles {
+        let last_message = handle
+            .last_message
+            .map(|dt| Local.from_utc_datetime(&dt).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let contact = db
+            .find_contact_by_identifier(&handle.identifier)?
+            .map(|contact| contact.name)
+            .unwrap_or_default();
+        println!(
+            "{:<28} {:<9} {:>8}  {:<16} {:<16} {}",
+            handle.identifier,
+            handle.service,
+            handle.message_count,
+            last_message,
+            contact,
+            handle.chats.join(", ")
+        );
+    }
+    println!("{} handles", handles.len());
+
+    Ok(handles.len())
+}
+
+/// Print the chats in chat.db with their participants, returning the number listed
+fn list_chat_db_chats(db: &Database, chat_db: &ChatDbConfig, filter: &Option<String>) -> Result<usize> {
+    let chat_db_path = chat_db.chat_db_path()?;
+    let conn = discovery::open_read_only(&chat_db_path, &RetryPolicy::from_config(chat_db))?;
+    let chats = discovery::discover_chats(&conn, filter.as_deref())?;
+
+    println!(
+        "{:<28} {:<20} {:<9} {:>8}  {:<16} {}",
+        "Chat ID", "Name", "Service", "Messages", "Last message", "Participants"
+    );
+    for chat in &chats {
+        let last_message = chat
+            .last_message
+            .map(|dt| Local.from_utc_datetime(&dt).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+
+        // Show contact names where the handle belongs to a known contact
+        let mut participants = Vec::with_capacity(chat.participants.len());
+        for identifier in &chat.participants {
+            participants.push(match db.find_contact_by_identifier(identifier)? {
+                Some(contact) => format!("{} ({})", contact.name, identifier),
+                None => identifier.clone(),
+            });
+        }
+
+        println!(
+            "{:<28} {:<20} {:<9} {:>8}  {:<16} {}",
+            chat.chat_identifier,
+            chat.display_name.as_deref().unwrap_or(""),
+            chat.service,
+            chat.message_count,
+            last_message,
+            participants.join(", ")
+        );
     }
+    println!("{} chats", chats.len());
+
+    Ok(chats.len())
+}
 
+/// Print the result of a read-only query, returning the number of rows
+fn run_sql(db: &Database, query: &str, format: &str) -> Result<usize> {
+    let output = SqlOutput::parse(format)?;
+    let result = db.run_read_only_query(query)?;
+    println!("{}", result.render(output)?);
+    Ok(result.rows.len())
+}
+
+/// Print the completion script for `shell` to stdout
+fn print_completions(shell: clap_complete::Shell) -> Result<()> {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
     Ok(())
 }
 
-/// Write messages to a text file
-fn write_txt_file(messages: &[models::Message], file_path: &str) -> Result<()> {
-    use std::fs::File;
-    use std::io::{BufWriter, Write};
+/// Write a man page for the command and one for each subcommand, returning how many were written
+fn write_man_pages(output_dir: &str) -> Result<usize> {
+    std::fs::create_dir_all(output_dir)?;
+    clap_mangen::generate_to(Cli::command(), output_dir)
+        .with_context(|| format!("Failed to write man pages to {}", output_dir))?;
 
-    let file = File::create(file_path)?;
-    let mut writer = BufWriter::new(file);
+    let pages = std::fs::read_dir(output_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "1"))
+        .count();
+    println!("Wrote {} man pages to {}", pages, output_dir);
+    println!("View one with: man {}/{}.1", output_dir, Cli::command().get_name());
 
-    for message in messages {
-        writeln!(
-            writer,
-            "{}, {}, {}\n",
-            message.sender,
-            message.timestamp.format("%b %d, %Y %r"),
-            message.content
+    Ok(pages)
+}
+
+/// Print the configured pipelines, returning how many there are
+fn list_pipelines(config: &Config) -> Result<usize> {
+    if config.pipelines.is_empty() {
+        println!("No pipelines configured in {}", Config::path().display());
+    }
+
+    for (name, pipeline) in &config.pipelines {
+        let steps: Vec<&str> = pipeline.steps.iter().map(|step| step.as_str()).collect();
+        println!("{}: {} ({})", name, steps.join(" -> "), pipeline.contacts.join(", "));
+    }
+
+    Ok(config.pipelines.len())
+}
+
+fn find_pipeline<'a>(config: &'a Config, name: &str) -> Result<&'a PipelineConfig> {
+    config
+        .pipelines
+        .get(name)
+        .ok_or_else(|| anyhow::anyhow!("No pipeline named {} in {}", name, Config::path().display()))
+}
+
+/// Print the status of a pipeline's latest run
+fn pipeline_status(config: &Config, profile: &Profile, name: &str) -> Result<usize> {
+    find_pipeline(config, name)?;
+
+    match PipelineRun::load(&profile.pipelines_dir().join(format!("{}.json", name)))? {
+        Some(run) => {
+            println!("Pipeline {} started {}", name, run.started_at.format("%Y-%m-%d %H:%M:%S"));
+            println!("{}", run.summary());
+        },
+        None => println!("Pipeline {} hasn't run yet", name),
+    }
+
+    Ok(0)
+}
+
+/// Run a pipeline's steps in order, saving each step's status so a failed run can be
+/// resumed. Returns the number of steps run.
+async fn run_pipeline(
+    db: &Database,
+    config: &Config,
+    profile: &Profile,
+    notifier: &Notifier,
+    database_url: &str,
+    name: &str,
+    resume: bool,
+) -> Result<usize> {
+    let pipeline = find_pipeline(config, name)?;
+    let status_path = profile.pipelines_dir().join(format!("{}.json", name));
+    let mut run = PipelineRun::start(name, pipeline, &status_path, resume)?;
+    let output_dir = match &pipeline.output_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => profile.output_dir().join(name),
+    };
+
+    let operation = format!("Pipeline {}", name);
+    let started = Instant::now();
+    let mut steps_run = 0;
+
+    while let Some(index) = run.next_step() {
+        let step = run.steps[index].step;
+        println!("\n[{}/{}] {}", index + 1, run.steps.len(), step.as_str());
+
+        let step_started = Instant::now();
+        let result = match step {
+            PipelineStep::Import => {
+                pipeline_import(db, pipeline, database_url, config, &profile.cache_dir()).await
+            },
+            PipelineStep::Dedupe => pipeline_dedupe(db, pipeline),
+            PipelineStep::Process => pipeline_process(db, pipeline, &config.entity_patterns),
+            PipelineStep::Digest => pipeline_digest(db, pipeline, &config.export_style, &output_dir),
+            PipelineStep::Export => {
+                pipeline_export(db, pipeline, &config.export_style, config.export.parallel_writes, &output_dir)
+            },
+            PipelineStep::Compress => compress_dir(&output_dir).map(|archive| {
+                println!("Wrote {}", archive.display());
+                1
+            }),
+            PipelineStep::Notify => {
+                let count = run.last_count(index);
+                notifier.notify(&OperationReport::new(&operation, &Ok(count), started.elapsed()));
+                Ok(count)
+            },
+        };
+
+        if let Err(e) = run.finish_step(index, result, step_started.elapsed()) {
+            println!("\n{}", run.summary());
+            if pipeline.steps.contains(&PipelineStep::Notify) {
+                let failed = Err(anyhow::anyhow!("{} step failed: {:#}", step.as_str(), e));
+                notifier.notify(&OperationReport::new(&operation, &failed, started.elapsed()));
+            }
+            return Err(e.context(format!(
+                "Pipeline {} stopped at the {} step. Run `pipeline run {} --resume` to continue",
+                name,
+                step.as_str(),
+                name
+            )));
+        }
+        steps_run += 1;
+    }
+
+    println!("\n{}", run.summary());
+    Ok(steps_run)
+}
+
+/// Import each pipeline contact's messages from chat.db, returning the number fetched
+async fn pipeline_import(
+    db: &Database,
+    pipeline: &PipelineConfig,
+    database_url: &str,
+    config: &Config,
+    cache_dir: &Path,
+) -> Result<usize> {
+    let chat_db_path = config.chat_db.chat_db_path()?;
+    let (repo, _snapshot) = open_chat_db(chat_db_path, database_url, &config.chat_db, false)?;
+    let repo = repo.with_import_filter(ImportFilter::new(&config.import_filter)?);
+    let date_range = parse_date_range(&pipeline.start_date, &pipeline.end_date)?;
+
+    let mut imported = 0;
+    for name in &pipeline.contacts {
+        let contact = get_contact_info(db, name)?;
+        let fetched = repo.fetch_messages(&contact, &date_range).await?;
+        invalidate_cached_messages(cache_dir, &config.cache, &contact.name);
+        println!("{}: {} messages", contact.name, fetched.messages.len());
+        if !fetched.warnings.is_empty() {
+            println!("{}: {}", contact.name, fetched.warnings.summary());
+        }
+        imported += fetched.messages.len();
+    }
+
+    Ok(imported)
+}
+
+/// Delete duplicate messages for each pipeline contact, returning the number deleted
+fn pipeline_dedupe(db: &Database, pipeline: &PipelineConfig) -> Result<usize> {
+    let mut deleted = 0;
+
+    for name in &pipeline.contacts {
+        let contact = db
+            .get_contact(name)?
+            .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+        let count = db.delete_duplicate_messages(contact.id)?;
+        println!("{}: removed {} duplicate messages", contact.name, count);
+        deleted += count;
+    }
+
+    Ok(deleted)
+}
+
+/// NLP process each pipeline contact's new messages, returning the number processed
+fn pipeline_process(db: &Database, pipeline: &PipelineConfig, entity_patterns: &[EntityPattern]) -> Result<usize> {
+    let mut processed = 0;
+
+    for name in &pipeline.contacts {
+        processed += process_messages(
+            db,
+            &pipeline.nlp_version,
+            &Some(name.clone()),
+            &pipeline.start_date,
+            &pipeline.end_date,
+            100,
+            ProcessFilter::default(),
+            None,
+            entity_patterns,
+            ThrottleConfig::default(),
+            false,
         )?;
     }
 
-    Ok(())
+    Ok(processed)
 }
 
-/// Write messages to a CSV file
-fn write_csv_file(messages: &[models::Message], file_path: &str) -> Result<()> {
-    use std::fs::File;
-    use std::io::BufWriter;
+/// Write `digest.md` into each pipeline contact's output directory, returning the number of days covered
+fn pipeline_digest(db: &Database, pipeline: &PipelineConfig, style: &ExportStyle, output_dir: &Path) -> Result<usize> {
+    let date_range = parse_date_range(&pipeline.start_date, &pipeline.end_date)?;
+    let mut days = 0;
 
-    let file = File::create(file_path)?;
-    let mut writer = csv::Writer::from_writer(BufWriter::new(file));
+    for name in &pipeline.contacts {
+        let mut messages: Vec<_> = db
+            .get_messages(
+                name,
+                date_range.start.map(|dt| dt.naive_local()),
+                date_range.end.map(|dt| dt.naive_local()),
+            )?
+            .iter()
+            .map(|m| m.to_message())
+            .collect();
+        style.redact(&mut messages)?;
+        let digest = Digest::build(&messages, pipeline.digest_highlights);
 
-    // Write header
-    writer.write_record(&["Sender", "Timestamp", "Content"])?;
+        let contact_dir = output_dir.join(name);
+        std::fs::create_dir_all(&contact_dir)?;
+        let path = contact_dir.join("digest.md");
+        std::fs::write(&path, digest.to_markdown(name))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Wrote {}", path.display());
 
-    // Write data
-    for message in messages {
-        writer.write_record(&[
-            &message.sender,
-            &message.timestamp.format("%b %d, %Y %r").to_string(),
-            &message.content,
-        ])?;
+        days += digest.days.len();
     }
 
-    writer.flush()?;
-    Ok(())
+    Ok(days)
+}
+
+/// Export each pipeline contact's conversation into its own directory, returning the number of messages
+fn pipeline_export(
+    db: &Database,
+    pipeline: &PipelineConfig,
+    style: &ExportStyle,
+    parallel_writes: usize,
+    output_dir: &Path,
+) -> Result<usize> {
+    let date_range = parse_date_range(&pipeline.start_date, &pipeline.end_date)?;
+    let flags = QueryFlags { yes: true, ..Default::default() };
+    let mut exported = 0;
+
+    for name in &pipeline.contacts {
+        let options = ExportOptions::new(output_dir.join(name))
+            .with_format_name(&pipeline.format)
+            .with_date_range(date_range.clone())
+            .with_chunking(Chunking::from_options(None, pipeline.lines_per_chunk, pipeline.tokens_per_chunk))
+            .with_style(style.clone())
+            .with_parallel_writes(parallel_writes);
+        let result = query_messages(db, &Some(name.clone()), &None, &options, &flags);
+        match result {
+            Ok(count) => exported += count,
+            Err(e) if matches!(e.downcast_ref(), Some(TxtHistoryError::NoMessages(_))) => {
+                println!("No messages for {}", name)
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(exported)
+}
+
+/// Export each contact into `<output path>/<contact>`, `jobs` contacts at a time, and write
+/// `export-summary.json` over all of them. A failed contact doesn't stop the others. Returns
+/// the number of messages exported.
+fn export_all(db: &Database, contacts: &[String], options: &ExportOptions, jobs: usize, yes: bool) -> Result<usize> {
+    let output_dir = options.output_path();
+
+    // Catch misspelled names before anything is written
+    for name in contacts {
+        db.get_contact(name)?
+            .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+    }
+
+    let question = format!("Export {} contacts into {}?", contacts.len(), output_dir.display());
+    if !confirm(&question, yes)? {
+        progress("Export cancelled");
+        return Ok(0);
+    }
+
+    // Each contact was confirmed above, so its own preview isn't asked about again
+    let flags = QueryFlags { yes: true, ..Default::default() };
+    let results = run_in_parallel(contacts, jobs, |name| {
+        let started = Instant::now();
+        let options = options.clone().with_output_path(output_dir.join(name));
+        let result = query_messages(db, &Some(name.clone()), &None, &options, &flags);
+        (result, started.elapsed())
+    });
+
+    let date_range = options.date_range();
+    let mut summary = BatchSummary::new(
+        options.format_name(),
+        &date_range.start.map(|dt| format_date_option(&dt)),
+        &date_range.end.map(|dt| format_date_option(&dt)),
+    );
+    for (name, (result, elapsed)) in contacts.iter().zip(results) {
+        summary.add(match result {
+            Ok(count) => BatchContact::new(name, BatchStatus::Exported, count, elapsed),
+            Err(e) if matches!(e.downcast_ref(), Some(TxtHistoryError::NoMessages(_))) => {
+                BatchContact::new(name, BatchStatus::NoMessages, 0, elapsed)
+            },
+            Err(e) => BatchContact::failed(name, &e, elapsed),
+        });
+    }
+    let path = summary.write(output_dir)?;
+
+    println!("
+{:<20} {:<12} {:>8} {:>8}", "Contact", "Status", "Messages", "Seconds");
+    for contact in &summary.contacts {
+        let status = match contact.status {
+            BatchStatus::Exported => "exported",
+            BatchStatus::NoMessages => "no messages",
+            BatchStatus::Failed => "failed",
+        };
+        println!(
+            "{:<20} {:<12} {:>8} {:>8.1}",
+            contact.contact,
+            status,
+            contact.messages,
+            contact.elapsed_ms as f64 / 1000.0
+        );
+        if let Some(error) = &contact.error {
+            println!("  {}", error);
+        }
+    }
+    println!("Summary written to {}", path.display());
+
+    if summary.failed() > 0 {
+        return Err(anyhow::anyhow!("{} of {} contacts failed to export", summary.failed(), contacts.len()));
+    }
+    Ok(summary.messages())
+}
+
+/// Look up a contact to import by name
+fn get_contact_info(db: &Database, name: &str) -> Result<Contact> {
+    let contact = db
+        .get_contact(name)?
+        .ok_or_else(|| TxtHistoryError::ContactNotFound(name.to_string()))?;
+
+    Ok(Contact { name: contact.name, phone: contact.phone, email: contact.email })
+}
+
+/// Parse date range from string options
+fn parse_date_range(
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+) -> Result<DateRange> {
+    let start = if let Some(date_str) = start_date {
+        Some(
+            DateTime::parse_from_str(&format!("{} 00:00:00 +0000", date_str), "%Y-%m-%d %H:%M:%S %z")
+                .context("Invalid start date format, use YYYY-MM-DD")?
+                .with_timezone(&Local),
+        )
+    } else {
+        None
+    };
+
+    let end = if let Some(date_str) = end_date {
+        Some(
+            DateTime::parse_from_str(&format!("{} 23:59:59 +0000", date_str), "%Y-%m-%d %H:%M:%S %z")
+                .context("Invalid end date format, use YYYY-MM-DD")?
+                .with_timezone(&Local),
+        )
+    } else {
+        None
+    };
+
+    Ok(DateRange { start, end })
+}
+
+/// The YYYY-MM-DD option `parse_date_range` read a bound of the range from
+fn format_date_option(date: &DateTime<Local>) -> String {
+    date.with_timezone(&Utc).format("%Y-%m-%d").to_string()
 }
@@ -0,0 +1,145 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::file_writer::{write_file, WritePolicy, WrittenFile};
+use crate::models::{Message, OutputFormat};
+use crate::style::ExportStyle;
+
+/// A message in a merged export, with the conversation it came from
+#[derive(Debug, Clone, Serialize)]
+pub struct MergedMessage {
+    /// Contact whose one-on-one conversation the message is from
+    pub conversation: String,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+/// Interleave several conversations into one chronological list. Messages sent at the same
+/// moment keep the order the conversations were given in.
+pub fn merge_conversations(conversations: Vec<(String, Vec<Message>)>) -> Vec<MergedMessage> {
+    let mut merged: Vec<MergedMessage> = conversations
+        .into_iter()
+        .flat_map(|(conversation, messages)| {
+            messages.into_iter().map(move |message| MergedMessage { conversation: conversation.clone(), message })
+        })
+        .collect();
+    merged.sort_by_key(|m| m.message.timestamp);
+    merged
+}
+
+/// Format one message like a TXT export line, labeled with its conversation
+fn format_merged_message(merged: &MergedMessage, style: &ExportStyle) -> String {
+    let message = &merged.message;
+    let id = match (&message.imessage_id, style.include_ids) {
+        (Some(id), true) => format!("[id:{}] ", id),
+        _ => String::new(),
+    };
+
+    format!(
+        "{}{}[{}] {}, {}, {}\n\n",
+        style.txt_prefix(message),
+        id,
+        merged.conversation,
+        message.sender,
+        message.timestamp.format("%b %d, %Y %r"),
+        message.content
+    )
+}
+
+fn write_merged_txt(writer: &mut impl Write, messages: &[MergedMessage], style: &ExportStyle) -> Result<()> {
+    for merged in messages {
+        write!(writer, "{}", format_merged_message(merged, style))?;
+    }
+    Ok(())
+}
+
+fn write_merged_csv(writer: impl Write, messages: &[MergedMessage]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(["Conversation", "Sender", "Timestamp", "Content"])?;
+    for merged in messages {
+        writer.write_record([
+            merged.conversation.as_str(),
+            merged.message.sender.as_str(),
+            &merged.message.timestamp.format("%b %d, %Y %r").to_string(),
+            merged.message.content.as_str(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write a merged transcript to `merged.txt`, `merged.csv` or `merged.json` in `output_dir`
+pub fn write_merged_file(
+    messages: &[MergedMessage],
+    format: OutputFormat,
+    style: &ExportStyle,
+    output_dir: &str,
+    policy: WritePolicy,
+) -> Result<WrittenFile> {
+    if policy == WritePolicy::Append {
+        return Err(anyhow::anyhow!("Merged exports can't be appended to; use --overwrite or --skip-existing"));
+    }
+
+    match format {
+        OutputFormat::Txt => write_file(&format!("{}/merged.txt", output_dir), messages.len(), policy, |writer, _| {
+            write_merged_txt(writer, messages, style)
+        }),
+        OutputFormat::Csv => write_file(&format!("{}/merged.csv", output_dir), messages.len(), policy, |writer, _| {
+            write_merged_csv(writer, messages)
+        }),
+        OutputFormat::Json => write_file(&format!("{}/merged.json", output_dir), messages.len(), policy, |writer, _| {
+            Ok(serde_json::to_writer_pretty(writer, messages)?)
+        }),
+        OutputFormat::Parquet => Err(anyhow::anyhow!("Merged exports are written as txt, csv or json")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(sender: &str, minute: u32, content: &str) -> Message {
+        Message {
+            sender: sender.to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 17, minute, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: sender == "Jess",
+        }
+    }
+
+    #[test]
+    fn test_merge_conversations() {
+        let merged = merge_conversations(vec![
+            ("Phil".to_string(), vec![message("Phil", 5, "Running late"), message("Jess", 20, "ok")]),
+            ("Rhonda".to_string(), vec![message("Jess", 5, "Can you get her?"), message("Rhonda", 10, "Sure")]),
+        ]);
+
+        let order: Vec<(&str, &str)> =
+            merged.iter().map(|m| (m.conversation.as_str(), m.message.content.as_str())).collect();
+        assert_eq!(
+            order,
+            vec![("Phil", "Running late"), ("Rhonda", "Can you get her?"), ("Rhonda", "Sure"), ("Phil", "ok")]
+        );
+
+        let mut text = Vec::new();
+        write_merged_txt(&mut text, &merged[..1], &ExportStyle::default()).unwrap();
+        assert_eq!(String::from_utf8(text).unwrap(), "[Phil] Phil, Jan 20, 2025 05:05:00 PM, Running late\n\n");
+
+        let mut csv = Vec::new();
+        write_merged_csv(&mut csv, &merged[2..3]).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "Conversation,Sender,Timestamp,Content\nRhonda,Rhonda,\"Jan 20, 2025 05:10:00 PM\",Sure\n"
+        );
+
+        let json = serde_json::to_value(&merged[3]).unwrap();
+        assert_eq!(json["conversation"], "Phil");
+        assert_eq!(json["sender"], "Jess");
+    }
+}
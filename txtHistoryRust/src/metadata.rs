@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde::Serialize;
+
+use crate::file_writer::{txt_line_counts, write_file, Chunking, CsvOptions, WritePolicy, WriteStatus, WrittenFile};
+use crate::models::{DbMessage, DbProcessedMessage, Message, OutputFormat};
+use crate::sources::has_multiple_sources;
+use crate::style::ExportStyle;
+use crate::totals::ExportTotals;
+
+/// One line of a `.meta.jsonl` file: what the TXT export leaves out about one message
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessageMetadata {
+    /// Line of the TXT file the message starts on, counting from 1
+    pub line: usize,
+    /// iMessage GUID or other source ID, as in `[id:...]` prefixes
+    pub id: Option<String>,
+    /// Row ID in the local database, as taken by `hide` and `sql`
+    pub message_id: Option<i32>,
+    pub sender: String,
+    pub is_from_me: bool,
+    pub timestamp: DateTime<Local>,
+    pub service: Option<String>,
+    pub source: Option<String>,
+    pub thread_id: Option<String>,
+    pub kind: Option<&'static str>,
+    pub has_attachments: Option<bool>,
+    /// When the message was read, from chat.db
+    pub read_at: Option<DateTime<Local>>,
+    pub sentiment: Option<f32>,
+    /// ISO 639-3 code
+    pub language: Option<String>,
+    pub flag_severity: Option<f32>,
+}
+
+/// Stored fields, NLP results and read times of exported messages, looked up by source ID
+#[derive(Debug, Default)]
+pub struct MetadataIndex {
+    messages: HashMap<String, DbMessage>,
+    /// By local message ID
+    processed: HashMap<i32, DbProcessedMessage>,
+    /// UTC read times by GUID
+    read_times: HashMap<String, NaiveDateTime>,
+}
+
+impl MetadataIndex {
+    pub fn new(
+        messages: &[DbMessage],
+        processed: Vec<DbProcessedMessage>,
+        read_times: HashMap<String, NaiveDateTime>,
+    ) -> Self {
+        Self {
+            messages: messages.iter().map(|m| (m.imessage_id.clone(), m.clone())).collect(),
+            processed: processed.into_iter().map(|p| (p.original_message_id, p)).collect(),
+            read_times,
+        }
+    }
+
+    /// Metadata for messages that start on `first_line` and take `line_counts` lines each
+    pub fn lines(&self, messages: &[Message], line_counts: &[usize], first_line: usize) -> Vec<MessageMetadata> {
+        let mut line = first_line;
+        messages
+            .iter()
+            .zip(line_counts)
+            .map(|(message, count)| {
+                let stored = message.imessage_id.as_ref().and_then(|id| self.messages.get(id));
+                let processed = stored.and_then(|m| self.processed.get(&m.id));
+                let read_at = message.imessage_id.as_ref().and_then(|id| self.read_times.get(id));
+
+                let metadata = MessageMetadata {
+                    line,
+                    id: message.imessage_id.clone(),
+                    message_id: stored.map(|m| m.id),
+                    sender: message.sender.clone(),
+                    is_from_me: message.is_from_me,
+                    timestamp: message.timestamp,
+                    service: message.service.clone(),
+                    source: message.source.clone(),
+                    thread_id: stored.and_then(|m| m.thread_id.clone()),
+                    kind: stored.map(|m| m.message_kind.as_str()),
+                    has_attachments: stored.map(|m| m.has_attachments),
+                    read_at: read_at.map(|date| Local.from_utc_datetime(date)),
+                    sentiment: processed.and_then(|p| p.sentiment_score),
+                    language: processed.and_then(|p| p.language.clone()),
+                    flag_severity: processed.and_then(|p| p.flag_severity),
+                };
+                line += count;
+                metadata
+            })
+            .collect()
+    }
+}
+
+/// Write `chunk_N.meta.jsonl` next to each TXT chunk in `written`, with a JSON line per
+/// message in the same order. Chunks left alone because they already exist get no metadata
+/// file, and appended chunks get their metadata appended with line numbers that follow on.
+pub fn write_metadata_files(
+    messages: &[Message],
+    written: &[WrittenFile],
+    index: &MetadataIndex,
+    chunking: Chunking,
+    style: &ExportStyle,
+    policy: WritePolicy,
+) -> Result<Vec<WrittenFile>> {
+    // Split and label the same way the TXT files were
+    let chunks = chunking.split(messages, OutputFormat::Txt, &CsvOptions::default(), style)?;
+    let label_sources = has_multiple_sources(messages);
+
+    let mut files = Vec::new();
+    for (chunk, txt) in chunks.iter().zip(written) {
+        let Some(base) = txt.path.strip_suffix(".txt") else { continue };
+        if txt.status == WriteStatus::Skipped {
+            continue;
+        }
+
+        let line_counts = txt_line_counts(chunk, label_sources, style);
+        let first_line = if txt.status == WriteStatus::Appended {
+            // The appended messages follow whatever the file held before
+            let footer =
+                if style.txt_totals { ExportTotals::from_messages(chunk).txt_footer().matches('\n').count() } else { 0 };
+            let appended = line_counts.iter().sum::<usize>() + footer;
+            let total = fs::read(&txt.path)?.iter().filter(|&&b| b == b'\n').count();
+            total.saturating_sub(appended) + 1
+        } else {
+            1
+        };
+
+        let lines = index.lines(chunk, &line_counts, first_line);
+        let meta_path = format!("{}.meta.jsonl", base);
+        files.push(write_file(&meta_path, chunk.len(), policy, |writer, _| {
+            for line in &lines {
+                serde_json::to_writer(&mut *writer, line)?;
+                writeln!(writer)?;
+            }
+            Ok(())
+        })?);
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_writer::write_txt_file;
+    use crate::models::MessageKind;
+
+    fn message(id: &str, content: &str, minute: u32) -> Message {
+        Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, minute, 0).unwrap(),
+            content: content.to_string(),
+            source: Some("iMessage".to_string()),
+            imessage_id: Some(id.to_string()),
+            service: Some("iMessage".to_string()),
+            is_from_me: false,
+        }
+    }
+
+    fn stored(id: i32, guid: &str) -> DbMessage {
+        let date = NaiveDateTime::parse_from_str("2025-01-20 12:00", "%Y-%m-%d %H:%M").unwrap();
+        DbMessage {
+            id,
+            imessage_id: guid.to_string(),
+            text: Some("hi".to_string()),
+            sender: "Phil".to_string(),
+            is_from_me: false,
+            date_created: date,
+            date_imported: date,
+            handle_id: None,
+            service: Some("iMessage".to_string()),
+            thread_id: Some("+15551234567".to_string()),
+            has_attachments: true,
+            reply_to: None,
+            contact_id: Some(1),
+            message_kind: MessageKind::Text,
+            hidden: false,
+            hidden_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_metadata_files() {
+        let output_dir = std::env::temp_dir().join(format!("txt_history_metadata_test_{}", std::process::id()));
+        fs::create_dir_all(&output_dir).unwrap();
+        let messages = vec![message("m1", "Two\nlines", 0), message("m2", "One line", 1)];
+        let processed = DbProcessedMessage {
+            id: 1,
+            original_message_id: 10,
+            processed_text: "two lines".to_string(),
+            tokens: None,
+            lemmatized_text: None,
+            named_entities: None,
+            sentiment_score: Some(0.25),
+            processed_at: NaiveDateTime::default(),
+            processing_version: "v1.0".to_string(),
+            flags: None,
+            flag_severity: Some(0.0),
+            language: Some("eng".to_string()),
+            sentence_sentiments: None,
+        };
+        let read = NaiveDateTime::parse_from_str("2025-01-20 13:00", "%Y-%m-%d %H:%M").unwrap();
+        let index =
+            MetadataIndex::new(&[stored(10, "m1")], vec![processed], HashMap::from([("m1".to_string(), read)]));
+
+        let style = ExportStyle::default();
+        let txt_path = output_dir.join("chunk_1.txt").to_string_lossy().to_string();
+        let txt = write_txt_file(&messages, &txt_path, false, &style, WritePolicy::Overwrite).unwrap();
+        let files =
+            write_metadata_files(&messages, &[txt], &index, Chunking::Single, &style, WritePolicy::Overwrite).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].path.ends_with("chunk_1.meta.jsonl"));
+
+        let meta = fs::read_to_string(&files[0].path).unwrap();
+        let lines: Vec<serde_json::Value> = meta.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["line"], 1);
+        assert_eq!(lines[0]["id"], "m1");
+        assert_eq!(lines[0]["message_id"], 10);
+        assert_eq!(lines[0]["sentiment"], 0.25);
+        assert_eq!(lines[0]["language"], "eng");
+        assert_eq!(lines[0]["has_attachments"], true);
+        assert!(lines[0]["read_at"].is_string());
+        // The first message takes two lines and a blank one
+        assert_eq!(lines[1]["line"], 4);
+        assert!(lines[1]["message_id"].is_null());
+        assert!(lines[1]["read_at"].is_null());
+
+        // Each message's line in the TXT file starts with its sender
+        let txt = fs::read_to_string(&txt_path).unwrap();
+        let txt_lines: Vec<&str> = txt.lines().collect();
+        assert!(txt_lines[0].starts_with("Phil, "));
+        assert!(txt_lines[3].starts_with("Phil, "));
+
+        // Appended metadata carries on from the lines already in the file
+        let more = vec![message("m3", "Later", 2)];
+        let txt = write_txt_file(&more, &txt_path, false, &style, WritePolicy::Append).unwrap();
+        write_metadata_files(&more, &[txt], &index, Chunking::Single, &style, WritePolicy::Append).unwrap();
+        let meta = fs::read_to_string(&files[0].path).unwrap();
+        let last: serde_json::Value = serde_json::from_str(meta.lines().last().unwrap()).unwrap();
+        assert_eq!(last["id"], "m3");
+        assert_eq!(last["line"], 6);
+        assert!(fs::read_to_string(&txt_path).unwrap().lines().nth(5).unwrap().starts_with("Phil, "));
+
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
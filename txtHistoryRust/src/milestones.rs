@@ -0,0 +1,207 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+
+use crate::models::Message;
+use crate::totals::ExportTotals;
+
+/// Characters of a message shown for the first and last message
+const PREVIEW_CHARS: usize = 60;
+
+/// A run of consecutive days with at least one message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Streak {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl Streak {
+    pub fn days(&self) -> i64 {
+        (self.end - self.start).num_days() + 1
+    }
+}
+
+/// Milestones and totals for `stats`: first and last message, longest daily streak and
+/// silence, and words exchanged
+#[derive(Debug, Clone)]
+pub struct ConversationMilestones {
+    pub label: String,
+    pub first: Message,
+    pub last: Message,
+    pub totals: ExportTotals,
+    /// Days with at least one message
+    pub active_days: usize,
+    pub longest_streak: Streak,
+    /// Longest time between two messages, as (before, after); `None` with a single message
+    pub longest_silence: Option<(DateTime<Local>, DateTime<Local>)>,
+}
+
+impl ConversationMilestones {
+    /// Milestones for messages sorted by time, `None` when there are none
+    pub fn new(messages: &[Message], label: &str) -> Option<Self> {
+        let first = messages.first()?;
+        let last = messages.last()?;
+
+        let days: BTreeSet<NaiveDate> = messages.iter().map(|m| m.timestamp.date_naive()).collect();
+        let mut longest_streak = Streak { start: first.timestamp.date_naive(), end: first.timestamp.date_naive() };
+        let mut current: Option<Streak> = None;
+        for &day in &days {
+            let streak = match current {
+                Some(streak) if streak.end.succ_opt() == Some(day) => Streak { end: day, ..streak },
+                _ => Streak { start: day, end: day },
+            };
+            if streak.days() > longest_streak.days() {
+                longest_streak = streak;
+            }
+            current = Some(streak);
+        }
+
+        let longest_silence = messages
+            .windows(2)
+            .map(|pair| (pair[0].timestamp, pair[1].timestamp))
+            .max_by_key(|(before, after)| *after - *before);
+
+        Some(Self {
+            label: label.to_string(),
+            first: first.clone(),
+            last: last.clone(),
+            totals: ExportTotals::from_messages(messages),
+            active_days: days.len(),
+            longest_streak,
+            longest_silence,
+        })
+    }
+
+    /// Calendar days from the first message to the last, counting both
+    pub fn span_days(&self) -> i64 {
+        (self.last.timestamp.date_naive() - self.first.timestamp.date_naive()).num_days() + 1
+    }
+
+    /// Words per calendar day over the whole span, including days without messages
+    pub fn words_per_day(&self) -> f64 {
+        self.totals.words as f64 / self.span_days() as f64
+    }
+
+    pub fn longest_silence_duration(&self) -> Option<Duration> {
+        self.longest_silence.map(|(before, after)| after - before)
+    }
+
+    /// The first anniversary of the first message on or after `today`. February 29 falls on
+    /// February 28 in other years.
+    pub fn next_anniversary(&self, today: NaiveDate) -> NaiveDate {
+        let first = self.first.timestamp.date_naive();
+        let on = |year: i32| {
+            NaiveDate::from_ymd_opt(year, first.month(), first.day())
+                .or_else(|| NaiveDate::from_ymd_opt(year, first.month(), first.day() - 1))
+                .unwrap_or(today)
+        };
+        let this_year = on(today.year());
+        if this_year >= today {
+            this_year
+        } else {
+            on(today.year() + 1)
+        }
+    }
+
+    /// Several lines for the terminal
+    pub fn to_text(&self, today: NaiveDate) -> String {
+        let describe = |message: &Message| {
+            let mut preview: String = message.content.chars().take(PREVIEW_CHARS).collect();
+            if message.content.chars().count() > PREVIEW_CHARS {
+                preview.push('…');
+            }
+            format!("{} {}: {}", message.timestamp.format("%Y-%m-%d %H:%M"), message.sender, preview)
+        };
+        let anniversary = self.next_anniversary(today);
+        let years = anniversary.year() - self.first.timestamp.year();
+
+        let mut lines = vec![
+            self.label.clone(),
+            format!("  First message:   {}", describe(&self.first)),
+            format!("  Last message:    {}", describe(&self.last)),
+            format!(
+                "  Anniversary:     {} ({} years, in {} days)",
+                anniversary,
+                years,
+                (anniversary - today).num_days()
+            ),
+            format!(
+                "  Longest streak:  {} days, {} to {}",
+                self.longest_streak.days(),
+                self.longest_streak.start,
+                self.longest_streak.end
+            ),
+        ];
+        if let (Some((before, after)), Some(silence)) = (self.longest_silence, self.longest_silence_duration()) {
+            lines.push(format!(
+                "  Longest silence: {} days, {} to {}",
+                silence.num_days(),
+                before.format("%Y-%m-%d"),
+                after.format("%Y-%m-%d")
+            ));
+        }
+        lines.push(format!(
+            "  Words:           {} in {} messages on {} of {} days ({:.1} per day)",
+            self.totals.words,
+            self.totals.messages,
+            self.active_days,
+            self.span_days(),
+            self.words_per_day()
+        ));
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDateTime, TimeZone};
+
+    fn message(sender: &str, at: &str, content: &str) -> Message {
+        let naive = NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M").unwrap();
+        Message {
+            sender: sender.to_string(),
+            timestamp: Local.from_local_datetime(&naive).earliest().unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: sender == "Jess",
+        }
+    }
+
+    #[test]
+    fn test_milestones() {
+        let messages = vec![
+            message("Phil", "2020-02-29 09:00", "hello there"),
+            message("Jess", "2020-03-01 10:00", "hi"),
+            message("Phil", "2020-03-02 11:00", "how are you"),
+            message("Jess", "2020-03-03 12:00", "good"),
+            message("Phil", "2020-03-20 08:00", "long time"),
+            message("Jess", "2020-03-21 08:00", "yes it was"),
+        ];
+        let milestones = ConversationMilestones::new(&messages, "Phil").unwrap();
+
+        assert_eq!(milestones.first.content, "hello there");
+        assert_eq!(milestones.last.content, "yes it was");
+        assert_eq!(milestones.longest_streak.days(), 4);
+        assert_eq!(milestones.longest_streak.end, NaiveDate::from_ymd_opt(2020, 3, 3).unwrap());
+        assert_eq!(milestones.longest_silence_duration().unwrap().num_days(), 16);
+        assert_eq!(milestones.active_days, 6);
+        assert_eq!(milestones.span_days(), 22);
+        assert_eq!(milestones.totals.words, 12);
+        assert!((milestones.words_per_day() - 12.0 / 22.0).abs() < 1e-9);
+
+        let today = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        assert_eq!(milestones.next_anniversary(today), NaiveDate::from_ymd_opt(2026, 2, 28).unwrap());
+        let text = milestones.to_text(today);
+        assert!(text.contains("Longest streak:  4 days, 2020-02-29 to 2020-03-03"));
+        assert!(text.contains("(6 years, in 364 days)"));
+
+        assert!(ConversationMilestones::new(&[], "Nobody").is_none());
+        let single = ConversationMilestones::new(&messages[..1], "Phil").unwrap();
+        assert_eq!(single.longest_silence, None);
+        assert_eq!(single.longest_streak.days(), 1);
+    }
+}
@@ -1,13 +1,29 @@
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use std::fmt;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json;
 
+use crate::flags::{max_severity, LanguageFlag};
+use crate::sources::source_for_service;
+
 // Original models for compatibility with existing code
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub sender: String,
     pub timestamp: DateTime<Local>,
     pub content: String,
+    /// Archive the message was imported from (iMessage, Email, Telegram...)
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Original message ID (iMessage GUID or the imported source's ID)
+    #[serde(default)]
+    pub imessage_id: Option<String>,
+    /// Service the message was sent over (iMessage, SMS, Email...)
+    #[serde(default)]
+    pub service: Option<String>,
+    #[serde(default)]
+    pub is_from_me: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -17,7 +33,7 @@ pub struct Contact {
     pub email: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DateRange {
     pub start: Option<DateTime<Local>>,
     pub end: Option<DateTime<Local>>,
@@ -28,6 +44,65 @@ pub enum OutputFormat {
     Csv,
     Txt,
     Json,
+    /// Columnar file for analytics tools like DuckDB and pandas
+    Parquet,
+}
+
+/// Which messaging service to include when importing, querying or exporting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ServiceFilter {
+    IMessage,
+    Sms,
+    #[default]
+    All,
+}
+
+impl ServiceFilter {
+    /// Parse a filter from a CLI value (imessage, sms or all)
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "imessage" => Ok(Self::IMessage),
+            "sms" => Ok(Self::Sms),
+            "all" => Ok(Self::All),
+            _ => Err(anyhow::anyhow!("Invalid service: {}. Use imessage, sms or all", value)),
+        }
+    }
+
+    /// Whether a message sent over `service` passes the filter
+    pub fn matches(&self, service: Option<&str>) -> bool {
+        match self {
+            Self::All => true,
+            Self::IMessage => service.map_or(false, |s| s.eq_ignore_ascii_case("iMessage")),
+            Self::Sms => service.map_or(false, |s| s.eq_ignore_ascii_case("SMS")),
+        }
+    }
+}
+
+/// The CLI value the filter was parsed from
+impl fmt::Display for ServiceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IMessage => write!(f, "imessage"),
+            Self::Sms => write!(f, "sms"),
+            Self::All => write!(f, "all"),
+        }
+    }
+}
+
+/// Which messages an NLP run should process
+#[derive(Debug, Clone, Default)]
+pub struct ProcessFilter {
+    /// Only messages in the conversation with this contact
+    pub contact_id: Option<i32>,
+    /// Only my messages (`Some(true)`) or only the other side's (`Some(false)`)
+    pub is_from_me: Option<bool>,
+    /// Only messages with at least this many characters of text
+    pub min_length: Option<usize>,
+    pub service: ServiceFilter,
+    pub start_date: Option<NaiveDateTime>,
+    pub end_date: Option<NaiveDateTime>,
+    /// Process at most this many messages, oldest first
+    pub limit: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,7 +127,20 @@ pub struct DbMessage {
     pub service: Option<String>,
     pub thread_id: Option<String>,
     pub has_attachments: bool,
+    pub reply_to: Option<String>,
     pub contact_id: Option<i32>,
+    pub message_kind: MessageKind,
+    /// Left out of exports and reports, but kept in the archive
+    pub hidden: bool,
+    pub hidden_reason: Option<String>,
+}
+
+/// A conversation in the local database: the messages linked to a contact, or every message
+/// imported from one chat (its `thread_id`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conversation {
+    Contact(i32),
+    Thread(String),
 }
 
 // Struct to hold NLP analysis results
@@ -63,6 +151,10 @@ pub struct NlpAnalysis {
     pub sentiment_score: f32,
     pub language: Option<String>,
     pub processed_text: String,
+    /// Results of the abusive-language pass, `None` when it's turned off
+    pub flags: Option<Vec<LanguageFlag>>,
+    /// Sentiment of each sentence, in order
+    pub sentences: Vec<SentenceSentiment>,
 }
 
 // Named entity representation
@@ -70,8 +162,20 @@ pub struct NlpAnalysis {
 pub struct NamedEntity {
     pub text: String,
     pub entity_type: String,
+    /// Byte offsets into the original message text, where the entity can be highlighted
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Sentiment of one sentence of a message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentenceSentiment {
+    pub text: String,
+    /// Byte offsets into the original message text
     pub start: usize,
     pub end: usize,
+    /// -1.0 (negative) to 1.0 (positive)
+    pub score: f32,
 }
 
 // Conversion methods
@@ -85,21 +189,158 @@ impl NlpAnalysis {
             named_entities: Some(serde_json::to_string(&self.entities).unwrap_or_default()),
             sentiment_score: Some(self.sentiment_score),
             processing_version: version.to_string(),
+            flags: self
+                .flags
+                .as_ref()
+                .map(|flags| serde_json::to_string(flags).unwrap_or_default()),
+            flag_severity: self.flags.as_deref().map(max_severity),
+            language: self.language.clone(),
+            sentence_sentiments: Some(serde_json::to_string(&self.sentences).unwrap_or_default()),
         }
     }
 }
 
 impl DbMessage {
+    /// The chat this message was imported from, or the contact it's linked to if it has no chat
+    pub fn conversation(&self) -> Option<Conversation> {
+        match (&self.thread_id, self.contact_id) {
+            (Some(thread_id), _) => Some(Conversation::Thread(thread_id.clone())),
+            (None, Some(contact_id)) => Some(Conversation::Contact(contact_id)),
+            (None, None) => None,
+        }
+    }
+
     // Convert to the original Message format for compatibility
     pub fn to_message(&self) -> Message {
         Message {
             sender: self.sender.clone(),
             timestamp: Local.from_utc_datetime(&self.date_created),
-            content: self.text.clone().unwrap_or_default(),
+            content: self.message_kind.render(self.text.as_deref()),
+            source: Some(source_for_service(self.service.as_deref()).to_string()),
+            imessage_id: Some(self.imessage_id.clone()),
+            service: self.service.clone(),
+            is_from_me: self.is_from_me,
         }
     }
 }
 
+/// What a chat.db row holds besides plain text. Anything that isn't `Text` is shown in
+/// exports as a placeholder such as "[Sent location]".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageKind {
+    #[default]
+    Text,
+    Sticker,
+    /// A shared location pin
+    Location,
+    /// Apple Pay / Apple Cash
+    Payment,
+    /// GamePigeon and Game Center invites
+    GameInvite,
+    /// Any other iMessage app (polls, music, stores...)
+    App,
+    /// Group renames, members joining or leaving and similar events
+    System,
+}
+
+impl MessageKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Sticker => "sticker",
+            Self::Location => "location",
+            Self::Payment => "payment",
+            Self::GameInvite => "game_invite",
+            Self::App => "app",
+            Self::System => "system",
+        }
+    }
+
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "sticker" => Ok(Self::Sticker),
+            "location" => Ok(Self::Location),
+            "payment" => Ok(Self::Payment),
+            "game_invite" => Ok(Self::GameInvite),
+            "app" => Ok(Self::App),
+            "system" => Ok(Self::System),
+            _ => Err(anyhow::anyhow!("Invalid message kind: {}", value)),
+        }
+    }
+
+    /// Classify a chat.db message from its `item_type`, `associated_message_type`,
+    /// `balloon_bundle_id` and attachment file names
+    pub fn detect(
+        item_type: i32,
+        associated_message_type: i32,
+        balloon_bundle_id: Option<&str>,
+        attachment_names: &[&str],
+    ) -> Self {
+        if item_type != 0 {
+            return Self::System;
+        }
+        if associated_message_type == 1000 {
+            return Self::Sticker;
+        }
+        if attachment_names.iter().any(|name| name.to_lowercase().ends_with(".loc.vcf")) {
+            return Self::Location;
+        }
+
+        match balloon_bundle_id {
+            None => Self::Text,
+            // Link previews still carry their URL as text
+            Some(id) if id.contains("URLBalloonProvider") => Self::Text,
+            Some(id) if id.contains("PeerPayment") => Self::Payment,
+            Some(id) if id.contains("GamePigeon") || id.contains("gamecenter") => Self::GameInvite,
+            Some(id) if id.contains("Stickers") => Self::Sticker,
+            Some(id) if id.contains("findmy") => Self::Location,
+            Some(_) => Self::App,
+        }
+    }
+
+    /// Text to export for a message of this kind, keeping whatever text it had where useful
+    pub fn render(self, text: Option<&str>) -> String {
+        // The object replacement character stands in for the balloon itself
+        let text = text
+            .map(|t| t.trim_matches(|c: char| c.is_whitespace() || c == '\u{fffc}'))
+            .filter(|t| !t.is_empty());
+
+        match self {
+            Self::Text => text.unwrap_or_default().to_string(),
+            Self::Sticker => "[Sticker]".to_string(),
+            Self::Location => "[Sent location]".to_string(),
+            Self::Payment => match text.and_then(payment_amount) {
+                Some(amount) => format!("[Apple Pay {}]", amount),
+                None => "[Apple Pay]".to_string(),
+            },
+            Self::GameInvite => "[Game invite]".to_string(),
+            Self::App => match text {
+                Some(text) => format!("[App message: {}]", text),
+                None => "[App message]".to_string(),
+            },
+            Self::System => match text {
+                Some(text) => format!("[{}]", text),
+                None => "[System message]".to_string(),
+            },
+        }
+    }
+}
+
+/// First amount like "$40" or "€12.50" in a payment message
+fn payment_amount(text: &str) -> Option<String> {
+    let start = text.find(['$', '€', '£'])?;
+    let amount: String = text[start..]
+        .chars()
+        .enumerate()
+        .take_while(|(i, c)| *i == 0 || c.is_ascii_digit() || *c == '.' || *c == ',')
+        .map(|(_, c)| c)
+        .collect();
+    let amount = amount.trim_end_matches(['.', ',']);
+
+    (amount.chars().count() > 1).then(|| amount.to_string())
+}
+
 // Database models for rusqlite
 #[derive(Debug, Clone)]
 pub struct DbContact {
@@ -108,6 +349,19 @@ pub struct DbContact {
     pub phone: Option<String>,
     pub email: Option<String>,
     pub is_me: bool,
+    pub details: ContactDetails,
+}
+
+/// Notes and typed details kept on a contact, e.g. for a header like "Conversation with Phil
+/// (co-parent)" in an export template
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ContactDetails {
+    /// How the contact is related to me, e.g. "co-parent" or "lawyer"
+    pub relationship: Option<String>,
+    /// IANA time zone name, e.g. "America/Denver"
+    pub timezone: Option<String>,
+    pub birthday: Option<NaiveDate>,
+    pub notes: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -121,6 +375,275 @@ pub struct DbProcessedMessage {
     pub sentiment_score: Option<f32>,
     pub processed_at: NaiveDateTime,
     pub processing_version: String,
+    /// Flagged profanity or abusive language (JSON), `None` if the flag pass didn't run
+    pub flags: Option<String>,
+    /// Highest severity among the flags, 0.0 for a clean message
+    pub flag_severity: Option<f32>,
+    /// Detected language as an ISO 639-3 code, `None` when the text was too short to tell
+    pub language: Option<String>,
+    /// Sentiment of each sentence (JSON), `None` for versions processed before it was scored
+    pub sentence_sentiments: Option<String>,
+}
+
+impl DbProcessedMessage {
+    /// The stored per-sentence sentiment, empty when there is none
+    pub fn sentences(&self) -> Vec<SentenceSentiment> {
+        self.sentence_sentiments
+            .as_deref()
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbAttachment {
+    pub id: i32,
+    pub message_id: i32,
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct DbAttachmentTranscript {
+    pub id: i32,
+    pub attachment_id: i32,
+    pub message_id: i32,
+    pub processor: String,
+    pub transcript: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct DbMessageRevision {
+    pub id: i32,
+    pub message_id: i32,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_at: NaiveDateTime,
+}
+
+/// A recorded export, kept so an interrupted one can be resumed
+#[derive(Debug, Clone)]
+pub struct DbExportRun {
+    pub id: i32,
+    /// Hash of the query parameters and selected messages; a resume must produce the same hash
+    pub query_hash: String,
+    pub output_dir: String,
+    pub total_chunks: usize,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+/// A chunk file an export finished writing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbExportChunk {
+    pub export_id: i32,
+    pub chunk_index: usize,
+    pub path: String,
+    pub sha256: String,
+    pub messages: usize,
+    pub bytes: u64,
+}
+
+/// What a detected message reference is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitmentKind {
+    /// A request or question to the other person ("can you pick her up?")
+    Question,
+    /// A promise to do something ("I'll take her Friday")
+    Commitment,
+    /// Agreement to a request ("ok, that works")
+    Agreement,
+}
+
+impl CommitmentKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Question => "question",
+            Self::Commitment => "commitment",
+            Self::Agreement => "agreement",
+        }
+    }
+
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "question" => Ok(Self::Question),
+            "commitment" => Ok(Self::Commitment),
+            "agreement" => Ok(Self::Agreement),
+            _ => Err(anyhow::anyhow!(
+                "Invalid kind: {}. Use question, commitment or agreement",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewCommitment {
+    pub message_id: i32,
+    pub kind: CommitmentKind,
+    pub snippet: String,
+    /// First reply from the other side, for questions
+    pub answered_by: Option<i32>,
+}
+
+/// A detected question, commitment or agreement with the message it came from
+#[derive(Debug, Clone)]
+pub struct CommitmentReportEntry {
+    pub message_id: i32,
+    pub kind: CommitmentKind,
+    pub snippet: String,
+    pub sender: String,
+    pub date_created: NaiveDateTime,
+    pub answered_by: Option<i32>,
+    /// Text of the reply in `answered_by`
+    pub answer: Option<String>,
+}
+
+impl CommitmentReportEntry {
+    /// A question nobody replied to within the answer window
+    pub fn is_open(&self) -> bool {
+        self.kind == CommitmentKind::Question && self.answered_by.is_none()
+    }
+}
+
+/// A date or plan detected in a message
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewEvent {
+    pub message_id: i32,
+    /// Local date the message refers to
+    pub starts_on: NaiveDate,
+    /// Local time, or `None` for an all-day event
+    pub starts_at: Option<NaiveTime>,
+    /// Words the date was read from, e.g. "Friday at 3"
+    pub expression: String,
+    /// Sentence containing the expression
+    pub snippet: String,
+}
+
+/// An abstractive summary of a day or session, cached by the transcript it was made from
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewSummary {
+    pub input_hash: String,
+    pub model: String,
+    /// "day" or "session"
+    pub unit: String,
+    /// Local date, or start of the session
+    pub unit_key: String,
+    pub summary: String,
+}
+
+/// A detected event with the message it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventReportEntry {
+    pub message_id: i32,
+    pub imessage_id: String,
+    pub sender: String,
+    pub date_created: NaiveDateTime,
+    pub starts_on: NaiveDate,
+    pub starts_at: Option<NaiveTime>,
+    pub expression: String,
+    pub snippet: String,
+}
+
+/// What merging a duplicate contact into another changes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContactMerge {
+    /// Identifiers the kept contact takes over, as (column, value)
+    pub identifiers: Vec<(&'static str, String)>,
+    /// Identifiers the kept contact has no free column for
+    pub dropped_identifiers: Vec<String>,
+    /// Messages linked to the duplicate, now linked to the kept contact
+    pub messages: usize,
+    /// Received messages whose sender was the duplicate's name
+    pub renamed_senders: usize,
+}
+
+impl ContactMerge {
+    /// Multi-line description of the merge for the confirmation prompt
+    pub fn summary(&self, from: &str, into: &str) -> String {
+        let mut lines = vec![
+            format!("Merging \"{}\" into \"{}\":", from, into),
+            format!("  {} messages will be linked to {}", self.messages, into),
+            format!("  {} received messages will be renamed from {} to {}", self.renamed_senders, from, into),
+        ];
+        for (column, value) in &self.identifiers {
+            lines.push(format!("  {} will get {} {}", into, column, value));
+        }
+        for value in &self.dropped_identifiers {
+            lines.push(format!("  {} has no free identifier column for {}, it will be dropped", into, value));
+        }
+        lines.push(format!("  {} will be deleted", from));
+        lines.join("\n")
+    }
+}
+
+/// Rows deleted by a purge, per table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PurgeCounts {
+    pub messages: usize,
+    pub processed_messages: usize,
+    pub attachments: usize,
+    pub attachment_transcripts: usize,
+    pub revisions: usize,
+    pub commitments: usize,
+    pub events: usize,
+}
+
+impl PurgeCounts {
+    /// One-line description of what was (or would be) deleted
+    pub fn summary(&self) -> String {
+        format!(
+            "{} messages, {} processed messages, {} attachments, {} attachment transcripts, {} revisions, {} commitments and {} events",
+            self.messages,
+            self.processed_messages,
+            self.attachments,
+            self.attachment_transcripts,
+            self.revisions,
+            self.commitments,
+            self.events
+        )
+    }
+}
+
+impl std::ops::AddAssign for PurgeCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.messages += other.messages;
+        self.processed_messages += other.processed_messages;
+        self.attachments += other.attachments;
+        self.attachment_transcripts += other.attachment_transcripts;
+        self.revisions += other.revisions;
+        self.commitments += other.commitments;
+        self.events += other.events;
+    }
+}
+
+/// A field that differed between the stored message and a re-imported copy
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageChange {
+    pub field: &'static str,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+/// Result of upserting a message by its GUID
+#[derive(Debug, Clone)]
+pub enum UpsertOutcome {
+    Inserted(DbMessage),
+    Updated(DbMessage, Vec<MessageChange>),
+    Unchanged(DbMessage),
+}
+
+impl UpsertOutcome {
+    /// The stored message after the upsert
+    pub fn message(&self) -> &DbMessage {
+        match self {
+            Self::Inserted(message) | Self::Updated(message, _) | Self::Unchanged(message) => message,
+        }
+    }
 }
 
 // Structs for inserting new records
@@ -145,7 +668,9 @@ pub struct NewMessage {
     pub service: Option<String>,
     pub thread_id: Option<String>,
     pub has_attachments: bool,
+    pub reply_to: Option<String>,
     pub contact_id: Option<i32>,
+    pub message_kind: MessageKind,
 }
 
 #[derive(Debug, Clone)]
@@ -157,10 +682,37 @@ pub struct NewProcessedMessage {
     pub named_entities: Option<String>,
     pub sentiment_score: Option<f32>,
     pub processing_version: String,
+    pub flags: Option<String>,
+    pub flag_severity: Option<f32>,
+    pub language: Option<String>,
+    pub sentence_sentiments: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAttachment {
+    pub message_id: i32,
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
+
+/// An attachment with the message it was sent with, for `attachments list`
+#[derive(Debug, Clone)]
+pub struct AttachmentReportEntry {
+    pub attachment_id: i32,
+    pub message_id: i32,
+    pub sender: String,
+    pub date_created: NaiveDateTime,
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub size_bytes: Option<i64>,
+    /// Text sent with the attachment, if any
+    pub text: Option<String>,
 }
 
 // Query builder for rusqlite
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct QueryBuilder {
     pub filters: Vec<Filter>,
     pub order_by: Option<String>,
@@ -168,14 +720,14 @@ pub struct QueryBuilder {
     pub offset: Option<usize>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Filter {
     pub field: String,
     pub operator: Operator,
     pub value: FilterType,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Operator {
     Equal,
     NotEqual,
@@ -187,7 +739,7 @@ pub enum Operator {
     In,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FilterType {
     Text(String),
     Integer(i64),
@@ -226,3 +778,43 @@ impl QueryBuilder {
 pub enum MessageItem {
     Message(DbMessage),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_filter() {
+        assert_eq!(ServiceFilter::parse("SMS").unwrap(), ServiceFilter::Sms);
+        assert!(ServiceFilter::parse("fax").is_err());
+
+        assert!(ServiceFilter::All.matches(None));
+        assert!(ServiceFilter::IMessage.matches(Some("iMessage")));
+        assert!(!ServiceFilter::IMessage.matches(Some("SMS")));
+        assert!(ServiceFilter::Sms.matches(Some("SMS")));
+        assert!(!ServiceFilter::Sms.matches(None));
+        assert_eq!(ServiceFilter::Sms.to_string(), "sms");
+    }
+
+    #[test]
+    fn test_message_kind() {
+        let payment = "com.apple.messages.MSMessageExtensionBalloonPlugin:0000000000:com.apple.PassbookUIService.PeerPaymentMessagesExtension";
+        assert_eq!(MessageKind::detect(0, 0, Some(payment), &[]), MessageKind::Payment);
+        assert_eq!(MessageKind::detect(0, 0, None, &["CL.loc.vcf"]), MessageKind::Location);
+        assert_eq!(MessageKind::detect(2, 0, None, &[]), MessageKind::System);
+        assert_eq!(MessageKind::detect(0, 1000, None, &[]), MessageKind::Sticker);
+        assert_eq!(
+            MessageKind::detect(0, 0, Some("com.apple.messages.URLBalloonProvider"), &[]),
+            MessageKind::Text
+        );
+
+        assert_eq!(MessageKind::Payment.render(Some("\u{fffc}$40 with Apple Cash")), "[Apple Pay $40]");
+        assert_eq!(MessageKind::Payment.render(None), "[Apple Pay]");
+        assert_eq!(MessageKind::Location.render(Some("\u{fffc}")), "[Sent location]");
+        assert_eq!(MessageKind::System.render(Some("Phil named the conversation")), "[Phil named the conversation]");
+        assert_eq!(MessageKind::Text.render(Some("hi")), "hi");
+
+        assert_eq!(MessageKind::parse("game_invite").unwrap(), MessageKind::GameInvite);
+        assert!(MessageKind::parse("fax").is_err());
+    }
+}
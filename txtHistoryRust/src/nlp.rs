@@ -1,14 +1,30 @@
 use anyhow::{Context, Result};
 use regex::Regex;
-use rust_stemmers::{Algorithm, Stemmer};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use stop_words::{get, LANGUAGE};
-use unicode_normalization::UnicodeNormalization;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
 use whatlang::{detect, Lang};
 
+use crate::cleaned_text::CleanedText;
 use crate::db::Database;
-use crate::models::{DbMessage, DbProcessedMessage, NamedEntity, NewProcessedMessage, NlpAnalysis};
+use crate::entities::EntityExtractor;
+use crate::flags::LanguageFlagger;
+use crate::lexicon::{Lexicon, LexiconStats};
+use crate::models::{DbProcessedMessage, NamedEntity, NlpAnalysis, SentenceSentiment};
+
+/// Result of processing one batch of messages
+#[derive(Debug)]
+pub struct BatchReport {
+    /// Results for the batch, including messages processed by an earlier run
+    pub processed: Vec<DbProcessedMessage>,
+    /// Results newly written by this batch
+    pub inserted: usize,
+    /// Time spent reading messages and analyzing text
+    pub analysis_time: Duration,
+    /// Time spent writing results
+    pub write_time: Duration,
+}
 
 /// NLP processor for text analysis
 pub struct NlpProcessor {
@@ -17,8 +33,9 @@ pub struct NlpProcessor {
     emoji_regex: Regex,
     special_chars_regex: Regex,
     extra_spaces_regex: Regex,
-    stopwords: HashSet<String>,
-    stemmer: Stemmer,
+    lexicon: Lexicon,
+    flagger: Option<LanguageFlagger>,
+    entity_extractor: Option<EntityExtractor>,
 }
 
 impl NlpProcessor {
@@ -30,14 +47,8 @@ impl NlpProcessor {
         let special_chars_regex = Regex::new(r"[^\w\s]").unwrap();
         let extra_spaces_regex = Regex::new(r"\s+").unwrap();
 
-        // Initialize stopwords for English
-        let stopwords: HashSet<String> = get(LANGUAGE::English)
-            .iter()
-            .map(|s| s.to_string())
-            .collect();
-
-        // Initialize stemmer for English
-        let stemmer = Stemmer::create(Algorithm::English);
+        // Stopwords and stemmers are loaded per language the first time a message needs them
+        let lexicon = Lexicon::default();
 
         Self {
             version: version.to_string(),
@@ -45,76 +56,126 @@ impl NlpProcessor {
             emoji_regex,
             special_chars_regex,
             extra_spaces_regex,
-            stopwords,
-            stemmer,
+            lexicon,
+            flagger: None,
+            entity_extractor: None,
         }
     }
 
+    /// How the per-language stopword and stemmer pipelines were used so far
+    pub fn lexicon_stats(&self) -> LexiconStats {
+        self.lexicon.stats()
+    }
+
+    /// Also run the profanity/abusive-language pass on every message
+    pub fn with_flagger(mut self, flagger: LanguageFlagger) -> Self {
+        self.flagger = Some(flagger);
+        self
+    }
+
+    /// Also store matches of user-defined entity patterns as named entities
+    pub fn with_entity_extractor(mut self, extractor: EntityExtractor) -> Self {
+        self.entity_extractor = Some(extractor);
+        self
+    }
+
     /// Process a message and return NLP analysis
     pub fn process_text(&self, text: &str) -> Result<NlpAnalysis> {
-        // Clean the text
-        let processed_text = self.clean_text(text);
+        // Detect the language on the original text, since cleaning drops the accents whatlang
+        // relies on, and pick that language's stopwords and stemmer
+        let language = detect_language(text);
+        let pipeline = self.lexicon.pipeline(language.as_deref());
+
+        // Clean the text, keeping track of where each part of it came from
+        let cleaned = self.clean(text);
+        let processed_text = cleaned.as_str().to_string();
 
         // Tokenize the text
-        let tokens = self.tokenize(&processed_text);
+        let tokens = pipeline.tokenize(&processed_text);
 
         // Lemmatize/stem the text
-        let lemmatized_text = self.lemmatize(&tokens);
+        let lemmatized_text = pipeline.lemmatize(&tokens);
+
+        // Extract named entities (simplified implementation), storing their positions in the
+        // original text so they can be highlighted there
+        let mut named_entities: Vec<NamedEntity> = self
+            .extract_entities(&processed_text)
+            .into_iter()
+            .filter_map(|entity| {
+                let range = cleaned.original_range(entity.start..entity.end)?;
+                Some(NamedEntity { start: range.start, end: range.end, ..entity })
+            })
+            .collect();
 
-        // Extract named entities (simplified implementation)
-        let named_entities = self.extract_entities(&processed_text);
+        // Cleaning lowercases and drops the dashes in identifiers like "24-CV-01932", so custom
+        // patterns run on the original text
+        if let Some(extractor) = &self.entity_extractor {
+            named_entities.extend(extractor.extract(text));
+        }
 
         // Calculate sentiment score (simplified implementation)
         let sentiment_score = self.analyze_sentiment(&processed_text);
 
+        // Score each sentence too, so one angry line in a long, friendly message isn't averaged away
+        let sentences = split_sentences(text)
+            .into_iter()
+            .map(|(start, sentence)| SentenceSentiment {
+                text: sentence.to_string(),
+                start,
+                end: start + sentence.len(),
+                score: self.analyze_sentiment(self.clean(sentence).as_str()),
+            })
+            .collect();
+
+        // Cleaning strips punctuation that phrases like "you'll regret" need, so flag the original
+        let flags = self.flagger.as_ref().map(|flagger| flagger.flag(text));
+
         Ok(NlpAnalysis {
             processed_text,
             tokens,
             lemmatized_text: Some(lemmatized_text),
             named_entities: Some(named_entities),
             sentiment_score: Some(sentiment_score),
+            flags,
+            language,
+            sentences,
         })
     }
 
     /// Clean the text by removing URLs, emojis, and normalizing whitespace
-    fn clean_text(&self, text: &str) -> String {
+    fn clean(&self, text: &str) -> CleanedText {
         // Normalize Unicode characters
-        let normalized = text.nfc().collect::<String>();
+        let normalized = CleanedText::new(text).nfc();
 
         // Remove URLs
-        let no_urls = self.url_regex.replace_all(&normalized, " ").to_string();
+        let no_urls = normalized.replace_all(&self.url_regex, " ");
 
         // Remove emojis
-        let no_emojis = self.emoji_regex.replace_all(&no_urls, " ").to_string();
+        let no_emojis = no_urls.replace_all(&self.emoji_regex, " ");
 
         // Replace special characters with space
-        let no_special = self.special_chars_regex.replace_all(&no_emojis, " ").to_string();
+        let no_special = no_emojis.replace_all(&self.special_chars_regex, " ");
 
         // Normalize whitespace
-        let normalized_spaces = self.extra_spaces_regex.replace_all(&no_special, " ").to_string();
+        let normalized_spaces = no_special.replace_all(&self.extra_spaces_regex, " ");
 
         // Trim and convert to lowercase
         normalized_spaces.trim().to_lowercase()
     }
 
-    /// Tokenize the text into words
-    fn tokenize(&self, text: &str) -> Vec<String> {
-        text.split_whitespace()
-            .map(|s| s.to_string())
-            .filter(|s| !s.is_empty() && !self.stopwords.contains(s))
-            .collect()
+    /// The cleaned text alone
+    #[cfg(test)]
+    fn clean_text(&self, text: &str) -> String {
+        self.clean(text).into_string()
     }
 
-    /// Lemmatize/stem the tokens
-    fn lemmatize(&self, tokens: &[String]) -> String {
-        tokens
-            .iter()
-            .map(|token| self.stemmer.stem(token))
-            .collect::<Vec<_>>()
-            .join(" ")
+    /// Tokenize the text into words, dropping the stopwords of its language
+    #[cfg(test)]
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        self.lexicon.pipeline(detect_language(text).as_deref()).tokenize(text)
     }
 
-    /// Extract named entities from text (simplified implementation)
+    /// Extract named entities from cleaned text (simplified implementation), with positions in it
     fn extract_entities(&self, text: &str) -> Vec<NamedEntity> {
         // This is a very simplified implementation
         // In a real-world scenario, you would use a proper NER model
@@ -125,20 +186,21 @@ impl NlpProcessor {
             if info.lang() == Lang::Eng && info.confidence() > 0.5 {
                 // Simple rule-based entity extraction for demonstration
                 // Look for capitalized words that might be names
-                let words: Vec<&str> = text.split_whitespace().collect();
-                
-                for (i, word) in words.iter().enumerate() {
+                // Cleaned text has single spaces between words, so each word's position follows
+                // from the ones before it
+                let mut start = 0;
+                for (i, word) in text.split(' ').enumerate() {
+                    let word_start = start;
+                    start += word.len() + 1;
+
                     if !word.is_empty() && word.chars().next().unwrap().is_uppercase() {
                         // Skip common sentence starters
-                        if i > 0 || !["I", "The", "A", "An", "This", "That"].contains(word) {
-                            let start_pos = text.find(word).unwrap_or(0);
-                            let end_pos = start_pos + word.len();
-                            
+                        if i > 0 || !["I", "The", "A", "An", "This", "That"].contains(&word) {
                             entities.push(NamedEntity {
                                 text: word.to_string(),
                                 entity_type: "PERSON".to_string(), // Simplified
-                                start_pos,
-                                end_pos,
+                                start: word_start,
+                                end: word_start + word.len(),
                             });
                         }
                     }
@@ -178,41 +240,117 @@ impl NlpProcessor {
         }
     }
 
-    /// Process a batch of messages and store results in the database
-    pub fn process_messages(&self, db: &Database, message_ids: &[i32]) -> Result<Vec<DbProcessedMessage>> {
-        let mut processed_messages = Vec::new();
-        let conn = &mut db.get_connection()?;
+    /// Process a batch of messages and store results in the database.
+    ///
+    /// Messages and existing results are read with one query each, and all new results are
+    /// written in a single transaction.
+    pub fn process_messages(&self, db: &Database, message_ids: &[i32]) -> Result<BatchReport> {
+        let started = Instant::now();
+
+        // Messages already processed with this version are returned as they are
+        let mut processed_messages = db.get_processed_messages_for_ids(message_ids, &self.version)?;
+        let done: HashSet<i32> = processed_messages.iter().map(|p| p.original_message_id).collect();
+
+        let pending: Vec<i32> = message_ids.iter().copied().filter(|id| !done.contains(id)).collect();
+        let messages = db.get_messages_by_ids(&pending)?;
+        if let Some(missing) = pending.iter().find(|id| !messages.iter().any(|m| m.id == **id)) {
+            return Err(anyhow::anyhow!("Message with ID {} not found", missing));
+        }
 
-        for &message_id in message_ids {
-            // Check if message has already been processed with this version
-            let existing = db.get_processed_message(message_id, &self.version)?;
-            if existing.is_some() {
-                processed_messages.push(existing.unwrap());
+        let mut new_processed = Vec::with_capacity(messages.len());
+        for message in messages {
+            // Skip messages without text
+            let Some(text) = message.text else {
                 continue;
-            }
+            };
 
-            // Get the message from the database
-            let message = db.get_message_by_id(message_id)?
-                .context(format!("Message with ID {} not found", message_id))?;
+            let analysis = self
+                .process_text(&text)
+                .with_context(|| format!("Failed to process message {}", message.id))?;
+            new_processed.push(analysis.to_new_processed_message(message.id, &self.version));
+        }
+        let analysis_time = started.elapsed();
 
-            // Skip messages without text
-            if message.text.is_none() {
-                continue;
-            }
+        let write_started = Instant::now();
+        let inserted = db.add_processed_messages(new_processed)?;
+        let write_time = write_started.elapsed();
 
-            // Process the message text
-            let analysis = self.process_text(&message.text.unwrap())?;
+        let inserted_count = inserted.len();
+        processed_messages.extend(inserted);
 
-            // Convert to database model
-            let new_processed = analysis.to_new_processed_message(message_id, &self.version);
+        Ok(BatchReport {
+            processed: processed_messages,
+            inserted: inserted_count,
+            analysis_time,
+            write_time,
+        })
+    }
+}
 
-            // Save to database
-            let processed_message = db.add_processed_message(new_processed)?;
-            processed_messages.push(processed_message);
-        }
+/// The language of `text` as an ISO 639-3 code (e.g. "eng", "fra"), or `None` when whatlang
+/// isn't confident, which is usual for short replies like "ok" or "lol"
+pub fn detect_language(text: &str) -> Option<String> {
+    detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code().to_string())
+}
 
-        Ok(processed_messages)
+/// Sentences of `text` by Unicode sentence boundaries, each with its byte offset and without
+/// surrounding whitespace
+pub fn split_sentences(text: &str) -> Vec<(usize, &str)> {
+    text.split_sentence_bound_indices()
+        .filter_map(|(start, sentence)| {
+            let trimmed = sentence.trim_start();
+            let start = start + sentence.len() - trimmed.len();
+            let trimmed = trimmed.trim_end();
+            (!trimmed.is_empty()).then_some((start, trimmed))
+        })
+        .collect()
+}
+
+/// The most negative and most positive of a message's sentences, leaving out neutral ones
+pub fn extreme_sentences(sentences: &[SentenceSentiment]) -> (Option<&SentenceSentiment>, Option<&SentenceSentiment>) {
+    let negative = sentences.iter().filter(|s| s.score < 0.0).min_by(|a, b| a.score.total_cmp(&b.score));
+    let positive = sentences.iter().filter(|s| s.score > 0.0).max_by(|a, b| a.score.total_cmp(&b.score));
+    (negative, positive)
+}
+
+/// Names of the analysis fields that differ between two versions' output for one message
+pub fn changed_fields(old: &DbProcessedMessage, new: &DbProcessedMessage) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+
+    if old.processed_text != new.processed_text {
+        fields.push("processed_text");
+    }
+    if old.tokens != new.tokens {
+        fields.push("tokens");
+    }
+    if old.lemmatized_text != new.lemmatized_text {
+        fields.push("lemmatized_text");
+    }
+    if old.named_entities != new.named_entities {
+        fields.push("named_entities");
     }
+    if old.flags != new.flags {
+        fields.push("flags");
+    }
+    if old.language != new.language {
+        fields.push("language");
+    }
+    if old.sentence_sentiments != new.sentence_sentiments {
+        fields.push("sentence_sentiments");
+    }
+
+    // Scores round-trip through SQLite REAL, so ignore float noise
+    let sentiment_changed = match (old.sentiment_score, new.sentiment_score) {
+        (Some(a), Some(b)) => (a - b).abs() > 1e-6,
+        (a, b) => a.is_some() != b.is_some(),
+    };
+    if sentiment_changed {
+        fields.push("sentiment_score");
+    }
+
+    fields
 }
 
 #[cfg(test)]
@@ -246,6 +384,40 @@ mod tests {
         assert_eq!(cleaned, "too many spaces");
     }
 
+    #[test]
+    fn test_entity_offsets() {
+        let processor = NlpProcessor::new("test_v1");
+
+        // A repeated word gets its own position each time
+        let entities = processor.extract_entities("Saw Rhonda and Rhonda again at the lake with friends");
+        let rhondas: Vec<(usize, usize)> = entities
+            .iter()
+            .filter(|entity| entity.text == "Rhonda")
+            .map(|entity| (entity.start, entity.end))
+            .collect();
+        assert_eq!(rhondas, vec![(4, 10), (15, 21)]);
+    }
+
+    #[test]
+    fn test_sentence_sentiment() {
+        let text = "Great game today!  I hate the traffic though. See you";
+        let sentences = split_sentences(text);
+        assert_eq!(sentences.len(), 3);
+        assert_eq!(sentences[1], (19, "I hate the traffic though."));
+        assert_eq!(&text[sentences[2].0..], "See you");
+
+        let processor = NlpProcessor::new("test_v1");
+        let analysis = processor.process_text(text).unwrap();
+        let scores: Vec<f32> = analysis.sentences.iter().map(|s| s.score).collect();
+        assert_eq!(scores, vec![1.0, -1.0, 0.0]);
+        assert_eq!(&text[analysis.sentences[1].start..analysis.sentences[1].end], "I hate the traffic though.");
+
+        let (negative, positive) = extreme_sentences(&analysis.sentences);
+        assert_eq!(negative.unwrap().text, "I hate the traffic though.");
+        assert_eq!(positive.unwrap().text, "Great game today!");
+        assert_eq!(extreme_sentences(&analysis.sentences[2..]), (None, None));
+    }
+
     #[test]
     fn test_tokenize() {
         let processor = NlpProcessor::new("test_v1");
@@ -265,6 +437,31 @@ mod tests {
         assert!(tokens.contains(&"stopwords".to_string()));
     }
 
+    #[test]
+    fn test_changed_fields() {
+        let processed = |text: &str, score: f32| DbProcessedMessage {
+            id: 1,
+            original_message_id: 1,
+            processed_text: text.to_string(),
+            tokens: None,
+            lemmatized_text: None,
+            named_entities: None,
+            sentiment_score: Some(score),
+            processed_at: chrono::Utc::now().naive_utc(),
+            processing_version: "v1.0".to_string(),
+            flags: None,
+            flag_severity: None,
+            language: None,
+            sentence_sentiments: None,
+        };
+
+        assert!(changed_fields(&processed("hi", 0.5), &processed("hi", 0.5)).is_empty());
+        assert_eq!(
+            changed_fields(&processed("hi", 0.5), &processed("hello", -0.5)),
+            vec!["processed_text", "sentiment_score"]
+        );
+    }
+
     #[test]
     fn test_sentiment_analysis() {
         let processor = NlpProcessor::new("test_v1");
@@ -284,4 +481,17 @@ mod tests {
         let neutral_score = processor.analyze_sentiment(neutral_text);
         assert_eq!(neutral_score, 0.0);
     }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_clean_text_normalizes_any_input(text in "\\PC{0,200}|[ \t\n]{0,5}https?://[a-z./]{1,20}[ \t\n]{0,5}") {
+            let processor = NlpProcessor::new("test_v1");
+            let cleaned = processor.clean_text(&text);
+
+            // Single spaces between words, none at the ends, and no URLs left behind
+            proptest::prop_assert!(cleaned.split(' ').all(|word| !word.is_empty() && !word.contains(char::is_whitespace)) || cleaned.is_empty());
+            proptest::prop_assert!(!cleaned.contains("://"));
+            proptest::prop_assert_eq!(cleaned.clone(), processor.clean_text(&text));
+        }
+    }
 }
@@ -0,0 +1,234 @@
+use anyhow::Result;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::Serialize;
+
+use crate::models::{Message, NamedEntity, SentenceSentiment};
+use crate::nlp::extreme_sentences;
+use crate::style::ExportStyle;
+
+/// Columns of an `export-nlp` CSV, in order
+const CSV_HEADER: [&str; 20] = [
+    "message_id",
+    "imessage_id",
+    "sent_at",
+    "contact",
+    "sender",
+    "is_from_me",
+    "service",
+    "text",
+    "processed_text",
+    "tokens",
+    "token_count",
+    "lemmatized_text",
+    "entities",
+    "entity_count",
+    "sentiment",
+    "most_negative_sentence",
+    "most_positive_sentence",
+    "language",
+    "flag_severity",
+    "processing_version",
+];
+
+/// A message joined with its processed row for one version, as `export-nlp` writes it
+#[derive(Debug, Clone, Serialize)]
+pub struct NlpExportRow {
+    /// Row ID in the local database, as taken by `hide` and `sql`
+    pub message_id: i32,
+    pub imessage_id: String,
+    /// Local time
+    pub sent_at: NaiveDateTime,
+    /// Contact the conversation is linked to
+    pub contact: Option<String>,
+    pub sender: String,
+    pub is_from_me: bool,
+    pub service: Option<String>,
+    pub text: Option<String>,
+    pub processed_text: String,
+    pub tokens: Vec<String>,
+    pub lemmatized_text: Option<String>,
+    pub entities: Vec<NamedEntity>,
+    pub sentiment: Option<f32>,
+    /// Sentiment of each sentence, empty for versions processed before it was scored
+    pub sentences: Vec<SentenceSentiment>,
+    /// ISO 639-3 code
+    pub language: Option<String>,
+    pub flag_severity: Option<f32>,
+    pub processing_version: String,
+    pub processed_at: NaiveDateTime,
+}
+
+impl NlpExportRow {
+    /// Entities as "TYPE:text", separated by "; ", for a single CSV cell
+    fn entities_cell(&self) -> String {
+        self.entities
+            .iter()
+            .map(|entity| format!("{}:{}", entity.entity_type, entity.text))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Apply the export style's redactions to message text. Rows that change also lose their
+/// processed text, tokens, lemmas, entities and sentences, which would show what was hidden.
+pub fn redact(rows: &mut [NlpExportRow], style: &ExportStyle) -> Result<usize> {
+    let mut messages: Vec<Message> = rows
+        .iter()
+        .map(|row| Message {
+            sender: row.sender.clone(),
+            timestamp: Local.from_local_datetime(&row.sent_at).earliest().unwrap_or_else(Local::now),
+            content: row.text.clone().unwrap_or_default(),
+            source: None,
+            imessage_id: Some(row.imessage_id.clone()),
+            service: row.service.clone(),
+            is_from_me: row.is_from_me,
+        })
+        .collect();
+
+    let redacted = style.redact(&mut messages)?;
+    for &i in &redacted {
+        let row = &mut rows[i];
+        row.text = Some(std::mem::take(&mut messages[i].content));
+        row.processed_text.clear();
+        row.tokens.clear();
+        row.lemmatized_text = None;
+        row.entities.clear();
+        row.sentences.clear();
+    }
+    Ok(redacted.len())
+}
+
+/// Split stored space-separated tokens
+pub fn split_tokens(tokens: Option<&str>) -> Vec<String> {
+    tokens.map(|tokens| tokens.split_whitespace().map(String::from).collect()).unwrap_or_default()
+}
+
+/// One row per message with tokens space-separated, entities flattened and counts alongside,
+/// so the file loads straight into a spreadsheet or dataframe
+pub fn to_csv(rows: &[NlpExportRow]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(CSV_HEADER)?;
+
+    let optional = |value: Option<f32>| value.map(|v| v.to_string()).unwrap_or_default();
+    for row in rows {
+        let (negative, positive) = extreme_sentences(&row.sentences);
+        let sentence = |s: Option<&SentenceSentiment>| s.map(|s| s.text.clone()).unwrap_or_default();
+        writer.write_record([
+            row.message_id.to_string(),
+            row.imessage_id.clone(),
+            row.sent_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            row.contact.clone().unwrap_or_default(),
+            row.sender.clone(),
+            row.is_from_me.to_string(),
+            row.service.clone().unwrap_or_default(),
+            row.text.clone().unwrap_or_default(),
+            row.processed_text.clone(),
+            row.tokens.join(" "),
+            row.tokens.len().to_string(),
+            row.lemmatized_text.clone().unwrap_or_default(),
+            row.entities_cell(),
+            row.entities.len().to_string(),
+            optional(row.sentiment),
+            sentence(negative),
+            sentence(positive),
+            row.language.clone().unwrap_or_default(),
+            optional(row.flag_severity),
+            row.processing_version.clone(),
+        ])?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// A JSON array of rows, keeping tokens as an array and entities with their types and offsets
+pub fn to_json(rows: &[NlpExportRow]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(rows)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact::RedactionRule;
+
+    fn row() -> NlpExportRow {
+        let date = NaiveDateTime::parse_from_str("2025-01-20 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        NlpExportRow {
+            message_id: 7,
+            imessage_id: "guid-7".to_string(),
+            sent_at: date,
+            contact: Some("Phil".to_string()),
+            sender: "Phil".to_string(),
+            is_from_me: false,
+            service: Some("iMessage".to_string()),
+            text: Some("Meet Rhonda in Boston, ok?".to_string()),
+            processed_text: "meet rhonda in boston ok".to_string(),
+            tokens: split_tokens(Some("meet rhonda boston")),
+            lemmatized_text: None,
+            entities: vec![
+                NamedEntity { text: "Rhonda".to_string(), entity_type: "PERSON".to_string(), start: 5, end: 11 },
+                NamedEntity { text: "Boston".to_string(), entity_type: "LOCATION".to_string(), start: 15, end: 21 },
+            ],
+            sentiment: Some(0.5),
+            sentences: vec![
+                SentenceSentiment { text: "Meet Rhonda in Boston, ok?".to_string(), start: 0, end: 26, score: 0.0 },
+            ],
+            language: Some("eng".to_string()),
+            flag_severity: None,
+            processing_version: "v2.0".to_string(),
+            processed_at: date,
+        }
+    }
+
+    #[test]
+    fn test_nlp_export_csv() {
+        let csv = to_csv(&[row()]).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("message_id,imessage_id,sent_at,contact,"));
+        assert_eq!(
+            lines[1],
+            "7,guid-7,2025-01-20 12:00:00,Phil,Phil,false,iMessage,\"Meet Rhonda in Boston, ok?\",\
+             meet rhonda in boston ok,meet rhonda boston,3,,PERSON:Rhonda; LOCATION:Boston,2,0.5,,,eng,,v2.0"
+        );
+
+        // The strongest sentences get their own columns
+        let mut mixed = row();
+        mixed.sentences.push(SentenceSentiment { text: "I hate waiting".to_string(), start: 27, end: 41, score: -1.0 });
+        assert!(to_csv(&[mixed]).unwrap().contains(",0.5,I hate waiting,,eng,"));
+    }
+
+    #[test]
+    fn test_nlp_export_json() {
+        let json: serde_json::Value = serde_json::from_str(&to_json(&[row()]).unwrap()).unwrap();
+        assert_eq!(json[0]["tokens"][1], "rhonda");
+        assert_eq!(json[0]["entities"][1]["entity_type"], "LOCATION");
+        assert_eq!(json[0]["sentiment"], 0.5);
+        assert!(json[0]["flag_severity"].is_null());
+        assert_eq!(json[0]["sentences"][0]["end"], 26);
+
+        assert!(split_tokens(None).is_empty());
+    }
+
+    #[test]
+    fn test_nlp_export_redaction() {
+        let style = ExportStyle {
+            redactions: vec![RedactionRule { pattern: "Boston".to_string(), replacement: "[city]".to_string() }],
+            ..Default::default()
+        };
+        let mut other = row();
+        other.text = Some("See you soon".to_string());
+        let mut rows = vec![row(), other];
+        assert_eq!(redact(&mut rows, &style).unwrap(), 1);
+
+        assert_eq!(rows[0].text.as_deref(), Some("Meet Rhonda in [city], ok?"));
+        assert!(rows[0].processed_text.is_empty() && rows[0].tokens.is_empty());
+        assert!(rows[0].entities.is_empty() && rows[0].sentences.is_empty());
+        assert_eq!(rows[0].sentiment, Some(0.5));
+        assert!(!to_csv(&rows[..1]).unwrap().contains("Boston"));
+
+        // Rows without a match keep everything derived from their text
+        assert_eq!(rows[1].tokens.len(), 3);
+        assert_eq!(rows[1].entities.len(), 2);
+    }
+}
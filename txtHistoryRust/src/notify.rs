@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::alerts::SentimentAlert;
+use crate::config::NotificationConfig;
+
+/// Outcome of a long-running operation, sent to the configured notification sinks
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationReport {
+    pub operation: String,
+    pub success: bool,
+    /// Number of items handled (messages, files or transcripts depending on the operation)
+    pub count: usize,
+    pub duration_secs: f64,
+    pub error: Option<String>,
+}
+
+impl OperationReport {
+    /// Build a report from the result of an operation
+    pub fn new(operation: &str, result: &Result<usize>, duration: Duration) -> Self {
+        Self {
+            operation: operation.to_string(),
+            success: result.is_ok(),
+            count: *result.as_ref().unwrap_or(&0),
+            duration_secs: duration.as_secs_f64(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        }
+    }
+
+    /// One-line human readable summary
+    pub fn summary(&self) -> String {
+        match &self.error {
+            None => format!(
+                "{} finished: {} items in {:.1}s",
+                self.operation, self.count, self.duration_secs
+            ),
+            Some(error) => format!(
+                "{} failed after {:.1}s: {}",
+                self.operation, self.duration_secs, error
+            ),
+        }
+    }
+}
+
+/// Sends operation reports to a webhook and/or the macOS notification center
+pub struct Notifier {
+    config: NotificationConfig,
+}
+
+impl Notifier {
+    /// Create a notifier from configuration
+    pub fn new(config: &NotificationConfig) -> Self {
+        Self { config: config.clone() }
+    }
+
+    /// Send a report to every configured sink. Failures are logged, never returned,
+    /// so a broken webhook can't fail the operation being reported on.
+    pub fn notify(&self, report: &OperationReport) {
+        self.send(report, &report.summary());
+    }
+
+    /// Send a sentiment alert from `watch` mode to every configured sink
+    pub fn alert(&self, alert: &SentimentAlert) {
+        self.send(alert, &alert.summary());
+    }
+
+    fn send<T: Serialize>(&self, payload: &T, summary: &str) {
+        if let Some(url) = &self.config.webhook_url {
+            if let Err(e) = Self::send_webhook(url, payload) {
+                eprintln!("Failed to send webhook notification: {:#}", e);
+            }
+        }
+
+        if self.config.macos {
+            if let Err(e) = Self::send_macos_notification(summary) {
+                eprintln!("Failed to send macOS notification: {:#}", e);
+            }
+        }
+    }
+
+    fn send_webhook<T: Serialize>(url: &str, payload: &T) -> Result<()> {
+        ureq::post(url)
+            .timeout(Duration::from_secs(10))
+            .send_json(payload)
+            .context("Webhook request failed")?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn send_macos_notification(summary: &str) -> Result<()> {
+        let script = format!(
+            "display notification \"{}\" with title \"txt-history\"",
+            summary.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+
+        let status = std::process::Command::new("osascript")
+            .arg("-e")
+            .arg(script)
+            .status()
+            .context("Failed to run osascript")?;
+
+        if !status.success() {
+            return Err(anyhow::anyhow!("osascript exited with {}", status));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn send_macos_notification(_summary: &str) -> Result<()> {
+        Err(anyhow::anyhow!("macOS notifications are only available on macOS"))
+    }
+}
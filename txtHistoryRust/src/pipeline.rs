@@ -0,0 +1,323 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDateTime};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+/// A step of a pipeline. Steps run in the order they're listed in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PipelineStep {
+    /// Import each contact's messages from chat.db into the database
+    Import,
+    /// Delete messages stored twice for a contact (same sender, text and time)
+    Dedupe,
+    /// Run NLP processing on new messages
+    Process,
+    /// Write an extractive digest per contact
+    Digest,
+    /// Export each contact's conversation
+    Export,
+    /// Pack the output directory into a .tar.gz next to it
+    Compress,
+    /// Send a notification with the pipeline's results
+    Notify,
+}
+
+impl PipelineStep {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Import => "import",
+            Self::Dedupe => "dedupe",
+            Self::Process => "process",
+            Self::Digest => "digest",
+            Self::Export => "export",
+            Self::Compress => "compress",
+            Self::Notify => "notify",
+        }
+    }
+}
+
+/// A named chain of steps defined under `pipelines` in the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    /// Contacts every step runs for
+    pub contacts: Vec<String>,
+
+    pub steps: Vec<PipelineStep>,
+
+    /// Start date for message range (YYYY-MM-DD)
+    #[serde(default)]
+    pub start_date: Option<String>,
+
+    /// End date for message range (YYYY-MM-DD)
+    #[serde(default)]
+    pub end_date: Option<String>,
+
+    /// Directory that receives one subdirectory per contact (defaults to the profile's output directory)
+    #[serde(default)]
+    pub output_dir: Option<String>,
+
+    /// Export format (txt, csv, json or parquet)
+    #[serde(default = "default_format")]
+    pub format: String,
+
+    /// Number of lines per exported chunk
+    #[serde(default)]
+    pub lines_per_chunk: Option<usize>,
+
+    /// Approximate number of LLM tokens per chunk (takes precedence over lines)
+    #[serde(default)]
+    pub tokens_per_chunk: Option<usize>,
+
+    /// Version the process step stores results under
+    #[serde(default = "default_nlp_version")]
+    pub nlp_version: String,
+
+    /// Highlights per day in the digest
+    #[serde(default = "default_digest_highlights")]
+    pub digest_highlights: usize,
+}
+
+fn default_format() -> String {
+    "txt".to_string()
+}
+
+fn default_nlp_version() -> String {
+    "v1.0".to_string()
+}
+
+fn default_digest_highlights() -> usize {
+    5
+}
+
+/// How far a step got in the latest run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+/// Status of one step, saved after every step so a failed run can be resumed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepRecord {
+    pub step: PipelineStep,
+    pub status: StepStatus,
+    /// Number of items the step handled
+    pub count: usize,
+    pub duration_secs: f64,
+    pub finished_at: Option<NaiveDateTime>,
+    pub error: Option<String>,
+}
+
+/// Per-step status of a pipeline's latest run, stored as JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineRun {
+    pub name: String,
+    pub started_at: NaiveDateTime,
+    pub steps: Vec<StepRecord>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl PipelineRun {
+    /// Start a run of `pipeline`, saving its status to `path`.
+    ///
+    /// With `resume`, steps that finished in the previous run are skipped, as long as the
+    /// pipeline's steps haven't changed since.
+    pub fn start(name: &str, pipeline: &PipelineConfig, path: &Path, resume: bool) -> Result<Self> {
+        if resume {
+            if let Some(previous) = Self::load(path)? {
+                let same_steps = previous.steps.iter().map(|s| s.step).eq(pipeline.steps.iter().copied());
+                if !same_steps {
+                    return Err(anyhow::anyhow!(
+                        "Pipeline {} changed since its last run; run it again without --resume",
+                        name
+                    ));
+                }
+                return Ok(previous);
+            }
+        }
+
+        let run = Self {
+            name: name.to_string(),
+            started_at: Local::now().naive_local(),
+            steps: pipeline
+                .steps
+                .iter()
+                .map(|&step| StepRecord {
+                    step,
+                    status: StepStatus::Pending,
+                    count: 0,
+                    duration_secs: 0.0,
+                    finished_at: None,
+                    error: None,
+                })
+                .collect(),
+            path: path.to_path_buf(),
+        };
+        run.save()?;
+
+        Ok(run)
+    }
+
+    /// Load the status of a pipeline's latest run, if it has run before
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pipeline status {}", path.display()))?;
+        let mut run: Self = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid pipeline status {}", path.display()))?;
+        run.path = path.to_path_buf();
+
+        Ok(Some(run))
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write pipeline status {}", self.path.display()))
+    }
+
+    /// Index of the next step that hasn't finished
+    pub fn next_step(&self) -> Option<usize> {
+        self.steps.iter().position(|s| s.status != StepStatus::Done)
+    }
+
+    /// Record a step's result and save the run's status. A failed step's error is returned
+    /// so the pipeline stops there.
+    pub fn finish_step(&mut self, index: usize, result: Result<usize>, duration: Duration) -> Result<usize> {
+        let record = &mut self.steps[index];
+        record.duration_secs = duration.as_secs_f64();
+        record.finished_at = Some(Local::now().naive_local());
+
+        match &result {
+            Ok(count) => {
+                record.status = StepStatus::Done;
+                record.count = *count;
+                record.error = None;
+            },
+            Err(e) => {
+                record.status = StepStatus::Failed;
+                record.error = Some(format!("{:#}", e));
+            },
+        }
+
+        self.save()?;
+        result
+    }
+
+    /// Items handled by the last finished step before `index`
+    pub fn last_count(&self, index: usize) -> usize {
+        self.steps[..index]
+            .iter()
+            .rev()
+            .find(|s| s.status == StepStatus::Done)
+            .map_or(0, |s| s.count)
+    }
+
+    /// One line per step, e.g. "export     done       120 items in 1.2s"
+    pub fn summary(&self) -> String {
+        self.steps
+            .iter()
+            .map(|record| {
+                let status = match record.status {
+                    StepStatus::Pending => "pending",
+                    StepStatus::Done => "done",
+                    StepStatus::Failed => "failed",
+                };
+                let mut line = format!("{:<10} {:<8}", record.step.as_str(), status);
+                if record.status != StepStatus::Pending {
+                    line.push_str(&format!(" {:>6} items in {:.1}s", record.count, record.duration_secs));
+                }
+                if let Some(error) = &record.error {
+                    line.push_str(&format!(": {}", error));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Pack a directory into a gzipped tarball, returning the archive's path (`<dir>.tar.gz`)
+pub fn compress_dir(dir: &Path) -> Result<PathBuf> {
+    let name = dir
+        .file_name()
+        .with_context(|| format!("Can't compress {}", dir.display()))?;
+    let archive_path = dir.with_file_name(format!("{}.tar.gz", name.to_string_lossy()));
+
+    let file = File::create(&archive_path)
+        .with_context(|| format!("Failed to create {}", archive_path.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    archive.append_dir_all(name, dir)?;
+    archive.into_inner()?.finish()?;
+
+    Ok(archive_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pipeline(steps: Vec<PipelineStep>) -> PipelineConfig {
+        serde_json::from_value(serde_json::json!({
+            "contacts": ["Phil"],
+            "steps": steps,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resume_skips_finished_steps() {
+        let path = std::env::temp_dir().join(format!("txt_history_pipeline_test_{}.json", std::process::id()));
+        let config = pipeline(vec![PipelineStep::Import, PipelineStep::Export, PipelineStep::Notify]);
+        assert_eq!(config.format, "txt");
+
+        let mut run = PipelineRun::start("monthly", &config, &path, false).unwrap();
+        assert_eq!(run.next_step(), Some(0));
+        run.finish_step(0, Ok(12), Duration::from_secs(1)).unwrap();
+        assert!(run
+            .finish_step(1, Err(anyhow::anyhow!("disk full")), Duration::from_secs(1))
+            .is_err());
+
+        let resumed = PipelineRun::start("monthly", &config, &path, true).unwrap();
+        assert_eq!(resumed.next_step(), Some(1));
+        assert_eq!(resumed.steps[0].count, 12);
+        assert!(resumed.summary().contains("failed"));
+
+        // Without --resume every step runs again
+        let fresh = PipelineRun::start("monthly", &config, &path, false).unwrap();
+        assert_eq!(fresh.next_step(), Some(0));
+
+        let changed = pipeline(vec![PipelineStep::Import]);
+        assert!(PipelineRun::start("monthly", &changed, &path, true).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_compress_dir() {
+        let dir = std::env::temp_dir().join(format!("txt_history_compress_test_{}", std::process::id()));
+        let output = dir.join("monthly");
+        fs::create_dir_all(output.join("Phil")).unwrap();
+        fs::write(output.join("Phil").join("chunk_1.txt"), "hi").unwrap();
+
+        let archive = compress_dir(&output).unwrap();
+        assert_eq!(archive, dir.join("monthly.tar.gz"));
+        assert!(fs::metadata(&archive).unwrap().len() > 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+/// Profile used when `--profile` isn't given; keeps the original data locations
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Directory holding one subdirectory per named profile
+const PROFILES_DIR: &str = "data/profiles";
+
+/// A separate archive ("workspace") with its own database, cache and output directory,
+/// so e.g. `personal` and `legal` exports never mix.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    name: String,
+}
+
+impl Profile {
+    /// Select a profile by name, or the default profile
+    pub fn load(name: Option<&str>) -> Result<Self> {
+        let name = name.unwrap_or(DEFAULT_PROFILE);
+
+        let valid = !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(anyhow::anyhow!(
+                "Invalid profile name: {}. Use letters, numbers, '-' and '_'",
+                name
+            ));
+        }
+
+        Ok(Self { name: name.to_string() })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.name == DEFAULT_PROFILE
+    }
+
+    /// Directory that holds the profile's database and cache
+    pub fn data_dir(&self) -> PathBuf {
+        Path::new(PROFILES_DIR).join(&self.name)
+    }
+
+    /// Database URL for the profile. The default profile honours `DATABASE_URL`.
+    pub fn database_url(&self) -> String {
+        if self.is_default() {
+            return env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:data/messages.db".to_string());
+        }
+
+        self.data_dir().join("messages.db").to_string_lossy().to_string()
+    }
+
+    /// Directory for the message cache
+    pub fn cache_dir(&self) -> PathBuf {
+        if self.is_default() {
+            return PathBuf::from(".message_cache");
+        }
+
+        self.data_dir().join("cache")
+    }
+
+    /// Default output directory for exports
+    pub fn output_dir(&self) -> PathBuf {
+        if self.is_default() {
+            return PathBuf::from("./output");
+        }
+
+        Path::new("./output").join(&self.name)
+    }
+
+    /// Directory for the daily database snapshots
+    pub fn snapshots_dir(&self) -> PathBuf {
+        if self.is_default() {
+            return PathBuf::from("data/snapshots");
+        }
+
+        self.data_dir().join("snapshots")
+    }
+
+    /// Directory holding the status of each pipeline's latest run
+    pub fn pipelines_dir(&self) -> PathBuf {
+        if self.is_default() {
+            return PathBuf::from("data/pipelines");
+        }
+
+        self.data_dir().join("pipelines")
+    }
+
+    /// Names of all profiles that have been used, including the default
+    pub fn list() -> Result<Vec<String>> {
+        let mut names = vec![DEFAULT_PROFILE.to_string()];
+
+        if Path::new(PROFILES_DIR).is_dir() {
+            for entry in fs::read_dir(PROFILES_DIR)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() && entry.file_name() != DEFAULT_PROFILE {
+                    names.push(entry.file_name().to_string_lossy().to_string());
+                }
+            }
+        }
+
+        names[1..].sort();
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_paths() {
+        let legal = Profile::load(Some("legal")).unwrap();
+        assert_eq!(legal.database_url(), "data/profiles/legal/messages.db");
+        assert_eq!(legal.cache_dir(), PathBuf::from("data/profiles/legal/cache"));
+        assert_eq!(legal.output_dir(), PathBuf::from("./output/legal"));
+        assert_eq!(legal.pipelines_dir(), PathBuf::from("data/profiles/legal/pipelines"));
+
+        let default = Profile::load(None).unwrap();
+        assert!(default.is_default());
+        assert_eq!(default.output_dir(), PathBuf::from("./output"));
+
+        assert!(Profile::load(Some("../etc")).is_err());
+        assert!(Profile::load(Some("")).is_err());
+    }
+}
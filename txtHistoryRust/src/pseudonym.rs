@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::models::Message;
+
+/// Phone numbers and email addresses in message text
+const IDENTIFIER_PATTERN: &str = r"[\w.+-]+@[\w-]+(?:\.[\w-]+)+|\+?\(?\d[\d\s().-]{7,}\d";
+
+/// Hex digits of the salted hash kept in identifier tokens
+const TOKEN_LEN: usize = 12;
+
+/// Pseudonyms for the names and identifiers in anonymized exports. Reusing the same file across
+/// exports keeps each person's pseudonym the same; anyone holding it can reverse them, so keep
+/// it apart from the exports.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PseudonymMap {
+    /// Mixed into identifier hashes, so they can't be matched against hashes of known numbers
+    pub salt: String,
+
+    /// Real name to pseudonym. Names added by hand are replaced in message text too.
+    pub names: BTreeMap<String, String>,
+
+    /// Normalized phone number or email to the token shown in its place
+    pub identifiers: BTreeMap<String, String>,
+}
+
+impl PseudonymMap {
+    /// An empty map with a new random salt
+    pub fn new() -> Self {
+        let salt: [u8; 16] = rand::random();
+        Self { salt: salt.iter().map(|b| format!("{:02x}", b)).collect(), ..Self::default() }
+    }
+
+    /// Read the map at `path`, or start a new one if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pseudonym map {}", path.display()))?;
+        let map: Self = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid pseudonym map {}", path.display()))?;
+        if map.salt.is_empty() {
+            return Err(anyhow::anyhow!("Pseudonym map {} has no salt", path.display()));
+        }
+        Ok(map)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write pseudonym map {}", path.display()))
+    }
+
+    /// The pseudonym for a name, assigning the next free "Person N" if it has none
+    pub fn pseudonym(&mut self, name: &str) -> String {
+        if let Some(pseudonym) = self.names.get(name) {
+            return pseudonym.clone();
+        }
+
+        let taken: Vec<&String> = self.names.values().collect();
+        let pseudonym = (self.names.len() + 1..)
+            .map(|n| format!("Person {}", n))
+            .find(|candidate| !taken.contains(&candidate))
+            .unwrap_or_default();
+        self.names.insert(name.to_string(), pseudonym.clone());
+        pseudonym
+    }
+
+    /// The token for a phone number or email: a salted hash, so the same identifier always gets
+    /// the same token under this map
+    pub fn identifier_token(&mut self, identifier: &str) -> String {
+        let normalized = normalize_identifier(identifier);
+        if let Some(token) = self.identifiers.get(&normalized) {
+            return token.clone();
+        }
+
+        let hash = Sha256::new()
+            .chain_update(self.salt.as_bytes())
+            .chain_update(normalized.as_bytes())
+            .finalize();
+        let token = format!("[id:{}]", &format!("{:x}", hash)[..TOKEN_LEN]);
+        self.identifiers.insert(normalized, token.clone());
+        token
+    }
+
+    /// Replace senders with pseudonyms, and known names, phone numbers and emails in message
+    /// text. Returns the indices of the messages whose text changed.
+    pub fn anonymize(&mut self, messages: &mut [Message]) -> Result<Vec<usize>> {
+        for message in messages.iter() {
+            self.pseudonym(&message.sender);
+        }
+
+        let by_name: HashMap<String, String> = self
+            .names
+            .iter()
+            .filter(|(name, _)| !name.trim().is_empty())
+            .map(|(name, pseudonym)| (name.to_lowercase(), pseudonym.clone()))
+            .collect();
+        let mut names: Vec<&String> = by_name.keys().collect();
+        // Longest first, so "Phil Smith" is replaced whole rather than as "Phil"
+        names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+        let names_regex = match names.is_empty() {
+            true => None,
+            false => {
+                let alternatives: Vec<String> = names.iter().map(|name| regex::escape(name)).collect();
+                Some(Regex::new(&format!(r"(?i)\b(?:{})\b", alternatives.join("|")))?)
+            },
+        };
+        let identifier_regex = Regex::new(IDENTIFIER_PATTERN)?;
+
+        let mut changed = Vec::new();
+        for (i, message) in messages.iter_mut().enumerate() {
+            message.sender = self.names[&message.sender].clone();
+
+            // Identifiers first, so "phil@example.com" isn't taken for the name "Phil"
+            let mut content = identifier_regex
+                .replace_all(&message.content, |caps: &Captures<'_>| match is_identifier(&caps[0]) {
+                    true => self.identifier_token(&caps[0]),
+                    false => caps[0].to_string(),
+                })
+                .into_owned();
+            if let Some(regex) = &names_regex {
+                content = regex
+                    .replace_all(&content, |caps: &Captures<'_>| by_name[&caps[0].to_lowercase()].clone())
+                    .into_owned();
+            }
+
+            if content != message.content {
+                message.content = content;
+                changed.push(i);
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+/// Emails, and numbers with as many digits as a phone number (not dates or amounts)
+fn is_identifier(text: &str) -> bool {
+    let digits = text.chars().filter(char::is_ascii_digit).count();
+    text.contains('@') || (10..=15).contains(&digits)
+}
+
+/// Lowercase emails and keep the last ten digits of phone numbers, like `same_identifier`, so
+/// "(555) 123-4567" and "+15551234567" get the same token
+fn normalize_identifier(identifier: &str) -> String {
+    if identifier.contains('@') {
+        return identifier.trim().to_lowercase();
+    }
+
+    let digits: String = identifier.chars().filter(char::is_ascii_digit).collect();
+    digits[digits.len().saturating_sub(10)..].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(sender: &str, content: &str) -> Message {
+        Message {
+            sender: sender.to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    #[test]
+    fn test_pseudonyms_are_consistent() {
+        let mut map = PseudonymMap::new();
+        map.names.insert("Everly".to_string(), "Child 1".to_string());
+
+        let mut messages = vec![
+            message("Phil", "Call me at (555) 123-4567 or phil@example.com"),
+            message("Jess", "phil, Everly's pickup is 2025-01-20"),
+        ];
+        assert_eq!(map.anonymize(&mut messages).unwrap(), vec![0, 1]);
+        assert_eq!(messages[0].sender, "Person 2");
+        assert_eq!(messages[1].sender, "Person 3");
+        assert_eq!(messages[1].content, "Person 2, Child 1's pickup is 2025-01-20");
+
+        // A later export with the saved map gets the same pseudonyms and tokens
+        let dir = std::env::temp_dir().join(format!("txt-history-pseudonyms-{}", std::process::id()));
+        let path = dir.join("map.json");
+        map.save(&path).unwrap();
+        let mut reloaded = PseudonymMap::load(&path).unwrap();
+        let mut later = vec![message("Jess", "new number: +1 555 123 4567"), message("Phil", "ok")];
+        reloaded.anonymize(&mut later).unwrap();
+        assert_eq!(later[0].sender, "Person 3");
+        assert_eq!(later[1].sender, "Person 2");
+        let token = map.identifiers["5551234567"].clone();
+        assert_eq!(messages[0].content, format!("Call me at {} or {}", token, map.identifiers["phil@example.com"]));
+        assert_eq!(later[0].content, format!("new number: {}", token));
+
+        // Another salt gives other tokens
+        assert_ne!(PseudonymMap::new().identifier_token("5551234567"), map.identifier_token("5551234567"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+use std::borrow::Cow;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::models::Message;
+
+/// A pattern to hide in exported message text and what to show instead
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedactionRule {
+    /// Regular expression, e.g. "(?i)lincoln elementary" or "\\d+ Oak St(reet)?"
+    pub pattern: String,
+
+    /// Text put in place of each match; `$1` and `${name}` refer to capture groups
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+}
+
+fn default_replacement() -> String {
+    "[redacted]".to_string()
+}
+
+/// Compiled redaction rules, applied in order
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Redactor {
+    pub fn new(rules: &[RedactionRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let regex = Regex::new(&rule.pattern)
+                    .with_context(|| format!("Invalid redaction pattern: {}", rule.pattern))?;
+                Ok((regex, rule.replacement.clone()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for (regex, replacement) in &self.rules {
+            let replaced = match regex.replace_all(&text, replacement.as_str()) {
+                Cow::Owned(replaced) => Some(replaced),
+                Cow::Borrowed(_) => None,
+            };
+            if let Some(replaced) = replaced {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+
+    /// Redact message text in place, returning the indices of the messages that changed
+    pub fn redact_messages(&self, messages: &mut [Message]) -> Vec<usize> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let mut changed = Vec::new();
+        for (i, message) in messages.iter_mut().enumerate() {
+            let redacted = match self.redact(&message.content) {
+                Cow::Owned(content) => Some(content),
+                Cow::Borrowed(_) => None,
+            };
+            if let Some(content) = redacted {
+                message.content = content;
+                changed.push(i);
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(content: &str) -> Message {
+        Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    #[test]
+    fn test_redaction() {
+        let rules: Vec<RedactionRule> = serde_json::from_str(
+            r#"[
+                {"pattern": "(?i)lincoln elementary", "replacement": "[school]"},
+                {"pattern": "\\d+ Oak St(reet)?"},
+                {"pattern": "Everly (\\w+)", "replacement": "E. $1"}
+            ]"#,
+        )
+        .unwrap();
+        let redactor = Redactor::new(&rules).unwrap();
+
+        let mut messages = vec![
+            message("Pickup at Lincoln Elementary, then 42 Oak Street"),
+            message("See you soon"),
+            message("Everly Johnson is at LINCOLN ELEMENTARY"),
+        ];
+        assert_eq!(redactor.redact_messages(&mut messages), vec![0, 2]);
+        assert_eq!(messages[0].content, "Pickup at [school], then [redacted]");
+        assert_eq!(messages[1].content, "See you soon");
+        assert_eq!(messages[2].content, "E. Johnson is at [school]");
+
+        let error = Redactor::new(&[RedactionRule { pattern: "(".to_string(), replacement: String::new() }]);
+        assert!(error.unwrap_err().to_string().contains("Invalid redaction pattern"));
+        assert!(Redactor::new(&[]).unwrap().redact_messages(&mut messages).is_empty());
+    }
+}
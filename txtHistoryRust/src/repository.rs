@@ -1,90 +1,176 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use anyhow::Result;
 use async_trait::async_trait;
-use chrono::{Local, TimeZone};
+use chrono::{Local, TimeZone, Utc};
 use imessage_database::{
     tables::{
         chat::Chat,
         handle::Handle,
         message::Message as ImessageMessage,
     },
+    util::streamtyped,
     IMessageChat, IMessageDb,
 };
 
-use crate::models::{Contact, DateRange, Message, OutputFormat};
+use crate::async_writer::{
+    write_csv_file_async, write_json_file_async, write_parquet_file_async, write_txt_file_async,
+};
+use crate::chat_db::RetryPolicy;
+use crate::chat_db_schema::ChatDbSchema;
+use crate::discovery;
+use crate::export_options::ExportOptions;
+use crate::file_writer::{CsvOptions, WritePolicy};
+use crate::import_filter::{ImportFilter, SkippedMessages};
+use crate::import_warnings::{is_bad_timestamp, ImportWarnings, WarningReason};
+use crate::models::{
+    Contact, DateRange, Message, MessageKind, NewAttachment, OutputFormat, ServiceFilter, UpsertOutcome,
+};
+use crate::sources::{has_multiple_sources, merge_service_resends, resolve_conflicts, source_for_service, SourceFilter};
+use crate::style::ExportStyle;
+use crate::throttle::{Throttle, ThrottleConfig};
+
+/// Messages fetched for a contact, and those that couldn't be imported
+#[derive(Debug, Clone, Default)]
+pub struct FetchedMessages {
+    pub messages: Vec<Message>,
+    pub warnings: ImportWarnings,
+}
 
 #[async_trait]
 pub trait MessageRepository {
-    async fn fetch_messages(&self, contact: &Contact, date_range: &DateRange) -> Result<Vec<Message>>;
+    async fn fetch_messages(&self, contact: &Contact, date_range: &DateRange) -> Result<FetchedMessages>;
     async fn save_messages(&self, messages: &[Message], format: OutputFormat, path: &Path) -> Result<()>;
-    async fn export_conversation_by_person(
-        &self,
-        person_name: &str,
-        format: OutputFormat,
-        output_path: &Path,
-        date_range: &DateRange,
-        chunk_size: Option<usize>,
-        lines_per_chunk: Option<usize>
-    ) -> Result<Vec<PathBuf>>;
+    /// Export the conversation with a person as TXT and CSV files next to
+    /// `options.output_path()`, returning the files written. `options.format()` is ignored.
+    async fn export_conversation_by_person(&self, person_name: &str, options: &ExportOptions) -> Result<Vec<PathBuf>>;
 }
 
 pub struct IMessageDatabaseRepo {
     db: IMessageDb,
     database: Database,
+    service_filter: ServiceFilter,
+    source_filter: SourceFilter,
+    refresh: bool,
+    retry: RetryPolicy,
+    throttle: ThrottleConfig,
+    import_filter: ImportFilter,
+    schema: ChatDbSchema,
 }
 
 impl IMessageDatabaseRepo {
-    pub fn new(chat_db_path: PathBuf) -> Result<Self> {
+    pub fn new(chat_db_path: PathBuf, database_url: &str) -> Result<Self> {
+        Self::open(chat_db_path, database_url, RetryPolicy::default())
+    }
+
+    /// Open chat.db, retrying with exponential backoff while Messages.app holds a lock. Fails
+    /// with `TxtHistoryError::UnsupportedChatDb` when its schema is missing what imports read.
+    pub fn open(chat_db_path: PathBuf, database_url: &str, retry: RetryPolicy) -> Result<Self> {
+        // Check the layout first, so an unsupported release fails clearly rather than mid-import
+        let schema = ChatDbSchema::detect(&discovery::open_read_only(&chat_db_path, &retry)?)?;
+
         // Initialize iMessage database
-        let db = IMessageDb::new(chat_db_path)
-            .map_err(|e| anyhow::anyhow!("Failed to initialize iMessage database: {}", e))?;
+        let db = retry.run(|| {
+            IMessageDb::new(chat_db_path.clone())
+                .map_err(|e| anyhow::anyhow!("Failed to initialize iMessage database: {}", e))
+        })?;
 
         // Initialize our database
-        let database = Database::new("sqlite:data/messages.db")?;
+        let database = Database::new(database_url)?;
+
+        Ok(Self {
+            db,
+            database,
+            service_filter: ServiceFilter::All,
+            source_filter: SourceFilter::default(),
+            refresh: false,
+            retry,
+            throttle: ThrottleConfig::default(),
+            import_filter: ImportFilter::default(),
+            schema,
+        })
+    }
 
-        Ok(Self { db, database })
+    /// The detected chat.db layout and what it can hold
+    pub fn schema(&self) -> &ChatDbSchema {
+        &self.schema
     }
 
-    // Helper method to find a handle by phone or email
-    async fn find_handle(&self, contact: &Contact) -> Result<Option<Handle>> {
-        // Try to find by phone first
-        if let Some(phone) = &contact.phone {
-            let handle = self
-                .db
-                .get_handle_by_id(phone)
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to get handle by phone: {}", e))?;
+    /// Only export messages from the given sources, preferring them in the given order
+    pub fn with_source_filter(mut self, source_filter: SourceFilter) -> Self {
+        self.source_filter = source_filter;
+        self
+    }
 
-            if handle.is_some() {
-                return Ok(handle);
-            }
-        }
+    /// Update text, attachments and service of messages that were already imported
+    pub fn with_refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
 
-        // Then try by email
-        if let Some(email) = &contact.email {
-            let handle = self
-                .db
-                .get_handle_by_id(email)
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to get handle by email: {}", e))?;
+    /// Limit how fast fetched messages are saved to the local database
+    pub fn with_throttle(mut self, throttle: ThrottleConfig) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Leave out messages the filter skips, e.g. emoji-only replies
+    pub fn with_import_filter(mut self, import_filter: ImportFilter) -> Self {
+        self.import_filter = import_filter;
+        self
+    }
 
-            return Ok(handle);
+    /// Only fetch and export messages sent over the given service
+    pub fn with_service_filter(mut self, service_filter: ServiceFilter) -> Self {
+        self.service_filter = service_filter;
+        self
+    }
+
+    // Helper method to find the handles for a contact's phone and email
+    async fn find_handles(&self, contact: &Contact) -> Result<Vec<Handle>> {
+        let mut handles = Vec::new();
+
+        for identifier in [&contact.phone, &contact.email].into_iter().flatten() {
+            let handle = self
+                .retry
+                .run_async(|| async {
+                    self.db
+                        .get_handle_by_id(identifier)
+                        .await
+                        .map_err(|e| anyhow::anyhow!("Failed to get handle {}: {}", identifier, e))
+                })
+                .await?;
+
+            handles.extend(handle);
         }
 
-        // No handle found
-        Ok(None)
+        Ok(handles)
     }
 
-    // Helper method to find a chat by handle
-    async fn find_chat_by_handle(&self, handle: &Handle) -> Result<Option<Chat>> {
-        let chats = self
-            .db
-            .get_chats_by_handle_id(handle.rowid)
+    // Helper method to find every chat with a handle. The same person over SMS and iMessage
+    // shows up as separate chats.
+    async fn find_chats_by_handle(&self, handle: &Handle) -> Result<Vec<Chat>> {
+        self.retry
+            .run_async(|| async {
+                self.db
+                    .get_chats_by_handle_id(handle.rowid)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to get chats by handle: {}", e))
+            })
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to get chats by handle: {}", e))?;
+    }
+
+    // Get the text of a message, decoding the typedstream attributedBody when `text` is NULL.
+    // Newer macOS releases often only populate attributedBody.
+    fn message_text(msg: &ImessageMessage) -> Option<String> {
+        if let Some(text) = msg.text.as_ref().filter(|t| !t.trim().is_empty()) {
+            return Some(text.clone());
+        }
 
-        // Just return the first chat for now
-        Ok(chats.into_iter().next())
+        let body = msg.attributed_body.clone()?;
+        streamtyped::parse(body)
+            .ok()
+            .filter(|text| !text.trim().is_empty())
     }
 
     // Save messages to database
@@ -98,32 +184,22 @@ impl IMessageDatabaseRepo {
             primary_identifier: None, // Will be auto-set based on phone/email
         })?;
 
-        // Ensure "me" contact exists
-        let me_contact = self.database.add_or_update_contact(crate::models::NewContact {
-            name: "Jess".to_string(),
-            phone: None,
-            email: None,
-            is_me: true,
-            primary_identifier: Some("Jess".to_string()),
-        })?;
-
         // Save messages
         for message in messages {
             let new_message = NewMessage {
                 imessage_id: format!("generated_{}", message.timestamp.timestamp()),
                 text: Some(message.content.clone()),
                 sender: message.sender.clone(),
-                is_from_me: message.sender == "Jess",
+                is_from_me: message.is_from_me,
                 date_created: message.timestamp.naive_local(),
                 handle_id: None,
                 service: Some("iMessage".to_string()),
                 thread_id: None,
                 has_attachments: false,
-                contact_id: Some(if message.sender == "Jess" {
-                    db_contact.id // Link to the recipient (the other person)
-                } else {
-                    me_contact.id // Link to me as the recipient
-                }),
+                reply_to: None,
+                // Both sides of the conversation link to the other person
+                contact_id: Some(db_contact.id),
+                message_kind: MessageKind::Text,
             };
 
             self.database.add_message(new_message)?;
@@ -135,253 +211,302 @@ impl IMessageDatabaseRepo {
 
 #[async_trait]
 impl MessageRepository for IMessageDatabaseRepo {
-    async fn fetch_messages(&self, contact: &Contact, date_range: &DateRange) -> Result<Vec<Message>> {
-        // Find handle for the contact
-        let handle = match self.find_handle(contact).await? {
-            Some(h) => h,
-            None => return Err(anyhow::anyhow!("No handle found for contact: {}", contact.name)),
-        };
+    async fn fetch_messages(&self, contact: &Contact, date_range: &DateRange) -> Result<FetchedMessages> {
+        // Find handles for the contact
+        let handles = self.find_handles(contact).await?;
+        if handles.is_empty() {
+            return Err(anyhow::anyhow!("No handle found for contact: {}", contact.name));
+        }
 
-        // Find chat for the handle
-        let chat = match self.find_chat_by_handle(&handle).await? {
-            Some(c) => c,
-            None => return Err(anyhow::anyhow!("No chat found for contact: {}", contact.name)),
-        };
+        // Find every chat with the contact, across services, each with the handle it was found by
+        let mut chats: Vec<(Chat, String)> = Vec::new();
+        for handle in &handles {
+            for chat in self.find_chats_by_handle(handle).await? {
+                if !chats.iter().any(|(c, _)| c.chat_identifier == chat.chat_identifier) {
+                    chats.push((chat, handle.id.clone()));
+                }
+            }
+        }
+        if chats.is_empty() {
+            return Err(anyhow::anyhow!("No chat found for contact: {}", contact.name));
+        }
 
-        // Build query
-        let mut query = QueryBuilder::new();
+        // Ensure the contact exists so imported messages can link to it
+        let db_contact = self.database.add_or_update_contact(crate::models::NewContact {
+            name: contact.name.clone(),
+            phone: contact.phone.clone(),
+            email: contact.email.clone(),
+            is_me: false,
+            primary_identifier: None,
+        })?;
 
-        // Add chat filter
-        query.add_filter(Filter {
-            field: "message.cache_roomnames".to_string(),
-            operator: Operator::Equal,
-            value: FilterType::Text(chat.chat_identifier.clone()),
-        });
+        // Convert to our Message format
+        let my_name = self.database.my_name()?;
+        let mut messages = Vec::new();
+        let mut seen_guids = HashSet::new();
+        let mut throttle = Throttle::new(self.throttle);
+        let mut skipped = SkippedMessages::default();
+        let mut warnings = ImportWarnings::default();
+        let now = Utc::now().naive_utc();
 
-        // Add date filters if provided
-        if let Some(start) = &date_range.start {
-            query.add_filter(Filter {
-                field: "message.date".to_string(),
-                operator: Operator::GreaterThanOrEqual,
-                value: FilterType::Date(start.naive_utc()),
-            });
-        }
+        for (chat, handle_id) in &chats {
+            // Build query
+            let mut query = QueryBuilder::new();
 
-        if let Some(end) = &date_range.end {
+            // Add chat filter
             query.add_filter(Filter {
-                field: "message.date".to_string(),
-                operator: Operator::LessThanOrEqual,
-                value: FilterType::Date(end.naive_utc()),
+                field: "message.cache_roomnames".to_string(),
+                operator: Operator::Equal,
+                value: FilterType::Text(chat.chat_identifier.clone()),
             });
-        }
 
-        // Execute query
-        let message_items = self
-            .db
-            .get_messages_by_query(query)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to get messages: {}", e))?;
+            // Add date filters if provided
+            if let Some(start) = &date_range.start {
+                query.add_filter(Filter {
+                    field: "message.date".to_string(),
+                    operator: Operator::GreaterThanOrEqual,
+                    value: FilterType::Date(start.naive_utc()),
+                });
+            }
 
-        // Convert to our Message format
-        let mut messages = Vec::new();
+            if let Some(end) = &date_range.end {
+                query.add_filter(Filter {
+                    field: "message.date".to_string(),
+                    operator: Operator::LessThanOrEqual,
+                    value: FilterType::Date(end.naive_utc()),
+                });
+            }
 
-        for item in message_items {
-            if let MessageItem::Message(msg) = item {
-                // Skip messages without text
-                if let Some(text) = msg.text {
-                    // Determine sender name
-                    let sender = if msg.is_from_me {
-                        "Jess".to_string()
-                    } else {
-                        contact.name.clone()
-                    };
-
-                    // Convert date
-                    let timestamp = Local.from_utc_datetime(&msg.date);
-
-                    // Create message
-                    let message = Message {
-                        sender,
-                        timestamp,
-                        content: text,
-                    };
-
-                    messages.push(message);
-
-                    // Save to database
-                    let new_message = NewMessage {
-                        imessage_id: msg.guid,
-                        text: msg.text,
-                        sender: if msg.is_from_me {
-                            "Jess".to_string()
+            // Execute query
+            let message_items = self
+                .retry
+                .run_async(|| async {
+                    self.db
+                        .get_messages_by_query(query.clone())
+                        .await
+                        .map_err(|e| anyhow::anyhow!("Failed to get messages: {}", e))
+                })
+                .await?;
+
+            for item in message_items {
+                if let MessageItem::Message(msg) = item {
+                    if !self.service_filter.matches(msg.service.as_deref()) {
+                        continue;
+                    }
+
+                    // Chats can overlap, so each message is only taken once
+                    if !seen_guids.insert(msg.guid.clone()) {
+                        continue;
+                    }
+
+                    // An unset or future date can't be placed in the conversation
+                    if is_bad_timestamp(msg.date, now) {
+                        warnings.add(WarningReason::BadTimestamp, &msg.guid);
+                        continue;
+                    }
+
+                    // Classify app, payment, location and system messages so they aren't dropped
+                    let attachment_names: Vec<&str> = msg
+                        .attachments
+                        .iter()
+                        .filter_map(|attachment| attachment.filename.as_deref())
+                        .collect();
+                    let kind = MessageKind::detect(
+                        msg.item_type,
+                        msg.associated_message_type.unwrap_or(0),
+                        msg.balloon_bundle_id.as_deref(),
+                        &attachment_names,
+                    );
+
+                    // Skip plain messages without text, recovering it from attributedBody when needed
+                    let text = Self::message_text(&msg);
+                    let has_attachments = !msg.attachments.is_empty();
+                    if let Some(reason) = self.import_filter.skip_reason(text.as_deref(), kind, has_attachments) {
+                        skipped.add(reason);
+                        continue;
+                    }
+                    if text.is_some() || kind != MessageKind::Text {
+                        // Determine sender name
+                        let sender = if msg.is_from_me {
+                            my_name.clone()
                         } else {
                             contact.name.clone()
-                        },
-                        is_from_me: msg.is_from_me,
-                        date_created: msg.date,
-                        handle_id: Some(handle.id.clone()),
-                        service: msg.service,
-                        thread_id: Some(chat.chat_identifier.clone()),
-                        has_attachments: !msg.attachments.is_empty(),
-                        contact_id: if msg.is_from_me {
-                            Some(me_contact.id)
+                        };
+
+                        // Convert date
+                        let timestamp = Local.from_utc_datetime(&msg.date);
+
+                        // Create message
+                        let message = Message {
+                            sender,
+                            timestamp,
+                            content: kind.render(text.as_deref()),
+                            source: Some(source_for_service(msg.service.as_deref()).to_string()),
+                            imessage_id: Some(msg.guid.clone()),
+                            service: msg.service.clone(),
+                            is_from_me: msg.is_from_me,
+                        };
+
+                        messages.push(message);
+
+                        // Save to database
+                        let new_message = NewMessage {
+                            imessage_id: msg.guid.clone(),
+                            text,
+                            sender: if msg.is_from_me {
+                                my_name.clone()
+                            } else {
+                                contact.name.clone()
+                            },
+                            is_from_me: msg.is_from_me,
+                            date_created: msg.date,
+                            handle_id: Some(handle_id.clone()),
+                            service: msg.service.clone(),
+                            thread_id: Some(chat.chat_identifier.clone()),
+                            has_attachments,
+                            reply_to: None,
+                            // Both sides of the conversation link to the other person
+                            contact_id: Some(db_contact.id),
+                            message_kind: kind,
+                        };
+
+                        // Add to database, updating the stored copy when refreshing
+                        let saved = if self.refresh {
+                            match self.database.upsert_message(new_message)? {
+                                UpsertOutcome::Updated(saved, changes) => {
+                                    let fields: Vec<&str> = changes.iter().map(|c| c.field).collect();
+                                    println!("Updated {}: {}", saved.imessage_id, fields.join(", "));
+                                    saved
+                                }
+                                outcome => outcome.message().clone(),
+                            }
                         } else {
-                            Some(db_contact.id)
-                        },
-                    };
-
-                    // Add to database
-                    self.database.add_message(new_message)?;
+                            self.database.add_message(new_message)?
+                        };
+
+                        // Record attachments so they can be transcribed later
+                        for attachment in &msg.attachments {
+                            self.database.add_attachment(NewAttachment {
+                                message_id: saved.id,
+                                filename: attachment.filename.clone(),
+                                mime_type: attachment.mime_type.clone(),
+                                size_bytes: Some(attachment.total_bytes),
+                                created_at: msg.date,
+                            })?;
+                        }
+
+                        if let Some(delay) = throttle.delay(1) {
+                            tokio::time::sleep(delay).await;
+                        }
+                    } else if msg.attributed_body.is_some() {
+                        warnings.add(WarningReason::ExtractionFailed, &msg.guid);
+                    } else {
+                        warnings.add(WarningReason::MissingText, &msg.guid);
+                    }
                 }
             }
         }
 
-        // Sort by date
+        if skipped.total() > 0 {
+            println!("{}", skipped.summary());
+        }
+
+        // Sort by date, then drop messages resent over another service
         messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
 
-        Ok(messages)
+        Ok(FetchedMessages { messages: merge_service_resends(messages), warnings })
     }
 
     async fn save_messages(&self, messages: &[Message], format: OutputFormat, path: &Path) -> Result<()> {
-        match format {
-            OutputFormat::Txt => {
-                use std::fs::File;
-                use std::io::{BufWriter, Write};
-
-                let file = File::create(path)?;
-                let mut writer = BufWriter::new(file);
-
-                for message in messages {
-                    writeln!(
-                        writer,
-                        "{}, {}, {}\n",
-                        message.sender,
-                        message.timestamp.format("%b %d, %Y %r"),
-                        message.content
-                    )?;
-                }
-            }
-            OutputFormat::Csv => {
-                let file = std::fs::File::create(path)?;
-                let mut writer = csv::Writer::from_writer(file);
-
-                // Write header
-                writer.write_record(&["Sender", "Timestamp", "Content"])?;
-
-                // Write data
-                for message in messages {
-                    writer.write_record(&[
-                        &message.sender,
-                        &message.timestamp.format("%b %d, %Y %r").to_string(),
-                        &message.content,
-                    ])?;
-                }
-
-                writer.flush()?;
-            }
-        }
-
-        Ok(())
+        save_messages_to(messages, format, path).await
     }
 
-    // Export conversation with a person in the specified format
-    async fn export_conversation_by_person(
-        &self,
-        person_name: &str,
-        format: OutputFormat,
-        output_path: &Path,
-        date_range: &DateRange,
-        chunk_size: Option<usize>,
-        lines_per_chunk: Option<usize>
-    ) -> Result<Vec<PathBuf>> {
+    // Export conversation with a person as TXT and CSV
+    async fn export_conversation_by_person(&self, person_name: &str, options: &ExportOptions) -> Result<Vec<PathBuf>> {
         // Get all messages with this person
+        let date_range = options.date_range();
         let messages = self.database.get_conversation_with_person(
             person_name,
             date_range.start.map(|dt| dt.naive_local()),
             date_range.end.map(|dt| dt.naive_local()),
         )?;
+        let messages: Vec<_> = messages
+            .into_iter()
+            .filter(|m| self.service_filter.matches(m.service.as_deref()))
+            .filter(|m| self.source_filter.matches(m.service.as_deref()))
+            .collect();
 
         if messages.is_empty() {
             return Ok(Vec::new());
         }
 
-        // Convert database messages to the Message format
-        let messages: Vec<Message> = messages
-            .into_iter()
-            .map(|db_msg| Message {
-                content: db_msg.text.unwrap_or_default(),
-                sender: db_msg.sender,
-                timestamp: Local.from_utc_datetime(&db_msg.date_created),
-            })
-            .collect();
-
-        // Determine how to chunk the messages
-        let chunks = if let Some(lines) = lines_per_chunk {
-            // Chunk by number of messages
-            messages
-                .chunks(lines)
-                .map(|chunk| chunk.to_vec())
-                .collect::<Vec<_>>()
-        } else if let Some(size_mb) = chunk_size {
-            // Chunk by approximate size in MB
-            let bytes_per_mb = 1024 * 1024;
-            let target_bytes = size_mb as usize * bytes_per_mb;
-
-            let mut chunks = Vec::new();
-            let mut current_chunk = Vec::new();
-            let mut current_size = 0;
-
-            for msg in messages {
-                // Estimate size of this message (content + metadata)
-                let msg_size = msg.content.len() + msg.sender.len() + 50; // 50 bytes for timestamp and formatting
-
-                if current_size + msg_size > target_bytes && !current_chunk.is_empty() {
-                    chunks.push(current_chunk);
-                    current_chunk = Vec::new();
-                    current_size = 0;
-                }
-
-                current_chunk.push(msg);
-                current_size += msg_size;
-            }
+        // Convert database messages to the Message format, dropping exchanges duplicated across sources
+        let messages: Vec<Message> = messages.into_iter().map(|db_msg| db_msg.to_message()).collect();
+        let messages = resolve_conflicts(messages, &self.source_filter);
+        write_conversation(&messages, options).await
+    }
+}
 
-            if !current_chunk.is_empty() {
-                chunks.push(current_chunk);
-            }
+/// Write `messages` to a single file at `path` with the default export settings
+pub(crate) async fn save_messages_to(messages: &[Message], format: OutputFormat, path: &Path) -> Result<()> {
+    let path = path.to_string_lossy();
+
+    let policy = WritePolicy::Overwrite;
+    match format {
+        OutputFormat::Txt => {
+            let style = ExportStyle::default();
+            write_txt_file_async(messages, &path, has_multiple_sources(messages), &style, policy).await
+        },
+        OutputFormat::Csv => write_csv_file_async(messages, &path, &CsvOptions::default(), policy).await,
+        OutputFormat::Json => write_json_file_async(messages, &path, policy).await,
+        OutputFormat::Parquet => write_parquet_file_async(messages, &path, None, policy).await,
+    }
+    .map(|_| ())
+}
 
-            chunks
+/// Write a conversation as TXT and CSV chunk files next to `options.output_path()`, returning
+/// the files written
+pub(crate) async fn write_conversation(messages: &[Message], options: &ExportOptions) -> Result<Vec<PathBuf>> {
+    let mut messages = messages.to_vec();
+    options.style().redact(&mut messages)?;
+    let messages = messages.as_slice();
+    let label_sources = has_multiple_sources(messages);
+
+    // Chunks are measured as TXT, the larger of the two files
+    let chunks = options
+        .chunking()
+        .split(messages, OutputFormat::Txt, options.csv_options(), options.style())?;
+
+    // Create output files for each chunk
+    let mut output_files = Vec::new();
+
+    let output_path = options.output_path();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let chunk_number = i + 1;
+        let file_stem = output_path.file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("conversation");
+
+        let file_name = if chunks.len() > 1 {
+            format!("{}_chunk_{}", file_stem, chunk_number)
         } else {
-            // No chunking, just one file
-            vec![messages]
+            file_stem.to_string()
         };
 
-        // Create output files for each chunk
-        let mut output_files = Vec::new();
-
-        for (i, chunk) in chunks.iter().enumerate() {
-            let chunk_number = i + 1;
-            let file_stem = output_path.file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("conversation");
-
-            let file_name = if chunks.len() > 1 {
-                format!("{}_chunk_{}", file_stem, chunk_number)
-            } else {
-                file_stem.to_string()
-            };
-
-            // Create both TXT and CSV files
-            let txt_path = output_path.with_file_name(format!("{}.txt", file_name));
-            let csv_path = output_path.with_file_name(format!("{}.csv", file_name));
-
-            // Format and save the messages
-            self.save_messages(chunk, OutputFormat::Txt, &txt_path).await?;
-            self.save_messages(chunk, OutputFormat::Csv, &csv_path).await?;
+        // Create both TXT and CSV files
+        let txt_path = output_path.with_file_name(format!("{}.txt", file_name));
+        let csv_path = output_path.with_file_name(format!("{}.csv", file_name));
 
-            output_files.push(txt_path);
-            output_files.push(csv_path);
-        }
+        // Format and save the messages, writing both files at once
+        let (txt_file, csv_file) = (txt_path.to_string_lossy(), csv_path.to_string_lossy());
+        tokio::try_join!(
+            write_txt_file_async(chunk, &txt_file, label_sources, options.style(), options.policy()),
+            write_csv_file_async(chunk, &csv_file, options.csv_options(), options.policy()),
+        )?;
 
-        Ok(output_files)
+        output_files.push(txt_path);
+        output_files.push(csv_path);
     }
+
+    Ok(output_files)
 }
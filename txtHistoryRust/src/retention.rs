@@ -0,0 +1,33 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
+
+use crate::config::RetentionConfig;
+use crate::db::Database;
+use crate::models::PurgeCounts;
+
+/// Start of the retention window: messages sent before this (UTC) are older than `max_age_days`
+pub fn retention_cutoff(now: DateTime<Local>, max_age_days: u32) -> NaiveDateTime {
+    (now - Duration::days(i64::from(max_age_days))).naive_utc()
+}
+
+/// Purge every message older than its contact's limit, or the default limit for contacts
+/// without one. Contacts with their own limit are left out of the default purge, so a longer
+/// per-contact limit keeps their messages longer.
+pub fn enforce_retention(db: &Database, config: &RetentionConfig, now: DateTime<Local>) -> Result<PurgeCounts> {
+    let mut purged = PurgeCounts::default();
+    let mut own_limits = Vec::new();
+
+    for (name, max_age_days) in &config.contacts {
+        let contact = db
+            .get_contact(name)?
+            .ok_or_else(|| anyhow::anyhow!("Retention limit set for unknown contact: {}", name))?;
+        purged += db.purge_messages(retention_cutoff(now, *max_age_days), Some(contact.id), &[], false)?;
+        own_limits.push(contact.id);
+    }
+
+    if let Some(max_age_days) = config.max_age_days {
+        purged += db.purge_messages(retention_cutoff(now, max_age_days), None, &own_limits, false)?;
+    }
+
+    Ok(purged)
+}
@@ -0,0 +1,288 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, Timelike};
+
+use crate::config::ExportSchedule;
+use crate::db::Database;
+use crate::file_writer::{write_messages_to_files, Chunking, CsvOptions, WritePolicy};
+use crate::importers::dates::local_to_utc;
+use crate::style::ExportStyle;
+use crate::models::OutputFormat;
+
+/// A parsed cron-like expression: minute hour day-of-month month day-of-week.
+///
+/// Each field accepts `*`, `*/n`, a number, a range `a-b`, or a comma separated list of those.
+/// Day-of-week 0 and 7 are both Sunday. Unlike classic cron, all five fields must match.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days_of_month: Vec<u32>,
+    months: Vec<u32>,
+    days_of_week: Vec<u32>,
+}
+
+impl CronSchedule {
+    /// Parse a five-field cron expression
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(anyhow::anyhow!(
+                "Invalid schedule '{}': expected 5 fields (minute hour day month weekday)",
+                expression
+            ));
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week: days_of_week(fields[4])?,
+        })
+    }
+
+    /// Whether the schedule fires during the minute containing `time`
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        self.minutes.contains(&time.minute())
+            && self.hours.contains(&time.hour())
+            && self.days_of_month.contains(&time.day())
+            && self.months.contains(&time.month())
+            && self.days_of_week.contains(&time.weekday().num_days_from_sunday())
+    }
+
+    /// Whether the schedule fires during any minute after the one containing `last_check`, up to
+    /// and including the one containing `now`. Checking the whole span means a check that runs
+    /// late, or after the machine slept, still catches a minute it skipped over.
+    pub fn fires_between(&self, last_check: &DateTime<Local>, now: &DateTime<Local>) -> bool {
+        let start_of_minute =
+            |time: &DateTime<Local>| time.with_second(0).and_then(|time| time.with_nanosecond(0)).unwrap_or(*time);
+        let mut minute = start_of_minute(last_check) + Duration::minutes(1);
+        let last = start_of_minute(now);
+
+        while minute <= last {
+            if self.matches(&minute) {
+                return true;
+            }
+            minute += Duration::minutes(1);
+        }
+        false
+    }
+}
+
+/// Parse a single cron field into the list of values it allows
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>> {
+    let mut values = Vec::new();
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().context("Invalid cron step")?),
+            None => (part, 1),
+        };
+
+        if step == 0 {
+            return Err(anyhow::anyhow!("Invalid cron step in '{}'", field));
+        }
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse::<u32>()?, end.parse::<u32>()?)
+        } else {
+            let value = range.parse::<u32>().with_context(|| format!("Invalid cron field '{}'", field))?;
+            (value, value)
+        };
+
+        if start < min || end > max || start > end {
+            return Err(anyhow::anyhow!("Cron field '{}' out of range {}-{}", field, min, max));
+        }
+
+        values.extend((start..=end).step_by(step as usize));
+    }
+
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+/// Parse the day-of-week field, where 7 is Sunday as well as 0
+fn days_of_week(field: &str) -> Result<Vec<u32>> {
+    let mut days: Vec<u32> = parse_field(field, 0, 7)?.into_iter().map(|day| day % 7).collect();
+    days.sort_unstable();
+    days.dedup();
+    Ok(days)
+}
+
+/// Start (inclusive) and end (exclusive) of the calendar month before `today`
+pub fn previous_month_range(today: NaiveDate) -> (NaiveDateTime, NaiveDateTime) {
+    let first_of_this_month = today.with_day(1).unwrap_or(today);
+    let first_of_previous_month = (first_of_this_month - Duration::days(1))
+        .with_day(1)
+        .unwrap_or(first_of_this_month);
+
+    (
+        first_of_previous_month.and_hms_opt(0, 0, 0).unwrap_or_default(),
+        first_of_this_month.and_hms_opt(0, 0, 0).unwrap_or_default(),
+    )
+}
+
+/// Export last month's messages for every scheduled contact, then apply retention.
+///
+/// The export is written to a `.YYYY-MM.partial` directory that's renamed once every contact is
+/// written, so a run that fails partway is retried from scratch next time. Returns the directory
+/// written to, or `None` if this month's export already exists.
+pub fn run_scheduled_export(
+    db: &Database,
+    schedule: &ExportSchedule,
+    style: &ExportStyle,
+    parallel_writes: usize,
+    now: DateTime<Local>,
+) -> Result<Option<PathBuf>> {
+    let (start, end) = previous_month_range(now.date_naive());
+    // The month's bounds are local midnights, while messages are stored in UTC
+    let (utc_start, utc_end) = (local_to_utc(&start).unwrap_or(start), local_to_utc(&end).unwrap_or(end));
+    let export_dir = Path::new(&schedule.target_dir).join(start.format("%Y-%m").to_string());
+
+    if export_dir.exists() {
+        return Ok(None);
+    }
+    let partial_dir = Path::new(&schedule.target_dir).join(format!(".{}.partial", start.format("%Y-%m")));
+    if partial_dir.exists() {
+        fs::remove_dir_all(&partial_dir)
+            .with_context(|| format!("Failed to remove unfinished export {}", partial_dir.display()))?;
+    }
+
+    let format = match schedule.format.to_lowercase().as_str() {
+        "csv" => OutputFormat::Csv,
+        "json" => OutputFormat::Json,
+        "parquet" => OutputFormat::Parquet,
+        _ => OutputFormat::Txt,
+    };
+
+    for contact in &schedule.contacts {
+        let mut db_messages = db.get_conversation_with_person(contact, Some(utc_start), None)?;
+        db_messages.retain(|m| m.date_created < utc_end);
+        let mut messages = db.to_messages_with_transcripts(&db_messages)?;
+        style.redact(&mut messages)?;
+
+        let contact_dir = partial_dir.join(contact);
+        fs::create_dir_all(&contact_dir)?;
+        write_messages_to_files(
+            &messages,
+            format,
+            &CsvOptions::default().with_ids(style.include_ids),
+            style,
+            Chunking::from_options(None, schedule.lines_per_chunk, schedule.tokens_per_chunk),
+            &contact_dir.to_string_lossy(),
+            WritePolicy::Overwrite,
+            parallel_writes,
+        )?;
+    }
+
+    fs::create_dir_all(&partial_dir)?;
+    fs::rename(&partial_dir, &export_dir)
+        .with_context(|| format!("Failed to move the export to {}", export_dir.display()))?;
+
+    // The export just written is always kept
+    if let Some(keep) = schedule.retention {
+        prune_exports(Path::new(&schedule.target_dir), keep.max(1))?;
+    }
+
+    Ok(Some(export_dir))
+}
+
+/// Remove the oldest `YYYY-MM` export directories so that at most `keep` remain
+pub fn prune_exports(target_dir: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut export_dirs: Vec<PathBuf> = fs::read_dir(target_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_dir()
+                && path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .map_or(false, |n| NaiveDate::parse_from_str(&format!("{}-01", n), "%Y-%m-%d").is_ok())
+        })
+        .collect();
+
+    // Directory names sort chronologically
+    export_dirs.sort();
+
+    let excess = export_dirs.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = export_dirs.into_iter().take(excess).collect();
+
+    for dir in &removed {
+        fs::remove_dir_all(dir).with_context(|| format!("Failed to remove {}", dir.display()))?;
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_cron() {
+        let schedule = CronSchedule::parse("0 3 1 * *").unwrap();
+        let due = Local.with_ymd_and_hms(2025, 4, 1, 3, 0, 0).unwrap();
+        let not_due = Local.with_ymd_and_hms(2025, 4, 2, 3, 0, 0).unwrap();
+        assert!(schedule.matches(&due));
+        assert!(!schedule.matches(&not_due));
+
+        let every_quarter_hour = CronSchedule::parse("*/15 * * * 1-5").unwrap();
+        assert_eq!(every_quarter_hour.minutes, vec![0, 15, 30, 45]);
+        assert_eq!(every_quarter_hour.days_of_week, vec![1, 2, 3, 4, 5]);
+
+        assert!(CronSchedule::parse("0 3 1 *").is_err());
+        assert!(CronSchedule::parse("61 * * * *").is_err());
+
+        // 7 is Sunday too
+        let sundays = CronSchedule::parse("0 9 * * 7").unwrap();
+        assert_eq!(sundays.days_of_week, vec![0]);
+        assert_eq!(CronSchedule::parse("0 9 * * 5-7").unwrap().days_of_week, vec![0, 5, 6]);
+        assert!(CronSchedule::parse("0 9 * * 8").is_err());
+    }
+
+    #[test]
+    fn test_fires_between() {
+        let schedule = CronSchedule::parse("0 3 1 * *").unwrap();
+        let at = |day, hour, minute, second| Local.with_ymd_and_hms(2025, 4, day, hour, minute, second).unwrap();
+
+        // A check that ran late, skipping over 03:00, still fires
+        assert!(schedule.fires_between(&at(1, 2, 59, 30), &at(1, 3, 1, 10)));
+        assert!(schedule.fires_between(&at(1, 2, 59, 59), &at(1, 3, 0, 0)));
+        // The minute of the last check already had its chance
+        assert!(!schedule.fires_between(&at(1, 3, 0, 5), &at(1, 3, 0, 50)));
+        assert!(!schedule.fires_between(&at(1, 3, 0, 50), &at(1, 3, 2, 0)));
+        // A machine asleep over the whole day catches up when it wakes
+        let previous_month = Local.with_ymd_and_hms(2025, 3, 31, 23, 0, 0).unwrap();
+        assert!(schedule.fires_between(&previous_month, &at(2, 8, 0, 0)));
+    }
+
+    #[test]
+    fn test_prune_exports() {
+        let target_dir = std::env::temp_dir().join(format!("txt_history_prune_{}", std::process::id()));
+        for name in ["2025-01", "2025-02", "2025-03", ".2025-04.partial"] {
+            fs::create_dir_all(target_dir.join(name)).unwrap();
+        }
+
+        let removed = prune_exports(&target_dir, 2).unwrap();
+        assert_eq!(removed, vec![target_dir.join("2025-01")]);
+        assert!(target_dir.join("2025-03").exists());
+        // Unfinished exports aren't month directories
+        assert!(target_dir.join(".2025-04.partial").exists());
+
+        fs::remove_dir_all(&target_dir).unwrap();
+    }
+
+    #[test]
+    fn test_previous_month_range() {
+        let (start, end) = previous_month_range(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap());
+        assert_eq!(start.date(), NaiveDate::from_ymd_opt(2024, 12, 1).unwrap());
+        assert_eq!(end.date(), NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+    }
+}
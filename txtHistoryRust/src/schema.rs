@@ -9,6 +9,11 @@ pub mod contacts {
     pub const PHONE: &str = "phone";
     pub const EMAIL: &str = "email";
     pub const IS_ME: &str = "is_me";
+    pub const PRIMARY_IDENTIFIER: &str = "primary_identifier";
+    pub const RELATIONSHIP: &str = "relationship";
+    pub const TIMEZONE: &str = "timezone";
+    pub const BIRTHDAY: &str = "birthday";
+    pub const NOTES: &str = "notes";
 }
 
 pub mod messages {
@@ -24,6 +29,11 @@ pub mod messages {
     pub const SERVICE: &str = "service";
     pub const THREAD_ID: &str = "thread_id";
     pub const HAS_ATTACHMENTS: &str = "has_attachments";
+    pub const CONTACT_ID: &str = "contact_id";
+    pub const REPLY_TO: &str = "reply_to";
+    pub const MESSAGE_KIND: &str = "message_kind";
+    pub const HIDDEN: &str = "hidden";
+    pub const HIDDEN_REASON: &str = "hidden_reason";
 }
 
 pub mod attachments {
@@ -47,4 +57,82 @@ pub mod processed_messages {
     pub const SENTIMENT_SCORE: &str = "sentiment_score";
     pub const PROCESSED_AT: &str = "processed_at";
     pub const PROCESSING_VERSION: &str = "processing_version";
+    pub const FLAGS: &str = "flags";
+    pub const FLAG_SEVERITY: &str = "flag_severity";
+    pub const LANGUAGE: &str = "language";
+    pub const SENTENCE_SENTIMENTS: &str = "sentence_sentiments";
+}
+
+pub mod attachment_transcripts {
+    pub const TABLE: &str = "attachment_transcripts";
+    pub const ID: &str = "id";
+    pub const ATTACHMENT_ID: &str = "attachment_id";
+    pub const MESSAGE_ID: &str = "message_id";
+    pub const PROCESSOR: &str = "processor";
+    pub const TRANSCRIPT: &str = "transcript";
+    pub const CREATED_AT: &str = "created_at";
+}
+
+pub mod message_revisions {
+    pub const TABLE: &str = "message_revisions";
+    pub const ID: &str = "id";
+    pub const MESSAGE_ID: &str = "message_id";
+    pub const FIELD: &str = "field";
+    pub const OLD_VALUE: &str = "old_value";
+    pub const NEW_VALUE: &str = "new_value";
+    pub const CHANGED_AT: &str = "changed_at";
+}
+
+pub mod message_commitments {
+    pub const TABLE: &str = "message_commitments";
+    pub const ID: &str = "id";
+    pub const MESSAGE_ID: &str = "message_id";
+    pub const KIND: &str = "kind";
+    pub const SNIPPET: &str = "snippet";
+    pub const ANSWERED_BY: &str = "answered_by";
+    pub const DETECTED_AT: &str = "detected_at";
+}
+
+pub mod message_events {
+    pub const TABLE: &str = "message_events";
+    pub const ID: &str = "id";
+    pub const MESSAGE_ID: &str = "message_id";
+    pub const STARTS_ON: &str = "starts_on";
+    pub const STARTS_AT: &str = "starts_at";
+    pub const EXPRESSION: &str = "expression";
+    pub const SNIPPET: &str = "snippet";
+    pub const DETECTED_AT: &str = "detected_at";
+}
+
+pub mod export_runs {
+    pub const TABLE: &str = "export_runs";
+    pub const ID: &str = "id";
+    pub const QUERY_HASH: &str = "query_hash";
+    pub const OUTPUT_DIR: &str = "output_dir";
+    pub const TOTAL_CHUNKS: &str = "total_chunks";
+    pub const STARTED_AT: &str = "started_at";
+    pub const FINISHED_AT: &str = "finished_at";
+}
+
+pub mod export_chunks {
+    pub const TABLE: &str = "export_chunks";
+    pub const ID: &str = "id";
+    pub const EXPORT_ID: &str = "export_id";
+    pub const CHUNK_INDEX: &str = "chunk_index";
+    pub const PATH: &str = "path";
+    pub const SHA256: &str = "sha256";
+    pub const MESSAGES: &str = "messages";
+    pub const BYTES: &str = "bytes";
+    pub const WRITTEN_AT: &str = "written_at";
+}
+
+pub mod summaries {
+    pub const TABLE: &str = "summaries";
+    pub const ID: &str = "id";
+    pub const INPUT_HASH: &str = "input_hash";
+    pub const MODEL: &str = "model";
+    pub const UNIT: &str = "unit";
+    pub const UNIT_KEY: &str = "unit_key";
+    pub const SUMMARY: &str = "summary";
+    pub const CREATED_AT: &str = "created_at";
 }
@@ -0,0 +1,153 @@
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+
+use crate::models::Message;
+
+/// Markers around the matched text in `search` output, plain text so they survive CSV and
+/// spreadsheets
+pub const HIGHLIGHT_START: &str = "[[";
+pub const HIGHLIGHT_END: &str = "]]";
+
+/// Columns of a `search --export` CSV, in order
+const CSV_HEADER: [&str; 7] = ["result", "role", "message_id", "sent_at", "sender", "service", "text"];
+
+/// One message of a search result
+#[derive(Debug, Clone)]
+pub struct SearchRow {
+    /// Row ID in the local database, as taken by `context --message-id` and `hide`
+    pub message_id: i32,
+    pub message: Message,
+    /// Whether this is the message that matched rather than one around it
+    pub is_match: bool,
+}
+
+/// A message that matched, with the messages sent around it, oldest first
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub rows: Vec<SearchRow>,
+}
+
+/// Wraps every occurrence of the searched text in highlight markers. Like SQLite's LIKE, case
+/// is only ignored for ASCII letters, so it highlights exactly what the database matched.
+pub struct Highlighter {
+    regex: Regex,
+}
+
+impl Highlighter {
+    pub fn new(text: &str) -> Result<Self> {
+        let regex = RegexBuilder::new(&regex::escape(text)).case_insensitive(true).unicode(false).build()?;
+        Ok(Self { regex })
+    }
+
+    /// Whether the text still contains what was searched for, e.g. after redaction
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    pub fn highlight(&self, text: &str) -> String {
+        self.regex.replace_all(text, format!("{}$0{}", HIGHLIGHT_START, HIGHLIGHT_END)).into_owned()
+    }
+}
+
+/// Results as the terminal shows them: each result's messages, the match marked with ">",
+/// and "--" between results like `grep --context`
+pub fn to_text(results: &[SearchResult], highlighter: &Highlighter) -> String {
+    results
+        .iter()
+        .map(|result| {
+            result
+                .rows
+                .iter()
+                .map(|row| {
+                    format!(
+                        "{} [{}] {}, {}: {}\n",
+                        if row.is_match { ">" } else { " " },
+                        row.message_id,
+                        row.message.timestamp.format("%b %d, %Y %r"),
+                        row.message.sender,
+                        highlighter.highlight(&row.message.content)
+                    )
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("--\n")
+}
+
+/// One row per message, numbered by result, so a match and its context sort together. A
+/// message in the context of two matches is written with each.
+pub fn to_csv(results: &[SearchResult], highlighter: &Highlighter) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(CSV_HEADER)?;
+
+    for (i, result) in results.iter().enumerate() {
+        for row in &result.rows {
+            writer.write_record([
+                (i + 1).to_string(),
+                if row.is_match { "match" } else { "context" }.to_string(),
+                row.message_id.to_string(),
+                row.message.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                row.message.sender.clone(),
+                row.message.service.clone().unwrap_or_default(),
+                highlighter.highlight(&row.message.content),
+            ])?;
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn row(message_id: i32, minute: u32, content: &str, is_match: bool) -> SearchRow {
+        SearchRow {
+            message_id,
+            message: Message {
+                sender: "Phil".to_string(),
+                timestamp: Local.with_ymd_and_hms(2025, 1, 20, 14, minute, 0).unwrap(),
+                content: content.to_string(),
+                source: None,
+                imessage_id: None,
+                service: Some("iMessage".to_string()),
+                is_from_me: false,
+            },
+            is_match,
+        }
+    }
+
+    #[test]
+    fn test_search_output() {
+        let highlighter = Highlighter::new("pick up").unwrap();
+        assert_eq!(highlighter.highlight("Pick up Everly, then pick UP milk"), "[[Pick up]] Everly, then [[pick UP]] milk");
+        // The searched text is literal, not a pattern
+        assert_eq!(Highlighter::new("3.50").unwrap().highlight("$3.50 or 3550"), "$[[3.50]] or 3550");
+        // Only ASCII letters match in either case, as in SQLite
+        let accented = Highlighter::new("école").unwrap();
+        assert_eq!(accented.highlight("ÉCOLE or École or école"), "ÉCOLE or École or [[école]]");
+        assert!(accented.is_match("L'éCOLE"));
+        assert!(!accented.is_match("L'ÉCOLE"));
+
+        let results = vec![
+            SearchResult { rows: vec![row(1, 0, "Can you pick up Everly?", true), row(2, 1, "Sure, at 3", false)] },
+            SearchResult { rows: vec![row(9, 30, "I'll pick up dinner", true)] },
+        ];
+
+        let text = to_text(&results, &highlighter);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("> [1] Jan 20, 2025"));
+        assert!(lines[0].ends_with("Phil: Can you [[pick up]] Everly?"));
+        assert!(lines[1].starts_with("  [2] "));
+        assert_eq!(lines[2], "--");
+
+        let csv = to_csv(&results, &highlighter).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "result,role,message_id,sent_at,sender,service,text");
+        assert_eq!(lines[1], "1,match,1,2025-01-20 14:00:00,Phil,iMessage,Can you [[pick up]] Everly?");
+        assert_eq!(lines[2], "1,context,2,2025-01-20 14:01:00,Phil,iMessage,\"Sure, at 3\"");
+        assert_eq!(lines[3], "2,match,9,2025-01-20 14:30:00,Phil,iMessage,I'll [[pick up]] dinner");
+    }
+}
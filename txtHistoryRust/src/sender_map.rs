@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// A display name from another platform's export and the contact it belongs to, e.g. "Phil G 🏒"
+/// on WhatsApp or Telegram for the contact "Phil"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SenderMapping {
+    /// Regular expression matched against the display name, e.g. "^Phil G\\b"
+    pub pattern: String,
+
+    /// Name of the contact the matching names belong to
+    pub contact: String,
+
+    #[serde(default)]
+    pub case_insensitive: bool,
+}
+
+/// The configured sender mappings with their patterns compiled. The first matching pattern wins.
+#[derive(Debug, Default)]
+pub struct SenderMap {
+    mappings: Vec<(Regex, String)>,
+}
+
+impl SenderMap {
+    pub fn new(mappings: &[SenderMapping]) -> Result<Self> {
+        let mut compiled = Vec::new();
+
+        for mapping in mappings {
+            let contact = mapping.contact.trim();
+            if contact.is_empty() {
+                return Err(anyhow::anyhow!("Sender mapping \"{}\" has no contact", mapping.pattern));
+            }
+
+            let regex = RegexBuilder::new(&mapping.pattern)
+                .case_insensitive(mapping.case_insensitive)
+                .build()
+                .with_context(|| format!("Invalid sender pattern for {}", contact))?;
+            compiled.push((regex, contact.to_string()));
+        }
+
+        Ok(Self { mappings: compiled })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+
+    /// Every contact the mappings point at, so they can be checked before importing
+    pub fn contacts(&self) -> impl Iterator<Item = &str> {
+        self.mappings.iter().map(|(_, contact)| contact.as_str())
+    }
+
+    /// Contact a display name belongs to, or None when no pattern matches it
+    pub fn contact_for(&self, display_name: &str) -> Option<&str> {
+        self.mappings
+            .iter()
+            .find(|(regex, _)| regex.is_match(display_name.trim()))
+            .map(|(_, contact)| contact.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(pattern: &str, contact: &str) -> SenderMapping {
+        SenderMapping { pattern: pattern.to_string(), contact: contact.to_string(), case_insensitive: false }
+    }
+
+    #[test]
+    fn test_sender_map() {
+        let map = SenderMap::new(&[
+            mapping(r"^Phil G\b", "Phil"),
+            SenderMapping { case_insensitive: true, ..mapping(r"^mom$|^rhonda", "Rhonda") },
+            mapping(r"Phil", "Phil Someone Else"),
+        ])
+        .unwrap();
+
+        assert_eq!(map.contact_for("Phil G 🏒"), Some("Phil"));
+        assert_eq!(map.contact_for(" MOM "), Some("Rhonda"));
+        assert_eq!(map.contact_for("Rhonda (work)"), Some("Rhonda"));
+        // The first matching pattern wins
+        assert_eq!(map.contact_for("Phil"), Some("Phil Someone Else"));
+        assert_eq!(map.contact_for("Mommy"), None);
+        assert_eq!(map.contacts().collect::<Vec<_>>(), vec!["Phil", "Rhonda", "Phil Someone Else"]);
+
+        assert!(SenderMap::default().is_empty());
+        assert!(SenderMap::new(&[mapping("(", "Phil")]).is_err());
+        assert!(SenderMap::new(&[mapping("Phil", " ")]).is_err());
+    }
+}
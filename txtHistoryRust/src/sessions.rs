@@ -0,0 +1,95 @@
+use chrono::Duration;
+
+use crate::models::DbMessage;
+
+/// Default quiet time that ends a session, in minutes
+pub const DEFAULT_SESSION_GAP_MINUTES: i64 = 60;
+
+/// Split a conversation sorted by date into sessions: runs of messages where no two in a row
+/// are more than `gap` apart
+pub fn split_sessions(messages: &[DbMessage], gap: Duration) -> Vec<&[DbMessage]> {
+    let mut sessions = Vec::new();
+    let mut start = 0;
+
+    for i in 1..messages.len() {
+        if messages[i].date_created - messages[i - 1].date_created > gap {
+            sessions.push(&messages[start..i]);
+            start = i;
+        }
+    }
+
+    if start < messages.len() {
+        sessions.push(&messages[start..]);
+    }
+
+    sessions
+}
+
+/// Split a session into bursts: runs of messages from the same sender with nobody else in between
+pub fn split_bursts(session: &[DbMessage]) -> Vec<&[DbMessage]> {
+    let mut bursts = Vec::new();
+    let mut start = 0;
+
+    for i in 1..session.len() {
+        if session[i].sender != session[i - 1].sender {
+            bursts.push(&session[start..i]);
+            start = i;
+        }
+    }
+
+    if start < session.len() {
+        bursts.push(&session[start..]);
+    }
+
+    bursts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MessageKind;
+    use chrono::NaiveDateTime;
+
+    fn message(sender: &str, at: &str) -> DbMessage {
+        let date_created = NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M").unwrap();
+        DbMessage {
+            id: 0,
+            imessage_id: String::new(),
+            text: Some("hi".to_string()),
+            sender: sender.to_string(),
+            is_from_me: sender == "Jess",
+            date_created,
+            date_imported: date_created,
+            handle_id: None,
+            service: None,
+            thread_id: None,
+            has_attachments: false,
+            reply_to: None,
+            contact_id: Some(1),
+            message_kind: MessageKind::Text,
+            hidden: false,
+            hidden_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_sessions_and_bursts() {
+        let messages = vec![
+            message("Phil", "2025-01-20 09:00"),
+            message("Phil", "2025-01-20 09:01"),
+            message("Jess", "2025-01-20 09:30"),
+            message("Jess", "2025-01-20 10:30"),
+            message("Phil", "2025-01-20 11:31"),
+        ];
+
+        let sessions = split_sessions(&messages, Duration::minutes(DEFAULT_SESSION_GAP_MINUTES));
+        // Exactly an hour apart stays in the same session
+        assert_eq!(sessions.iter().map(|s| s.len()).collect::<Vec<_>>(), vec![4, 1]);
+
+        let bursts = split_bursts(sessions[0]);
+        let bursts: Vec<_> = bursts.iter().map(|b| (b[0].sender.as_str(), b.len())).collect();
+        assert_eq!(bursts, vec![("Phil", 2), ("Jess", 2)]);
+
+        assert!(split_sessions(&[], Duration::minutes(60)).is_empty());
+    }
+}
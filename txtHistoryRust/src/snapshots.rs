@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDateTime};
+
+use crate::config::SnapshotConfig;
+use crate::db::Database;
+
+/// Snapshots are named `messages-YYYYMMDD-HHMMSS.db` after the local time they were taken
+const FILE_PREFIX: &str = "messages-";
+const FILE_SUFFIX: &str = ".db";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// A copy of the local database taken with SQLite's backup API
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    /// Local time
+    pub taken_at: NaiveDateTime,
+    pub bytes: u64,
+}
+
+impl Snapshot {
+    /// File name, as `snapshots list` shows it and `snapshots restore` takes it
+    pub fn name(&self) -> String {
+        self.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+    }
+}
+
+/// Directory of database snapshots, e.g. `data/snapshots`
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Snapshots in the directory, newest first. Other files are ignored.
+    pub fn list(&self) -> Result<Vec<Snapshot>> {
+        if !self.dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&self.dir).with_context(|| format!("Failed to read {}", self.dir.display()))? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(taken_at) = parse_file_name(&name) else { continue };
+            snapshots.push(Snapshot { path: entry.path(), taken_at, bytes: entry.metadata()?.len() });
+        }
+        snapshots.sort_by(|a, b| b.taken_at.cmp(&a.taken_at));
+
+        Ok(snapshots)
+    }
+
+    /// The snapshot with the given file name, or the newest one for "latest"
+    pub fn find(&self, name: &str) -> Result<Snapshot> {
+        let snapshots = self.list()?;
+        let found = match name {
+            "latest" => snapshots.into_iter().next(),
+            name => snapshots.into_iter().find(|snapshot| snapshot.name() == name),
+        };
+
+        found.ok_or_else(|| {
+            anyhow::anyhow!("Snapshot not found: {}. Run `snapshots list` to see what's in {}", name, self.dir.display())
+        })
+    }
+
+    /// Whether no snapshot has been taken yet on the day of `now`
+    pub fn is_due(&self, now: NaiveDateTime) -> Result<bool> {
+        Ok(self.list()?.first().map_or(true, |newest| newest.taken_at.date() != now.date()))
+    }
+
+    /// Copy the database into a new snapshot. The copy is written under a temporary name and
+    /// renamed when it's complete, so an interrupted backup never shows up as a snapshot.
+    pub fn take(&self, db: &Database, now: NaiveDateTime) -> Result<Snapshot> {
+        fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        let path = self.dir.join(format!("{}{}{}", FILE_PREFIX, now.format(TIMESTAMP_FORMAT), FILE_SUFFIX));
+        let partial = path.with_extension("db.partial");
+        db.backup_to(&partial)?;
+        fs::rename(&partial, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(Snapshot { bytes: fs::metadata(&path)?.len(), path, taken_at: now })
+    }
+
+    /// Delete the snapshots the policy no longer keeps, returning them
+    pub fn rotate(&self, config: &SnapshotConfig) -> Result<Vec<Snapshot>> {
+        let snapshots = self.list()?;
+        let expired: Vec<Snapshot> = expired(&snapshots, config.daily, config.weekly).into_iter().cloned().collect();
+        for snapshot in &expired {
+            fs::remove_file(&snapshot.path)
+                .with_context(|| format!("Failed to delete snapshot {}", snapshot.path.display()))?;
+        }
+
+        Ok(expired)
+    }
+}
+
+/// Snapshots (newest first) a policy doesn't keep. It keeps the newest snapshot of each of the
+/// `daily` most recent days that have one and of each of the `weekly` most recent ISO weeks, so
+/// a week's snapshot can also be one of the daily ones.
+pub fn expired(snapshots: &[Snapshot], daily: usize, weekly: usize) -> Vec<&Snapshot> {
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+
+    snapshots
+        .iter()
+        .filter(|snapshot| {
+            let day = snapshot.taken_at.date();
+            let week = (day.iso_week().year(), day.iso_week().week());
+            let kept_daily = days.len() < daily && days.insert(day);
+            let kept_weekly = weeks.len() < weekly && weeks.insert(week);
+            !kept_daily && !kept_weekly
+        })
+        .collect()
+}
+
+fn parse_file_name(name: &str) -> Option<NaiveDateTime> {
+    let timestamp = name.strip_prefix(FILE_PREFIX)?.strip_suffix(FILE_SUFFIX)?;
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    fn snapshot(taken_at: NaiveDateTime) -> Snapshot {
+        Snapshot {
+            path: PathBuf::from(format!("{}{}{}", FILE_PREFIX, taken_at.format(TIMESTAMP_FORMAT), FILE_SUFFIX)),
+            taken_at,
+            bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        let taken_at = NaiveDate::from_ymd_opt(2025, 4, 17).unwrap().and_hms_opt(9, 30, 5).unwrap();
+        assert_eq!(parse_file_name("messages-20250417-093005.db"), Some(taken_at));
+        assert_eq!(snapshot(taken_at).name(), "messages-20250417-093005.db");
+        assert_eq!(parse_file_name("messages-20250417-093005.db.partial"), None);
+        assert_eq!(parse_file_name("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_expired_snapshots() {
+        // One snapshot a day for 60 days, newest first, plus an extra one on the newest day.
+        // 2025-04-17 is a Thursday
+        let newest = NaiveDate::from_ymd_opt(2025, 4, 17).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        let mut snapshots = vec![snapshot(newest + Duration::hours(3))];
+        snapshots.extend((0..60).map(|days| snapshot(newest - Duration::days(days))));
+
+        let deleted = expired(&snapshots, 7, 4);
+        let kept: Vec<NaiveDate> = snapshots
+            .iter()
+            .filter(|snapshot| !deleted.contains(snapshot))
+            .map(|snapshot| snapshot.taken_at.date())
+            .collect();
+
+        // The newest of the last 7 days, then the newest (Sunday) of the 3 weeks before them
+        let date = |month, day| NaiveDate::from_ymd_opt(2025, month, day).unwrap();
+        assert_eq!(
+            kept,
+            vec![
+                date(4, 17),
+                date(4, 16),
+                date(4, 15),
+                date(4, 14),
+                date(4, 13),
+                date(4, 12),
+                date(4, 11),
+                date(4, 6),
+                date(3, 30),
+            ]
+        );
+        // Only the newest snapshot of a day is kept
+        assert!(deleted.contains(&&snapshots[1]));
+
+        assert_eq!(expired(&snapshots, 0, 0).len(), snapshots.len());
+    }
+}
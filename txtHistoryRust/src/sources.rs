@@ -0,0 +1,224 @@
+use std::fmt;
+
+use anyhow::Result;
+use chrono::Duration;
+
+use crate::models::Message;
+
+/// Sources that can be imported, in the default priority order used to resolve conflicts
+pub const KNOWN_SOURCES: &[&str] = &["iMessage", "WhatsApp", "Telegram", "Email"];
+
+/// Messages from different sources closer together than this may be the same exchange
+pub const CONFLICT_WINDOW_SECS: i64 = 120;
+
+/// Map a stored `service` value to the source it was imported from.
+/// chat.db services (iMessage, SMS, RCS) all count as the iMessage source.
+pub fn source_for_service(service: Option<&str>) -> &'static str {
+    match service.map(str::to_lowercase).as_deref() {
+        Some("email") => "Email",
+        Some("telegram") => "Telegram",
+        Some("whatsapp") => "WhatsApp",
+        _ => "iMessage",
+    }
+}
+
+/// Which sources to include, in priority order for conflict resolution
+#[derive(Debug, Clone, Default)]
+pub struct SourceFilter {
+    /// Selected sources; empty means all sources in the default order
+    sources: Vec<&'static str>,
+}
+
+impl SourceFilter {
+    /// Parse a comma separated list of sources (e.g. "imessage,whatsapp,email") or "all"
+    pub fn parse(value: &str) -> Result<Self> {
+        if value.trim().eq_ignore_ascii_case("all") {
+            return Ok(Self::default());
+        }
+
+        let mut sources = Vec::new();
+        for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let source = KNOWN_SOURCES
+                .iter()
+                .find(|known| known.eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Unknown source: {}. Available sources: {}", name, KNOWN_SOURCES.join(", "))
+                })?;
+            sources.push(*source);
+        }
+
+        Ok(Self { sources })
+    }
+
+    /// Whether a message stored with `service` belongs to a selected source
+    pub fn matches(&self, service: Option<&str>) -> bool {
+        self.sources.is_empty() || self.sources.contains(&source_for_service(service))
+    }
+
+    /// Lower is preferred
+    fn priority(&self, source: &str) -> usize {
+        let order = if self.sources.is_empty() { KNOWN_SOURCES } else { &self.sources[..] };
+        order.iter().position(|s| *s == source).unwrap_or(order.len())
+    }
+}
+
+/// A value `SourceFilter::parse` reads back as the same filter
+impl fmt::Display for SourceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.sources.is_empty() {
+            write!(f, "all")
+        } else {
+            write!(f, "{}", self.sources.join(","))
+        }
+    }
+}
+
+/// Drop messages that repeat the same exchange from a lower-priority source.
+///
+/// Two messages conflict when they come from different sources, have the same sender and
+/// text (ignoring case and whitespace), and are within `CONFLICT_WINDOW_SECS` of each other.
+/// Messages must be sorted by timestamp.
+pub fn resolve_conflicts(messages: Vec<Message>, filter: &SourceFilter) -> Vec<Message> {
+    let window = Duration::seconds(CONFLICT_WINDOW_SECS);
+    let mut kept: Vec<Message> = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        let text = normalize(&message.content);
+
+        // Only look back through kept messages inside the window
+        let conflict = kept
+            .iter()
+            .enumerate()
+            .rev()
+            .take_while(|(_, other)| message.timestamp - other.timestamp <= window)
+            .find(|(_, other)| {
+                other.source != message.source
+                    && other.sender == message.sender
+                    && normalize(&other.content) == text
+            })
+            .map(|(index, _)| index);
+
+        match conflict {
+            Some(index) => {
+                let existing = kept[index].source.as_deref().unwrap_or_default();
+                let current = message.source.as_deref().unwrap_or_default();
+                if filter.priority(current) < filter.priority(existing) {
+                    kept[index] = message;
+                }
+            }
+            None => kept.push(message),
+        }
+    }
+
+    kept
+}
+
+/// Drop messages that were resent over another service, such as an iMessage that failed and
+/// went out again with "Send as Text Message". A message is a resend when the same sender sent
+/// the same text over a different service within `CONFLICT_WINDOW_SECS`; the first copy is kept.
+/// Messages must be sorted by timestamp.
+pub fn merge_service_resends(messages: Vec<Message>) -> Vec<Message> {
+    let window = Duration::seconds(CONFLICT_WINDOW_SECS);
+    let mut kept: Vec<Message> = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        let text = normalize(&message.content);
+        let resend = kept
+            .iter()
+            .rev()
+            .take_while(|other| message.timestamp - other.timestamp <= window)
+            .any(|other| {
+                other.service != message.service
+                    && other.sender == message.sender
+                    && normalize(&other.content) == text
+            });
+
+        if !resend {
+            kept.push(message);
+        }
+    }
+
+    kept
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Whether messages come from more than one source and should be labeled in exports
+pub fn has_multiple_sources(messages: &[Message]) -> bool {
+    let mut sources = messages.iter().map(|m| m.source.as_deref());
+    match sources.next() {
+        Some(first) => sources.any(|s| s != first),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(sender: &str, seconds: u32, content: &str, source: &str) -> Message {
+        Message {
+            sender: sender.to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, seconds).unwrap(),
+            content: content.to_string(),
+            source: Some(source.to_string()),
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    #[test]
+    fn test_merge_service_resends() {
+        let mut sms = message("Jess", 40, "On my way", "iMessage");
+        sms.service = Some("SMS".to_string());
+        let mut imessage = message("Jess", 0, "On my way", "iMessage");
+        imessage.service = Some("iMessage".to_string());
+        let mut reply = message("Phil", 50, "ok", "iMessage");
+        reply.service = Some("SMS".to_string());
+        let mut again = message("Jess", 55, "On my way", "iMessage");
+        again.service = Some("iMessage".to_string());
+
+        let merged = merge_service_resends(vec![imessage, sms, reply, again]);
+        // The SMS copy goes, but sending the same text twice over one service is kept
+        let services: Vec<_> = merged.iter().map(|m| (m.sender.as_str(), m.service.as_deref())).collect();
+        assert_eq!(services, vec![("Jess", Some("iMessage")), ("Phil", Some("SMS")), ("Jess", Some("iMessage"))]);
+    }
+
+    #[test]
+    fn test_parse_sources() {
+        let filter = SourceFilter::parse("imessage, Email").unwrap();
+        assert!(filter.matches(Some("SMS")));
+        assert!(filter.matches(Some("Email")));
+        assert!(!filter.matches(Some("Telegram")));
+        assert!(SourceFilter::parse("all").unwrap().matches(Some("Telegram")));
+        assert!(SourceFilter::parse("myspace").is_err());
+        assert_eq!(filter.to_string(), "iMessage,Email");
+        assert_eq!(SourceFilter::default().to_string(), "all");
+    }
+
+    #[test]
+    fn test_resolve_conflicts_prefers_higher_priority() {
+        let messages = vec![
+            message("Phil", 0, "See you at 3", "Email"),
+            message("Phil", 30, "see you  at 3", "iMessage"),
+            message("Phil", 40, "Different text", "Email"),
+        ];
+
+        let resolved = resolve_conflicts(messages, &SourceFilter::default());
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].source.as_deref(), Some("iMessage"));
+
+        // Explicit order flips the preference
+        let messages = vec![
+            message("Phil", 0, "See you at 3", "Email"),
+            message("Phil", 30, "See you at 3", "iMessage"),
+        ];
+        let resolved = resolve_conflicts(messages, &SourceFilter::parse("email,imessage").unwrap());
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].source.as_deref(), Some("Email"));
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::Result;
+use rusqlite::types::ValueRef;
+use serde_json::{Map, Value};
+
+/// Longest cell shown in table output before it's truncated
+const MAX_CELL_CHARS: usize = 60;
+
+/// How `sql` prints its results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlOutput {
+    Table,
+    Csv,
+    Json,
+}
+
+impl SqlOutput {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow::anyhow!("Invalid output: {}. Use table, csv or json", value)),
+        }
+    }
+}
+
+/// Columns and rows returned by an ad-hoc query
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl QueryResult {
+    /// Convert a SQLite value; blobs are summarized rather than dumped
+    pub fn value(value: ValueRef<'_>) -> Value {
+        match value {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(i) => Value::from(i),
+            ValueRef::Real(f) => Value::from(f),
+            ValueRef::Text(text) => Value::from(String::from_utf8_lossy(text).to_string()),
+            ValueRef::Blob(bytes) => Value::from(format!("<{} bytes>", bytes.len())),
+        }
+    }
+
+    pub fn render(&self, output: SqlOutput) -> Result<String> {
+        match output {
+            SqlOutput::Table => Ok(self.to_table()),
+            SqlOutput::Csv => self.to_csv(),
+            SqlOutput::Json => self.to_json(),
+        }
+    }
+
+    /// Aligned columns with a header rule, like the sqlite3 shell's column mode
+    pub fn to_table(&self) -> String {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|value| truncate(&cell(value))).collect())
+            .collect();
+
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([column.chars().count()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let line = |values: Vec<&str>| {
+            values
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("{:<width$}", value, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        let mut lines = vec![
+            line(self.columns.iter().map(String::as_str).collect()),
+            line(rule.iter().map(String::as_str).collect()),
+        ];
+        lines.extend(cells.iter().map(|row| line(row.iter().map(String::as_str).collect())));
+        lines.push(format!("({} rows)", self.rows.len()));
+
+        lines.join("\n")
+    }
+
+    pub fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(&self.columns)?;
+        for row in &self.rows {
+            writer.write_record(row.iter().map(cell))?;
+        }
+
+        let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    /// One JSON object per row, keyed by column name
+    pub fn to_json(&self) -> Result<String> {
+        let rows: Vec<Value> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let object: Map<String, Value> = self.columns.iter().cloned().zip(row.iter().cloned()).collect();
+                Value::Object(object)
+            })
+            .collect();
+
+        Ok(serde_json::to_string_pretty(&rows)?)
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn truncate(text: &str) -> String {
+    let text = text.replace('\n', " ");
+    if text.chars().count() > MAX_CELL_CHARS {
+        format!("{}...", text.chars().take(MAX_CELL_CHARS - 3).collect::<String>())
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result() -> QueryResult {
+        QueryResult {
+            columns: vec!["sender".to_string(), "count(*)".to_string()],
+            rows: vec![
+                vec![Value::from("Phil"), Value::from(120)],
+                vec![Value::from("Jess, J"), Value::Null],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_render_query_result() {
+        assert_eq!(
+            result().to_table(),
+            "sender   count(*)\n-------  --------\nPhil     120\nJess, J\n(2 rows)"
+        );
+        assert_eq!(result().to_csv().unwrap(), "sender,count(*)\nPhil,120\n\"Jess, J\",\n");
+
+        let json: Value = serde_json::from_str(&result().to_json().unwrap()).unwrap();
+        assert_eq!(json[0]["count(*)"], 120);
+        assert!(json[1]["count(*)"].is_null());
+
+        assert_eq!(SqlOutput::parse("CSV").unwrap(), SqlOutput::Csv);
+        assert!(SqlOutput::parse("xml").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::models::Message;
+use crate::redact::{RedactionRule, Redactor};
+use crate::verbosity::detail;
+
+/// Which side of a chat view a sender's messages appear on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+}
+
+/// How one sender's messages are shown in HTML and TXT exports
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SenderStyle {
+    pub side: Side,
+
+    /// CSS color for the sender's bubbles, e.g. "#0b84fe"
+    #[serde(default)]
+    pub color: Option<String>,
+
+    /// Text put before each of the sender's TXT lines when TXT prefixes are on
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+/// Per-sender export styling. Senders without an entry default to me on the right and
+/// everyone else on the left.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportStyle {
+    /// Styles keyed by sender name
+    pub senders: BTreeMap<String, SenderStyle>,
+
+    /// Prefix TXT lines by side ("> " for the right side unless a sender has its own prefix)
+    pub txt_prefixes: bool,
+
+    /// End each TXT file with its message, word and character counts and reading time
+    pub txt_totals: bool,
+
+    /// Patterns hidden in message text in every export format
+    pub redactions: Vec<RedactionRule>,
+
+    /// Put each message's ID in TXT lines and templates, and first in CSV rows
+    pub include_ids: bool,
+}
+
+impl ExportStyle {
+    /// Style for a message's sender
+    pub fn style_for(&self, message: &Message) -> SenderStyle {
+        self.senders.get(&message.sender).cloned().unwrap_or(SenderStyle {
+            side: if message.is_from_me { Side::Right } else { Side::Left },
+            color: None,
+            prefix: None,
+        })
+    }
+
+    /// Prefix for a message's TXT line, empty when TXT prefixes are off
+    pub fn txt_prefix(&self, message: &Message) -> String {
+        if !self.txt_prefixes {
+            return String::new();
+        }
+
+        let style = self.style_for(message);
+        match (style.prefix, style.side) {
+            (Some(prefix), _) => prefix,
+            (None, Side::Right) => "> ".to_string(),
+            (None, Side::Left) => String::new(),
+        }
+    }
+
+    /// Apply the redaction rules to message text, returning the indices of the messages changed
+    pub fn redact(&self, messages: &mut [Message]) -> Result<Vec<usize>> {
+        let redacted = Redactor::new(&self.redactions)?.redact_messages(messages);
+        if !redacted.is_empty() {
+            detail(format!("Redacted text in {} messages", redacted.len()));
+        }
+        Ok(redacted)
+    }
+
+    /// Turn on message IDs from the command line; IDs on in the config stay on
+    pub fn with_ids(mut self, include_ids: bool) -> Self {
+        self.include_ids |= include_ids;
+        self
+    }
+
+    /// Apply command line overrides like "Jess=right:#0b84fe" or "Phil=left"
+    pub fn with_overrides(mut self, overrides: &[String], txt_prefixes: bool, txt_totals: bool) -> Result<Self> {
+        for value in overrides {
+            let (sender, spec) = value
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid sender style: {}. Use NAME=SIDE[:COLOR]", value))?;
+
+            let (side, color) = match spec.split_once(':') {
+                Some((side, color)) => (side, Some(color.to_string())),
+                None => (spec, None),
+            };
+            let side = match side.to_lowercase().as_str() {
+                "left" => Side::Left,
+                "right" => Side::Right,
+                other => return Err(anyhow::anyhow!("Invalid side: {}. Use left or right", other)),
+            };
+
+            let prefix = self.senders.get(sender.trim()).and_then(|s| s.prefix.clone());
+            self.senders
+                .insert(sender.trim().to_string(), SenderStyle { side, color, prefix });
+        }
+
+        self.txt_prefixes |= txt_prefixes;
+        self.txt_totals |= txt_totals;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(sender: &str, is_from_me: bool) -> Message {
+        Message {
+            sender: sender.to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: "hi".to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me,
+        }
+    }
+
+    #[test]
+    fn test_sender_styles() {
+        let style = ExportStyle::default()
+            .with_overrides(&["Phil=right:#ff0000".to_string()], true, false)
+            .unwrap();
+
+        assert_eq!(style.style_for(&message("Jess", true)).side, Side::Right);
+        assert_eq!(style.style_for(&message("Rhonda", false)).side, Side::Left);
+
+        let phil = style.style_for(&message("Phil", false));
+        assert_eq!(phil.side, Side::Right);
+        assert_eq!(phil.color.as_deref(), Some("#ff0000"));
+        assert_eq!(style.txt_prefix(&message("Phil", false)), "> ");
+        assert_eq!(style.txt_prefix(&message("Rhonda", false)), "");
+
+        assert!(ExportStyle::default().with_overrides(&["Phil=up".to_string()], false, false).is_err());
+        assert_eq!(ExportStyle::default().txt_prefix(&message("Jess", true)), "");
+    }
+}
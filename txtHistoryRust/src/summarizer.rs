@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::models::Message;
+use crate::sessions::DEFAULT_SESSION_GAP_MINUTES;
+
+/// Environment variable the API key is read from unless the config names another
+pub const DEFAULT_API_KEY_ENV: &str = "OPENAI_API_KEY";
+
+/// Changing the prompt changes what a summary means, so it's part of the cache key
+const PROMPT_VERSION: &str = "v1";
+
+pub(crate) const SYSTEM_PROMPT: &str = "You summarize text message conversations. For each excerpt, write two or \
+    three sentences covering what was discussed, decided or planned. Use the participants' names. Don't \
+    quote messages word for word or invent anything that isn't in the excerpt.";
+
+/// Delay before the first retry when the endpoint doesn't say how long to wait
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Where summaries are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummarizerBackend {
+    /// An OpenAI-compatible chat completions endpoint
+    #[default]
+    Api,
+    /// A GGUF model run in-process with llama.cpp; needs the `local-summarizer` feature
+    Local,
+}
+
+/// The model that writes abstractive summaries: an OpenAI-compatible chat completions endpoint,
+/// or a local model so nothing leaves the machine. The API is off until `endpoint` is set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SummarizerConfig {
+    pub backend: SummarizerBackend,
+
+    /// Base URL of the API, e.g. "https://api.openai.com/v1" or "http://localhost:11434/v1"
+    pub endpoint: Option<String>,
+
+    /// Model name sent with every request
+    pub model: String,
+
+    /// GGUF model file for the local backend
+    pub model_path: Option<PathBuf>,
+
+    /// Context window of the local model in tokens; a batch and its summaries must fit in it
+    pub context_size: u32,
+
+    /// Environment variable holding the API key. Leave it unset for local servers that don't
+    /// need one.
+    pub api_key_env: String,
+
+    /// Most requests sent per minute
+    pub requests_per_minute: u32,
+
+    /// Most days or sessions summarized in one request
+    pub batch_size: usize,
+
+    /// Most transcript characters in one request; longer days or sessions are cut short
+    pub max_batch_chars: usize,
+
+    /// Retries after a rate limit (429) or server error, waiting twice as long each time
+    pub max_retries: u32,
+
+    /// Seconds to wait for each response
+    pub timeout_secs: u64,
+}
+
+impl Default for SummarizerConfig {
+    fn default() -> Self {
+        Self {
+            backend: SummarizerBackend::Api,
+            endpoint: None,
+            model: "gpt-4o-mini".to_string(),
+            model_path: None,
+            context_size: 8192,
+            api_key_env: DEFAULT_API_KEY_ENV.to_string(),
+            requests_per_minute: 20,
+            batch_size: 10,
+            max_batch_chars: 12_000,
+            max_retries: 3,
+            timeout_secs: 60,
+        }
+    }
+}
+
+impl SummarizerConfig {
+    /// Model name stored with each summary: the API model, or the local model's file name
+    pub fn model_name(&self) -> String {
+        match (self.backend, &self.model_path) {
+            (SummarizerBackend::Local, Some(path)) => {
+                path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+            },
+            _ => self.model.clone(),
+        }
+    }
+}
+
+/// What each summary covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryUnit {
+    Day,
+    /// Runs of messages with no gap over an hour, as in `report style`
+    Session,
+}
+
+impl SummaryUnit {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "day" => Ok(Self::Day),
+            "session" => Ok(Self::Session),
+            _ => Err(anyhow::anyhow!("Invalid unit: {}. Use day or session", value)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Session => "session",
+        }
+    }
+}
+
+/// One day or session of a conversation, as sent to the model
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryInput {
+    /// Local date ("2025-01-20") or session start ("2025-01-20 09:00")
+    pub key: String,
+    pub message_count: usize,
+    /// One "HH:MM Sender: text" line per message
+    pub transcript: String,
+}
+
+impl SummaryInput {
+    fn new(key: String, messages: &[Message], max_chars: usize) -> Self {
+        let mut transcript = String::new();
+        for message in messages {
+            let line = format!(
+                "{} {}: {}\n",
+                message.timestamp.format("%H:%M"),
+                message.sender,
+                message.content.replace('\n', " ")
+            );
+            if transcript.len() + line.len() > max_chars {
+                transcript.push_str("[rest of the conversation left out]\n");
+                break;
+            }
+            transcript.push_str(&line);
+        }
+        Self { key, message_count: messages.len(), transcript }
+    }
+
+    /// Cache key: the same transcript under the same prompt gets the same summary
+    pub fn input_hash(&self) -> String {
+        let hash = Sha256::new()
+            .chain_update(PROMPT_VERSION.as_bytes())
+            .chain_update(self.transcript.as_bytes())
+            .finalize();
+        format!("{:x}", hash)
+    }
+}
+
+/// Split messages sorted by date into days or sessions, keeping each transcript within
+/// `max_chars`. Messages are taken as given, so redact them first.
+pub fn split_inputs(messages: &[Message], unit: SummaryUnit, max_chars: usize) -> Vec<SummaryInput> {
+    let gap = chrono::Duration::minutes(DEFAULT_SESSION_GAP_MINUTES);
+    let splits = |before: &Message, after: &Message| match unit {
+        SummaryUnit::Day => before.timestamp.date_naive() != after.timestamp.date_naive(),
+        SummaryUnit::Session => after.timestamp - before.timestamp > gap,
+    };
+    let key_format = match unit {
+        SummaryUnit::Day => "%Y-%m-%d",
+        SummaryUnit::Session => "%Y-%m-%d %H:%M",
+    };
+
+    let mut inputs = Vec::new();
+    let mut start = 0;
+    for i in 1..=messages.len() {
+        if i == messages.len() || splits(&messages[i - 1], &messages[i]) {
+            let key = messages[start].timestamp.format(key_format).to_string();
+            inputs.push(SummaryInput::new(key, &messages[start..i], max_chars));
+            start = i;
+        }
+    }
+    inputs
+}
+
+/// Group inputs into requests of at most `batch_size` inputs and `max_chars` transcript
+/// characters. An input longer than `max_chars` on its own still gets a request.
+pub fn plan_batches(inputs: &[SummaryInput], batch_size: usize, max_chars: usize) -> Vec<&[SummaryInput]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut chars = 0;
+
+    for (i, input) in inputs.iter().enumerate() {
+        let full = i - start >= batch_size.max(1) || (i > start && chars + input.transcript.len() > max_chars);
+        if full {
+            batches.push(&inputs[start..i]);
+            start = i;
+            chars = 0;
+        }
+        chars += input.transcript.len();
+    }
+    if start < inputs.len() {
+        batches.push(&inputs[start..]);
+    }
+
+    batches
+}
+
+/// Keeps requests within `requests_per_minute`, like `Throttle` does for rows
+#[derive(Debug)]
+pub struct RequestLimiter {
+    requests_per_minute: u32,
+    started: Instant,
+    requests: u32,
+}
+
+impl RequestLimiter {
+    pub fn new(requests_per_minute: u32) -> Self {
+        Self { requests_per_minute, started: Instant::now(), requests: 0 }
+    }
+
+    /// Sleep until another request fits within the rate, then count it
+    pub fn wait(&mut self) {
+        if let Some(delay) = self.delay_after(self.started.elapsed()) {
+            std::thread::sleep(delay);
+        }
+    }
+
+    fn delay_after(&mut self, elapsed: Duration) -> Option<Duration> {
+        let delay = match self.requests_per_minute {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(f64::from(self.requests) * 60.0 / f64::from(rate)).saturating_sub(elapsed),
+        };
+        self.requests += 1;
+        (!delay.is_zero()).then_some(delay)
+    }
+}
+
+/// Writes one summary for each day or session in a batch
+pub trait Summarizer {
+    /// Summarize a batch, returning (key, summary) for each input the model answered for.
+    /// Inputs it skipped are left out, so they're sent again next time.
+    fn summarize(&mut self, title: &str, batch: &[SummaryInput]) -> Result<Vec<(String, String)>>;
+}
+
+/// Open the backend the config selects
+pub fn open_summarizer(config: &SummarizerConfig) -> Result<Box<dyn Summarizer>> {
+    match config.backend {
+        SummarizerBackend::Api => Ok(Box::new(ApiSummarizer::new(config)?)),
+        #[cfg(feature = "local-summarizer")]
+        SummarizerBackend::Local => Ok(Box::new(crate::local_summarizer::LocalSummarizer::new(config)?)),
+        #[cfg(not(feature = "local-summarizer"))]
+        SummarizerBackend::Local => Err(anyhow::anyhow!(
+            "The local summarizer isn't built in; rebuild with --features local-summarizer"
+        )),
+    }
+}
+
+/// Sends batches of days or sessions to an OpenAI-compatible endpoint
+pub struct ApiSummarizer {
+    config: SummarizerConfig,
+    endpoint: String,
+    api_key: Option<String>,
+    limiter: RequestLimiter,
+}
+
+impl ApiSummarizer {
+    /// Fails when no endpoint is configured
+    pub fn new(config: &SummarizerConfig) -> Result<Self> {
+        let endpoint = config
+            .endpoint
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Set summarizer.endpoint in the config to an OpenAI-compatible API"))?;
+        Ok(Self {
+            config: config.clone(),
+            endpoint: format!("{}/chat/completions", endpoint.trim_end_matches('/')),
+            api_key: std::env::var(&config.api_key_env).ok().filter(|key| !key.is_empty()),
+            limiter: RequestLimiter::new(config.requests_per_minute),
+        })
+    }
+
+    fn send(&mut self, body: &Value) -> Result<Value> {
+        let mut backoff = INITIAL_BACKOFF;
+
+        for attempt in 0.. {
+            self.limiter.wait();
+            let mut request = ureq::post(&self.endpoint).timeout(Duration::from_secs(self.config.timeout_secs));
+            if let Some(key) = &self.api_key {
+                request = request.set("Authorization", &format!("Bearer {}", key));
+            }
+
+            match request.send_json(body) {
+                Ok(response) => return response.into_json().context("Invalid JSON from the summarizer"),
+                Err(ureq::Error::Status(status, response))
+                    if (status == 429 || status >= 500) && attempt < self.config.max_retries =>
+                {
+                    let wait = response
+                        .header("Retry-After")
+                        .and_then(|seconds| seconds.trim().parse().ok())
+                        .map(Duration::from_secs)
+                        .unwrap_or(backoff);
+                    eprintln!("Summarizer returned {}; retrying in {}s", status, wait.as_secs());
+                    std::thread::sleep(wait);
+                    backoff *= 2;
+                },
+                Err(ureq::Error::Status(status, response)) => {
+                    let detail = response.into_string().unwrap_or_default();
+                    return Err(anyhow::anyhow!("Summarizer request failed with {}: {}", status, detail.trim()));
+                },
+                Err(e) => return Err(e).context("Summarizer request failed"),
+            }
+        }
+
+        unreachable!("the retry loop only ends by returning")
+    }
+}
+
+impl Summarizer for ApiSummarizer {
+    fn summarize(&mut self, title: &str, batch: &[SummaryInput]) -> Result<Vec<(String, String)>> {
+        let body = json!({
+            "model": self.config.model,
+            "temperature": 0.2,
+            "messages": [
+                { "role": "system", "content": SYSTEM_PROMPT },
+                { "role": "user", "content": build_prompt(title, batch) },
+            ],
+        });
+
+        let response = self.send(&body)?;
+        let content = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("The summarizer's response has no message content"))?;
+        parse_summaries(content, batch)
+    }
+}
+
+/// The user message for a batch: instructions for the JSON reply, then each transcript under
+/// its key
+pub(crate) fn build_prompt(title: &str, batch: &[SummaryInput]) -> String {
+    let mut prompt = format!(
+        "Summarize each excerpt of the conversation with {}. Reply with JSON only, in the form \
+         {{\"summaries\": [{{\"key\": \"<key>\", \"summary\": \"<summary>\"}}]}}, with one entry per key.\n",
+        title
+    );
+    for input in batch {
+        prompt.push_str(&format!("\n### {}\n{}", input.key, input.transcript));
+    }
+    prompt
+}
+
+/// Read the summaries out of the model's reply, which may wrap the JSON in a code fence
+pub(crate) fn parse_summaries(content: &str, batch: &[SummaryInput]) -> Result<Vec<(String, String)>> {
+    let json = match (content.find('{'), content.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &content[start..=end],
+        _ => return Err(anyhow::anyhow!("The summarizer didn't reply with JSON: {}", content)),
+    };
+    let reply: Value = serde_json::from_str(json).context("The summarizer's reply isn't valid JSON")?;
+
+    let summaries = reply["summaries"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some((entry["key"].as_str()?, entry["summary"].as_str()?.trim())))
+        .filter(|(key, summary)| !summary.is_empty() && batch.iter().any(|input| input.key == *key))
+        .map(|(key, summary)| (key.to_string(), summary.to_string()))
+        .collect();
+    Ok(summaries)
+}
+
+/// Render summaries as Markdown with one section per day or session; ones without a summary yet
+/// say so
+pub fn to_markdown(title: &str, inputs: &[SummaryInput], summaries: &HashMap<String, String>) -> String {
+    let mut output = format!("# {}\n", title);
+
+    for input in inputs {
+        let summary = summaries.get(&input.key).map(String::as_str).unwrap_or("_Not summarized yet._");
+        output.push_str(&format!("\n## {} ({} messages)\n\n{}\n", input.key, input.message_count, summary));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, NaiveDateTime, TimeZone};
+
+    fn message(sender: &str, at: &str, content: &str) -> Message {
+        let naive = NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M").unwrap();
+        Message {
+            sender: sender.to_string(),
+            timestamp: Local.from_local_datetime(&naive).earliest().unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: sender == "Jess",
+        }
+    }
+
+    #[test]
+    fn test_batches_and_replies() {
+        let messages = vec![
+            message("Phil", "2025-01-20 09:00", "Can you take her to soccer?"),
+            message("Jess", "2025-01-20 09:05", "Yes, at 5"),
+            message("Phil", "2025-01-20 20:00", "Thanks"),
+            message("Jess", "2025-01-21 08:00", "Running late"),
+        ];
+
+        let days = split_inputs(&messages, SummaryUnit::Day, 1000);
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].key, "2025-01-20");
+        assert_eq!(days[0].message_count, 3);
+        assert_eq!(days[1].transcript, "08:00 Jess: Running late\n");
+        let sessions = split_inputs(&messages, SummaryUnit::Session, 1000);
+        let keys: Vec<&str> = sessions.iter().map(|s| s.key.as_str()).collect();
+        assert_eq!(keys, vec!["2025-01-20 09:00", "2025-01-20 20:00", "2025-01-21 08:00"]);
+
+        // Long transcripts are cut, and change the cache key
+        let short = split_inputs(&messages, SummaryUnit::Day, 40);
+        assert!(short[0].transcript.ends_with("[rest of the conversation left out]\n"));
+        assert_ne!(short[0].input_hash(), days[0].input_hash());
+        assert_eq!(days[0].input_hash(), split_inputs(&messages, SummaryUnit::Day, 1000)[0].input_hash());
+
+        let sizes: Vec<usize> = plan_batches(&sessions, 2, 1000).iter().map(|b| b.len()).collect();
+        assert_eq!(sizes, vec![2, 1]);
+        let sizes: Vec<usize> = plan_batches(&sessions, 10, 60).iter().map(|b| b.len()).collect();
+        assert_eq!(sizes, vec![1, 2]);
+
+        let reply = "```json\n{\"summaries\": [{\"key\": \"2025-01-20\", \"summary\": \"Jess agreed to take her to \
+                     soccer at 5.\"}, {\"key\": \"1999-01-01\", \"summary\": \"Made up\"}]}\n```";
+        let summaries = parse_summaries(reply, &days).unwrap();
+        assert_eq!(
+            summaries,
+            vec![("2025-01-20".to_string(), "Jess agreed to take her to soccer at 5.".to_string())]
+        );
+        assert!(parse_summaries("Sorry, I can't help with that", &days).is_err());
+        assert!(build_prompt("Phil", &days).contains("### 2025-01-21\n08:00 Jess: Running late"));
+
+        let markdown = to_markdown("Phil", &days, &summaries.into_iter().collect());
+        assert!(markdown.contains("## 2025-01-20 (3 messages)\n\nJess agreed to take her to soccer at 5.\n"));
+        assert!(markdown.contains("## 2025-01-21 (1 messages)\n\n_Not summarized yet._\n"));
+    }
+
+    #[test]
+    fn test_backends() {
+        let api = SummarizerConfig::default();
+        assert_eq!(api.model_name(), "gpt-4o-mini");
+        // Without an endpoint nothing is sent anywhere
+        assert!(open_summarizer(&api).is_err());
+
+        let local: SummarizerConfig =
+            serde_json::from_str(r#"{"backend": "local", "model_path": "/models/llama-3.2-3b-instruct.Q4_K_M.gguf"}"#)
+                .unwrap();
+        assert_eq!(local.backend, SummarizerBackend::Local);
+        assert_eq!(local.model_name(), "llama-3.2-3b-instruct.Q4_K_M");
+        assert_eq!(local.context_size, 8192);
+    }
+
+    #[test]
+    fn test_request_limiter() {
+        // 30 requests a minute: the first goes at once, the next two seconds after it
+        let mut limiter = RequestLimiter::new(30);
+        assert_eq!(limiter.delay_after(Duration::ZERO), None);
+        assert_eq!(limiter.delay_after(Duration::from_millis(500)), Some(Duration::from_millis(1500)));
+        assert_eq!(limiter.delay_after(Duration::from_secs(10)), None);
+
+        let mut unlimited = RequestLimiter::new(0);
+        assert_eq!(unlimited.delay_after(Duration::ZERO), None);
+        assert_eq!(unlimited.delay_after(Duration::ZERO), None);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Local};
+
+use crate::models::Message;
+
+/// Counts for `query --summary`: who sent how much and who starts conversations
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversationSummary {
+    pub label: String,
+    pub messages: usize,
+    pub sent: usize,
+    pub received: usize,
+    /// Messages per sender, most first
+    pub by_sender: Vec<(String, usize)>,
+    pub first: Option<DateTime<Local>>,
+    pub last: Option<DateTime<Local>>,
+    /// Sessions, as split by `session_gap`
+    pub sessions: usize,
+    /// Sessions each sender sent the first message of, most first
+    pub initiated: Vec<(String, usize)>,
+    pub session_gap: Duration,
+}
+
+impl ConversationSummary {
+    /// Summarize a conversation sorted by time. A new session starts after `session_gap`
+    /// without messages.
+    pub fn new(messages: &[Message], label: &str, session_gap: Duration) -> Self {
+        let mut by_sender: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut initiated: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut sessions = 0;
+        let mut previous: Option<DateTime<Local>> = None;
+
+        for message in messages {
+            *by_sender.entry(message.sender.as_str()).or_default() += 1;
+            if previous.map_or(true, |at| message.timestamp - at > session_gap) {
+                *initiated.entry(message.sender.as_str()).or_default() += 1;
+                sessions += 1;
+            }
+            previous = Some(message.timestamp);
+        }
+
+        let sent = messages.iter().filter(|m| m.is_from_me).count();
+        Self {
+            label: label.to_string(),
+            messages: messages.len(),
+            sent,
+            received: messages.len() - sent,
+            by_sender: most_first(by_sender),
+            first: messages.first().map(|m| m.timestamp),
+            last: messages.last().map(|m| m.timestamp),
+            sessions,
+            initiated: most_first(initiated),
+            session_gap,
+        }
+    }
+
+    /// Sent messages per received message, `None` when nothing was received
+    pub fn sent_received_ratio(&self) -> Option<f64> {
+        (self.received > 0).then(|| self.sent as f64 / self.received as f64)
+    }
+
+    /// Several lines for the terminal
+    pub fn to_text(&self) -> String {
+        let format = |at: Option<DateTime<Local>>| at.map_or_else(|| "-".to_string(), |at| at.format("%Y-%m-%d %H:%M").to_string());
+        let share = |count: usize, total: usize| if total == 0 { 0.0 } else { count as f64 * 100.0 / total as f64 };
+
+        let mut lines = vec![
+            format!("{}: {} messages", self.label, self.messages),
+            format!("First: {}", format(self.first)),
+            format!("Last:  {}", format(self.last)),
+            match self.sent_received_ratio() {
+                Some(ratio) => format!("Sent {}, received {} (ratio {:.2})", self.sent, self.received, ratio),
+                None => format!("Sent {}, received {}", self.sent, self.received),
+            },
+            "By sender:".to_string(),
+        ];
+        for (sender, count) in &self.by_sender {
+            lines.push(format!("  {:<20} {:>8} {:>6.1}%", sender, count, share(*count, self.messages)));
+        }
+
+        lines.push(format!(
+            "Started {} sessions (a session ends after {} min without messages):",
+            self.sessions,
+            self.session_gap.num_minutes()
+        ));
+        for (sender, count) in &self.initiated {
+            lines.push(format!("  {:<20} {:>8} {:>6.1}%", sender, count, share(*count, self.sessions)));
+        }
+
+        lines.join("\n")
+    }
+}
+
+fn most_first(counts: BTreeMap<&str, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+    // Stable, so ties stay in name order
+    counts.sort_by(|a, b| b.1.cmp(&a.1));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDateTime, TimeZone};
+
+    fn message(sender: &str, at: &str) -> Message {
+        let naive = NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M").unwrap();
+        Message {
+            sender: sender.to_string(),
+            timestamp: Local.from_local_datetime(&naive).earliest().unwrap(),
+            content: "hi".to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: sender == "Jess",
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let messages = vec![
+            message("Phil", "2025-01-20 09:00"),
+            message("Jess", "2025-01-20 09:05"),
+            message("Jess", "2025-01-20 18:00"),
+            message("Phil", "2025-01-20 18:01"),
+            message("Phil", "2025-01-20 18:02"),
+            message("Phil", "2025-01-21 08:00"),
+        ];
+        let summary = ConversationSummary::new(&messages, "Phil", Duration::minutes(60));
+
+        assert_eq!((summary.messages, summary.sent, summary.received), (6, 2, 4));
+        assert_eq!(summary.sent_received_ratio(), Some(0.5));
+        assert_eq!(summary.by_sender, vec![("Phil".to_string(), 4), ("Jess".to_string(), 2)]);
+        assert_eq!(summary.sessions, 3);
+        assert_eq!(summary.initiated, vec![("Phil".to_string(), 2), ("Jess".to_string(), 1)]);
+        assert_eq!(summary.first, Some(messages[0].timestamp));
+        assert_eq!(summary.last, Some(messages[5].timestamp));
+
+        let text = summary.to_text();
+        assert!(text.starts_with("Phil: 6 messages\nFirst: 2025-01-20 09:00\nLast:  2025-01-21 08:00\n"));
+        assert!(text.contains("Sent 2, received 4 (ratio 0.50)"));
+        assert!(text.contains("  Phil                        2   66.7%"));
+
+        let empty = ConversationSummary::new(&[], "Phil", Duration::minutes(60));
+        assert_eq!((empty.sessions, empty.sent_received_ratio()), (0, None));
+    }
+}
@@ -0,0 +1,330 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use minijinja::{context, Environment};
+use serde::Serialize;
+
+use crate::models::{ContactDetails, Message};
+use crate::style::ExportStyle;
+
+/// Templates shipped with the tool, selectable by name with `--template`
+const BUILTIN_TEMPLATES: &[(&str, &str, &str)] = &[
+    ("plain", "txt", include_str!("../templates/plain.txt")),
+    ("bubbles", "html", include_str!("../templates/bubbles.html")),
+    ("court-transcript", "txt", include_str!("../templates/court-transcript.txt")),
+];
+
+/// Page listing the chunks of a multi-chunk HTML export
+const CHUNK_INDEX_TEMPLATE: &str = include_str!("../templates/chunk_index.html");
+
+/// File name of the chunk index written next to multi-chunk HTML exports
+pub const CHUNK_INDEX_FILE: &str = "index.html";
+
+/// A user supplied or built-in minijinja template that renders one export file per chunk.
+///
+/// Templates receive `contact`, `chunk`, `chunks`, `label_sources`, a flat `messages` list and
+/// the same messages grouped by calendar day in `days`. `prev` and `next` are the file names of
+/// the neighbouring chunks (unset for the first and last), and `index` is `index.html` when the
+/// export is HTML and has more than one chunk. Each message has `number`, `sender`,
+/// `timestamp`, `date`, `time`, `iso`, `content`, `source`, and the sender's `side` ("left" or
+/// "right") and optional `color` from the export style. `contact_details` has the contact's
+/// `relationship`, `timezone`, `birthday` and `notes`, each unset unless `contacts edit` set it.
+#[derive(Debug, Clone)]
+pub struct ExportTemplate {
+    name: String,
+    source: String,
+    extension: String,
+    contact_details: ContactDetails,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TemplateMessage<'a> {
+    number: usize,
+    /// Original message ID, shown by the built-in templates with `include_ids`
+    id: Option<&'a str>,
+    sender: &'a str,
+    timestamp: String,
+    date: String,
+    time: String,
+    iso: String,
+    content: &'a str,
+    source: Option<&'a str>,
+    side: &'static str,
+    color: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct TemplateDay<'a> {
+    date: String,
+    messages: Vec<TemplateMessage<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct IndexEntry {
+    number: usize,
+    file: String,
+    messages: usize,
+    first: String,
+    last: String,
+}
+
+/// Name of chunk `chunk` (starting at 1) of a templated export
+pub fn chunk_file_name(chunk: usize, extension: &str) -> String {
+    format!("chunk_{}.{}", chunk, extension)
+}
+
+/// Render the index page of a multi-chunk HTML export: every chunk with its date range and a
+/// link to its file
+pub fn render_chunk_index(contact: &str, chunks: &[&[Message]], extension: &str) -> Result<String> {
+    let entries: Vec<IndexEntry> = chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let format = |message: Option<&Message>| {
+                message.map(|m| m.timestamp.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default()
+            };
+            IndexEntry {
+                number: i + 1,
+                file: chunk_file_name(i + 1, extension),
+                messages: chunk.len(),
+                first: format(chunk.first()),
+                last: format(chunk.last()),
+            }
+        })
+        .collect();
+
+    let mut env = Environment::new();
+    env.add_template(CHUNK_INDEX_FILE, CHUNK_INDEX_TEMPLATE)?;
+    env.get_template(CHUNK_INDEX_FILE)?
+        .render(context! {
+            contact => contact,
+            total => chunks.iter().map(|chunk| chunk.len()).sum::<usize>(),
+            chunks => entries,
+        })
+        .context("Failed to render chunk index")
+}
+
+impl ExportTemplate {
+    /// Load a built-in template by name, or a template file from disk
+    pub fn load(spec: &str) -> Result<Self> {
+        if let Some((name, extension, source)) = BUILTIN_TEMPLATES.iter().find(|(name, _, _)| *name == spec) {
+            return Ok(Self {
+                // The extension in the name turns on HTML escaping for HTML templates
+                name: format!("{}.{}", name, extension),
+                source: source.to_string(),
+                extension: extension.to_string(),
+                contact_details: ContactDetails::default(),
+            });
+        }
+
+        let path = Path::new(spec);
+        if !path.exists() {
+            let builtins: Vec<&str> = BUILTIN_TEMPLATES.iter().map(|(name, _, _)| *name).collect();
+            return Err(anyhow::anyhow!(
+                "Template not found: {}. Built-in templates: {}",
+                spec,
+                builtins.join(", ")
+            ));
+        }
+
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read template {}", path.display()))?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| spec.to_string());
+
+        Ok(Self {
+            extension: output_extension(&name),
+            name,
+            source,
+            contact_details: ContactDetails::default(),
+        })
+    }
+
+    /// Pass the exported contact's notes and details to the template
+    pub fn with_contact_details(mut self, details: ContactDetails) -> Self {
+        self.contact_details = details;
+        self
+    }
+
+    /// File extension for rendered output, e.g. "html" for `layout.html.tera`
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Render one chunk of messages
+    pub fn render(
+        &self,
+        messages: &[Message],
+        contact: &str,
+        chunk: usize,
+        chunks: usize,
+        label_sources: bool,
+        style: &ExportStyle,
+    ) -> Result<String> {
+        let mut env = Environment::new();
+        env.add_template(&self.name, &self.source)
+            .with_context(|| format!("Invalid template {}", self.name))?;
+
+        let template_messages: Vec<TemplateMessage<'_>> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let sender_style = style.style_for(message);
+                TemplateMessage {
+                    number: i + 1,
+                    id: message.imessage_id.as_deref(),
+                    sender: &message.sender,
+                    timestamp: message.timestamp.format("%b %d, %Y %r").to_string(),
+                    date: message.timestamp.format("%Y-%m-%d").to_string(),
+                    time: message.timestamp.format("%H:%M:%S").to_string(),
+                    iso: message.timestamp.to_rfc3339(),
+                    content: &message.content,
+                    source: message.source.as_deref(),
+                    side: sender_style.side.as_str(),
+                    color: sender_style.color,
+                }
+            })
+            .collect();
+
+        let mut days: Vec<TemplateDay<'_>> = Vec::new();
+        for message in &template_messages {
+            if days.last().map_or(true, |day| day.date != message.date) {
+                days.push(TemplateDay { date: message.date.clone(), messages: Vec::new() });
+            }
+            if let Some(day) = days.last_mut() {
+                day.messages.push(message.clone());
+            }
+        }
+
+        env.get_template(&self.name)?
+            .render(context! {
+                contact => contact,
+                contact_details => &self.contact_details,
+                chunk => chunk,
+                chunks => chunks,
+                label_sources => label_sources,
+                include_ids => style.include_ids,
+                messages => template_messages,
+                days => days,
+                prev => (chunk > 1).then(|| chunk_file_name(chunk - 1, &self.extension)),
+                next => (chunk < chunks).then(|| chunk_file_name(chunk + 1, &self.extension)),
+                index => (chunks > 1 && self.extension == "html").then_some(CHUNK_INDEX_FILE),
+            })
+            .with_context(|| format!("Failed to render template {}", self.name))
+    }
+}
+
+/// Strip template engine suffixes and use the remaining extension, defaulting to txt
+fn output_extension(file_name: &str) -> String {
+    let stem = [".tera", ".jinja", ".j2"]
+        .iter()
+        .find_map(|suffix| file_name.strip_suffix(suffix))
+        .unwrap_or(file_name);
+
+    match Path::new(stem).extension() {
+        Some(ext) => ext.to_string_lossy().to_string(),
+        None => "txt".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(sender: &str, day: u32, content: &str) -> Message {
+        Message {
+            sender: sender.to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, day, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    #[test]
+    fn test_output_extension() {
+        assert_eq!(output_extension("layout.html.tera"), "html");
+        assert_eq!(output_extension("my_layout.tera"), "txt");
+        assert_eq!(output_extension("layout.md"), "md");
+    }
+
+    #[test]
+    fn test_builtin_templates_render() {
+        let messages = vec![
+            message("Phil", 20, "Pick up at <3pm>"),
+            message("Jess", 20, "ok"),
+            message("Phil", 21, "Thanks"),
+        ];
+
+        let plain = ExportTemplate::load("plain").unwrap();
+        let output = plain.render(&messages, "Phil", 1, 1, false, &ExportStyle::default()).unwrap();
+        assert!(output.starts_with("Phil, Jan 20, 2025"));
+
+        let court = ExportTemplate::load("court-transcript").unwrap();
+        let output = court.render(&messages, "Phil", 1, 1, false, &ExportStyle::default()).unwrap();
+        assert!(output.contains("=== 2025-01-20 ==="));
+        assert!(output.contains("=== 2025-01-21 ==="));
+
+        let bubbles = ExportTemplate::load("bubbles").unwrap();
+        assert_eq!(bubbles.extension(), "html");
+        let output = bubbles.render(&messages, "Phil", 1, 1, false, &ExportStyle::default()).unwrap();
+        assert!(output.contains("&lt;3pm&gt;"));
+
+        let style = ExportStyle::default()
+            .with_overrides(&["Jess=right:#0b84fe".to_string()], false, false)
+            .unwrap();
+        let output = bubbles.render(&messages, "Phil", 1, 1, false, &style).unwrap();
+        assert!(output.contains("message right"));
+        assert!(output.contains("#0b84fe"));
+
+        assert!(ExportTemplate::load("no-such-template").is_err());
+    }
+
+    #[test]
+    fn test_contact_details_in_headers() {
+        let messages = vec![message("Phil", 20, "Pick up at 3pm")];
+        let details = ContactDetails { relationship: Some("co-parent".to_string()), ..Default::default() };
+
+        let bubbles = ExportTemplate::load("bubbles").unwrap();
+        let output = bubbles.render(&messages, "Phil", 1, 1, false, &ExportStyle::default()).unwrap();
+        assert!(output.contains("<title>Conversation with Phil</title>"));
+        let output = bubbles
+            .with_contact_details(details.clone())
+            .render(&messages, "Phil", 1, 1, false, &ExportStyle::default())
+            .unwrap();
+        assert!(output.contains("<title>Conversation with Phil (co-parent)</title>"));
+
+        let court = ExportTemplate::load("court-transcript").unwrap().with_contact_details(details);
+        let output = court.render(&messages, "Phil", 1, 1, false, &ExportStyle::default()).unwrap();
+        assert!(output.contains("CONVERSATION WITH PHIL (CO-PARENT)"));
+    }
+
+    #[test]
+    fn test_chunk_navigation() {
+        let messages = vec![message("Phil", 20, "First"), message("Jess", 21, "Second"), message("Phil", 22, "Third")];
+        let bubbles = ExportTemplate::load("bubbles").unwrap();
+
+        let only = bubbles.render(&messages, "Phil", 1, 1, false, &ExportStyle::default()).unwrap();
+        assert!(!only.contains("<nav>"));
+
+        let middle = bubbles.render(&messages[1..2], "Phil", 2, 3, false, &ExportStyle::default()).unwrap();
+        assert!(middle.contains("<a href=\"chunk_1.html\">&larr; Previous</a>"));
+        assert!(middle.contains("<a href=\"index.html\">Part 2 of 3</a>"));
+        assert!(middle.contains("<a href=\"chunk_3.html\">Next &rarr;</a>"));
+
+        let last = bubbles.render(&messages[2..], "Phil", 3, 3, false, &ExportStyle::default()).unwrap();
+        assert!(!last.contains("Next"));
+
+        let chunks: Vec<&[Message]> = vec![&messages[..2], &messages[2..]];
+        let index = render_chunk_index("Phil <Work>", &chunks, "html").unwrap();
+        assert!(index.contains("<title>Conversation with Phil &lt;Work&gt;</title>"));
+        assert!(index.contains("3 messages in 2 parts"));
+        assert!(index.contains("<a href=\"chunk_2.html\">2</a></td><td>2025-01-22 12:00</td><td>2025-01-22 12:00</td>"));
+    }
+}
@@ -0,0 +1,312 @@
+//! Fakes for testing code built on this crate, enabled with the `test-util` feature
+
+pub mod chat_db;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+
+use crate::export_options::ExportOptions;
+use crate::models::{Contact, DateRange, Message, MessageKind, NewContact, NewMessage, OutputFormat};
+use crate::repository::{save_messages_to, write_conversation, FetchedMessages, MessageRepository};
+use crate::sources::source_for_service;
+
+/// A `MessageRepository` call, for failure injection and checking what was called
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockCall {
+    FetchMessages,
+    SaveMessages,
+    ExportConversation,
+}
+
+/// In-memory `MessageRepository` serving canned conversations. Saves and exports write real
+/// files the way `IMessageDatabaseRepo` does, so their output can be checked.
+#[derive(Default)]
+pub struct MockMessageRepository {
+    conversations: HashMap<String, Vec<Message>>,
+    failures: HashSet<MockCall>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl MockMessageRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `messages` as the conversation with `contact`, in timestamp order
+    pub fn with_conversation(mut self, contact: &str, mut messages: Vec<Message>) -> Self {
+        messages.sort_by_key(|m| m.timestamp);
+        self.conversations.insert(contact.to_string(), messages);
+        self
+    }
+
+    /// Make every `call` fail with an error
+    pub fn failing(mut self, call: MockCall) -> Self {
+        self.failures.insert(call);
+        self
+    }
+
+    /// Calls made so far, in order, including ones that failed
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn call(&self, call: MockCall) -> Result<()> {
+        self.calls.lock().unwrap().push(call);
+        if self.failures.contains(&call) {
+            return Err(anyhow::anyhow!("Injected failure in {:?}", call));
+        }
+        Ok(())
+    }
+
+    /// The conversation with `contact` within `date_range`
+    fn conversation(&self, contact: &str, date_range: &DateRange) -> Vec<Message> {
+        self.conversations
+            .get(contact)
+            .map(|messages| {
+                messages
+                    .iter()
+                    .filter(|m| date_range.start.map_or(true, |start| m.timestamp >= start))
+                    .filter(|m| date_range.end.map_or(true, |end| m.timestamp <= end))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl MessageRepository for MockMessageRepository {
+    async fn fetch_messages(&self, contact: &Contact, date_range: &DateRange) -> Result<FetchedMessages> {
+        self.call(MockCall::FetchMessages)?;
+        Ok(FetchedMessages { messages: self.conversation(&contact.name, date_range), ..Default::default() })
+    }
+
+    async fn save_messages(&self, messages: &[Message], format: OutputFormat, path: &Path) -> Result<()> {
+        self.call(MockCall::SaveMessages)?;
+        save_messages_to(messages, format, path).await
+    }
+
+    async fn export_conversation_by_person(&self, person_name: &str, options: &ExportOptions) -> Result<Vec<PathBuf>> {
+        self.call(MockCall::ExportConversation)?;
+        let messages = self.conversation(person_name, options.date_range());
+        if messages.is_empty() {
+            return Ok(Vec::new());
+        }
+        write_conversation(&messages, options).await
+    }
+}
+
+/// Builds a fake `Message`, by default received over iMessage at noon on Jan 20, 2025
+pub struct MessageBuilder {
+    message: Message,
+}
+
+impl MessageBuilder {
+    pub fn new(sender: &str, content: &str) -> Self {
+        Self {
+            message: Message {
+                sender: sender.to_string(),
+                timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+                content: content.to_string(),
+                source: Some(source_for_service(Some("iMessage")).to_string()),
+                imessage_id: None,
+                service: Some("iMessage".to_string()),
+                is_from_me: false,
+            },
+        }
+    }
+
+    /// Local send time as "YYYY-MM-DD HH:MM:SS"; panics if it isn't valid
+    pub fn at(mut self, local_time: &str) -> Self {
+        let naive = NaiveDateTime::parse_from_str(local_time, "%Y-%m-%d %H:%M:%S")
+            .unwrap_or_else(|_| panic!("Invalid time: {}", local_time));
+        self.message.timestamp = Local.from_local_datetime(&naive).earliest().expect("Time doesn't exist locally");
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: DateTime<Local>) -> Self {
+        self.message.timestamp = timestamp;
+        self
+    }
+
+    pub fn from_me(mut self) -> Self {
+        self.message.is_from_me = true;
+        self
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.message.imessage_id = Some(id.to_string());
+        self
+    }
+
+    /// Service the message was sent over; also sets its source, e.g. "Email" for email
+    pub fn service(mut self, service: &str) -> Self {
+        self.message.source = Some(source_for_service(Some(service)).to_string());
+        self.message.service = Some(service.to_string());
+        self
+    }
+
+    pub fn build(self) -> Message {
+        self.message
+    }
+}
+
+/// Builds a fake `NewMessage` for the database: "message <id>" received from Phil over iMessage
+/// at noon UTC on Jan 20, 2025, with the GUID "guid-<id>"
+pub struct NewMessageBuilder {
+    message: NewMessage,
+}
+
+impl NewMessageBuilder {
+    pub fn new(id: usize) -> Self {
+        Self {
+            message: NewMessage {
+                imessage_id: format!("guid-{}", id),
+                text: Some(format!("message {}", id)),
+                sender: "Phil".to_string(),
+                is_from_me: false,
+                date_created: NaiveDateTime::parse_from_str("2025-01-20 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+                date_imported: None,
+                handle_id: None,
+                service: Some("iMessage".to_string()),
+                thread_id: None,
+                has_attachments: false,
+                reply_to: None,
+                contact_id: None,
+                message_kind: MessageKind::Text,
+            },
+        }
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.message.text = Some(text.to_string());
+        self
+    }
+
+    /// A message with no text, e.g. only an attachment
+    pub fn without_text(mut self) -> Self {
+        self.message.text = None;
+        self
+    }
+
+    pub fn contact(mut self, contact_id: i32) -> Self {
+        self.message.contact_id = Some(contact_id);
+        self
+    }
+
+    /// UTC send time as stored, "YYYY-MM-DD HH:MM:SS"; panics if it isn't valid
+    pub fn at(mut self, utc_time: &str) -> Self {
+        self.message.date_created = NaiveDateTime::parse_from_str(utc_time, "%Y-%m-%d %H:%M:%S")
+            .unwrap_or_else(|_| panic!("Invalid time: {}", utc_time));
+        self
+    }
+
+    pub fn date_created(mut self, date_created: NaiveDateTime) -> Self {
+        self.message.date_created = date_created;
+        self
+    }
+
+    pub fn with_attachments(mut self) -> Self {
+        self.message.has_attachments = true;
+        self
+    }
+
+    pub fn build(self) -> NewMessage {
+        self.message
+    }
+}
+
+/// Builds a fake contact, either for a repository (`build`) or for the database (`build_new`)
+pub struct ContactBuilder {
+    contact: NewContact,
+}
+
+impl ContactBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            contact: NewContact {
+                name: name.to_string(),
+                phone: None,
+                email: None,
+                is_me: false,
+                primary_identifier: None,
+            },
+        }
+    }
+
+    pub fn phone(mut self, phone: &str) -> Self {
+        self.contact.phone = Some(phone.to_string());
+        self
+    }
+
+    pub fn email(mut self, email: &str) -> Self {
+        self.contact.email = Some(email.to_string());
+        self
+    }
+
+    /// Mark the contact as the database owner
+    pub fn me(mut self) -> Self {
+        self.contact.is_me = true;
+        self.contact.primary_identifier = Some(self.contact.name.clone());
+        self
+    }
+
+    pub fn build(self) -> Contact {
+        Contact {
+            name: self.contact.name,
+            phone: self.contact.phone,
+            email: self.contact.email,
+        }
+    }
+
+    pub fn build_new(self) -> NewContact {
+        self.contact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_repository() {
+        let repo: Box<dyn MessageRepository> = Box::new(
+            MockMessageRepository::new().with_conversation(
+                "Phil",
+                vec![
+                    MessageBuilder::new("Phil", "Second").at("2025-01-21 09:00:00").build(),
+                    MessageBuilder::new("Phil", "First").at("2025-01-20 09:00:00").build(),
+                ],
+            ),
+        );
+
+        let phil = ContactBuilder::new("Phil").phone("+15551234567").build();
+        let all = DateRange { start: None, end: None };
+        let messages = repo.fetch_messages(&phil, &all).await.unwrap().messages;
+        assert_eq!(messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), vec!["First", "Second"]);
+
+        let from_21st = DateRange {
+            start: Some(Local.with_ymd_and_hms(2025, 1, 21, 0, 0, 0).unwrap()),
+            end: None,
+        };
+        assert_eq!(repo.fetch_messages(&phil, &from_21st).await.unwrap().messages.len(), 1);
+        assert!(repo.fetch_messages(&ContactBuilder::new("Sherry").build(), &all).await.unwrap().messages.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mock_failures() {
+        let repo = MockMessageRepository::new().failing(MockCall::ExportConversation);
+        let options = ExportOptions::new(std::env::temp_dir().join("mock_failure"));
+
+        assert!(repo.export_conversation_by_person("Phil", &options).await.is_err());
+        let phil = ContactBuilder::new("Phil").build();
+        let all = DateRange { start: None, end: None };
+        assert!(repo.fetch_messages(&phil, &all).await.is_ok());
+        assert_eq!(repo.calls(), vec![MockCall::ExportConversation, MockCall::FetchMessages]);
+    }
+}
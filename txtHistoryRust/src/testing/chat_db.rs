@@ -0,0 +1,415 @@
+//! A miniature chat.db with the tables and columns Messages.app uses, for integration-testing
+//! imports without a real Messages database
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::{params, Connection};
+
+/// The subset of the chat.db schema the importer reads
+const SCHEMA: &str = "
+CREATE TABLE handle (
+    ROWID INTEGER PRIMARY KEY AUTOINCREMENT UNIQUE,
+    id TEXT NOT NULL,
+    country TEXT,
+    service TEXT NOT NULL,
+    uncanonicalized_id TEXT,
+    person_centric_id TEXT,
+    UNIQUE (id, service)
+);
+CREATE TABLE chat (
+    ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+    guid TEXT UNIQUE NOT NULL,
+    style INTEGER,
+    state INTEGER,
+    account_id TEXT,
+    chat_identifier TEXT,
+    service_name TEXT,
+    room_name TEXT,
+    display_name TEXT,
+    group_id TEXT,
+    is_archived INTEGER DEFAULT 0,
+    last_read_message_timestamp INTEGER DEFAULT 0
+);
+CREATE TABLE message (
+    ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+    guid TEXT UNIQUE NOT NULL,
+    text TEXT,
+    replace INTEGER DEFAULT 0,
+    service_center TEXT,
+    handle_id INTEGER DEFAULT 0,
+    subject TEXT,
+    country TEXT,
+    attributedBody BLOB,
+    version INTEGER DEFAULT 0,
+    type INTEGER DEFAULT 0,
+    service TEXT,
+    account TEXT,
+    account_guid TEXT,
+    error INTEGER DEFAULT 0,
+    date INTEGER,
+    date_read INTEGER,
+    date_delivered INTEGER,
+    is_delivered INTEGER DEFAULT 0,
+    is_finished INTEGER DEFAULT 0,
+    is_emote INTEGER DEFAULT 0,
+    is_from_me INTEGER DEFAULT 0,
+    is_empty INTEGER DEFAULT 0,
+    is_delayed INTEGER DEFAULT 0,
+    is_auto_reply INTEGER DEFAULT 0,
+    is_prepared INTEGER DEFAULT 0,
+    is_read INTEGER DEFAULT 0,
+    is_system_message INTEGER DEFAULT 0,
+    is_sent INTEGER DEFAULT 0,
+    has_dd_results INTEGER DEFAULT 0,
+    cache_has_attachments INTEGER DEFAULT 0,
+    cache_roomnames TEXT,
+    item_type INTEGER DEFAULT 0,
+    other_handle INTEGER DEFAULT 0,
+    group_title TEXT,
+    group_action_type INTEGER DEFAULT 0,
+    share_status INTEGER DEFAULT 0,
+    share_direction INTEGER DEFAULT 0,
+    associated_message_guid TEXT,
+    associated_message_type INTEGER DEFAULT 0,
+    associated_message_emoji TEXT,
+    balloon_bundle_id TEXT,
+    payload_data BLOB,
+    expressive_send_style_id TEXT,
+    associated_message_range_location INTEGER DEFAULT 0,
+    associated_message_range_length INTEGER DEFAULT 0,
+    message_summary_info BLOB,
+    destination_caller_id TEXT,
+    thread_originator_guid TEXT,
+    thread_originator_part TEXT,
+    date_edited INTEGER DEFAULT 0
+);
+CREATE TABLE attachment (
+    ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+    guid TEXT UNIQUE NOT NULL,
+    created_date INTEGER DEFAULT 0,
+    filename TEXT,
+    uti TEXT,
+    mime_type TEXT,
+    transfer_state INTEGER DEFAULT 0,
+    is_outgoing INTEGER DEFAULT 0,
+    transfer_name TEXT,
+    total_bytes INTEGER DEFAULT 0,
+    is_sticker INTEGER DEFAULT 0,
+    hide_attachment INTEGER DEFAULT 0,
+    emoji_image_short_description TEXT
+);
+CREATE TABLE chat_handle_join (
+    chat_id INTEGER REFERENCES chat (ROWID) ON DELETE CASCADE,
+    handle_id INTEGER REFERENCES handle (ROWID) ON DELETE CASCADE,
+    UNIQUE (chat_id, handle_id)
+);
+CREATE TABLE chat_message_join (
+    chat_id INTEGER REFERENCES chat (ROWID) ON DELETE CASCADE,
+    message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+    message_date INTEGER DEFAULT 0,
+    PRIMARY KEY (chat_id, message_id)
+);
+CREATE TABLE message_attachment_join (
+    message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+    attachment_id INTEGER REFERENCES attachment (ROWID) ON DELETE CASCADE,
+    UNIQUE (message_id, attachment_id)
+);
+CREATE TABLE chat_recoverable_message_join (
+    chat_id INTEGER REFERENCES chat (ROWID) ON DELETE CASCADE,
+    message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+    delete_date INTEGER,
+    PRIMARY KEY (chat_id, message_id)
+);
+";
+
+/// Reactions long-pressed onto a message, stored as messages of their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tapback {
+    Loved,
+    Liked,
+    Disliked,
+    Laughed,
+    Emphasized,
+    Questioned,
+}
+
+impl Tapback {
+    /// `associated_message_type` Messages.app stores for the reaction
+    fn associated_message_type(self) -> i32 {
+        2000 + self as i32
+    }
+
+    /// Text Messages.app stores alongside the reaction, for clients that don't show tapbacks
+    fn text(self, original: &str) -> String {
+        let verb = match self {
+            Self::Loved => "Loved",
+            Self::Liked => "Liked",
+            Self::Disliked => "Disliked",
+            Self::Laughed => "Laughed at",
+            Self::Emphasized => "Emphasized",
+            Self::Questioned => "Questioned",
+        };
+        format!("{} \u{201c}{}\u{201d}", verb, original)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SyntheticAttachment {
+    filename: String,
+    mime_type: String,
+    total_bytes: i64,
+}
+
+/// One row of the `message` table, by default received over iMessage at noon UTC on Jan 20, 2025
+#[derive(Debug, Clone)]
+pub struct SyntheticMessage {
+    guid: String,
+    handle: String,
+    text: Option<String>,
+    service: String,
+    is_from_me: bool,
+    date: NaiveDateTime,
+    item_type: i32,
+    balloon_bundle_id: Option<String>,
+    tapback: Option<(Tapback, String)>,
+    attachments: Vec<SyntheticAttachment>,
+}
+
+impl SyntheticMessage {
+    /// A message from `handle` (a phone number or email) to me
+    pub fn received(guid: &str, handle: &str, text: &str) -> Self {
+        Self {
+            guid: guid.to_string(),
+            handle: handle.to_string(),
+            text: Some(text.to_string()),
+            service: "iMessage".to_string(),
+            is_from_me: false,
+            date: NaiveDate::from_ymd_opt(2025, 1, 20).unwrap().and_hms_opt(12, 0, 0).unwrap(),
+            item_type: 0,
+            balloon_bundle_id: None,
+            tapback: None,
+            attachments: Vec::new(),
+        }
+    }
+
+    /// A message from me to `handle`
+    pub fn sent(guid: &str, handle: &str, text: &str) -> Self {
+        Self { is_from_me: true, ..Self::received(guid, handle, text) }
+    }
+
+    /// UTC send time as "YYYY-MM-DD HH:MM:SS", so the database is the same in every time zone;
+    /// panics if it isn't valid
+    pub fn at(mut self, utc_time: &str) -> Self {
+        self.date = NaiveDateTime::parse_from_str(utc_time, "%Y-%m-%d %H:%M:%S")
+            .unwrap_or_else(|_| panic!("Invalid time: {}", utc_time));
+        self
+    }
+
+    /// Service the message was sent over, e.g. "SMS". Each service has its own chat.
+    pub fn service(mut self, service: &str) -> Self {
+        self.service = service.to_string();
+        self
+    }
+
+    /// Leave `text` NULL, the way attachment-only messages are stored
+    pub fn without_text(mut self) -> Self {
+        self.text = None;
+        self
+    }
+
+    /// A group event such as a rename rather than a message
+    pub fn item_type(mut self, item_type: i32) -> Self {
+        self.item_type = item_type;
+        self
+    }
+
+    /// Sent from an iMessage app, e.g. "com.apple.messages.URLBalloonProvider"
+    pub fn balloon(mut self, bundle_id: &str) -> Self {
+        self.balloon_bundle_id = Some(bundle_id.to_string());
+        self
+    }
+
+    /// Make this a reaction to the message `original_guid` whose text is `original_text`
+    pub fn tapback(mut self, tapback: Tapback, original_guid: &str, original_text: &str) -> Self {
+        self.text = Some(tapback.text(original_text));
+        self.tapback = Some((tapback, original_guid.to_string()));
+        self
+    }
+
+    /// Attach a file, stored under `~/Library/Messages/Attachments` like real ones
+    pub fn attachment(mut self, filename: &str, mime_type: &str, total_bytes: i64) -> Self {
+        self.attachments.push(SyntheticAttachment {
+            filename: filename.to_string(),
+            mime_type: mime_type.to_string(),
+            total_bytes,
+        });
+        self
+    }
+}
+
+/// Builds a chat.db file with one-to-one chats for every handle and service its messages use.
+/// Rows are inserted in the order they're added, so the same builder always writes the same
+/// database.
+#[derive(Debug, Clone, Default)]
+pub struct SyntheticChatDb {
+    messages: Vec<SyntheticMessage>,
+}
+
+impl SyntheticChatDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_message(mut self, message: SyntheticMessage) -> Self {
+        self.messages.push(message);
+        self
+    }
+
+    /// Write the database to `path`, replacing any file already there
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+
+        let mut conn = Connection::open(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let tx = conn.transaction()?;
+        tx.execute_batch(SCHEMA)?;
+
+        for (i, message) in self.messages.iter().enumerate() {
+            let handle_id = Self::handle(&tx, &message.handle, &message.service)?;
+            let chat_id = Self::chat(&tx, handle_id, &message.handle, &message.service)?;
+            let date = apple_time(message.date);
+            let (associated_message_type, associated_message_guid) = match &message.tapback {
+                Some((tapback, guid)) => (tapback.associated_message_type(), Some(format!("p:0/{}", guid))),
+                None => (0, None),
+            };
+
+            tx.execute(
+                "INSERT INTO message (guid, text, handle_id, service, date, date_delivered, is_from_me, is_sent,
+                     is_delivered, is_finished, is_read, cache_has_attachments, cache_roomnames, item_type,
+                     associated_message_guid, associated_message_type, balloon_bundle_id)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1, 1, 1, ?, ?, ?, ?, ?, ?)",
+                params![
+                    message.guid,
+                    message.text,
+                    handle_id,
+                    message.service,
+                    date,
+                    date,
+                    message.is_from_me,
+                    message.is_from_me,
+                    !message.attachments.is_empty(),
+                    message.handle,
+                    message.item_type,
+                    associated_message_guid,
+                    associated_message_type,
+                    message.balloon_bundle_id,
+                ],
+            )?;
+            let message_id = tx.last_insert_rowid();
+            tx.execute(
+                "INSERT INTO chat_message_join (chat_id, message_id, message_date) VALUES (?, ?, ?)",
+                params![chat_id, message_id, date],
+            )?;
+
+            for (j, attachment) in message.attachments.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO attachment (guid, created_date, filename, mime_type, is_outgoing, transfer_name, total_bytes, transfer_state)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, 5)",
+                    params![
+                        format!("at_{}_{}", i, j),
+                        date / 1_000_000_000,
+                        format!("~/Library/Messages/Attachments/{:02x}/{:02}/{}", i % 256, j, attachment.filename),
+                        attachment.mime_type,
+                        message.is_from_me,
+                        attachment.filename,
+                        attachment.total_bytes,
+                    ],
+                )?;
+                tx.execute(
+                    "INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (?, ?)",
+                    params![message_id, tx.last_insert_rowid()],
+                )?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn handle(conn: &Connection, id: &str, service: &str) -> Result<i64> {
+        conn.execute(
+            "INSERT OR IGNORE INTO handle (id, service, uncanonicalized_id) VALUES (?, ?, ?)",
+            params![id, service, id],
+        )?;
+        Ok(conn.query_row("SELECT ROWID FROM handle WHERE id = ? AND service = ?", params![id, service], |row| {
+            row.get(0)
+        })?)
+    }
+
+    fn chat(conn: &Connection, handle_id: i64, identifier: &str, service: &str) -> Result<i64> {
+        let guid = format!("{};-;{}", service, identifier);
+        conn.execute(
+            "INSERT OR IGNORE INTO chat (guid, style, state, chat_identifier, service_name) VALUES (?, 45, 3, ?, ?)",
+            params![guid, identifier, service],
+        )?;
+        let chat_id: i64 = conn.query_row("SELECT ROWID FROM chat WHERE guid = ?", params![guid], |row| row.get(0))?;
+        conn.execute(
+            "INSERT OR IGNORE INTO chat_handle_join (chat_id, handle_id) VALUES (?, ?)",
+            params![chat_id, handle_id],
+        )?;
+        Ok(chat_id)
+    }
+}
+
+/// chat.db stores dates as nanoseconds since 2001-01-01 UTC
+fn apple_time(date: NaiveDateTime) -> i64 {
+    let epoch = NaiveDate::from_ymd_opt(2001, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    (date - epoch).num_nanoseconds().expect("Date out of range")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_chat_db() {
+        let path = std::env::temp_dir().join(format!("txt_history_synthetic_chat_{}.db", std::process::id()));
+        SyntheticChatDb::new()
+            .with_message(SyntheticMessage::received("m1", "+15551234567", "Hey").at("2025-01-20 09:00:00"))
+            .with_message(SyntheticMessage::sent("m2", "+15551234567", "Hi").service("SMS"))
+            .with_message(SyntheticMessage::sent("m3", "+15551234567", "").tapback(Tapback::Loved, "m1", "Hey"))
+            .with_message(
+                SyntheticMessage::received("m4", "+15551234567", "")
+                    .without_text()
+                    .attachment("IMG_0001.jpeg", "image/jpeg", 2048),
+            )
+            .write(&path)
+            .unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        // One handle and one chat per service
+        assert_eq!(count("SELECT COUNT(*) FROM handle"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM chat"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM chat_message_join"), 4);
+        assert_eq!(count("SELECT COUNT(*) FROM message_attachment_join"), 1);
+
+        let (text, kind, target): (String, i32, String) = conn
+            .query_row(
+                "SELECT text, associated_message_type, associated_message_guid FROM message WHERE guid = 'm3'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((text.as_str(), kind, target.as_str()), ("Loved \u{201c}Hey\u{201d}", 2000, "p:0/m1"));
+
+        // 2025-01-01 is 24 years (6 of them leap years) after the Apple epoch
+        let new_year = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(apple_time(new_year), (24 * 365 + 6) * 86_400 * 1_000_000_000);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Rows written between pauses when no batch size is given
+pub const DEFAULT_PAUSE_EVERY: usize = 500;
+
+/// Limits on how fast imports and NLP processing write, so background runs leave the CPU and
+/// disk to whatever else the machine is doing. Nothing is limited by default.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    /// Most rows written per second
+    pub rows_per_sec: Option<u32>,
+
+    /// Milliseconds to sleep after every `pause_every` rows
+    pub pause_ms: u64,
+
+    /// Rows between pauses (NLP processing pauses after each batch instead)
+    pub pause_every: usize,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self { rows_per_sec: None, pause_ms: 0, pause_every: DEFAULT_PAUSE_EVERY }
+    }
+}
+
+impl ThrottleConfig {
+    /// Build from `--throttle` and `--throttle-pause`
+    pub fn from_flags(rows_per_sec: Option<u32>, pause_ms: Option<u64>) -> Self {
+        Self { rows_per_sec, pause_ms: pause_ms.unwrap_or(0), ..Self::default() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.rows_per_sec.is_some() || self.pause_ms > 0
+    }
+
+    /// Pause after every `rows` rows instead
+    pub fn pausing_every(mut self, rows: usize) -> Self {
+        self.pause_every = rows;
+        self
+    }
+}
+
+/// Tracks rows written during one run and works out how long to sleep to stay within a
+/// `ThrottleConfig`
+#[derive(Debug)]
+pub struct Throttle {
+    config: ThrottleConfig,
+    started: Instant,
+    rows: usize,
+    since_pause: usize,
+}
+
+impl Throttle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Self { config, started: Instant::now(), rows: 0, since_pause: 0 }
+    }
+
+    /// Record `rows` more rows and sleep as long as the limits require
+    pub fn wait(&mut self, rows: usize) {
+        if let Some(delay) = self.delay(rows) {
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// Record `rows` more rows and return how long to sleep, for callers that sleep themselves
+    pub fn delay(&mut self, rows: usize) -> Option<Duration> {
+        if !self.config.is_enabled() {
+            return None;
+        }
+        self.delay_after(rows, self.started.elapsed())
+    }
+
+    fn delay_after(&mut self, rows: usize, elapsed: Duration) -> Option<Duration> {
+        self.rows += rows;
+        self.since_pause += rows;
+
+        // Sleep until the rows so far fit within the rate
+        let mut delay = match self.config.rows_per_sec {
+            Some(rate) if rate > 0 => {
+                Duration::from_secs_f64(self.rows as f64 / f64::from(rate)).saturating_sub(elapsed)
+            },
+            _ => Duration::ZERO,
+        };
+
+        if self.config.pause_ms > 0 && self.since_pause >= self.config.pause_every.max(1) {
+            self.since_pause = 0;
+            delay += Duration::from_millis(self.config.pause_ms);
+            // The pause isn't time spent writing, so don't let the rate catch up on it
+            self.started += Duration::from_millis(self.config.pause_ms);
+        }
+
+        (!delay.is_zero()).then_some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_delays() {
+        let mut unlimited = Throttle::new(ThrottleConfig::default());
+        assert_eq!(unlimited.delay(10_000), None);
+
+        // 100 rows/sec: 50 rows after 0.1s should wait until 0.5s
+        let mut throttle = Throttle::new(ThrottleConfig::from_flags(Some(100), None));
+        assert_eq!(throttle.delay_after(50, Duration::from_millis(100)), Some(Duration::from_millis(400)));
+        assert_eq!(throttle.delay_after(50, Duration::from_secs(2)), None);
+
+        // Pauses come after every batch, on top of the rate
+        let config = ThrottleConfig::from_flags(None, Some(250)).pausing_every(100);
+        let mut throttle = Throttle::new(config);
+        assert_eq!(throttle.delay_after(60, Duration::ZERO), None);
+        assert_eq!(throttle.delay_after(60, Duration::ZERO), Some(Duration::from_millis(250)));
+        assert_eq!(throttle.delay_after(60, Duration::ZERO), None);
+        assert!(config.is_enabled());
+        assert!(!ThrottleConfig::from_flags(None, Some(0)).is_enabled());
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Local, NaiveTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// A daily window of local time, e.g. 22:00-06:00. Windows that end before they start run past
+/// midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HourRange {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl HourRange {
+    /// Parse "HH:MM-HH:MM"; the start is included and the end isn't
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid hours: {}. Use HH:MM-HH:MM, e.g. 22:00-06:00", value);
+        let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+        let parse = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| invalid());
+        let range = Self { start: parse(start)?, end: parse(end)? };
+        if range.start == range.end {
+            return Err(anyhow::anyhow!("Invalid hours: {}. The start and end are the same", value));
+        }
+        Ok(range)
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl fmt::Display for HourRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+/// Which days of the week to keep
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DayFilter {
+    #[default]
+    All,
+    /// Monday to Friday
+    Weekdays,
+    /// Saturday and Sunday
+    Weekends,
+}
+
+/// Keeps messages sent at certain local times of day and days of the week, from `--hours`,
+/// `--weekdays` and `--weekends`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeFilter {
+    pub hours: Option<HourRange>,
+    pub days: DayFilter,
+}
+
+impl TimeFilter {
+    pub fn from_flags(hours: &Option<String>, weekdays: bool, weekends: bool) -> Result<Self> {
+        let days = match (weekdays, weekends) {
+            (true, true) => return Err(anyhow::anyhow!("Use either --weekdays or --weekends, not both")),
+            (true, false) => DayFilter::Weekdays,
+            (false, true) => DayFilter::Weekends,
+            (false, false) => DayFilter::All,
+        };
+        Ok(Self { hours: hours.as_deref().map(HourRange::parse).transpose()?, days })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hours.is_none() && self.days == DayFilter::All
+    }
+
+    pub fn matches(&self, timestamp: &DateTime<Local>) -> bool {
+        let weekend = matches!(timestamp.weekday(), Weekday::Sat | Weekday::Sun);
+        let day_matches = match self.days {
+            DayFilter::All => true,
+            DayFilter::Weekdays => !weekend,
+            DayFilter::Weekends => weekend,
+        };
+        // Seconds are dropped so a message at 05:59:30 is inside 22:00-06:00
+        let time = NaiveTime::from_hms_opt(timestamp.hour(), timestamp.minute(), 0).unwrap_or_default();
+        day_matches && self.hours.map_or(true, |hours| hours.contains(time))
+    }
+
+    /// Description for progress output, e.g. "22:00-06:00 on weekends"
+    pub fn describe(&self) -> String {
+        let hours = self.hours.map(|hours| hours.to_string());
+        match (hours, self.days) {
+            (Some(hours), DayFilter::All) => hours,
+            (Some(hours), DayFilter::Weekdays) => format!("{} on weekdays", hours),
+            (Some(hours), DayFilter::Weekends) => format!("{} on weekends", hours),
+            (None, DayFilter::Weekdays) => "weekdays".to_string(),
+            (None, DayFilter::Weekends) => "weekends".to_string(),
+            (None, DayFilter::All) => "any time".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        // 2025-03-07 is a Friday, 2025-03-08 a Saturday
+        Local.with_ymd_and_hms(2025, 3, day, hour, minute, 30).unwrap()
+    }
+
+    #[test]
+    fn test_time_filter() {
+        let late = TimeFilter::from_flags(&Some("22:00-06:00".to_string()), false, false).unwrap();
+        assert!(late.matches(&at(7, 23, 15)));
+        assert!(late.matches(&at(7, 5, 59)));
+        assert!(late.matches(&at(7, 22, 0)));
+        assert!(!late.matches(&at(7, 6, 0)));
+        assert!(!late.matches(&at(7, 12, 0)));
+
+        let lunch = TimeFilter::from_flags(&Some("12:00-13:30".to_string()), true, false).unwrap();
+        assert!(lunch.matches(&at(7, 12, 45)));
+        assert!(!lunch.matches(&at(8, 12, 45)));
+        assert_eq!(lunch.describe(), "12:00-13:30 on weekdays");
+
+        let weekends = TimeFilter::from_flags(&None, false, true).unwrap();
+        assert!(weekends.matches(&at(8, 9, 0)));
+        assert!(!weekends.matches(&at(7, 9, 0)));
+
+        assert!(TimeFilter::from_flags(&None, false, false).unwrap().is_empty());
+        assert!(TimeFilter::from_flags(&Some("22:00".to_string()), false, false).is_err());
+        assert!(TimeFilter::from_flags(&Some("25:00-06:00".to_string()), false, false).is_err());
+        assert!(TimeFilter::from_flags(&Some("06:00-06:00".to_string()), false, false).is_err());
+        assert!(TimeFilter::from_flags(&None, true, true).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::Message;
+
+/// Reading speed used for reading time estimates
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// Size of a set of exported messages, for deciding how to split material for a reviewer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportTotals {
+    pub messages: usize,
+    /// Whitespace-separated words in the message text
+    pub words: usize,
+    /// Characters (not bytes) in the message text
+    pub characters: usize,
+    /// Estimated reading time at `WORDS_PER_MINUTE`, rounded up
+    pub reading_minutes: usize,
+}
+
+impl ExportTotals {
+    pub fn from_messages(messages: &[Message]) -> Self {
+        let words: usize = messages.iter().map(|m| m.content.split_whitespace().count()).sum();
+        Self {
+            messages: messages.len(),
+            words,
+            characters: messages.iter().map(|m| m.content.chars().count()).sum(),
+            reading_minutes: words.div_ceil(WORDS_PER_MINUTE),
+        }
+    }
+
+    /// One-line description, e.g. "120 messages, 1540 words, 8210 characters, about 8 min to read"
+    pub fn summary(&self) -> String {
+        format!(
+            "{} messages, {} words, {} characters, about {} min to read",
+            self.messages, self.words, self.characters, self.reading_minutes
+        )
+    }
+
+    /// Line put at the end of TXT files when `txt_totals` is on
+    pub fn txt_footer(&self) -> String {
+        format!("-- {} --\n", self.summary())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(content: &str) -> Message {
+        Message {
+            sender: "Phil".to_string(),
+            timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, 0, 0).unwrap(),
+            content: content.to_string(),
+            source: None,
+            imessage_id: None,
+            service: None,
+            is_from_me: false,
+        }
+    }
+
+    #[test]
+    fn test_totals() {
+        let totals = ExportTotals::from_messages(&[message("See you  at 3"), message("café")]);
+        assert_eq!(totals.messages, 2);
+        assert_eq!(totals.words, 5);
+        assert_eq!(totals.characters, 17);
+        assert_eq!(totals.reading_minutes, 1);
+        assert_eq!(ExportTotals::from_messages(&[]).reading_minutes, 0);
+
+        let long = ExportTotals::from_messages(&[message(&"word ".repeat(201))]);
+        assert_eq!(long.reading_minutes, 2);
+
+        assert_eq!(
+            totals.txt_footer(),
+            "-- 2 messages, 5 words, 17 characters, about 1 min to read --\n"
+        );
+    }
+}
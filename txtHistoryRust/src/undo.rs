@@ -0,0 +1,267 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::{Connection, Params};
+use serde::{Deserialize, Serialize};
+
+/// Extension of journals that haven't been undone yet
+const JOURNAL_EXTENSION: &str = "json";
+
+/// Extension a journal is renamed to once it has been undone
+const UNDONE_EXTENSION: &str = "undone";
+
+/// Extension of journals whose change hasn't been committed yet
+const PENDING_EXTENSION: &str = "pending";
+
+/// One SQLite value, kept with its storage class so it's restored exactly
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JournalValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl From<Value> for JournalValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Integer(i) => Self::Integer(i),
+            Value::Real(f) => Self::Real(f),
+            Value::Text(s) => Self::Text(s),
+            Value::Blob(b) => Self::Blob(b),
+        }
+    }
+}
+
+impl From<JournalValue> for Value {
+    fn from(value: JournalValue) -> Self {
+        match value {
+            JournalValue::Null => Value::Null,
+            JournalValue::Integer(i) => Value::Integer(i),
+            JournalValue::Real(f) => Value::Real(f),
+            JournalValue::Text(s) => Value::Text(s),
+            JournalValue::Blob(b) => Value::Blob(b),
+        }
+    }
+}
+
+/// Rows of one table as they were before a destructive command changed or deleted them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalTable {
+    pub table: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<JournalValue>>,
+}
+
+impl JournalTable {
+    /// Copy every row of `table` matching `condition`
+    pub fn capture(conn: &Connection, table: &str, condition: &str, params: impl Params) -> Result<Self> {
+        let mut stmt = conn.prepare(&format!("SELECT * FROM {} WHERE {}", table, condition))?;
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+
+        let rows = stmt
+            .query_map(params, |row| {
+                (0..columns.len())
+                    .map(|i| row.get::<_, Value>(i).map(JournalValue::from))
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Self { table: table.to_string(), columns, rows })
+    }
+
+    /// Put the rows back: rows that still exist get their old values, deleted rows are
+    /// inserted again with their old ids
+    fn restore(&self, conn: &Connection) -> Result<usize> {
+        let id = self
+            .columns
+            .iter()
+            .position(|c| c == "id")
+            .ok_or_else(|| anyhow::anyhow!("Journal for {} has no id column", self.table))?;
+
+        let assignments: Vec<String> = self.columns.iter().map(|c| format!("{} = ?", c)).collect();
+        let update = format!("UPDATE {} SET {} WHERE id = ?", self.table, assignments.join(", "));
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            self.table,
+            self.columns.join(", "),
+            vec!["?"; self.columns.len()].join(", ")
+        );
+
+        for row in &self.rows {
+            let mut values: Vec<Value> = row.iter().cloned().map(Value::from).collect();
+            values.push(row[id].clone().into());
+
+            let updated = conn.execute(&update, rusqlite::params_from_iter(values.iter()))?;
+            if updated == 0 {
+                values.pop();
+                conn.execute(&insert, rusqlite::params_from_iter(values.iter()))
+                    .with_context(|| format!("Failed to restore a row of {}", self.table))?;
+            }
+        }
+
+        Ok(self.rows.len())
+    }
+}
+
+/// Everything a destructive command changed, written before the change is committed so
+/// `undo --last` can put it back. It only counts as written once the change is committed (see
+/// `PendingJournal`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoJournal {
+    /// Command that wrote the journal, e.g. "purge" or "contacts merge"
+    pub operation: String,
+    /// What the command did, in the words it printed
+    pub description: String,
+    /// When the command ran
+    pub created_at: DateTime<Utc>,
+    /// Parent tables come before the tables referencing them, the order rows are restored in
+    pub tables: Vec<JournalTable>,
+}
+
+impl UndoJournal {
+    pub fn new(operation: &str, description: &str) -> Self {
+        Self {
+            operation: operation.to_string(),
+            description: description.to_string(),
+            created_at: Utc::now(),
+            tables: Vec::new(),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.tables.iter().map(|t| t.rows.len()).sum()
+    }
+
+    /// Write the journal to a new file in `dir`, named so the newest sorts last. `list` skips it
+    /// until it's committed.
+    pub fn write(&self, dir: &Path) -> Result<PendingJournal> {
+        fs::create_dir_all(dir)?;
+        let slug = self.operation.replace(' ', "_");
+        let path = dir.join(format!("{}_{}.{}", Utc::now().format("%Y%m%dT%H%M%S%.6f"), slug, PENDING_EXTENSION));
+        fs::write(&path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("Failed to write undo journal {}", path.display()))?;
+        Ok(PendingJournal { path, committed: false })
+    }
+
+    /// Journals in `dir` that haven't been undone, oldest first
+    pub fn list(dir: &Path) -> Result<Vec<(PathBuf, Self)>> {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == JOURNAL_EXTENSION))
+                .collect(),
+            Err(_) => return Ok(Vec::new()),
+        };
+        paths.sort();
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let contents = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+                let journal = serde_json::from_slice(&contents)
+                    .with_context(|| format!("Invalid undo journal {}", path.display()))?;
+                Ok((path, journal))
+            })
+            .collect()
+    }
+
+    /// Restore every row in one transaction, then mark the journal at `path` as undone so the
+    /// next `undo --last` goes one step further back
+    pub fn undo(&self, conn: &mut Connection, path: &Path) -> Result<usize> {
+        let tx = conn.transaction()?;
+        let mut restored = 0;
+        for table in &self.tables {
+            restored += table.restore(&tx)?;
+        }
+        tx.commit()?;
+
+        fs::rename(path, path.with_extension(UNDONE_EXTENSION))
+            .with_context(|| format!("Restored, but failed to mark {} as undone", path.display()))?;
+        Ok(restored)
+    }
+}
+
+/// A journal written before its transaction commits. `commit` it after the transaction does;
+/// dropping it uncommitted, e.g. when the transaction fails, deletes the file so `undo` never
+/// offers to restore rows that were never removed.
+#[derive(Debug)]
+pub struct PendingJournal {
+    path: PathBuf,
+    committed: bool,
+}
+
+impl PendingJournal {
+    /// Make the journal visible to `undo`, returning its path
+    pub fn commit(mut self) -> Result<PathBuf> {
+        // The change is committed either way, so keep the file even if it can't be renamed
+        self.committed = true;
+        let path = self.path.with_extension(JOURNAL_EXTENSION);
+        fs::rename(&self.path, &path)
+            .with_context(|| format!("Committed, but failed to rename undo journal {}", self.path.display()))?;
+        Ok(path)
+    }
+}
+
+impl Drop for PendingJournal {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_and_restore() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT NOT NULL, score REAL, photo BLOB);
+             INSERT INTO people VALUES (1, 'Phil', 0.5, x'00ff'), (2, 'Jess', NULL, NULL), (3, 'Robert', 2.0, NULL);",
+        )
+        .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("txt_history_undo_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut journal = UndoJournal::new("contacts merge", "Merged Robert into Phil");
+        journal.tables.push(JournalTable::capture(&conn, "people", "id IN (1, 3)", []).unwrap());
+        let pending = journal.write(&dir).unwrap();
+        // Not offered until the change it records is committed, and gone if it never is
+        assert!(UndoJournal::list(&dir).unwrap().is_empty());
+        drop(UndoJournal::new("purge", "Purged messages sent before 2020-01-01").write(&dir).unwrap());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let path = pending.commit().unwrap();
+        conn.execute_batch("UPDATE people SET name = 'Phil G', score = 1 WHERE id = 1; DELETE FROM people WHERE id = 3;")
+            .unwrap();
+
+        let journals = UndoJournal::list(&dir).unwrap();
+        assert_eq!(journals.len(), 1);
+        assert_eq!(journals[0].1, journal);
+        assert_eq!(journals[0].1.rows(), 2);
+
+        assert_eq!(journal.undo(&mut conn, &path).unwrap(), 2);
+        let rows: Vec<(i64, String, Option<f64>, Option<Vec<u8>>)> = conn
+            .prepare("SELECT * FROM people ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows[0], (1, "Phil".to_string(), Some(0.5), Some(vec![0, 255])));
+        assert_eq!(rows[2], (3, "Robert".to_string(), Some(2.0), None));
+
+        // Undone journals aren't offered again
+        assert!(UndoJournal::list(&dir).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
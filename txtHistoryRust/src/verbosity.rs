@@ -0,0 +1,64 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much an export prints, from `-q` and `-v`. Set once at startup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only the final summary line
+    Quiet,
+    #[default]
+    Normal,
+    /// Also each chunk's date range and how long it took to write
+    Verbose,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+impl Verbosity {
+    pub fn from_flags(verbose: bool, quiet: bool) -> Self {
+        match (verbose, quiet) {
+            (_, true) => Self::Quiet,
+            (true, false) => Self::Verbose,
+            (false, false) => Self::Normal,
+        }
+    }
+
+    pub fn set(self) {
+        LEVEL.store(self as u8, Ordering::Relaxed);
+    }
+
+    pub fn current() -> Self {
+        match LEVEL.load(Ordering::Relaxed) {
+            0 => Self::Quiet,
+            2 => Self::Verbose,
+            _ => Self::Normal,
+        }
+    }
+}
+
+/// Print a progress line, unless running with `-q`
+pub fn progress(line: impl Display) {
+    if Verbosity::current() > Verbosity::Quiet {
+        println!("{}", line);
+    }
+}
+
+/// Print a line only with `-v`
+pub fn detail(line: impl Display) {
+    if Verbosity::current() == Verbosity::Verbose {
+        println!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_flags() {
+        assert_eq!(Verbosity::from_flags(false, false), Verbosity::Normal);
+        assert_eq!(Verbosity::from_flags(true, false), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(false, true), Verbosity::Quiet);
+        assert!(Verbosity::Quiet < Verbosity::Normal && Verbosity::Normal < Verbosity::Verbose);
+    }
+}
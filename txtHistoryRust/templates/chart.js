@@ -0,0 +1,78 @@
+// Minimal SVG charts for the dashboard, so it works offline without a CDN.
+// chart(container, { type: "line" | "bar", labels, series: [{ name, color, values }], unit })
+// Missing values (null) leave a gap in lines and no bar.
+(function () {
+  var NS = "http://www.w3.org/2000/svg";
+  var WIDTH = 900, HEIGHT = 260, LEFT = 50, RIGHT = 10, TOP = 10, BOTTOM = 40;
+
+  function el(name, attrs, parent) {
+    var node = document.createElementNS(NS, name);
+    for (var key in attrs) node.setAttribute(key, attrs[key]);
+    if (parent) parent.appendChild(node);
+    return node;
+  }
+
+  function niceRange(min, max) {
+    if (min === max) { min -= 1; max += 1; }
+    var step = Math.pow(10, Math.floor(Math.log10((max - min) / 4)));
+    [1, 2, 5, 10].some(function (m) { if ((max - min) / (step * m) <= 5) { step *= m; return true; } });
+    return { min: Math.floor(min / step) * step, max: Math.ceil(max / step) * step, step: step };
+  }
+
+  window.chart = function (container, options) {
+    var values = [];
+    options.series.forEach(function (s) { s.values.forEach(function (v) { if (v !== null) values.push(v); }); });
+    if (values.length === 0) {
+      container.textContent = "No data";
+      return;
+    }
+
+    var min = Math.min.apply(null, values), max = Math.max.apply(null, values);
+    if (options.type === "bar") min = Math.min(0, min);
+    var range = niceRange(min, max);
+    var count = options.labels.length;
+    var plotWidth = WIDTH - LEFT - RIGHT, plotHeight = HEIGHT - TOP - BOTTOM;
+    var x = function (i) { return LEFT + (count === 1 ? plotWidth / 2 : (i + 0.5) * plotWidth / count); };
+    var y = function (v) { return TOP + plotHeight - (v - range.min) / (range.max - range.min) * plotHeight; };
+
+    var svg = el("svg", { viewBox: "0 0 " + WIDTH + " " + HEIGHT, class: "chart" }, container);
+    for (var tick = range.min; tick <= range.max + range.step / 2; tick += range.step) {
+      el("line", { x1: LEFT, x2: WIDTH - RIGHT, y1: y(tick), y2: y(tick), class: "grid" }, svg);
+      el("text", { x: LEFT - 6, y: y(tick) + 4, "text-anchor": "end" }, svg).textContent = +tick.toFixed(2);
+    }
+
+    var every = Math.max(1, Math.ceil(count / 12));
+    options.labels.forEach(function (label, i) {
+      if (i % every === 0) el("text", { x: x(i), y: HEIGHT - BOTTOM + 16, "text-anchor": "middle" }, svg).textContent = label;
+    });
+
+    var barWidth = plotWidth / count / (options.series.length + 1);
+    options.series.forEach(function (s, n) {
+      if (options.type === "bar") {
+        s.values.forEach(function (v, i) {
+          if (v === null) return;
+          var left = x(i) - barWidth * options.series.length / 2 + n * barWidth;
+          var top = Math.min(y(v), y(Math.max(0, range.min)));
+          var rect = el("rect", { x: left, y: top, width: Math.max(barWidth, 1), height: Math.abs(y(v) - y(Math.max(0, range.min))), fill: s.color }, svg);
+          el("title", {}, rect).textContent = options.labels[i] + " " + s.name + ": " + v + (options.unit || "");
+        });
+        return;
+      }
+
+      var path = "";
+      s.values.forEach(function (v, i) {
+        if (v === null) return;
+        path += (path && s.values[i - 1] !== null ? "L" : "M") + x(i) + "," + y(v);
+        var dot = el("circle", { cx: x(i), cy: y(v), r: 2.5, fill: s.color }, svg);
+        el("title", {}, dot).textContent = options.labels[i] + " " + s.name + ": " + v + (options.unit || "");
+      });
+      el("path", { d: path, stroke: s.color, fill: "none", "stroke-width": 2 }, svg);
+    });
+
+    var legend = el("g", {}, svg);
+    options.series.forEach(function (s, n) {
+      el("rect", { x: LEFT + n * 160, y: HEIGHT - 14, width: 10, height: 10, fill: s.color }, legend);
+      el("text", { x: LEFT + n * 160 + 14, y: HEIGHT - 5 }, legend).textContent = s.name;
+    });
+  };
+})();
@@ -0,0 +1,106 @@
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::attachments::attachments_csv;
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{NewAttachment, NewContact};
+use txtHistoryRust::testing::NewMessageBuilder;
+
+#[test]
+fn test_list_attachments() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = db
+        .add_or_update_contact(NewContact {
+            name: "Phil".to_string(),
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact");
+
+    let files = [
+        (10, Some("Look at this"), "IMG_0001.HEIC", "image/heic", 2_400_000),
+        (12, None, "clip.mov", "video/quicktime", 18_000_000),
+        (20, Some("Report card"), "report_card.png", "image/png", 350_000),
+    ];
+    for (i, (day, text, filename, mime_type, size)) in files.into_iter().enumerate() {
+        let message = NewMessageBuilder::new(i).contact(contact.id).at(&format!("2025-01-{:02} 12:00:00", day));
+        let message = match text {
+            Some(text) => message.text(text),
+            None => message.without_text(),
+        };
+        let saved = db.add_message(message.with_attachments().build()).expect("Failed to add message");
+        db.add_attachment(NewAttachment {
+            message_id: saved.id,
+            filename: Some(format!("~/Library/Messages/Attachments/{}", filename)),
+            mime_type: Some(mime_type.to_string()),
+            size_bytes: Some(size),
+            created_at: saved.date_created,
+        })
+        .expect("Failed to add attachment");
+    }
+
+    let all = db.get_attachment_report(Some(contact.id), None, None, None).unwrap();
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[1].mime_type.as_deref(), Some("video/quicktime"));
+
+    // Photos from the first half of the month
+    let end = NaiveDateTime::parse_from_str("2025-01-15 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let photos = db.get_attachment_report(Some(contact.id), None, Some(end), Some("image/")).unwrap();
+    assert_eq!(photos.len(), 1);
+    assert_eq!(photos[0].text.as_deref(), Some("Look at this"));
+    assert_eq!(photos[0].size_bytes, Some(2_400_000));
+
+    // LIKE wildcards in the prefix are matched literally
+    assert!(db.get_attachment_report(None, None, None, Some("%")).unwrap().is_empty());
+
+    let csv = attachments_csv(&all).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "date,sender,type,size_bytes,filename,message_text,message_id");
+    assert!(lines[2].contains(",Phil,video/quicktime,18000000,~/Library/Messages/Attachments/clip.mov,,"));
+}
+
+#[test]
+fn test_messages_with_transcripts() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = db
+        .add_or_update_contact(NewContact {
+            name: "Phil".to_string(),
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact");
+
+    let memo = db.add_message(NewMessageBuilder::new(0).text("Listen").contact(contact.id).with_attachments().build()).unwrap();
+    let attachment = db
+        .add_attachment(NewAttachment {
+            message_id: memo.id,
+            filename: Some("~/Library/Messages/Attachments/memo.caf".to_string()),
+            mime_type: Some("audio/x-caf".to_string()),
+            size_bytes: Some(48_000),
+            created_at: memo.date_created,
+        })
+        .unwrap();
+    db.add_attachment_transcript(&attachment, "whisper", "Pick up at five").unwrap();
+    let plain = db.add_message(NewMessageBuilder::new(1).text("ok").contact(contact.id).at("2025-01-21 12:00:00").build()).unwrap();
+
+    // Every export path converts messages the same way, transcripts included
+    let messages = db.to_messages_with_transcripts(&[memo, plain]).unwrap();
+    assert_eq!(messages[0].content, "Listen [Transcript: Pick up at five]");
+    assert_eq!(messages[1].content, "ok");
+}
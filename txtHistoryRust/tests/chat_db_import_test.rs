@@ -0,0 +1,108 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::chat_db::RetryPolicy;
+use txtHistoryRust::discovery::{self, ChatDbMessage};
+use txtHistoryRust::models::MessageKind;
+use txtHistoryRust::testing::chat_db::{SyntheticChatDb, SyntheticMessage, Tapback};
+
+const PHIL: &str = "+15551234567";
+
+/// A short conversation with Phil covering a tapback, an attachment, an SMS fallback and a
+/// link preview
+fn fixture() -> SyntheticChatDb {
+    SyntheticChatDb::new()
+        .with_message(SyntheticMessage::received("m1", PHIL, "Are you picking up Everly?").at("2025-01-20 14:26:27"))
+        .with_message(SyntheticMessage::sent("m2", PHIL, "Yes, leaving now").at("2025-01-20 14:27:00"))
+        .with_message(
+            SyntheticMessage::received("m3", PHIL, "")
+                .tapback(Tapback::Loved, "m2", "Yes, leaving now")
+                .at("2025-01-20 14:27:30"),
+        )
+        .with_message(
+            SyntheticMessage::received("m4", PHIL, "Look at this")
+                .attachment("IMG_0001.jpeg", "image/jpeg", 2048)
+                .at("2025-01-20 15:00:00"),
+        )
+        // Attachment-only messages without a text have nothing to export
+        .with_message(
+            SyntheticMessage::received("m5", PHIL, "")
+                .without_text()
+                .attachment("IMG_0002.jpeg", "image/jpeg", 4096)
+                .at("2025-01-20 15:00:05"),
+        )
+        .with_message(
+            SyntheticMessage::sent("m6", PHIL, "Running late, no data")
+                .service("SMS")
+                .at("2025-01-21 08:00:00"),
+        )
+        .with_message(
+            SyntheticMessage::received("m7", PHIL, "https://example.com")
+                .balloon("com.apple.messages.URLBalloonProvider")
+                .at("2025-01-21 08:05:00"),
+        )
+}
+
+/// Compare `actual` with `tests/snapshots/{name}.txt`, or rewrite the snapshot when
+/// `UPDATE_SNAPSHOTS` is set
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).expect("Failed to update snapshot");
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).expect("Missing snapshot, run with UPDATE_SNAPSHOTS=1");
+    assert_eq!(actual, expected, "{} doesn't match the snapshot", name);
+}
+
+/// One line per message with its UTC time, so the snapshot is the same in every time zone
+fn render(messages: &[ChatDbMessage]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            format!(
+                "{} {} {}: {}\n",
+                m.date.format("%Y-%m-%d %H:%M:%S"),
+                m.service.as_deref().unwrap_or("-"),
+                m.sender_handle.as_deref().unwrap_or("Me"),
+                m.kind.render(m.text.as_deref())
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_read_synthetic_chat_db() {
+    // Create a temporary chat.db for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let chat_db_path = temp_dir.path().join("chat.db");
+    fixture().write(&chat_db_path).expect("Failed to write chat.db");
+    let conn = discovery::open_read_only(&chat_db_path, &RetryPolicy::default()).expect("Failed to open chat.db");
+
+    // The iMessage and SMS chats with Phil share a chat identifier and are read together
+    let chats = discovery::discover_chats(&conn, Some(PHIL)).unwrap();
+    assert_eq!(chats.len(), 2);
+    assert!(chats.iter().all(|chat| chat.participants == vec![PHIL.to_string()]));
+
+    let messages = discovery::read_chat_messages(&conn, PHIL, None, None).expect("Failed to read chat.db");
+    assert_snapshot("chat_db_import", &render(&messages));
+
+    // The attachment-only message has no text to export, while the captioned one keeps its
+    // attachment flag and the link preview stays plain text
+    assert!(messages.iter().all(|m| m.guid != "m5"));
+    let by_guid = |guid: &str| messages.iter().find(|m| m.guid == guid).unwrap();
+    assert!(by_guid("m4").has_attachments);
+    assert!(!by_guid("m1").has_attachments);
+    assert_eq!(by_guid("m7").kind, MessageKind::Text);
+
+    // Only messages on or after the start of the range are read
+    let start = NaiveDate::from_ymd_opt(2025, 1, 21).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let later = discovery::read_chat_messages(&conn, PHIL, Some(start), None).unwrap();
+    let guids: Vec<&str> = later.iter().map(|m| m.guid.as_str()).collect();
+    assert_eq!(guids, vec!["m6", "m7"]);
+}
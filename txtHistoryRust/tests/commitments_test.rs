@@ -0,0 +1,75 @@
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::commitments::CommitmentExtractor;
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{CommitmentKind, MessageKind, NewContact, NewMessage};
+
+fn message(id: usize, contact_id: i32, is_from_me: bool, hour: u32, text: &str) -> NewMessage {
+    NewMessage {
+        imessage_id: format!("guid-{}", id),
+        text: Some(text.to_string()),
+        sender: if is_from_me { "Jess" } else { "Phil" }.to_string(),
+        is_from_me,
+        date_created: NaiveDateTime::parse_from_str(&format!("2025-01-20 {:02}:00:00", hour), "%Y-%m-%d %H:%M:%S")
+            .unwrap(),
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: Some(contact_id),
+        message_kind: MessageKind::Text,
+    }
+}
+
+#[test]
+fn test_save_and_report_commitments() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = db
+        .add_or_update_contact(NewContact {
+            name: "Phil".to_string(),
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact");
+
+    let texts = [
+        (false, 9, "Can you pick her up Friday?"),
+        (true, 10, "Sure, I'll get her at 3."),
+        (false, 11, "Did you pay the dentist?"),
+    ];
+    for (i, (is_from_me, hour, text)) in texts.into_iter().enumerate() {
+        db.add_message(message(i, contact.id, is_from_me, hour, text))
+            .expect("Failed to add message");
+    }
+
+    let messages = db
+        .get_messages_for_contact_id(contact.id, None, None)
+        .expect("Failed to get messages");
+    let commitments = CommitmentExtractor::default().extract(&messages);
+    assert_eq!(db.save_commitments(&commitments).unwrap(), 4);
+
+    // Saving again keeps one row per detected sentence
+    db.save_commitments(&commitments).unwrap();
+    let report = db.get_commitment_report(Some(contact.id), None, None).unwrap();
+    assert_eq!(report.len(), 4);
+
+    assert_eq!(report[0].kind, CommitmentKind::Question);
+    assert_eq!(report[0].answer.as_deref(), Some("Sure, I'll get her at 3."));
+    assert!(report.iter().any(|e| e.kind == CommitmentKind::Commitment && e.sender == "Jess"));
+
+    let open: Vec<_> = report.iter().filter(|e| e.is_open()).collect();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].snippet, "Did you pay the dentist?");
+}
@@ -1,11 +1,12 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use std::path::Path;
 use std::fs;
 use tempfile::tempdir;
 
 // Import the necessary modules from the crate
+use txtHistoryRust::config::MeConfig;
 use txtHistoryRust::db::Database;
-use txtHistoryRust::models::{NewContact, DbContact, NewMessage, DbMessage};
+use txtHistoryRust::models::{ContactDetails, NewContact, DbContact, NewMessage, DbMessage, MessageKind};
 
 #[test]
 fn test_add_or_update_contact() {
@@ -78,7 +79,7 @@ fn test_get_conversation_with_person() {
     };
     
     let person = db.add_or_update_contact(person_contact).expect("Failed to add person contact");
-    let me = db.add_or_update_contact(me_contact).expect("Failed to add me contact");
+    db.add_or_update_contact(me_contact).expect("Failed to add me contact");
     
     // Create test messages
     let timestamp1 = NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
@@ -96,7 +97,9 @@ fn test_get_conversation_with_person() {
         service: Some("iMessage".to_string()),
         thread_id: None,
         has_attachments: false,
-        contact_id: Some(me.id),
+        reply_to: None,
+        contact_id: Some(person.id),
+        message_kind: MessageKind::Text,
     };
     
     // Message from me to person
@@ -110,7 +113,9 @@ fn test_get_conversation_with_person() {
         service: Some("iMessage".to_string()),
         thread_id: None,
         has_attachments: false,
+        reply_to: None,
         contact_id: Some(person.id),
+        message_kind: MessageKind::Text,
     };
     
     // Another message from person to me
@@ -124,7 +129,9 @@ fn test_get_conversation_with_person() {
         service: Some("iMessage".to_string()),
         thread_id: None,
         has_attachments: false,
-        contact_id: Some(me.id),
+        reply_to: None,
+        contact_id: Some(person.id),
+        message_kind: MessageKind::Text,
     };
     
     db.add_message(message1).expect("Failed to add message 1");
@@ -156,3 +163,244 @@ fn test_get_conversation_with_person() {
     assert_eq!(filtered_conversation[0].text, Some("Hello from me".to_string()));
     assert_eq!(filtered_conversation[1].text, Some("How are you?".to_string()));
 }
+
+#[test]
+fn test_conversation_links_by_contact_id() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+    
+    let db = Database::new(&db_url).expect("Failed to create database");
+    
+    let person = db.add_or_update_contact(NewContact {
+        name: "Test Person".to_string(),
+        phone: Some("+15551234567".to_string()),
+        email: None,
+        is_me: false,
+        primary_identifier: None,
+    }).expect("Failed to add person contact");
+    
+    let timestamp = NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    
+    // Sender text that doesn't match the contact name, e.g. a raw handle
+    let message = NewMessage {
+        imessage_id: "handle1".to_string(),
+        text: Some("Sent from a raw handle".to_string()),
+        sender: "+15551234567".to_string(),
+        is_from_me: false,
+        date_created: timestamp,
+        handle_id: None,
+        service: Some("SMS".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: Some(person.id),
+        message_kind: MessageKind::Text,
+    };
+    db.add_message(message).expect("Failed to add message");
+    
+    let conversation = db.get_conversation_with_person("Test Person", None, None)
+        .expect("Failed to get conversation");
+    assert_eq!(conversation.len(), 1);
+    assert_eq!(conversation[0].contact_id, Some(person.id));
+    
+    // Unknown contacts are rejected by the foreign key
+    let dangling = NewMessage {
+        imessage_id: "dangling".to_string(),
+        text: None,
+        sender: "Nobody".to_string(),
+        is_from_me: false,
+        date_created: timestamp,
+        handle_id: None,
+        service: None,
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: Some(person.id + 1000),
+        message_kind: MessageKind::Text,
+    };
+    assert!(db.add_message(dangling).is_err());
+}
+
+#[test]
+fn test_merge_contacts() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let into = db.add_or_update_contact(NewContact {
+        name: "Philip".to_string(),
+        phone: Some("+15550000001".to_string()),
+        email: None,
+        is_me: false,
+        primary_identifier: None,
+    }).expect("Failed to add contact");
+    let from = db.add_or_update_contact(NewContact {
+        name: "Phil G".to_string(),
+        phone: Some("+15550000002".to_string()),
+        email: Some("phil@example.com".to_string()),
+        is_me: false,
+        primary_identifier: None,
+    }).expect("Failed to add duplicate contact");
+
+    let timestamp = NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    for (i, (sender, is_from_me)) in [("Phil G", false), ("Phil G", false), ("Jess", true)].into_iter().enumerate() {
+        db.add_message(NewMessage {
+            imessage_id: format!("merge-{}", i),
+            text: Some(format!("message {}", i)),
+            sender: sender.to_string(),
+            is_from_me,
+            date_created: timestamp,
+            date_imported: None,
+            handle_id: None,
+            service: Some("iMessage".to_string()),
+            thread_id: None,
+            has_attachments: false,
+            reply_to: None,
+            contact_id: Some(from.id),
+            message_kind: MessageKind::Text,
+        }).expect("Failed to add message");
+    }
+
+    // A dry run reports the changes without making them
+    let preview = db.merge_contacts(&into, &from, true).expect("Failed to preview merge");
+    assert_eq!(preview.messages, 3);
+    assert_eq!(preview.renamed_senders, 2);
+    assert_eq!(
+        preview.identifiers,
+        vec![("primary_identifier", "+15550000002".to_string()), ("email", "phil@example.com".to_string())]
+    );
+    assert!(preview.dropped_identifiers.is_empty());
+    assert!(db.get_contact("Phil G").unwrap().is_some());
+    assert_eq!(db.get_messages_for_contact_id(from.id, None, None).unwrap().len(), 3);
+
+    let merged = db.merge_contacts(&into, &from, false).expect("Failed to merge contacts");
+    assert_eq!(merged, preview);
+    assert!(db.get_contact("Phil G").unwrap().is_none());
+    assert_eq!(db.find_contact_by_identifier("+15550000002").unwrap().unwrap().id, into.id);
+    assert_eq!(db.get_contact("Philip").unwrap().unwrap().email, Some("phil@example.com".to_string()));
+
+    let messages = db.get_messages_for_contact_id(into.id, None, None).unwrap();
+    assert_eq!(messages.len(), 3);
+    assert!(messages.iter().all(|m| m.sender == if m.is_from_me { "Jess" } else { "Philip" }));
+
+    assert!(db.merge_contacts(&into, &into, false).is_err());
+}
+
+#[test]
+fn test_update_contact_details() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = db.add_or_update_contact(NewContact {
+        name: "Phil".to_string(),
+        phone: Some("+15551234567".to_string()),
+        email: None,
+        is_me: false,
+        primary_identifier: None,
+    }).expect("Failed to add contact");
+    assert_eq!(contact.details, ContactDetails::default());
+
+    let details = ContactDetails {
+        relationship: Some("co-parent".to_string()),
+        timezone: Some("America/Denver".to_string()),
+        birthday: NaiveDate::from_ymd_opt(1985, 6, 14),
+        notes: Some("Prefers texts over calls".to_string()),
+    };
+    db.update_contact_details(contact.id, &details).expect("Failed to update contact details");
+    assert_eq!(db.get_contact("Phil").unwrap().unwrap().details, details);
+
+    // Updating the contact's identifiers keeps its details
+    let updated = db.add_or_update_contact(NewContact {
+        name: "Phil".to_string(),
+        phone: None,
+        email: Some("phil@example.com".to_string()),
+        is_me: false,
+        primary_identifier: None,
+    }).expect("Failed to update contact");
+    assert_eq!(updated.details, details);
+
+    db.update_contact_details(contact.id, &ContactDetails::default()).unwrap();
+    assert_eq!(db.get_contact("Phil").unwrap().unwrap().details, ContactDetails::default());
+    assert!(db.update_contact_details(contact.id + 100, &details).is_err());
+}
+
+#[test]
+fn test_sync_me_renames_sent_messages() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+    db.initialize().expect("Failed to initialize database");
+    assert!(db.get_me_contact().unwrap().is_none());
+    assert_eq!(db.my_name().unwrap(), "Me");
+
+    let jess = db
+        .sync_me(&MeConfig { name: "Jess".to_string(), identifiers: vec!["jess@example.com".to_string()] })
+        .expect("Failed to create me contact");
+    assert_eq!(jess.email, Some("jess@example.com".to_string()));
+
+    let timestamp = NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    db.add_message(NewMessage {
+        imessage_id: "me-1".to_string(),
+        text: Some("hi".to_string()),
+        sender: "Jess".to_string(),
+        is_from_me: true,
+        date_created: timestamp,
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: Some(jess.id),
+        message_kind: MessageKind::Text,
+    }).expect("Failed to add message");
+
+    let me = db
+        .sync_me(&MeConfig { name: "Alex".to_string(), identifiers: vec!["+15550000009".to_string()] })
+        .expect("Failed to set me contact");
+    assert_eq!(me.id, jess.id);
+    assert_eq!(me.name, "Alex");
+    assert_eq!(me.phone, Some("+15550000009".to_string()));
+    assert_eq!(me.email, Some("jess@example.com".to_string()));
+
+    let messages = db.get_messages_for_contact_id(me.id, None, None).unwrap();
+    assert_eq!(messages[0].sender, "Alex");
+
+    // Restarting doesn't bring back the old name
+    db.initialize().expect("Failed to initialize database");
+    assert!(db.get_contact("Jess").unwrap().is_none());
+    assert_eq!(db.get_me_contact().unwrap().unwrap().name, "Alex");
+}
+
+#[test]
+fn test_needs_setup() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+    let me = MeConfig { name: "Jess".to_string(), identifiers: vec!["jess@example.com".to_string()] };
+
+    // Each step of setting up has to run once
+    let db = Database::open(&db_url).expect("Failed to open database");
+    assert!(db.needs_setup(&me).unwrap());
+    db.migrate().expect("Failed to migrate database");
+    assert!(db.needs_setup(&me).unwrap());
+    db.initialize().expect("Failed to initialize database");
+    assert!(db.needs_setup(&me).unwrap());
+    db.sync_me(&me).expect("Failed to create me contact");
+    assert!(!db.needs_setup(&me).unwrap());
+
+    // A new name or identifier has to be written, but identifiers left out of the config are kept
+    let renamed = MeConfig { name: "Alex".to_string(), identifiers: me.identifiers.clone() };
+    assert!(db.needs_setup(&renamed).unwrap());
+    let new_phone = MeConfig { name: "Jess".to_string(), identifiers: vec!["+15550000009".to_string()] };
+    assert!(db.needs_setup(&new_phone).unwrap());
+    assert!(!db.needs_setup(&MeConfig { name: "Jess".to_string(), identifiers: Vec::new() }).unwrap());
+}
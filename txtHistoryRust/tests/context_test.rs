@@ -0,0 +1,93 @@
+use chrono::{Duration, NaiveDateTime};
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{Conversation, MessageKind, NewMessage};
+
+fn message(id: usize, thread: &str, date_created: NaiveDateTime) -> NewMessage {
+    NewMessage {
+        imessage_id: format!("guid-{}", id),
+        text: Some(format!("message {}", id)),
+        sender: "Phil".to_string(),
+        is_from_me: false,
+        date_created,
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: Some(thread.to_string()),
+        has_attachments: false,
+        reply_to: None,
+        contact_id: None,
+        message_kind: MessageKind::Text,
+    }
+}
+
+#[test]
+fn test_message_context() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    // Ten messages a minute apart, the fifth and sixth sent in the same second,
+    // with another chat's message in between
+    let start = NaiveDateTime::parse_from_str("2025-01-20 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let mut ids = Vec::new();
+    for i in 0..10 {
+        let minute = if i == 5 { 4 } else { i as i64 };
+        let saved = db
+            .add_message(message(i, "chat-1", start + Duration::minutes(minute)))
+            .expect("Failed to add message");
+        ids.push(saved.id);
+    }
+    db.add_message(message(99, "chat-2", start + Duration::minutes(3)))
+        .expect("Failed to add message");
+
+    let conversation = Conversation::Thread("chat-1".to_string());
+    let target = db.get_message_by_id(ids[4]).unwrap().unwrap();
+    assert_eq!(target.conversation(), Some(conversation.clone()));
+
+    let context = db
+        .get_message_context(&conversation, &target, 2, 2)
+        .expect("Failed to get context");
+    let texts: Vec<&str> = context.iter().map(|m| m.text.as_deref().unwrap()).collect();
+    assert_eq!(texts, vec!["message 2", "message 3", "message 4", "message 5", "message 6"]);
+
+    // Windows stop at the ends of the conversation
+    let first = db.get_message_by_id(ids[0]).unwrap().unwrap();
+    let context = db.get_message_context(&conversation, &first, 50, 1).unwrap();
+    assert_eq!(context.len(), 2);
+
+    // Look up the target by time instead
+    let nearest = db
+        .find_message_nearest(&conversation, start + Duration::seconds(7 * 60 + 20))
+        .unwrap()
+        .unwrap();
+    assert_eq!(nearest.id, ids[7]);
+}
+
+#[test]
+fn test_message_years() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let at = |value: &str| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap();
+    db.add_message(message(1, "chat-1", at("2019-12-31 23:59:59"))).unwrap();
+    db.add_message(message(2, "chat-1", at("2021-01-01 00:00:00"))).unwrap();
+    db.add_message(message(3, "chat-1", at("2021-06-01 12:00:00"))).unwrap();
+    let hidden = db.add_message(message(4, "chat-1", at("2023-03-01 12:00:00"))).unwrap();
+    db.add_message(message(5, "chat-2", at("2024-03-01 12:00:00"))).unwrap();
+    db.hide_messages(&[hidden.id], "test").unwrap();
+
+    // Years without messages are skipped, and hidden messages don't count
+    let years = db.get_message_years(&Conversation::Thread("chat-1".to_string())).unwrap();
+    assert_eq!(years, vec![(2019, 1), (2021, 2)]);
+    assert!(db.get_message_years(&Conversation::Thread("chat-3".to_string())).unwrap().is_empty());
+}
@@ -0,0 +1,85 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::events::{to_ics, EventExtractor};
+use txtHistoryRust::models::{MessageKind, NewContact, NewMessage};
+
+fn message(id: usize, contact_id: i32, is_from_me: bool, text: &str) -> NewMessage {
+    NewMessage {
+        imessage_id: format!("guid-{}", id),
+        text: Some(text.to_string()),
+        sender: if is_from_me { "Jess" } else { "Phil" }.to_string(),
+        is_from_me,
+        // Midday UTC keeps the message on Monday the 20th in any local time zone
+        date_created: NaiveDateTime::parse_from_str(&format!("2025-01-20 12:{:02}:00", id), "%Y-%m-%d %H:%M:%S")
+            .unwrap(),
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: Some(contact_id),
+        message_kind: MessageKind::Text,
+    }
+}
+
+#[test]
+fn test_save_and_report_events() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = db
+        .add_or_update_contact(NewContact {
+            name: "Phil".to_string(),
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact");
+
+    let texts = [
+        (false, "Can you take her Friday at 3?"),
+        (true, "Sure. Dentist is on the 12th too"),
+        (false, "Thanks!"),
+    ];
+    for (i, (is_from_me, text)) in texts.into_iter().enumerate() {
+        db.add_message(message(i, contact.id, is_from_me, text))
+            .expect("Failed to add message");
+    }
+
+    let messages = db
+        .get_messages_for_contact_id(contact.id, None, None)
+        .expect("Failed to get messages");
+    let events = EventExtractor::default().extract(&messages);
+    assert_eq!(db.save_events(&events).unwrap(), 2);
+
+    // Saving again keeps one row per expression in a message
+    db.save_events(&events).unwrap();
+    let report = db.get_event_report(Some(contact.id), None, None).unwrap();
+    assert_eq!(report.len(), 2);
+
+    assert_eq!(report[0].expression, "Friday at 3");
+    assert_eq!(report[0].starts_on, NaiveDate::from_ymd_opt(2025, 1, 24).unwrap());
+    assert_eq!(report[0].starts_at, NaiveTime::from_hms_opt(15, 0, 0));
+    assert_eq!(report[1].sender, "Jess");
+    assert_eq!(report[1].starts_on, NaiveDate::from_ymd_opt(2025, 2, 12).unwrap());
+    assert_eq!(report[1].starts_at, None);
+
+    let ics = to_ics(&report, "Phil");
+    assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+    assert!(ics.contains("DTSTART;VALUE=DATE:20250212"));
+
+    // Purging the messages takes their events with them
+    let before = NaiveDate::from_ymd_opt(2025, 2, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let counts = db.purge_messages(before, Some(contact.id), &[], false).unwrap();
+    assert_eq!(counts.events, 2);
+    assert!(db.get_event_report(None, None, None).unwrap().is_empty());
+}
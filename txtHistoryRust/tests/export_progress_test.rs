@@ -0,0 +1,77 @@
+use chrono::{Local, TimeZone};
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::export_progress::{export_hash, ExportProgress};
+use txtHistoryRust::file_writer::{write_messages_with_progress, Chunking, CsvOptions, WritePolicy, WriteStatus};
+use txtHistoryRust::models::{Message, OutputFormat};
+use txtHistoryRust::style::ExportStyle;
+
+fn message(minute: u32, content: &str) -> Message {
+    Message {
+        sender: "Phil".to_string(),
+        timestamp: Local.with_ymd_and_hms(2025, 1, 20, 12, minute, 0).unwrap(),
+        content: content.to_string(),
+        source: None,
+        imessage_id: None,
+        service: None,
+        is_from_me: false,
+    }
+}
+
+#[test]
+fn test_resume_export() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+    let output_dir = temp_dir.path().join("out");
+    std::fs::create_dir_all(&output_dir).unwrap();
+    let output_dir = output_dir.to_string_lossy().to_string();
+
+    let messages = vec![message(0, "First"), message(1, "Second"), message(2, "Third")];
+    let query_hash = export_hash("Phil|txt", &messages);
+    let write = |progress: &ExportProgress| {
+        write_messages_with_progress(
+            &messages,
+            OutputFormat::Txt,
+            &CsvOptions::default(),
+            &ExportStyle::default(),
+            Chunking::Lines(1),
+            &output_dir,
+            WritePolicy::Overwrite,
+            1,
+            Some(progress),
+        )
+        .expect("Failed to write export")
+    };
+
+    let progress = ExportProgress::start(&db, &query_hash, &output_dir, 3).expect("Failed to start export");
+    let export_id = progress.export_id();
+    let written = write(&progress);
+    assert!(written.iter().all(|file| file.status == WriteStatus::Created));
+    assert_eq!(db.get_export_chunks(export_id).unwrap().len(), 3);
+    assert!(db.get_export_run(export_id).unwrap().unwrap().finished_at.is_none());
+
+    // A chunk modified after it was written fails its checksum and is written again
+    std::fs::write(format!("{}/chunk_2.txt", output_dir), "edited").unwrap();
+
+    let progress = ExportProgress::resume(&db, export_id, &query_hash, &output_dir).expect("Failed to resume export");
+    assert_eq!(progress.completed_count(), 3);
+    let written = write(&progress);
+    let statuses: Vec<WriteStatus> = written.iter().map(|file| file.status).collect();
+    assert_eq!(statuses, vec![WriteStatus::Resumed, WriteStatus::Replaced, WriteStatus::Resumed]);
+    assert!(std::fs::read_to_string(format!("{}/chunk_2.txt", output_dir)).unwrap().contains("Second"));
+
+    progress.finish().unwrap();
+    assert!(db.get_export_run(export_id).unwrap().unwrap().finished_at.is_some());
+
+    // Different messages or output directory can't resume the export
+    let changed = export_hash("Phil|txt", &messages[..2]);
+    assert!(ExportProgress::resume(&db, export_id, &changed, &output_dir).is_err());
+    assert!(ExportProgress::resume(&db, export_id, &query_hash, "elsewhere").is_err());
+    assert!(ExportProgress::resume(&db, export_id + 1, &query_hash, &output_dir).is_err());
+}
@@ -1,13 +1,14 @@
-use chrono::NaiveDateTime;
-use std::path::{Path, PathBuf};
+use chrono::{Local, NaiveDateTime, TimeZone};
 use std::fs;
 use tempfile::tempdir;
 use tokio::runtime::Runtime;
 
 // Import the necessary modules from the crate
 use txtHistoryRust::db::Database;
-use txtHistoryRust::models::{NewContact, DbContact, NewMessage, DbMessage, Contact, DateRange, OutputFormat, Message};
-use txtHistoryRust::repository::{MessageRepository, IMessageDatabaseRepo};
+use txtHistoryRust::export_options::ExportOptions;
+use txtHistoryRust::models::{NewContact, NewMessage, DbMessage, DateRange, Message, MessageKind};
+use txtHistoryRust::repository::MessageRepository;
+use txtHistoryRust::testing::{MessageBuilder, MockMessageRepository};
 
 #[test]
 fn test_export_conversation_by_person() {
@@ -40,7 +41,7 @@ fn test_export_conversation_by_person() {
     };
     
     let person = db.add_or_update_contact(person_contact).expect("Failed to add person contact");
-    let me = db.add_or_update_contact(me_contact).expect("Failed to add me contact");
+    db.add_or_update_contact(me_contact).expect("Failed to add me contact");
     
     // Create test messages with realistic timestamps
     let timestamp1 = NaiveDateTime::parse_from_str("2025-01-20 12:21:19", "%Y-%m-%d %H:%M:%S").unwrap();
@@ -58,7 +59,9 @@ fn test_export_conversation_by_person() {
         service: Some("iMessage".to_string()),
         thread_id: None,
         has_attachments: false,
-        contact_id: Some(me.id),
+        reply_to: None,
+        contact_id: Some(person.id),
+        message_kind: MessageKind::Text,
     };
     
     // Message from Jess to Phil
@@ -72,7 +75,9 @@ fn test_export_conversation_by_person() {
         service: Some("iMessage".to_string()),
         thread_id: None,
         has_attachments: false,
+        reply_to: None,
         contact_id: Some(person.id),
+        message_kind: MessageKind::Text,
     };
     
     // Another message from Phil to Jess
@@ -86,113 +91,47 @@ fn test_export_conversation_by_person() {
         service: Some("iMessage".to_string()),
         thread_id: None,
         has_attachments: false,
-        contact_id: Some(me.id),
+        reply_to: None,
+        contact_id: Some(person.id),
+        message_kind: MessageKind::Text,
     };
     
     db.add_message(message1).expect("Failed to add message 1");
     db.add_message(message2).expect("Failed to add message 2");
     db.add_message(message3).expect("Failed to add message 3");
     
-    // Create a mock repository for testing
-    struct MockRepo {
-        db: Database,
-    }
-    
-    impl MockRepo {
-        fn new(db: Database) -> Self {
-            Self { db }
-        }
-    }
-    
-    #[async_trait::async_trait]
-    impl MessageRepository for MockRepo {
-        async fn fetch_messages(&self, _contact: &Contact, _date_range: &DateRange) -> anyhow::Result<Vec<Message>> {
-            Ok(Vec::new())
-        }
-        
-        async fn save_messages(&self, messages: &[Message], format: OutputFormat, path: &Path) -> anyhow::Result<()> {
-            // Simple implementation for testing
-            let content = messages.iter()
-                .map(|m| format!("{}, {}, {}", 
-                    m.sender,
-                    m.timestamp.format("%b %d, %Y %I:%M:%S %p"),
-                    m.content
-                ))
-                .collect::<Vec<_>>()
-                .join("\n\n");
-                
-            if format == OutputFormat::Txt {
-                fs::write(path, content)?;
+    // Serve the stored conversation from the crate's mock repository
+    let messages: Vec<Message> = db
+        .get_conversation_with_person("Phil", None, None)
+        .expect("Failed to get conversation")
+        .into_iter()
+        .map(|db_msg| {
+            let timestamp = Local.from_local_datetime(&db_msg.date_created).single().expect("Invalid timestamp");
+            let builder = MessageBuilder::new(&db_msg.sender, &db_msg.text.unwrap_or_default())
+                .timestamp(timestamp)
+                .id(&db_msg.imessage_id);
+            if db_msg.is_from_me {
+                builder.from_me().build()
             } else {
-                // For CSV, just write the same content for simplicity in testing
-                fs::write(path, content)?;
+                builder.build()
             }
-            
-            Ok(())
-        }
-        
-        async fn export_conversation_by_person(
-            &self, 
-            person_name: &str, 
-            format: OutputFormat, 
-            output_path: &Path,
-            date_range: &DateRange,
-            chunk_size: Option<usize>,
-            lines_per_chunk: Option<usize>
-        ) -> anyhow::Result<Vec<PathBuf>> {
-            // Get all messages with this person
-            let messages = self.db.get_conversation_with_person(
-                person_name,
-                date_range.start.map(|dt| dt.naive_local()),
-                date_range.end.map(|dt| dt.naive_local()),
-            )?;
-            
-            if messages.is_empty() {
-                return Ok(Vec::new());
-            }
-            
-            // Convert database messages to the Message format
-            let messages: Vec<Message> = messages
-                .into_iter()
-                .map(|db_msg| Message {
-                    content: db_msg.text.unwrap_or_default(),
-                    sender: db_msg.sender,
-                    timestamp: chrono::DateTime::<chrono::Local>::from_naive_local(&db_msg.date_created)
-                        .expect("Invalid timestamp"),
-                })
-                .collect();
-            
-            // For simplicity in testing, just create one file
-            let txt_path = output_path.with_extension("txt");
-            let csv_path = output_path.with_extension("csv");
-            
-            self.save_messages(&messages, OutputFormat::Txt, &txt_path).await?;
-            self.save_messages(&messages, OutputFormat::Csv, &csv_path).await?;
-            
-            Ok(vec![txt_path, csv_path])
-        }
-    }
-    
+        })
+        .collect();
+    assert_eq!(messages.len(), 3);
+
     // Run the export test
     rt.block_on(async {
         let output_dir = temp_dir.path().join("output");
         fs::create_dir_all(&output_dir).expect("Failed to create output directory");
         
-        let repo = MockRepo::new(db);
+        let repo = MockMessageRepository::new().with_conversation("Phil", messages);
         let date_range = DateRange {
             start: None,
             end: None,
         };
         
-        let output_path = output_dir.join("phil_conversation");
-        let result = repo.export_conversation_by_person(
-            "Phil",
-            OutputFormat::Txt,
-            &output_path,
-            &date_range,
-            None,
-            None
-        ).await.expect("Export failed");
+        let options = ExportOptions::new(output_dir.join("phil_conversation")).with_date_range(date_range);
+        let result = repo.export_conversation_by_person("Phil", &options).await.expect("Export failed");
         
         // Verify files were created
         assert_eq!(result.len(), 2);
@@ -206,10 +145,65 @@ fn test_export_conversation_by_person() {
         assert!(txt_content.contains("Phil, Jan 20, 2025 02:26:27 PM, Are you picking up Everly?"));
         
         // Verify chronological order
-        let lines: Vec<&str> = txt_content.split("\n\n").collect();
+        let lines: Vec<&str> = txt_content.trim_end().split("\n\n").collect();
         assert_eq!(lines.len(), 3);
         assert!(lines[0].contains("12:21:19"));
         assert!(lines[1].contains("12:22:28"));
         assert!(lines[2].contains("02:26:27"));
     });
 }
+
+#[test]
+fn test_get_my_messages_across_contacts() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let mut contact_ids = Vec::new();
+    for (name, phone) in [("Phil", "+15551234567"), ("Robert", "+15559876543")] {
+        let contact = db
+            .add_or_update_contact(NewContact {
+                name: name.to_string(),
+                phone: Some(phone.to_string()),
+                email: None,
+                is_me: false,
+                primary_identifier: None,
+            })
+            .expect("Failed to add contact");
+        contact_ids.push(contact.id);
+    }
+
+    // Sent to Robert first, then to Phil, with a reply from Phil in between
+    let messages = [
+        ("sent-robert", "Jess", true, "2025-01-20 09:00:00", contact_ids[1]),
+        ("from-phil", "Phil", false, "2025-01-20 10:00:00", contact_ids[0]),
+        ("sent-phil", "Jess", true, "2025-01-20 11:00:00", contact_ids[0]),
+        ("sent-old", "Jess", true, "2024-12-31 11:00:00", contact_ids[0]),
+    ];
+    for (id, sender, is_from_me, date, contact_id) in messages {
+        db.add_message(NewMessage {
+            imessage_id: id.to_string(),
+            text: Some(format!("Message {}", id)),
+            sender: sender.to_string(),
+            is_from_me,
+            date_created: NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").unwrap(),
+            date_imported: None,
+            handle_id: None,
+            service: Some("iMessage".to_string()),
+            thread_id: None,
+            has_attachments: false,
+            reply_to: None,
+            contact_id: Some(contact_id),
+            message_kind: MessageKind::Text,
+        })
+        .expect("Failed to add message");
+    }
+
+    let start = NaiveDateTime::parse_from_str("2025-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let mine: Vec<DbMessage> = db.get_my_messages(Some(start), None).expect("Failed to get my messages");
+    let ids: Vec<&str> = mine.iter().map(|m| m.imessage_id.as_str()).collect();
+    assert_eq!(ids, vec!["sent-robert", "sent-phil"]);
+    assert!(mine.iter().all(|m| m.is_from_me));
+}
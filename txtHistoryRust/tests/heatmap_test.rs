@@ -0,0 +1,80 @@
+use chrono::{Datelike, Duration, Local, NaiveDateTime, TimeZone, Timelike};
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{MessageKind, NewContact, NewMessage};
+
+fn message(id: usize, contact_id: i32, date_created: NaiveDateTime) -> NewMessage {
+    NewMessage {
+        imessage_id: format!("guid-{}", id),
+        text: Some(format!("message {}", id)),
+        sender: "Phil".to_string(),
+        is_from_me: false,
+        date_created,
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: Some(contact_id),
+        message_kind: MessageKind::Text,
+    }
+}
+
+#[test]
+fn test_activity_heatmap() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = db
+        .add_or_update_contact(NewContact {
+            name: "Phil".to_string(),
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact");
+
+    // Three messages in one hour, then one three days later; dates are stored in UTC
+    let start = NaiveDateTime::parse_from_str("2025-01-20 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let dates = vec![
+        start,
+        start + Duration::minutes(10),
+        start + Duration::minutes(20),
+        start + Duration::days(3),
+    ];
+    for (i, date) in dates.iter().enumerate() {
+        db.add_message(message(i, contact.id, *date)).expect("Failed to add message");
+    }
+
+    let heatmap = db
+        .get_activity_heatmap(Some(contact.id), None, None)
+        .expect("Failed to build heatmap");
+    assert_eq!(heatmap.total(), 4);
+
+    // Counts are grouped in local time
+    let local = Local.from_utc_datetime(&start);
+    let day = local.weekday().num_days_from_sunday() as usize;
+    assert_eq!(heatmap.weekly[day][local.hour() as usize], 3);
+
+    // The days in between are filled in with zero
+    assert_eq!(heatmap.daily.len(), 4);
+    assert_eq!(heatmap.daily[0].date, local.date_naive());
+    assert_eq!(heatmap.daily[0].messages, 3);
+    assert_eq!(heatmap.daily[1].messages, 0);
+    assert_eq!(heatmap.daily[3].messages, 1);
+
+    // Date ranges narrow the counts
+    let heatmap = db
+        .get_activity_heatmap(Some(contact.id), Some(start + Duration::days(1)), None)
+        .unwrap();
+    assert_eq!(heatmap.total(), 1);
+    assert_eq!(heatmap.daily.len(), 1);
+}
@@ -0,0 +1,71 @@
+use chrono::{Duration, NaiveDateTime};
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{MessageKind, NewContact, NewMessage};
+
+#[test]
+fn test_hide_and_unhide_messages() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = db
+        .add_or_update_contact(NewContact {
+            name: "Phil".to_string(),
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact");
+
+    let start = NaiveDateTime::parse_from_str("2025-01-20 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let mut ids = Vec::new();
+    for (i, text) in ["See you at 3", "Your code is 482913", "ok"].iter().enumerate() {
+        let saved = db
+            .add_message(NewMessage {
+                imessage_id: format!("guid-{}", i),
+                text: Some(text.to_string()),
+                sender: "Phil".to_string(),
+                is_from_me: false,
+                date_created: start + Duration::minutes(i as i64),
+                date_imported: None,
+                handle_id: None,
+                service: Some("SMS".to_string()),
+                thread_id: None,
+                has_attachments: false,
+                reply_to: None,
+                contact_id: Some(contact.id),
+                message_kind: MessageKind::Text,
+            })
+            .expect("Failed to add message");
+        ids.push(saved.id);
+    }
+
+    // Unknown IDs are ignored
+    assert_eq!(db.hide_messages(&[ids[1], 9999], "verification code").unwrap(), 1);
+
+    let visible = db.get_messages_for_contact_id(contact.id, None, None).unwrap();
+    assert_eq!(visible.len(), 2);
+    assert!(visible.iter().all(|m| m.id != ids[1]));
+
+    let hidden = db.get_hidden_messages().unwrap();
+    assert_eq!(hidden.len(), 1);
+    assert_eq!(hidden[0].hidden_reason.as_deref(), Some("verification code"));
+    assert!(db.get_hidden_imessage_ids().unwrap().contains("guid-1"));
+
+    // Reports skip hidden messages, but the archive still holds them
+    assert_eq!(db.get_activity_heatmap(Some(contact.id), None, None).unwrap().total(), 2);
+    assert_eq!(db.get_message_dates_for_contact_id(contact.id).unwrap().len(), 3);
+
+    assert_eq!(db.unhide_messages(&[ids[1]]).unwrap(), 1);
+    assert_eq!(db.get_messages_for_contact_id(contact.id, None, None).unwrap().len(), 3);
+    let message = db.get_message_by_id(ids[1]).unwrap().unwrap();
+    assert!(!message.hidden);
+    assert_eq!(message.hidden_reason, None);
+}
@@ -0,0 +1,61 @@
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{MessageKind, NewContact, NewMessage};
+
+#[test]
+fn test_maintain_reclaims_purged_space() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact_id = db
+        .add_or_update_contact(NewContact {
+            name: "Robert Test".to_string(),
+            phone: None,
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact")
+        .id;
+
+    let date = NaiveDateTime::parse_from_str("2017-06-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    for i in 0..500 {
+        db.add_message(NewMessage {
+            imessage_id: format!("msg-{}", i),
+            text: Some("a long message ".repeat(40)),
+            sender: "Robert Test".to_string(),
+            is_from_me: false,
+            date_created: date + chrono::Duration::minutes(i),
+            date_imported: None,
+            handle_id: None,
+            service: Some("iMessage".to_string()),
+            thread_id: None,
+            has_attachments: false,
+            reply_to: None,
+            contact_id: Some(contact_id),
+            message_kind: MessageKind::Text,
+        })
+        .expect("Failed to add message");
+    }
+
+    let cutoff = NaiveDateTime::parse_from_str("2018-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    db.purge_messages(cutoff, None, &[], false).unwrap();
+
+    // Deleting leaves the pages in the file until it's vacuumed
+    let purged = db.database_size().unwrap();
+    assert!(purged.free_pages > 0);
+
+    let report = db.maintain().unwrap();
+    assert!(report.problems.is_empty());
+    assert_eq!(report.before, purged);
+    assert_eq!(report.after.free_pages, 0);
+    assert!(report.reclaimed() > 0);
+    assert_eq!(report.after.bytes(), db.database_size().unwrap().bytes());
+}
@@ -0,0 +1,60 @@
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{MessageKind, NewMessage, UpsertOutcome};
+
+fn message(text: &str, has_attachments: bool) -> NewMessage {
+    NewMessage {
+        imessage_id: "guid-1".to_string(),
+        text: Some(text.to_string()),
+        sender: "Test Person".to_string(),
+        is_from_me: false,
+        date_created: NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments,
+        reply_to: None,
+        contact_id: None,
+        message_kind: MessageKind::Text,
+    }
+}
+
+#[test]
+fn test_upsert_message_records_changes() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+    
+    let db = Database::new(&db_url).expect("Failed to create database");
+    
+    let inserted = db.upsert_message(message("See you at 3", false)).expect("Failed to insert");
+    assert!(matches!(inserted, UpsertOutcome::Inserted(_)));
+    
+    // Re-importing the same content changes nothing
+    let unchanged = db.upsert_message(message("See you at 3", false)).expect("Failed to upsert");
+    assert!(matches!(unchanged, UpsertOutcome::Unchanged(_)));
+    
+    // An edited message with a late-delivered attachment updates the stored copy
+    let updated = db.upsert_message(message("See you at 4", true)).expect("Failed to upsert");
+    let UpsertOutcome::Updated(stored, changes) = updated else {
+        panic!("Expected the message to be updated");
+    };
+    assert_eq!(stored.text, Some("See you at 4".to_string()));
+    assert!(stored.has_attachments);
+    assert_eq!(changes.len(), 2);
+    
+    let revisions = db.get_message_revisions(stored.id).expect("Failed to get revisions");
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[0].field, "text");
+    assert_eq!(revisions[0].old_value, Some("See you at 3".to_string()));
+    assert_eq!(revisions[0].new_value, Some("See you at 4".to_string()));
+    
+    // add_message still leaves existing messages alone
+    let kept = db.add_message(message("Something else", false)).expect("Failed to add");
+    assert_eq!(kept.text, Some("See you at 4".to_string()));
+}
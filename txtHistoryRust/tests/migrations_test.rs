@@ -0,0 +1,43 @@
+use rusqlite::Connection;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+
+#[test]
+fn test_upgrade_untracked_database() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+
+    // A database set up before applied migrations were tracked, with a contact in it
+    let conn = Connection::open(&db_path).expect("Failed to open database");
+    conn.execute_batch(include_str!("../migrations/2025-03-15-000000_create_tables/up.sql")).unwrap();
+    conn.execute_batch(include_str!("../migrations/2025-03-15-000001_add_processed_messages/up.sql")).unwrap();
+    conn.execute_batch(include_str!("../migrations/2025-03-19-000000_enhance_contact_linking/up.sql")).unwrap();
+    conn.execute("INSERT INTO contacts (name, phone, is_me) VALUES ('Phil', '+15551234567', 0)", [])
+        .unwrap();
+    drop(conn);
+
+    let db_url = db_path.to_str().unwrap();
+    let db = Database::new(db_url).expect("Failed to upgrade database");
+    let contact = db.get_contact("Phil").unwrap().expect("Contact lost in the upgrade");
+    assert_eq!(contact.phone.as_deref(), Some("+15551234567"));
+
+    // The migrations it already had are recorded, so opening it again doesn't rerun them
+    let recorded: i64 = db
+        .get_connection()
+        .unwrap()
+        .query_row(
+            "SELECT COUNT(*) FROM __migrations WHERE name IN (
+                '2025-03-15-000000_create_tables',
+                '2025-03-15-000001_add_processed_messages',
+                '2025-03-19-000000_enhance_contact_linking'
+            )",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(recorded, 3);
+    drop(db);
+    Database::new(db_url).expect("Failed to reopen upgraded database");
+}
@@ -0,0 +1,91 @@
+use chrono::{Duration, NaiveDateTime};
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{NewContact, NewProcessedMessage};
+use txtHistoryRust::testing::NewMessageBuilder;
+
+fn processed(message_id: i32, version: &str) -> NewProcessedMessage {
+    NewProcessedMessage {
+        original_message_id: message_id,
+        processed_text: "meeting rhonda in boston".to_string(),
+        tokens: Some("meeting rhonda boston".to_string()),
+        lemmatized_text: None,
+        named_entities: Some(
+            r#"[{"text":"Boston","entity_type":"LOCATION","start":18,"end":24}]"#.to_string(),
+        ),
+        sentiment_score: Some(0.25),
+        processing_version: version.to_string(),
+        flags: None,
+        flag_severity: None,
+        language: Some("eng".to_string()),
+        sentence_sentiments: Some(
+            r#"[{"text":"Meeting Rhonda in Boston","start":0,"end":24,"score":-0.5}]"#.to_string(),
+        ),
+    }
+}
+
+#[test]
+fn test_nlp_export_rows() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = db
+        .add_or_update_contact(NewContact {
+            name: "Phil".to_string(),
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact");
+
+    let start = NaiveDateTime::parse_from_str("2025-01-20 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        let stored = db
+            .add_message(
+                NewMessageBuilder::new(i)
+                    .text(&format!("Meeting Rhonda in Boston {}", i))
+                    .contact(contact.id)
+                    .date_created(start + Duration::days(i as i64))
+                    .build(),
+            )
+            .expect("Failed to add message");
+        ids.push(stored.id);
+    }
+
+    // Only the first two are processed with v2.0; the third only with v1.0
+    db.add_processed_message(processed(ids[0], "v2.0")).unwrap();
+    db.add_processed_message(processed(ids[1], "v2.0")).unwrap();
+    db.add_processed_message(processed(ids[2], "v1.0")).unwrap();
+
+    let rows = db.get_nlp_export_rows("v2.0", None, None, None).expect("Failed to read NLP rows");
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].message_id, ids[0]);
+    assert_eq!(rows[0].contact.as_deref(), Some("Phil"));
+    assert_eq!(rows[0].text.as_deref(), Some("Meeting Rhonda in Boston 0"));
+    assert_eq!(rows[0].tokens, vec!["meeting", "rhonda", "boston"]);
+    assert_eq!(rows[0].entities[0].entity_type, "LOCATION");
+    assert_eq!(rows[0].sentiment, Some(0.25));
+    assert_eq!(rows[0].sentences[0].score, -0.5);
+    assert_eq!(rows[0].language.as_deref(), Some("eng"));
+    assert_eq!(rows[0].processing_version, "v2.0");
+
+    // Date ranges and contacts narrow the rows
+    let rows = db
+        .get_nlp_export_rows("v2.0", Some(contact.id), Some(start + Duration::hours(12)), None)
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].message_id, ids[1]);
+
+    // Hidden messages are left out
+    db.hide_messages(&[ids[1]], "private").unwrap();
+    assert_eq!(db.get_nlp_export_rows("v2.0", None, None, None).unwrap().len(), 1);
+    assert!(db.get_nlp_export_rows("v3.0", None, None, None).unwrap().is_empty());
+}
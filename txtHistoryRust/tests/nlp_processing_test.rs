@@ -0,0 +1,105 @@
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{MessageKind, NewMessage, ProcessFilter, ServiceFilter};
+use txtHistoryRust::nlp::NlpProcessor;
+
+fn message(id: usize, text: Option<&str>) -> NewMessage {
+    NewMessage {
+        imessage_id: format!("guid-{}", id),
+        text: text.map(ToString::to_string),
+        sender: "Test Person".to_string(),
+        is_from_me: false,
+        date_created: NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: None,
+        message_kind: MessageKind::Text,
+    }
+}
+
+#[test]
+fn test_process_messages_in_batches() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let mut ids = Vec::new();
+    for i in 0..20 {
+        let text = if i == 7 { None } else { Some("What a great day") };
+        ids.push(db.add_message(message(i, text)).expect("Failed to add message").id);
+    }
+
+    let processor = NlpProcessor::new("v1.0");
+    let report = processor.process_messages(&db, &ids).expect("Failed to process");
+
+    // The message without text is skipped
+    assert_eq!(report.inserted, 19);
+    assert_eq!(report.processed.len(), 19);
+    assert!(report.processed.iter().all(|p| p.sentiment_score > Some(0.0)));
+
+    // Re-running returns the stored results without writing them again
+    let report = processor.process_messages(&db, &ids).expect("Failed to reprocess");
+    assert_eq!(report.inserted, 0);
+    assert_eq!(report.processed.len(), 19);
+
+    NlpProcessor::new("v2.0")
+        .process_messages(&db, &ids[..5])
+        .expect("Failed to process v2.0");
+    assert_eq!(db.count_shared_processed("v1.0", "v2.0").unwrap(), 5);
+    assert_eq!(db.sample_shared_processed_ids("v1.0", "v2.0", 3).unwrap().len(), 3);
+
+    let versions = db.list_processing_versions().expect("Failed to list versions");
+    assert_eq!(versions.len(), 2);
+
+    assert_eq!(db.purge_processing_version("v1.0").unwrap(), 19);
+    assert_eq!(db.get_processed_messages_by_version("v1.0").unwrap().len(), 0);
+    assert_eq!(db.get_processed_messages_by_version("v2.0").unwrap().len(), 5);
+
+    assert!(processor.process_messages(&db, &[9999]).is_err());
+}
+
+#[test]
+fn test_process_filters() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    for i in 0..6 {
+        let mut new_message = message(i, Some(if i % 3 == 0 { "ok" } else { "Running about twenty minutes late" }));
+        new_message.is_from_me = i % 2 == 0;
+        if i == 5 {
+            new_message.service = Some("SMS".to_string());
+        }
+        db.add_message(new_message).expect("Failed to add message");
+    }
+
+    let ids = |filter: ProcessFilter| db.get_unprocessed_message_ids_filtered("v1.0", &filter).unwrap();
+
+    assert_eq!(ids(ProcessFilter::default()).len(), 6);
+    // Messages 1 and 5 are from the other side and longer than 20 characters
+    let long_from_contact = ProcessFilter {
+        is_from_me: Some(false),
+        min_length: Some(20),
+        ..ProcessFilter::default()
+    };
+    assert_eq!(ids(long_from_contact.clone()).len(), 2);
+    assert_eq!(ids(ProcessFilter { service: ServiceFilter::Sms, ..long_from_contact }).len(), 1);
+    assert_eq!(ids(ProcessFilter { limit: Some(4), ..ProcessFilter::default() }).len(), 4);
+
+    // Processed messages drop out of the next run
+    let first_two = ids(ProcessFilter { limit: Some(2), ..ProcessFilter::default() });
+    NlpProcessor::new("v1.0").process_messages(&db, &first_two).unwrap();
+    assert_eq!(ids(ProcessFilter::default()).len(), 4);
+}
@@ -0,0 +1,64 @@
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{MessageKind, NewContact, NewMessage};
+use txtHistoryRust::nlp::NlpProcessor;
+
+fn message(guid: &str, contact_id: i32, text: &str, minute: u32) -> NewMessage {
+    NewMessage {
+        imessage_id: guid.to_string(),
+        text: Some(text.to_string()),
+        sender: "Phil".to_string(),
+        is_from_me: false,
+        date_created: NaiveDateTime::parse_from_str(&format!("2025-01-20 10:{:02}:00", minute), "%Y-%m-%d %H:%M:%S")
+            .unwrap(),
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: Some(contact_id),
+        message_kind: MessageKind::Text,
+    }
+}
+
+#[test]
+fn test_delete_duplicate_messages() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = db
+        .add_or_update_contact(NewContact {
+            name: "Phil".to_string(),
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact");
+
+    // The same message imported from chat.db and again from a restored export
+    let original = db.add_message(message("guid-1", contact.id, "Running late", 0)).unwrap();
+    let restored = db.add_message(message("export-1", contact.id, "Running late", 0)).unwrap();
+    db.add_message(message("guid-2", contact.id, "Running late", 5)).unwrap();
+
+    // Results stored for the duplicate go with it
+    NlpProcessor::new("v1.0")
+        .process_messages(&db, &[original.id, restored.id])
+        .unwrap();
+
+    assert_eq!(db.delete_duplicate_messages(contact.id).unwrap(), 1);
+    assert_eq!(db.delete_duplicate_messages(contact.id).unwrap(), 0);
+
+    let messages = db.get_messages_for_contact_id(contact.id, None, None).unwrap();
+    assert_eq!(messages.len(), 2);
+    assert!(messages.iter().any(|m| m.id == original.id));
+    assert_eq!(db.get_processed_messages_by_version("v1.0").unwrap().len(), 1);
+}
@@ -0,0 +1,127 @@
+use chrono::{Local, NaiveDateTime, TimeZone};
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::config::RetentionConfig;
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{MessageKind, NewAttachment, NewContact, NewMessage, NewProcessedMessage};
+use txtHistoryRust::retention::enforce_retention;
+
+fn add_contact(db: &Database, name: &str) -> i32 {
+    db.add_or_update_contact(NewContact {
+        name: name.to_string(),
+        phone: None,
+        email: None,
+        is_me: false,
+        primary_identifier: None,
+    })
+    .expect("Failed to add contact")
+    .id
+}
+
+fn add_message(db: &Database, id: &str, contact_id: i32, date: &str) -> i32 {
+    db.add_message(NewMessage {
+        imessage_id: id.to_string(),
+        text: Some(format!("message {}", id)),
+        sender: "Someone".to_string(),
+        is_from_me: false,
+        date_created: NaiveDateTime::parse_from_str(&format!("{} 12:00:00", date), "%Y-%m-%d %H:%M:%S").unwrap(),
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: Some(contact_id),
+        message_kind: MessageKind::Text,
+    })
+    .expect("Failed to add message")
+    .id
+}
+
+#[test]
+fn test_purge_messages() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let robert = add_contact(&db, "Robert Test");
+    let sherry = add_contact(&db, "Sherry Test");
+    let old = add_message(&db, "old", robert, "2017-06-01");
+    add_message(&db, "new", robert, "2019-06-01");
+    add_message(&db, "other", sherry, "2017-06-01");
+
+    db.add_processed_message(NewProcessedMessage {
+        original_message_id: old,
+        processed_text: "message old".to_string(),
+        tokens: None,
+        lemmatized_text: None,
+        named_entities: None,
+        sentiment_score: None,
+        processing_version: "v1.0".to_string(),
+        flags: None,
+        flag_severity: None,
+        language: None,
+        sentence_sentiments: None,
+    })
+    .expect("Failed to add processed message");
+    db.add_attachment(NewAttachment {
+        message_id: old,
+        filename: Some("photo.jpg".to_string()),
+        mime_type: Some("image/jpeg".to_string()),
+        size_bytes: Some(1024),
+        created_at: NaiveDateTime::parse_from_str("2017-06-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+    })
+    .expect("Failed to add attachment");
+
+    let cutoff = NaiveDateTime::parse_from_str("2018-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+    // A dry run counts without deleting
+    let preview = db.purge_messages(cutoff, Some(robert), &[], true).unwrap();
+    assert_eq!((preview.messages, preview.processed_messages, preview.attachments), (1, 1, 1));
+    assert!(db.get_message_by_id(old).unwrap().is_some());
+
+    let purged = db.purge_messages(cutoff, Some(robert), &[], false).unwrap();
+    assert_eq!(purged, preview);
+    assert!(db.get_message_by_id(old).unwrap().is_none());
+    assert!(db.get_processed_message(old, "v1.0").unwrap().is_none());
+    assert_eq!(db.get_messages_for_contact_id(robert, None, None).unwrap().len(), 1);
+    // Other contacts are left alone
+    assert_eq!(db.get_messages_for_contact_id(sherry, None, None).unwrap().len(), 1);
+}
+
+#[test]
+fn test_enforce_retention() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let robert = add_contact(&db, "Robert Test");
+    let sherry = add_contact(&db, "Sherry Test");
+    add_message(&db, "robert-old", robert, "2015-06-01");
+    add_message(&db, "robert-recent", robert, "2024-06-01");
+    add_message(&db, "sherry-old", sherry, "2015-06-01");
+    add_message(&db, "sherry-recent", sherry, "2024-06-01");
+
+    // Robert's messages are kept for 20 years, everyone else's for 5
+    let mut config = RetentionConfig {
+        max_age_days: Some(5 * 365),
+        ..RetentionConfig::default()
+    };
+    config.contacts.insert("Robert Test".to_string(), 20 * 365);
+
+    let now = Local.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap();
+    let purged = enforce_retention(&db, &config, now).unwrap();
+    assert_eq!(purged.messages, 1);
+    assert_eq!(db.get_messages_for_contact_id(robert, None, None).unwrap().len(), 2);
+    assert_eq!(db.get_messages_for_contact_id(sherry, None, None).unwrap().len(), 1);
+
+    config.contacts.insert("Nobody".to_string(), 30);
+    assert!(enforce_retention(&db, &config, now).is_err());
+}
@@ -0,0 +1,80 @@
+use chrono::{Duration, NaiveDateTime};
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{Conversation, NewAttachment, NewContact};
+use txtHistoryRust::testing::NewMessageBuilder;
+
+#[test]
+fn test_search_messages_with_context() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let contact = |name: &str, phone: &str| {
+        db.add_or_update_contact(NewContact {
+            name: name.to_string(),
+            phone: Some(phone.to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact")
+    };
+    let phil = contact("Phil", "+15551234567");
+    let rhonda = contact("Rhonda", "+15557654321");
+
+    let start = NaiveDateTime::parse_from_str("2025-01-20 14:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let texts = ["Are you picking up Everly?", "Yes", "Pick up milk too", "ok", "Got 100% of it"];
+    for (i, text) in texts.iter().enumerate() {
+        let date_created = start + Duration::minutes(i as i64);
+        db.add_message(NewMessageBuilder::new(i).text(text).contact(phil.id).date_created(date_created).build())
+            .unwrap();
+    }
+    let kids = NewMessageBuilder::new(10).text("Can you pick up the kids?").contact(rhonda.id).date_created(start);
+    db.add_message(kids.build()).unwrap();
+
+    // Case is ignored, and every conversation is searched unless one is given
+    let found = db.search_messages("PICK UP", None, None, None).unwrap();
+    let ids: Vec<&str> = found.iter().map(|m| m.imessage_id.as_str()).collect();
+    assert_eq!(ids, vec!["guid-10", "guid-2"]);
+    let phil_only = db.search_messages("pick up", Some(&Conversation::Contact(phil.id)), None, None).unwrap();
+    assert_eq!(phil_only.len(), 1);
+
+    // LIKE wildcards in the text are matched literally
+    assert_eq!(db.search_messages("100%", None, None, None).unwrap().len(), 1);
+    assert!(db.search_messages("1_0", None, None, None).unwrap().is_empty());
+    let later = start + Duration::minutes(3);
+    assert!(db.search_messages("pick", Some(&Conversation::Contact(phil.id)), Some(later), None).unwrap().is_empty());
+
+    // Attachment transcripts are searched along with the text
+    let memo = NewMessageBuilder::new(11).without_text().contact(rhonda.id).date_created(start + Duration::minutes(9));
+    let memo = db.add_message(memo.build()).unwrap();
+    let attachment = db
+        .add_attachment(NewAttachment {
+            message_id: memo.id,
+            filename: Some("memo.caf".to_string()),
+            mime_type: Some("audio/x-caf".to_string()),
+            size_bytes: None,
+            created_at: memo.date_created,
+        })
+        .unwrap();
+    db.add_attachment_transcript(&attachment, "whisper", "Don't forget Everly's recital").unwrap();
+    let found = db.search_messages("everly", None, None, None).unwrap();
+    let ids: Vec<&str> = found.iter().map(|m| m.imessage_id.as_str()).collect();
+    assert_eq!(ids, vec!["guid-0", "guid-11"]);
+
+    // Only ASCII letters are matched in either case, as SQLite's LIKE does
+    let school = NewMessageBuilder::new(12).text("See you at the école").contact(rhonda.id).date_created(start);
+    db.add_message(school.build()).unwrap();
+    assert_eq!(db.search_messages("ÉCOLE", None, None, None).unwrap().len(), 0);
+    assert_eq!(db.search_messages("éCOLE", None, None, None).unwrap().len(), 1);
+
+    // The context window around a match stays in its own conversation
+    let window = db.get_message_context(&Conversation::Contact(phil.id), &phil_only[0], 1, 1).unwrap();
+    let texts: Vec<&str> = window.iter().filter_map(|m| m.text.as_deref()).collect();
+    assert_eq!(texts, vec!["Yes", "Pick up milk too", "ok"]);
+}
@@ -0,0 +1,73 @@
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::import_filter::ImportFilter;
+use txtHistoryRust::importers::{save_imported_messages, ImportedMessage};
+use txtHistoryRust::models::NewContact;
+use txtHistoryRust::sender_map::{SenderMap, SenderMapping};
+
+fn contact(name: &str, is_me: bool) -> NewContact {
+    NewContact { name: name.to_string(), phone: None, email: None, is_me, primary_identifier: None }
+}
+
+fn telegram_message(id: &str, sender: &str, chat: &str, minute: u32) -> ImportedMessage {
+    ImportedMessage {
+        source_id: format!("telegram:{}", id),
+        sender_name: sender.to_string(),
+        is_from_me: sender == "Jess",
+        counterpart_identifier: None,
+        counterpart_name: Some(chat.to_string()),
+        timestamp: NaiveDateTime::parse_from_str(&format!("2025-01-20 12:{:02}", minute), "%Y-%m-%d %H:%M").unwrap(),
+        text: Some("See you at the rink".to_string()),
+        service: "Telegram".to_string(),
+        thread_id: Some(chat.to_string()),
+        has_attachments: false,
+        reply_to: None,
+    }
+}
+
+#[test]
+fn test_sender_map_attaches_display_names_to_contacts() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+    db.add_or_update_contact(contact("Jess", true)).expect("Failed to add me");
+    let phil = db.add_or_update_contact(contact("Phil", false)).expect("Failed to add contact");
+
+    let messages = vec![
+        // A one-on-one chat named after Phil's display name
+        telegram_message("1", "Phil G 🏒", "Phil G 🏒", 0),
+        telegram_message("2", "Jess", "Phil G 🏒", 1),
+        // A group chat, where only the sender points at Phil
+        telegram_message("3", "phil g 🏒", "Hockey dads", 2),
+        telegram_message("4", "Coach Dan", "Hockey dads", 3),
+    ];
+
+    let senders = SenderMap::new(&[SenderMapping {
+        pattern: r"^Phil G\b".to_string(),
+        contact: "Phil".to_string(),
+        case_insensitive: true,
+    }])
+    .unwrap();
+    let summary = save_imported_messages(&db, &messages, &ImportFilter::default(), &senders).unwrap();
+    assert_eq!(summary.imported, 4);
+    assert_eq!(summary.unmatched, 1);
+
+    let stored = db.get_messages_for_contact_id(phil.id, None, None).unwrap();
+    let senders: Vec<(&str, &str)> = stored.iter().map(|m| (m.imessage_id.as_str(), m.sender.as_str())).collect();
+    assert_eq!(senders, vec![("telegram:1", "Phil"), ("telegram:2", "Jess"), ("telegram:3", "Phil")]);
+
+    // A mapping to a contact that doesn't exist fails before anything is saved
+    let typo = SenderMap::new(&[SenderMapping {
+        pattern: "Coach".to_string(),
+        contact: "Dann".to_string(),
+        case_insensitive: false,
+    }])
+    .unwrap();
+    assert!(save_imported_messages(&db, &messages, &ImportFilter::default(), &typo).is_err());
+}
@@ -0,0 +1,81 @@
+use chrono::{Duration, NaiveDateTime};
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::config::SnapshotConfig;
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::NewContact;
+use txtHistoryRust::snapshots::SnapshotStore;
+use txtHistoryRust::testing::NewMessageBuilder;
+
+#[test]
+fn test_snapshot_take_and_restore() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let mut db = Database::new(&db_url).expect("Failed to create database");
+    let store = SnapshotStore::new(temp_dir.path().join("snapshots"));
+    assert!(store.list().unwrap().is_empty());
+
+    let contact = db
+        .add_or_update_contact(NewContact {
+            name: "Phil".to_string(),
+            phone: Some("+15551234567".to_string()),
+            email: None,
+            is_me: false,
+            primary_identifier: None,
+        })
+        .expect("Failed to add contact");
+    let now = NaiveDateTime::parse_from_str("2025-04-17 09:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    db.add_message(NewMessageBuilder::new(0).contact(contact.id).date_created(now).build()).unwrap();
+
+    assert!(store.is_due(now).unwrap());
+    let snapshot = store.take(&db, now).expect("Failed to take snapshot");
+    assert_eq!(snapshot.name(), "messages-20250417-093000.db");
+    assert!(snapshot.bytes > 0);
+    assert!(!store.is_due(now + Duration::hours(2)).unwrap());
+    assert!(store.is_due(now + Duration::days(1)).unwrap());
+
+    // Messages added after the snapshot are gone once it's restored
+    db.add_message(NewMessageBuilder::new(1).contact(contact.id).date_created(now).build()).unwrap();
+    assert_eq!(db.get_messages_for_contact_id(contact.id, None, None).unwrap().len(), 2);
+    db.restore_from(&store.find("latest").unwrap().path).expect("Failed to restore snapshot");
+    assert_eq!(db.get_messages_for_contact_id(contact.id, None, None).unwrap().len(), 1);
+
+    assert!(store.find("messages-20200101-000000.db").is_err());
+}
+
+#[test]
+fn test_snapshot_rotation() {
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+    let store = SnapshotStore::new(temp_dir.path().join("snapshots"));
+
+    // Ten daily snapshots, then rotation keeps the last three days and one more week
+    let newest = NaiveDateTime::parse_from_str("2025-04-17 03:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    for days in (0..10).rev() {
+        store.take(&db, newest - Duration::days(days)).unwrap();
+    }
+    std::fs::write(store.dir().join("notes.txt"), "not a snapshot").unwrap();
+
+    let config = SnapshotConfig { enabled: true, daily: 3, weekly: 2 };
+    let deleted = store.rotate(&config).expect("Failed to rotate snapshots");
+    assert_eq!(deleted.len(), 6);
+
+    let kept: Vec<String> = store.list().unwrap().iter().map(|snapshot| snapshot.name()).collect();
+    assert_eq!(
+        kept,
+        vec![
+            "messages-20250417-030000.db",
+            "messages-20250416-030000.db",
+            "messages-20250415-030000.db",
+            "messages-20250413-030000.db",
+        ]
+    );
+    assert!(store.dir().join("notes.txt").exists());
+}
@@ -0,0 +1,59 @@
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{MessageKind, NewMessage};
+
+fn message(id: usize, sender: &str) -> NewMessage {
+    NewMessage {
+        imessage_id: format!("guid-{}", id),
+        text: Some("See you at 3".to_string()),
+        sender: sender.to_string(),
+        is_from_me: sender == "Jess",
+        date_created: NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: None,
+        message_kind: MessageKind::Text,
+    }
+}
+
+#[test]
+fn test_read_only_queries() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    for (i, sender) in ["Phil", "Phil", "Jess"].into_iter().enumerate() {
+        db.add_message(message(i, sender)).expect("Failed to add message");
+    }
+
+    let result = db
+        .run_read_only_query("SELECT sender, count(*) AS n FROM messages GROUP BY 1 ORDER BY 1")
+        .expect("Failed to run query");
+    assert_eq!(result.columns, vec!["sender", "n"]);
+    assert_eq!(result.rows.len(), 2);
+    assert_eq!(result.rows[1][0], "Phil");
+    assert_eq!(result.rows[1][1], 2);
+
+    // Writes are rejected and leave the data alone
+    assert!(db.run_read_only_query("DELETE FROM messages").is_err());
+    assert!(db.run_read_only_query("DROP TABLE messages").is_err());
+    assert!(db.run_read_only_query("SELECT 1; DELETE FROM messages").is_err());
+    assert!(db.run_read_only_query("SELECT 1; SELECT 2").is_err());
+    assert!(db.run_read_only_query("  ").is_err());
+    assert_eq!(db.run_read_only_query("SELECT 1; ").unwrap().rows.len(), 1);
+
+    // The pooled connection still accepts writes from the rest of the app
+    db.add_message(message(3, "Phil")).expect("Failed to add message after query");
+    let count = db.run_read_only_query("SELECT count(*) FROM messages").unwrap();
+    assert_eq!(count.rows[0][0], 4);
+}
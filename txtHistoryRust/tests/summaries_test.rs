@@ -0,0 +1,39 @@
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::NewSummary;
+
+fn summary(input_hash: &str, model: &str, text: &str) -> NewSummary {
+    NewSummary {
+        input_hash: input_hash.to_string(),
+        model: model.to_string(),
+        unit: "day".to_string(),
+        unit_key: "2025-01-20".to_string(),
+        summary: text.to_string(),
+    }
+}
+
+#[test]
+fn test_summary_cache() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    assert_eq!(db.get_summary("abc", "gpt-4o-mini").unwrap(), None);
+    db.save_summary(&summary("abc", "gpt-4o-mini", "Plans for soccer")).unwrap();
+    assert_eq!(db.get_summary("abc", "gpt-4o-mini").unwrap().as_deref(), Some("Plans for soccer"));
+
+    // Another model's summary of the same transcript is cached separately
+    assert_eq!(db.get_summary("abc", "llama3").unwrap(), None);
+
+    // Summarizing again with --refresh replaces the cached summary
+    db.save_summary(&summary("abc", "gpt-4o-mini", "Jess takes her to soccer at 5")).unwrap();
+    assert_eq!(
+        db.get_summary("abc", "gpt-4o-mini").unwrap().as_deref(),
+        Some("Jess takes her to soccer at 5")
+    );
+}
@@ -0,0 +1,113 @@
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+// Import the necessary modules from the crate
+use txtHistoryRust::db::Database;
+use txtHistoryRust::models::{MessageKind, NewAttachment, NewContact, NewMessage};
+
+fn add_contact(db: &Database, name: &str, phone: Option<&str>) -> i32 {
+    db.add_or_update_contact(NewContact {
+        name: name.to_string(),
+        phone: phone.map(str::to_string),
+        email: None,
+        is_me: false,
+        primary_identifier: None,
+    })
+    .expect("Failed to add contact")
+    .id
+}
+
+fn add_message(db: &Database, id: &str, sender: &str, contact_id: i32, date: &str) -> i32 {
+    db.add_message(NewMessage {
+        imessage_id: id.to_string(),
+        text: Some(format!("message {}", id)),
+        sender: sender.to_string(),
+        is_from_me: false,
+        date_created: NaiveDateTime::parse_from_str(&format!("{} 12:00:00", date), "%Y-%m-%d %H:%M:%S").unwrap(),
+        date_imported: None,
+        handle_id: None,
+        service: Some("iMessage".to_string()),
+        thread_id: None,
+        has_attachments: false,
+        reply_to: None,
+        contact_id: Some(contact_id),
+        message_kind: MessageKind::Text,
+    })
+    .expect("Failed to add message")
+    .id
+}
+
+#[test]
+fn test_undo_purge() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let robert = add_contact(&db, "Robert Test", None);
+    let old = add_message(&db, "old", "Robert Test", robert, "2017-06-01");
+    add_message(&db, "new", "Robert Test", robert, "2019-06-01");
+    db.add_attachment(NewAttachment {
+        message_id: old,
+        filename: Some("photo.jpg".to_string()),
+        mime_type: Some("image/jpeg".to_string()),
+        size_bytes: Some(1024),
+        created_at: NaiveDateTime::parse_from_str("2017-06-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+    })
+    .expect("Failed to add attachment");
+
+    let cutoff = NaiveDateTime::parse_from_str("2018-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+    // Dry runs and purges that delete nothing leave nothing to undo
+    db.purge_messages(cutoff, Some(robert), &[], true).unwrap();
+    db.purge_messages(NaiveDateTime::MIN, Some(robert), &[], false).unwrap();
+    assert!(db.undo_journals().unwrap().is_empty());
+
+    db.purge_messages(cutoff, Some(robert), &[], false).unwrap();
+    assert!(db.get_message_by_id(old).unwrap().is_none());
+    assert_eq!(db.undo_journals().unwrap().len(), 1);
+
+    let journal = db.undo_last().unwrap().expect("Nothing to undo");
+    assert_eq!(journal.operation, "purge");
+    assert_eq!(journal.rows(), 2);
+    let message = db.get_message_by_id(old).unwrap().expect("Message was not restored");
+    assert_eq!((message.imessage_id.as_str(), message.contact_id), ("old", Some(robert)));
+    let attachments = db.get_attachment_report(Some(robert), None, None, None).unwrap();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].message_id, old);
+
+    // Each journal is only undone once
+    assert!(db.undo_last().unwrap().is_none());
+}
+
+#[test]
+fn test_undo_merge() {
+    // Create a temporary database for testing
+    let temp_dir = tempdir().expect("Failed to create temp directory");
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite://{}", db_path.display());
+
+    let db = Database::new(&db_url).expect("Failed to create database");
+
+    let phil = add_contact(&db, "Phil", Some("+15551234567"));
+    let phil_work = add_contact(&db, "Phil Work", Some("+15557654321"));
+    add_message(&db, "home", "Phil", phil, "2024-06-01");
+    let work = add_message(&db, "work", "Phil Work", phil_work, "2024-06-02");
+
+    let into = db.get_contact("Phil").unwrap().unwrap();
+    let from = db.get_contact("Phil Work").unwrap().unwrap();
+    db.merge_contacts(&into, &from, false).expect("Failed to merge contacts");
+    assert!(db.get_contact("Phil Work").unwrap().is_none());
+
+    let journal = db.undo_last().unwrap().expect("Nothing to undo");
+    assert_eq!(journal.description, "Merged Phil Work into Phil");
+
+    // The duplicate is back with its own identifiers and messages
+    let restored = db.get_contact("Phil Work").unwrap().expect("Contact was not restored");
+    assert_eq!((restored.id, restored.phone), (phil_work, from.phone));
+    assert_eq!(db.get_contact("Phil").unwrap().unwrap().phone, into.phone);
+    let message = db.get_message_by_id(work).unwrap().unwrap();
+    assert_eq!((message.sender.as_str(), message.contact_id), ("Phil Work", Some(phil_work)));
+}